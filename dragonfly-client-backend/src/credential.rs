@@ -0,0 +1,671 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use chrono::{DateTime, Utc};
+use dragonfly_client_core::error::BackendError;
+use dragonfly_client_core::{Error as ClientError, Result as ClientResult};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+use tracing::{debug, error, info};
+
+/// ENV_ACCESS_KEY_IDS are the environment variables of the access key id, the former has
+/// higher priority.
+const ENV_ACCESS_KEY_IDS: [&str; 2] = ["DRAGONFLY_STORAGE_ACCESS_KEY_ID", "AWS_ACCESS_KEY_ID"];
+
+/// ENV_ACCESS_KEY_SECRETS are the environment variables of the access key secret, the former
+/// has higher priority.
+const ENV_ACCESS_KEY_SECRETS: [&str; 2] = [
+    "DRAGONFLY_STORAGE_ACCESS_KEY_SECRET",
+    "AWS_SECRET_ACCESS_KEY",
+];
+
+/// ENV_SESSION_TOKENS are the environment variables of the session token, the former has
+/// higher priority.
+const ENV_SESSION_TOKENS: [&str; 2] = ["DRAGONFLY_STORAGE_SESSION_TOKEN", "AWS_SESSION_TOKEN"];
+
+/// SECRET_ACCESS_KEY_ID_FILE is the file name of the access key id in the secret directory.
+const SECRET_ACCESS_KEY_ID_FILE: &str = "access_key_id";
+
+/// SECRET_ACCESS_KEY_SECRET_FILE is the file name of the access key secret in the secret
+/// directory.
+const SECRET_ACCESS_KEY_SECRET_FILE: &str = "access_key_secret";
+
+/// SECRET_SESSION_TOKEN_FILE is the file name of the session token in the secret directory.
+const SECRET_SESSION_TOKEN_FILE: &str = "session_token";
+
+/// METADATA_REQUEST_TIMEOUT is the timeout to request the credential from the metadata
+/// endpoint.
+const METADATA_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// METADATA_REFRESH_AHEAD is the duration before the expiration to refresh the credential
+/// from the metadata endpoint.
+const METADATA_REFRESH_AHEAD: Duration = Duration::from_secs(300);
+
+/// Credential is the credential to access the object storage.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credential {
+    /// access_key_id is the access key id of the credential.
+    pub access_key_id: String,
+
+    /// access_key_secret is the access key secret of the credential.
+    pub access_key_secret: String,
+
+    /// session_token is the session token of the temporary credential.
+    pub session_token: Option<String>,
+
+    /// expiration is the expiration time of the temporary credential.
+    pub expiration: Option<DateTime<Utc>>,
+}
+
+/// CredentialScope is the buckets and the endpoints of the object storage which the
+/// credential of the provider can be applied to, so the credential is not sent to the
+/// buckets and the endpoints chosen by the requests. The credential is not applied to any
+/// bucket if the scope is empty.
+#[derive(Debug, Clone, Default)]
+pub struct CredentialScope {
+    /// allowed_buckets are the buckets which the credential can be applied to.
+    pub allowed_buckets: Vec<String>,
+
+    /// allowed_endpoints are the endpoints which the credential can be applied to. The request
+    /// without the endpoint uses the default endpoint of the object storage, so it is allowed.
+    pub allowed_endpoints: Vec<String>,
+}
+
+/// CredentialScope implements the credential scope.
+impl CredentialScope {
+    /// new returns a new CredentialScope.
+    pub fn new(allowed_buckets: Vec<String>, allowed_endpoints: Vec<String>) -> Self {
+        Self {
+            allowed_buckets,
+            allowed_endpoints,
+        }
+    }
+
+    /// contains returns whether the credential can be applied to the bucket and the endpoint.
+    pub fn contains(&self, bucket: &str, endpoint: Option<&str>) -> bool {
+        if !self
+            .allowed_buckets
+            .iter()
+            .any(|allowed_bucket| allowed_bucket == bucket)
+        {
+            return false;
+        }
+
+        match endpoint.map(|endpoint| endpoint.trim_end_matches('/')) {
+            None | Some("") => true,
+            Some(endpoint) => self.allowed_endpoints.iter().any(|allowed_endpoint| {
+                allowed_endpoint
+                    .trim_end_matches('/')
+                    .eq_ignore_ascii_case(endpoint)
+            }),
+        }
+    }
+}
+
+/// CredentialProvider provides the credential of the object storage on the dfdaemon side, so
+/// the clients do not need to send the secrets in every download request.
+#[tonic::async_trait]
+pub trait CredentialProvider: Send + Sync {
+    /// name returns the name of the provider.
+    fn name(&self) -> &str;
+
+    /// credential returns the credential, None means the provider has no credential.
+    async fn credential(&self) -> ClientResult<Option<Credential>>;
+}
+
+/// CredentialProviderChain returns the credential of the first provider which has the
+/// credential, the failed providers are skipped.
+#[derive(Default)]
+pub struct CredentialProviderChain {
+    /// providers are the providers of the chain in order.
+    providers: Vec<Box<dyn CredentialProvider>>,
+}
+
+/// CredentialProviderChain implements the credential provider chain.
+impl CredentialProviderChain {
+    /// new returns a new CredentialProviderChain.
+    pub fn new(providers: Vec<Box<dyn CredentialProvider>>) -> Self {
+        Self { providers }
+    }
+
+    /// is_empty returns whether the chain has no provider.
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+}
+
+/// CredentialProviderChain implements the CredentialProvider trait.
+#[tonic::async_trait]
+impl CredentialProvider for CredentialProviderChain {
+    /// name returns the name of the provider.
+    fn name(&self) -> &str {
+        "chain"
+    }
+
+    /// credential returns the credential of the first provider which has the credential.
+    async fn credential(&self) -> ClientResult<Option<Credential>> {
+        for provider in self.providers.iter() {
+            match provider.credential().await {
+                Ok(Some(credential)) => {
+                    debug!("load credential from {} provider", provider.name());
+                    return Ok(Some(credential));
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    error!(
+                        "load credential from {} provider failed: {}",
+                        provider.name(),
+                        err
+                    );
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// EnvironmentProvider loads the credential from the environment variables, such as
+/// `DRAGONFLY_STORAGE_ACCESS_KEY_ID` and `AWS_ACCESS_KEY_ID`.
+#[derive(Default)]
+pub struct EnvironmentProvider;
+
+/// EnvironmentProvider implements the environment provider.
+impl EnvironmentProvider {
+    /// new returns a new EnvironmentProvider.
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// var returns the value of the first set environment variable.
+    fn var(keys: &[&str]) -> Option<String> {
+        keys.iter()
+            .filter_map(|key| std::env::var(key).ok())
+            .find(|value| !value.is_empty())
+    }
+}
+
+/// EnvironmentProvider implements the CredentialProvider trait.
+#[tonic::async_trait]
+impl CredentialProvider for EnvironmentProvider {
+    /// name returns the name of the provider.
+    fn name(&self) -> &str {
+        "environment"
+    }
+
+    /// credential returns the credential of the environment variables.
+    async fn credential(&self) -> ClientResult<Option<Credential>> {
+        let (Some(access_key_id), Some(access_key_secret)) = (
+            Self::var(&ENV_ACCESS_KEY_IDS),
+            Self::var(&ENV_ACCESS_KEY_SECRETS),
+        ) else {
+            return Ok(None);
+        };
+
+        Ok(Some(Credential {
+            access_key_id,
+            access_key_secret,
+            session_token: Self::var(&ENV_SESSION_TOKENS),
+            expiration: None,
+        }))
+    }
+}
+
+/// SharedCredentialsFileProvider loads the credential of the profile from the shared
+/// credentials file, such as `~/.aws/credentials`. The file is reloaded when it is modified.
+pub struct SharedCredentialsFileProvider {
+    /// path is the path of the shared credentials file.
+    path: PathBuf,
+
+    /// profile is the profile of the credential in the file.
+    profile: String,
+
+    /// cache is the loaded credential with the modified time of the file.
+    cache: Mutex<Option<(SystemTime, Option<Credential>)>>,
+}
+
+/// SharedCredentialsFileProvider implements the shared credentials file provider.
+impl SharedCredentialsFileProvider {
+    /// new returns a new SharedCredentialsFileProvider.
+    pub fn new(path: PathBuf, profile: &str) -> Self {
+        Self {
+            path,
+            profile: profile.to_string(),
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// parse parses the credential of the profile from the ini content of the file.
+    fn parse(content: &str, profile: &str) -> Option<Credential> {
+        let mut section = None;
+        let mut values = HashMap::new();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                let name = name.trim();
+                section = Some(
+                    name.strip_prefix("profile ")
+                        .unwrap_or(name)
+                        .trim()
+                        .to_string(),
+                );
+                continue;
+            }
+
+            if section.as_deref() != Some(profile) {
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once('=') {
+                values.insert(key.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+
+        Some(Credential {
+            access_key_id: values.remove("aws_access_key_id")?,
+            access_key_secret: values.remove("aws_secret_access_key")?,
+            session_token: values.remove("aws_session_token"),
+            expiration: None,
+        })
+    }
+}
+
+/// SharedCredentialsFileProvider implements the CredentialProvider trait.
+#[tonic::async_trait]
+impl CredentialProvider for SharedCredentialsFileProvider {
+    /// name returns the name of the provider.
+    fn name(&self) -> &str {
+        "shared_credentials_file"
+    }
+
+    /// credential returns the credential of the profile, the file is reloaded if it is
+    /// modified after the latest load.
+    async fn credential(&self) -> ClientResult<Option<Credential>> {
+        let modified = match fs::metadata(&self.path).await {
+            Ok(metadata) => metadata.modified()?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        if let Some((cached_modified, credential)) = self.cache.lock().unwrap().as_ref() {
+            if *cached_modified == modified {
+                return Ok(credential.clone());
+            }
+        }
+
+        let content = fs::read_to_string(&self.path).await?;
+        let credential = Self::parse(&content, &self.profile);
+        info!(
+            "reload shared credentials file {:?} with profile {}",
+            self.path, self.profile
+        );
+
+        *self.cache.lock().unwrap() = Some((modified, credential.clone()));
+        Ok(credential)
+    }
+}
+
+/// SecretDirProvider loads the credential from the files mounted in the secret directory,
+/// such as the kubernetes secret. The directory contains the `access_key_id`,
+/// `access_key_secret` and the optional `session_token` files, and the files are reloaded
+/// when they are rotated.
+pub struct SecretDirProvider {
+    /// dir is the secret directory.
+    dir: PathBuf,
+
+    /// cache is the loaded credential with the latest modified time of the files.
+    cache: Mutex<Option<(SystemTime, Credential)>>,
+}
+
+/// SecretDirProvider implements the secret directory provider.
+impl SecretDirProvider {
+    /// new returns a new SecretDirProvider.
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// read reads the trimmed content of the secret file, None means the file does not
+    /// exist.
+    async fn read(path: &Path) -> ClientResult<Option<(SystemTime, String)>> {
+        let metadata = match fs::metadata(path).await {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let content = fs::read_to_string(path).await?;
+        Ok(Some((metadata.modified()?, content.trim().to_string())))
+    }
+
+    /// modified returns the latest modified time of the secret files.
+    async fn modified(&self) -> ClientResult<Option<SystemTime>> {
+        let mut latest = None;
+        for name in [
+            SECRET_ACCESS_KEY_ID_FILE,
+            SECRET_ACCESS_KEY_SECRET_FILE,
+            SECRET_SESSION_TOKEN_FILE,
+        ] {
+            match fs::metadata(self.dir.join(name)).await {
+                Ok(metadata) => latest = latest.max(Some(metadata.modified()?)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(latest)
+    }
+}
+
+/// SecretDirProvider implements the CredentialProvider trait.
+#[tonic::async_trait]
+impl CredentialProvider for SecretDirProvider {
+    /// name returns the name of the provider.
+    fn name(&self) -> &str {
+        "secret_dir"
+    }
+
+    /// credential returns the credential of the secret files, the files are reloaded if
+    /// any of them is modified after the latest load.
+    async fn credential(&self) -> ClientResult<Option<Credential>> {
+        let Some(modified) = self.modified().await? else {
+            return Ok(None);
+        };
+
+        if let Some((cached_modified, credential)) = self.cache.lock().unwrap().as_ref() {
+            if *cached_modified == modified {
+                return Ok(Some(credential.clone()));
+            }
+        }
+
+        let (Some((_, access_key_id)), Some((_, access_key_secret))) = (
+            Self::read(&self.dir.join(SECRET_ACCESS_KEY_ID_FILE)).await?,
+            Self::read(&self.dir.join(SECRET_ACCESS_KEY_SECRET_FILE)).await?,
+        ) else {
+            return Ok(None);
+        };
+
+        let credential = Credential {
+            access_key_id,
+            access_key_secret,
+            session_token: Self::read(&self.dir.join(SECRET_SESSION_TOKEN_FILE))
+                .await?
+                .map(|(_, session_token)| session_token)
+                .filter(|session_token| !session_token.is_empty()),
+            expiration: None,
+        };
+        info!("reload credential from secret directory {:?}", self.dir);
+
+        *self.cache.lock().unwrap() = Some((modified, credential.clone()));
+        Ok(Some(credential))
+    }
+}
+
+/// MetadataCredential is the credential returned by the metadata endpoint, it is compatible
+/// with the container credentials of the ECS and the security credentials of the IMDS.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MetadataCredential {
+    /// access_key_id is the access key id of the credential.
+    access_key_id: String,
+
+    /// secret_access_key is the access key secret of the credential.
+    secret_access_key: String,
+
+    /// token is the session token of the credential.
+    token: Option<String>,
+
+    /// expiration is the expiration time of the credential.
+    expiration: Option<DateTime<Utc>>,
+}
+
+/// MetadataProvider loads the temporary credential from the local metadata endpoint, such as
+/// the IMDS or the STS compatible credential service. The credential is refreshed before it
+/// expires.
+pub struct MetadataProvider {
+    /// endpoint is the url of the metadata endpoint which returns the credential.
+    endpoint: String,
+
+    /// client is the http client to request the metadata endpoint.
+    client: reqwest::Client,
+
+    /// cache is the credential loaded from the metadata endpoint.
+    cache: Mutex<Option<Credential>>,
+}
+
+/// MetadataProvider implements the metadata provider.
+impl MetadataProvider {
+    /// new returns a new MetadataProvider.
+    pub fn new(endpoint: &str) -> ClientResult<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(METADATA_REQUEST_TIMEOUT)
+            .build()?;
+
+        Ok(Self {
+            endpoint: endpoint.to_string(),
+            client,
+            cache: Mutex::new(None),
+        })
+    }
+
+    /// is_valid returns whether the credential does not expire within the refresh ahead.
+    fn is_valid(credential: &Credential) -> bool {
+        credential.expiration.is_none_or(|expiration| {
+            expiration - chrono::Duration::from_std(METADATA_REFRESH_AHEAD).unwrap_or_default()
+                > Utc::now()
+        })
+    }
+}
+
+/// MetadataProvider implements the CredentialProvider trait.
+#[tonic::async_trait]
+impl CredentialProvider for MetadataProvider {
+    /// name returns the name of the provider.
+    fn name(&self) -> &str {
+        "metadata"
+    }
+
+    /// credential returns the cached credential if it is valid, otherwise it requests the
+    /// metadata endpoint.
+    async fn credential(&self) -> ClientResult<Option<Credential>> {
+        if let Some(credential) = self.cache.lock().unwrap().as_ref() {
+            if Self::is_valid(credential) {
+                return Ok(Some(credential.clone()));
+            }
+        }
+
+        let response = self.client.get(&self.endpoint).send().await?;
+        let status_code = response.status();
+        if !status_code.is_success() {
+            return Err(ClientError::BackendError(Box::new(BackendError {
+                message: format!("request metadata endpoint {} failed", self.endpoint),
+                status_code: Some(status_code),
+                header: Some(response.headers().clone()),
+            })));
+        }
+
+        let content = response.bytes().await?;
+        let metadata_credential: MetadataCredential = serde_json::from_slice(&content)
+            .map_err(|err| ClientError::Unknown(format!("invalid credential response: {}", err)))?;
+        let credential = Credential {
+            access_key_id: metadata_credential.access_key_id,
+            access_key_secret: metadata_credential.secret_access_key,
+            session_token: metadata_credential.token,
+            expiration: metadata_credential.expiration,
+        };
+        info!(
+            "refresh credential from metadata endpoint {}, expiration: {:?}",
+            self.endpoint, credential.expiration
+        );
+
+        *self.cache.lock().unwrap() = Some(credential.clone());
+        Ok(Some(credential))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// StaticProvider returns the static credential.
+    struct StaticProvider(Option<Credential>);
+
+    #[tonic::async_trait]
+    impl CredentialProvider for StaticProvider {
+        fn name(&self) -> &str {
+            "static"
+        }
+
+        async fn credential(&self) -> ClientResult<Option<Credential>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn new_credential(access_key_id: &str) -> Credential {
+        Credential {
+            access_key_id: access_key_id.to_string(),
+            access_key_secret: "secret".to_string(),
+            session_token: None,
+            expiration: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_get_credential_from_first_provider() {
+        let chain = CredentialProviderChain::new(vec![
+            Box::new(StaticProvider(None)),
+            Box::new(SecretDirProvider::new(PathBuf::from(
+                "/non-existent-dragonfly-secret",
+            ))),
+            Box::new(StaticProvider(Some(new_credential("foo")))),
+            Box::new(StaticProvider(Some(new_credential("bar")))),
+        ]);
+
+        let credential = chain.credential().await.unwrap().unwrap();
+        assert_eq!(credential.access_key_id, "foo");
+        assert!(CredentialProviderChain::default()
+            .credential()
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn should_get_credential_from_shared_credentials_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("credentials");
+        std::fs::write(
+            &path,
+            "[default]\naws_access_key_id = foo\naws_secret_access_key = foo-secret\n\n\
+             # comment\n[profile dragonfly]\naws_access_key_id=bar\naws_secret_access_key=bar-secret\naws_session_token=token\n",
+        )
+        .unwrap();
+
+        let credential = SharedCredentialsFileProvider::new(path.clone(), "default")
+            .credential()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(credential.access_key_id, "foo");
+        assert_eq!(credential.access_key_secret, "foo-secret");
+        assert!(credential.session_token.is_none());
+
+        let credential = SharedCredentialsFileProvider::new(path.clone(), "dragonfly")
+            .credential()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(credential.access_key_id, "bar");
+        assert_eq!(credential.session_token.as_deref(), Some("token"));
+
+        assert!(SharedCredentialsFileProvider::new(path, "unknown")
+            .credential()
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn should_reload_credential_from_secret_dir() {
+        let dir = tempdir().unwrap();
+        let provider = SecretDirProvider::new(dir.path().to_path_buf());
+        assert!(provider.credential().await.unwrap().is_none());
+
+        std::fs::write(dir.path().join(SECRET_ACCESS_KEY_ID_FILE), "foo\n").unwrap();
+        std::fs::write(
+            dir.path().join(SECRET_ACCESS_KEY_SECRET_FILE),
+            "foo-secret\n",
+        )
+        .unwrap();
+        let credential = provider.credential().await.unwrap().unwrap();
+        assert_eq!(credential.access_key_id, "foo");
+        assert_eq!(credential.access_key_secret, "foo-secret");
+
+        // Rotate the secret, the modified time is changed explicitly because the resolution
+        // of the modified time may be coarse.
+        let path = dir.path().join(SECRET_ACCESS_KEY_ID_FILE);
+        std::fs::write(&path, "bar").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        let credential = provider.credential().await.unwrap().unwrap();
+        assert_eq!(credential.access_key_id, "bar");
+    }
+
+    #[tokio::test]
+    async fn should_refresh_credential_from_metadata_endpoint() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/credentials"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "AccessKeyId": "foo",
+                "SecretAccessKey": "foo-secret",
+                "Token": "token",
+                "Expiration": (Utc::now() + chrono::Duration::hours(1)).to_rfc3339(),
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider = MetadataProvider::new(&format!("{}/credentials", server.uri())).unwrap();
+        for _ in 0..2 {
+            let credential = provider.credential().await.unwrap().unwrap();
+            assert_eq!(credential.access_key_id, "foo");
+            assert_eq!(credential.session_token.as_deref(), Some("token"));
+        }
+
+        // The credential expiring soon is refreshed.
+        let mut credential = new_credential("foo");
+        credential.expiration = Some(Utc::now() + chrono::Duration::seconds(10));
+        assert!(!MetadataProvider::is_valid(&credential));
+    }
+}
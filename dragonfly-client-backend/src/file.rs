@@ -0,0 +1,567 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use chrono::{DateTime, Utc};
+use dragonfly_client_core::error::BackendError;
+use dragonfly_client_core::{Error as ClientError, Result as ClientResult};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, LAST_MODIFIED};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tracing::{error, info, instrument};
+use url::Url;
+
+/// FILE_SCHEME is the scheme of the local file system.
+pub const FILE_SCHEME: &str = "file";
+
+/// File is a struct that implements the Backend trait, it reads the files of the local file
+/// system, including the shared file systems mounted locally, such as NFS and Lustre. Only the
+/// files in the allowed directories can be read.
+#[derive(Default)]
+pub struct File {
+    /// scheme is the scheme of the local file system.
+    scheme: String,
+
+    /// allowed_dirs are the directories which can be read by the file backend.
+    allowed_dirs: Vec<PathBuf>,
+}
+
+/// File implements the Backend trait.
+impl File {
+    /// new returns a new file backend which reads the files in the allowed directories.
+    pub fn new(allowed_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            scheme: FILE_SCHEME.to_string(),
+            allowed_dirs,
+        }
+    }
+
+    /// path returns the local path of the url, the url must be an absolute file url
+    /// without host, such as `file:///mnt/nfs/file`. The path is resolved with the symlinks
+    /// and must be in one of the allowed directories.
+    async fn path(&self, url: &str) -> ClientResult<PathBuf> {
+        let url = Url::parse(url).map_err(|_| ClientError::InvalidURI(url.to_string()))?;
+        let path = url
+            .to_file_path()
+            .map_err(|_| ClientError::InvalidURI(url.to_string()))?;
+        let path = fs::canonicalize(&path)
+            .await
+            .map_err(|err| Self::backend_error(&path, err))?;
+
+        for allowed_dir in self.allowed_dirs.iter() {
+            match fs::canonicalize(allowed_dir).await {
+                Ok(allowed_dir) if path.starts_with(&allowed_dir) => return Ok(path),
+                Ok(_) => {}
+                Err(err) => {
+                    error!("canonicalize allowed dir {:?} failed: {}", allowed_dir, err);
+                }
+            }
+        }
+
+        error!("{:?} is not in the allowed dirs", path);
+        Err(ClientError::BackendError(Box::new(BackendError {
+            message: format!("{} is not in the allowed dirs", path.display()),
+            status_code: Some(reqwest::StatusCode::FORBIDDEN),
+            header: None,
+        })))
+    }
+
+    /// entries returns the entries in the directory recursively, the url of the directory
+    /// entry ends with `/`. The symlinks are skipped, so the entries can not escape the
+    /// directory.
+    async fn entries(dir: &Path) -> ClientResult<Vec<super::DirEntry>> {
+        Ok(Self::walk(dir, None, usize::MAX, None).await?.entries)
+    }
+
+    /// walk lists a page of the entries in the directory in the lexicographic order of the
+    /// urls. The children of a directory are sorted before they are visited, and the url of
+    /// a directory is the prefix of its descendants, so the entries are visited in the order
+    /// of the urls. The subtrees listed by the previous pages are skipped without reading
+    /// them, so only the directories on the path of the continuation token are read again.
+    async fn walk(
+        dir: &Path,
+        continuation_token: Option<&str>,
+        page_size: usize,
+        max_depth: Option<usize>,
+    ) -> ClientResult<super::ListResponse> {
+        let mut entries = Vec::new();
+        let mut stack = Self::children(dir, 1).await?;
+        while let Some((entry, path, depth)) = stack.pop() {
+            let descend = entry.is_dir && max_depth.is_none_or(|max_depth| depth < max_depth);
+
+            // Skip the entries listed by the previous pages, the directory is only read if
+            // the continuation token is in it.
+            if let Some(token) = continuation_token {
+                if entry.url.as_str() <= token {
+                    if descend && token.starts_with(&entry.url) {
+                        stack.extend(Self::children(&path, depth + 1).await?);
+                    }
+
+                    continue;
+                }
+            }
+
+            // The page is full and there are more entries.
+            if entries.len() == page_size {
+                let continuation_token = entries
+                    .last()
+                    .map(|entry: &super::DirEntry| entry.url.clone());
+                return Ok(super::ListResponse {
+                    entries,
+                    continuation_token,
+                });
+            }
+
+            if descend {
+                stack.extend(Self::children(&path, depth + 1).await?);
+            }
+
+            entries.push(entry);
+        }
+
+        Ok(super::ListResponse {
+            entries,
+            continuation_token: None,
+        })
+    }
+
+    /// children returns the direct children of the directory with the paths and the depth,
+    /// which are sorted by the urls in the descending order, so the smallest child is popped
+    /// first. The symlinks are skipped.
+    async fn children(
+        dir: &Path,
+        depth: usize,
+    ) -> ClientResult<Vec<(super::DirEntry, PathBuf, usize)>> {
+        let mut children = Vec::new();
+        let mut read_dir = fs::read_dir(dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+            let metadata = fs::symlink_metadata(&path).await?;
+            if metadata.is_symlink() {
+                info!("skip the symlink {:?}", path);
+                continue;
+            }
+
+            let entry = if metadata.is_dir() {
+                let url = Url::from_directory_path(&path)
+                    .map_err(|_| ClientError::InvalidURI(path.display().to_string()))?;
+                super::DirEntry {
+                    url: url.to_string(),
+                    content_length: 0,
+                    is_dir: true,
+                }
+            } else {
+                let url = Url::from_file_path(&path)
+                    .map_err(|_| ClientError::InvalidURI(path.display().to_string()))?;
+                super::DirEntry {
+                    url: url.to_string(),
+                    content_length: metadata.len() as usize,
+                    is_dir: false,
+                }
+            };
+
+            children.push((entry, path, depth));
+        }
+
+        children.sort_by(|(a, _, _), (b, _, _)| b.url.cmp(&a.url));
+        Ok(children)
+    }
+
+    /// backend_error converts the io error of the path to the backend error.
+    fn backend_error(path: &Path, err: std::io::Error) -> ClientError {
+        ClientError::BackendError(Box::new(BackendError {
+            message: format!("{}: {}", path.display(), err),
+            status_code: None,
+            header: None,
+        }))
+    }
+}
+
+/// Implement the Backend trait for File.
+#[tonic::async_trait]
+impl super::Backend for File {
+    /// scheme returns the scheme of the file backend.
+    fn scheme(&self) -> String {
+        self.scheme.clone()
+    }
+
+    /// head gets the size and the modified time of the file, and the entries if the url
+    /// points to a directory.
+    #[instrument(skip_all)]
+    async fn head(&self, request: super::HeadRequest) -> ClientResult<super::HeadResponse> {
+        info!(
+            "head request {} {}: {:?}",
+            request.task_id, request.url, request.http_header
+        );
+
+        let path = self.path(&request.url).await?;
+        let metadata = fs::metadata(&path).await.map_err(|err| {
+            error!(
+                "head request failed {} {}: {}",
+                request.task_id, request.url, err
+            );
+            Self::backend_error(&path, err)
+        })?;
+
+        // Get the entries if url point to a directory.
+        let entries = if metadata.is_dir() && request.url.ends_with('/') {
+            Self::entries(&path).await.map_err(|err| {
+                error!(
+                    "list request failed {} {}: {}",
+                    request.task_id, request.url, err
+                );
+                err
+            })?
+        } else {
+            Vec::new()
+        };
+
+        // Set the size and the modified time of the file as the headers.
+        let mut header = HeaderMap::new();
+        header.insert(CONTENT_LENGTH, HeaderValue::from(metadata.len()));
+        if let Ok(modified) = metadata.modified() {
+            let modified = DateTime::<Utc>::from(modified)
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string();
+            if let Ok(modified) = HeaderValue::from_str(&modified) {
+                header.insert(LAST_MODIFIED, modified);
+            }
+        }
+
+        info!(
+            "head response {} {}: {}",
+            request.task_id,
+            request.url,
+            metadata.len()
+        );
+
+        Ok(super::HeadResponse {
+            success: true,
+            content_length: Some(metadata.len()),
+            http_header: Some(header),
+            http_status_code: Some(reqwest::StatusCode::OK),
+            error_message: None,
+            entries,
+        })
+    }
+
+    /// list lists the entries of the directory by pages, the directories are walked in the
+    /// lexicographic order of the urls and the walking stops when the page is full.
+    #[instrument(skip_all)]
+    async fn list(&self, request: super::ListRequest) -> ClientResult<super::ListResponse> {
+        info!(
+            "list request {} {}: {:?}",
+            request.task_id, request.url, request.continuation_token
+        );
+
+        // Only the directory can be listed.
+        if !request.url.ends_with('/') {
+            return Err(ClientError::InvalidURI(request.url.clone()));
+        }
+
+        let path = self.path(&request.url).await?;
+        let response = Self::walk(
+            &path,
+            request.continuation_token.as_deref(),
+            request.page_size,
+            request.max_depth,
+        )
+        .await
+        .inspect_err(|err| {
+            error!(
+                "list request failed {} {}: {}",
+                request.task_id, request.url, err
+            );
+        })?;
+
+        info!(
+            "list response {} {}: {} {:?}",
+            request.task_id,
+            request.url,
+            response.entries.len(),
+            response.continuation_token
+        );
+
+        Ok(response)
+    }
+
+    /// get returns content of requested file, the range of the file is read if the range
+    /// is specified.
+    #[instrument(skip_all)]
+    async fn get(
+        &self,
+        request: super::GetRequest,
+    ) -> ClientResult<super::GetResponse<super::Body>> {
+        info!(
+            "get request {} {}: {:?}",
+            request.piece_id, request.url, request.http_header
+        );
+
+        let path = self.path(&request.url).await?;
+        let mut file = fs::File::open(&path).await.map_err(|err| {
+            error!(
+                "get request failed {} {}: {}",
+                request.piece_id, request.url, err
+            );
+            Self::backend_error(&path, err)
+        })?;
+
+        let reader: super::Body = match request.range {
+            Some(range) => {
+                file.seek(SeekFrom::Start(range.start))
+                    .await
+                    .map_err(|err| Self::backend_error(&path, err))?;
+                Box::new(file.take(range.length))
+            }
+            None => Box::new(file),
+        };
+
+        Ok(super::GetResponse {
+            success: true,
+            http_header: None,
+            http_status_code: Some(reqwest::StatusCode::OK),
+            reader,
+            error_message: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, GetRequest, HeadRequest, ListRequest};
+    use dragonfly_api::common::v2::Range;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    fn new_file(dir: &Path) -> File {
+        File::new(vec![dir.to_path_buf()])
+    }
+
+    fn head_request(url: &str) -> HeadRequest {
+        HeadRequest {
+            task_id: "test".to_string(),
+            url: url.to_string(),
+            http_header: None,
+            timeout: Duration::from_secs(5),
+            client_cert: None,
+            object_storage: None,
+            hdfs: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_head_file_and_directory() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("foo"), b"hello").unwrap();
+        std::fs::write(dir.path().join("sub/bar"), b"world!").unwrap();
+
+        let url = Url::from_file_path(dir.path().join("foo")).unwrap();
+        let response = new_file(dir.path())
+            .head(head_request(url.as_str()))
+            .await
+            .unwrap();
+        assert!(response.success);
+        assert_eq!(response.content_length, Some(5));
+        assert!(response.entries.is_empty());
+        assert!(response.http_header.unwrap().contains_key(LAST_MODIFIED));
+
+        let url = Url::from_directory_path(dir.path()).unwrap();
+        let mut entries = new_file(dir.path())
+            .head(head_request(url.as_str()))
+            .await
+            .unwrap()
+            .entries;
+        entries.sort_by(|a, b| a.url.cmp(&b.url));
+        assert_eq!(entries.len(), 3);
+        assert!(entries[0].url.ends_with("/foo"));
+        assert_eq!(entries[0].content_length, 5);
+        assert!(entries[1].url.ends_with("/sub/"));
+        assert!(entries[1].is_dir);
+        assert!(entries[2].url.ends_with("/sub/bar"));
+        assert_eq!(entries[2].content_length, 6);
+    }
+
+    #[tokio::test]
+    async fn should_list_directory_by_pages() {
+        let dir = tempdir().unwrap();
+        for path in ["a", "sub", "sub/deep", "sub-file"] {
+            std::fs::create_dir_all(dir.path().join(path)).unwrap();
+        }
+        for path in ["a/1", "a/2", "b", "sub/c", "sub/deep/d", "sub-file/e"] {
+            std::fs::write(dir.path().join(path), b"foo").unwrap();
+        }
+
+        let url = Url::from_directory_path(dir.path()).unwrap();
+        let file = new_file(dir.path());
+        let list = |page_size: usize, max_depth: Option<usize>| {
+            let file = &file;
+            let url = url.clone();
+            async move {
+                let mut urls = Vec::new();
+                let mut continuation_token = None;
+                loop {
+                    let response = file
+                        .list(ListRequest {
+                            task_id: "test".to_string(),
+                            url: url.to_string(),
+                            http_header: None,
+                            timeout: Duration::from_secs(5),
+                            client_cert: None,
+                            object_storage: None,
+                            hdfs: None,
+                            continuation_token,
+                            page_size,
+                            max_depth,
+                        })
+                        .await
+                        .unwrap();
+                    assert!(response.entries.len() <= page_size);
+                    urls.extend(
+                        response
+                            .entries
+                            .into_iter()
+                            .map(|entry| entry.url.trim_start_matches(url.as_str()).to_string()),
+                    );
+
+                    match response.continuation_token {
+                        Some(token) => continuation_token = Some(token),
+                        None => return urls,
+                    }
+                }
+            }
+        };
+
+        // The entries of all the pages are in the lexicographic order of the urls.
+        let expected = vec![
+            "a/",
+            "a/1",
+            "a/2",
+            "b",
+            "sub-file/",
+            "sub-file/e",
+            "sub/",
+            "sub/c",
+            "sub/deep/",
+            "sub/deep/d",
+        ];
+        for page_size in [1, 2, 3, 100] {
+            assert_eq!(list(page_size, None).await, expected);
+        }
+
+        assert_eq!(list(2, Some(1)).await, vec!["a/", "b", "sub-file/", "sub/"]);
+
+        // The file can not be listed.
+        let url = Url::from_file_path(dir.path().join("b")).unwrap();
+        let request = ListRequest {
+            task_id: "test".to_string(),
+            url: url.to_string(),
+            http_header: None,
+            timeout: Duration::from_secs(5),
+            client_cert: None,
+            object_storage: None,
+            hdfs: None,
+            continuation_token: None,
+            page_size: 10,
+            max_depth: None,
+        };
+        assert!(matches!(
+            file.list(request).await,
+            Err(ClientError::InvalidURI(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_get_file_range() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("foo"), b"hello, world!").unwrap();
+
+        let url = Url::from_file_path(dir.path().join("foo")).unwrap();
+        let mut response = new_file(dir.path())
+            .get(GetRequest {
+                task_id: "test".to_string(),
+                piece_id: "test".to_string(),
+                url: url.to_string(),
+                range: Some(Range {
+                    start: 7,
+                    length: 5,
+                }),
+                http_header: None,
+                timeout: Duration::from_secs(5),
+                client_cert: None,
+                object_storage: None,
+                hdfs: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "world");
+    }
+
+    #[tokio::test]
+    async fn should_return_error_when_file_not_found() {
+        let result = File::new(vec![PathBuf::from("/")])
+            .head(head_request("file:///non-existent-dragonfly-file"))
+            .await;
+        assert!(matches!(result, Err(ClientError::BackendError(_))));
+
+        let result = File::new(vec![PathBuf::from("/")])
+            .head(head_request("file://example.com/file"))
+            .await;
+        assert!(matches!(result, Err(ClientError::InvalidURI(_))));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn should_deny_file_outside_allowed_dirs() {
+        let dir = tempdir().unwrap();
+        let allowed_dir = dir.path().join("allowed");
+        std::fs::create_dir(&allowed_dir).unwrap();
+        std::fs::write(allowed_dir.join("foo"), b"hello").unwrap();
+        std::fs::write(dir.path().join("secret"), b"secret").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("secret"), allowed_dir.join("link")).unwrap();
+        let file = new_file(&allowed_dir);
+
+        // The file outside the allowed dirs is denied, even if it is reached by the parent
+        // directory or the symlink.
+        for path in [
+            dir.path().join("secret"),
+            allowed_dir.join("../secret"),
+            allowed_dir.join("link"),
+        ] {
+            let url = format!("file://{}", path.display());
+            let result = file.head(head_request(&url)).await;
+            assert!(matches!(
+                result,
+                Err(ClientError::BackendError(err)) if err.status_code == Some(reqwest::StatusCode::FORBIDDEN)
+            ));
+        }
+
+        // The symlink is skipped in the entries.
+        let url = Url::from_directory_path(&allowed_dir).unwrap();
+        let entries = file.head(head_request(url.as_str())).await.unwrap().entries;
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].url.ends_with("/foo"));
+
+        // The file backend without allowed dirs denies all files.
+        let url = Url::from_file_path(allowed_dir.join("foo")).unwrap();
+        assert!(File::new(Vec::new())
+            .head(head_request(url.as_str()))
+            .await
+            .is_err());
+    }
+}
@@ -19,10 +19,13 @@ use dragonfly_client_core::error::BackendError;
 use dragonfly_client_core::{Error as ClientError, Result as ClientResult};
 use opendal::{layers::TimeoutLayer, Metakey, Operator};
 use percent_encoding::percent_decode_str;
+use reqwest::Method;
+use serde::Deserialize;
 use std::time::Duration;
 use tokio_util::io::StreamReader;
 use tracing::{error, info, instrument};
 use url::Url;
+use uuid::Uuid;
 
 /// HDFS_SCHEME is the scheme of the HDFS.
 pub const HDFS_SCHEME: &str = "hdfs";
@@ -30,6 +33,13 @@ pub const HDFS_SCHEME: &str = "hdfs";
 /// DEFAULT_NAMENODE_PORT is the default port of the HDFS namenode.
 const DEFAULT_NAMENODE_PORT: u16 = 9870;
 
+/// RenameResponse is the response of the WebHDFS rename operation.
+#[derive(Debug, Deserialize)]
+struct RenameResponse {
+    /// boolean is whether the file is renamed.
+    boolean: bool,
+}
+
 /// Hdfs is a struct that implements the Backend trait.
 #[derive(Default)]
 pub struct Hdfs {
@@ -77,6 +87,61 @@ impl Hdfs {
             .finish()
             .layer(TimeoutLayer::new().with_timeout(timeout)))
     }
+
+    /// rename renames the file by the WebHDFS rename operation, the operator does not support
+    /// the rename of the WebHDFS.
+    async fn rename(
+        &self,
+        url: &Url,
+        from: &str,
+        to: &str,
+        config: Option<&common::v2::Hdfs>,
+        timeout: Duration,
+    ) -> ClientResult<()> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| ClientError::InvalidURI(url.to_string()))?;
+        let port = url.port().unwrap_or(DEFAULT_NAMENODE_PORT);
+
+        let mut rename_url = Url::parse(&format!("http://{}:{}", host, port))
+            .map_err(|_| ClientError::InvalidURI(url.to_string()))?;
+        rename_url.set_path(&format!("/webhdfs/v1{}", from));
+        rename_url
+            .query_pairs_mut()
+            .append_pair("op", "RENAME")
+            .append_pair("destination", to);
+        if let Some(delegation_token) = config.and_then(|config| config.delegation_token.as_ref()) {
+            rename_url
+                .query_pairs_mut()
+                .append_pair("delegation", delegation_token);
+        }
+
+        let response = reqwest::Client::new()
+            .request(Method::PUT, rename_url)
+            .timeout(timeout)
+            .send()
+            .await?;
+        let status_code = response.status();
+        let header = response.headers().clone();
+        let renamed = if status_code.is_success() {
+            let content = response.bytes().await?;
+            serde_json::from_slice::<RenameResponse>(&content)
+                .map(|response| response.boolean)
+                .unwrap_or(false)
+        } else {
+            false
+        };
+
+        if !renamed {
+            return Err(ClientError::BackendError(Box::new(BackendError {
+                message: format!("rename {} to {} failed", from, to),
+                status_code: Some(status_code),
+                header: Some(header),
+            })));
+        }
+
+        Ok(())
+    }
 }
 
 /// Implement the Backend trait for Hdfs.
@@ -243,11 +308,167 @@ impl super::Backend for Hdfs {
             error_message: None,
         })
     }
+
+    /// list lists the entries of the directory by pages.
+    #[instrument(skip_all)]
+    async fn list(&self, request: super::ListRequest) -> ClientResult<super::ListResponse> {
+        info!(
+            "list request {} {}: {:?}",
+            request.task_id, request.url, request.continuation_token
+        );
+
+        // Parse the URL.
+        let url = Url::parse(request.url.as_ref())
+            .map_err(|_| ClientError::InvalidURI(request.url.clone()))?;
+        let decoded_path = percent_decode_str(url.path())
+            .decode_utf8_lossy()
+            .to_string();
+
+        // Only the directory can be listed.
+        if !url.path().ends_with('/') {
+            return Err(ClientError::InvalidURI(request.url.clone()));
+        }
+
+        // Initialize the operator with the parsed URL and HDFS config.
+        let operator = self.operator(url.clone(), request.hdfs.clone(), request.timeout)?;
+
+        let response = super::list_by_operator(&operator, &decoded_path, &request, |path| {
+            let mut url = url.clone();
+            url.set_path(path);
+            url
+        })
+        .await
+        .map_err(|err| {
+            error!(
+                "list request failed {} {}: {}",
+                request.task_id, request.url, err
+            );
+            ClientError::BackendError(Box::new(BackendError {
+                message: err.to_string(),
+                status_code: None,
+                header: None,
+            }))
+        })?;
+
+        info!(
+            "list response {} {}: {}",
+            request.task_id,
+            request.url,
+            response.entries.len()
+        );
+
+        Ok(response)
+    }
+
+    /// put writes the content to the file. WebHDFS can not upload the parts of the file
+    /// concurrently, so the existing file is deleted and the content is appended in chunks.
+    #[instrument(skip_all)]
+    async fn put(
+        &self,
+        request: super::PutRequest,
+        mut reader: super::Body,
+    ) -> ClientResult<super::PutResponse> {
+        info!(
+            "put request {} {}: {:?}",
+            request.task_id, request.url, request.http_header
+        );
+
+        // Parse the URL.
+        let url = Url::parse(request.url.as_ref())
+            .map_err(|_| ClientError::InvalidURI(request.url.clone()))?;
+        if url.path().ends_with('/') {
+            return Err(ClientError::InvalidURI(request.url.clone()));
+        }
+
+        let decoded_path = percent_decode_str(url.path())
+            .decode_utf8_lossy()
+            .to_string();
+
+        // Initialize the operator with the parsed URL and HDFS config.
+        let operator = self.operator(url.clone(), request.hdfs.clone(), request.timeout)?;
+        let to_backend_error = |err: opendal::Error| {
+            error!(
+                "put request failed {} {}: {}",
+                request.task_id, request.url, err
+            );
+            ClientError::BackendError(Box::new(BackendError {
+                message: err.to_string(),
+                status_code: None,
+                header: None,
+            }))
+        };
+
+        // The content is written to the temporary file in the same directory, and renamed to
+        // the file after the content is written, so the file is never left partially written.
+        let temporary_path = format!("{}.{}.tmp", decoded_path, Uuid::new_v4());
+        let writer = operator
+            .writer_with(temporary_path.as_str())
+            .append(true)
+            .chunk(super::PUT_CHUNK_SIZE)
+            .await
+            .map_err(to_backend_error)?;
+
+        let result = async {
+            let content_length =
+                super::write_by_operator(&mut reader, writer, request.content_length).await?;
+
+            operator
+                .delete(decoded_path.as_str())
+                .await
+                .map_err(to_backend_error)?;
+            self.rename(
+                &url,
+                temporary_path.as_str(),
+                decoded_path.as_str(),
+                request.hdfs.as_ref(),
+                request.timeout,
+            )
+            .await?;
+
+            Ok::<_, ClientError>(content_length)
+        }
+        .await;
+
+        let content_length = match result {
+            Ok(content_length) => content_length,
+            Err(err) => {
+                error!(
+                    "put request failed {} {}: {}",
+                    request.task_id, request.url, err
+                );
+
+                operator
+                    .delete(temporary_path.as_str())
+                    .await
+                    .unwrap_or_else(|err| {
+                        error!("delete temporary file {} failed: {}", temporary_path, err);
+                    });
+                return Err(err);
+            }
+        };
+
+        info!(
+            "put response {} {}: {}",
+            request.task_id, request.url, content_length
+        );
+
+        Ok(super::PutResponse {
+            success: true,
+            content_length,
+            http_header: None,
+            http_status_code: None,
+            error_message: None,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
 
     #[tokio::test]
     async fn should_get_operator() {
@@ -269,4 +490,54 @@ mod tests {
         assert!(result.is_err());
         assert!(matches!(result.unwrap_err(), ClientError::InvalidURI(..)));
     }
+
+    #[tokio::test]
+    async fn should_rename_by_webhdfs() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/webhdfs/v1/dir/file.tmp"))
+            .and(query_param("op", "RENAME"))
+            .and(query_param("destination", "/dir/file"))
+            .and(query_param("delegation", "token"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"boolean":true}"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/webhdfs/v1/dir/exists.tmp"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"boolean":false}"#))
+            .mount(&server)
+            .await;
+
+        let url = Url::parse(&format!(
+            "hdfs://{}/dir/file",
+            server.uri().trim_start_matches("http://")
+        ))
+        .unwrap();
+        let config = common::v2::Hdfs {
+            delegation_token: Some("token".to_string()),
+        };
+
+        let hdfs = Hdfs::new();
+        assert!(hdfs
+            .rename(
+                &url,
+                "/dir/file.tmp",
+                "/dir/file",
+                Some(&config),
+                Duration::from_secs(5)
+            )
+            .await
+            .is_ok());
+        assert!(matches!(
+            hdfs.rename(
+                &url,
+                "/dir/exists.tmp",
+                "/dir/file",
+                None,
+                Duration::from_secs(5)
+            )
+            .await,
+            Err(ClientError::BackendError(_))
+        ));
+    }
 }
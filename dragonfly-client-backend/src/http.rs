@@ -17,8 +17,12 @@
 use dragonfly_client_core::{Error, Result};
 use dragonfly_client_util::tls::NoVerifier;
 use futures::TryStreamExt;
+use reqwest::{header, StatusCode};
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
-use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use reqwest_retry::{
+    default_on_request_failure, default_on_request_success, policies::ExponentialBackoff,
+    RetryTransientMiddleware, Retryable, RetryableStrategy,
+};
 use reqwest_tracing::TracingMiddleware;
 use rustls_pki_types::CertificateDer;
 use std::io::{Error as IOError, ErrorKind};
@@ -31,6 +35,33 @@ pub const HTTP_SCHEME: &str = "http";
 /// HTTPS_SCHEME is the HTTPS scheme.
 pub const HTTPS_SCHEME: &str = "https";
 
+/// RetryAfterStrategy is the retryable strategy of the HTTP backend. The 429 and 503 responses
+/// with Retry-After are not retried immediately, they are returned to the caller to wait for
+/// the Retry-After, other responses are retried by the default strategy.
+struct RetryAfterStrategy;
+
+/// RetryAfterStrategy implements the RetryableStrategy trait.
+impl RetryableStrategy for RetryAfterStrategy {
+    /// handle returns whether the response should be retried.
+    fn handle(
+        &self,
+        res: &std::result::Result<reqwest::Response, reqwest_middleware::Error>,
+    ) -> Option<Retryable> {
+        match res {
+            Ok(response)
+                if matches!(
+                    response.status(),
+                    StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+                ) && response.headers().contains_key(header::RETRY_AFTER) =>
+            {
+                None
+            }
+            Ok(response) => default_on_request_success(response),
+            Err(err) => default_on_request_failure(err),
+        }
+    }
+}
+
 /// HTTP is the HTTP backend.
 pub struct HTTP {
     /// scheme is the scheme of the HTTP backend.
@@ -76,7 +107,10 @@ impl HTTP {
             ExponentialBackoff::builder().build_with_max_retries(super::MAX_RETRY_TIMES);
         let client = ClientBuilder::new(client)
             .with(TracingMiddleware::default())
-            .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+            .with(RetryTransientMiddleware::new_with_policy_and_strategy(
+                retry_policy,
+                RetryAfterStrategy,
+            ))
             .build();
 
         Ok(Self {
@@ -86,7 +120,7 @@ impl HTTP {
     }
 
     /// client returns a new reqwest client.
-    fn client(
+    pub(crate) fn client(
         &self,
         client_cert: Option<Vec<CertificateDer<'static>>>,
     ) -> Result<ClientWithMiddleware> {
@@ -124,7 +158,10 @@ impl HTTP {
                     ExponentialBackoff::builder().build_with_max_retries(super::MAX_RETRY_TIMES);
                 let client = ClientBuilder::new(client)
                     .with(TracingMiddleware::default())
-                    .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+                    .with(RetryTransientMiddleware::new_with_policy_and_strategy(
+                        retry_policy,
+                        RetryAfterStrategy,
+                    ))
                     .build();
 
                 Ok(client)
@@ -533,6 +570,37 @@ TrIVG3cErZoBC6zqBs/Ibe9q3gdHGqS3QLAKy/k=
         assert_eq!(resp.text().await.unwrap(), "OK");
     }
 
+    #[tokio::test]
+    async fn should_not_retry_response_with_retry_after() {
+        let server = wiremock::MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/get"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "10"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let resp = HTTP::new(HTTP_SCHEME)
+            .unwrap()
+            .get(GetRequest {
+                task_id: "test".to_string(),
+                piece_id: "test".to_string(),
+                url: format!("{}/get", server.uri()),
+                range: None,
+                http_header: Some(HeaderMap::new()),
+                timeout: std::time::Duration::from_secs(5),
+                client_cert: None,
+                object_storage: None,
+                hdfs: None,
+            })
+            .await
+            .unwrap();
+
+        assert!(!resp.success);
+        assert_eq!(resp.http_status_code, Some(StatusCode::TOO_MANY_REQUESTS));
+        assert_eq!(resp.http_header.unwrap().get("Retry-After").unwrap(), "10");
+    }
+
     #[tokio::test]
     async fn should_get_head_response_with_self_signed_cert() {
         let server_addr = start_https_server(SERVER_CERT, SERVER_KEY).await;
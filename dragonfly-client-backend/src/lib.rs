@@ -19,19 +19,24 @@ use dragonfly_client_core::{
     error::{ErrorType, OrErr},
     Error, Result,
 };
+use futures::TryStreamExt;
 use libloading::Library;
 use reqwest::header::HeaderMap;
 use rustls_pki_types::CertificateDer;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::{collections::HashMap, pin::Pin, time::Duration};
 use std::{fmt::Debug, fs};
 use tokio::io::{AsyncRead, AsyncReadExt};
 use tracing::{error, info, warn};
 use url::Url;
 
+pub mod credential;
+pub mod file;
 pub mod hdfs;
 pub mod http;
 pub mod object_storage;
+pub mod oci;
 
 /// POOL_MAX_IDLE_PER_HOST is the max idle connections per host.
 const POOL_MAX_IDLE_PER_HOST: usize = 1024;
@@ -48,6 +53,36 @@ const HTTP2_KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(20);
 /// MAX_RETRY_TIMES is the max retry times for the request.
 const MAX_RETRY_TIMES: u32 = 1;
 
+/// PUT_CHUNK_SIZE is the size of the chunk to put the content to the backend, the content
+/// larger than it is uploaded by multipart.
+const PUT_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// PUT_CONCURRENCY is the number of the chunks uploaded concurrently.
+const PUT_CONCURRENCY: usize = 4;
+
+/// DEFAULT_LIST_PAGE_SIZE is the default max number of the entries in a page of the listing.
+pub const DEFAULT_LIST_PAGE_SIZE: usize = 1000;
+
+/// LIST_HEADER_PREFIX is the prefix of the reserved request headers of the list task entries.
+/// The unary list task entries RPC has no field for the pagination, so the paginated listing
+/// is requested by the reserved headers instead of a server-streaming RPC, and the listing by
+/// the prefix and the delimiter is not supported. The reserved headers are removed from the
+/// request header before it is sent to the backend.
+pub const LIST_HEADER_PREFIX: &str = "X-Dragonfly-List-";
+
+/// LIST_PAGE_SIZE_HEADER is the request header of the list task entries to list the entries
+/// by pages, the value is the max number of the entries in a page.
+pub const LIST_PAGE_SIZE_HEADER: &str = "X-Dragonfly-List-Page-Size";
+
+/// LIST_MAX_DEPTH_HEADER is the request header of the list task entries to limit the depth of
+/// the entries, 1 means only the direct children of the directory are listed.
+pub const LIST_MAX_DEPTH_HEADER: &str = "X-Dragonfly-List-Max-Depth";
+
+/// LIST_CONTINUATION_TOKEN_HEADER is the request and response header of the list task entries,
+/// the response returns the token if there are more entries, and the next request continues
+/// the listing after the token.
+pub const LIST_CONTINUATION_TOKEN_HEADER: &str = "X-Dragonfly-List-Continuation-Token";
+
 /// NAME is the name of the package.
 pub const NAME: &str = "backend";
 
@@ -165,6 +200,100 @@ where
     }
 }
 
+/// ListRequest is the list request for backend, the entries of the directory are listed by
+/// pages in the lexicographic order of the urls.
+pub struct ListRequest {
+    /// task_id is the id of the task.
+    pub task_id: String,
+
+    /// url is the url of the directory, it is the prefix of the listed entries.
+    pub url: String,
+
+    /// http_header is the headers of the request.
+    pub http_header: Option<HeaderMap>,
+
+    /// timeout is the timeout of the request.
+    pub timeout: Duration,
+
+    /// client_cert is the client certificates for the request.
+    pub client_cert: Option<Vec<CertificateDer<'static>>>,
+
+    /// object_storage is the object storage related information.
+    pub object_storage: Option<ObjectStorage>,
+
+    /// hdfs is the hdfs related information.
+    pub hdfs: Option<Hdfs>,
+
+    /// continuation_token is the token returned by the previous page, the listing continues
+    /// after it.
+    pub continuation_token: Option<String>,
+
+    /// page_size is the max number of the entries in the page.
+    pub page_size: usize,
+
+    /// max_depth is the max depth of the entries relative to the directory, 1 means only the
+    /// direct children are listed. None means the entries are listed recursively.
+    pub max_depth: Option<usize>,
+}
+
+/// ListResponse is the list response for backend.
+#[derive(Debug)]
+pub struct ListResponse {
+    /// entries are the entries of the page.
+    pub entries: Vec<DirEntry>,
+
+    /// continuation_token is the token to list the next page, None means the listing is
+    /// finished.
+    pub continuation_token: Option<String>,
+}
+
+/// PutRequest is the put request for backend.
+pub struct PutRequest {
+    /// task_id is the id of the task.
+    pub task_id: String,
+
+    /// url is the url of the request.
+    pub url: String,
+
+    /// content_length is the length of the content, None means the content is streamed
+    /// with the unknown length.
+    pub content_length: Option<u64>,
+
+    /// http_header is the headers of the request.
+    pub http_header: Option<HeaderMap>,
+
+    /// timeout is the timeout of the request.
+    pub timeout: Duration,
+
+    /// client_cert is the client certificates for the request.
+    pub client_cert: Option<Vec<CertificateDer<'static>>>,
+
+    /// object_storage is the object storage related information.
+    pub object_storage: Option<ObjectStorage>,
+
+    /// hdfs is the hdfs related information.
+    pub hdfs: Option<Hdfs>,
+}
+
+/// PutResponse is the put response for backend.
+#[derive(Debug)]
+pub struct PutResponse {
+    /// success is the success of the response.
+    pub success: bool,
+
+    /// content_length is the length of the content written to the backend.
+    pub content_length: u64,
+
+    /// http_header is the headers of the response.
+    pub http_header: Option<HeaderMap>,
+
+    /// http_status_code is the status code of the response.
+    pub http_status_code: Option<reqwest::StatusCode>,
+
+    /// error_message is the error message of the response.
+    pub error_message: Option<String>,
+}
+
 /// The File Entry of a directory, including some relevant file metadata.
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct DirEntry {
@@ -189,6 +318,220 @@ pub trait Backend {
 
     /// get gets the content of the request.
     async fn get(&self, request: GetRequest) -> Result<GetResponse<Body>>;
+
+    /// list lists the entries of the directory by pages. The default implementation lists
+    /// all the entries by head and pages them, the backends which support the paginated
+    /// listing override it to bound the memory of the large directories.
+    async fn list(&self, request: ListRequest) -> Result<ListResponse> {
+        let response = self
+            .head(HeadRequest {
+                task_id: request.task_id,
+                url: request.url.clone(),
+                http_header: request.http_header,
+                timeout: request.timeout,
+                client_cert: request.client_cert,
+                object_storage: request.object_storage,
+                hdfs: request.hdfs,
+            })
+            .await?;
+
+        let root = Url::parse(&request.url).or_err(ErrorType::ParseError)?;
+        let mut entries = response
+            .entries
+            .into_iter()
+            .filter(|entry| {
+                request
+                    .continuation_token
+                    .as_ref()
+                    .is_none_or(|token| entry.url > *token)
+            })
+            .filter(|entry| {
+                request.max_depth.is_none_or(|max_depth| {
+                    Url::parse(&entry.url)
+                        .is_ok_and(|url| entry_depth(root.path(), url.path()) <= max_depth)
+                })
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.url.cmp(&b.url));
+
+        let continuation_token = if entries.len() > request.page_size {
+            entries.truncate(request.page_size);
+            entries.last().map(|entry| entry.url.clone())
+        } else {
+            None
+        };
+
+        Ok(ListResponse {
+            entries,
+            continuation_token,
+        })
+    }
+
+    /// put writes the content read from the reader to the url of the request. The content
+    /// is uploaded in chunks, so the large content is streamed by multipart upload if the
+    /// backend supports it. The backends which can not be written return unsupported.
+    async fn put(&self, request: PutRequest, _reader: Body) -> Result<PutResponse> {
+        Err(Error::Unsupported(format!(
+            "put {} by {}",
+            request.url,
+            self.scheme()
+        )))
+    }
+}
+
+/// write_by_operator writes the content read from the reader to the writer of the OpenDAL
+/// operator in chunks, and returns the length of the written content. The writer is aborted
+/// if the writing fails, so the uploaded parts are cleaned up by the backend.
+async fn write_by_operator(
+    reader: &mut Body,
+    mut writer: opendal::Writer,
+    expected_length: Option<u64>,
+) -> Result<u64> {
+    let mut length = 0;
+    let result = async {
+        loop {
+            let mut buffer = Vec::with_capacity(PUT_CHUNK_SIZE);
+            let n = (&mut *reader)
+                .take(PUT_CHUNK_SIZE as u64)
+                .read_to_end(&mut buffer)
+                .await?;
+            if n == 0 {
+                break;
+            }
+
+            length += n as u64;
+            writer.write(buffer).await?;
+        }
+
+        if let Some(expected_length) = expected_length {
+            if length != expected_length {
+                return Err(Error::ContentLengthMismatch(expected_length, length));
+            }
+        }
+
+        writer.close().await?;
+        Ok::<_, Error>(length)
+    }
+    .await;
+
+    if result.is_err() {
+        writer.abort().await.unwrap_or_else(|err| {
+            error!("abort writer failed: {}", err);
+        });
+    }
+
+    result
+}
+
+/// is_list_header returns whether the key is the reserved header of the list task entries,
+/// the key is compared case insensitively as the http header.
+pub fn is_list_header(key: &str) -> bool {
+    key.len() >= LIST_HEADER_PREFIX.len()
+        && key.as_bytes()[..LIST_HEADER_PREFIX.len()]
+            .eq_ignore_ascii_case(LIST_HEADER_PREFIX.as_bytes())
+}
+
+/// take_list_header removes the reserved header of the list task entries from the request
+/// header, and returns its value. The key is compared case insensitively as the http header.
+pub fn take_list_header(header: &mut HashMap<String, String>, key: &str) -> Option<String> {
+    let key = header
+        .keys()
+        .find(|k| k.eq_ignore_ascii_case(key))
+        .cloned()?;
+    header.remove(&key)
+}
+
+/// entry_depth returns the depth of the entry path relative to the root path, the direct
+/// children of the root are at depth 1.
+fn entry_depth(root: &str, path: &str) -> usize {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .count()
+}
+
+/// list_by_operator lists the entries of the path by the lister of the OpenDAL operator, the
+/// entries are read lazily and the listing stops when the page is full. The continuation
+/// token is the path of the last entry if the service lists by the lexicographic order with
+/// start after, otherwise it is the offset of the entries and the listed entries are
+/// skipped.
+async fn list_by_operator<F>(
+    operator: &opendal::Operator,
+    path: &str,
+    request: &ListRequest,
+    make_url: F,
+) -> Result<ListResponse>
+where
+    F: Fn(&str) -> Url,
+{
+    // Parse the continuation token of the previous page.
+    let start_after = operator.info().full_capability().list_with_start_after;
+    let mut offset = 0;
+    let mut lister = operator
+        .lister_with(path)
+        .recursive(request.max_depth != Some(1))
+        .metakey(opendal::Metakey::ContentLength | opendal::Metakey::Mode);
+    if let Some(token) = request.continuation_token.as_deref() {
+        if start_after {
+            lister = lister.start_after(token);
+        } else {
+            offset = token
+                .parse::<usize>()
+                .map_err(|_| Error::InvalidParameter)?;
+        }
+    }
+
+    // The paths of the entries are relative to the root of the operator.
+    let root = path.trim_start_matches('/');
+    let mut lister = lister.await?;
+    let mut entries = Vec::new();
+    let mut last_path = None;
+    let mut skipped = 0;
+    while let Some(entry) = lister.try_next().await? {
+        // Skip the directory itself and the entries deeper than the max depth.
+        if entry.path() == root
+            || request
+                .max_depth
+                .is_some_and(|max_depth| entry_depth(root, entry.path()) > max_depth)
+        {
+            continue;
+        }
+
+        // Skip the entries listed by the previous pages if the service does not support
+        // start after.
+        if skipped < offset {
+            skipped += 1;
+            continue;
+        }
+
+        // The page is full and there are more entries.
+        if entries.len() == request.page_size {
+            let continuation_token = if start_after {
+                last_path
+            } else {
+                Some((offset + entries.len()).to_string())
+            };
+
+            return Ok(ListResponse {
+                entries,
+                continuation_token,
+            });
+        }
+
+        let metadata = entry.metadata();
+        entries.push(DirEntry {
+            url: make_url(entry.path()).to_string(),
+            content_length: metadata.content_length() as usize,
+            is_dir: metadata.is_dir(),
+        });
+        last_path = Some(entry.path().to_string());
+    }
+
+    Ok(ListResponse {
+        entries,
+        continuation_token: None,
+    })
 }
 
 /// BackendFactory is the factory of the backend.
@@ -206,7 +549,8 @@ pub struct BackendFactory {
 /// backends and plugin backends.
 ///
 /// The builtin backends are http, https, etc., which are implemented
-/// by the HTTP struct.
+/// by the HTTP struct, and the file backend reads the local file system if the allowed
+/// directories are configured.
 ///
 /// The plugin backends are shared libraries, which are loaded
 /// by the `register_plugin` function. The file name of the shared
@@ -227,8 +571,19 @@ pub struct BackendFactory {
 impl BackendFactory {
     /// new returns a new BackendFactory.
     pub fn new(plugin_dir: Option<&Path>) -> Result<Self> {
+        Self::new_with_credential_provider(plugin_dir, None, credential::CredentialScope::default())
+    }
+
+    /// new_with_credential_provider returns a new BackendFactory, the object storage backends
+    /// load the credential by the credential provider if the request does not carry it and
+    /// the bucket and the endpoint of the request are in the credential scope.
+    pub fn new_with_credential_provider(
+        plugin_dir: Option<&Path>,
+        credential_provider: Option<Arc<dyn credential::CredentialProvider>>,
+        credential_scope: credential::CredentialScope,
+    ) -> Result<Self> {
         let mut backend_factory = Self::default();
-        backend_factory.load_builtin_backends()?;
+        backend_factory.load_builtin_backends(credential_provider, credential_scope)?;
         if let Some(plugin_dir) = plugin_dir {
             backend_factory
                 .load_plugin_backends(plugin_dir)
@@ -240,6 +595,35 @@ impl BackendFactory {
         Ok(backend_factory)
     }
 
+    /// with_file_allowed_dirs loads the file backend which reads the files in the allowed
+    /// directories. The file backend is not loaded if the allowed directories are empty,
+    /// because the urls are sent by the remote peers and the scheduler.
+    pub fn with_file_allowed_dirs(mut self, allowed_dirs: Vec<PathBuf>) -> Self {
+        if allowed_dirs.is_empty() {
+            return self;
+        }
+
+        self.backends.insert(
+            file::FILE_SCHEME.to_string(),
+            Box::new(file::File::new(allowed_dirs)),
+        );
+        info!("load [file] builtin backend");
+        self
+    }
+
+    /// with_oci_allowed_token_hosts reloads the oci backend with the hosts of the token
+    /// services which are allowed to receive the credential besides the registry itself.
+    pub fn with_oci_allowed_token_hosts(
+        mut self,
+        allowed_token_hosts: Vec<String>,
+    ) -> Result<Self> {
+        self.backends.insert(
+            oci::OCI_SCHEME.to_string(),
+            Box::new(oci::OCI::new()?.with_allowed_token_hosts(allowed_token_hosts)),
+        );
+        Ok(self)
+    }
+
     /// unsupported_download_directory returns whether the scheme does not support directory download.
     pub fn unsupported_download_directory(scheme: &str) -> bool {
         scheme == http::HTTP_SCHEME || scheme == http::HTTPS_SCHEME
@@ -259,7 +643,21 @@ impl BackendFactory {
     }
 
     /// load_builtin_backends loads the builtin backends.
-    fn load_builtin_backends(&mut self) -> Result<()> {
+    fn load_builtin_backends(
+        &mut self,
+        credential_provider: Option<Arc<dyn credential::CredentialProvider>>,
+        credential_scope: credential::CredentialScope,
+    ) -> Result<()> {
+        // Initialize the object storage backend with the credential provider.
+        let new_object_storage = |scheme| -> Result<object_storage::ObjectStorage> {
+            let object_storage = object_storage::ObjectStorage::new(scheme)?;
+            Ok(match credential_provider.clone() {
+                Some(credential_provider) => object_storage
+                    .with_credential_provider(credential_provider, credential_scope.clone()),
+                None => object_storage,
+            })
+        };
+
         self.backends.insert(
             "http".to_string(),
             Box::new(http::HTTP::new(http::HTTP_SCHEME)?),
@@ -274,49 +672,37 @@ impl BackendFactory {
 
         self.backends.insert(
             "s3".to_string(),
-            Box::new(object_storage::ObjectStorage::new(
-                object_storage::Scheme::S3,
-            )?),
+            Box::new(new_object_storage(object_storage::Scheme::S3)?),
         );
         info!("load [s3] builtin backend");
 
         self.backends.insert(
             "gs".to_string(),
-            Box::new(object_storage::ObjectStorage::new(
-                object_storage::Scheme::GCS,
-            )?),
+            Box::new(new_object_storage(object_storage::Scheme::GCS)?),
         );
         info!("load [gcs] builtin backend");
 
         self.backends.insert(
             "abs".to_string(),
-            Box::new(object_storage::ObjectStorage::new(
-                object_storage::Scheme::ABS,
-            )?),
+            Box::new(new_object_storage(object_storage::Scheme::ABS)?),
         );
         info!("load [abs] builtin backend");
 
         self.backends.insert(
             "oss".to_string(),
-            Box::new(object_storage::ObjectStorage::new(
-                object_storage::Scheme::OSS,
-            )?),
+            Box::new(new_object_storage(object_storage::Scheme::OSS)?),
         );
         info!("load [oss] builtin backend");
 
         self.backends.insert(
             "obs".to_string(),
-            Box::new(object_storage::ObjectStorage::new(
-                object_storage::Scheme::OBS,
-            )?),
+            Box::new(new_object_storage(object_storage::Scheme::OBS)?),
         );
         info!("load [obs] builtin backend");
 
         self.backends.insert(
             "cos".to_string(),
-            Box::new(object_storage::ObjectStorage::new(
-                object_storage::Scheme::COS,
-            )?),
+            Box::new(new_object_storage(object_storage::Scheme::COS)?),
         );
         info!("load [cos] builtin backend");
 
@@ -324,6 +710,10 @@ impl BackendFactory {
             .insert("hdfs".to_string(), Box::new(hdfs::Hdfs::new()));
         info!("load [hdfs] builtin backend");
 
+        self.backends
+            .insert("oci".to_string(), Box::new(oci::OCI::new()?));
+        info!("load [oci] builtin backend");
+
         Ok(())
     }
 
@@ -383,11 +773,16 @@ mod tests {
     fn should_load_builtin_backends() {
         let factory = BackendFactory::new(None).unwrap();
         let expected_backends = vec![
-            "http", "https", "s3", "gs", "abs", "oss", "obs", "cos", "hdfs",
+            "http", "https", "s3", "gs", "abs", "oss", "obs", "cos", "hdfs", "oci",
         ];
         for backend in expected_backends {
             assert!(factory.backends.contains_key(backend));
         }
+
+        // The file backend is loaded only if the allowed dirs are configured.
+        assert!(!factory.backends.contains_key("file"));
+        let factory = factory.with_file_allowed_dirs(vec![PathBuf::from("/mnt/nfs")]);
+        assert!(factory.backends.contains_key("file"));
     }
 
     #[test]
@@ -415,7 +810,7 @@ mod tests {
         let plugin_dir = dir.path().join("non_existent_plugin_dir");
 
         let factory = BackendFactory::new(Some(&plugin_dir)).unwrap();
-        assert_eq!(factory.backends.len(), 9);
+        assert_eq!(factory.backends.len(), 10);
     }
 
     #[test]
@@ -490,6 +885,221 @@ mod tests {
         );
     }
 
+    fn list_request(url: &str, page_size: usize, max_depth: Option<usize>) -> ListRequest {
+        ListRequest {
+            task_id: "test".to_string(),
+            url: url.to_string(),
+            http_header: None,
+            timeout: Duration::from_secs(5),
+            client_cert: None,
+            object_storage: None,
+            hdfs: None,
+            continuation_token: None,
+            page_size,
+            max_depth,
+        }
+    }
+
+    #[tokio::test]
+    async fn should_list_by_operator() {
+        let operator = opendal::Operator::new(opendal::services::Memory::default())
+            .unwrap()
+            .finish();
+        for path in ["dir/a", "dir/b", "dir/c", "dir/sub/d", "other/e"] {
+            operator.write(path, b"foo".to_vec()).await.unwrap();
+        }
+
+        let operator = &operator;
+        let make_url = |path: &str| Url::parse(&format!("s3://bucket/{}", path)).unwrap();
+        let list_files = |max_depth| async move {
+            let mut request = list_request("s3://bucket/dir/", 2, max_depth);
+            let mut files = Vec::new();
+            let mut pages = 0;
+            loop {
+                let response = list_by_operator(operator, "dir/", &request, make_url)
+                    .await
+                    .unwrap();
+                assert!(response.entries.len() <= 2);
+                pages += 1;
+                files.extend(
+                    response
+                        .entries
+                        .into_iter()
+                        .filter(|entry| !entry.is_dir)
+                        .map(|entry| entry.url),
+                );
+
+                match response.continuation_token {
+                    Some(token) => request.continuation_token = Some(token),
+                    None => break,
+                }
+            }
+
+            files.sort();
+            (files, pages)
+        };
+
+        let (files, pages) = list_files(None).await;
+        assert_eq!(
+            files,
+            vec![
+                "s3://bucket/dir/a",
+                "s3://bucket/dir/b",
+                "s3://bucket/dir/c",
+                "s3://bucket/dir/sub/d"
+            ]
+        );
+        assert!(pages >= 2);
+
+        let (files, _) = list_files(Some(1)).await;
+        assert_eq!(
+            files,
+            vec![
+                "s3://bucket/dir/a",
+                "s3://bucket/dir/b",
+                "s3://bucket/dir/c"
+            ]
+        );
+    }
+
+    #[test]
+    fn should_take_list_headers() {
+        let mut header = HashMap::from([
+            ("x-dragonfly-list-page-size".to_string(), "10".to_string()),
+            (LIST_MAX_DEPTH_HEADER.to_string(), "1".to_string()),
+            ("X-Dragonfly-List-Unknown".to_string(), "foo".to_string()),
+            ("Authorization".to_string(), "bar".to_string()),
+        ]);
+
+        assert_eq!(
+            take_list_header(&mut header, LIST_PAGE_SIZE_HEADER),
+            Some("10".to_string())
+        );
+        assert_eq!(
+            take_list_header(&mut header, LIST_MAX_DEPTH_HEADER),
+            Some("1".to_string())
+        );
+        assert_eq!(
+            take_list_header(&mut header, LIST_CONTINUATION_TOKEN_HEADER),
+            None
+        );
+
+        header.retain(|key, _| !is_list_header(key));
+        assert_eq!(
+            header,
+            HashMap::from([("Authorization".to_string(), "bar".to_string())])
+        );
+    }
+
+    #[tokio::test]
+    async fn should_list_by_head() {
+        // HeadBackend returns all the entries of the directory by head.
+        struct HeadBackend(Vec<DirEntry>);
+
+        #[tonic::async_trait]
+        impl Backend for HeadBackend {
+            fn scheme(&self) -> String {
+                "test".to_string()
+            }
+
+            async fn head(&self, _request: HeadRequest) -> Result<HeadResponse> {
+                Ok(HeadResponse {
+                    success: true,
+                    content_length: None,
+                    http_header: None,
+                    http_status_code: None,
+                    error_message: None,
+                    entries: self.0.clone(),
+                })
+            }
+
+            async fn get(&self, request: GetRequest) -> Result<GetResponse<Body>> {
+                Err(Error::Unsupported(format!("get {}", request.url)))
+            }
+        }
+
+        let new_entry = |url: &str| DirEntry {
+            url: url.to_string(),
+            content_length: 3,
+            is_dir: url.ends_with('/'),
+        };
+        let backend = HeadBackend(vec![
+            new_entry("test://root/sub/c"),
+            new_entry("test://root/b"),
+            new_entry("test://root/sub/"),
+            new_entry("test://root/a"),
+        ]);
+
+        let url = "test://root/";
+        let response = backend.list(list_request(url, 2, None)).await.unwrap();
+        assert_eq!(response.entries.len(), 2);
+        assert!(response.entries[0].url.ends_with("/a"));
+        assert!(response.entries[1].url.ends_with("/b"));
+
+        let mut request = list_request(url, 2, None);
+        request.continuation_token = response.continuation_token;
+        let response = backend.list(request).await.unwrap();
+        assert_eq!(response.entries.len(), 2);
+        assert!(response.entries[0].url.ends_with("/sub/"));
+        assert!(response.entries[1].url.ends_with("/sub/c"));
+        assert!(response.continuation_token.is_none());
+
+        let response = backend.list(list_request(url, 10, Some(1))).await.unwrap();
+        assert_eq!(response.entries.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn should_write_by_operator() {
+        let operator = opendal::Operator::new(opendal::services::Memory::default())
+            .unwrap()
+            .finish();
+
+        let mut reader: Body = Box::new(std::io::Cursor::new(b"hello, world!".to_vec()));
+        let writer = operator.writer("file").await.unwrap();
+        assert_eq!(
+            write_by_operator(&mut reader, writer, Some(13))
+                .await
+                .unwrap(),
+            13
+        );
+        assert_eq!(
+            operator.read("file").await.unwrap().to_vec(),
+            b"hello, world!"
+        );
+
+        // The content is not committed if the length mismatches.
+        let mut reader: Body = Box::new(std::io::Cursor::new(b"hello".to_vec()));
+        let writer = operator.writer("mismatch").await.unwrap();
+        assert!(matches!(
+            write_by_operator(&mut reader, writer, Some(13)).await,
+            Err(Error::ContentLengthMismatch(13, 5))
+        ));
+        assert!(!operator.is_exist("mismatch").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn should_return_unsupported_when_put_by_http() {
+        let factory = BackendFactory::new(None).unwrap();
+        let result = factory
+            .build("http://example.com/file")
+            .unwrap()
+            .put(
+                PutRequest {
+                    task_id: "test".to_string(),
+                    url: "http://example.com/file".to_string(),
+                    content_length: None,
+                    http_header: None,
+                    timeout: Duration::from_secs(5),
+                    client_cert: None,
+                    object_storage: None,
+                    hdfs: None,
+                },
+                Box::new(tokio::io::empty()),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::Unsupported(_))));
+    }
+
     // build_example_plugin builds the example plugin.
     fn build_example_plugin(backend_dir: &Path) {
         // Build example plugin.
@@ -14,19 +14,27 @@
  * limitations under the License.
  */
 
+use crate::credential::{CredentialProvider, CredentialScope};
 use dragonfly_api::common;
 use dragonfly_client_core::error::BackendError;
 use dragonfly_client_core::{Error as ClientError, Result as ClientResult};
+use dragonfly_client_util::digest::{Algorithm, Hasher};
+use lru::LruCache;
 use opendal::{layers::TimeoutLayer, raw::HttpClient, Metakey, Operator};
 use percent_encoding::percent_decode_str;
 use std::fmt;
+use std::num::NonZeroUsize;
 use std::result::Result;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio_util::io::StreamReader;
 use tracing::{debug, error, instrument};
 use url::Url;
 
+/// OPERATOR_CACHE_CAPACITY is the max number of the operators cached by the object storage.
+const OPERATOR_CACHE_CAPACITY: usize = 128;
+
 /// Scheme is the scheme of the object storage.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Scheme {
@@ -172,6 +180,18 @@ pub struct ObjectStorage {
 
     /// client is the reqwest client.
     client: reqwest::Client,
+
+    /// operators are the operators cached by the scheme, bucket, endpoint and credential
+    /// identity, the operators are reused by the requests of the same bucket and credential.
+    operators: Mutex<LruCache<String, Operator>>,
+
+    /// credential_provider provides the credential when the request does not carry the
+    /// access key.
+    credential_provider: Option<Arc<dyn CredentialProvider>>,
+
+    /// credential_scope is the buckets and the endpoints which the credential of the provider
+    /// can be applied to.
+    credential_scope: CredentialScope,
 }
 
 /// ObjectStorage implements the ObjectStorage trait.
@@ -192,10 +212,107 @@ impl ObjectStorage {
             .http2_keep_alive_while_idle(true)
             .build()?;
 
-        Ok(Self { scheme, client })
+        Ok(Self {
+            scheme,
+            client,
+            operators: Mutex::new(LruCache::new(
+                NonZeroUsize::new(OPERATOR_CACHE_CAPACITY).unwrap(),
+            )),
+            credential_provider: None,
+            credential_scope: CredentialScope::default(),
+        })
+    }
+
+    /// with_credential_provider sets the credential provider of the object storage, the
+    /// credential is only applied to the buckets and the endpoints of the scope.
+    pub fn with_credential_provider(
+        mut self,
+        credential_provider: Arc<dyn CredentialProvider>,
+        credential_scope: CredentialScope,
+    ) -> Self {
+        self.credential_provider = Some(credential_provider);
+        self.credential_scope = credential_scope;
+        self
     }
 
-    /// operator initializes the operator with the parsed URL and object storage.
+    /// object_storage fills the access key of the object storage by the credential provider
+    /// if the request does not carry it and the bucket and the endpoint are in the scope of
+    /// the credential. GCS is authenticated by the credential file, so it is not filled.
+    async fn object_storage(
+        &self,
+        bucket: &str,
+        object_storage: Option<common::v2::ObjectStorage>,
+    ) -> ClientResult<Option<common::v2::ObjectStorage>> {
+        let Some(credential_provider) = self.credential_provider.as_ref() else {
+            return Ok(object_storage);
+        };
+
+        if self.scheme == Scheme::GCS {
+            return Ok(object_storage);
+        }
+
+        let mut object_storage = object_storage.unwrap_or_default();
+        if object_storage.access_key_id.is_some() && object_storage.access_key_secret.is_some() {
+            return Ok(Some(object_storage));
+        }
+
+        if !self
+            .credential_scope
+            .contains(bucket, object_storage.endpoint.as_deref())
+        {
+            debug!(
+                "bucket {} and endpoint {:?} are not in the credential scope",
+                bucket, object_storage.endpoint
+            );
+            return Ok(Some(object_storage));
+        }
+
+        if let Some(credential) = credential_provider.credential().await? {
+            object_storage.access_key_id = Some(credential.access_key_id);
+            object_storage.access_key_secret = Some(credential.access_key_secret);
+            object_storage.session_token = credential.session_token;
+        }
+
+        Ok(Some(object_storage))
+    }
+
+    /// operator_key returns the key of the cached operator, every component is hashed with
+    /// the length prefix, so the components can not be shifted into each other and the
+    /// secrets are not kept in the key.
+    fn operator_key(
+        &self,
+        parsed_url: &super::object_storage::ParsedURL,
+        object_storage: &common::v2::ObjectStorage,
+    ) -> String {
+        let scheme = self.scheme.to_string();
+        let mut hasher = Hasher::new(Algorithm::Sha256);
+        for field in [
+            Some(scheme.as_str()),
+            Some(parsed_url.bucket.as_str()),
+            object_storage.endpoint.as_deref(),
+            object_storage.region.as_deref(),
+            object_storage.access_key_id.as_deref(),
+            object_storage.access_key_secret.as_deref(),
+            object_storage.session_token.as_deref(),
+            object_storage.credential_path.as_deref(),
+            object_storage.predefined_acl.as_deref(),
+        ] {
+            // The unset field is distinguished from the empty field by the tag.
+            match field {
+                Some(field) => {
+                    hasher.update(&[1]);
+                    hasher.update(&(field.len() as u64).to_be_bytes());
+                    hasher.update(field.as_bytes());
+                }
+                None => hasher.update(&[0]),
+            }
+        }
+
+        hasher.finalize().encoded().to_string()
+    }
+
+    /// operator returns the operator with the parsed URL and object storage, the operator is
+    /// reused if it is cached, and the timeout is layered for each request.
     pub fn operator(
         &self,
         parsed_url: &super::object_storage::ParsedURL,
@@ -211,14 +328,24 @@ impl ObjectStorage {
             })));
         };
 
-        match self.scheme {
-            Scheme::S3 => self.s3_operator(parsed_url, object_storage, timeout),
-            Scheme::GCS => self.gcs_operator(parsed_url, object_storage, timeout),
-            Scheme::ABS => self.abs_operator(parsed_url, object_storage, timeout),
-            Scheme::OSS => self.oss_operator(parsed_url, object_storage, timeout),
-            Scheme::OBS => self.obs_operator(parsed_url, object_storage, timeout),
-            Scheme::COS => self.cos_operator(parsed_url, object_storage, timeout),
+        let key = self.operator_key(parsed_url, &object_storage);
+        if let Some(operator) = self.operators.lock().unwrap().get(&key) {
+            return Ok(operator
+                .clone()
+                .layer(TimeoutLayer::new().with_timeout(timeout)));
         }
+
+        let operator = match self.scheme {
+            Scheme::S3 => self.s3_operator(parsed_url, object_storage),
+            Scheme::GCS => self.gcs_operator(parsed_url, object_storage),
+            Scheme::ABS => self.abs_operator(parsed_url, object_storage),
+            Scheme::OSS => self.oss_operator(parsed_url, object_storage),
+            Scheme::OBS => self.obs_operator(parsed_url, object_storage),
+            Scheme::COS => self.cos_operator(parsed_url, object_storage),
+        }?;
+
+        self.operators.lock().unwrap().put(key, operator.clone());
+        Ok(operator.layer(TimeoutLayer::new().with_timeout(timeout)))
     }
 
     /// s3_operator initializes the S3 operator with the parsed URL and object storage.
@@ -226,7 +353,6 @@ impl ObjectStorage {
         &self,
         parsed_url: &super::object_storage::ParsedURL,
         object_storage: common::v2::ObjectStorage,
-        timeout: Duration,
     ) -> ClientResult<Operator> {
         // S3 requires the access key id and the secret access key.
         let (Some(access_key_id), Some(access_key_secret), Some(region)) = (
@@ -268,9 +394,7 @@ impl ObjectStorage {
             builder = builder.session_token(session_token);
         }
 
-        Ok(Operator::new(builder)?
-            .finish()
-            .layer(TimeoutLayer::new().with_timeout(timeout)))
+        Ok(Operator::new(builder)?.finish())
     }
 
     /// gcs_operator initializes the GCS operator with the parsed URL and object storage.
@@ -278,7 +402,6 @@ impl ObjectStorage {
         &self,
         parsed_url: &super::object_storage::ParsedURL,
         object_storage: common::v2::ObjectStorage,
-        timeout: Duration,
     ) -> ClientResult<Operator> {
         // Initialize the GCS operator with the object storage.
         let mut builder = opendal::services::Gcs::default();
@@ -302,9 +425,7 @@ impl ObjectStorage {
             builder = builder.predefined_acl(predefined_acl);
         }
 
-        Ok(Operator::new(builder)?
-            .finish()
-            .layer(TimeoutLayer::new().with_timeout(timeout)))
+        Ok(Operator::new(builder)?.finish())
     }
 
     /// abs_operator initializes the ABS operator with the parsed URL and object storage.
@@ -312,7 +433,6 @@ impl ObjectStorage {
         &self,
         parsed_url: &super::object_storage::ParsedURL,
         object_storage: common::v2::ObjectStorage,
-        timeout: Duration,
     ) -> ClientResult<Operator> {
         // ABS requires the account name and the account key.
         let (Some(access_key_id), Some(access_key_secret), Some(endpoint)) = (
@@ -344,9 +464,7 @@ impl ObjectStorage {
             .container(&parsed_url.bucket)
             .endpoint(endpoint);
 
-        Ok(Operator::new(builder)?
-            .finish()
-            .layer(TimeoutLayer::new().with_timeout(timeout)))
+        Ok(Operator::new(builder)?.finish())
     }
 
     /// oss_operator initializes the OSS operator with the parsed URL and object storage.
@@ -354,7 +472,6 @@ impl ObjectStorage {
         &self,
         parsed_url: &super::object_storage::ParsedURL,
         object_storage: common::v2::ObjectStorage,
-        timeout: Duration,
     ) -> ClientResult<Operator> {
         // OSS requires the access key id, access key secret, and endpoint.
         let (Some(access_key_id), Some(access_key_secret), Some(endpoint)) = (
@@ -387,9 +504,7 @@ impl ObjectStorage {
             .root("/")
             .bucket(&parsed_url.bucket);
 
-        Ok(Operator::new(builder)?
-            .finish()
-            .layer(TimeoutLayer::new().with_timeout(timeout)))
+        Ok(Operator::new(builder)?.finish())
     }
 
     /// obs_operator initializes the OBS operator with the parsed URL and object storage.
@@ -397,7 +512,6 @@ impl ObjectStorage {
         &self,
         parsed_url: &super::object_storage::ParsedURL,
         object_storage: common::v2::ObjectStorage,
-        timeout: Duration,
     ) -> ClientResult<Operator> {
         // OBS requires the endpoint, access key id, and access key secret.
         let (Some(access_key_id), Some(access_key_secret), Some(endpoint)) = (
@@ -429,9 +543,7 @@ impl ObjectStorage {
             .http_client(HttpClient::with(self.client.clone()))
             .bucket(&parsed_url.bucket);
 
-        Ok(Operator::new(builder)?
-            .finish()
-            .layer(TimeoutLayer::new().with_timeout(timeout)))
+        Ok(Operator::new(builder)?.finish())
     }
 
     /// cos_operator initializes the COS operator with the parsed URL and object storage.
@@ -439,7 +551,6 @@ impl ObjectStorage {
         &self,
         parsed_url: &super::object_storage::ParsedURL,
         object_storage: common::v2::ObjectStorage,
-        timeout: Duration,
     ) -> ClientResult<Operator> {
         // COS requires the access key id, the access key secret, and the endpoint.
         let (Some(access_key_id), Some(access_key_secret), Some(endpoint)) = (
@@ -471,9 +582,7 @@ impl ObjectStorage {
             .http_client(HttpClient::with(self.client.clone()))
             .bucket(&parsed_url.bucket);
 
-        Ok(Operator::new(builder)?
-            .finish()
-            .layer(TimeoutLayer::new().with_timeout(timeout)))
+        Ok(Operator::new(builder)?.finish())
     }
 }
 
@@ -506,7 +615,10 @@ impl crate::Backend for ObjectStorage {
         })?;
 
         // Initialize the operator with the parsed URL, object storage, and timeout.
-        let operator = self.operator(&parsed_url, request.object_storage, request.timeout)?;
+        let object_storage = self
+            .object_storage(&parsed_url.bucket, request.object_storage)
+            .await?;
+        let operator = self.operator(&parsed_url, object_storage, request.timeout)?;
 
         // Get the entries if url point to a directory.
         let entries = if parsed_url.is_dir() {
@@ -594,8 +706,11 @@ impl crate::Backend for ObjectStorage {
         })?;
 
         // Initialize the operator with the parsed URL, object storage, and timeout.
+        let object_storage = self
+            .object_storage(&parsed_url.bucket, request.object_storage)
+            .await?;
         let operator_reader = self
-            .operator(&parsed_url, request.object_storage, request.timeout)?
+            .operator(&parsed_url, object_storage, request.timeout)?
             .reader(&parsed_url.key)
             .await
             .map_err(|err| {
@@ -646,6 +761,138 @@ impl crate::Backend for ObjectStorage {
             error_message: None,
         })
     }
+
+    /// list lists the objects with the prefix of the directory by pages.
+    #[instrument(skip_all)]
+    async fn list(&self, request: super::ListRequest) -> ClientResult<super::ListResponse> {
+        debug!(
+            "list request {} {}: {:?}",
+            request.task_id, request.url, request.continuation_token
+        );
+
+        // Parse the URL and convert it to a ParsedURL for create the ObjectStorage operator.
+        let url: Url = request
+            .url
+            .parse()
+            .map_err(|_| ClientError::InvalidURI(request.url.clone()))?;
+        let parsed_url: super::object_storage::ParsedURL = url.try_into().inspect_err(|err| {
+            error!(
+                "parse list request url failed {} {}: {}",
+                request.task_id, request.url, err
+            );
+        })?;
+
+        // Only the directory can be listed.
+        if !parsed_url.is_dir() {
+            return Err(ClientError::InvalidURI(request.url.clone()));
+        }
+
+        // Initialize the operator with the parsed URL, object storage, and timeout.
+        let object_storage = self
+            .object_storage(&parsed_url.bucket, request.object_storage.clone())
+            .await?;
+        let operator = self.operator(&parsed_url, object_storage, request.timeout)?;
+
+        let response = super::list_by_operator(&operator, &parsed_url.key, &request, |path| {
+            parsed_url.make_url_by_entry_path(path)
+        })
+        .await
+        .map_err(|err| {
+            error!(
+                "list request failed {} {}: {}",
+                request.task_id, request.url, err
+            );
+            ClientError::BackendError(Box::new(BackendError {
+                message: err.to_string(),
+                status_code: None,
+                header: None,
+            }))
+        })?;
+
+        debug!(
+            "list response {} {}: {}",
+            request.task_id,
+            request.url,
+            response.entries.len()
+        );
+
+        Ok(response)
+    }
+
+    /// put writes the content to the object, the content is uploaded by multipart in chunks
+    /// concurrently.
+    #[instrument(skip_all)]
+    async fn put(
+        &self,
+        request: super::PutRequest,
+        mut reader: super::Body,
+    ) -> ClientResult<super::PutResponse> {
+        debug!(
+            "put request {} {}: {:?}",
+            request.task_id, request.url, request.http_header
+        );
+
+        // Parse the URL and convert it to a ParsedURL for create the ObjectStorage operator.
+        let url: Url = request
+            .url
+            .parse()
+            .map_err(|_| ClientError::InvalidURI(request.url.clone()))?;
+        let parsed_url: super::object_storage::ParsedURL = url.try_into().inspect_err(|err| {
+            error!(
+                "parse put request url failed {} {}: {}",
+                request.task_id, request.url, err
+            );
+        })?;
+
+        // The content can not be written to a directory.
+        if parsed_url.is_dir() {
+            return Err(ClientError::InvalidURI(request.url.clone()));
+        }
+
+        // Initialize the operator with the parsed URL, object storage, and timeout.
+        let object_storage = self
+            .object_storage(&parsed_url.bucket, request.object_storage)
+            .await?;
+        let writer = self
+            .operator(&parsed_url, object_storage, request.timeout)?
+            .writer_with(&parsed_url.key)
+            .chunk(super::PUT_CHUNK_SIZE)
+            .concurrent(super::PUT_CONCURRENCY)
+            .await
+            .map_err(|err| {
+                error!(
+                    "put request failed {} {}: {}",
+                    request.task_id, request.url, err
+                );
+                ClientError::BackendError(Box::new(BackendError {
+                    message: err.to_string(),
+                    status_code: None,
+                    header: None,
+                }))
+            })?;
+
+        let content_length = super::write_by_operator(&mut reader, writer, request.content_length)
+            .await
+            .inspect_err(|err| {
+                error!(
+                    "put request failed {} {}: {}",
+                    request.task_id, request.url, err
+                );
+            })?;
+
+        debug!(
+            "put response {} {}: {}",
+            request.task_id, request.url, content_length
+        );
+
+        Ok(super::PutResponse {
+            success: true,
+            content_length,
+            http_header: None,
+            http_status_code: None,
+            error_message: None,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -1274,4 +1521,172 @@ mod tests {
             assert_eq!(result.unwrap_err().to_string(), error_message);
         }
     }
+
+    #[test]
+    fn should_reuse_cached_operator() {
+        let object_storage = ObjectStorage::new(Scheme::S3).unwrap();
+        let new_object_storage_info = |access_key_id: &str| ObjectStorageInfo {
+            access_key_id: Some(access_key_id.into()),
+            access_key_secret: Some("access_key_secret".into()),
+            region: Some("test-region".into()),
+            ..Default::default()
+        };
+
+        for bucket in ["test-bucket", "test-bucket", "other-bucket"] {
+            let url: Url = format!("s3://{}/file", bucket).parse().unwrap();
+            let parsed_url: ParsedURL = url.try_into().unwrap();
+            object_storage
+                .operator(
+                    &parsed_url,
+                    Some(new_object_storage_info("access_key_id")),
+                    Duration::from_secs(3),
+                )
+                .unwrap();
+        }
+        assert_eq!(object_storage.operators.lock().unwrap().len(), 2);
+
+        // The operator of the other credential is not shared.
+        let url: Url = "s3://test-bucket/file".parse().unwrap();
+        let parsed_url: ParsedURL = url.try_into().unwrap();
+        object_storage
+            .operator(
+                &parsed_url,
+                Some(new_object_storage_info("other_access_key_id")),
+                Duration::from_secs(3),
+            )
+            .unwrap();
+        assert_eq!(object_storage.operators.lock().unwrap().len(), 3);
+
+        // The components are not shifted into each other.
+        let url: Url = "s3://bucket/file".parse().unwrap();
+        let parsed_url: ParsedURL = url.try_into().unwrap();
+        let shifted = |region: Option<&str>, endpoint: Option<&str>| {
+            object_storage.operator_key(
+                &parsed_url,
+                &ObjectStorageInfo {
+                    region: region.map(Into::into),
+                    endpoint: endpoint.map(Into::into),
+                    ..Default::default()
+                },
+            )
+        };
+        assert_ne!(shifted(Some("a/b"), None), shifted(Some("b"), Some("a")));
+        assert_ne!(shifted(Some(""), None), shifted(None, None));
+    }
+
+    #[tokio::test]
+    async fn should_fill_object_storage_by_credential_provider() {
+        struct StaticProvider;
+
+        #[tonic::async_trait]
+        impl CredentialProvider for StaticProvider {
+            fn name(&self) -> &str {
+                "static"
+            }
+
+            async fn credential(&self) -> ClientResult<Option<crate::credential::Credential>> {
+                Ok(Some(crate::credential::Credential {
+                    access_key_id: "provided_access_key_id".into(),
+                    access_key_secret: "provided_access_key_secret".into(),
+                    session_token: Some("provided_session_token".into()),
+                    expiration: None,
+                }))
+            }
+        }
+
+        // The credential of the provider is used if the request does not carry it.
+        let object_storage = ObjectStorage::new(Scheme::S3)
+            .unwrap()
+            .with_credential_provider(
+                Arc::new(StaticProvider),
+                CredentialScope::new(
+                    vec!["bucket".to_string()],
+                    vec!["https://s3.example.com".to_string()],
+                ),
+            );
+        let object_storage_info = object_storage
+            .object_storage(
+                "bucket",
+                Some(ObjectStorageInfo {
+                    region: Some("test-region".into()),
+                    ..Default::default()
+                }),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            object_storage_info.access_key_id.as_deref(),
+            Some("provided_access_key_id")
+        );
+        assert_eq!(
+            object_storage_info.session_token.as_deref(),
+            Some("provided_session_token")
+        );
+        assert_eq!(object_storage_info.region.as_deref(), Some("test-region"));
+
+        // The credential of the provider is used for the allowed endpoint.
+        let object_storage_info = object_storage
+            .object_storage(
+                "bucket",
+                Some(ObjectStorageInfo {
+                    endpoint: Some("https://s3.example.com/".into()),
+                    ..Default::default()
+                }),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            object_storage_info.access_key_id.as_deref(),
+            Some("provided_access_key_id")
+        );
+
+        // The credential of the provider is not used out of the scope.
+        for (bucket, endpoint) in [
+            ("other-bucket", None),
+            ("bucket", Some("https://attacker.example.com")),
+        ] {
+            let object_storage_info = object_storage
+                .object_storage(
+                    bucket,
+                    Some(ObjectStorageInfo {
+                        endpoint: endpoint.map(Into::into),
+                        ..Default::default()
+                    }),
+                )
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(object_storage_info.access_key_id.is_none());
+            assert!(object_storage_info.session_token.is_none());
+        }
+
+        // The credential of the request has higher priority.
+        let object_storage_info = object_storage
+            .object_storage(
+                "bucket",
+                Some(ObjectStorageInfo {
+                    access_key_id: Some("access_key_id".into()),
+                    access_key_secret: Some("access_key_secret".into()),
+                    ..Default::default()
+                }),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            object_storage_info.access_key_id.as_deref(),
+            Some("access_key_id")
+        );
+        assert!(object_storage_info.session_token.is_none());
+
+        // The object storage is required if there is no credential provider.
+        assert!(ObjectStorage::new(Scheme::S3)
+            .unwrap()
+            .object_storage("bucket", None)
+            .await
+            .unwrap()
+            .is_none());
+    }
 }
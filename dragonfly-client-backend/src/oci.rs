@@ -0,0 +1,1234 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::http::{HTTP, HTTPS_SCHEME};
+use bytes::Bytes;
+use dragonfly_client_core::error::BackendError;
+use dragonfly_client_core::{Error as ClientError, Result as ClientResult};
+use dragonfly_client_util::digest::{Algorithm, Hasher, SEPARATOR};
+use futures::TryStreamExt;
+use reqwest::header::{
+    HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, HOST, RANGE,
+    WWW_AUTHENTICATE,
+};
+use reqwest::{Method, StatusCode};
+use rustls_pki_types::CertificateDer;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Cursor, Error as IOError, ErrorKind};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio_util::io::StreamReader;
+use tracing::{debug, error, info, instrument, warn};
+use url::Url;
+
+/// OCI_SCHEME is the scheme of the OCI registry.
+pub const OCI_SCHEME: &str = "oci";
+
+/// DEFAULT_TAG is the default tag of the image.
+const DEFAULT_TAG: &str = "latest";
+
+/// DEFAULT_TOKEN_EXPIRES_IN is the default lifetime of the bearer token, if the token
+/// service does not return the expires_in.
+const DEFAULT_TOKEN_EXPIRES_IN: Duration = Duration::from_secs(60);
+
+/// DOCKER_CONTENT_DIGEST is the header of the digest of the content returned by the registry.
+const DOCKER_CONTENT_DIGEST: &str = "docker-content-digest";
+
+/// MANIFEST_MEDIA_TYPES are the media types of the manifests accepted from the registry.
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.oci.image.index.v1+json, application/vnd.docker.distribution.manifest.list.v2+json, application/vnd.oci.image.manifest.v1+json, application/vnd.docker.distribution.manifest.v2+json";
+
+/// MAX_MANIFEST_DEPTH is the max depth of the manifests to resolve, the image index refers to
+/// the platform-specific manifests.
+const MAX_MANIFEST_DEPTH: usize = 2;
+
+/// Reference is the reference of the manifest in the repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reference {
+    /// Tag is the tag of the manifest.
+    Tag(String),
+
+    /// Digest is the digest of the manifest.
+    Digest(String),
+}
+
+/// Reference implements the Display.
+impl fmt::Display for Reference {
+    /// fmt formats the value using the given formatter.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reference::Tag(tag) => write!(f, "{}", tag),
+            Reference::Digest(digest) => write!(f, "{}", digest),
+        }
+    }
+}
+
+/// Image is the image parsed from the url of the OCI registry. The url is formatted as
+/// `oci://<registry>/<repository>[:<tag>|@<digest>][/][<digest>]`:
+///
+/// - `oci://registry/repo:tag` is the manifest of the image for the platform.
+/// - `oci://registry/repo@sha256:...` is the blob referenced by the digest.
+/// - `oci://registry/repo:tag/` is the directory of the image, whose entries are the config
+///   and the layers of the manifest for the platform.
+/// - `oci://registry/repo:tag/sha256:...` is the blob of the entry in the directory.
+///
+/// The query `plain-http=true` accesses the registry by http instead of https, and the query
+/// `platform=<os>/<arch>[/<variant>]` selects the manifest of the platform from the image
+/// index, the platform of the host is selected by default.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    /// endpoint is the endpoint of the registry, such as `https://registry:5000`.
+    pub endpoint: String,
+
+    /// repository is the repository of the image.
+    pub repository: String,
+
+    /// reference is the reference of the manifest.
+    pub reference: Reference,
+
+    /// blob is the digest of the blob, None means the url points to the manifest.
+    pub blob: Option<String>,
+
+    /// platform is the platform of the manifest selected from the image index.
+    pub platform: String,
+}
+
+/// Image implements the image of the OCI registry.
+impl Image {
+    /// parse parses the image from the url.
+    pub fn parse(url: &Url) -> ClientResult<Self> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| ClientError::InvalidURI(url.to_string()))?;
+        let address = match url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        };
+
+        let query = url.query_pairs().collect::<HashMap<_, _>>();
+        let endpoint = match query.get("plain-http").map(|value| value.as_ref()) {
+            Some("true") => format!("http://{}", address),
+            _ => format!("{}://{}", HTTPS_SCHEME, address),
+        };
+        let platform = query
+            .get("platform")
+            .map(|platform| platform.to_string())
+            .unwrap_or_else(default_platform);
+
+        let is_dir = url.path().ends_with('/');
+        let path = url.path().trim_matches('/');
+        if path.is_empty() {
+            return Err(ClientError::InvalidURI(url.to_string()));
+        }
+
+        // The last segment of the path is the blob of the entry in the directory.
+        let (name, mut blob) = match path.rsplit_once('/') {
+            Some((name, last)) if !is_dir && is_digest(last) => (name, Some(last.to_string())),
+            _ => (path, None),
+        };
+
+        let (repository, reference) = match name.split_once('@') {
+            Some((repository, digest)) => {
+                if !is_digest(digest) {
+                    return Err(ClientError::InvalidURI(url.to_string()));
+                }
+
+                // The digest of the file url refers to the blob.
+                if !is_dir && blob.is_none() {
+                    blob = Some(digest.to_string());
+                }
+
+                (repository, Reference::Digest(digest.to_string()))
+            }
+            None => match name.rsplit_once(':') {
+                Some((repository, tag)) if !tag.contains('/') => {
+                    (repository, Reference::Tag(tag.to_string()))
+                }
+                _ => (name, Reference::Tag(DEFAULT_TAG.to_string())),
+            },
+        };
+
+        if repository.is_empty() {
+            return Err(ClientError::InvalidURI(url.to_string()));
+        }
+
+        Ok(Self {
+            endpoint,
+            repository: repository.to_string(),
+            reference,
+            blob,
+            platform,
+        })
+    }
+}
+
+/// blob_digest returns the digest of the blob if the url points to the blob in the OCI
+/// registry, the content of the blob is addressed by the digest.
+pub fn blob_digest(url: &Url) -> Option<String> {
+    if url.scheme() != OCI_SCHEME {
+        return None;
+    }
+
+    Image::parse(url).ok().and_then(|image| image.blob)
+}
+
+/// is_digest returns whether the value is the digest of the content, such as `sha256:...`.
+fn is_digest(value: &str) -> bool {
+    match value.split_once(SEPARATOR) {
+        Some((algorithm, encoded)) => {
+            matches!(algorithm, "sha256" | "sha512")
+                && !encoded.is_empty()
+                && encoded.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => false,
+    }
+}
+
+/// default_platform returns the platform of the host in the format of the OCI image index.
+fn default_platform() -> String {
+    let architecture = match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "386",
+        architecture => architecture,
+    };
+
+    format!("linux/{}", architecture)
+}
+
+/// Platform is the platform of the manifest in the image index.
+#[derive(Debug, Deserialize)]
+struct Platform {
+    /// architecture is the cpu architecture of the platform.
+    architecture: String,
+
+    /// os is the operating system of the platform.
+    os: String,
+
+    /// variant is the variant of the cpu architecture.
+    variant: Option<String>,
+}
+
+/// Platform implements the Display.
+impl fmt::Display for Platform {
+    /// fmt formats the value using the given formatter.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.variant.as_ref() {
+            Some(variant) => write!(f, "{}/{}/{}", self.os, self.architecture, variant),
+            None => write!(f, "{}/{}", self.os, self.architecture),
+        }
+    }
+}
+
+/// Descriptor is the descriptor of the content in the manifest.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    /// digest is the digest of the content.
+    digest: String,
+
+    /// size is the size of the content.
+    size: u64,
+
+    /// platform is the platform of the manifest in the image index.
+    platform: Option<Platform>,
+}
+
+/// Manifest is the image manifest or the image index.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    /// media_type is the media type of the manifest.
+    media_type: Option<String>,
+
+    /// config is the config of the image manifest.
+    config: Option<Descriptor>,
+
+    /// layers are the layers of the image manifest.
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+
+    /// manifests are the platform-specific manifests of the image index.
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+}
+
+/// ResolvedManifest is the manifest resolved for the platform.
+struct ResolvedManifest {
+    /// manifest is the parsed manifest.
+    manifest: Manifest,
+
+    /// content is the raw content of the manifest.
+    content: Bytes,
+
+    /// digest is the digest of the manifest.
+    digest: String,
+}
+
+/// Token is the bearer token issued by the token service of the registry.
+struct Token {
+    /// value is the value of the token.
+    value: String,
+
+    /// expires_at is the time when the token expires.
+    expires_at: Instant,
+}
+
+/// TokenResponse is the response of the token service.
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    /// token is the bearer token.
+    #[serde(default)]
+    token: String,
+
+    /// access_token is the bearer token for the OAuth 2.0 compatibility.
+    #[serde(default)]
+    access_token: String,
+
+    /// expires_in is the lifetime of the token in seconds.
+    expires_in: Option<u64>,
+}
+
+/// Context is the context of the request to the registry.
+struct Context {
+    /// header is the header forwarded to the registry.
+    header: HeaderMap,
+
+    /// authorization is the credential to request the token service or the registry.
+    authorization: Option<HeaderValue>,
+
+    /// timeout is the timeout of the request.
+    timeout: Duration,
+
+    /// client_cert is the client certificates for the request.
+    client_cert: Option<Vec<CertificateDer<'static>>>,
+}
+
+/// Context implements the context of the request.
+impl Context {
+    /// new returns the context from the header of the request, the authorization is taken
+    /// out of the header and the headers not applied to the registry are removed.
+    fn new(
+        header: Option<HeaderMap>,
+        timeout: Duration,
+        client_cert: Option<Vec<CertificateDer<'static>>>,
+    ) -> Self {
+        let mut header = header.unwrap_or_default();
+        let authorization = header.remove(AUTHORIZATION);
+        header.remove(HOST);
+        header.remove(RANGE);
+
+        Self {
+            header,
+            authorization,
+            timeout,
+            client_cert,
+        }
+    }
+}
+
+/// OCI is the backend of the OCI registry, it pulls the manifests and the blobs by the
+/// distribution api with the bearer token authentication.
+pub struct OCI {
+    /// scheme is the scheme of the OCI registry.
+    scheme: String,
+
+    /// http is the http backend to request the registry.
+    http: HTTP,
+
+    /// tokens are the bearer tokens cached by the repository and the credential.
+    tokens: Mutex<HashMap<String, Token>>,
+
+    /// allowed_token_hosts are the hosts of the token services which are allowed to receive
+    /// the credential besides the registry itself, such as `auth.docker.io`.
+    allowed_token_hosts: Vec<String>,
+}
+
+/// OCI implements the OCI registry backend.
+impl OCI {
+    /// new returns a new OCI backend.
+    pub fn new() -> ClientResult<Self> {
+        Ok(Self {
+            scheme: OCI_SCHEME.to_string(),
+            http: HTTP::new(HTTPS_SCHEME)?,
+            tokens: Mutex::new(HashMap::new()),
+            allowed_token_hosts: Vec::new(),
+        })
+    }
+
+    /// with_allowed_token_hosts sets the hosts of the token services which are allowed to
+    /// receive the credential, the host can be `host` or `host:port`.
+    pub fn with_allowed_token_hosts(mut self, allowed_token_hosts: Vec<String>) -> Self {
+        self.allowed_token_hosts = allowed_token_hosts;
+        self
+    }
+
+    /// is_trusted_realm returns whether the credential can be forwarded to the token service
+    /// of the realm. The credential is never forwarded when the realm downgrades the https
+    /// scheme of the registry, and is only forwarded to the registry itself or the allowed
+    /// token hosts over https.
+    fn is_trusted_realm(&self, image: &Image, realm: &Url) -> bool {
+        let Ok(endpoint) = Url::parse(&image.endpoint) else {
+            return false;
+        };
+
+        if endpoint.scheme() == HTTPS_SCHEME && realm.scheme() != HTTPS_SCHEME {
+            return false;
+        }
+
+        let Some(host) = realm.host_str() else {
+            return false;
+        };
+
+        if endpoint.host_str() == Some(host)
+            && endpoint.port_or_known_default() == realm.port_or_known_default()
+        {
+            return true;
+        }
+
+        // The credential is sent to the other token hosts only over https.
+        if realm.scheme() != HTTPS_SCHEME {
+            return false;
+        }
+
+        let address = match realm.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        };
+
+        self.allowed_token_hosts.iter().any(|allowed_token_host| {
+            allowed_token_host.eq_ignore_ascii_case(host)
+                || allowed_token_host.eq_ignore_ascii_case(&address)
+        })
+    }
+
+    /// send sends the request to the distribution api of the repository. If the registry
+    /// challenges the request by the bearer scheme, the token is requested from the token
+    /// service and the request is retried with the token.
+    async fn send(
+        &self,
+        image: &Image,
+        method: Method,
+        path: &str,
+        header: HeaderMap,
+        context: &Context,
+    ) -> ClientResult<reqwest::Response> {
+        let url = format!("{}/v2/{}/{}", image.endpoint, image.repository, path);
+        let key = format!(
+            "{} {} {:?}",
+            image.endpoint, image.repository, context.authorization
+        );
+        let client = self.http.client(context.client_cert.clone())?;
+
+        let mut request = client
+            .request(method.clone(), &url)
+            .headers(context.header.clone())
+            .headers(header.clone())
+            .timeout(context.timeout);
+        if let Some(token) = self.token(&key) {
+            request = request.bearer_auth(token);
+        } else if let Some(authorization) = context.authorization.clone() {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+
+        let response = request.send().await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        let Some(challenge) = response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|challenge| challenge.to_str().ok())
+            .and_then(parse_bearer_challenge)
+        else {
+            return Ok(response);
+        };
+
+        let token = self.request_token(image, &challenge, context).await?;
+        let response = client
+            .request(method, &url)
+            .headers(context.header.clone())
+            .headers(header)
+            .timeout(context.timeout)
+            .bearer_auth(&token.value)
+            .send()
+            .await?;
+
+        self.tokens.lock().unwrap().insert(key, token);
+        Ok(response)
+    }
+
+    /// token returns the cached token which is not expired.
+    fn token(&self, key: &str) -> Option<String> {
+        let mut tokens = self.tokens.lock().unwrap();
+        match tokens.get(key) {
+            Some(token) if token.expires_at > Instant::now() => Some(token.value.clone()),
+            Some(_) => {
+                tokens.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// request_token requests the bearer token from the token service of the challenge, the
+    /// credential of the request is only forwarded to the trusted token service, otherwise
+    /// the token is requested anonymously.
+    async fn request_token(
+        &self,
+        image: &Image,
+        challenge: &HashMap<String, String>,
+        context: &Context,
+    ) -> ClientResult<Token> {
+        let realm = challenge
+            .get("realm")
+            .ok_or_else(|| ClientError::Unknown("missing realm in challenge".to_string()))?;
+        let mut url = Url::parse(realm).map_err(|_| ClientError::InvalidURI(realm.clone()))?;
+        for key in ["service", "scope"] {
+            if let Some(value) = challenge.get(key) {
+                url.query_pairs_mut().append_pair(key, value);
+            }
+        }
+
+        let mut request = self
+            .http
+            .client(context.client_cert.clone())?
+            .get(url.as_str())
+            .timeout(context.timeout);
+        if let Some(authorization) = context.authorization.clone() {
+            if self.is_trusted_realm(image, &url) {
+                request = request.header(AUTHORIZATION, authorization);
+            } else {
+                warn!(
+                    "realm {} is not trusted by {}, request token without credential",
+                    realm, image.endpoint
+                );
+            }
+        }
+
+        let response = request.send().await?;
+        let status_code = response.status();
+        if !status_code.is_success() {
+            error!("request token from {} failed: {}", realm, status_code);
+            return Err(ClientError::BackendError(Box::new(BackendError {
+                message: format!("request token failed: {}", status_code),
+                status_code: Some(status_code),
+                header: Some(response.headers().clone()),
+            })));
+        }
+
+        let content = response.bytes().await?;
+        let response: TokenResponse = serde_json::from_slice(&content)
+            .map_err(|err| ClientError::Unknown(format!("invalid token response: {}", err)))?;
+        let value = if response.token.is_empty() {
+            response.access_token
+        } else {
+            response.token
+        };
+
+        Ok(Token {
+            value,
+            expires_at: Instant::now()
+                + response
+                    .expires_in
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_TOKEN_EXPIRES_IN),
+        })
+    }
+
+    /// resolve_manifest gets the manifest of the image, and resolves the manifest of the
+    /// platform if the manifest is the image index.
+    async fn resolve_manifest(
+        &self,
+        image: &Image,
+        context: &Context,
+    ) -> ClientResult<ResolvedManifest> {
+        let mut header = HeaderMap::new();
+        header.insert(ACCEPT, HeaderValue::from_static(MANIFEST_MEDIA_TYPES));
+
+        let mut reference = image.reference.clone();
+        for _ in 0..MAX_MANIFEST_DEPTH {
+            let response = self
+                .send(
+                    image,
+                    Method::GET,
+                    &format!("manifests/{}", reference),
+                    header.clone(),
+                    context,
+                )
+                .await?;
+
+            let status_code = response.status();
+            if !status_code.is_success() {
+                return Err(ClientError::BackendError(Box::new(BackendError {
+                    message: format!("get manifest {} failed: {}", reference, status_code),
+                    status_code: Some(status_code),
+                    header: Some(response.headers().clone()),
+                })));
+            }
+
+            // The manifest referenced by the digest is verified by the algorithm of the
+            // digest, otherwise the digest of the manifest is reported by sha256.
+            let content = response.bytes().await?;
+            let algorithm = match &reference {
+                Reference::Digest(expected_digest) => expected_digest
+                    .split_once(SEPARATOR)
+                    .and_then(|(algorithm, _)| algorithm.parse::<Algorithm>().ok())
+                    .filter(|algorithm| matches!(algorithm, Algorithm::Sha256 | Algorithm::Sha512))
+                    .ok_or_else(|| {
+                        ClientError::Unsupported(format!(
+                            "digest {} of {}",
+                            expected_digest, image.repository
+                        ))
+                    })?,
+                Reference::Tag(_) => Algorithm::Sha256,
+            };
+
+            let mut hasher = Hasher::new(algorithm);
+            hasher.update(&content);
+            let digest = hasher.finalize().to_string();
+            if let Reference::Digest(expected_digest) = &reference {
+                if *expected_digest != digest {
+                    return Err(ClientError::DigestMismatch(expected_digest.clone(), digest));
+                }
+            }
+
+            let manifest: Manifest = serde_json::from_slice(&content)
+                .map_err(|err| ClientError::Unknown(format!("invalid manifest: {}", err)))?;
+            if manifest.manifests.is_empty() {
+                debug!(
+                    "resolve manifest {} of {} to {}",
+                    image.reference, image.repository, digest
+                );
+
+                return Ok(ResolvedManifest {
+                    manifest,
+                    content,
+                    digest,
+                });
+            }
+
+            // Select the manifest of the platform from the image index.
+            let descriptor = manifest
+                .manifests
+                .iter()
+                .find(|descriptor| {
+                    descriptor
+                        .platform
+                        .as_ref()
+                        .is_some_and(|platform| platform.to_string() == image.platform)
+                })
+                .ok_or_else(|| {
+                    ClientError::Unsupported(format!(
+                        "platform {} of {}:{}",
+                        image.platform, image.repository, image.reference
+                    ))
+                })?;
+
+            reference = Reference::Digest(descriptor.digest.clone());
+        }
+
+        Err(ClientError::Unsupported(format!(
+            "nested image index of {}:{}",
+            image.repository, image.reference
+        )))
+    }
+}
+
+/// parse_bearer_challenge parses the parameters of the bearer challenge in the
+/// www-authenticate header, such as `Bearer realm="...",service="...",scope="..."`.
+fn parse_bearer_challenge(challenge: &str) -> Option<HashMap<String, String>> {
+    let (scheme, parameters) = challenge.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+
+    let mut result = HashMap::new();
+    let mut rest = parameters.trim();
+    while !rest.is_empty() {
+        let (key, value) = rest.split_once('=')?;
+        let key = key.trim().to_lowercase();
+        let value = value.trim_start();
+
+        // The value is quoted or ends with the comma.
+        let (value, remaining) = match value.strip_prefix('"') {
+            Some(value) => {
+                let end = value.find('"')?;
+                (&value[..end], &value[end + 1..])
+            }
+            None => match value.find(',') {
+                Some(end) => (&value[..end], &value[end..]),
+                None => (value, ""),
+            },
+        };
+
+        result.insert(key, value.to_string());
+        rest = remaining.trim_start().trim_start_matches(',').trim_start();
+    }
+
+    Some(result)
+}
+
+/// Implement the Backend trait for OCI.
+#[tonic::async_trait]
+impl super::Backend for OCI {
+    /// scheme returns the scheme of the OCI backend.
+    fn scheme(&self) -> String {
+        self.scheme.clone()
+    }
+
+    /// head gets the header of the blob or the manifest, and the config and the layers of
+    /// the image as the entries if the url points to the directory.
+    #[instrument(skip_all)]
+    async fn head(&self, request: super::HeadRequest) -> ClientResult<super::HeadResponse> {
+        info!(
+            "head request {} {}: {:?}",
+            request.task_id, request.url, request.http_header
+        );
+
+        let url = Url::parse(request.url.as_ref())
+            .map_err(|_| ClientError::InvalidURI(request.url.clone()))?;
+        let image = Image::parse(&url)?;
+        let context = Context::new(request.http_header, request.timeout, request.client_cert);
+
+        // Head the blob to get the size of the blob.
+        if let Some(blob) = image.blob.as_ref() {
+            let response = self
+                .send(
+                    &image,
+                    Method::HEAD,
+                    &format!("blobs/{}", blob),
+                    HeaderMap::new(),
+                    &context,
+                )
+                .await
+                .inspect_err(|err| {
+                    error!(
+                        "head request failed {} {}: {}",
+                        request.task_id, request.url, err
+                    );
+                })?;
+
+            let header = response.headers().clone();
+            let status_code = response.status();
+            let content_length = header
+                .get(CONTENT_LENGTH)
+                .and_then(|content_length| content_length.to_str().ok())
+                .and_then(|content_length| content_length.parse::<u64>().ok());
+
+            info!(
+                "head response {} {}: {:?} {:?}",
+                request.task_id, request.url, status_code, content_length
+            );
+
+            return Ok(super::HeadResponse {
+                success: status_code.is_success(),
+                content_length,
+                http_header: Some(header),
+                http_status_code: Some(status_code),
+                entries: Vec::new(),
+                error_message: Some(status_code.to_string()),
+            });
+        }
+
+        let resolved = self
+            .resolve_manifest(&image, &context)
+            .await
+            .inspect_err(|err| {
+                error!(
+                    "resolve manifest failed {} {}: {}",
+                    request.task_id, request.url, err
+                );
+            })?;
+
+        // The config and the layers of the manifest are the entries of the directory.
+        let entries = if url.path().ends_with('/') {
+            resolved
+                .manifest
+                .config
+                .iter()
+                .chain(resolved.manifest.layers.iter())
+                .map(|descriptor| {
+                    let mut entry_url = url.clone();
+                    entry_url.set_path(&format!("{}{}", url.path(), descriptor.digest));
+                    super::DirEntry {
+                        url: entry_url.to_string(),
+                        content_length: descriptor.size as usize,
+                        is_dir: false,
+                    }
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut header = HeaderMap::new();
+        if let Some(media_type) = resolved.manifest.media_type.as_ref() {
+            if let Ok(media_type) = HeaderValue::from_str(media_type) {
+                header.insert(CONTENT_TYPE, media_type);
+            }
+        }
+        if let Ok(digest) = HeaderValue::from_str(&resolved.digest) {
+            header.insert(DOCKER_CONTENT_DIGEST, digest);
+        }
+
+        info!(
+            "head response {} {}: {} {}",
+            request.task_id,
+            request.url,
+            resolved.digest,
+            resolved.content.len()
+        );
+
+        Ok(super::HeadResponse {
+            success: true,
+            content_length: Some(resolved.content.len() as u64),
+            http_header: Some(header),
+            http_status_code: Some(StatusCode::OK),
+            entries,
+            error_message: None,
+        })
+    }
+
+    /// get gets the content of the blob or the manifest.
+    #[instrument(skip_all)]
+    async fn get(
+        &self,
+        request: super::GetRequest,
+    ) -> ClientResult<super::GetResponse<super::Body>> {
+        info!(
+            "get request {} {}: {:?}",
+            request.piece_id, request.url, request.http_header
+        );
+
+        let url = Url::parse(request.url.as_ref())
+            .map_err(|_| ClientError::InvalidURI(request.url.clone()))?;
+        let image = Image::parse(&url)?;
+        let context = Context::new(request.http_header, request.timeout, request.client_cert);
+
+        // Get the range of the blob.
+        if let Some(blob) = image.blob.as_ref() {
+            let mut header = HeaderMap::new();
+            if let Some(range) = request.range {
+                let range = format!(
+                    "bytes={}-{}",
+                    range.start,
+                    range.start + range.length.saturating_sub(1)
+                );
+                if let Ok(range) = HeaderValue::from_str(&range) {
+                    header.insert(RANGE, range);
+                }
+            }
+
+            let response = self
+                .send(
+                    &image,
+                    Method::GET,
+                    &format!("blobs/{}", blob),
+                    header,
+                    &context,
+                )
+                .await
+                .inspect_err(|err| {
+                    error!(
+                        "get request failed {} {}: {}",
+                        request.piece_id, request.url, err
+                    );
+                })?;
+
+            let header = response.headers().clone();
+            let status_code = response.status();
+            let reader = Box::new(StreamReader::new(
+                response
+                    .bytes_stream()
+                    .map_err(|err| IOError::new(ErrorKind::Other, err)),
+            ));
+
+            return Ok(super::GetResponse {
+                success: status_code.is_success(),
+                http_header: Some(header),
+                http_status_code: Some(status_code),
+                reader,
+                error_message: Some(status_code.to_string()),
+            });
+        }
+
+        let resolved = self
+            .resolve_manifest(&image, &context)
+            .await
+            .inspect_err(|err| {
+                error!(
+                    "resolve manifest failed {} {}: {}",
+                    request.piece_id, request.url, err
+                );
+            })?;
+
+        let content = match request.range {
+            Some(range) => {
+                let start = (range.start as usize).min(resolved.content.len());
+                let end = (range.start + range.length).min(resolved.content.len() as u64);
+                resolved.content.slice(start..end as usize)
+            }
+            None => resolved.content,
+        };
+
+        Ok(super::GetResponse {
+            success: true,
+            http_header: None,
+            http_status_code: Some(StatusCode::OK),
+            reader: Box::new(Cursor::new(content)),
+            error_message: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, GetRequest, HeadRequest};
+    use dragonfly_api::common::v2::Range;
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn sha256(content: &[u8]) -> String {
+        let mut hasher = Hasher::new(Algorithm::Sha256);
+        hasher.update(content);
+        hasher.finalize().to_string()
+    }
+
+    #[test]
+    fn should_parse_image() {
+        let digest = sha256(b"layer");
+        let cases = vec![
+            (
+                "oci://registry:5000/library/alpine:3.19".to_string(),
+                "https://registry:5000",
+                "library/alpine",
+                Reference::Tag("3.19".to_string()),
+                None,
+            ),
+            (
+                "oci://registry/alpine/?plain-http=true".to_string(),
+                "http://registry",
+                "alpine",
+                Reference::Tag(DEFAULT_TAG.to_string()),
+                None,
+            ),
+            (
+                format!("oci://registry/alpine@{}", digest),
+                "https://registry",
+                "alpine",
+                Reference::Digest(digest.clone()),
+                Some(digest.clone()),
+            ),
+            (
+                format!("oci://registry/alpine@{}/", digest),
+                "https://registry",
+                "alpine",
+                Reference::Digest(digest.clone()),
+                None,
+            ),
+            (
+                format!("oci://registry/library/alpine:3.19/{}", digest),
+                "https://registry",
+                "library/alpine",
+                Reference::Tag("3.19".to_string()),
+                Some(digest.clone()),
+            ),
+        ];
+
+        for (url, endpoint, repository, reference, blob) in cases {
+            let image = Image::parse(&Url::parse(&url).unwrap()).unwrap();
+            assert_eq!(image.endpoint, endpoint);
+            assert_eq!(image.repository, repository);
+            assert_eq!(image.reference, reference);
+            assert_eq!(image.blob, blob);
+            assert_eq!(image.platform, default_platform());
+        }
+
+        assert!(Image::parse(&Url::parse("oci://registry/").unwrap()).is_err());
+        assert!(Image::parse(&Url::parse("oci://registry/alpine@sha256:xyz").unwrap()).is_err());
+        assert_eq!(
+            blob_digest(&Url::parse(&format!("oci://registry/alpine@{}", digest)).unwrap()),
+            Some(digest.clone())
+        );
+        assert_eq!(
+            blob_digest(&Url::parse("oci://registry/alpine:3.19").unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn should_parse_bearer_challenge() {
+        let challenge = parse_bearer_challenge(
+            r#"Bearer realm="https://auth.example.com/token",service="registry",scope="repository:alpine:pull,push""#,
+        )
+        .unwrap();
+        assert_eq!(challenge["realm"], "https://auth.example.com/token");
+        assert_eq!(challenge["service"], "registry");
+        assert_eq!(challenge["scope"], "repository:alpine:pull,push");
+
+        assert!(parse_bearer_challenge(r#"Basic realm="registry""#).is_none());
+    }
+
+    #[test]
+    fn should_check_trusted_realm() {
+        let image = Image::parse(&Url::parse("oci://registry/alpine:3.19").unwrap()).unwrap();
+        let oci = OCI::new()
+            .unwrap()
+            .with_allowed_token_hosts(vec!["auth.example.com".to_string()]);
+
+        let cases = vec![
+            ("https://registry/token", true),
+            ("https://registry:443/token", true),
+            ("https://auth.example.com/token", true),
+            ("https://auth.example.com:8443/token", true),
+            ("http://registry/token", false),
+            ("http://auth.example.com/token", false),
+            ("https://registry:8443/token", false),
+            ("https://attacker.example.com/token", false),
+        ];
+
+        for (realm, trusted) in cases {
+            assert_eq!(
+                oci.is_trusted_realm(&image, &Url::parse(realm).unwrap()),
+                trusted,
+                "{}",
+                realm
+            );
+        }
+
+        let image =
+            Image::parse(&Url::parse("oci://registry/alpine/?plain-http=true").unwrap()).unwrap();
+        assert!(oci.is_trusted_realm(&image, &Url::parse("http://registry/token").unwrap()));
+        assert!(!oci.is_trusted_realm(
+            &image,
+            &Url::parse("http://auth.example.com:8080/token").unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_not_forward_credential_to_untrusted_realm() {
+        let server = MockServer::start().await;
+        let realm = MockServer::start().await;
+        let address = server.uri().trim_start_matches("http://").to_string();
+        let manifest = r#"{"schemaVersion":2,"config":{"digest":"sha256:0","size":0},"layers":[]}"#;
+
+        // The token service on the other host only issues the token without the credential.
+        Mock::given(method("GET"))
+            .and(path("/token"))
+            .and(header("authorization", "Basic secret"))
+            .respond_with(ResponseTemplate::new(403))
+            .with_priority(1)
+            .mount(&realm)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"token":"anonymous"}"#))
+            .with_priority(10)
+            .mount(&realm)
+            .await;
+        Mock::given(path("/v2/alpine/manifests/latest"))
+            .and(header("authorization", "Bearer anonymous"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(manifest))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(401).insert_header(
+                "www-authenticate",
+                format!(r#"Bearer realm="{}/token",service="registry""#, realm.uri()).as_str(),
+            ))
+            .with_priority(10)
+            .mount(&server)
+            .await;
+
+        let mut http_header = HeaderMap::new();
+        http_header.insert(AUTHORIZATION, HeaderValue::from_static("Basic secret"));
+        let response = OCI::new()
+            .unwrap()
+            .head(HeadRequest {
+                task_id: "test".to_string(),
+                url: format!("oci://{}/alpine/?plain-http=true", address),
+                http_header: Some(http_header),
+                timeout: Duration::from_secs(5),
+                client_cert: None,
+                object_storage: None,
+                hdfs: None,
+            })
+            .await
+            .unwrap();
+        assert!(response.success);
+        assert_eq!(response.content_length, Some(manifest.len() as u64));
+    }
+
+    #[tokio::test]
+    async fn should_verify_manifest_by_sha512() {
+        let server = MockServer::start().await;
+        let address = server.uri().trim_start_matches("http://").to_string();
+        let manifest = r#"{"schemaVersion":2,"config":{"digest":"sha256:0","size":0},"layers":[]}"#;
+
+        let mut hasher = Hasher::new(Algorithm::Sha512);
+        hasher.update(manifest.as_bytes());
+        let digest = hasher.finalize().to_string();
+        let mut hasher = Hasher::new(Algorithm::Sha512);
+        hasher.update(b"tampered");
+        let tampered_digest = hasher.finalize().to_string();
+
+        Mock::given(path(format!("/v2/alpine/manifests/{}", digest)))
+            .respond_with(ResponseTemplate::new(200).set_body_string(manifest))
+            .mount(&server)
+            .await;
+        Mock::given(path(format!("/v2/alpine/manifests/{}", tampered_digest)))
+            .respond_with(ResponseTemplate::new(200).set_body_string(manifest))
+            .mount(&server)
+            .await;
+
+        let oci = OCI::new().unwrap();
+        let head = |digest: String| HeadRequest {
+            task_id: "test".to_string(),
+            url: format!("oci://{}/alpine@{}/?plain-http=true", address, digest),
+            http_header: None,
+            timeout: Duration::from_secs(5),
+            client_cert: None,
+            object_storage: None,
+            hdfs: None,
+        };
+
+        let response = oci.head(head(digest)).await.unwrap();
+        assert!(response.success);
+        assert!(matches!(
+            oci.head(head(tampered_digest)).await,
+            Err(ClientError::DigestMismatch(_, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_head_and_get_image_by_token() {
+        let server = MockServer::start().await;
+        let address = server.uri().trim_start_matches("http://").to_string();
+
+        let layer = b"hello, world!".to_vec();
+        let config = br#"{"architecture":"arm64","os":"linux"}"#.to_vec();
+        let manifest = format!(
+            r#"{{"schemaVersion":2,"mediaType":"application/vnd.oci.image.manifest.v1+json","config":{{"digest":"{}","size":{}}},"layers":[{{"digest":"{}","size":{}}}]}}"#,
+            sha256(&config),
+            config.len(),
+            sha256(&layer),
+            layer.len()
+        );
+        let index = format!(
+            r#"{{"schemaVersion":2,"mediaType":"application/vnd.oci.image.index.v1+json","manifests":[{{"digest":"{}","size":2,"platform":{{"architecture":"amd64","os":"linux"}}}},{{"digest":"{}","size":{},"platform":{{"architecture":"arm64","os":"linux","variant":"v8"}}}}]}}"#,
+            sha256(b"{}"),
+            sha256(manifest.as_bytes()),
+            manifest.len()
+        );
+
+        // The registry challenges the requests without the token.
+        Mock::given(method("GET"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"token":"secret"}"#))
+            .mount(&server)
+            .await;
+        Mock::given(path("/v2/library/alpine/manifests/3.19"))
+            .and(header("authorization", "Bearer secret"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(index))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(path(format!(
+            "/v2/library/alpine/manifests/{}",
+            sha256(manifest.as_bytes())
+        )))
+        .and(header("authorization", "Bearer secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(manifest.clone()))
+        .with_priority(1)
+        .mount(&server)
+        .await;
+        Mock::given(path(format!("/v2/library/alpine/blobs/{}", sha256(&layer))))
+            .and(header("authorization", "Bearer secret"))
+            .and(header("range", "bytes=7-11"))
+            .respond_with(ResponseTemplate::new(206).set_body_bytes(&layer[7..12]))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(401).insert_header(
+                "www-authenticate",
+                format!(
+                    r#"Bearer realm="{}/token",service="registry",scope="repository:library/alpine:pull""#,
+                    server.uri()
+                )
+                .as_str(),
+            ))
+            .with_priority(10)
+            .mount(&server)
+            .await;
+
+        let oci = OCI::new().unwrap();
+        let url = format!(
+            "oci://{}/library/alpine:3.19/?plain-http=true&platform=linux/arm64/v8",
+            address
+        );
+        let response = oci
+            .head(HeadRequest {
+                task_id: "test".to_string(),
+                url: url.clone(),
+                http_header: None,
+                timeout: Duration::from_secs(5),
+                client_cert: None,
+                object_storage: None,
+                hdfs: None,
+            })
+            .await
+            .unwrap();
+        assert!(response.success);
+        assert_eq!(response.content_length, Some(manifest.len() as u64));
+        assert_eq!(response.entries.len(), 2);
+        assert_eq!(response.entries[1].content_length, layer.len());
+
+        let entry_url = Url::parse(&response.entries[1].url).unwrap();
+        assert_eq!(
+            entry_url.path(),
+            format!("/library/alpine:3.19/{}", sha256(&layer))
+        );
+        assert_eq!(blob_digest(&entry_url), Some(sha256(&layer)));
+
+        let mut response = oci
+            .get(GetRequest {
+                task_id: "test".to_string(),
+                piece_id: "test".to_string(),
+                url: entry_url.to_string(),
+                range: Some(Range {
+                    start: 7,
+                    length: 5,
+                }),
+                http_header: None,
+                timeout: Duration::from_secs(5),
+                client_cert: None,
+                object_storage: None,
+                hdfs: None,
+            })
+            .await
+            .unwrap();
+        assert!(response.success);
+        assert_eq!(response.text().await.unwrap(), "world");
+    }
+}
@@ -23,6 +23,7 @@ use dragonfly_client_util::{
     http::basic_auth,
     tls::{generate_ca_cert_from_pem, generate_cert_from_pem},
 };
+use ipnetwork::IpNetwork;
 use local_ip_address::{local_ip, local_ipv6};
 use rcgen::Certificate;
 use regex::Regex;
@@ -146,6 +147,41 @@ fn default_download_piece_timeout() -> Duration {
     Duration::from_secs(120)
 }
 
+/// default_download_origin_max_retry_after is the default max duration to honour the
+/// Retry-After of an origin.
+#[inline]
+fn default_download_origin_max_retry_after() -> Duration {
+    Duration::from_secs(60)
+}
+
+/// default_download_origin_circuit_breaker_window is the default window to count the
+/// back-to-source requests of an origin.
+#[inline]
+fn default_download_origin_circuit_breaker_window() -> Duration {
+    Duration::from_secs(10)
+}
+
+/// default_download_origin_circuit_breaker_min_requests is the default min number of the
+/// requests in the window to open the circuit breaker.
+#[inline]
+fn default_download_origin_circuit_breaker_min_requests() -> u32 {
+    20
+}
+
+/// default_download_origin_circuit_breaker_failure_threshold_percent is the default percent of
+/// the failed requests in the window to open the circuit breaker.
+#[inline]
+fn default_download_origin_circuit_breaker_failure_threshold_percent() -> u8 {
+    50
+}
+
+/// default_download_origin_circuit_breaker_open_duration is the default duration of the circuit
+/// breaker staying open before probing the origin.
+#[inline]
+fn default_download_origin_circuit_breaker_open_duration() -> Duration {
+    Duration::from_secs(30)
+}
+
 /// default_collected_download_piece_timeout is the default timeout for collecting one piece from the parent in the stream.
 #[inline]
 fn default_collected_download_piece_timeout() -> Duration {
@@ -194,12 +230,37 @@ fn default_storage_server_protocol() -> String {
     "grpc".to_string()
 }
 
+/// default_storage_server_tcp_port is the default port of the tcp storage server.
+#[inline]
+fn default_storage_server_tcp_port() -> u16 {
+    4005
+}
+
 /// default_storage_keep is the default keep of the task's metadata and content when the dfdaemon restarts.
 #[inline]
 fn default_storage_keep() -> bool {
     false
 }
 
+/// default_storage_dedup is the default dedup of the task's content by the digest.
+#[inline]
+fn default_storage_dedup() -> bool {
+    false
+}
+
+/// default_storage_dir_weight is the default weight of the content directory.
+#[inline]
+fn default_storage_dir_weight() -> u32 {
+    1
+}
+
+/// default_storage_promotion_uploaded_count is the default uploaded count of the task to be
+/// promoted to the fastest tier.
+#[inline]
+fn default_storage_promotion_uploaded_count() -> u64 {
+    10
+}
+
 /// default_storage_write_piece_timeout is the default timeout for writing a piece to storage(e.g., disk
 /// or cache).
 #[inline]
@@ -207,6 +268,12 @@ fn default_storage_write_piece_timeout() -> Duration {
     Duration::from_secs(90)
 }
 
+/// default_storage_compression_level is the default zstd level to compress the piece content.
+#[inline]
+fn default_storage_compression_level() -> i32 {
+    3
+}
+
 /// default_storage_write_buffer_size is the default buffer size for writing piece to disk, default is 4MB.
 #[inline]
 fn default_storage_write_buffer_size() -> usize {
@@ -385,6 +452,12 @@ fn default_proxy_registry_mirror_addr() -> String {
     "https://index.docker.io".to_string()
 }
 
+/// default_backend_credential_profile is the default profile of the shared credentials file.
+#[inline]
+fn default_backend_credential_profile() -> String {
+    "default".to_string()
+}
+
 /// Host is the host configuration for dfdaemon.
 #[derive(Debug, Clone, Validate, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
@@ -499,6 +572,13 @@ pub struct Download {
     #[serde(default = "default_download_concurrent_piece_count")]
     #[validate(range(min = 1))]
     pub concurrent_piece_count: u32,
+
+    /// integrity is the piece integrity configuration for downloading from the parents.
+    pub integrity: Integrity,
+
+    /// origin is the configuration of protecting the origins from the back-to-source traffic.
+    #[validate]
+    pub origin: Origin,
 }
 
 /// Download implements Default.
@@ -511,10 +591,125 @@ impl Default for Download {
             piece_timeout: default_download_piece_timeout(),
             collected_piece_timeout: default_collected_download_piece_timeout(),
             concurrent_piece_count: default_download_concurrent_piece_count(),
+            integrity: Integrity::default(),
+            origin: Origin::default(),
         }
     }
 }
 
+/// Origin is the configuration of protecting the origins from the back-to-source traffic. The
+/// limits and the circuit breaker are applied to each origin, which is the scheme, host and
+/// port of the url. When the origin responds 429 or 503 with Retry-After, the back-to-source
+/// requests to it wait until the Retry-After.
+///
+/// The protection is opt-in, the limits are disabled and the circuit breaker is disabled by
+/// default, so the back-to-source requests are never rejected unless they are configured.
+/// Only the Retry-After of the origins is honoured by default.
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Origin {
+    /// concurrent_request_limit is the max number of the concurrent back-to-source requests to
+    /// an origin, 0 means no limit.
+    pub concurrent_request_limit: usize,
+
+    /// request_rate_limit is the max number of the back-to-source requests per second to an
+    /// origin, 0 means no limit.
+    pub request_rate_limit: usize,
+
+    /// max_retry_after is the max duration to honour the Retry-After of an origin, the longer
+    /// Retry-After is truncated to it.
+    #[serde(
+        default = "default_download_origin_max_retry_after",
+        with = "humantime_serde"
+    )]
+    pub max_retry_after: Duration,
+
+    /// circuit_breaker is the circuit breaker configuration of the origins.
+    #[validate]
+    pub circuit_breaker: CircuitBreaker,
+}
+
+/// Origin implements Default.
+impl Default for Origin {
+    fn default() -> Self {
+        Origin {
+            concurrent_request_limit: 0,
+            request_rate_limit: 0,
+            max_retry_after: default_download_origin_max_retry_after(),
+            circuit_breaker: CircuitBreaker::default(),
+        }
+    }
+}
+
+/// CircuitBreaker is the circuit breaker configuration of the origins. If the percent of the
+/// failed back-to-source requests to an origin in the window exceeds the threshold, the
+/// circuit breaker opens and the back-to-source requests fail fast, so the scheduler prefers
+/// the parents. After the open duration, one request probes the origin and closes the circuit
+/// breaker if it succeeds.
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CircuitBreaker {
+    /// enable indicates whether enable the circuit breaker, it is disabled by default.
+    pub enable: bool,
+
+    /// window is the window to count the back-to-source requests of an origin.
+    #[serde(
+        default = "default_download_origin_circuit_breaker_window",
+        with = "humantime_serde"
+    )]
+    pub window: Duration,
+
+    /// min_requests is the min number of the requests in the window to open the circuit
+    /// breaker, it prevents the circuit breaker from opening by a few failures.
+    #[serde(default = "default_download_origin_circuit_breaker_min_requests")]
+    pub min_requests: u32,
+
+    /// failure_threshold_percent is the percent of the failed requests in the window to open
+    /// the circuit breaker.
+    #[serde(default = "default_download_origin_circuit_breaker_failure_threshold_percent")]
+    #[validate(range(min = 1, max = 100))]
+    pub failure_threshold_percent: u8,
+
+    /// open_duration is the duration of the circuit breaker staying open before probing the
+    /// origin.
+    #[serde(
+        default = "default_download_origin_circuit_breaker_open_duration",
+        with = "humantime_serde"
+    )]
+    pub open_duration: Duration,
+}
+
+/// CircuitBreaker implements Default.
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        CircuitBreaker {
+            enable: false,
+            window: default_download_origin_circuit_breaker_window(),
+            min_requests: default_download_origin_circuit_breaker_min_requests(),
+            failure_threshold_percent:
+                default_download_origin_circuit_breaker_failure_threshold_percent(),
+            open_duration: default_download_origin_circuit_breaker_open_duration(),
+        }
+    }
+}
+
+/// Integrity is the piece integrity configuration for dfdaemon.
+///
+/// If the integrity is enabled, the pieces are hashed by sha256 instead of crc32, and the
+/// pieces downloaded from the parents are verified by the digests of the seed peers before
+/// they are stored. The digests of all the pieces form the merkle tree of the task, and the
+/// merkle root must be the same for all the seed peers. The parents which send the forged
+/// pieces are reported to the scheduler and skipped. It must be enabled for all the seed peers
+/// in the cluster, because the pieces can only be verified by the sha256 digests of the seed
+/// peers. If the scheduler returns no seed peer in the candidate parents, or the seed peers do
+/// not report the sha256 digests, the pieces are downloaded from the source instead.
+#[derive(Debug, Clone, Default, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Integrity {
+    /// enable indicates whether enable the piece integrity verification.
+    pub enable: bool,
+}
+
 /// UploadServer is the upload server configuration for dfdaemon.
 #[derive(Debug, Clone, Validate, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
@@ -542,6 +737,9 @@ pub struct UploadServer {
     /// default is 4000 req/s.
     #[serde(default = "default_upload_request_rate_limit")]
     pub request_rate_limit: u64,
+
+    /// auth is the authorization configuration of the upload server.
+    pub auth: UploadAuth,
 }
 
 /// UploadServer implements Default.
@@ -554,10 +752,50 @@ impl Default for UploadServer {
             cert: None,
             key: None,
             request_rate_limit: default_upload_request_rate_limit(),
+            auth: UploadAuth::default(),
         }
     }
 }
 
+/// UploadAuth is the authorization configuration of the upload server. If the token and the
+/// peer cidrs are not configured, all the read requests are allowed. The mutating requests are
+/// authorized as the read requests, unless the admin cidrs are configured to restrict them.
+#[derive(Debug, Clone, Default, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct UploadAuth {
+    /// token is the token shared by the peers, the request carrying the token in the
+    /// authorization metadata is allowed to read the tasks and pieces. The peer sends the
+    /// token configured by `upload.client.token`.
+    pub token: Option<String>,
+
+    /// peer_cidrs are the cidrs of the known peers, the request from them is allowed to read
+    /// the tasks and pieces without the token.
+    pub peer_cidrs: Vec<IpNetwork>,
+
+    /// admin_cidrs are the cidrs of the scheduler and manager. If it is configured, only the
+    /// request from them is allowed to delete and update the tasks, otherwise the mutating
+    /// request is authorized as the read request.
+    pub admin_cidrs: Vec<IpNetwork>,
+}
+
+/// UploadAuth is the implementation of UploadAuth.
+impl UploadAuth {
+    /// is_enabled returns whether the read request needs to be authorized.
+    pub fn is_enabled(&self) -> bool {
+        self.token.is_some() || !self.peer_cidrs.is_empty()
+    }
+
+    /// is_peer returns whether the ip is in the cidrs of the known peers.
+    pub fn is_peer(&self, ip: IpAddr) -> bool {
+        self.peer_cidrs.iter().any(|cidr| cidr.contains(ip))
+    }
+
+    /// is_admin returns whether the ip is in the cidrs of the scheduler and manager.
+    pub fn is_admin(&self, ip: IpAddr) -> bool {
+        self.admin_cidrs.iter().any(|cidr| cidr.contains(ip))
+    }
+}
+
 /// UploadServer is the implementation of UploadServer.
 impl UploadServer {
     /// load_server_tls_config loads the server tls config.
@@ -598,6 +836,10 @@ pub struct UploadClient {
     /// key is the client key path with PEM format for the upload client and it is used for
     /// mutual TLS.
     pub key: Option<PathBuf>,
+
+    /// token is sent in the authorization metadata of the request to the upload server of
+    /// the other peers, it needs to match `upload.server.auth.token` of them.
+    pub token: Option<String>,
 }
 
 /// UploadClient is the implementation of UploadClient.
@@ -656,7 +898,7 @@ impl UploadClient {
 /// |                                                +------------+     |
 /// +-------------------------------------------------------------------+
 /// ```
-#[derive(Debug, Clone, Default, Validate, Deserialize)]
+#[derive(Debug, Clone, Validate, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct ParentSelector {
     /// enable indicates whether enable parent selector for downloading.
@@ -679,6 +921,17 @@ pub struct ParentSelector {
     pub capacity: usize,
 }
 
+/// ParentSelector implements Default.
+impl Default for ParentSelector {
+    fn default() -> Self {
+        ParentSelector {
+            enable: false,
+            sync_interval: default_parent_selector_sync_interval(),
+            capacity: default_parent_selector_capacity(),
+        }
+    }
+}
+
 /// Upload is the upload configuration for dfdaemon.
 #[derive(Debug, Clone, Validate, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
@@ -950,12 +1203,24 @@ impl Default for Dynconfig {
 #[serde(default, rename_all = "camelCase")]
 pub struct StorageServer {
     /// protocol is the protocol of the storage server. The protocol used for downloading pieces
-    /// between different peers, now only support gRPC.
+    /// between different peers, support gRPC and TCP. All peers in the same cluster must use
+    /// the same protocol.
     ///
     /// gRPC Protocol: The storage server will start a gRPC service in the DfdaemonUploadServer,
     /// refer to https://github.com/dragonflyoss/api/blob/main/proto/dfdaemon.proto#L185.
+    ///
+    /// TCP Protocol: The storage server will start a raw TCP service, the piece content is
+    /// written to the connection without protobuf framing. The content is copied through a
+    /// userspace buffer, sendfile and splice are not used.
     #[serde(default = "default_storage_server_protocol")]
     pub protocol: String,
+
+    /// ip is the listen ip of the tcp storage server.
+    pub ip: Option<IpAddr>,
+
+    /// tcp_port is the port of the tcp storage server, it is used when the protocol is tcp.
+    #[serde(default = "default_storage_server_tcp_port")]
+    pub tcp_port: u16,
 }
 
 /// Storage implements Default.
@@ -963,10 +1228,114 @@ impl Default for StorageServer {
     fn default() -> Self {
         StorageServer {
             protocol: default_storage_server_protocol(),
+            ip: None,
+            tcp_port: default_storage_server_tcp_port(),
         }
     }
 }
 
+/// StorageDir is the directory to store the content of the tasks.
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageDir {
+    /// path is the path of the directory, it is usually the mount point of a disk.
+    pub path: PathBuf,
+
+    /// tier is the tier of the directory, the lower tier is the faster disk, e.g. tier 0 for
+    /// the NVMe and tier 1 for the HDD. The new tasks are placed in the slowest tier, and the
+    /// hot tasks are promoted to the fastest tier.
+    #[serde(default)]
+    pub tier: u32,
+
+    /// weight is the weight of the directory in the same tier, the new tasks are placed in
+    /// the directories of the same tier in proportion to the weights.
+    #[serde(default = "default_storage_dir_weight")]
+    #[validate(range(min = 1))]
+    pub weight: u32,
+}
+
+/// Promotion is the configuration of promoting the hot tasks to the fastest tier of
+/// the content directories.
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Promotion {
+    /// enable indicates whether promote the hot tasks, it is checked every gc interval.
+    pub enable: bool,
+
+    /// uploaded_count is the uploaded count of the finished task to be promoted.
+    #[serde(default = "default_storage_promotion_uploaded_count")]
+    pub uploaded_count: u64,
+}
+
+/// Promotion implements Default.
+impl Default for Promotion {
+    fn default() -> Self {
+        Promotion {
+            enable: false,
+            uploaded_count: default_storage_promotion_uploaded_count(),
+        }
+    }
+}
+
+/// Compression is the configuration of compressing the piece content at rest.
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Compression {
+    /// enable indicates whether compress the pieces of the tasks by zstd when they are written
+    /// to the disk. The crc32 of the piece is calculated over the uncompressed content, so
+    /// the peer protocol is not changed. The piece which is not compressible is stored
+    /// uncompressed. The compressed content can not be hard linked to the output path, so it
+    /// is copied and decompressed.
+    pub enable: bool,
+
+    /// level is the zstd compression level, the higher level compresses better but slower.
+    #[serde(default = "default_storage_compression_level")]
+    #[validate(range(min = 1, max = 22))]
+    pub level: i32,
+}
+
+/// Compression implements Default.
+impl Default for Compression {
+    fn default() -> Self {
+        Compression {
+            enable: false,
+            level: default_storage_compression_level(),
+        }
+    }
+}
+
+/// Encryption is the configuration of encrypting the piece content at rest.
+#[derive(Debug, Clone, Default, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Encryption {
+    /// enable indicates whether encrypt the pieces of the tasks and the persistent cache tasks
+    /// when they are written to the disk. Each piece is encrypted by the active key with a
+    /// random nonce, the key id, nonce and tag are stored in the piece metadata, so the
+    /// content file has the same layout as the plaintext. The task id and piece number are
+    /// authenticated, so the encrypted piece can not be moved to the other piece or task. The
+    /// encrypted content can not be hard linked to the output path.
+    pub enable: bool,
+
+    /// algorithm is the AEAD algorithm to encrypt the pieces, support aes-256-gcm and
+    /// chacha20-poly1305. The pieces encrypted by the other algorithm can still be decrypted.
+    pub algorithm: EncryptionAlgorithm,
+
+    /// key_file is the path of the local key file. Each line of the file is `<key id>:<hex
+    /// encoded 32 bytes key>`, the empty lines and the lines starting with `#` are ignored.
+    /// The last key is the active key to encrypt the new pieces, and the other keys are kept
+    /// to decrypt the pieces encrypted before the rotation.
+    pub key_file: Option<PathBuf>,
+
+    /// kms_endpoint is the http endpoint of the key management service which returns the
+    /// keys in the same format as the key_file, it is used if the key_file is not set.
+    pub kms_endpoint: Option<String>,
+
+    /// allow_plaintext_output indicates whether the encrypted task can be copied to the
+    /// output path decrypted. If it is false, downloading the encrypted task to the output
+    /// path fails, and the content is only served to the peers and the stream readers.
+    pub allow_plaintext_output: bool,
+}
+
 /// Storage is the storage configuration for dfdaemon.
 #[derive(Debug, Clone, Validate, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
@@ -982,6 +1351,47 @@ pub struct Storage {
     #[serde(default = "default_storage_keep")]
     pub keep: bool,
 
+    /// dirs are the directories to store the content of the tasks, e.g. the mount points of
+    /// the NVMe and the HDDs. If it is empty, the content is stored in the dir. The metadata is
+    /// always stored in the dir.
+    #[validate]
+    pub dirs: Vec<StorageDir>,
+
+    /// promotion is the configuration of promoting the hot tasks to the fastest tier of
+    /// the dirs.
+    #[validate]
+    pub promotion: Promotion,
+
+    /// dedup indicates whether reuse the content and pieces of the finished task which has the
+    /// same sha256 digest, the digest is from the download request or the Docker-Content-Digest
+    /// response header. It avoids storing the same content twice when it is served by
+    /// different urls, e.g. the layer mirrored in different registries.
+    #[serde(default = "default_storage_dedup")]
+    pub dedup: bool,
+
+    /// metadata_engine is the embedded database to store the metadata of the tasks and pieces,
+    /// support rocksdb, redb and memory. The memory engine loses the metadata when the dfdaemon
+    /// restarts, it is used for the tests and the ephemeral nodes. Use `dfdaemon
+    /// --migrate-metadata-from` to copy the metadata when the engine is changed.
+    pub metadata_engine: MetadataEngineType,
+
+    /// io_engine is the engine to write and read the piece content of the disk, support
+    /// standard, direct and io_uring. The direct engine bypasses the page cache by O_DIRECT,
+    /// it avoids polluting the page cache by the content which is rarely read again. The
+    /// io_uring engine submits the aligned direct I/O of the piece by io_uring, it is used
+    /// for the seed peers with the high bandwidth. The direct and io_uring engines are only
+    /// supported on linux, the standard engine is used on the other platforms.
+    pub io_engine: IoEngineType,
+
+    /// compression is the configuration of compressing the piece content at rest.
+    #[validate]
+    pub compression: Compression,
+
+    /// encryption is the configuration of encrypting the piece content at rest. The keys are
+    /// reloaded every gc interval, so the key is rotated by appending the new key.
+    #[validate]
+    pub encryption: Encryption,
+
     /// write_piece_timeout is the timeout for writing a piece to storage(e.g., disk
     /// or cache).
     #[serde(
@@ -1030,6 +1440,12 @@ pub struct Storage {
     ///```   
     #[serde(with = "bytesize_serde", default = "default_storage_cache_capacity")]
     pub cache_capacity: ByteSize,
+
+    /// cache_policy is the policy to evict the tasks from the cache, support lru and tinylfu.
+    /// The tinylfu policy admits the new task only if it is accessed more frequently than the
+    /// tasks to be evicted, so the one-off large task does not evict the hot tasks. The cache
+    /// task which is not admitted fails with no space.
+    pub cache_policy: CachePolicyType,
 }
 
 /// Storage implements Default.
@@ -1039,14 +1455,193 @@ impl Default for Storage {
             server: StorageServer::default(),
             dir: crate::default_storage_dir(),
             keep: default_storage_keep(),
+            dirs: Vec::new(),
+            promotion: Promotion::default(),
+            dedup: default_storage_dedup(),
+            metadata_engine: MetadataEngineType::default(),
+            io_engine: IoEngineType::default(),
+            compression: Compression::default(),
+            encryption: Encryption::default(),
             write_piece_timeout: default_storage_write_piece_timeout(),
             write_buffer_size: default_storage_write_buffer_size(),
             read_buffer_size: default_storage_read_buffer_size(),
             cache_capacity: default_storage_cache_capacity(),
+            cache_policy: CachePolicyType::default(),
+        }
+    }
+}
+
+/// MetadataEngineType is the type of the embedded database to store the metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum MetadataEngineType {
+    /// Rocksdb stores the metadata in rocksdb.
+    #[default]
+    #[serde(rename = "rocksdb")]
+    Rocksdb,
+
+    /// Redb stores the metadata in redb, a pure-Rust embedded B-tree database.
+    #[serde(rename = "redb")]
+    Redb,
+
+    /// Memory stores the metadata in memory, the metadata is lost when the dfdaemon restarts.
+    #[serde(rename = "memory")]
+    Memory,
+}
+
+/// MetadataEngineType implements Display.
+impl fmt::Display for MetadataEngineType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataEngineType::Rocksdb => write!(f, "rocksdb"),
+            MetadataEngineType::Redb => write!(f, "redb"),
+            MetadataEngineType::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+/// IoEngineType is the type of the engine to write and read the piece content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum IoEngineType {
+    /// Standard writes and reads the piece content by the buffered I/O of the tokio.
+    #[default]
+    #[serde(rename = "standard")]
+    Standard,
+
+    /// Direct writes and reads the piece content by O_DIRECT with the aligned buffers.
+    #[serde(rename = "direct")]
+    Direct,
+
+    /// IoUring writes and reads the piece content by io_uring with O_DIRECT.
+    #[serde(rename = "io_uring")]
+    IoUring,
+}
+
+/// IoEngineType implements Display.
+impl fmt::Display for IoEngineType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IoEngineType::Standard => write!(f, "standard"),
+            IoEngineType::Direct => write!(f, "direct"),
+            IoEngineType::IoUring => write!(f, "io_uring"),
+        }
+    }
+}
+
+/// CachePolicyType is the policy to evict the tasks from the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum CachePolicyType {
+    /// Lru evicts the least recently used task first.
+    #[default]
+    #[serde(rename = "lru")]
+    Lru,
+
+    /// TinyLfu admits the tasks by the frequency sketch, and evicts the tasks by the window
+    /// LRU and the segmented LRU, it is W-TinyLFU.
+    #[serde(rename = "tinylfu")]
+    TinyLfu,
+}
+
+/// CachePolicyType implements Display.
+impl fmt::Display for CachePolicyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CachePolicyType::Lru => write!(f, "lru"),
+            CachePolicyType::TinyLfu => write!(f, "tinylfu"),
         }
     }
 }
 
+/// EncryptionAlgorithm is the AEAD algorithm to encrypt the piece content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum EncryptionAlgorithm {
+    /// Aes256Gcm encrypts the piece content by AES-256-GCM, it is fast with AES-NI.
+    #[default]
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm,
+
+    /// ChaCha20Poly1305 encrypts the piece content by ChaCha20-Poly1305, it is fast on the
+    /// platforms without AES acceleration.
+    #[serde(rename = "chacha20-poly1305")]
+    ChaCha20Poly1305,
+}
+
+/// EncryptionAlgorithm implements Display.
+impl fmt::Display for EncryptionAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptionAlgorithm::Aes256Gcm => write!(f, "aes-256-gcm"),
+            EncryptionAlgorithm::ChaCha20Poly1305 => write!(f, "chacha20-poly1305"),
+        }
+    }
+}
+
+/// EvictionPolicyType is the type of the eviction policy, it decides which task is evicted first
+/// when the disk usage is higher than the high threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+pub enum EvictionPolicyType {
+    /// LRU evicts the least recently used task first.
+    #[default]
+    #[serde(rename = "lru")]
+    Lru,
+
+    /// LFU evicts the task uploaded to the other peers least frequently first.
+    #[serde(rename = "lfu")]
+    Lfu,
+
+    /// Size evicts the task with the largest size weighted by the idle time first, it frees
+    /// more space with fewer evicted tasks.
+    #[serde(rename = "size")]
+    Size,
+}
+
+/// EvictionPolicyType implements Display.
+impl fmt::Display for EvictionPolicyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvictionPolicyType::Lru => write!(f, "lru"),
+            EvictionPolicyType::Lfu => write!(f, "lfu"),
+            EvictionPolicyType::Size => write!(f, "size"),
+        }
+    }
+}
+
+/// Pinned is the configuration of the pinned tasks, the pinned tasks are never evicted by gc.
+#[derive(Debug, Clone, Default, Validate, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Pinned {
+    /// applications is the list of the applications, the task downloaded by the application is
+    /// pinned.
+    pub applications: Vec<String>,
+
+    /// tags is the list of the tags, the task downloaded with the tag is pinned.
+    pub tags: Vec<String>,
+}
+
+/// Pinned implements the pinned configuration.
+impl Pinned {
+    /// is_pinned returns whether the task with the application and tag is pinned.
+    pub fn is_pinned(&self, application: Option<&str>, tag: Option<&str>) -> bool {
+        application.is_some_and(|application| self.applications.iter().any(|a| a == application))
+            || tag.is_some_and(|tag| self.tags.iter().any(|t| t == tag))
+    }
+}
+
+/// Quota is the storage quota of the application, the total content length of the tasks
+/// downloaded by the application can not exceed the limit.
+#[derive(Debug, Clone, Validate, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quota {
+    /// application is the application of the tasks.
+    #[validate(length(min = 1))]
+    pub application: String,
+
+    /// limit is the maximum bytes of the tasks downloaded by the application. If the limit is
+    /// exceeded, the tasks of the application are evicted first, and the download is rejected
+    /// if the space can not be released.
+    #[serde(with = "bytesize_serde")]
+    pub limit: ByteSize,
+}
+
 /// Policy is the policy configuration for gc.
 #[derive(Debug, Clone, Validate, Deserialize, Serialize)]
 #[serde(default, rename_all = "camelCase")]
@@ -1083,6 +1678,29 @@ pub struct Policy {
     #[serde(default = "default_gc_policy_dist_low_threshold_percent")]
     #[validate(range(min = 1, max = 99))]
     pub dist_low_threshold_percent: u8,
+
+    /// eviction is the eviction policy of the task when the disk usage is higher than
+    /// dist_high_threshold_percent, support lru, lfu and size, default is lru.
+    pub eviction: EvictionPolicyType,
+
+    /// pinned is the pinned tasks by application and tag, the pinned tasks are never
+    /// evicted by ttl or disk usage.
+    pub pinned: Pinned,
+
+    /// quotas is the storage quotas by application, the application without quota is only
+    /// limited by the disk usage.
+    #[validate]
+    pub quotas: Vec<Quota>,
+}
+
+/// Policy implements the policy configuration.
+impl Policy {
+    /// quota returns the storage quota of the application.
+    pub fn quota(&self, application: &str) -> Option<&Quota> {
+        self.quotas
+            .iter()
+            .find(|quota| quota.application == application)
+    }
 }
 
 /// Policy implements Default.
@@ -1093,6 +1711,9 @@ impl Default for Policy {
             task_ttl: default_gc_policy_task_ttl(),
             dist_high_threshold_percent: default_gc_policy_dist_high_threshold_percent(),
             dist_low_threshold_percent: default_gc_policy_dist_low_threshold_percent(),
+            eviction: EvictionPolicyType::default(),
+            pinned: Pinned::default(),
+            quotas: Vec::new(),
         }
     }
 }
@@ -1106,6 +1727,7 @@ pub struct GC {
     pub interval: Duration,
 
     /// policy is the gc policy.
+    #[validate]
     pub policy: Policy,
 }
 
@@ -1482,6 +2104,103 @@ impl Default for Tracing {
     }
 }
 
+/// Credential is the configuration of the credential providers of the object storage, the
+/// credential is loaded by the providers when the download request does not carry the access
+/// key. The providers are tried in the order of the environment variables, the shared
+/// credentials file, the secret directory and the metadata endpoint.
+#[derive(Debug, Clone, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Credential {
+    /// env indicates whether load the credential from the environment variables, such as
+    /// `DRAGONFLY_STORAGE_ACCESS_KEY_ID`, `DRAGONFLY_STORAGE_ACCESS_KEY_SECRET` and
+    /// `DRAGONFLY_STORAGE_SESSION_TOKEN`, the `AWS_*` variables are used if they are not set.
+    pub env: bool,
+
+    /// shared_credentials_file is the path of the shared credentials file, such as
+    /// `~/.aws/credentials`. The file is reloaded when it is modified.
+    pub shared_credentials_file: Option<PathBuf>,
+
+    /// profile is the profile of the credential in the shared credentials file.
+    #[serde(default = "default_backend_credential_profile")]
+    pub profile: String,
+
+    /// secret_dir is the directory of the mounted secret files, such as the kubernetes secret.
+    /// The directory contains the `access_key_id`, `access_key_secret` and the optional
+    /// `session_token` files, and the files are reloaded when they are rotated.
+    pub secret_dir: Option<PathBuf>,
+
+    /// metadata_endpoint is the url of the local IMDS or STS compatible endpoint which returns
+    /// the temporary credential in the json format of `AccessKeyId`, `SecretAccessKey`, `Token`
+    /// and `Expiration`. The credential is refreshed before it expires.
+    pub metadata_endpoint: Option<String>,
+
+    /// allowed_buckets are the buckets which the credential of the providers can be applied
+    /// to, the credential is not applied to any bucket if it is empty, because the buckets
+    /// are chosen by the requests.
+    pub allowed_buckets: Vec<String>,
+
+    /// allowed_endpoints are the endpoints of the object storage which the credential of the
+    /// providers can be applied to, such as `https://s3.us-east-1.amazonaws.com`. The request
+    /// without the endpoint uses the default endpoint of the object storage. The credential is
+    /// never applied to the requests received by the upload server.
+    pub allowed_endpoints: Vec<String>,
+}
+
+/// Credential implements Default.
+impl Default for Credential {
+    fn default() -> Self {
+        Credential {
+            env: false,
+            shared_credentials_file: None,
+            profile: default_backend_credential_profile(),
+            secret_dir: None,
+            metadata_endpoint: None,
+            allowed_buckets: Vec::new(),
+            allowed_endpoints: Vec::new(),
+        }
+    }
+}
+
+/// FileBackend is the configuration of the file backend, which reads the local file system.
+#[derive(Debug, Clone, Default, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FileBackend {
+    /// allowed_dirs are the directories which can be read by the `file://` urls, the path of
+    /// the url is resolved with the symlinks and must be in one of the directories. The file
+    /// backend is disabled if it is empty, because the urls are sent by the remote peers and
+    /// the scheduler.
+    pub allowed_dirs: Vec<PathBuf>,
+}
+
+/// OCIBackend is the configuration of the oci backend, which pulls the images from the
+/// registries.
+#[derive(Debug, Clone, Default, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct OCIBackend {
+    /// allowed_token_hosts are the hosts of the token services which can receive the
+    /// credential of the request besides the registry itself, such as `auth.docker.io` or
+    /// `auth.example.com:8443`. The credential is never forwarded to the token service which
+    /// downgrades the https scheme of the registry.
+    pub allowed_token_hosts: Vec<String>,
+}
+
+/// Backend is the backend configuration for dfdaemon.
+#[derive(Debug, Clone, Default, Validate, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Backend {
+    /// credential is the configuration of the credential providers of the object storage.
+    #[validate]
+    pub credential: Credential,
+
+    /// file is the configuration of the file backend.
+    #[validate]
+    pub file: FileBackend,
+
+    /// oci is the configuration of the oci backend.
+    #[validate]
+    pub oci: OCIBackend,
+}
+
 /// Config is the configuration for dfdaemon.
 #[derive(Debug, Clone, Default, Validate, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
@@ -1553,6 +2272,10 @@ pub struct Config {
     /// network is the network configuration for dfdaemon.
     #[validate]
     pub network: Network,
+
+    /// backend is the backend configuration for dfdaemon.
+    #[validate]
+    pub backend: Backend,
 }
 
 /// Config implements the config operation of dfdaemon.
@@ -1593,6 +2316,15 @@ impl Config {
             }
         }
 
+        // Convert storage server listen ip.
+        if self.storage.server.ip.is_none() {
+            self.storage.server.ip = if self.network.enable_ipv6 {
+                Some(Ipv6Addr::UNSPECIFIED.into())
+            } else {
+                Some(Ipv4Addr::UNSPECIFIED.into())
+            }
+        }
+
         // Convert metrics server listen ip.
         if self.health.server.ip.is_none() {
             self.health.server.ip = if self.network.enable_ipv6 {
@@ -1701,7 +2433,20 @@ mod tests {
             },
             "rateLimit": "50GiB",
             "pieceTimeout": "30s",
-            "concurrentPieceCount": 10
+            "concurrentPieceCount": 10,
+            "integrity": {
+                "enable": true
+            },
+            "origin": {
+                "concurrentRequestLimit": 32,
+                "requestRateLimit": 100,
+                "maxRetryAfter": "2m",
+                "circuitBreaker": {
+                    "enable": true,
+                    "window": "20s",
+                    "failureThresholdPercent": 80
+                }
+            }
         }"#;
 
         let download: Download = serde_json::from_str(json_data).unwrap();
@@ -1715,6 +2460,58 @@ mod tests {
         assert_eq!(download.rate_limit, ByteSize::gib(50));
         assert_eq!(download.piece_timeout, Duration::from_secs(30));
         assert_eq!(download.concurrent_piece_count, 10);
+        assert!(download.integrity.enable);
+
+        assert_eq!(download.origin.concurrent_request_limit, 32);
+        assert_eq!(download.origin.request_rate_limit, 100);
+        assert_eq!(download.origin.max_retry_after, Duration::from_secs(120));
+        assert!(download.origin.circuit_breaker.enable);
+        assert_eq!(
+            download.origin.circuit_breaker.window,
+            Duration::from_secs(20)
+        );
+        assert_eq!(download.origin.circuit_breaker.min_requests, 20);
+        assert_eq!(
+            download.origin.circuit_breaker.failure_threshold_percent,
+            80
+        );
+        assert_eq!(
+            download.origin.circuit_breaker.open_duration,
+            Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn default_parent_selector() {
+        let default_parent_selector = ParentSelector::default();
+        assert!(!default_parent_selector.enable);
+        assert_eq!(
+            default_parent_selector.sync_interval,
+            Duration::from_secs(3)
+        );
+        assert_eq!(default_parent_selector.capacity, 20);
+
+        let default_download = Download::default();
+        assert!(!default_download.parent_selector.enable);
+        assert_eq!(
+            default_download.parent_selector.sync_interval,
+            Duration::from_secs(3)
+        );
+        assert_eq!(default_download.parent_selector.capacity, 20);
+    }
+
+    #[test]
+    fn default_origin() {
+        // The origin protection is opt-in, only the Retry-After is honoured by default.
+        let default_origin = Origin::default();
+        assert_eq!(default_origin.concurrent_request_limit, 0);
+        assert_eq!(default_origin.request_rate_limit, 0);
+        assert_eq!(default_origin.max_retry_after, Duration::from_secs(60));
+        assert!(!default_origin.circuit_breaker.enable);
+
+        let origin: Origin = serde_json::from_str("{}").unwrap();
+        assert_eq!(origin.concurrent_request_limit, 0);
+        assert!(!origin.circuit_breaker.enable);
     }
 
     #[test]
@@ -1726,12 +2523,18 @@ mod tests {
                 "ip": "127.0.0.1",
                 "caCert": "/etc/ssl/certs/ca.crt",
                 "cert": "/etc/ssl/certs/server.crt",
-                "key": "/etc/ssl/private/server.pem"
+                "key": "/etc/ssl/private/server.pem",
+                "auth": {
+                    "token": "foo",
+                    "peerCidrs": ["10.0.0.0/8"],
+                    "adminCidrs": ["192.168.1.10/32"]
+                }
             },
             "client": {
                 "caCert": "/etc/ssl/certs/ca.crt",
                 "cert": "/etc/ssl/certs/client.crt",
-                "key": "/etc/ssl/private/client.pem"
+                "key": "/etc/ssl/private/client.pem",
+                "token": "foo"
             },
             "disableShared": false,
             "rateLimit": "10GiB"
@@ -1769,6 +2572,15 @@ mod tests {
             upload.client.key,
             Some(PathBuf::from("/etc/ssl/private/client.pem"))
         );
+        assert_eq!(upload.client.token, Some("foo".to_string()));
+
+        let auth = &upload.server.auth;
+        assert!(auth.is_enabled());
+        assert_eq!(auth.token, Some("foo".to_string()));
+        assert!(auth.is_peer("10.1.2.3".parse().unwrap()));
+        assert!(!auth.is_peer("192.168.1.10".parse().unwrap()));
+        assert!(auth.is_admin("192.168.1.10".parse().unwrap()));
+        assert!(!auth.is_admin("192.168.1.11".parse().unwrap()));
 
         assert!(!upload.disable_shared);
         assert_eq!(upload.rate_limit, ByteSize::gib(10));
@@ -1787,6 +2599,7 @@ mod tests {
             server.request_rate_limit,
             default_upload_request_rate_limit()
         );
+        assert!(!server.auth.is_enabled());
     }
 
     #[tokio::test]
@@ -1833,6 +2646,7 @@ mod tests {
             ca_cert: Some(ca_file.path().to_path_buf()),
             cert: Some(cert_file.path().to_path_buf()),
             key: Some(key_file.path().to_path_buf()),
+            token: None,
         };
 
         let tls_config = client.load_client_tls_config("example.com").await.unwrap();
@@ -2040,25 +2854,83 @@ key: /etc/ssl/private/client.pem
         let json_data = r#"
         {
             "server": {
-                "protocol": "http"
+                "protocol": "tcp",
+                "ip": "127.0.0.1",
+                "tcpPort": 4010
             },
             "dir": "/tmp/storage",
             "keep": true,
+            "dedup": true,
+            "dirs": [
+                {
+                    "path": "/mnt/nvme",
+                    "tier": 0
+                },
+                {
+                    "path": "/mnt/hdd",
+                    "tier": 1,
+                    "weight": 2
+                }
+            ],
+            "promotion": {
+                "enable": true,
+                "uploadedCount": 5
+            },
+            "metadataEngine": "redb",
+            "ioEngine": "io_uring",
+            "compression": {
+                "enable": true,
+                "level": 9
+            },
+            "encryption": {
+                "enable": true,
+                "algorithm": "chacha20-poly1305",
+                "keyFile": "/etc/dragonfly/keys"
+            },
             "writePieceTimeout": "20s",
             "writeBufferSize": 8388608,
             "readBufferSize": 8388608,
-            "cacheCapacity": "256MB"
+            "cacheCapacity": "256MB",
+            "cachePolicy": "tinylfu"
         }"#;
 
         let storage: Storage = serde_json::from_str(json_data).unwrap();
 
-        assert_eq!(storage.server.protocol, "http".to_string());
+        assert_eq!(storage.server.protocol, "tcp".to_string());
+        assert_eq!(
+            storage.server.ip,
+            Some(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)))
+        );
+        assert_eq!(storage.server.tcp_port, 4010);
         assert_eq!(storage.dir, PathBuf::from("/tmp/storage"));
         assert!(storage.keep);
+        assert!(storage.dedup);
+        assert_eq!(storage.metadata_engine, MetadataEngineType::Redb);
+        assert_eq!(storage.io_engine, IoEngineType::IoUring);
+        assert!(storage.compression.enable);
+        assert_eq!(storage.compression.level, 9);
+        assert!(storage.encryption.enable);
+        assert_eq!(
+            storage.encryption.algorithm,
+            EncryptionAlgorithm::ChaCha20Poly1305
+        );
+        assert_eq!(
+            storage.encryption.key_file,
+            Some(PathBuf::from("/etc/dragonfly/keys"))
+        );
+        assert!(!storage.encryption.allow_plaintext_output);
+        assert_eq!(storage.dirs.len(), 2);
+        assert_eq!(storage.dirs[0].path, PathBuf::from("/mnt/nvme"));
+        assert_eq!(storage.dirs[0].weight, 1);
+        assert_eq!(storage.dirs[1].tier, 1);
+        assert_eq!(storage.dirs[1].weight, 2);
+        assert!(storage.promotion.enable);
+        assert_eq!(storage.promotion.uploaded_count, 5);
         assert_eq!(storage.write_piece_timeout, Duration::from_secs(20));
         assert_eq!(storage.write_buffer_size, 8 * 1024 * 1024);
         assert_eq!(storage.read_buffer_size, 8 * 1024 * 1024);
         assert_eq!(storage.cache_capacity, ByteSize::mb(256));
+        assert_eq!(storage.cache_policy, CachePolicyType::TinyLfu);
     }
 
     #[test]
@@ -2068,6 +2940,12 @@ key: /etc/ssl/private/client.pem
             dist_threshold: ByteSize::mb(100),
             dist_high_threshold_percent: 90,
             dist_low_threshold_percent: 70,
+            eviction: EvictionPolicyType::Lfu,
+            pinned: Pinned::default(),
+            quotas: vec![Quota {
+                application: "ml".to_string(),
+                limit: ByteSize::gb(100),
+            }],
         };
         assert!(valid_policy.validate().is_ok());
 
@@ -2076,6 +2954,18 @@ key: /etc/ssl/private/client.pem
             dist_threshold: ByteSize::mb(100),
             dist_high_threshold_percent: 100,
             dist_low_threshold_percent: 70,
+            eviction: EvictionPolicyType::Lru,
+            pinned: Pinned::default(),
+            quotas: Vec::new(),
+        };
+        assert!(invalid_policy.validate().is_err());
+
+        let invalid_policy = Policy {
+            quotas: vec![Quota {
+                application: "".to_string(),
+                limit: ByteSize::gb(100),
+            }],
+            ..Default::default()
         };
         assert!(invalid_policy.validate().is_err());
     }
@@ -2088,7 +2978,18 @@ key: /etc/ssl/private/client.pem
             "policy": {
                 "taskTTL": "12h",
                 "distHighThresholdPercent": 90,
-                "distLowThresholdPercent": 70
+                "distLowThresholdPercent": 70,
+                "eviction": "size",
+                "pinned": {
+                    "applications": ["base-images"],
+                    "tags": ["golden"]
+                },
+                "quotas": [
+                    {
+                        "application": "ml",
+                        "limit": "100GiB"
+                    }
+                ]
             }
         }"#;
 
@@ -2098,6 +2999,16 @@ key: /etc/ssl/private/client.pem
         assert_eq!(gc.policy.task_ttl, Duration::from_secs(12 * 3600));
         assert_eq!(gc.policy.dist_high_threshold_percent, 90);
         assert_eq!(gc.policy.dist_low_threshold_percent, 70);
+        assert_eq!(gc.policy.eviction, EvictionPolicyType::Size);
+        assert!(gc.policy.pinned.is_pinned(Some("base-images"), None));
+        assert!(gc.policy.pinned.is_pinned(None, Some("golden")));
+        assert!(!gc.policy.pinned.is_pinned(Some("other"), Some("other")));
+        assert!(!gc.policy.pinned.is_pinned(None, None));
+        assert_eq!(
+            gc.policy.quota("ml").map(|quota| quota.limit),
+            Some(ByteSize::gib(100))
+        );
+        assert!(gc.policy.quota("other").is_none());
     }
 
     #[test]
@@ -2209,4 +3120,54 @@ key: /etc/ssl/private/client.pem
             Some("127.0.0.1".parse::<IpAddr>().unwrap())
         );
     }
+
+    #[test]
+    fn deserialize_backend_correctly() {
+        let json_data = r#"
+        {
+            "credential": {
+                "env": true,
+                "sharedCredentialsFile": "/root/.aws/credentials",
+                "secretDir": "/etc/dragonfly/secrets/storage",
+                "metadataEndpoint": "http://169.254.170.2/v2/credentials",
+                "allowedBuckets": ["models"],
+                "allowedEndpoints": ["https://s3.us-east-1.amazonaws.com"]
+            },
+            "file": {
+                "allowedDirs": ["/mnt/nfs"]
+            },
+            "oci": {
+                "allowedTokenHosts": ["auth.docker.io"]
+            }
+        }"#;
+
+        let backend: Backend = serde_json::from_str(json_data).unwrap();
+        assert_eq!(backend.file.allowed_dirs, vec![PathBuf::from("/mnt/nfs")]);
+        assert_eq!(
+            backend.oci.allowed_token_hosts,
+            vec!["auth.docker.io".to_string()]
+        );
+        assert!(backend.credential.env);
+        assert_eq!(
+            backend.credential.allowed_buckets,
+            vec!["models".to_string()]
+        );
+        assert_eq!(
+            backend.credential.allowed_endpoints,
+            vec!["https://s3.us-east-1.amazonaws.com".to_string()]
+        );
+        assert_eq!(
+            backend.credential.shared_credentials_file,
+            Some(PathBuf::from("/root/.aws/credentials"))
+        );
+        assert_eq!(backend.credential.profile, "default");
+        assert_eq!(
+            backend.credential.secret_dir,
+            Some(PathBuf::from("/etc/dragonfly/secrets/storage"))
+        );
+        assert_eq!(
+            backend.credential.metadata_endpoint,
+            Some("http://169.254.170.2/v2/credentials".to_string())
+        );
+    }
 }
@@ -180,6 +180,10 @@ pub struct DownloadFromParentFailed {
 
     /// parent_id is the parent id of the piece.
     pub parent_id: String,
+
+    /// temporary indicates whether the failure is temporary, e.g. the network is unstable. The
+    /// failure of the parent which sends the forged piece is not temporary.
+    pub temporary: bool,
 }
 
 #[cfg(test)]
@@ -17,7 +17,7 @@
 use bytes::Bytes;
 use bytesize::ByteSize;
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
-use dragonfly_client_config::dfdaemon::{Config, Storage};
+use dragonfly_client_config::dfdaemon::{CachePolicyType, Config, Storage};
 use dragonfly_client_storage::{cache::Cache, metadata::Piece};
 use std::sync::Arc;
 use tokio::io::AsyncReadExt;
@@ -26,6 +26,15 @@ use tokio::runtime::Runtime;
 // Number of pieces to write/read in each benchmark.
 const PIECE_COUNT: usize = 100;
 
+// Number of the distinct tasks of the skewed workload.
+const WORKLOAD_TASK_COUNT: usize = 1000;
+
+// Number of the requests of the skewed workload.
+const WORKLOAD_REQUEST_COUNT: usize = 20000;
+
+// Number of the requests between the one-off large tasks in the skewed workload.
+const WORKLOAD_LARGE_TASK_INTERVAL: usize = 50;
+
 fn create_config(capacity: ByteSize) -> Config {
     Config {
         storage: Storage {
@@ -48,7 +57,67 @@ fn create_piece(length: u64) -> Piece {
         updated_at: chrono::Utc::now().naive_utc(),
         created_at: chrono::Utc::now().naive_utc(),
         finished_at: None,
+        compressed_length: None,
+        encryption: None,
+    }
+}
+
+/// Xorshift is the deterministic pseudo-random number generator, so the workloads are the
+/// same in each run.
+struct Xorshift(u64);
+
+impl Xorshift {
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// skewed_workload generates the requests of the tasks with their content length, the tasks
+/// follow the Zipf distribution, mixed with the one-off large tasks.
+fn skewed_workload() -> Vec<(String, u64)> {
+    let mut cdf = Vec::with_capacity(WORKLOAD_TASK_COUNT);
+    let mut total = 0.0;
+    for rank in 1..=WORKLOAD_TASK_COUNT {
+        total += 1.0 / (rank as f64).powf(0.9);
+        cdf.push(total);
+    }
+
+    let mut rng = Xorshift(0x2545f4914f6cdd1d);
+    let mut requests = Vec::with_capacity(WORKLOAD_REQUEST_COUNT);
+    for i in 0..WORKLOAD_REQUEST_COUNT {
+        if i % WORKLOAD_LARGE_TASK_INTERVAL == 0 {
+            requests.push((format!("large_task_{}", i), ByteSize::mib(128).as_u64()));
+        }
+
+        let sample = rng.next_f64() * total;
+        let rank = cdf.partition_point(|&value| value < sample);
+        requests.push((format!("task_{}", rank), ByteSize::mib(8).as_u64()));
     }
+
+    requests
+}
+
+/// replay replays the requests with the cache and returns the hit ratio.
+async fn replay(policy: CachePolicyType, requests: &[(String, u64)]) -> f64 {
+    let mut config = create_config(ByteSize::gib(1));
+    config.storage.cache_policy = policy;
+    let cache = Cache::new(Arc::new(config));
+
+    let mut hits = 0;
+    for (task_id, content_length) in requests {
+        if cache.access_task(task_id).await.is_some() {
+            hits += 1;
+        } else {
+            cache
+                .put_task(task_id, ByteSize::mib(4).as_u64(), *content_length)
+                .await;
+        }
+    }
+
+    hits as f64 / requests.len() as f64
 }
 
 pub fn put_task(c: &mut Criterion) {
@@ -61,9 +130,11 @@ pub fn put_task(c: &mut Criterion) {
         |b, size| {
             b.iter_batched(
                 || rt.block_on(async { Cache::new(Arc::new(create_config(ByteSize::gb(2)))) }),
-                |mut cache| {
+                |cache| {
                     rt.block_on(async {
-                        cache.put_task("task", black_box(size.as_u64())).await;
+                        cache
+                            .put_task("task", ByteSize::mb(4).as_u64(), black_box(size.as_u64()))
+                            .await;
                     });
                 },
                 criterion::BatchSize::SmallInput,
@@ -77,9 +148,11 @@ pub fn put_task(c: &mut Criterion) {
         |b, size| {
             b.iter_batched(
                 || rt.block_on(async { Cache::new(Arc::new(create_config(ByteSize::gb(2)))) }),
-                |mut cache| {
+                |cache| {
                     rt.block_on(async {
-                        cache.put_task("task", black_box(size.as_u64())).await;
+                        cache
+                            .put_task("task", ByteSize::mb(4).as_u64(), black_box(size.as_u64()))
+                            .await;
                     });
                 },
                 criterion::BatchSize::SmallInput,
@@ -93,9 +166,11 @@ pub fn put_task(c: &mut Criterion) {
         |b, size| {
             b.iter_batched(
                 || rt.block_on(async { Cache::new(Arc::new(create_config(ByteSize::gb(2)))) }),
-                |mut cache| {
+                |cache| {
                     rt.block_on(async {
-                        cache.put_task("task", black_box(size.as_u64())).await;
+                        cache
+                            .put_task("task", ByteSize::mb(4).as_u64(), black_box(size.as_u64()))
+                            .await;
                     });
                 },
                 criterion::BatchSize::SmallInput,
@@ -116,14 +191,16 @@ pub fn delete_task(c: &mut Criterion) {
         |b, size| {
             b.iter_batched(
                 || {
-                    let mut cache =
+                    let cache =
                         rt.block_on(async { Cache::new(Arc::new(create_config(ByteSize::gb(2)))) });
                     rt.block_on(async {
-                        cache.put_task("task", black_box(size.as_u64())).await;
+                        cache
+                            .put_task("task", ByteSize::mb(4).as_u64(), black_box(size.as_u64()))
+                            .await;
                     });
                     cache
                 },
-                |mut cache| {
+                |cache| {
                     rt.block_on(async {
                         cache.delete_task("task").await.unwrap();
                     });
@@ -139,14 +216,16 @@ pub fn delete_task(c: &mut Criterion) {
         |b, size| {
             b.iter_batched(
                 || {
-                    let mut cache =
+                    let cache =
                         rt.block_on(async { Cache::new(Arc::new(create_config(ByteSize::gb(2)))) });
                     rt.block_on(async {
-                        cache.put_task("task", black_box(size.as_u64())).await;
+                        cache
+                            .put_task("task", ByteSize::mb(4).as_u64(), black_box(size.as_u64()))
+                            .await;
                     });
                     cache
                 },
-                |mut cache| {
+                |cache| {
                     rt.block_on(async {
                         cache.delete_task("task").await.unwrap();
                     });
@@ -162,14 +241,16 @@ pub fn delete_task(c: &mut Criterion) {
         |b, size| {
             b.iter_batched(
                 || {
-                    let mut cache =
+                    let cache =
                         rt.block_on(async { Cache::new(Arc::new(create_config(ByteSize::gb(2)))) });
                     rt.block_on(async {
-                        cache.put_task("task", black_box(size.as_u64())).await;
+                        cache
+                            .put_task("task", ByteSize::mb(4).as_u64(), black_box(size.as_u64()))
+                            .await;
                     });
                     cache
                 },
-                |mut cache| {
+                |cache| {
                     rt.block_on(async {
                         cache.delete_task("task").await.unwrap();
                     });
@@ -192,7 +273,7 @@ pub fn write_piece(c: &mut Criterion) {
         |b, data| {
             b.iter_batched(
                 || {
-                    let mut cache = rt.block_on(async {
+                    let cache = rt.block_on(async {
                         Cache::new(Arc::new(create_config(
                             ByteSize::mb(4) * PIECE_COUNT as u64,
                         )))
@@ -200,7 +281,11 @@ pub fn write_piece(c: &mut Criterion) {
 
                     rt.block_on(async {
                         cache
-                            .put_task("task", (ByteSize::mb(4) * PIECE_COUNT as u64).as_u64())
+                            .put_task(
+                                "task",
+                                ByteSize::mb(4).as_u64(),
+                                (ByteSize::mb(4) * PIECE_COUNT as u64).as_u64(),
+                            )
                             .await;
                     });
                     cache
@@ -230,7 +315,7 @@ pub fn write_piece(c: &mut Criterion) {
         |b, data| {
             b.iter_batched(
                 || {
-                    let mut cache = rt.block_on(async {
+                    let cache = rt.block_on(async {
                         Cache::new(Arc::new(create_config(
                             ByteSize::mb(10) * PIECE_COUNT as u64,
                         )))
@@ -238,7 +323,11 @@ pub fn write_piece(c: &mut Criterion) {
 
                     rt.block_on(async {
                         cache
-                            .put_task("task", (ByteSize::mb(10) * PIECE_COUNT as u64).as_u64())
+                            .put_task(
+                                "task",
+                                ByteSize::mb(10).as_u64(),
+                                (ByteSize::mb(10) * PIECE_COUNT as u64).as_u64(),
+                            )
                             .await;
                     });
                     cache
@@ -268,7 +357,7 @@ pub fn write_piece(c: &mut Criterion) {
         |b, data| {
             b.iter_batched(
                 || {
-                    let mut cache = rt.block_on(async {
+                    let cache = rt.block_on(async {
                         Cache::new(Arc::new(create_config(
                             ByteSize::mb(16) * PIECE_COUNT as u64,
                         )))
@@ -276,7 +365,11 @@ pub fn write_piece(c: &mut Criterion) {
 
                     rt.block_on(async {
                         cache
-                            .put_task("task", (ByteSize::mb(16) * PIECE_COUNT as u64).as_u64())
+                            .put_task(
+                                "task",
+                                ByteSize::mb(16).as_u64(),
+                                (ByteSize::mb(16) * PIECE_COUNT as u64).as_u64(),
+                            )
                             .await;
                     });
                     cache
@@ -313,7 +406,7 @@ pub fn read_piece(c: &mut Criterion) {
         |b, data| {
             b.iter_batched(
                 || {
-                    let mut cache = rt.block_on(async {
+                    let cache = rt.block_on(async {
                         Cache::new(Arc::new(create_config(
                             ByteSize::mb(4) * PIECE_COUNT as u64,
                         )))
@@ -321,7 +414,11 @@ pub fn read_piece(c: &mut Criterion) {
 
                     rt.block_on(async {
                         cache
-                            .put_task("task", (ByteSize::mb(4) * PIECE_COUNT as u64).as_u64())
+                            .put_task(
+                                "task",
+                                ByteSize::mb(4).as_u64(),
+                                (ByteSize::mb(4) * PIECE_COUNT as u64).as_u64(),
+                            )
                             .await;
                         for i in 0..PIECE_COUNT {
                             cache
@@ -364,7 +461,7 @@ pub fn read_piece(c: &mut Criterion) {
         |b, data| {
             b.iter_batched(
                 || {
-                    let mut cache = rt.block_on(async {
+                    let cache = rt.block_on(async {
                         Cache::new(Arc::new(create_config(
                             ByteSize::mb(10) * PIECE_COUNT as u64,
                         )))
@@ -372,7 +469,11 @@ pub fn read_piece(c: &mut Criterion) {
 
                     rt.block_on(async {
                         cache
-                            .put_task("task", (ByteSize::mb(10) * PIECE_COUNT as u64).as_u64())
+                            .put_task(
+                                "task",
+                                ByteSize::mb(10).as_u64(),
+                                (ByteSize::mb(10) * PIECE_COUNT as u64).as_u64(),
+                            )
                             .await;
                         for i in 0..PIECE_COUNT {
                             cache
@@ -415,7 +516,7 @@ pub fn read_piece(c: &mut Criterion) {
         |b, data| {
             b.iter_batched(
                 || {
-                    let mut cache = rt.block_on(async {
+                    let cache = rt.block_on(async {
                         Cache::new(Arc::new(create_config(
                             ByteSize::mb(16) * PIECE_COUNT as u64,
                         )))
@@ -423,7 +524,11 @@ pub fn read_piece(c: &mut Criterion) {
 
                     rt.block_on(async {
                         cache
-                            .put_task("task", (ByteSize::mb(16) * PIECE_COUNT as u64).as_u64())
+                            .put_task(
+                                "task",
+                                ByteSize::mb(16).as_u64(),
+                                (ByteSize::mb(16) * PIECE_COUNT as u64).as_u64(),
+                            )
                             .await;
                         for i in 0..PIECE_COUNT {
                             cache
@@ -463,6 +568,40 @@ pub fn read_piece(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, put_task, delete_task, write_piece, read_piece,);
+pub fn hit_ratio(c: &mut Criterion) {
+    let rt: Runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("Hit Ratio");
+    let requests = skewed_workload();
+
+    for policy in [CachePolicyType::Lru, CachePolicyType::TinyLfu] {
+        println!(
+            "skewed workload hit ratio of {}: {:.4}",
+            policy,
+            rt.block_on(replay(policy, &requests))
+        );
+    }
+
+    group.sample_size(10);
+    for policy in [CachePolicyType::Lru, CachePolicyType::TinyLfu] {
+        group.bench_with_input(
+            BenchmarkId::new("Hit Ratio", policy),
+            &requests,
+            |b, requests| {
+                b.iter(|| black_box(rt.block_on(replay(policy, requests))));
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    put_task,
+    delete_task,
+    write_piece,
+    read_piece,
+    hit_ratio,
+);
 
 criterion_main!(benches);
@@ -17,10 +17,96 @@
 use bytesize::ByteSize;
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
 use dragonfly_client_storage::cache::lru_cache::LruCache;
+use dragonfly_client_storage::cache::tiny_lfu::{TinyLfuCache, DEFAULT_SKETCH_WIDTH};
 
 // Number of operations to perform in each benchmark
 const OPERATION_COUNT: usize = 1000;
 
+// Number of the distinct keys of the skewed workload.
+const WORKLOAD_KEY_COUNT: usize = 10000;
+
+// Number of the requests of the skewed workload.
+const WORKLOAD_REQUEST_COUNT: usize = 100000;
+
+// Number of the entries of the caches replaying the skewed workload.
+const WORKLOAD_CACHE_CAPACITY: usize = 500;
+
+// Number of the one-off keys of each scan burst in the skewed workload.
+const WORKLOAD_SCAN_LENGTH: usize = 2000;
+
+// Number of the requests between the scan bursts in the skewed workload.
+const WORKLOAD_SCAN_INTERVAL: usize = 10000;
+
+/// Xorshift is the deterministic pseudo-random number generator, so the workloads are the
+/// same in each run.
+struct Xorshift(u64);
+
+impl Xorshift {
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// skewed_workload generates the requests whose keys follow the Zipf distribution, mixed
+/// with the bursts of the one-off keys like the scans of the large datasets.
+fn skewed_workload() -> Vec<String> {
+    let mut cdf = Vec::with_capacity(WORKLOAD_KEY_COUNT);
+    let mut total = 0.0;
+    for rank in 1..=WORKLOAD_KEY_COUNT {
+        total += 1.0 / (rank as f64).powf(0.9);
+        cdf.push(total);
+    }
+
+    let mut rng = Xorshift(0x2545f4914f6cdd1d);
+    let mut requests = Vec::with_capacity(WORKLOAD_REQUEST_COUNT);
+    for i in 0..WORKLOAD_REQUEST_COUNT {
+        if i % WORKLOAD_SCAN_INTERVAL == 0 {
+            for j in 0..WORKLOAD_SCAN_LENGTH {
+                requests.push(format!("scan{}-{}", i, j));
+            }
+        }
+
+        let sample = rng.next_f64() * total;
+        let rank = cdf.partition_point(|&value| value < sample);
+        requests.push(format!("key{}", rank));
+    }
+
+    requests
+}
+
+/// replay_lru replays the requests with the LRU cache and returns the hit ratio.
+fn replay_lru(requests: &[String]) -> f64 {
+    let mut cache = LruCache::new(WORKLOAD_CACHE_CAPACITY);
+    let mut hits = 0;
+    for key in requests {
+        if cache.get(key).is_some() {
+            hits += 1;
+        } else {
+            cache.put(key.clone(), ());
+        }
+    }
+
+    hits as f64 / requests.len() as f64
+}
+
+/// replay_tiny_lfu replays the requests with the TinyLFU cache and returns the hit ratio.
+fn replay_tiny_lfu(requests: &[String]) -> f64 {
+    let mut cache = TinyLfuCache::new(WORKLOAD_CACHE_CAPACITY as u64, DEFAULT_SKETCH_WIDTH);
+    let mut hits = 0;
+    for key in requests {
+        if cache.get(key).is_some() {
+            hits += 1;
+        } else {
+            cache.put(key.clone(), (), 1);
+        }
+    }
+
+    hits as f64 / requests.len() as f64
+}
+
 pub fn lru_cache_put(c: &mut Criterion) {
     let mut group = c.benchmark_group("Lru Cache Put");
 
@@ -435,6 +521,36 @@ pub fn lru_cache_pop_lru(c: &mut Criterion) {
     group.finish();
 }
 
+pub fn cache_hit_ratio(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Cache Hit Ratio");
+    let requests = skewed_workload();
+
+    println!(
+        "skewed workload hit ratio: lru {:.4}, tinylfu {:.4}",
+        replay_lru(&requests),
+        replay_tiny_lfu(&requests)
+    );
+
+    group.sample_size(10);
+    group.bench_with_input(
+        BenchmarkId::new("Cache Hit Ratio", "lru"),
+        &requests,
+        |b, requests| {
+            b.iter(|| black_box(replay_lru(requests)));
+        },
+    );
+
+    group.bench_with_input(
+        BenchmarkId::new("Cache Hit Ratio", "tinylfu"),
+        &requests,
+        |b, requests| {
+            b.iter(|| black_box(replay_tiny_lfu(requests)));
+        },
+    );
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     lru_cache_put,
@@ -443,6 +559,7 @@ criterion_group!(
     lru_cache_contains,
     lru_cache_pop,
     lru_cache_pop_lru,
+    cache_hit_ratio,
 );
 
 criterion_main!(benches);
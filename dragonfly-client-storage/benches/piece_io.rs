@@ -0,0 +1,150 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use bytesize::ByteSize;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dragonfly_client_config::dfdaemon::{Config, IoEngineType, Storage};
+use dragonfly_client_storage::content::Content;
+use dragonfly_client_storage::metadata::Piece;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+use tokio::runtime::Runtime;
+
+// Number of pieces to write/read in each benchmark.
+const PIECE_COUNT: u64 = 16;
+
+// Task id of the benchmark.
+const TASK_ID: &str = "d3c4b5a6978800112233445566778899aabbccddeeff00112233445566778899";
+
+// Io engines to compare in each benchmark.
+const IO_ENGINES: [IoEngineType; 3] = [
+    IoEngineType::Standard,
+    IoEngineType::Direct,
+    IoEngineType::IoUring,
+];
+
+fn create_content(rt: &Runtime, dir: &Path, io_engine: IoEngineType, piece_length: u64) -> Content {
+    let config = Config {
+        storage: Storage {
+            io_engine,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    rt.block_on(async {
+        let content = Content::new(Arc::new(config), dir).await.unwrap();
+        content
+            .create_task(TASK_ID, piece_length * PIECE_COUNT)
+            .await
+            .unwrap();
+        content
+    })
+}
+
+pub fn write_piece(c: &mut Criterion) {
+    let rt: Runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("Write Piece");
+    let piece_length = ByteSize::mib(4).as_u64();
+    let data = vec![1u8; piece_length as usize];
+    group.throughput(Throughput::Bytes(piece_length * PIECE_COUNT));
+
+    for io_engine in IO_ENGINES {
+        let dir = tempfile::tempdir().unwrap();
+        let content = create_content(&rt, dir.path(), io_engine, piece_length);
+        group.bench_with_input(
+            BenchmarkId::new("Write Piece", io_engine),
+            &content,
+            |b, content| {
+                b.iter(|| {
+                    rt.block_on(async {
+                        for i in 0..PIECE_COUNT {
+                            content
+                                .write_piece(
+                                    TASK_ID,
+                                    i as u32,
+                                    i * piece_length,
+                                    piece_length,
+                                    &mut Cursor::new(data.as_slice()),
+                                )
+                                .await
+                                .unwrap();
+                        }
+                    });
+                });
+            },
+        );
+    }
+
+    group.finish();
+}
+
+pub fn read_piece(c: &mut Criterion) {
+    let rt: Runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("Read Piece");
+    let piece_length = ByteSize::mib(4).as_u64();
+    let data = vec![1u8; piece_length as usize];
+    group.throughput(Throughput::Bytes(piece_length * PIECE_COUNT));
+
+    for io_engine in IO_ENGINES {
+        let dir = tempfile::tempdir().unwrap();
+        let content = create_content(&rt, dir.path(), io_engine, piece_length);
+        rt.block_on(async {
+            for i in 0..PIECE_COUNT {
+                content
+                    .write_piece(
+                        TASK_ID,
+                        i as u32,
+                        i * piece_length,
+                        piece_length,
+                        &mut Cursor::new(data.as_slice()),
+                    )
+                    .await
+                    .unwrap();
+            }
+        });
+
+        group.bench_with_input(
+            BenchmarkId::new("Read Piece", io_engine),
+            &content,
+            |b, content| {
+                b.iter(|| {
+                    rt.block_on(async {
+                        for i in 0..PIECE_COUNT {
+                            let piece = Piece {
+                                offset: i * piece_length,
+                                length: piece_length,
+                                ..Default::default()
+                            };
+                            let mut reader =
+                                content.read_piece(TASK_ID, &piece, None).await.unwrap();
+                            let mut buffer = Vec::with_capacity(piece_length as usize);
+                            reader.read_to_end(&mut buffer).await.unwrap();
+                        }
+                    });
+                });
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, write_piece, read_piece,);
+
+criterion_main!(benches);
@@ -0,0 +1,728 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::{metadata, Storage};
+use dragonfly_client_core::{
+    error::{ErrorType, OrErr},
+    Error, Result,
+};
+use dragonfly_client_util::{
+    digest::{Algorithm, Digest, Hasher, SEPARATOR},
+    http::hashmap_to_headermap,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Cursor;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::{error, info, instrument, warn};
+
+/// BUNDLE_MAGIC is the magic number at the beginning of the bundle.
+pub const BUNDLE_MAGIC: &[u8; 8] = b"DFBUNDLE";
+
+/// BUNDLE_VERSION is the version of the bundle format, the bundle whose version is greater
+/// than it can not be imported.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// MAX_BUNDLE_HEADER_LENGTH is the maximum length of the header of the task in the bundle,
+/// it prevents the corrupted bundle from allocating the huge memory.
+const MAX_BUNDLE_HEADER_LENGTH: u64 = 64 * 1024 * 1024;
+
+/// MAX_BUNDLE_PIECE_LENGTH is the maximum length of the piece in the bundle, it is the same as
+/// the maximum piece length of the dfdaemon, and prevents the corrupted bundle from allocating
+/// the huge memory for the content of the piece.
+const MAX_BUNDLE_PIECE_LENGTH: u64 = 64 * 1024 * 1024;
+
+/// BUNDLE_TASK_ID_LENGTH is the length of the task id in the bundle, the task id is the hex
+/// encoded sha256 digest.
+const BUNDLE_TASK_ID_LENGTH: usize = 64;
+
+/// BundleTask is the header of the task in the bundle, the content of the pieces follows the
+/// header in the order of the piece number.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleTask {
+    /// id is the task id.
+    id: String,
+
+    /// piece_length is the length of the piece.
+    piece_length: u64,
+
+    /// content_length is the length of the content.
+    content_length: u64,
+
+    /// response_header is the header of the response.
+    response_header: HashMap<String, String>,
+
+    /// application is the application of the task.
+    application: Option<String>,
+
+    /// tag is the tag of the task.
+    tag: Option<String>,
+
+    /// digest is the sha256 digest of the content.
+    digest: Option<String>,
+
+    /// pieces are the pieces of the task.
+    pieces: Vec<BundlePiece>,
+}
+
+/// BundleTask implements the header of the task in the bundle.
+impl BundleTask {
+    /// validate validates the task before the content is read. The task id must be the lower
+    /// case hex encoded sha256 digest, because it is joined into the path of the content. The
+    /// pieces must be numbered in order and contiguous, all of them except the last one must
+    /// have the piece length, and their lengths must sum to the content length.
+    fn validate(&self) -> Result<()> {
+        if self.id.len() != BUNDLE_TASK_ID_LENGTH
+            || !self
+                .id
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+        {
+            return Err(Error::ValidationError(format!(
+                "invalid task id {:?}",
+                self.id
+            )));
+        }
+
+        if self.piece_length == 0 || self.piece_length > MAX_BUNDLE_PIECE_LENGTH {
+            return Err(Error::ValidationError(format!(
+                "invalid piece length {} of task {}",
+                self.piece_length, self.id
+            )));
+        }
+
+        let mut offset = 0;
+        for (index, piece) in self.pieces.iter().enumerate() {
+            let is_last = index + 1 == self.pieces.len();
+            if piece.number != index as u32
+                || piece.offset != offset
+                || piece.length == 0
+                || piece.length > self.piece_length
+                || (!is_last && piece.length != self.piece_length)
+            {
+                return Err(Error::ValidationError(format!(
+                    "invalid piece {} of task {}: offset {}, length {}",
+                    piece.number, self.id, piece.offset, piece.length
+                )));
+            }
+
+            offset += piece.length;
+        }
+
+        if offset != self.content_length {
+            return Err(Error::ValidationError(format!(
+                "pieces of task {} sum to {}, but content length is {}",
+                self.id, offset, self.content_length
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// BundlePiece is the piece of the task in the bundle, the content of the piece is stored
+/// in plaintext, so the bundle can be imported by the dfdaemon with the different compression
+/// and encryption configuration.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundlePiece {
+    /// number is the piece number.
+    number: u32,
+
+    /// offset is the offset of the piece in the task.
+    offset: u64,
+
+    /// length is the length of the piece.
+    length: u64,
+
+    /// digest is the digest of the piece.
+    digest: String,
+}
+
+/// BundleReport is the report of exporting or importing the bundle.
+#[derive(Debug, Default)]
+pub struct BundleReport {
+    /// tasks are the ids of the exported or imported tasks.
+    pub tasks: Vec<String>,
+
+    /// pieces is the number of the exported or imported pieces.
+    pub pieces: usize,
+
+    /// bytes is the number of the exported or imported content bytes.
+    pub bytes: u64,
+
+    /// skipped_tasks are the ids of the tasks which are skipped, because they are not finished
+    /// when exporting or already exist when importing.
+    pub skipped_tasks: Vec<String>,
+}
+
+/// Storage implements the bundle of the storage.
+impl Storage {
+    /// export_bundle writes the finished tasks with their metadatas and contents into the
+    /// bundle, so they can be imported into the storage of another dfdaemon without network.
+    /// If task_ids is empty, all the finished tasks are exported. The bundle is formatted as
+    /// the magic number and the version, followed by the length prefixed json header and the
+    /// content of every task, and is terminated by the zero length header.
+    #[instrument(skip_all)]
+    pub async fn export_bundle<W: AsyncWrite + Unpin + ?Sized>(
+        &self,
+        task_ids: &[String],
+        writer: &mut W,
+    ) -> Result<BundleReport> {
+        let mut report = BundleReport::default();
+        let tasks = if task_ids.is_empty() {
+            self.metadata.get_tasks()?
+        } else {
+            let mut tasks = Vec::with_capacity(task_ids.len());
+            for task_id in task_ids {
+                match self.metadata.get_task(task_id)? {
+                    Some(task) => tasks.push(task),
+                    None => return Err(Error::TaskNotFound(task_id.clone())),
+                }
+            }
+
+            tasks
+        };
+
+        writer.write_all(BUNDLE_MAGIC).await?;
+        writer.write_u32(BUNDLE_VERSION).await?;
+        for task in tasks.iter() {
+            let Some(pieces) = self.get_bundle_pieces(task)? else {
+                warn!("task {} is not finished, skip to export it", task.id);
+                report.skipped_tasks.push(task.id.clone());
+                continue;
+            };
+
+            let header = serde_json::to_vec(&BundleTask {
+                id: task.id.clone(),
+                piece_length: task.piece_length().unwrap_or_default(),
+                content_length: task.content_length().unwrap_or_default(),
+                response_header: task.response_header.clone(),
+                application: task.application.clone(),
+                tag: task.tag.clone(),
+                digest: task.digest.clone(),
+                pieces: pieces
+                    .iter()
+                    .map(|piece| BundlePiece {
+                        number: piece.number,
+                        offset: piece.offset,
+                        length: piece.length,
+                        digest: piece.digest.clone(),
+                    })
+                    .collect(),
+            })
+            .or_err(ErrorType::SerializeError)?;
+            writer.write_u64(header.len() as u64).await?;
+            writer.write_all(&header).await?;
+
+            // Write the decoded content of the pieces.
+            for piece in pieces.iter() {
+                let reader = self.content.read_piece(&task.id, piece, None).await?;
+                let length = tokio::io::copy(&mut reader.take(piece.length), writer).await?;
+                if length != piece.length {
+                    return Err(Error::ContentLengthMismatch(piece.length, length));
+                }
+
+                report.pieces += 1;
+                report.bytes += length;
+            }
+
+            info!("export task {} to bundle", task.id);
+            report.tasks.push(task.id.clone());
+        }
+
+        writer.write_u64(0).await?;
+        writer.flush().await?;
+        Ok(report)
+    }
+
+    /// import_bundle reads the tasks from the bundle and stores their metadatas and contents,
+    /// the pieces are verified by their digests and stored by the compression and encryption
+    /// of the storage. The tasks which already finished in the storage are skipped. It must be
+    /// called when the storage is not used by the running dfdaemon.
+    #[instrument(skip_all)]
+    pub async fn import_bundle<R: AsyncRead + Unpin + ?Sized>(
+        &self,
+        reader: &mut R,
+    ) -> Result<BundleReport> {
+        let mut magic = [0; BUNDLE_MAGIC.len()];
+        reader.read_exact(&mut magic).await?;
+        if magic != *BUNDLE_MAGIC {
+            return Err(Error::InvalidParameter);
+        }
+
+        let version = reader.read_u32().await?;
+        if version > BUNDLE_VERSION {
+            return Err(Error::Unsupported(format!("bundle version {}", version)));
+        }
+
+        let mut report = BundleReport::default();
+        loop {
+            let header_length = reader.read_u64().await?;
+            if header_length == 0 {
+                break;
+            }
+
+            if header_length > MAX_BUNDLE_HEADER_LENGTH {
+                return Err(Error::InvalidParameter);
+            }
+
+            let mut header = vec![0; header_length as usize];
+            reader.read_exact(&mut header).await?;
+            let task: BundleTask =
+                serde_json::from_slice(&header).or_err(ErrorType::SerializeError)?;
+            task.validate()?;
+
+            let existing_task = self.metadata.get_task(&task.id)?;
+            if let Some(existing_task) = existing_task.as_ref() {
+                if existing_task.is_finished() {
+                    warn!("task {} already exists, skip to import it", task.id);
+                    tokio::io::copy(
+                        &mut (&mut *reader).take(task.content_length),
+                        &mut tokio::io::sink(),
+                    )
+                    .await?;
+                    report.skipped_tasks.push(task.id);
+                    continue;
+                }
+
+                // The unfinished task is imported only if it has the same layout as the task
+                // in the bundle, otherwise its pieces are overwritten by the different layout.
+                if existing_task
+                    .piece_length
+                    .is_some_and(|piece_length| piece_length != task.piece_length)
+                    || existing_task
+                        .content_length()
+                        .is_some_and(|content_length| content_length != task.content_length)
+                {
+                    return Err(Error::ValidationError(format!(
+                        "task {} already exists with the different piece length or content length",
+                        task.id
+                    )));
+                }
+            }
+
+            if let Err(err) = self.import_bundle_task(&task, reader, &mut report).await {
+                error!("import task {} failed: {}", task.id, err);
+
+                // Only the task created by the import is deleted, the unfinished task which
+                // already exists keeps its pieces.
+                if existing_task.is_none() {
+                    self.delete_task(&task.id).await;
+                }
+
+                return Err(err);
+            }
+
+            info!("import task {} from bundle", task.id);
+            report.tasks.push(task.id);
+        }
+
+        Ok(report)
+    }
+
+    /// get_bundle_pieces returns the finished pieces of the task in the order of the piece
+    /// number, and returns None if the task is not finished or misses any piece.
+    fn get_bundle_pieces(&self, task: &metadata::Task) -> Result<Option<Vec<metadata::Piece>>> {
+        if !task.is_finished() {
+            return Ok(None);
+        }
+
+        let mut pieces = self
+            .metadata
+            .get_pieces(&task.id)?
+            .into_iter()
+            .filter(|piece| piece.is_finished())
+            .collect::<Vec<_>>();
+        pieces.sort_by_key(|piece| piece.number);
+
+        let length: u64 = pieces.iter().map(|piece| piece.length).sum();
+        if Some(length) != task.content_length() {
+            return Ok(None);
+        }
+
+        Ok(Some(pieces))
+    }
+
+    /// import_bundle_task stores the metadatas and the contents of the task read from the
+    /// bundle.
+    async fn import_bundle_task<R: AsyncRead + Unpin + ?Sized>(
+        &self,
+        task: &BundleTask,
+        reader: &mut R,
+        report: &mut BundleReport,
+    ) -> Result<()> {
+        if !self.content.has_enough_space(task.content_length)? {
+            return Err(Error::NoSpace(format!(
+                "not enough space to import task {}",
+                task.id
+            )));
+        }
+
+        self.content
+            .create_task(&task.id, task.content_length)
+            .await?;
+        self.metadata.download_task_started(
+            &task.id,
+            Some(task.piece_length),
+            Some(task.content_length),
+            Some(hashmap_to_headermap(&task.response_header)?),
+            task.application.clone(),
+            task.tag.clone(),
+            task.digest.clone(),
+        )?;
+
+        for piece in task.pieces.iter() {
+            let mut content = vec![0; piece.length as usize];
+            reader.read_exact(&mut content).await?;
+
+            // Verify the content of the piece by the digest in the bundle.
+            let algorithm = piece
+                .digest
+                .split_once(SEPARATOR)
+                .and_then(|(algorithm, _)| algorithm.parse::<Algorithm>().ok())
+                .ok_or(Error::InvalidParameter)?;
+            let mut hasher = Hasher::new(algorithm);
+            hasher.update(&content);
+            let digest = hasher.finalize().to_string();
+            if digest != piece.digest {
+                return Err(Error::DigestMismatch(piece.digest.clone(), digest));
+            }
+
+            let piece_id = self.metadata.piece_id(&task.id, piece.number);
+            self.metadata
+                .download_piece_started(&piece_id, piece.number)?;
+            let response = self
+                .content
+                .write_piece(
+                    &task.id,
+                    piece.number,
+                    piece.offset,
+                    piece.length,
+                    &mut Cursor::new(content),
+                )
+                .await?;
+
+            let digest = Digest::new(self.content.piece_digest_algorithm(), response.hash);
+            self.metadata.download_piece_finished(
+                &piece_id,
+                piece.offset,
+                piece.length,
+                digest.to_string().as_str(),
+                None,
+                response.compressed_length,
+                response.encryption,
+            )?;
+
+            report.pieces += 1;
+            report.bytes += piece.length;
+        }
+
+        self.metadata.download_task_finished(&task.id)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dragonfly_client_config::dfdaemon::Config;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    async fn new_storage(dir: &std::path::Path) -> Storage {
+        Storage::new(Arc::new(Config::default()), dir, dir.join("log"))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_export_and_import_bundle() {
+        let dir = tempdir().unwrap();
+        let storage = new_storage(dir.path()).await;
+
+        // Download a finished task with two pieces and an unfinished task.
+        let finished_task_id = "6b2c9e1f0a3d4c5b8e7f6a5d4c3b2a1f0e9d8c7b6a5f4e3d2c1b0a9f8e7d6c5b";
+        let unfinished_task_id = "c4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5";
+        storage
+            .download_task_started(finished_task_id, 4, 8, None, None)
+            .await
+            .unwrap();
+        for (number, content) in [b"hell", b"o, w"].iter().enumerate() {
+            let piece_id = storage.piece_id(finished_task_id, number as u32);
+            storage
+                .download_piece_started(&piece_id, number as u32)
+                .await
+                .unwrap();
+            storage
+                .download_piece_from_source_finished(
+                    &piece_id,
+                    finished_task_id,
+                    number as u32,
+                    number as u64 * 4,
+                    4,
+                    &mut Cursor::new(content.to_vec()),
+                    Duration::from_secs(10),
+                )
+                .await
+                .unwrap();
+        }
+        storage.download_task_finished(finished_task_id).unwrap();
+        storage
+            .download_task_started(unfinished_task_id, 4, 8, None, None)
+            .await
+            .unwrap();
+
+        let mut bundle = Vec::new();
+        let report = storage.export_bundle(&[], &mut bundle).await.unwrap();
+        assert_eq!(report.tasks, vec![finished_task_id.to_string()]);
+        assert_eq!(report.skipped_tasks, vec![unfinished_task_id.to_string()]);
+        assert_eq!(report.pieces, 2);
+        assert_eq!(report.bytes, 8);
+        assert!(bundle.starts_with(BUNDLE_MAGIC));
+
+        // Import the bundle into another storage.
+        let another_dir = tempdir().unwrap();
+        let another_storage = new_storage(another_dir.path()).await;
+        let report = another_storage
+            .import_bundle(&mut Cursor::new(bundle.clone()))
+            .await
+            .unwrap();
+        assert_eq!(report.tasks, vec![finished_task_id.to_string()]);
+        assert_eq!(report.pieces, 2);
+
+        let task = another_storage.get_task(finished_task_id).unwrap().unwrap();
+        assert!(task.is_finished());
+        assert_eq!(task.content_length(), Some(8));
+
+        let piece = another_storage
+            .get_piece(&another_storage.piece_id(finished_task_id, 1))
+            .unwrap()
+            .unwrap();
+        assert!(piece.is_finished());
+        assert_eq!(piece.offset, 4);
+
+        // The imported task is exported to the same bundle.
+        let mut another_bundle = Vec::new();
+        another_storage
+            .export_bundle(&[], &mut another_bundle)
+            .await
+            .unwrap();
+        assert_eq!(another_bundle, bundle);
+
+        // The finished task is skipped when the bundle is imported again.
+        let report = another_storage
+            .import_bundle(&mut Cursor::new(bundle))
+            .await
+            .unwrap();
+        assert!(report.tasks.is_empty());
+        assert_eq!(report.skipped_tasks, vec![finished_task_id.to_string()]);
+    }
+
+    #[tokio::test]
+    async fn should_reject_corrupted_bundle() {
+        let dir = tempdir().unwrap();
+        let storage = new_storage(dir.path()).await;
+
+        let task_id = "6b2c9e1f0a3d4c5b8e7f6a5d4c3b2a1f0e9d8c7b6a5f4e3d2c1b0a9f8e7d6c5b";
+        storage
+            .download_task_started(task_id, 4, 4, None, None)
+            .await
+            .unwrap();
+        let piece_id = storage.piece_id(task_id, 0);
+        storage.download_piece_started(&piece_id, 0).await.unwrap();
+        storage
+            .download_piece_from_source_finished(
+                &piece_id,
+                task_id,
+                0,
+                0,
+                4,
+                &mut Cursor::new(b"test".to_vec()),
+                Duration::from_secs(10),
+            )
+            .await
+            .unwrap();
+        storage.download_task_finished(task_id).unwrap();
+
+        let mut bundle = Vec::new();
+        storage
+            .export_bundle(&[task_id.to_string()], &mut bundle)
+            .await
+            .unwrap();
+
+        // Corrupt the content of the piece, which is followed by the zero length header.
+        let index = bundle.len() - 8 - 1;
+        bundle[index] ^= 0xff;
+
+        let another_dir = tempdir().unwrap();
+        let another_storage = new_storage(another_dir.path()).await;
+        assert!(matches!(
+            another_storage
+                .import_bundle(&mut Cursor::new(bundle))
+                .await,
+            Err(Error::DigestMismatch(_, _))
+        ));
+        assert!(another_storage.get_task(task_id).unwrap().is_none());
+
+        // The bundle without the magic number is rejected.
+        assert!(another_storage
+            .import_bundle(&mut Cursor::new(b"NOBUNDLE".to_vec()))
+            .await
+            .is_err());
+    }
+
+    fn new_bundle(task: &BundleTask, content: &[u8]) -> Vec<u8> {
+        let header = serde_json::to_vec(task).unwrap();
+        let mut bundle = BUNDLE_MAGIC.to_vec();
+        bundle.extend_from_slice(&BUNDLE_VERSION.to_be_bytes());
+        bundle.extend_from_slice(&(header.len() as u64).to_be_bytes());
+        bundle.extend_from_slice(&header);
+        bundle.extend_from_slice(content);
+        bundle.extend_from_slice(&0u64.to_be_bytes());
+        bundle
+    }
+
+    fn new_bundle_task(task_id: &str, pieces: &[(u64, &[u8])]) -> BundleTask {
+        BundleTask {
+            id: task_id.to_string(),
+            piece_length: 4,
+            content_length: pieces.iter().map(|(_, content)| content.len() as u64).sum(),
+            response_header: HashMap::new(),
+            application: None,
+            tag: None,
+            digest: None,
+            pieces: pieces
+                .iter()
+                .enumerate()
+                .map(|(number, (offset, content))| {
+                    let mut hasher = Hasher::new(Algorithm::Crc32);
+                    hasher.update(content);
+                    BundlePiece {
+                        number: number as u32,
+                        offset: *offset,
+                        length: content.len() as u64,
+                        digest: hasher.finalize().to_string(),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_reject_bundle_with_invalid_pieces() {
+        let dir = tempdir().unwrap();
+        let storage = new_storage(dir.path()).await;
+        let task_id = "7c3d0f2a1b4e5d6c9f8a7b6e5d4c3b2a1f0e9d8c7b6a5f4e3d2c1b0a9f8e7d6c";
+
+        // The pieces are not contiguous.
+        let task = new_bundle_task(task_id, &[(0, b"test"), (8, b"data")]);
+        assert!(matches!(
+            storage
+                .import_bundle(&mut Cursor::new(new_bundle(&task, b"testdata")))
+                .await,
+            Err(Error::ValidationError(_))
+        ));
+
+        // The pieces do not sum to the content length.
+        let mut task = new_bundle_task(task_id, &[(0, b"test")]);
+        task.content_length = 8;
+        assert!(matches!(
+            storage
+                .import_bundle(&mut Cursor::new(new_bundle(&task, b"test")))
+                .await,
+            Err(Error::ValidationError(_))
+        ));
+
+        // The huge piece is rejected before the content is allocated.
+        let mut task = new_bundle_task(task_id, &[(0, b"test")]);
+        task.piece_length = u64::MAX;
+        task.content_length = u64::MAX;
+        task.pieces[0].length = u64::MAX;
+        assert!(matches!(
+            storage
+                .import_bundle(&mut Cursor::new(new_bundle(&task, b"test")))
+                .await,
+            Err(Error::ValidationError(_))
+        ));
+        assert!(storage.get_task(task_id).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn should_reject_bundle_with_invalid_task_id() {
+        let dir = tempdir().unwrap();
+        let storage = new_storage(dir.path()).await;
+
+        for task_id in [
+            "../../../../etc/cron.d/x",
+            "ab",
+            "\u{e9}\u{e9}",
+            "7C3D0F2A1B4E5D6C9F8A7B6E5D4C3B2A1F0E9D8C7B6A5F4E3D2C1B0A9F8E7D6C",
+            "../../../../../../../../../../../../../../../../../../../../etcx",
+        ] {
+            let task = new_bundle_task(task_id, &[(0, b"test")]);
+            assert!(matches!(
+                storage
+                    .import_bundle(&mut Cursor::new(new_bundle(&task, b"test")))
+                    .await,
+                Err(Error::ValidationError(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn should_keep_unfinished_task_when_import_failed() {
+        let dir = tempdir().unwrap();
+        let storage = new_storage(dir.path()).await;
+
+        // The unfinished task has the first piece.
+        let task_id = "8d4e1f3a2b5c6d7e0f9a8b7c6d5e4f3a2b1c0d9e8f7a6b5c4d3e2f1a0b9c8d7e";
+        storage
+            .download_task_started(task_id, 4, 8, None, None)
+            .await
+            .unwrap();
+        let piece_id = storage.piece_id(task_id, 0);
+        storage.download_piece_started(&piece_id, 0).await.unwrap();
+        storage
+            .download_piece_from_source_finished(
+                &piece_id,
+                task_id,
+                0,
+                0,
+                4,
+                &mut Cursor::new(b"test".to_vec()),
+                Duration::from_secs(10),
+            )
+            .await
+            .unwrap();
+
+        // The second piece of the bundle is corrupted.
+        let mut task = new_bundle_task(task_id, &[(0, b"test"), (4, b"data")]);
+        task.pieces[1].digest = "crc32:0".to_string();
+        assert!(matches!(
+            storage
+                .import_bundle(&mut Cursor::new(new_bundle(&task, b"testdata")))
+                .await,
+            Err(Error::DigestMismatch(_, _))
+        ));
+
+        // The unfinished task and its piece are kept.
+        assert!(storage.get_task(task_id).unwrap().is_some());
+        assert!(storage.get_piece(&piece_id).unwrap().unwrap().is_finished());
+    }
+}
@@ -285,6 +285,22 @@ impl<K: Hash + Eq, V> LruCache<K, V> {
         }
     }
 
+    /// iter_lru iterates the entries from the least recently used to the most recently used.
+    pub fn iter_lru(&self) -> impl Iterator<Item = (&K, &V)> {
+        let mut current = self.tail;
+        std::iter::from_fn(move || {
+            current.map(|entry| unsafe {
+                current = (*entry).prev;
+                (&(*entry).key, &(*entry).value)
+            })
+        })
+    }
+
+    /// len returns the number of the entries in the cache.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
     /// is_empty checks whether the cache is empty.
     pub fn is_empty(&self) -> bool {
         self.map.is_empty()
@@ -483,6 +499,26 @@ mod tests {
         assert!(cache.is_empty());
     }
 
+    #[test]
+    fn test_iter_lru() {
+        let mut cache: LruCache<String, i32> = LruCache::new(3);
+        assert_eq!(cache.iter_lru().count(), 0);
+
+        for (key, value) in [("key1", 1), ("key2", 2), ("key3", 3)] {
+            cache.put(key.to_string(), value);
+        }
+        cache.get("key1");
+
+        assert_eq!(
+            cache
+                .iter_lru()
+                .map(|(_, value)| *value)
+                .collect::<Vec<_>>(),
+            vec![2, 3, 1]
+        );
+        assert_eq!(cache.len(), 3);
+    }
+
     #[test]
     fn test_pop() {
         let mut cache: LruCache<String, i32> = LruCache::new(3);
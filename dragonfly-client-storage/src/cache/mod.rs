@@ -16,22 +16,28 @@
 
 use bytes::Bytes;
 use dragonfly_api::common::v2::Range;
-use dragonfly_client_config::dfdaemon::Config;
+use dragonfly_client_config::dfdaemon::{CachePolicyType, Config};
 use dragonfly_client_core::{Error, Result};
 use lru_cache::LruCache;
 use std::cmp::{max, min};
 use std::collections::HashMap;
 use std::io::Cursor;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use tiny_lfu::TinyLfuCache;
 use tokio::io::{AsyncRead, BufReader};
 use tokio::sync::RwLock;
 use tracing::{error, info};
 
 pub mod lru_cache;
+pub mod tiny_lfu;
 
 /// Task is the task content in the cache.
 #[derive(Clone, Debug)]
-struct Task {
+pub struct Task {
+    /// piece_length is the length of the piece.
+    piece_length: u64,
+
     /// content_length is the length of the task content.
     content_length: u64,
 
@@ -42,8 +48,9 @@ struct Task {
 /// Task implements the task content in the cache.
 impl Task {
     /// new creates a new task.
-    fn new(content_length: u64) -> Self {
+    fn new(piece_length: u64, content_length: u64) -> Self {
         Self {
+            piece_length,
             content_length,
             pieces: Arc::new(RwLock::new(HashMap::new())),
         }
@@ -67,13 +74,105 @@ impl Task {
         pieces.contains_key(id)
     }
 
+    /// piece_length returns the piece length of the task.
+    pub fn piece_length(&self) -> u64 {
+        self.piece_length
+    }
+
     /// content_length returns the content length of the task.
-    fn content_length(&self) -> u64 {
+    pub fn content_length(&self) -> u64 {
         self.content_length
     }
+
+    /// piece_count returns the count of the pieces in the task.
+    pub async fn piece_count(&self) -> usize {
+        let pieces = self.pieces.read().await;
+        pieces.len()
+    }
+
+    /// downgrade returns the weak reference of the task, which does not hold the pieces.
+    fn downgrade(&self) -> WeakTask {
+        WeakTask {
+            piece_length: self.piece_length,
+            content_length: self.content_length,
+            pieces: Arc::downgrade(&self.pieces),
+        }
+    }
+}
+
+/// WeakTask is the weak reference of the task, it is used for the tasks which bypass or are
+/// evicted from the cache, so the pieces are released once the downloads of the task are
+/// finished.
+#[derive(Clone, Debug)]
+struct WeakTask {
+    /// piece_length is the length of the piece.
+    piece_length: u64,
+
+    /// content_length is the length of the task content.
+    content_length: u64,
+
+    /// pieces is the weak reference of the pieces content of the task.
+    pieces: Weak<RwLock<HashMap<String, Bytes>>>,
+}
+
+/// WeakTask implements the weak reference of the task.
+impl WeakTask {
+    /// upgrade returns the task if the pieces are still held by the downloads.
+    fn upgrade(&self) -> Option<Task> {
+        Some(Task {
+            piece_length: self.piece_length,
+            content_length: self.content_length,
+            pieces: self.pieces.upgrade()?,
+        })
+    }
+}
+
+/// Tasks is the tasks in the cache, evicted by the cache policy.
+enum Tasks {
+    /// Lru evicts the least recently used tasks.
+    Lru(LruCache<String, Task>),
+
+    /// TinyLfu admits the new tasks only if they are accessed more frequently than the
+    /// tasks to be evicted.
+    TinyLfu(TinyLfuCache<String, Task>),
+}
+
+/// Tasks implements the tasks in the cache.
+impl Tasks {
+    /// get gets the task and records the access of the task.
+    fn get(&mut self, task_id: &str) -> Option<&Task> {
+        match self {
+            Tasks::Lru(tasks) => tasks.get(task_id),
+            Tasks::TinyLfu(tasks) => tasks.get(task_id),
+        }
+    }
+
+    /// peek peeks the task without recording the access of the task.
+    fn peek(&self, task_id: &str) -> Option<&Task> {
+        match self {
+            Tasks::Lru(tasks) => tasks.peek(task_id),
+            Tasks::TinyLfu(tasks) => tasks.peek(task_id),
+        }
+    }
+
+    /// contains checks whether the task exists.
+    fn contains(&self, task_id: &str) -> bool {
+        match self {
+            Tasks::Lru(tasks) => tasks.contains(task_id),
+            Tasks::TinyLfu(tasks) => tasks.contains(task_id),
+        }
+    }
+
+    /// pop removes and returns the task.
+    fn pop(&mut self, task_id: &str) -> Option<(String, Task)> {
+        match self {
+            Tasks::Lru(tasks) => tasks.pop(task_id),
+            Tasks::TinyLfu(tasks) => tasks.pop(task_id),
+        }
+    }
 }
 
-/// Cache is the cache for storing piece content by LRU algorithm.
+/// Cache is the cache for storing piece content by the cache policy.
 ///
 /// Cache storage:
 /// 1. Users can preheat task by caching to memory (via CacheTask) or to disk (via Task).
@@ -109,28 +208,43 @@ pub struct Cache {
     /// config is the configuration of the dfdaemon.
     config: Arc<Config>,
 
-    /// size is the size of the cache in bytes.
-    size: u64,
+    /// size is the size of the cache in bytes, it is shared by the clones of the cache.
+    size: Arc<AtomicU64>,
 
     /// capacity is the maximum capacity of the cache in bytes.
     capacity: u64,
 
     /// tasks stores the tasks with their task id.
-    tasks: Arc<RwLock<LruCache<String, Task>>>,
+    tasks: Arc<RwLock<Tasks>>,
+
+    /// bypassed stores the tasks which are not admitted or are evicted by the cache policy.
+    /// They are not counted in the cache size, and are only held by the downloads of the tasks.
+    bypassed: Arc<RwLock<HashMap<String, WeakTask>>>,
 }
 
-/// Cache implements the cache for storing piece content by LRU algorithm.
+/// Cache implements the cache for storing piece content by the cache policy.
 impl Cache {
     /// new creates a new cache with the specified capacity.
     pub fn new(config: Arc<Config>) -> Self {
-        Cache {
-            config: config.clone(),
-            size: 0,
-            capacity: config.storage.cache_capacity.as_u64(),
+        let capacity = config.storage.cache_capacity.as_u64();
+        let tasks = match config.storage.cache_policy {
             // LRU cache capacity is set to usize::MAX to avoid evicting tasks. LRU cache will evict tasks
             // by cache capacity(cache size) itself, and used pop_lru to evict the least recently
             // used task.
-            tasks: Arc::new(RwLock::new(LruCache::new(usize::MAX))),
+            CachePolicyType::Lru => Tasks::Lru(LruCache::new(usize::MAX)),
+            // TinyLFU cache is weighted by the content length of the tasks, so it evicts tasks
+            // by cache capacity itself.
+            CachePolicyType::TinyLfu => {
+                Tasks::TinyLfu(TinyLfuCache::new(capacity, tiny_lfu::DEFAULT_SKETCH_WIDTH))
+            }
+        };
+
+        Cache {
+            config: config.clone(),
+            size: Arc::new(AtomicU64::new(0)),
+            capacity,
+            tasks: Arc::new(RwLock::new(tasks)),
+            bypassed: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
@@ -142,15 +256,13 @@ impl Cache {
         piece: super::metadata::Piece,
         range: Option<Range>,
     ) -> Result<impl AsyncRead> {
-        let mut tasks = self.tasks.write().await;
-        let Some(task) = tasks.get(task_id) else {
+        let Some(task) = self.get_piece_task(task_id).await else {
             return Err(Error::TaskNotFound(task_id.to_string()));
         };
 
         let Some(piece_content) = task.read_piece(piece_id).await else {
             return Err(Error::PieceNotFound(piece_id.to_string()));
         };
-        drop(tasks);
 
         // Calculate the range of bytes to return based on the range provided.
         let (target_offset, target_length) = if let Some(range) = range {
@@ -190,8 +302,7 @@ impl Cache {
 
     /// write_piece writes the piece content to the cache.
     pub async fn write_piece(&self, task_id: &str, piece_id: &str, content: Bytes) -> Result<()> {
-        let mut tasks = self.tasks.write().await;
-        let Some(task) = tasks.get(task_id) else {
+        let Some(task) = self.get_piece_task(task_id).await else {
             return Err(Error::TaskNotFound(task_id.to_string()));
         };
 
@@ -203,11 +314,61 @@ impl Cache {
         Ok(())
     }
 
-    /// put_task puts a new task into the cache, constrained by the capacity of the cache.
-    pub async fn put_task(&mut self, task_id: &str, content_length: u64) {
+    /// get_piece_task returns the task to read or write its pieces. Reading and writing the
+    /// pieces are not the access of the task for TinyLFU, otherwise the one-off tasks will be
+    /// counted as frequently accessed by their pieces. The access is recorded once per task by
+    /// access_task.
+    async fn get_piece_task(&self, task_id: &str) -> Option<Task> {
+        let task = {
+            let mut tasks = self.tasks.write().await;
+            match *tasks {
+                Tasks::Lru(ref mut tasks) => tasks.get(task_id).cloned(),
+                Tasks::TinyLfu(ref tasks) => tasks.peek(task_id).cloned(),
+            }
+        };
+
+        match task {
+            Some(task) => Some(task),
+            None => self.get_bypassed_task(task_id).await,
+        }
+    }
+
+    /// bypass_task returns the task which bypasses the cache, it is used when the task is not
+    /// admitted by the cache policy. The pieces of the task are held by the returned task,
+    /// and released once the task and its clones are dropped.
+    pub async fn bypass_task(&self, task_id: &str, piece_length: u64, content_length: u64) -> Task {
+        let mut bypassed = self.bypassed.write().await;
+        if let Some(task) = bypassed.get(task_id).and_then(WeakTask::upgrade) {
+            return task;
+        }
+
+        // Remove the released tasks.
+        bypassed.retain(|_, task| task.pieces.strong_count() > 0);
+
+        let task = Task::new(piece_length, content_length);
+        bypassed.insert(task_id.to_string(), task.downgrade());
+        task
+    }
+
+    /// get_bypassed_task returns the task which bypasses the cache if it is not released.
+    async fn get_bypassed_task(&self, task_id: &str) -> Option<Task> {
+        let bypassed = self.bypassed.read().await;
+        bypassed.get(task_id).and_then(WeakTask::upgrade)
+    }
+
+    /// put_task puts a new task into the cache, constrained by the capacity of the cache. It
+    /// returns the task if the task is admitted by the cache policy. The evicted tasks are kept
+    /// as the weak references, so the downloads holding the evicted tasks pin their pieces and
+    /// keep writing them until the downloads are finished.
+    pub async fn put_task(
+        &self,
+        task_id: &str,
+        piece_length: u64,
+        content_length: u64,
+    ) -> Option<Task> {
         // If the content length is 0, we don't cache the task.
         if content_length == 0 {
-            return;
+            return None;
         }
 
         // If the content length is larger than the cache capacity and the task cannot be cached.
@@ -217,36 +378,109 @@ impl Cache {
                 task_id, content_length
             );
 
-            return;
+            return None;
         }
 
         let mut tasks = self.tasks.write().await;
-        while self.size + content_length > self.capacity {
-            match tasks.pop_lru() {
-                Some((_, task)) => {
-                    self.size -= task.content_length();
+        let task = Task::new(piece_length, content_length);
+        let mut evicted = Vec::new();
+        let admitted = match *tasks {
+            Tasks::Lru(ref mut tasks) => {
+                while self.size.load(Ordering::SeqCst) + content_length > self.capacity {
+                    match tasks.pop_lru() {
+                        Some(entry) => {
+                            self.size
+                                .fetch_sub(entry.1.content_length(), Ordering::SeqCst);
+                            evicted.push(entry);
+                        }
+                        None => break,
+                    }
                 }
-                None => {
-                    break;
+
+                if let Some(replaced) = tasks.put(task_id.to_string(), task.clone()) {
+                    self.size
+                        .fetch_sub(replaced.content_length(), Ordering::SeqCst);
+                    evicted.push((task_id.to_string(), replaced));
                 }
+                self.size.fetch_add(content_length, Ordering::SeqCst);
+                true
+            }
+            Tasks::TinyLfu(ref mut tasks) => {
+                // The evicted tasks include the task itself if it is not admitted.
+                self.size.fetch_add(content_length, Ordering::SeqCst);
+                evicted = tasks.put(task_id.to_string(), task.clone(), content_length);
+                for (_, evicted_task) in evicted.iter() {
+                    self.size
+                        .fetch_sub(evicted_task.content_length(), Ordering::SeqCst);
+                }
+
+                !evicted
+                    .iter()
+                    .any(|(_, evicted_task)| Arc::ptr_eq(&evicted_task.pieces, &task.pieces))
+            }
+        };
+
+        if !evicted.is_empty() {
+            let mut bypassed = self.bypassed.write().await;
+            bypassed.retain(|_, task| task.pieces.strong_count() > 0);
+            for (evicted_task_id, evicted_task) in evicted {
+                // The task which is not admitted is not held by any download yet.
+                if Arc::ptr_eq(&evicted_task.pieces, &task.pieces) {
+                    continue;
+                }
+
+                bypassed.insert(evicted_task_id, evicted_task.downgrade());
             }
         }
 
-        let task = Task::new(content_length);
-        tasks.put(task_id.to_string(), task);
-        self.size += content_length;
+        admitted.then_some(task)
     }
 
-    pub async fn delete_task(&mut self, task_id: &str) -> Result<()> {
+    /// delete_task deletes the task and its pieces from the cache.
+    pub async fn delete_task(&self, task_id: &str) -> Result<()> {
         let mut tasks = self.tasks.write().await;
         let Some((_, task)) = tasks.pop(task_id) else {
+            // The task which bypasses the cache is released by its downloads.
+            let mut bypassed = self.bypassed.write().await;
+            if bypassed.remove(task_id).is_some() {
+                return Ok(());
+            }
+
             return Err(Error::TaskNotFound(task_id.to_string()));
         };
 
-        self.size -= task.content_length();
+        self.size.fetch_sub(task.content_length(), Ordering::SeqCst);
         Ok(())
     }
 
+    /// get_task returns the task from the cache without updating the recently used order, or
+    /// the task which bypasses the cache.
+    pub async fn get_task(&self, task_id: &str) -> Option<Task> {
+        let task = {
+            let tasks = self.tasks.read().await;
+            tasks.peek(task_id).cloned()
+        };
+
+        match task {
+            Some(task) => Some(task),
+            None => self.get_bypassed_task(task_id).await,
+        }
+    }
+
+    /// access_task returns the task from the cache and records the access of the task, the
+    /// access frequency decides whether the task is admitted by TinyLFU.
+    pub async fn access_task(&self, task_id: &str) -> Option<Task> {
+        let task = {
+            let mut tasks = self.tasks.write().await;
+            tasks.get(task_id).cloned()
+        };
+
+        match task {
+            Some(task) => Some(task),
+            None => self.get_bypassed_task(task_id).await,
+        }
+    }
+
     /// contains_task checks whether the task exists in the cache.
     pub async fn contains_task(&self, id: &str) -> bool {
         let tasks = self.tasks.read().await;
@@ -255,11 +489,9 @@ impl Cache {
 
     /// contains_piece checks whether the piece exists in the specified task.
     pub async fn contains_piece(&self, task_id: &str, piece_id: &str) -> bool {
-        let tasks = self.tasks.read().await;
-        if let Some(task) = tasks.peek(task_id) {
-            task.contains(piece_id).await
-        } else {
-            false
+        match self.get_task(task_id).await {
+            Some(task) => task.contains(piece_id).await,
+            None => false,
         }
     }
 }
@@ -306,7 +538,7 @@ mod tests {
 
         for (config, expected_size, expected_capacity) in test_cases {
             let cache = Cache::new(Arc::new(config));
-            assert_eq!(cache.size, expected_size);
+            assert_eq!(cache.size.load(Ordering::SeqCst), expected_size);
             assert_eq!(cache.capacity, expected_capacity);
         }
     }
@@ -345,12 +577,13 @@ mod tests {
                     assert_eq!(cache.contains_task(task_id).await, expected_result);
                 }
                 "add" => {
-                    let task = Task::new(content_length);
-                    cache.tasks.write().await.put(task_id.to_string(), task);
+                    cache
+                        .put_task(task_id, ByteSize::mib(1).as_u64(), content_length)
+                        .await;
                     assert_eq!(cache.contains_task(task_id).await, expected_result);
                 }
                 "remove" => {
-                    cache.tasks.write().await.pop_lru();
+                    cache.delete_task(task_id).await.unwrap();
                     assert_eq!(cache.contains_task(task_id).await, expected_result);
                 }
                 _ => panic!("Unknown operation."),
@@ -367,7 +600,7 @@ mod tests {
             },
             ..Default::default()
         };
-        let mut cache = Cache::new(Arc::new(config));
+        let cache = Cache::new(Arc::new(config));
 
         let test_cases = vec![
             // Empty task should not be cached.
@@ -382,7 +615,9 @@ mod tests {
 
         for (task_id, size, should_exist) in test_cases {
             if size > 0 {
-                cache.put_task(task_id, size).await;
+                cache
+                    .put_task(task_id, ByteSize::mib(1).as_u64(), size)
+                    .await;
             }
 
             assert_eq!(cache.contains_task(task_id).await, should_exist);
@@ -398,7 +633,7 @@ mod tests {
             },
             ..Default::default()
         };
-        let mut cache = Cache::new(Arc::new(config));
+        let cache = Cache::new(Arc::new(config));
 
         let test_cases = vec![
             // Add tasks until eviction triggers.
@@ -414,12 +649,182 @@ mod tests {
 
         for (task_id, size, should_exist) in test_cases {
             if size > 0 {
-                cache.put_task(task_id, size).await;
+                cache
+                    .put_task(task_id, ByteSize::mib(1).as_u64(), size)
+                    .await;
             }
             assert_eq!(cache.contains_task(task_id).await, should_exist);
         }
     }
 
+    #[tokio::test]
+    async fn test_put_task_tiny_lfu() {
+        let config = Config {
+            storage: Storage {
+                cache_capacity: ByteSize::mib(10),
+                cache_policy: CachePolicyType::TinyLfu,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let cache = Cache::new(Arc::new(config));
+
+        // Access the hot tasks frequently.
+        for i in 0..4 {
+            let task_id = format!("hot_task_{}", i);
+            for _ in 0..4 {
+                cache.access_task(&task_id).await;
+            }
+
+            cache
+                .put_task(
+                    &task_id,
+                    ByteSize::mib(1).as_u64(),
+                    ByteSize::mib(2).as_u64(),
+                )
+                .await;
+            assert!(cache.contains_task(&task_id).await);
+        }
+
+        // The one-off tasks of the scan do not evict the hot tasks.
+        for i in 0..10 {
+            let task_id = format!("scan_task_{}", i);
+            cache.access_task(&task_id).await;
+            cache
+                .put_task(
+                    &task_id,
+                    ByteSize::mib(1).as_u64(),
+                    ByteSize::mib(2).as_u64(),
+                )
+                .await;
+        }
+
+        for i in 0..4 {
+            assert!(cache.contains_task(&format!("hot_task_{}", i)).await);
+        }
+        assert!(cache.size.load(Ordering::SeqCst) <= ByteSize::mib(10).as_u64());
+
+        cache.delete_task("hot_task_0").await.unwrap();
+        assert_eq!(cache.size.load(Ordering::SeqCst), {
+            let tasks = cache.tasks.read().await;
+            match *tasks {
+                Tasks::TinyLfu(ref tasks) => tasks.weight(),
+                Tasks::Lru(_) => unreachable!(),
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn test_bypass_task() {
+        let config = Config {
+            storage: Storage {
+                cache_capacity: ByteSize::mib(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let cache = Cache::new(Arc::new(config));
+
+        // The task larger than the capacity is not admitted, and bypasses the cache.
+        cache
+            .put_task(
+                "task1",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(2).as_u64(),
+            )
+            .await;
+        assert!(!cache.contains_task("task1").await);
+
+        let task = cache
+            .bypass_task(
+                "task1",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(2).as_u64(),
+            )
+            .await;
+        cache
+            .write_piece("task1", "piece1", Bytes::from_static(b"hello"))
+            .await
+            .unwrap();
+        assert!(cache.contains_piece("task1", "piece1").await);
+        assert_eq!(task.piece_count().await, 1);
+        assert_eq!(cache.size.load(Ordering::SeqCst), 0);
+
+        // The same task is shared by the downloads.
+        let shared_task = cache
+            .bypass_task(
+                "task1",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(2).as_u64(),
+            )
+            .await;
+        assert_eq!(shared_task.piece_count().await, 1);
+
+        // The task is released once the downloads are finished.
+        drop(task);
+        drop(shared_task);
+        assert!(cache.get_task("task1").await.is_none());
+        assert!(matches!(
+            cache
+                .write_piece("task1", "piece2", Bytes::from_static(b"world"))
+                .await,
+            Err(Error::TaskNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_evicted_task_pinned_by_download() {
+        let config = Config {
+            storage: Storage {
+                cache_capacity: ByteSize::mib(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let cache = Cache::new(Arc::new(config));
+
+        let task = cache
+            .put_task(
+                "task1",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(2).as_u64(),
+            )
+            .await
+            .unwrap();
+        cache
+            .write_piece("task1", "piece1", Bytes::from_static(b"hello"))
+            .await
+            .unwrap();
+
+        // The task is evicted while it is still held by the download.
+        assert!(cache
+            .put_task(
+                "task2",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(2).as_u64(),
+            )
+            .await
+            .is_some());
+        assert!(!cache.contains_task("task1").await);
+        assert_eq!(cache.size.load(Ordering::SeqCst), ByteSize::mib(2).as_u64());
+
+        cache
+            .write_piece("task1", "piece2", Bytes::from_static(b"world"))
+            .await
+            .unwrap();
+        assert_eq!(task.piece_count().await, 2);
+
+        // The pieces are released once the download is finished.
+        drop(task);
+        assert!(cache.get_task("task1").await.is_none());
+        assert!(matches!(
+            cache
+                .write_piece("task1", "piece3", Bytes::from_static(b"!"))
+                .await,
+            Err(Error::TaskNotFound(_))
+        ));
+    }
+
     #[tokio::test]
     async fn test_delete_task() {
         let config = Config {
@@ -429,11 +834,29 @@ mod tests {
             },
             ..Default::default()
         };
-        let mut cache = Cache::new(Arc::new(config));
+        let cache = Cache::new(Arc::new(config));
 
-        cache.put_task("task1", ByteSize::mib(1).as_u64()).await;
-        cache.put_task("task2", ByteSize::mib(1).as_u64()).await;
-        cache.put_task("task3", ByteSize::mib(1).as_u64()).await;
+        cache
+            .put_task(
+                "task1",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(1).as_u64(),
+            )
+            .await;
+        cache
+            .put_task(
+                "task2",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(1).as_u64(),
+            )
+            .await;
+        cache
+            .put_task(
+                "task3",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(1).as_u64(),
+            )
+            .await;
 
         let test_cases = vec![
             ("task1", true),
@@ -465,6 +888,45 @@ mod tests {
         assert!(!cache.contains_task("large_task").await);
     }
 
+    #[tokio::test]
+    async fn test_size_shared_by_clones() {
+        let config = Config {
+            storage: Storage {
+                cache_capacity: ByteSize::mib(10),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let cache = Cache::new(Arc::new(config));
+        let cloned_cache = cache.clone();
+
+        cache
+            .put_task(
+                "task1",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(2).as_u64(),
+            )
+            .await;
+        assert_eq!(
+            cloned_cache.size.load(Ordering::SeqCst),
+            ByteSize::mib(2).as_u64()
+        );
+
+        // Putting the same task again should replace the task instead of adding its size.
+        cloned_cache
+            .put_task(
+                "task1",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(3).as_u64(),
+            )
+            .await;
+        assert_eq!(cache.size.load(Ordering::SeqCst), ByteSize::mib(3).as_u64());
+
+        cloned_cache.delete_task("task1").await.unwrap();
+        assert_eq!(cache.size.load(Ordering::SeqCst), 0);
+        assert!(cache.get_task("task1").await.is_none());
+    }
+
     #[tokio::test]
     async fn test_contains_piece() {
         let config = Config {
@@ -474,7 +936,7 @@ mod tests {
             },
             ..Default::default()
         };
-        let mut cache = Cache::new(Arc::new(config));
+        let cache = Cache::new(Arc::new(config));
 
         let test_cases = vec![
             // Check non-existent task.
@@ -505,7 +967,9 @@ mod tests {
                     );
                 }
                 "add_task" => {
-                    cache.put_task(task_id, 1000).await;
+                    cache
+                        .put_task(task_id, ByteSize::mib(1).as_u64(), 1000)
+                        .await;
                     assert!(cache.contains_task(task_id).await);
                 }
                 "add_piece" => {
@@ -532,7 +996,7 @@ mod tests {
             },
             ..Default::default()
         };
-        let mut cache = Cache::new(Arc::new(config));
+        let cache = Cache::new(Arc::new(config));
 
         // Test writing to non-existent task.
         let test_data = b"test data".to_vec();
@@ -542,7 +1006,13 @@ mod tests {
         assert!(matches!(result, Err(Error::TaskNotFound(_))));
 
         // Create a task for testing.
-        cache.put_task("task1", ByteSize::mib(1).as_u64()).await;
+        cache
+            .put_task(
+                "task1",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(1).as_u64(),
+            )
+            .await;
         assert!(cache.contains_task("task1").await);
 
         let test_cases = vec![
@@ -574,6 +1044,8 @@ mod tests {
                 updated_at: chrono::Utc::now().naive_utc(),
                 created_at: chrono::Utc::now().naive_utc(),
                 finished_at: None,
+                compressed_length: None,
+                encryption: None,
             };
 
             let mut reader = cache
@@ -607,6 +1079,8 @@ mod tests {
                 updated_at: chrono::Utc::now().naive_utc(),
                 created_at: chrono::Utc::now().naive_utc(),
                 finished_at: None,
+                compressed_length: None,
+                encryption: None,
             };
 
             let mut reader = cache
@@ -629,7 +1103,7 @@ mod tests {
             },
             ..Default::default()
         };
-        let mut cache = Cache::new(Arc::new(config));
+        let cache = Cache::new(Arc::new(config));
 
         let piece = Piece {
             number: 0,
@@ -642,6 +1116,8 @@ mod tests {
             updated_at: chrono::Utc::now().naive_utc(),
             created_at: chrono::Utc::now().naive_utc(),
             finished_at: None,
+            compressed_length: None,
+            encryption: None,
         };
 
         let result = cache
@@ -649,7 +1125,13 @@ mod tests {
             .await;
         assert!(matches!(result, Err(Error::TaskNotFound(_))));
 
-        cache.put_task("task1", ByteSize::mib(50).as_u64()).await;
+        cache
+            .put_task(
+                "task1",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(50).as_u64(),
+            )
+            .await;
         let result = cache
             .read_piece("task1", "non_existent", piece.clone(), None)
             .await;
@@ -671,6 +1153,8 @@ mod tests {
                     updated_at: chrono::Utc::now().naive_utc(),
                     created_at: chrono::Utc::now().naive_utc(),
                     finished_at: None,
+                    compressed_length: None,
+                    encryption: None,
                 },
                 vec![
                     (None, b"hello world".to_vec()),
@@ -697,6 +1181,8 @@ mod tests {
                     updated_at: chrono::Utc::now().naive_utc(),
                     created_at: chrono::Utc::now().naive_utc(),
                     finished_at: None,
+                    compressed_length: None,
+                    encryption: None,
                 },
                 vec![
                     (None, b"rust lang".to_vec()),
@@ -723,6 +1209,8 @@ mod tests {
                     updated_at: chrono::Utc::now().naive_utc(),
                     created_at: chrono::Utc::now().naive_utc(),
                     finished_at: None,
+                    compressed_length: None,
+                    encryption: None,
                 },
                 vec![
                     (None, b"unit test".to_vec()),
@@ -753,6 +1241,8 @@ mod tests {
                     updated_at: chrono::Utc::now().naive_utc(),
                     created_at: chrono::Utc::now().naive_utc(),
                     finished_at: None,
+                    compressed_length: None,
+                    encryption: None,
                 },
                 vec![
                     // Full read.
@@ -818,8 +1308,14 @@ mod tests {
             },
             ..Default::default()
         };
-        let mut cache = Cache::new(Arc::new(config));
-        cache.put_task("task1", ByteSize::mib(1).as_u64()).await;
+        let cache = Cache::new(Arc::new(config));
+        cache
+            .put_task(
+                "task1",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(1).as_u64(),
+            )
+            .await;
 
         let content = b"test data for concurrent read".to_vec();
         cache
@@ -847,6 +1343,8 @@ mod tests {
                     updated_at: chrono::Utc::now().naive_utc(),
                     created_at: chrono::Utc::now().naive_utc(),
                     finished_at: None,
+                    compressed_length: None,
+                    encryption: None,
                 };
 
                 let range = if i % 2 == 0 {
@@ -888,8 +1386,14 @@ mod tests {
             },
             ..Default::default()
         };
-        let mut cache = Cache::new(Arc::new(config));
-        cache.put_task("task1", ByteSize::mib(1).as_u64()).await;
+        let cache = Cache::new(Arc::new(config));
+        cache
+            .put_task(
+                "task1",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(1).as_u64(),
+            )
+            .await;
 
         let cache_arc = Arc::new(cache);
         let mut join_set = tokio::task::JoinSet::new();
@@ -917,6 +1421,8 @@ mod tests {
                     updated_at: chrono::Utc::now().naive_utc(),
                     created_at: chrono::Utc::now().naive_utc(),
                     finished_at: None,
+                    compressed_length: None,
+                    encryption: None,
                 };
 
                 let mut reader = cache_clone
@@ -944,8 +1450,14 @@ mod tests {
             },
             ..Default::default()
         };
-        let mut cache = Cache::new(Arc::new(config));
-        cache.put_task("task1", ByteSize::mib(1).as_u64()).await;
+        let cache = Cache::new(Arc::new(config));
+        cache
+            .put_task(
+                "task1",
+                ByteSize::mib(1).as_u64(),
+                ByteSize::mib(1).as_u64(),
+            )
+            .await;
 
         let original_content = b"original content".to_vec();
         cache
@@ -984,6 +1496,8 @@ mod tests {
             updated_at: chrono::Utc::now().naive_utc(),
             created_at: chrono::Utc::now().naive_utc(),
             finished_at: None,
+            compressed_length: None,
+            encryption: None,
         };
 
         let mut reader = cache_arc
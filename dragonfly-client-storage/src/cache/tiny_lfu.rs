@@ -0,0 +1,471 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::lru_cache::LruCache;
+use std::borrow::Borrow;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// SKETCH_DEPTH is the number of the rows of the frequency sketch.
+const SKETCH_DEPTH: usize = 4;
+
+/// SKETCH_SEEDS are the seeds to hash the key for the rows of the frequency sketch.
+const SKETCH_SEEDS: [u64; SKETCH_DEPTH] = [
+    0xc3a5c85c97cb3127,
+    0xb492b66fbe98f273,
+    0x9ae16a3b2f90404f,
+    0xcbf29ce484222325,
+];
+
+/// MAX_FREQUENCY is the maximum frequency of the counters, the counters are 4 bits.
+const MAX_FREQUENCY: u8 = 15;
+
+/// DEFAULT_SKETCH_WIDTH is the default number of the counters in each row of the sketch.
+pub const DEFAULT_SKETCH_WIDTH: usize = 4096;
+
+/// WINDOW_PERCENT is the percent of the capacity for the window LRU.
+const WINDOW_PERCENT: u64 = 1;
+
+/// PROTECTED_PERCENT is the percent of the main capacity for the protected segment.
+const PROTECTED_PERCENT: u64 = 80;
+
+/// FrequencySketch is the count-min sketch to estimate the access frequency of the keys. The
+/// counters are halved when the number of the increments reaches the sample size, so the
+/// frequency of the keys which are no longer accessed decays.
+struct FrequencySketch {
+    /// width is the number of the counters in each row, it is the power of two.
+    width: usize,
+
+    /// counters are the counters of all the rows.
+    counters: Vec<u8>,
+
+    /// additions is the number of the increments since the last reset.
+    additions: usize,
+
+    /// sample_size is the number of the increments to reset the counters.
+    sample_size: usize,
+}
+
+/// FrequencySketch implements the frequency sketch.
+impl FrequencySketch {
+    /// new creates a new frequency sketch with the width of each row.
+    fn new(width: usize) -> Self {
+        let width = width.max(16).next_power_of_two();
+        Self {
+            width,
+            counters: vec![0; width * SKETCH_DEPTH],
+            additions: 0,
+            sample_size: width * 10,
+        }
+    }
+
+    /// increment increments the frequency of the key.
+    fn increment<Q: Hash + ?Sized>(&mut self, key: &Q) {
+        let hash = hash(key);
+        let mut added = false;
+        for row in 0..SKETCH_DEPTH {
+            let index = self.index(hash, row);
+            if self.counters[index] < MAX_FREQUENCY {
+                self.counters[index] += 1;
+                added = true;
+            }
+        }
+
+        if added {
+            self.additions += 1;
+            if self.additions >= self.sample_size {
+                self.reset();
+            }
+        }
+    }
+
+    /// frequency returns the estimated frequency of the key.
+    fn frequency<Q: Hash + ?Sized>(&self, key: &Q) -> u8 {
+        let hash = hash(key);
+        (0..SKETCH_DEPTH)
+            .map(|row| self.counters[self.index(hash, row)])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// reset halves all the counters to age the frequency.
+    fn reset(&mut self) {
+        for counter in self.counters.iter_mut() {
+            *counter >>= 1;
+        }
+
+        self.additions /= 2;
+    }
+
+    /// index returns the index of the counter of the key in the row.
+    fn index(&self, hash: u64, row: usize) -> usize {
+        let hash = (hash ^ SKETCH_SEEDS[row]).wrapping_mul(0x9e3779b97f4a7c15);
+        row * self.width + ((hash >> 32) as usize & (self.width - 1))
+    }
+}
+
+/// hash returns the hash of the key.
+fn hash<Q: Hash + ?Sized>(key: &Q) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Segment is the LRU segment of the cache with the total weight of its entries.
+struct Segment<K, V> {
+    /// entries are the entries with their weights.
+    entries: LruCache<K, (V, u64)>,
+
+    /// weight is the total weight of the entries.
+    weight: u64,
+}
+
+/// Segment implements the LRU segment.
+impl<K: Hash + Eq, V> Segment<K, V> {
+    /// new creates a new segment.
+    fn new() -> Self {
+        Self {
+            // The segment is bounded by the weight, not the number of the entries.
+            entries: LruCache::new(usize::MAX),
+            weight: 0,
+        }
+    }
+
+    /// put puts the entry as the most recently used entry.
+    fn put(&mut self, key: K, value: V, weight: u64) {
+        self.entries.put(key, (value, weight));
+        self.weight += weight;
+    }
+
+    /// pop removes the entry by key.
+    fn pop<Q>(&mut self, k: &Q) -> Option<(K, V, u64)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, (value, weight)) = self.entries.pop(k)?;
+        self.weight -= weight;
+        Some((key, value, weight))
+    }
+
+    /// pop_lru removes the least recently used entry.
+    fn pop_lru(&mut self) -> Option<(K, V, u64)> {
+        let (key, (value, weight)) = self.entries.pop_lru()?;
+        self.weight -= weight;
+        Some((key, value, weight))
+    }
+}
+
+/// TinyLfuCache is the W-TinyLFU cache bounded by the total weight of the entries. The new
+/// entries are put into the window LRU, and the entries evicted from the window are admitted
+/// into the main segmented LRU only if they are accessed more frequently than the entries
+/// to be evicted, so a scan of the one-off entries does not evict the hot entries. The main
+/// LRU is split into the probation and protected segments, the entry is promoted to the
+/// protected segment when it is accessed in the probation segment.
+pub struct TinyLfuCache<K, V> {
+    /// capacity is the maximum total weight of the entries.
+    capacity: u64,
+
+    /// window_capacity is the maximum total weight of the window segment.
+    window_capacity: u64,
+
+    /// protected_capacity is the maximum total weight of the protected segment.
+    protected_capacity: u64,
+
+    /// window is the window LRU for the new entries.
+    window: Segment<K, V>,
+
+    /// probation is the main segment for the entries accessed once in the main LRU.
+    probation: Segment<K, V>,
+
+    /// protected is the main segment for the entries accessed again in the main LRU.
+    protected: Segment<K, V>,
+
+    /// sketch estimates the access frequency of the keys.
+    sketch: FrequencySketch,
+}
+
+/// TinyLfuCache implements the W-TinyLFU cache.
+impl<K: Hash + Eq, V> TinyLfuCache<K, V> {
+    /// new creates a new cache with the capacity of the total weight.
+    pub fn new(capacity: u64, sketch_width: usize) -> Self {
+        let window_capacity = if capacity == 0 {
+            0
+        } else {
+            (capacity * WINDOW_PERCENT / 100).max(1)
+        };
+        let protected_capacity = (capacity - window_capacity) * PROTECTED_PERCENT / 100;
+        Self {
+            capacity,
+            window_capacity,
+            protected_capacity,
+            window: Segment::new(),
+            probation: Segment::new(),
+            protected: Segment::new(),
+            sketch: FrequencySketch::new(sketch_width),
+        }
+    }
+
+    /// get gets the value of the key, and records the access of the key.
+    pub fn get<Q>(&mut self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.sketch.increment(k);
+        if self.window.entries.contains(k) {
+            return self.window.entries.get(k).map(|(value, _)| value);
+        }
+
+        if self.protected.entries.contains(k) {
+            return self.protected.entries.get(k).map(|(value, _)| value);
+        }
+
+        // Promote the entry accessed in the probation segment to the protected segment, and
+        // demote the least recently used protected entries if the protected segment is full.
+        let (key, value, weight) = self.probation.pop(k)?;
+        self.protected.put(key, value, weight);
+        while self.protected.weight > self.protected_capacity {
+            let Some((key, value, weight)) = self.protected.pop_lru() else {
+                break;
+            };
+
+            self.probation.put(key, value, weight);
+        }
+
+        self.peek(k)
+    }
+
+    /// peek peeks the value of the key without recording the access.
+    pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.window
+            .entries
+            .peek(k)
+            .or_else(|| self.probation.entries.peek(k))
+            .or_else(|| self.protected.entries.peek(k))
+            .map(|(value, _)| value)
+    }
+
+    /// contains checks whether the key exists in the cache.
+    pub fn contains<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.window.entries.contains(k)
+            || self.probation.entries.contains(k)
+            || self.protected.entries.contains(k)
+    }
+
+    /// put puts the key and value with the weight into the window, and returns the evicted
+    /// entries, including the replaced entry of the key and the entry which is not admitted.
+    pub fn put(&mut self, key: K, value: V, weight: u64) -> Vec<(K, V)> {
+        let mut evicted = Vec::new();
+        if let Some(entry) = self.pop(&key) {
+            evicted.push(entry);
+        }
+
+        self.window.put(key, value, weight);
+        while self.window.weight > self.window_capacity {
+            let Some((key, value, weight)) = self.window.pop_lru() else {
+                break;
+            };
+
+            self.admit(key, value, weight, &mut evicted);
+        }
+
+        evicted
+    }
+
+    /// admit admits the candidate evicted from the window into the probation segment. If the
+    /// main segments are full, the candidate is admitted only if it is accessed at least as
+    /// frequently as each entry to be evicted for it, otherwise the candidate is evicted.
+    fn admit(&mut self, key: K, value: V, weight: u64, evicted: &mut Vec<(K, V)>) {
+        let main_capacity = self.capacity - self.window_capacity;
+        let main_weight = self.probation.weight + self.protected.weight;
+        if main_weight + weight > main_capacity {
+            if weight > main_capacity {
+                evicted.push((key, value));
+                return;
+            }
+
+            // Check the victims from the least recently used probation entries before
+            // evicting any of them.
+            let frequency = self.sketch.frequency(&key);
+            let required = main_weight + weight - main_capacity;
+            let mut released = 0;
+            for (victim, (_, victim_weight)) in self
+                .probation
+                .entries
+                .iter_lru()
+                .chain(self.protected.entries.iter_lru())
+            {
+                if released >= required {
+                    break;
+                }
+
+                if self.sketch.frequency(victim) > frequency {
+                    evicted.push((key, value));
+                    return;
+                }
+
+                released += victim_weight;
+            }
+
+            while self.probation.weight + self.protected.weight + weight > main_capacity {
+                match self
+                    .probation
+                    .pop_lru()
+                    .or_else(|| self.protected.pop_lru())
+                {
+                    Some((key, value, _)) => evicted.push((key, value)),
+                    None => break,
+                }
+            }
+        }
+
+        self.probation.put(key, value, weight);
+    }
+
+    /// pop removes and returns the entry by key.
+    pub fn pop<Q>(&mut self, k: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.window
+            .pop(k)
+            .or_else(|| self.probation.pop(k))
+            .or_else(|| self.protected.pop(k))
+            .map(|(key, value, _)| (key, value))
+    }
+
+    /// weight returns the total weight of the entries.
+    pub fn weight(&self) -> u64 {
+        self.window.weight + self.probation.weight + self.protected.weight
+    }
+
+    /// len returns the number of the entries.
+    pub fn len(&self) -> usize {
+        self.window.entries.len() + self.probation.entries.len() + self.protected.entries.len()
+    }
+
+    /// is_empty checks whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frequency_sketch() {
+        let mut sketch = FrequencySketch::new(64);
+        assert_eq!(sketch.frequency("key1"), 0);
+
+        for _ in 0..20 {
+            sketch.increment("key1");
+        }
+        sketch.increment("key2");
+        assert_eq!(sketch.frequency("key1"), MAX_FREQUENCY);
+        assert_eq!(sketch.frequency("key2"), 1);
+
+        sketch.reset();
+        assert_eq!(sketch.frequency("key1"), MAX_FREQUENCY / 2);
+        assert_eq!(sketch.frequency("key2"), 0);
+    }
+
+    #[test]
+    fn test_get_and_put() {
+        let mut cache: TinyLfuCache<String, i32> = TinyLfuCache::new(100, 1024);
+        assert!(cache.is_empty());
+
+        assert!(cache.put("key1".to_string(), 1, 10).is_empty());
+        assert!(cache.put("key2".to_string(), 2, 10).is_empty());
+        assert_eq!(cache.get("key1"), Some(&1));
+        assert_eq!(cache.peek("key2"), Some(&2));
+        assert_eq!(cache.get("key3"), None);
+        assert_eq!(cache.weight(), 20);
+        assert_eq!(cache.len(), 2);
+
+        // The replaced entry is returned.
+        assert_eq!(
+            cache.put("key1".to_string(), 10, 20),
+            vec![("key1".to_string(), 1)]
+        );
+        assert_eq!(cache.get("key1"), Some(&10));
+        assert_eq!(cache.weight(), 30);
+
+        assert_eq!(cache.pop("key2"), Some(("key2".to_string(), 2)));
+        assert!(!cache.contains("key2"));
+        assert_eq!(cache.weight(), 20);
+    }
+
+    #[test]
+    fn test_scan_resistance() {
+        let mut cache: TinyLfuCache<String, i32> = TinyLfuCache::new(100, 1024);
+
+        // Access the hot entries frequently.
+        for i in 0..9 {
+            let key = format!("hot{}", i);
+            cache.get(&key);
+            cache.put(key.clone(), i, 10);
+            for _ in 0..5 {
+                assert_eq!(cache.get(&key), Some(&i));
+            }
+        }
+
+        // The one-off entries of the scan are not admitted.
+        for i in 0..100 {
+            let key = format!("scan{}", i);
+            cache.get(&key);
+            cache.put(key, i, 10);
+        }
+
+        for i in 0..9 {
+            assert!(cache.contains(&format!("hot{}", i)));
+        }
+        assert!(cache.weight() <= 100);
+    }
+
+    #[test]
+    fn test_evict_cold_entries() {
+        let mut cache: TinyLfuCache<String, i32> = TinyLfuCache::new(100, 1024);
+        for i in 0..9 {
+            cache.put(format!("cold{}", i), i, 10);
+        }
+
+        // The new entry accessed more frequently than the cold entries is admitted.
+        for _ in 0..3 {
+            cache.get("new");
+        }
+        let evicted = cache.put("new".to_string(), 100, 30);
+        assert!(cache.contains("new"));
+        assert!(!evicted.is_empty());
+        assert!(evicted.iter().all(|(key, _)| key.starts_with("cold")));
+        assert!(cache.weight() <= 100);
+
+        // The entry larger than the main capacity is never admitted.
+        let evicted = cache.put("large".to_string(), 0, 200);
+        assert_eq!(evicted, vec![("large".to_string(), 0)]);
+    }
+}
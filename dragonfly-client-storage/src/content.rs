@@ -14,18 +14,33 @@
  * limitations under the License.
  */
 
+use crate::encryption::{piece_aad, Encryptor};
+use crate::io_engine::{AlignedBuffer, IoEngine};
+use crate::metadata::{Piece, PieceEncryption};
+use bytes::Bytes;
 use bytesize::ByteSize;
 use dragonfly_api::common::v2::Range;
 use dragonfly_client_config::dfdaemon::Config;
-use dragonfly_client_core::{Error, Result};
-use dragonfly_client_util::fs::fallocate;
+use dragonfly_client_core::{
+    error::{ErrorType, OrErr},
+    Error, Result,
+};
+use dragonfly_client_util::{
+    digest::{verify_file_digest, Algorithm, Digest, Hasher as DigestHasher},
+    fs::fallocate,
+};
 use std::cmp::{max, min};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io::Cursor;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use tokio::fs::{self, File, OpenOptions};
 use tokio::io::{
     self, AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader, BufWriter, SeekFrom,
 };
+use tokio_util::either::Either;
 use tokio_util::io::InspectReader;
 use tracing::{error, info, instrument, warn};
 use walkdir::WalkDir;
@@ -39,13 +54,48 @@ pub const DEFAULT_TASK_DIR: &str = "tasks";
 /// DEFAULT_PERSISTENT_CACHE_TASK_DIR is the default directory for store persistent cache task.
 pub const DEFAULT_PERSISTENT_CACHE_TASK_DIR: &str = "persistent-cache-tasks";
 
+/// DEFAULT_TEMP_DIR is the default directory for store the content being promoted.
+pub const DEFAULT_TEMP_DIR: &str = "tmp";
+
+/// RangeReader is the reader of the range of the content, it reads the file by the buffered
+/// I/O, or reads the content in the memory read by the io engine.
+type RangeReader = Either<io::Take<BufReader<File>>, Cursor<Bytes>>;
+
+/// ContentDir is the directory to store content, it is usually on the mount point of a disk.
+struct ContentDir {
+    /// dir is the directory to store content.
+    dir: PathBuf,
+
+    /// tier is the tier of the directory, the lower tier is the faster disk.
+    tier: u32,
+
+    /// weight is the weight of the directory in the same tier.
+    weight: u32,
+
+    /// legacy indicates the directory is the content directory of the storage.dir, it is
+    /// kept for the content stored before the storage.dirs is configured, and the new content
+    /// is not placed in it.
+    legacy: bool,
+}
+
 /// Content is the content of a piece.
 pub struct Content {
     /// config is the configuration of the dfdaemon.
     config: Arc<Config>,
 
-    /// dir is the directory to store content.
-    dir: PathBuf,
+    /// dirs are the directories to store content sorted by the tier, the directories of the
+    /// fastest tier are first.
+    dirs: Vec<ContentDir>,
+
+    /// index is the index of the directory in the dirs which stores the content by the sub
+    /// directory and the task id, so the content is found without checking the directories.
+    index: RwLock<HashMap<(&'static str, String), usize>>,
+
+    /// io_engine writes and reads the piece content if the io engine is not standard.
+    io_engine: IoEngine,
+
+    /// encryptor encrypts and decrypts the piece content if the encryption is enabled.
+    encryptor: Option<Arc<Encryptor>>,
 }
 
 /// WritePieceResponse is the response of writing a piece.
@@ -55,6 +105,14 @@ pub struct WritePieceResponse {
 
     /// hash is the hash of the piece.
     pub hash: String,
+
+    /// compressed_length is the length of the compressed piece stored in the content, None
+    /// means the piece is stored uncompressed.
+    pub compressed_length: Option<u64>,
+
+    /// encryption is the encryption of the piece stored in the content, None means the piece
+    /// is stored unencrypted.
+    pub encryption: Option<PieceEncryption>,
 }
 
 /// WritePersistentCacheTaskResponse is the response of writing a persistent cache task.
@@ -68,29 +126,134 @@ pub struct WritePersistentCacheTaskResponse {
 
 /// Content implements the content storage.
 impl Content {
-    /// new returns a new content.
+    /// new returns a new content. If the storage.dirs is empty, the content is stored in
+    /// the dir, otherwise the content is stored in the storage.dirs, and the content stored
+    /// in the dir before is still found.
     pub async fn new(config: Arc<Config>, dir: &Path) -> Result<Content> {
-        let dir = dir.join(DEFAULT_CONTENT_DIR);
+        let legacy_dir = dir.join(DEFAULT_CONTENT_DIR);
+        let mut dirs = if config.storage.dirs.is_empty() {
+            vec![ContentDir {
+                dir: legacy_dir,
+                tier: 0,
+                weight: 1,
+                legacy: false,
+            }]
+        } else {
+            let mut dirs = config
+                .storage
+                .dirs
+                .iter()
+                .map(|storage_dir| ContentDir {
+                    dir: storage_dir.path.join(DEFAULT_CONTENT_DIR),
+                    tier: storage_dir.tier,
+                    weight: storage_dir.weight,
+                    legacy: false,
+                })
+                .collect::<Vec<_>>();
+
+            // Include the content directory of the dir as the slowest tier, so the content
+            // stored before the storage.dirs is configured is still found and promoted.
+            if !dirs.iter().any(|content_dir| content_dir.dir == legacy_dir) {
+                if !config.storage.keep {
+                    fs::remove_dir_all(&legacy_dir).await.unwrap_or_else(|err| {
+                        if err.kind() != std::io::ErrorKind::NotFound {
+                            warn!("remove {:?} failed: {}", legacy_dir, err);
+                        }
+                    });
+                } else if legacy_dir.exists() {
+                    dirs.push(ContentDir {
+                        dir: legacy_dir,
+                        tier: u32::MAX,
+                        weight: 0,
+                        legacy: true,
+                    });
+                }
+            }
 
-        // If the storage is not kept, remove the directory.
-        if !config.storage.keep {
-            fs::remove_dir_all(&dir).await.unwrap_or_else(|err| {
-                warn!("remove {:?} failed: {}", dir, err);
-            });
+            dirs
+        };
+        dirs.sort_by_key(|content_dir| content_dir.tier);
+
+        for content_dir in dirs.iter() {
+            let dir = &content_dir.dir;
+
+            // If the storage is not kept, remove the directory.
+            if !config.storage.keep {
+                fs::remove_dir_all(dir).await.unwrap_or_else(|err| {
+                    warn!("remove {:?} failed: {}", dir, err);
+                });
+            }
+
+            // Remove the partial content left by the interrupted promotion.
+            if let Err(err) = fs::remove_dir_all(dir.join(DEFAULT_TEMP_DIR)).await {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    warn!("remove {:?} failed: {}", dir.join(DEFAULT_TEMP_DIR), err);
+                }
+            }
+
+            fs::create_dir_all(&dir.join(DEFAULT_TASK_DIR)).await?;
+            fs::create_dir_all(&dir.join(DEFAULT_PERSISTENT_CACHE_TASK_DIR)).await?;
+            info!(
+                "content initialized directory: {:?}, tier: {}",
+                dir, content_dir.tier
+            );
+        }
+
+        let io_engine = IoEngine::new(config.storage.io_engine, config.storage.write_buffer_size);
+        let encryptor = if config.storage.encryption.enable {
+            Some(Arc::new(Encryptor::new(config.clone()).await?))
+        } else {
+            None
+        };
+
+        let index = RwLock::new(Self::build_index(&dirs));
+        Ok(Content {
+            config,
+            dirs,
+            index,
+            io_engine,
+            encryptor,
+        })
+    }
+
+    /// build_index builds the index of the directories storing the content, if the content is
+    /// stored in multiple directories, the directory of the faster tier is indexed.
+    fn build_index(dirs: &[ContentDir]) -> HashMap<(&'static str, String), usize> {
+        let mut index = HashMap::new();
+        for (dir_index, content_dir) in dirs.iter().enumerate() {
+            for sub_dir in [DEFAULT_TASK_DIR, DEFAULT_PERSISTENT_CACHE_TASK_DIR] {
+                WalkDir::new(content_dir.dir.join(sub_dir))
+                    .min_depth(2)
+                    .max_depth(2)
+                    .into_iter()
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.file_type().is_file())
+                    .filter_map(|entry| entry.file_name().to_str().map(|name| name.to_string()))
+                    .for_each(|id| {
+                        index.entry((sub_dir, id)).or_insert(dir_index);
+                    });
+            }
         }
 
-        fs::create_dir_all(&dir.join(DEFAULT_TASK_DIR)).await?;
-        fs::create_dir_all(&dir.join(DEFAULT_PERSISTENT_CACHE_TASK_DIR)).await?;
-        info!("content initialized directory: {:?}", dir);
-        Ok(Content { config, dir })
+        index
+    }
+
+    /// reload_encryption_keys reloads the encryption keys to rotate the active key.
+    pub async fn reload_encryption_keys(&self) -> Result<()> {
+        match &self.encryptor {
+            Some(encryptor) => encryptor.reload().await,
+            None => Ok(()),
+        }
     }
 
-    /// available_space returns the available space of the disk.
+    /// available_space returns the available space of the disks.
     pub fn available_space(&self) -> Result<u64> {
         let dist_threshold = self.config.gc.policy.dist_threshold;
         if dist_threshold != ByteSize::default() {
-            let usage_space = WalkDir::new(&self.dir)
-                .into_iter()
+            let usage_space = self
+                .dirs
+                .iter()
+                .flat_map(|content_dir| WalkDir::new(&content_dir.dir))
                 .filter_map(|entry| entry.ok())
                 .filter_map(|entry| entry.metadata().ok())
                 .filter(|metadata| metadata.is_file())
@@ -108,11 +271,15 @@ impl Content {
             return Ok(dist_threshold.as_u64() - usage_space);
         }
 
-        let stat = fs2::statvfs(&self.dir)?;
-        Ok(stat.available_space())
+        let mut available_space = 0;
+        for mount in self.mounts() {
+            available_space += fs2::statvfs(mount)?.available_space();
+        }
+
+        Ok(available_space)
     }
 
-    /// total_space returns the total space of the disk.
+    /// total_space returns the total space of the disks.
     pub fn total_space(&self) -> Result<u64> {
         // If the dist_threshold is set, return it directly.
         let dist_threshold = self.config.gc.policy.dist_threshold;
@@ -120,8 +287,103 @@ impl Content {
             return Ok(dist_threshold.as_u64());
         }
 
-        let stat = fs2::statvfs(&self.dir)?;
-        Ok(stat.total_space())
+        let mut total_space = 0;
+        for mount in self.mounts() {
+            total_space += fs2::statvfs(mount)?.total_space();
+        }
+
+        Ok(total_space)
+    }
+
+    /// mounts returns the directories on the different devices, so the space of the disk
+    /// which has multiple directories is counted once. The legacy directory is not counted,
+    /// because the new content is not placed in it.
+    fn mounts(&self) -> Vec<&Path> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let mut devices = HashSet::new();
+            self.dirs
+                .iter()
+                .filter(|content_dir| !content_dir.legacy)
+                .map(|content_dir| content_dir.dir.as_path())
+                .filter(|dir| match std::fs::metadata(dir) {
+                    Ok(metadata) => devices.insert(metadata.dev()),
+                    Err(_) => true,
+                })
+                .collect()
+        }
+
+        #[cfg(not(unix))]
+        {
+            self.dirs
+                .iter()
+                .filter(|content_dir| !content_dir.legacy)
+                .map(|content_dir| content_dir.dir.as_path())
+                .collect()
+        }
+    }
+
+    /// place returns the directory to create the content of the new task. The content is placed
+    /// in the slowest tier which has enough space, and the directory in the tier is selected by
+    /// the weighted hash of the task id. If no directory has enough space, the directory with
+    /// the most available space is returned.
+    fn place(&self, task_id: &str, length: u64) -> &ContentDir {
+        if self.dirs.len() == 1 {
+            return &self.dirs[0];
+        }
+
+        let candidates = self
+            .dirs
+            .iter()
+            .filter(|content_dir| {
+                !content_dir.legacy && Self::dir_available_space(&content_dir.dir) >= length
+            })
+            .collect::<Vec<_>>();
+
+        match candidates.iter().map(|content_dir| content_dir.tier).max() {
+            Some(tier) => Self::select(
+                task_id,
+                candidates
+                    .into_iter()
+                    .filter(|content_dir| content_dir.tier == tier),
+            )
+            .unwrap_or(&self.dirs[0]),
+            None => self
+                .dirs
+                .iter()
+                .filter(|content_dir| !content_dir.legacy)
+                .max_by_key(|content_dir| Self::dir_available_space(&content_dir.dir))
+                .unwrap_or(&self.dirs[0]),
+        }
+    }
+
+    /// select selects the directory by the rendezvous hash of the task id weighted by the
+    /// weight of the directory, so the tasks are spread in proportion to the weights.
+    fn select<'a>(
+        task_id: &str,
+        content_dirs: impl Iterator<Item = &'a ContentDir>,
+    ) -> Option<&'a ContentDir> {
+        content_dirs
+            .map(|content_dir| {
+                let mut hasher = DefaultHasher::new();
+                task_id.hash(&mut hasher);
+                content_dir.dir.hash(&mut hasher);
+
+                // Map the hash to (0, 1), and the score is -weight / ln(hash).
+                let hash = (hasher.finish() as f64 + 1.0) / (u64::MAX as f64 + 2.0);
+                (content_dir, content_dir.weight as f64 / -hash.ln())
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(content_dir, _)| content_dir)
+    }
+
+    /// dir_available_space returns the available space of the disk of the directory.
+    fn dir_available_space(dir: &Path) -> u64 {
+        fs2::available_space(dir).unwrap_or_else(|err| {
+            warn!("get available space of {:?} failed: {}", dir, err);
+            0
+        })
     }
 
     /// has_enough_space checks if the storage has enough space to store the content.
@@ -182,7 +444,11 @@ impl Content {
             return Ok(task_path);
         }
 
-        let task_dir = self.dir.join(DEFAULT_TASK_DIR).join(&task_id[..3]);
+        let task_dir = self
+            .place(task_id, length)
+            .dir
+            .join(DEFAULT_TASK_DIR)
+            .join(&task_id[..3]);
         fs::create_dir_all(&task_dir).await.inspect_err(|err| {
             error!("create {:?} failed: {}", task_dir, err);
         })?;
@@ -193,11 +459,21 @@ impl Content {
                 error!("create {:?} failed: {}", task_dir, err);
             })?;
 
-        fallocate(&f, length).await.inspect_err(|err| {
-            error!("fallocate {:?} failed: {}", task_dir, err);
-        })?;
+        // The compressed pieces are stored in the sparse file, so only the compressed content
+        // occupies the disk.
+        if self.config.storage.compression.enable {
+            f.set_len(length).await.inspect_err(|err| {
+                error!("truncate {:?} failed: {}", task_dir, err);
+            })?;
+        } else {
+            fallocate(&f, length).await.inspect_err(|err| {
+                error!("fallocate {:?} failed: {}", task_dir, err);
+            })?;
+        }
 
-        Ok(task_dir.join(task_id))
+        let task_path = task_dir.join(task_id);
+        self.insert_index(DEFAULT_TASK_DIR, task_id, &task_path);
+        Ok(task_path)
     }
 
     /// Hard links the task content to the destination.
@@ -236,6 +512,69 @@ impl Content {
         Ok(())
     }
 
+    /// copy_task_by_pieces copies the plaintext content of the task to the destination piece
+    /// by piece, it is used when the pieces of the task are compressed or encrypted.
+    #[instrument(skip_all)]
+    pub async fn copy_task_by_pieces(
+        &self,
+        task_id: &str,
+        pieces: &[Piece],
+        to: &Path,
+    ) -> Result<()> {
+        self.copy_pieces(task_id, &self.get_task_path(task_id), pieces, to)
+            .await
+    }
+
+    /// link_task hard links the content of the task to the other task, the content created
+    /// for the other task is replaced. If the hard link fails, the content is copied.
+    #[instrument(skip_all)]
+    pub async fn link_task(&self, from_task_id: &str, to_task_id: &str) -> Result<()> {
+        let from_path = self.get_task_path(from_task_id);
+        for to_path in self.get_all_paths(DEFAULT_TASK_DIR, to_task_id) {
+            if let Err(err) = fs::remove_file(to_path.as_path()).await {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    error!("remove {:?} failed: {}", to_path, err);
+                    return Err(Error::IO(err));
+                }
+            }
+        }
+
+        // Link the content in the directory of the task, so the hard link is on the same disk.
+        let to_path = from_path
+            .parent()
+            .and_then(Path::parent)
+            .map(|dir| dir.join(&to_task_id[..3]).join(to_task_id))
+            .unwrap_or_else(|| self.get_task_path(to_task_id));
+
+        if let Some(parent) = to_path.parent() {
+            fs::create_dir_all(parent).await.inspect_err(|err| {
+                error!("create {:?} failed: {}", parent, err);
+            })?;
+        }
+
+        if let Err(err) = fs::hard_link(from_path.as_path(), to_path.as_path()).await {
+            warn!(
+                "hard link {:?} to {:?} failed, fallback to copy: {}",
+                from_path, to_path, err
+            );
+            fs::copy(from_path.as_path(), to_path.as_path()).await?;
+        }
+
+        self.insert_index(DEFAULT_TASK_DIR, to_task_id, &to_path);
+        info!("link {:?} to {:?} success", from_path, to_path);
+        Ok(())
+    }
+
+    /// verify_task_digest verifies the task content by the digest, the entire content is read
+    /// in the blocking thread.
+    #[instrument(skip_all)]
+    pub async fn verify_task_digest(&self, task_id: &str, digest: Digest) -> Result<()> {
+        let task_path = self.get_task_path(task_id);
+        tokio::task::spawn_blocking(move || verify_file_digest(digest, task_path.as_path()))
+            .await
+            .or_err(ErrorType::AsyncRuntimeError)?
+    }
+
     /// copy_task_by_range copies the task content to the destination by range.
     #[instrument(skip_all)]
     async fn copy_task_by_range(&self, task_id: &str, to: &Path, range: Range) -> Result<()> {
@@ -270,42 +609,122 @@ impl Content {
     /// delete_task deletes the task content.
     pub async fn delete_task(&self, task_id: &str) -> Result<()> {
         info!("delete task content: {}", task_id);
-        let task_path = self.get_task_path(task_id);
-        fs::remove_file(task_path.as_path())
-            .await
-            .inspect_err(|err| {
-                error!("remove {:?} failed: {}", task_path, err);
-            })?;
+        for task_path in self.get_all_paths(DEFAULT_TASK_DIR, task_id) {
+            fs::remove_file(task_path.as_path())
+                .await
+                .inspect_err(|err| {
+                    error!("remove {:?} failed: {}", task_path, err);
+                })?;
+        }
+
+        self.remove_index(DEFAULT_TASK_DIR, task_id);
         Ok(())
     }
 
-    /// read_piece reads the piece from the content.
+    /// promote_task moves the content of the finished task to the fastest tier if it is stored
+    /// in the slower tier, and returns whether the task is promoted. The task is not promoted
+    /// if the fastest tier has no enough space, or the content shared by the hard links needs
+    /// to be copied to the other filesystem.
+    #[instrument(skip_all)]
+    pub async fn promote_task(&self, task_id: &str, length: u64) -> Result<bool> {
+        let fastest_tier = self.dirs[0].tier;
+        let from_path = self.get_task_path(task_id);
+        if self.dirs.iter().any(|content_dir| {
+            content_dir.tier == fastest_tier && from_path.starts_with(&content_dir.dir)
+        }) {
+            return Ok(false);
+        }
+
+        let Some(content_dir) = Self::select(
+            task_id,
+            self.dirs.iter().filter(|content_dir| {
+                content_dir.tier == fastest_tier
+                    && Self::dir_available_space(&content_dir.dir) >= length
+            }),
+        ) else {
+            info!("no enough space to promote task {}", task_id);
+            return Ok(false);
+        };
+
+        let to_path = content_dir
+            .dir
+            .join(DEFAULT_TASK_DIR)
+            .join(&task_id[..3])
+            .join(task_id);
+        if let Some(parent) = to_path.parent() {
+            fs::create_dir_all(parent).await.inspect_err(|err| {
+                error!("create {:?} failed: {}", parent, err);
+            })?;
+        }
+
+        // Rename the content if the fastest tier is on the same filesystem, the inode is kept,
+        // so the hard links of the deduplicated tasks still share the content.
+        match fs::rename(&from_path, &to_path).await {
+            Ok(()) => {
+                self.insert_index(DEFAULT_TASK_DIR, task_id, &to_path);
+                info!("promote {:?} to {:?} success", from_path, to_path);
+                return Ok(true);
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::CrossesDevices => {}
+            Err(err) => {
+                error!("rename {:?} to {:?} failed: {}", from_path, to_path, err);
+                return Err(Error::IO(err));
+            }
+        }
+
+        // The content shared by the hard links of the deduplicated tasks is not copied to the
+        // other filesystem, otherwise the deduplicated tasks do not share the content anymore.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            if fs::metadata(&from_path).await?.nlink() > 1 {
+                info!("skip promoting task {} shared by hard links", task_id);
+                return Ok(false);
+            }
+        }
+
+        // Copy the content to the temporary directory of the fastest tier and rename it, so
+        // the partial content is never found by the task id.
+        let temp_dir = content_dir.dir.join(DEFAULT_TEMP_DIR);
+        fs::create_dir_all(&temp_dir).await.inspect_err(|err| {
+            error!("create {:?} failed: {}", temp_dir, err);
+        })?;
+
+        let temp_path = temp_dir.join(task_id);
+        if let Err(err) = fs::copy(&from_path, &temp_path).await {
+            error!("copy {:?} to {:?} failed: {}", from_path, temp_path, err);
+            fs::remove_file(&temp_path).await.unwrap_or_else(|err| {
+                warn!("remove {:?} failed: {}", temp_path, err);
+            });
+            return Err(Error::IO(err));
+        }
+
+        fs::rename(&temp_path, &to_path).await.inspect_err(|err| {
+            error!("rename {:?} to {:?} failed: {}", temp_path, to_path, err);
+        })?;
+        self.insert_index(DEFAULT_TASK_DIR, task_id, &to_path);
+
+        // The readers which have opened the original content are not affected by the removal.
+        fs::remove_file(&from_path).await.inspect_err(|err| {
+            error!("remove {:?} failed: {}", from_path, err);
+        })?;
+
+        info!("promote {:?} to {:?} success", from_path, to_path);
+        Ok(true)
+    }
+
+    /// read_piece reads the piece from the content. If the piece is stored compressed or
+    /// encrypted, the entire piece is read and decoded.
     #[instrument(skip_all)]
     pub async fn read_piece(
         &self,
         task_id: &str,
-        offset: u64,
-        length: u64,
+        piece: &Piece,
         range: Option<Range>,
     ) -> Result<impl AsyncRead> {
         let task_path = self.get_task_path(task_id);
-
-        // Calculate the target offset and length based on the range.
-        let (target_offset, target_length) = calculate_piece_range(offset, length, range);
-
-        let f = File::open(task_path.as_path()).await.inspect_err(|err| {
-            error!("open {:?} failed: {}", task_path, err);
-        })?;
-        let mut f_reader = BufReader::with_capacity(self.config.storage.read_buffer_size, f);
-
-        f_reader
-            .seek(SeekFrom::Start(target_offset))
+        self.read_piece_range(task_id, &task_path, piece, range)
             .await
-            .inspect_err(|err| {
-                error!("seek {:?} failed: {}", task_path, err);
-            })?;
-
-        Ok(f_reader.take(target_length))
     }
 
     /// read_piece_with_dual_read return two readers, one is the range reader, and the other is the
@@ -314,106 +733,64 @@ impl Content {
     pub async fn read_piece_with_dual_read(
         &self,
         task_id: &str,
-        offset: u64,
-        length: u64,
+        piece: &Piece,
         range: Option<Range>,
     ) -> Result<(impl AsyncRead, impl AsyncRead)> {
         let task_path = self.get_task_path(task_id);
-
-        // Calculate the target offset and length based on the range.
-        let (target_offset, target_length) = calculate_piece_range(offset, length, range);
-
-        let f = File::open(task_path.as_path()).await.inspect_err(|err| {
-            error!("open {:?} failed: {}", task_path, err);
-        })?;
-        let mut f_range_reader = BufReader::with_capacity(self.config.storage.read_buffer_size, f);
-
-        f_range_reader
-            .seek(SeekFrom::Start(target_offset))
-            .await
-            .inspect_err(|err| {
-                error!("seek {:?} failed: {}", task_path, err);
-            })?;
-        let range_reader = f_range_reader.take(target_length);
-
-        // Create full reader of the piece.
-        let f = File::open(task_path.as_path()).await.inspect_err(|err| {
-            error!("open {:?} failed: {}", task_path, err);
-        })?;
-        let mut f_reader = BufReader::with_capacity(self.config.storage.read_buffer_size, f);
-
-        f_reader
-            .seek(SeekFrom::Start(offset))
+        self.read_piece_range_with_dual_read(task_id, &task_path, piece, range)
             .await
-            .inspect_err(|err| {
-                error!("seek {:?} failed: {}", task_path, err);
-            })?;
-        let reader = f_reader.take(length);
+    }
 
-        Ok((range_reader, reader))
+    /// piece_digest_algorithm returns the algorithm to calculate the hash of the pieces, the
+    /// pieces are hashed by sha256 if the integrity is enabled, otherwise by crc32.
+    pub fn piece_digest_algorithm(&self) -> Algorithm {
+        if self.config.download.integrity.enable {
+            Algorithm::Sha256
+        } else {
+            Algorithm::Crc32
+        }
     }
 
-    /// write_piece writes the piece to the content and calculates the hash of the piece by crc32.
+    /// write_piece writes the piece to the content and calculates the hash of the piece by the
+    /// piece digest algorithm. If the compression is enabled, the piece is compressed by zstd,
+    /// and if the encryption is enabled, the piece is encrypted. The hash is still calculated
+    /// over the plaintext.
     #[instrument(skip_all)]
     pub async fn write_piece<R: AsyncRead + Unpin + ?Sized>(
         &self,
         task_id: &str,
+        number: u32,
         offset: u64,
         expected_length: u64,
         reader: &mut R,
     ) -> Result<WritePieceResponse> {
-        // Open the file and seek to the offset.
         let task_path = self.get_task_path(task_id);
-        let mut f = OpenOptions::new()
-            .truncate(false)
-            .write(true)
-            .open(task_path.as_path())
-            .await
-            .inspect_err(|err| {
-                error!("open {:?} failed: {}", task_path, err);
-            })?;
-
-        f.seek(SeekFrom::Start(offset)).await.inspect_err(|err| {
-            error!("seek {:?} failed: {}", task_path, err);
-        })?;
-
-        let reader = BufReader::with_capacity(self.config.storage.write_buffer_size, reader);
-        let mut writer = BufWriter::with_capacity(self.config.storage.write_buffer_size, f);
-
-        // Copy the piece to the file while updating the CRC32 value.
-        let mut hasher = crc32fast::Hasher::new();
-        let mut tee = InspectReader::new(reader, |bytes| {
-            hasher.update(bytes);
-        });
-
-        let length = io::copy(&mut tee, &mut writer).await.inspect_err(|err| {
-            error!("copy {:?} failed: {}", task_path, err);
-        })?;
-
-        writer.flush().await.inspect_err(|err| {
-            error!("flush {:?} failed: {}", task_path, err);
-        })?;
-
-        if length != expected_length {
-            return Err(Error::Unknown(format!(
-                "expected length {} but got {}",
-                expected_length, length
-            )));
+        let compress = self.config.storage.compression.enable;
+        if compress || self.encryptor.is_some() {
+            return self
+                .write_encoded_range(
+                    &task_path,
+                    piece_aad(task_id, number),
+                    offset,
+                    expected_length,
+                    reader,
+                    compress,
+                )
+                .await;
         }
 
-        // Calculate the hash of the piece.
-        Ok(WritePieceResponse {
-            length,
-            hash: hasher.finalize().to_string(),
-        })
+        self.write_range(&task_path, offset, expected_length, reader)
+            .await
+    }
+
+    /// get_task_ids returns the ids of the tasks stored in the content directories.
+    pub fn get_task_ids(&self) -> Vec<String> {
+        self.get_ids(DEFAULT_TASK_DIR)
     }
 
     /// get_task_path returns the task path by task id.
     fn get_task_path(&self, task_id: &str) -> PathBuf {
-        // The task needs split by the first 3 characters of task id(sha256) to
-        // avoid too many files in one directory.
-        let sub_dir = &task_id[..3];
-        self.dir.join(DEFAULT_TASK_DIR).join(sub_dir).join(task_id)
+        self.get_path(DEFAULT_TASK_DIR, task_id)
     }
 
     /// is_same_dev_inode_as_persistent_cache_task checks if the persistent cache task and target
@@ -444,6 +821,7 @@ impl Content {
         }
 
         let task_dir = self
+            .place(task_id, length)
             .dir
             .join(DEFAULT_PERSISTENT_CACHE_TASK_DIR)
             .join(&task_id[..3]);
@@ -461,7 +839,9 @@ impl Content {
             error!("fallocate {:?} failed: {}", task_dir, err);
         })?;
 
-        Ok(task_dir.join(task_id))
+        let task_path = task_dir.join(task_id);
+        self.insert_index(DEFAULT_PERSISTENT_CACHE_TASK_DIR, task_id, &task_path);
+        Ok(task_path)
     }
 
     /// Hard links the persistent cache task content to the destination.
@@ -500,33 +880,35 @@ impl Content {
         Ok(())
     }
 
+    /// copy_persistent_cache_task_by_pieces copies the plaintext content of the persistent
+    /// cache task to the destination piece by piece, it is used when the pieces are encrypted.
+    #[instrument(skip_all)]
+    pub async fn copy_persistent_cache_task_by_pieces(
+        &self,
+        task_id: &str,
+        pieces: &[Piece],
+        to: &Path,
+    ) -> Result<()> {
+        self.copy_pieces(
+            task_id,
+            &self.get_persistent_cache_task_path(task_id),
+            pieces,
+            to,
+        )
+        .await
+    }
+
     /// read_persistent_cache_piece reads the persistent cache piece from the content.
     #[instrument(skip_all)]
     pub async fn read_persistent_cache_piece(
         &self,
         task_id: &str,
-        offset: u64,
-        length: u64,
+        piece: &Piece,
         range: Option<Range>,
     ) -> Result<impl AsyncRead> {
         let task_path = self.get_persistent_cache_task_path(task_id);
-
-        // Calculate the target offset and length based on the range.
-        let (target_offset, target_length) = calculate_piece_range(offset, length, range);
-
-        let f = File::open(task_path.as_path()).await.inspect_err(|err| {
-            error!("open {:?} failed: {}", task_path, err);
-        })?;
-        let mut f_reader = BufReader::with_capacity(self.config.storage.read_buffer_size, f);
-
-        f_reader
-            .seek(SeekFrom::Start(target_offset))
+        self.read_piece_range(task_id, &task_path, piece, range)
             .await
-            .inspect_err(|err| {
-                error!("seek {:?} failed: {}", task_path, err);
-            })?;
-
-        Ok(f_reader.take(target_length))
     }
 
     /// read_persistent_cache_piece_with_dual_read return two readers, one is the range reader, and the other is the
@@ -535,31 +917,161 @@ impl Content {
     pub async fn read_persistent_cache_piece_with_dual_read(
         &self,
         task_id: &str,
-        offset: u64,
-        length: u64,
+        piece: &Piece,
         range: Option<Range>,
     ) -> Result<(impl AsyncRead, impl AsyncRead)> {
         let task_path = self.get_persistent_cache_task_path(task_id);
-
-        // Calculate the target offset and length based on the range.
-        let (target_offset, target_length) = calculate_piece_range(offset, length, range);
-
-        let f = File::open(task_path.as_path()).await.inspect_err(|err| {
-            error!("open {:?} failed: {}", task_path, err);
-        })?;
-        let mut f_range_reader = BufReader::with_capacity(self.config.storage.read_buffer_size, f);
-
-        f_range_reader
-            .seek(SeekFrom::Start(target_offset))
+        self.read_piece_range_with_dual_read(task_id, &task_path, piece, range)
             .await
-            .inspect_err(|err| {
-                error!("seek {:?} failed: {}", task_path, err);
+    }
+
+    /// write_persistent_cache_piece writes the persistent cache piece to the content and
+    /// calculates the hash of the piece by the piece digest algorithm. If the encryption is
+    /// enabled, the piece is encrypted, the persistent cache pieces are never compressed.
+    #[instrument(skip_all)]
+    pub async fn write_persistent_cache_piece<R: AsyncRead + Unpin + ?Sized>(
+        &self,
+        task_id: &str,
+        number: u32,
+        offset: u64,
+        expected_length: u64,
+        reader: &mut R,
+    ) -> Result<WritePieceResponse> {
+        let task_path = self.get_persistent_cache_task_path(task_id);
+        if self.encryptor.is_some() {
+            return self
+                .write_encoded_range(
+                    &task_path,
+                    piece_aad(task_id, number),
+                    offset,
+                    expected_length,
+                    reader,
+                    false,
+                )
+                .await;
+        }
+
+        self.write_range(&task_path, offset, expected_length, reader)
+            .await
+    }
+
+    /// delete_task deletes the persistent cache task content.
+    pub async fn delete_persistent_cache_task(&self, task_id: &str) -> Result<()> {
+        info!("delete persistent cache task content: {}", task_id);
+        for persistent_cache_task_path in
+            self.get_all_paths(DEFAULT_PERSISTENT_CACHE_TASK_DIR, task_id)
+        {
+            fs::remove_file(persistent_cache_task_path.as_path())
+                .await
+                .inspect_err(|err| {
+                    error!("remove {:?} failed: {}", persistent_cache_task_path, err);
+                })?;
+        }
+
+        self.remove_index(DEFAULT_PERSISTENT_CACHE_TASK_DIR, task_id);
+        Ok(())
+    }
+
+    /// get_persistent_cache_task_ids returns the ids of the persistent cache tasks stored in the
+    /// content directories.
+    pub fn get_persistent_cache_task_ids(&self) -> Vec<String> {
+        self.get_ids(DEFAULT_PERSISTENT_CACHE_TASK_DIR)
+    }
+
+    /// get_persistent_cache_task_path returns the persistent cache task path by task id.
+    fn get_persistent_cache_task_path(&self, task_id: &str) -> PathBuf {
+        self.get_path(DEFAULT_PERSISTENT_CACHE_TASK_DIR, task_id)
+    }
+
+    /// read_piece_range returns the reader of the range of the piece in the file. The compressed
+    /// or encrypted piece is read entirely and decoded in the memory.
+    async fn read_piece_range(
+        &self,
+        task_id: &str,
+        path: &Path,
+        piece: &Piece,
+        range: Option<Range>,
+    ) -> Result<RangeReader> {
+        // Calculate the target offset and length based on the range.
+        let (target_offset, target_length) =
+            calculate_piece_range(piece.offset, piece.length, range);
+        if piece.is_encoded() {
+            let content = self.read_encoded_range(task_id, path, piece).await?;
+            let start = (target_offset - piece.offset) as usize;
+            return Ok(Either::Right(Cursor::new(
+                content.slice(start..start + target_length as usize),
+            )));
+        }
+
+        self.read_range(path, target_offset, target_length).await
+    }
+
+    /// read_piece_range_with_dual_read returns the range reader and the full reader of the
+    /// piece, the compressed or encrypted piece is decoded once.
+    async fn read_piece_range_with_dual_read(
+        &self,
+        task_id: &str,
+        path: &Path,
+        piece: &Piece,
+        range: Option<Range>,
+    ) -> Result<(RangeReader, RangeReader)> {
+        if piece.is_encoded() {
+            let content = self.read_encoded_range(task_id, path, piece).await?;
+            let (target_offset, target_length) =
+                calculate_piece_range(piece.offset, piece.length, range);
+            let start = (target_offset - piece.offset) as usize;
+            let range_content = content.slice(start..start + target_length as usize);
+            return Ok((
+                Either::Right(Cursor::new(range_content)),
+                Either::Right(Cursor::new(content)),
+            ));
+        }
+
+        self.read_range_with_dual_read(path, piece.offset, piece.length, range)
+            .await
+    }
+
+    /// copy_pieces copies the plaintext content of the pieces in the file to the destination.
+    async fn copy_pieces(
+        &self,
+        task_id: &str,
+        path: &Path,
+        pieces: &[Piece],
+        to: &Path,
+    ) -> Result<()> {
+        let mut f = File::create(to).await.inspect_err(|err| {
+            error!("create {:?} failed: {}", to, err);
+        })?;
+
+        for piece in pieces {
+            let mut reader = self.read_piece_range(task_id, path, piece, None).await?;
+            f.seek(SeekFrom::Start(piece.offset)).await?;
+            io::copy(&mut reader, &mut f).await.inspect_err(|err| {
+                error!("copy piece {} to {:?} failed: {}", piece.number, to, err);
             })?;
-        let range_reader = f_range_reader.take(target_length);
+        }
 
-        // Create full reader of the piece.
-        let f = File::open(task_path.as_path()).await.inspect_err(|err| {
-            error!("open {:?} failed: {}", task_path, err);
+        f.flush().await?;
+        info!("copy to {:?} by pieces success", to);
+        Ok(())
+    }
+
+    /// read_range returns the reader of the content in the file by the offset and length. If
+    /// the io engine is not standard, the content is read into the memory by the io engine.
+    async fn read_range(&self, path: &Path, offset: u64, length: u64) -> Result<RangeReader> {
+        if !self.io_engine.is_standard() {
+            let content = self
+                .io_engine
+                .read_at(path, offset, length)
+                .await
+                .inspect_err(|err| {
+                    error!("read {:?} failed: {}", path, err);
+                })?;
+            return Ok(Either::Right(Cursor::new(content)));
+        }
+
+        let f = File::open(path).await.inspect_err(|err| {
+            error!("open {:?} failed: {}", path, err);
         })?;
         let mut f_reader = BufReader::with_capacity(self.config.storage.read_buffer_size, f);
 
@@ -567,29 +1079,68 @@ impl Content {
             .seek(SeekFrom::Start(offset))
             .await
             .inspect_err(|err| {
-                error!("seek {:?} failed: {}", task_path, err);
+                error!("seek {:?} failed: {}", path, err);
             })?;
-        let reader = f_reader.take(length);
 
+        Ok(Either::Left(f_reader.take(length)))
+    }
+
+    /// read_range_with_dual_read returns the range reader and the full reader of the piece. If
+    /// the io engine is not standard, the piece is read once, and the range is sliced from it.
+    async fn read_range_with_dual_read(
+        &self,
+        path: &Path,
+        offset: u64,
+        length: u64,
+        range: Option<Range>,
+    ) -> Result<(RangeReader, RangeReader)> {
+        // Calculate the target offset and length based on the range.
+        let (target_offset, target_length) = calculate_piece_range(offset, length, range);
+
+        if !self.io_engine.is_standard() {
+            let content = self
+                .io_engine
+                .read_at(path, offset, length)
+                .await
+                .inspect_err(|err| {
+                    error!("read {:?} failed: {}", path, err);
+                })?;
+
+            let start = (target_offset - offset) as usize;
+            let range_content = content.slice(start..start + target_length as usize);
+            return Ok((
+                Either::Right(Cursor::new(range_content)),
+                Either::Right(Cursor::new(content)),
+            ));
+        }
+
+        let range_reader = self.read_range(path, target_offset, target_length).await?;
+
+        // Create full reader of the piece.
+        let reader = self.read_range(path, offset, length).await?;
         Ok((range_reader, reader))
     }
 
-    /// write_persistent_cache_piece writes the persistent cache piece to the content and
-    /// calculates the hash of the piece by crc32.
-    #[instrument(skip_all)]
-    pub async fn write_persistent_cache_piece<R: AsyncRead + Unpin + ?Sized>(
+    /// write_range writes the piece to the file at the offset and calculates the hash of the
+    /// piece by the piece digest algorithm.
+    async fn write_range<R: AsyncRead + Unpin + ?Sized>(
         &self,
-        task_id: &str,
+        task_path: &Path,
         offset: u64,
         expected_length: u64,
         reader: &mut R,
     ) -> Result<WritePieceResponse> {
+        if !self.io_engine.is_standard() {
+            return self
+                .write_range_by_io_engine(task_path, offset, expected_length, reader)
+                .await;
+        }
+
         // Open the file and seek to the offset.
-        let task_path = self.get_persistent_cache_task_path(task_id);
         let mut f = OpenOptions::new()
             .truncate(false)
             .write(true)
-            .open(task_path.as_path())
+            .open(task_path)
             .await
             .inspect_err(|err| {
                 error!("open {:?} failed: {}", task_path, err);
@@ -602,8 +1153,8 @@ impl Content {
         let reader = BufReader::with_capacity(self.config.storage.write_buffer_size, reader);
         let mut writer = BufWriter::with_capacity(self.config.storage.write_buffer_size, f);
 
-        // Copy the piece to the file while updating the CRC32 value.
-        let mut hasher = crc32fast::Hasher::new();
+        // Copy the piece to the file while updating the hash.
+        let mut hasher = DigestHasher::new(self.piece_digest_algorithm());
         let mut tee = InspectReader::new(reader, |bytes| {
             hasher.update(bytes);
         });
@@ -626,30 +1177,327 @@ impl Content {
         // Calculate the hash of the piece.
         Ok(WritePieceResponse {
             length,
-            hash: hasher.finalize().to_string(),
+            hash: hasher.finalize().encoded().to_string(),
+            compressed_length: None,
+            encryption: None,
         })
     }
 
-    /// delete_task deletes the persistent cache task content.
-    pub async fn delete_persistent_cache_task(&self, task_id: &str) -> Result<()> {
-        info!("delete persistent cache task content: {}", task_id);
-        let persistent_cache_task_path = self.get_persistent_cache_task_path(task_id);
-        fs::remove_file(persistent_cache_task_path.as_path())
+    /// write_range_by_io_engine reads the piece into the aligned buffer and writes it by the io
+    /// engine, the hash of the piece is calculated while reading.
+    async fn write_range_by_io_engine<R: AsyncRead + Unpin + ?Sized>(
+        &self,
+        task_path: &Path,
+        offset: u64,
+        expected_length: u64,
+        reader: &mut R,
+    ) -> Result<WritePieceResponse> {
+        let mut buffer = AlignedBuffer::new(expected_length as usize);
+        let mut hasher = DigestHasher::new(self.piece_digest_algorithm());
+        while (buffer.len() as u64) < expected_length {
+            let remaining = expected_length as usize - buffer.len();
+            let spare = &mut buffer.spare_mut()[..remaining];
+            let n = reader.read(spare).await.inspect_err(|err| {
+                error!("read piece of {:?} failed: {}", task_path, err);
+            })?;
+            if n == 0 {
+                break;
+            }
+
+            hasher.update(&spare[..n]);
+            buffer.advance(n);
+        }
+
+        // Drain the reader to get the real length if the piece is longer than expected.
+        let length = buffer.len() as u64 + io::copy(reader, &mut io::sink()).await?;
+        if length != expected_length {
+            return Err(Error::Unknown(format!(
+                "expected length {} but got {}",
+                expected_length, length
+            )));
+        }
+
+        self.io_engine
+            .write_at(task_path, offset, buffer)
             .await
             .inspect_err(|err| {
-                error!("remove {:?} failed: {}", persistent_cache_task_path, err);
+                error!("write {:?} failed: {}", task_path, err);
             })?;
-        Ok(())
+
+        // Calculate the hash of the piece.
+        Ok(WritePieceResponse {
+            length,
+            hash: hasher.finalize().encoded().to_string(),
+            compressed_length: None,
+            encryption: None,
+        })
     }
 
-    /// get_persistent_cache_task_path returns the persistent cache task path by task id.
-    fn get_persistent_cache_task_path(&self, task_id: &str) -> PathBuf {
-        // The persistent cache task needs split by the first 3 characters of task id(sha256) to
+    /// write_encoded_range reads the entire piece, compresses it by zstd if compress is true,
+    /// encrypts it with the aad if the encryption is enabled, and writes it to the file at the
+    /// offset. If the piece is not compressible, it is stored uncompressed.
+    async fn write_encoded_range<R: AsyncRead + Unpin + ?Sized>(
+        &self,
+        task_path: &Path,
+        aad: Vec<u8>,
+        offset: u64,
+        expected_length: u64,
+        reader: &mut R,
+        compress: bool,
+    ) -> Result<WritePieceResponse> {
+        // Read one more byte to find out the piece which is longer than expected.
+        let mut content = Vec::with_capacity(expected_length as usize);
+        reader
+            .take(expected_length + 1)
+            .read_to_end(&mut content)
+            .await
+            .inspect_err(|err| {
+                error!("read piece of {:?} failed: {}", task_path, err);
+            })?;
+
+        let length = content.len() as u64;
+        if length != expected_length {
+            return Err(Error::Unknown(format!(
+                "expected length {} but got {}",
+                expected_length, length
+            )));
+        }
+
+        // Calculate the hash of the plaintext piece.
+        let mut hasher = DigestHasher::new(self.piece_digest_algorithm());
+        hasher.update(&content);
+        let hash = hasher.finalize().encoded().to_string();
+        let level = self.config.storage.compression.level;
+        let encryptor = self.encryptor.clone();
+        let (content, compressed_length, encryption) = tokio::task::spawn_blocking(
+            move || -> Result<(Vec<u8>, Option<u64>, Option<PieceEncryption>)> {
+                let (mut content, compressed_length) = if compress {
+                    let compressed = zstd::bulk::compress(&content, level)?;
+
+                    // Store the piece uncompressed if it is not compressible, e.g. the
+                    // archives which are already compressed.
+                    if compressed.len() < content.len() {
+                        let compressed_length = compressed.len() as u64;
+                        (compressed, Some(compressed_length))
+                    } else {
+                        (content, None)
+                    }
+                } else {
+                    (content, None)
+                };
+
+                // The task id and piece number are authenticated, so the encrypted piece can
+                // not be moved to the other piece or the other task.
+                let encryption = match encryptor {
+                    Some(encryptor) => Some(encryptor.encrypt(&aad, &mut content)?),
+                    None => None,
+                };
+
+                Ok((content, compressed_length, encryption))
+            },
+        )
+        .await
+        .or_err(ErrorType::AsyncRuntimeError)??;
+
+        if self.io_engine.is_standard() {
+            let mut f = OpenOptions::new()
+                .truncate(false)
+                .write(true)
+                .open(task_path)
+                .await
+                .inspect_err(|err| {
+                    error!("open {:?} failed: {}", task_path, err);
+                })?;
+
+            f.seek(SeekFrom::Start(offset)).await.inspect_err(|err| {
+                error!("seek {:?} failed: {}", task_path, err);
+            })?;
+
+            f.write_all(&content).await.inspect_err(|err| {
+                error!("write {:?} failed: {}", task_path, err);
+            })?;
+
+            f.flush().await.inspect_err(|err| {
+                error!("flush {:?} failed: {}", task_path, err);
+            })?;
+        } else {
+            let mut buffer = AlignedBuffer::new(content.len());
+            buffer.spare_mut()[..content.len()].copy_from_slice(&content);
+            buffer.advance(content.len());
+            self.io_engine
+                .write_at(task_path, offset, buffer)
+                .await
+                .inspect_err(|err| {
+                    error!("write {:?} failed: {}", task_path, err);
+                })?;
+        }
+
+        Ok(WritePieceResponse {
+            length,
+            hash,
+            compressed_length,
+            encryption,
+        })
+    }
+
+    /// read_encoded_range reads the piece stored compressed or encrypted at the offset, and
+    /// decrypts and decompresses it.
+    async fn read_encoded_range(
+        &self,
+        task_id: &str,
+        task_path: &Path,
+        piece: &Piece,
+    ) -> Result<Bytes> {
+        let offset = piece.offset;
+        let length = piece.length;
+        let compressed_length = piece.compressed_length;
+        let stored_length = compressed_length.unwrap_or(length);
+        let content = if self.io_engine.is_standard() {
+            let mut f = File::open(task_path).await.inspect_err(|err| {
+                error!("open {:?} failed: {}", task_path, err);
+            })?;
+
+            f.seek(SeekFrom::Start(offset)).await.inspect_err(|err| {
+                error!("seek {:?} failed: {}", task_path, err);
+            })?;
+
+            let mut content = vec![0; stored_length as usize];
+            f.read_exact(&mut content).await.inspect_err(|err| {
+                error!("read {:?} failed: {}", task_path, err);
+            })?;
+            content
+        } else {
+            Vec::from(
+                self.io_engine
+                    .read_at(task_path, offset, stored_length)
+                    .await
+                    .inspect_err(|err| {
+                        error!("read {:?} failed: {}", task_path, err);
+                    })?,
+            )
+        };
+
+        let decryption = match &piece.encryption {
+            Some(encryption) => {
+                let Some(encryptor) = self.encryptor.clone() else {
+                    return Err(Error::Unsupported(format!(
+                        "decrypt {:?} with encryption disabled",
+                        task_path
+                    )));
+                };
+
+                Some((
+                    encryptor,
+                    encryption.clone(),
+                    piece_aad(task_id, piece.number),
+                ))
+            }
+            None => None,
+        };
+
+        let content = tokio::task::spawn_blocking(move || -> Result<Vec<u8>> {
+            let mut content = content;
+            if let Some((encryptor, encryption, aad)) = decryption {
+                encryptor.decrypt(&aad, &mut content, &encryption)?;
+            }
+
+            if compressed_length.is_some() {
+                content = zstd::bulk::decompress(&content, length as usize)?;
+            }
+
+            Ok(content)
+        })
+        .await
+        .or_err(ErrorType::AsyncRuntimeError)?
+        .inspect_err(|err| {
+            error!("decode {:?} failed: {}", task_path, err);
+        })?;
+
+        if content.len() as u64 != length {
+            return Err(Error::Unknown(format!(
+                "expected length {} but got {}",
+                length,
+                content.len()
+            )));
+        }
+
+        Ok(Bytes::from(content))
+    }
+
+    /// get_path returns the path of the existing content by task id in the indexed directory.
+    /// If the content is not indexed, returns the path in the first directory.
+    fn get_path(&self, sub_dir: &'static str, task_id: &str) -> PathBuf {
+        // The task needs split by the first 3 characters of task id(sha256) to
         // avoid too many files in one directory.
-        self.dir
-            .join(DEFAULT_PERSISTENT_CACHE_TASK_DIR)
-            .join(&task_id[..3])
-            .join(task_id)
+        let relative_path = Path::new(sub_dir).join(&task_id[..3]).join(task_id);
+        let dir_index = self
+            .index
+            .read()
+            .unwrap()
+            .get(&(sub_dir, task_id.to_string()))
+            .copied()
+            .unwrap_or_default();
+
+        self.dirs[dir_index].dir.join(relative_path)
+    }
+
+    /// insert_index indexes the directory of the content path by task id.
+    fn insert_index(&self, sub_dir: &'static str, task_id: &str, path: &Path) {
+        if let Some(dir_index) = self
+            .dirs
+            .iter()
+            .position(|content_dir| path.starts_with(&content_dir.dir))
+        {
+            self.index
+                .write()
+                .unwrap()
+                .insert((sub_dir, task_id.to_string()), dir_index);
+        }
+    }
+
+    /// remove_index removes the index of the content by task id.
+    fn remove_index(&self, sub_dir: &'static str, task_id: &str) {
+        self.index
+            .write()
+            .unwrap()
+            .remove(&(sub_dir, task_id.to_string()));
+    }
+
+    /// get_all_paths returns the paths of the content by task id in all the directories, the
+    /// content may be left in multiple directories by the interrupted promotion. If the content
+    /// does not exist, returns the path in the first directory.
+    fn get_all_paths(&self, sub_dir: &str, task_id: &str) -> Vec<PathBuf> {
+        let relative_path = Path::new(sub_dir).join(&task_id[..3]).join(task_id);
+        let paths = self
+            .dirs
+            .iter()
+            .map(|content_dir| content_dir.dir.join(&relative_path))
+            .filter(|path| path.exists())
+            .collect::<Vec<_>>();
+
+        if paths.is_empty() {
+            return vec![self.dirs[0].dir.join(relative_path)];
+        }
+
+        paths
+    }
+
+    /// get_ids returns the names of the files split by the first 3 characters in the sub
+    /// directory of all the directories.
+    fn get_ids(&self, sub_dir: &str) -> Vec<String> {
+        let mut ids = HashSet::new();
+        self.dirs
+            .iter()
+            .flat_map(|content_dir| {
+                WalkDir::new(content_dir.dir.join(sub_dir))
+                    .min_depth(2)
+                    .max_depth(2)
+            })
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| entry.file_name().to_str().map(|name| name.to_string()))
+            .filter(|id| ids.insert(id.clone()))
+            .collect()
     }
 }
 
@@ -669,9 +1517,17 @@ pub fn calculate_piece_range(offset: u64, length: u64, range: Option<Range>) ->
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Cursor;
+    use dragonfly_client_config::dfdaemon::{IoEngineType, StorageDir};
     use tempfile::tempdir;
 
+    fn new_piece(offset: u64, length: u64) -> Piece {
+        Piece {
+            offset,
+            length,
+            ..Default::default()
+        }
+    }
+
     #[tokio::test]
     async fn test_create_task() {
         let temp_dir = tempdir().unwrap();
@@ -687,6 +1543,34 @@ mod tests {
         assert_eq!(task_path, task_path_exists);
     }
 
+    #[tokio::test]
+    async fn test_link_task() {
+        let temp_dir = tempdir().unwrap();
+        let config = Arc::new(Config::default());
+        let content = Content::new(config, temp_dir.path()).await.unwrap();
+
+        let from_task_id = "5c1f6e0a2b3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7";
+        let from_task_path = content.create_task(from_task_id, 0).await.unwrap();
+        fs::write(&from_task_path, b"test").await.unwrap();
+
+        let to_task_id = "7d6c5b4a3928170f6e5d4c3b2a1f0e9d8c7b6a5f4e3d2c1b0a9f8e7d6c5b4a39";
+        let to_task_path = content.create_task(to_task_id, 4).await.unwrap();
+        content.link_task(from_task_id, to_task_id).await.unwrap();
+        assert_eq!(fs::read(&to_task_path).await.unwrap(), b"test");
+        assert!(content
+            .is_same_dev_inode(&from_task_path, &to_task_path)
+            .await
+            .unwrap());
+
+        let digest = "sha256:9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+            .parse::<Digest>()
+            .unwrap();
+        content
+            .verify_task_digest(to_task_id, digest)
+            .await
+            .unwrap();
+    }
+
     #[tokio::test]
     async fn test_hard_link_task() {
         let temp_dir = tempdir().unwrap();
@@ -735,6 +1619,129 @@ mod tests {
         assert!(!task_path.exists());
     }
 
+    #[tokio::test]
+    async fn test_get_task_ids() {
+        let temp_dir = tempdir().unwrap();
+        let config = Arc::new(Config::default());
+        let content = Content::new(config, temp_dir.path()).await.unwrap();
+        assert!(content.get_task_ids().is_empty());
+
+        let task_id = "1d3b4e8e3a0ba2bfbe1f3c6ca8fb46fe1cce8a8ac3b5e0c6ab8b8b0f4c8e9f21";
+        content.create_task(task_id, 0).await.unwrap();
+        let persistent_cache_task_id =
+            "9f1d2c3b4a5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7f8";
+        content
+            .create_persistent_cache_task(persistent_cache_task_id, 0)
+            .await
+            .unwrap();
+
+        assert_eq!(content.get_task_ids(), vec![task_id.to_string()]);
+        assert_eq!(
+            content.get_persistent_cache_task_ids(),
+            vec![persistent_cache_task_id.to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tiered_dirs() {
+        let temp_dir = tempdir().unwrap();
+        let fast_dir = temp_dir.path().join("nvme");
+        let slow_dir = temp_dir.path().join("hdd");
+        let mut config = Config::default();
+        config.storage.dirs = vec![
+            StorageDir {
+                path: slow_dir.clone(),
+                tier: 1,
+                weight: 1,
+            },
+            StorageDir {
+                path: fast_dir.clone(),
+                tier: 0,
+                weight: 1,
+            },
+        ];
+        let content = Content::new(Arc::new(config), temp_dir.path())
+            .await
+            .unwrap();
+
+        // The new task is placed in the slowest tier.
+        let task_id = "3e1f5a7b9c0d2e4f6a8b0c1d3e5f7a9b1c2d4e6f8a0b2c3d5e7f9a1b3c4d6e8f";
+        let task_path = content.create_task(task_id, 13).await.unwrap();
+        assert!(task_path.starts_with(&slow_dir));
+
+        let data = b"hello, world!";
+        content
+            .write_piece(task_id, 0, 0, 13, &mut Cursor::new(data))
+            .await
+            .unwrap();
+
+        // The promoted task is moved to the fastest tier and can be read.
+        assert!(content.promote_task(task_id, 13).await.unwrap());
+        assert!(!task_path.exists());
+        assert!(content.get_task_path(task_id).starts_with(&fast_dir));
+        assert!(!content.promote_task(task_id, 13).await.unwrap());
+        assert_eq!(content.get_task_ids(), vec![task_id.to_string()]);
+
+        let mut reader = content
+            .read_piece(task_id, &new_piece(0, 13), None)
+            .await
+            .unwrap();
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).await.unwrap();
+        assert_eq!(buffer, data);
+
+        content.delete_task(task_id).await.unwrap();
+        assert!(content.get_task_ids().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_dir_and_promote_linked_tasks() {
+        let temp_dir = tempdir().unwrap();
+        let content = Content::new(Arc::new(Config::default()), temp_dir.path())
+            .await
+            .unwrap();
+
+        // The tasks are stored in the dir before the storage.dirs is configured.
+        let from_task_id = "5c1f6e0a2b3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7";
+        let from_task_path = content.create_task(from_task_id, 0).await.unwrap();
+        fs::write(&from_task_path, b"test").await.unwrap();
+
+        let to_task_id = "7d6c5b4a3928170f6e5d4c3b2a1f0e9d8c7b6a5f4e3d2c1b0a9f8e7d6c5b4a39";
+        content.create_task(to_task_id, 4).await.unwrap();
+        content.link_task(from_task_id, to_task_id).await.unwrap();
+
+        let fast_dir = temp_dir.path().join("nvme");
+        let mut config = Config::default();
+        config.storage.keep = true;
+        config.storage.dirs = vec![StorageDir {
+            path: fast_dir.clone(),
+            tier: 0,
+            weight: 1,
+        }];
+        let content = Content::new(Arc::new(config), temp_dir.path())
+            .await
+            .unwrap();
+
+        // The content of the legacy dir is found, and the new task is not placed in it.
+        assert_eq!(content.get_task_path(from_task_id), from_task_path);
+        let task_id = "3e1f5a7b9c0d2e4f6a8b0c1d3e5f7a9b1c2d4e6f8a0b2c3d5e7f9a1b3c4d6e8f";
+        assert!(content
+            .create_task(task_id, 4)
+            .await
+            .unwrap()
+            .starts_with(&fast_dir));
+
+        // The promoted task keeps sharing the content with the linked task.
+        assert!(content.promote_task(to_task_id, 4).await.unwrap());
+        let to_task_path = content.get_task_path(to_task_id);
+        assert!(to_task_path.starts_with(&fast_dir));
+        assert_eq!(fs::read(&to_task_path).await.unwrap(), b"test");
+        assert!(content
+            .is_same_dev_inode(&from_task_path, &to_task_path)
+            .await
+            .unwrap());
+    }
+
     #[tokio::test]
     async fn test_read_piece() {
         let temp_dir = tempdir().unwrap();
@@ -747,11 +1754,14 @@ mod tests {
         let data = b"hello, world!";
         let mut reader = Cursor::new(data);
         content
-            .write_piece(task_id, 0, 13, &mut reader)
+            .write_piece(task_id, 0, 0, 13, &mut reader)
             .await
             .unwrap();
 
-        let mut reader = content.read_piece(task_id, 0, 13, None).await.unwrap();
+        let mut reader = content
+            .read_piece(task_id, &new_piece(0, 13), None)
+            .await
+            .unwrap();
         let mut buffer = Vec::new();
         reader.read_to_end(&mut buffer).await.unwrap();
         assert_eq!(buffer, data);
@@ -759,8 +1769,7 @@ mod tests {
         let mut reader = content
             .read_piece(
                 task_id,
-                0,
-                13,
+                &new_piece(0, 13),
                 Some(Range {
                     start: 0,
                     length: 5,
@@ -785,13 +1794,240 @@ mod tests {
         let data = b"test";
         let mut reader = Cursor::new(data);
         let response = content
-            .write_piece(task_id, 0, 4, &mut reader)
+            .write_piece(task_id, 0, 0, 4, &mut reader)
             .await
             .unwrap();
         assert_eq!(response.length, 4);
         assert!(!response.hash.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_write_and_read_piece_by_io_engine() {
+        let temp_dir = tempdir().unwrap();
+        let mut config = Config::default();
+        config.storage.io_engine = IoEngineType::Direct;
+        let content = Content::new(Arc::new(config), temp_dir.path())
+            .await
+            .unwrap();
+
+        let task_id = "8f3c1a2b4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7f9";
+        let data = (0..10000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        content.create_task(task_id, 10000).await.unwrap();
+
+        // Write the aligned piece and the unaligned last piece.
+        let response = content
+            .write_piece(task_id, 0, 0, 8192, &mut Cursor::new(&data[..8192]))
+            .await
+            .unwrap();
+        assert_eq!(response.length, 8192);
+        assert_eq!(response.hash, crc32fast::hash(&data[..8192]).to_string());
+        content
+            .write_piece(task_id, 1, 8192, 1808, &mut Cursor::new(&data[8192..]))
+            .await
+            .unwrap();
+        assert!(content
+            .write_piece(task_id, 0, 0, 8192, &mut Cursor::new(&data[..100]))
+            .await
+            .is_err());
+
+        let mut reader = content
+            .read_piece(
+                task_id,
+                &new_piece(8192, 1808),
+                Some(Range {
+                    start: 8200,
+                    length: 100,
+                }),
+            )
+            .await
+            .unwrap();
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).await.unwrap();
+        assert_eq!(buffer, &data[8200..8300]);
+
+        let (mut range_reader, mut reader) = content
+            .read_piece_with_dual_read(
+                task_id,
+                &new_piece(0, 8192),
+                Some(Range {
+                    start: 10,
+                    length: 20,
+                }),
+            )
+            .await
+            .unwrap();
+        let mut buffer = Vec::new();
+        range_reader.read_to_end(&mut buffer).await.unwrap();
+        assert_eq!(buffer, &data[10..30]);
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).await.unwrap();
+        assert_eq!(buffer, &data[..8192]);
+    }
+
+    #[tokio::test]
+    async fn test_write_piece_with_integrity() {
+        let temp_dir = tempdir().unwrap();
+        let mut config = Config::default();
+        config.download.integrity.enable = true;
+        let content = Content::new(Arc::new(config), temp_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(content.piece_digest_algorithm(), Algorithm::Sha256);
+
+        let task_id = "5a7d1ed414474e4033ac29ccb8653d9b4a7d1ed414474e4033ac29ccb8653d9b";
+        let data = b"hello, world!";
+        content.create_task(task_id, 13).await.unwrap();
+
+        let response = content
+            .write_piece(task_id, 0, 0, 13, &mut Cursor::new(data))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.hash,
+            "68e656b251e67e8358bef8483ab0d51c6619f3e7a1a9f0e75838d41ff368f728"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_and_read_compressed_piece() {
+        let temp_dir = tempdir().unwrap();
+        let mut config = Config::default();
+        config.storage.compression.enable = true;
+        let content = Content::new(Arc::new(config), temp_dir.path())
+            .await
+            .unwrap();
+
+        let task_id = "4a7d1ed414474e4033ac29ccb8653d9b4a7d1ed414474e4033ac29ccb8653d9b";
+        let data = b"hello, world! ".repeat(1024);
+        let length = data.len() as u64;
+        content.create_task(task_id, length * 2).await.unwrap();
+
+        // The compressible piece is stored compressed, and the hash is calculated by plaintext.
+        let response = content
+            .write_piece(task_id, 0, 0, length, &mut Cursor::new(&data))
+            .await
+            .unwrap();
+        assert_eq!(response.length, length);
+        assert_eq!(response.hash, crc32fast::hash(&data).to_string());
+        assert!(response.compressed_length.unwrap() < length);
+        let piece = Piece {
+            compressed_length: response.compressed_length,
+            ..new_piece(0, length)
+        };
+
+        let mut reader = content.read_piece(task_id, &piece, None).await.unwrap();
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).await.unwrap();
+        assert_eq!(buffer, data);
+
+        let (mut range_reader, mut reader) = content
+            .read_piece_with_dual_read(
+                task_id,
+                &piece,
+                Some(Range {
+                    start: 7,
+                    length: 5,
+                }),
+            )
+            .await
+            .unwrap();
+        let mut buffer = Vec::new();
+        range_reader.read_to_end(&mut buffer).await.unwrap();
+        assert_eq!(buffer, b"world");
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).await.unwrap();
+        assert_eq!(buffer, data);
+
+        // The incompressible piece is stored uncompressed.
+        let mut state = 0x2545f4914f6cdd1du64;
+        let random = (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect::<Vec<_>>();
+        let response = content
+            .write_piece(task_id, 1, length, length, &mut Cursor::new(&random))
+            .await
+            .unwrap();
+        assert!(response.compressed_length.is_none());
+
+        let mut reader = content
+            .read_piece(task_id, &new_piece(length, length), None)
+            .await
+            .unwrap();
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).await.unwrap();
+        assert_eq!(buffer, random);
+    }
+
+    #[tokio::test]
+    async fn test_write_and_read_encrypted_piece() {
+        let temp_dir = tempdir().unwrap();
+        let key_file = temp_dir.path().join("keys");
+        std::fs::write(&key_file, format!("k1:{}\n", "11".repeat(32))).unwrap();
+
+        let mut config = Config::default();
+        config.storage.compression.enable = true;
+        config.storage.encryption.enable = true;
+        config.storage.encryption.key_file = Some(key_file);
+        let content = Content::new(Arc::new(config), temp_dir.path())
+            .await
+            .unwrap();
+
+        let task_id = "0b5e2a4d0a8f1c3e5b7d9f1a3c5e7b9d0b5e2a4d0a8f1c3e5b7d9f1a3c5e7b9d";
+        let data = b"hello, world! ".repeat(1024);
+        let length = data.len() as u64;
+        content.create_task(task_id, length).await.unwrap();
+
+        // The piece is compressed and encrypted, the hash is calculated by plaintext.
+        let response = content
+            .write_piece(task_id, 0, 0, length, &mut Cursor::new(&data))
+            .await
+            .unwrap();
+        assert_eq!(response.hash, crc32fast::hash(&data).to_string());
+        assert_eq!(response.encryption.as_ref().unwrap().key_id, "k1");
+        let piece = Piece {
+            compressed_length: response.compressed_length,
+            encryption: response.encryption,
+            ..new_piece(0, length)
+        };
+
+        let mut reader = content
+            .read_piece(
+                task_id,
+                &piece,
+                Some(Range {
+                    start: 7,
+                    length: 5,
+                }),
+            )
+            .await
+            .unwrap();
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer).await.unwrap();
+        assert_eq!(buffer, b"world");
+
+        // The content file does not contain the plaintext, and the copy is decrypted.
+        let stored = std::fs::read(content.get_task_path(task_id)).unwrap();
+        assert!(!stored.windows(13).any(|window| window == b"hello, world!"));
+
+        let to = temp_dir.path().join("output");
+        content
+            .copy_task_by_pieces(task_id, &[piece.clone()], &to)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&to).unwrap(), data);
+
+        // The piece can not be read as the other piece of the task.
+        assert!(content
+            .read_piece(task_id, &Piece { number: 1, ..piece }, None)
+            .await
+            .is_err());
+    }
+
     #[tokio::test]
     async fn test_create_persistent_task() {
         let temp_dir = tempdir().unwrap();
@@ -894,12 +2130,12 @@ mod tests {
         let data = b"hello, world!";
         let mut reader = Cursor::new(data);
         content
-            .write_persistent_cache_piece(task_id, 0, 13, &mut reader)
+            .write_persistent_cache_piece(task_id, 0, 0, 13, &mut reader)
             .await
             .unwrap();
 
         let mut reader = content
-            .read_persistent_cache_piece(task_id, 0, 13, None)
+            .read_persistent_cache_piece(task_id, &new_piece(0, 13), None)
             .await
             .unwrap();
         let mut buffer = Vec::new();
@@ -909,8 +2145,7 @@ mod tests {
         let mut reader = content
             .read_persistent_cache_piece(
                 task_id,
-                0,
-                13,
+                &new_piece(0, 13),
                 Some(Range {
                     start: 0,
                     length: 5,
@@ -938,7 +2173,7 @@ mod tests {
         let data = b"test";
         let mut reader = Cursor::new(data);
         let response = content
-            .write_persistent_cache_piece(task_id, 0, 4, &mut reader)
+            .write_persistent_cache_piece(task_id, 0, 0, 4, &mut reader)
             .await
             .unwrap();
         assert_eq!(response.length, 4);
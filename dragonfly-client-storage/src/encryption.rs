@@ -0,0 +1,357 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::metadata::PieceEncryption;
+use aes_gcm::aead::{generic_array::typenum::Unsigned, AeadInPlace, KeyInit, OsRng};
+use aes_gcm::Aes256Gcm;
+use chacha20poly1305::ChaCha20Poly1305;
+use dragonfly_client_config::dfdaemon::{Config, EncryptionAlgorithm};
+use dragonfly_client_core::{Error, Result};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tracing::info;
+
+/// KEY_LENGTH is the length of the keys, both the AES-256-GCM and ChaCha20-Poly1305 use the
+/// 256 bits keys.
+const KEY_LENGTH: usize = 32;
+
+/// Keyring is the keys to encrypt and decrypt the piece content.
+#[derive(Debug, Default)]
+struct Keyring {
+    /// active_key_id is the id of the key to encrypt the new pieces.
+    active_key_id: String,
+
+    /// keys are the keys by id, the retired keys are kept to decrypt the old pieces.
+    keys: HashMap<String, [u8; KEY_LENGTH]>,
+}
+
+/// Keyring implements the keyring.
+impl Keyring {
+    /// parse parses the keyring from the content of the key file. Each line is `<key id>:<hex
+    /// encoded key>`, and the last key is the active key.
+    fn parse(content: &str) -> Result<Self> {
+        let mut keyring = Keyring::default();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((id, key)) = line.split_once(':') else {
+                return Err(Error::ValidationError(
+                    "invalid key line, expected <key id>:<hex key>".to_string(),
+                ));
+            };
+
+            let key = hex::decode(key.trim())
+                .map_err(|err| Error::ValidationError(format!("invalid key {}: {}", id, err)))?;
+            let key: [u8; KEY_LENGTH] = key.try_into().map_err(|_| {
+                Error::ValidationError(format!("key {} must be {} bytes", id, KEY_LENGTH))
+            })?;
+
+            let id = id.trim().to_string();
+            keyring.keys.insert(id.clone(), key);
+            keyring.active_key_id = id;
+        }
+
+        if keyring.keys.is_empty() {
+            return Err(Error::ValidationError(
+                "no encryption key found".to_string(),
+            ));
+        }
+
+        Ok(keyring)
+    }
+
+    /// get returns the key by id.
+    fn get(&self, id: &str) -> Result<&[u8; KEY_LENGTH]> {
+        self.keys
+            .get(id)
+            .ok_or_else(|| Error::ValidationError(format!("encryption key {} not found", id)))
+    }
+}
+
+/// Encryptor encrypts and decrypts the piece content by the AEAD algorithm, the keys are
+/// loaded from the key file or the key management service.
+pub struct Encryptor {
+    /// config is the configuration of the dfdaemon.
+    config: Arc<Config>,
+
+    /// keyring is the loaded keys.
+    keyring: RwLock<Keyring>,
+}
+
+/// Encryptor implements the encryptor.
+impl Encryptor {
+    /// new returns a new encryptor with the loaded keys.
+    pub async fn new(config: Arc<Config>) -> Result<Self> {
+        let keyring = Self::load(&config).await?;
+        info!(
+            "encryption keys loaded, active key is {}",
+            keyring.active_key_id
+        );
+
+        Ok(Encryptor {
+            config,
+            keyring: RwLock::new(keyring),
+        })
+    }
+
+    /// reload reloads the keys to rotate the active key, the pieces encrypted by the retired
+    /// keys can be decrypted as long as the keys are kept.
+    pub async fn reload(&self) -> Result<()> {
+        let keyring = Self::load(&self.config).await?;
+        let mut current = self.keyring.write().unwrap();
+        if current.active_key_id != keyring.active_key_id {
+            info!(
+                "encryption key is rotated from {} to {}",
+                current.active_key_id, keyring.active_key_id
+            );
+        }
+
+        *current = keyring;
+        Ok(())
+    }
+
+    /// load loads the keys from the key file, or from the key management service if the key
+    /// file is not set.
+    async fn load(config: &Config) -> Result<Keyring> {
+        let encryption = &config.storage.encryption;
+        let content = match (&encryption.key_file, &encryption.kms_endpoint) {
+            (Some(key_file), _) => tokio::fs::read_to_string(key_file).await?,
+            (None, Some(kms_endpoint)) => {
+                reqwest::get(kms_endpoint)
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?
+            }
+            (None, None) => {
+                return Err(Error::ValidationError(
+                    "encryption requires keyFile or kmsEndpoint".to_string(),
+                ))
+            }
+        };
+
+        Keyring::parse(&content)
+    }
+
+    /// encrypt encrypts the buffer in place by the active key, the aad binds the encrypted
+    /// content to its position.
+    pub fn encrypt(&self, aad: &[u8], buffer: &mut [u8]) -> Result<PieceEncryption> {
+        let algorithm = self.config.storage.encryption.algorithm;
+        let keyring = self.keyring.read().unwrap();
+        let key = keyring.get(&keyring.active_key_id)?;
+        let (nonce, tag) = match algorithm {
+            EncryptionAlgorithm::Aes256Gcm => encrypt_in_place::<Aes256Gcm>(key, aad, buffer)?,
+            EncryptionAlgorithm::ChaCha20Poly1305 => {
+                encrypt_in_place::<ChaCha20Poly1305>(key, aad, buffer)?
+            }
+        };
+
+        Ok(PieceEncryption {
+            algorithm,
+            key_id: keyring.active_key_id.clone(),
+            nonce,
+            tag,
+        })
+    }
+
+    /// decrypt decrypts the buffer in place by the key and algorithm of the encryption.
+    pub fn decrypt(
+        &self,
+        aad: &[u8],
+        buffer: &mut [u8],
+        encryption: &PieceEncryption,
+    ) -> Result<()> {
+        let keyring = self.keyring.read().unwrap();
+        let key = keyring.get(&encryption.key_id)?;
+        match encryption.algorithm {
+            EncryptionAlgorithm::Aes256Gcm => {
+                decrypt_in_place::<Aes256Gcm>(key, aad, buffer, encryption)
+            }
+            EncryptionAlgorithm::ChaCha20Poly1305 => {
+                decrypt_in_place::<ChaCha20Poly1305>(key, aad, buffer, encryption)
+            }
+        }
+    }
+}
+
+/// piece_aad returns the aad of the piece, which binds the encrypted content to the task id
+/// and the piece number. The task id is length prefixed, so the different task id and piece
+/// number can not produce the same aad.
+pub fn piece_aad(task_id: &str, number: u32) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(8 + task_id.len() + 4);
+    aad.extend_from_slice(&(task_id.len() as u64).to_be_bytes());
+    aad.extend_from_slice(task_id.as_bytes());
+    aad.extend_from_slice(&number.to_be_bytes());
+    aad
+}
+
+/// encrypt_in_place encrypts the buffer in place with a random nonce, and returns the nonce
+/// and the detached tag.
+fn encrypt_in_place<C: AeadInPlace + KeyInit>(
+    key: &[u8],
+    aad: &[u8],
+    buffer: &mut [u8],
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let cipher = C::new_from_slice(key).map_err(|err| Error::Unknown(err.to_string()))?;
+    let nonce = C::generate_nonce(&mut OsRng);
+    let tag = cipher
+        .encrypt_in_place_detached(&nonce, aad, buffer)
+        .map_err(|err| Error::Unknown(format!("encrypt failed: {}", err)))?;
+
+    Ok((nonce.to_vec(), tag.to_vec()))
+}
+
+/// decrypt_in_place verifies the tag and decrypts the buffer in place.
+fn decrypt_in_place<C: AeadInPlace + KeyInit>(
+    key: &[u8],
+    aad: &[u8],
+    buffer: &mut [u8],
+    encryption: &PieceEncryption,
+) -> Result<()> {
+    if encryption.nonce.len() != <C::NonceSize as Unsigned>::USIZE
+        || encryption.tag.len() != <C::TagSize as Unsigned>::USIZE
+    {
+        return Err(Error::ValidationError(format!(
+            "invalid nonce or tag of key {}",
+            encryption.key_id
+        )));
+    }
+
+    let cipher = C::new_from_slice(key).map_err(|err| Error::Unknown(err.to_string()))?;
+    cipher
+        .decrypt_in_place_detached(
+            aes_gcm::aead::Nonce::<C>::from_slice(&encryption.nonce),
+            aad,
+            buffer,
+            aes_gcm::aead::Tag::<C>::from_slice(&encryption.tag),
+        )
+        .map_err(|err| Error::Unknown(format!("decrypt failed: {}", err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    async fn new_encryptor(
+        algorithm: EncryptionAlgorithm,
+        keys: &str,
+    ) -> (Encryptor, tempfile::TempDir) {
+        let dir = tempdir().unwrap();
+        let key_file = dir.path().join("keys");
+        std::fs::write(&key_file, keys).unwrap();
+
+        let mut config = Config::default();
+        config.storage.encryption.enable = true;
+        config.storage.encryption.algorithm = algorithm;
+        config.storage.encryption.key_file = Some(key_file);
+        (Encryptor::new(Arc::new(config)).await.unwrap(), dir)
+    }
+
+    #[test]
+    fn test_parse_keyring() {
+        let keyring = Keyring::parse(&format!(
+            "# rotated keys\nk1:{}\n\nk2:{}\n",
+            "11".repeat(32),
+            "22".repeat(32)
+        ))
+        .unwrap();
+        assert_eq!(keyring.active_key_id, "k2");
+        assert_eq!(keyring.get("k1").unwrap(), &[0x11; KEY_LENGTH]);
+
+        assert!(Keyring::parse("").is_err());
+        assert!(Keyring::parse("k1").is_err());
+        assert!(Keyring::parse("k1:1122").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_encrypt_and_decrypt() {
+        for algorithm in [
+            EncryptionAlgorithm::Aes256Gcm,
+            EncryptionAlgorithm::ChaCha20Poly1305,
+        ] {
+            let (encryptor, _dir) =
+                new_encryptor(algorithm, &format!("k1:{}", "11".repeat(32))).await;
+
+            let data = b"hello, world!".to_vec();
+            let mut buffer = data.clone();
+            let aad = piece_aad("task", 0);
+            let encryption = encryptor.encrypt(&aad, &mut buffer).unwrap();
+            assert_eq!(encryption.algorithm, algorithm);
+            assert_eq!(encryption.key_id, "k1");
+            assert_ne!(buffer, data);
+
+            // The content can not be decrypted as the other piece or the other task.
+            for moved_aad in [piece_aad("task", 1), piece_aad("other", 0)] {
+                let mut moved = buffer.clone();
+                assert!(encryptor
+                    .decrypt(&moved_aad, &mut moved, &encryption)
+                    .is_err());
+            }
+
+            encryptor.decrypt(&aad, &mut buffer, &encryption).unwrap();
+            assert_eq!(buffer, data);
+        }
+    }
+
+    #[test]
+    fn test_piece_aad() {
+        assert_eq!(
+            piece_aad("task", 1),
+            [&4u64.to_be_bytes()[..], b"task", &1u32.to_be_bytes()].concat()
+        );
+        assert_ne!(piece_aad("task1", 0), piece_aad("task", 1));
+    }
+
+    #[tokio::test]
+    async fn test_rotate_key() {
+        let (encryptor, dir) = new_encryptor(
+            EncryptionAlgorithm::Aes256Gcm,
+            &format!("k1:{}", "11".repeat(32)),
+        )
+        .await;
+
+        let data = b"hello, world!".to_vec();
+        let mut old = data.clone();
+        let old_encryption = encryptor.encrypt(b"0", &mut old).unwrap();
+
+        // Append the new key, the new pieces are encrypted by it and the old pieces can
+        // still be decrypted.
+        std::fs::write(
+            dir.path().join("keys"),
+            format!("k1:{}\nk2:{}\n", "11".repeat(32), "22".repeat(32)),
+        )
+        .unwrap();
+        encryptor.reload().await.unwrap();
+
+        let mut new = data.clone();
+        assert_eq!(encryptor.encrypt(b"0", &mut new).unwrap().key_id, "k2");
+        encryptor.decrypt(b"0", &mut old, &old_encryption).unwrap();
+        assert_eq!(old, data);
+
+        // The pieces can not be decrypted once the key is removed.
+        std::fs::write(dir.path().join("keys"), format!("k2:{}\n", "22".repeat(32))).unwrap();
+        encryptor.reload().await.unwrap();
+        let mut old = data.clone();
+        let old_encryption = PieceEncryption {
+            key_id: "k1".to_string(),
+            ..old_encryption
+        };
+        assert!(encryptor.decrypt(b"0", &mut old, &old_encryption).is_err());
+    }
+}
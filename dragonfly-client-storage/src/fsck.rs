@@ -0,0 +1,301 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::{metadata, Storage};
+use dragonfly_client_core::Result;
+use std::collections::HashSet;
+use tracing::{error, info, instrument, warn};
+
+/// CheckReport is the report of checking the integrity of the storage.
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    /// checked_tasks is the number of the checked tasks and persistent cache tasks.
+    pub checked_tasks: usize,
+
+    /// checked_pieces is the number of the checked finished pieces.
+    pub checked_pieces: usize,
+
+    /// corrupted_tasks are the ids of the tasks which have the pieces mismatched with
+    /// the content.
+    pub corrupted_tasks: Vec<String>,
+
+    /// orphaned_contents are the ids of the contents which have no task metadata.
+    pub orphaned_contents: Vec<String>,
+
+    /// orphaned_pieces are the ids of the piece metadatas which have no task metadata.
+    pub orphaned_pieces: Vec<String>,
+}
+
+/// CheckReport implements the report of checking the storage.
+impl CheckReport {
+    /// is_consistent returns whether the storage has no corrupted or orphaned items.
+    pub fn is_consistent(&self) -> bool {
+        self.corrupted_tasks.is_empty()
+            && self.orphaned_contents.is_empty()
+            && self.orphaned_pieces.is_empty()
+    }
+}
+
+/// Storage implements the integrity checking of the storage.
+impl Storage {
+    /// check walks the metadatas and the contents of the storage, recomputes the crc32 digest
+    /// of every finished piece, and reports the corrupted and orphaned items. If repair is true,
+    /// the corrupted tasks and orphaned items are deleted, so the tasks can be downloaded again.
+    /// It must be called when the storage is not used by the running dfdaemon.
+    #[instrument(skip_all)]
+    pub async fn check(&self, repair: bool) -> Result<CheckReport> {
+        let mut report = CheckReport::default();
+
+        // Check the pieces of the tasks.
+        let tasks = self.metadata.get_tasks()?;
+        for task in tasks.iter() {
+            report.checked_tasks += 1;
+            if self.check_task(task, &mut report).await? {
+                continue;
+            }
+
+            warn!("task {} is corrupted", task.id);
+            report.corrupted_tasks.push(task.id.clone());
+            if repair {
+                self.delete_task(&task.id).await;
+            }
+        }
+
+        // Check the pieces of the persistent cache tasks.
+        let persistent_cache_tasks = self.metadata.get_persistent_cache_tasks()?;
+        for task in persistent_cache_tasks.iter() {
+            report.checked_tasks += 1;
+            if self.check_persistent_cache_task(task, &mut report).await? {
+                continue;
+            }
+
+            warn!("persistent cache task {} is corrupted", task.id);
+            report.corrupted_tasks.push(task.id.clone());
+            if repair {
+                self.delete_persistent_cache_task(&task.id).await;
+            }
+        }
+
+        // Check the contents which have no task metadata.
+        let task_ids: HashSet<&str> = tasks.iter().map(|task| task.id.as_str()).collect();
+        for id in self.content.get_task_ids() {
+            if task_ids.contains(id.as_str()) {
+                continue;
+            }
+
+            warn!("task content {} is orphaned", id);
+            if repair {
+                self.content.delete_task(&id).await.unwrap_or_else(|err| {
+                    error!("delete task content failed: {}", err);
+                });
+            }
+
+            report.orphaned_contents.push(id);
+        }
+
+        let persistent_cache_task_ids: HashSet<&str> = persistent_cache_tasks
+            .iter()
+            .map(|task| task.id.as_str())
+            .collect();
+        for id in self.content.get_persistent_cache_task_ids() {
+            if persistent_cache_task_ids.contains(id.as_str()) {
+                continue;
+            }
+
+            warn!("persistent cache task content {} is orphaned", id);
+            if repair {
+                self.content
+                    .delete_persistent_cache_task(&id)
+                    .await
+                    .unwrap_or_else(|err| {
+                        error!("delete persistent cache task content failed: {}", err);
+                    });
+            }
+
+            report.orphaned_contents.push(id);
+        }
+
+        // Check the piece metadatas which have no task metadata, the piece id is
+        // formatted as {task_id}-{number}.
+        for piece_id in self.metadata.get_piece_ids()? {
+            let task_id = piece_id
+                .rsplit_once('-')
+                .map(|(task_id, _)| task_id)
+                .unwrap_or_default();
+            if task_ids.contains(task_id) || persistent_cache_task_ids.contains(task_id) {
+                continue;
+            }
+
+            warn!("piece {} is orphaned", piece_id);
+            if repair {
+                self.metadata.delete_piece(&piece_id)?;
+            }
+
+            report.orphaned_pieces.push(piece_id);
+        }
+
+        info!(
+            "check storage finished, checked tasks: {}, checked pieces: {}, corrupted tasks: {}, orphaned contents: {}, orphaned pieces: {}",
+            report.checked_tasks,
+            report.checked_pieces,
+            report.corrupted_tasks.len(),
+            report.orphaned_contents.len(),
+            report.orphaned_pieces.len()
+        );
+        Ok(report)
+    }
+
+    /// check_task verifies the finished pieces of the task, and returns false if any piece
+    /// is corrupted.
+    async fn check_task(&self, task: &metadata::Task, report: &mut CheckReport) -> Result<bool> {
+        for piece in self.metadata.get_pieces(&task.id)? {
+            if !piece.is_finished() {
+                continue;
+            }
+
+            report.checked_pieces += 1;
+            if let Err(err) = self.verify_piece(&task.id, &piece).await {
+                warn!(
+                    "verify piece {} failed: {}",
+                    self.piece_id(&task.id, piece.number),
+                    err
+                );
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// check_persistent_cache_task verifies the finished pieces of the persistent cache task,
+    /// and returns false if any piece is corrupted.
+    async fn check_persistent_cache_task(
+        &self,
+        task: &metadata::PersistentCacheTask,
+        report: &mut CheckReport,
+    ) -> Result<bool> {
+        for piece in self.metadata.get_pieces(&task.id)? {
+            if !piece.is_finished() {
+                continue;
+            }
+
+            report.checked_pieces += 1;
+            let result = match self
+                .content
+                .read_persistent_cache_piece(&task.id, &piece, None)
+                .await
+            {
+                Ok(reader) => self.verify_piece_content(reader, &piece).await,
+                Err(err) => Err(err),
+            };
+
+            if let Err(err) = result {
+                warn!(
+                    "verify persistent cache piece {} failed: {}",
+                    self.persistent_cache_piece_id(&task.id, piece.number),
+                    err
+                );
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dragonfly_client_config::dfdaemon::Config;
+    use std::io::Cursor;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn should_check_and_repair_storage() {
+        let dir = tempdir().unwrap();
+        let storage = Storage::new(
+            Arc::new(Config::default()),
+            dir.path(),
+            dir.path().join("log"),
+        )
+        .await
+        .unwrap();
+
+        // Download a healthy task and a task which will be corrupted.
+        let healthy_task_id = "6b2c9e1f0a3d4c5b8e7f6a5d4c3b2a1f0e9d8c7b6a5f4e3d2c1b0a9f8e7d6c5b";
+        let corrupted_task_id = "c4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5";
+        for task_id in [healthy_task_id, corrupted_task_id] {
+            storage
+                .download_task_started(task_id, 4, 4, None, None)
+                .await
+                .unwrap();
+            let piece_id = storage.piece_id(task_id, 0);
+            storage.download_piece_started(&piece_id, 0).await.unwrap();
+            storage
+                .download_piece_from_source_finished(
+                    &piece_id,
+                    task_id,
+                    0,
+                    0,
+                    4,
+                    &mut Cursor::new(b"test".to_vec()),
+                    Duration::from_secs(10),
+                )
+                .await
+                .unwrap();
+        }
+
+        // Corrupt the content of the task.
+        let corrupted_task_path = dir
+            .path()
+            .join("content/tasks")
+            .join(&corrupted_task_id[..3])
+            .join(corrupted_task_id);
+        tokio::fs::write(&corrupted_task_path, b"fake")
+            .await
+            .unwrap();
+
+        // Leave the piece metadata without task metadata.
+        let orphaned_piece_id = storage.piece_id(
+            "0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c4b5a69788796a5b4c3d2e1f0",
+            0,
+        );
+        storage
+            .download_piece_started(&orphaned_piece_id, 0)
+            .await
+            .unwrap();
+
+        let report = storage.check(false).await.unwrap();
+        assert!(!report.is_consistent());
+        assert_eq!(report.checked_tasks, 2);
+        assert_eq!(report.checked_pieces, 2);
+        assert_eq!(report.corrupted_tasks, vec![corrupted_task_id.to_string()]);
+        assert!(report.orphaned_contents.is_empty());
+        assert_eq!(report.orphaned_pieces, vec![orphaned_piece_id.clone()]);
+
+        let report = storage.check(true).await.unwrap();
+        assert!(!report.is_consistent());
+        assert!(storage.get_task(corrupted_task_id).unwrap().is_none());
+        assert!(!corrupted_task_path.exists());
+        assert!(storage.get_piece(&orphaned_piece_id).unwrap().is_none());
+
+        let report = storage.check(false).await.unwrap();
+        assert!(report.is_consistent());
+        assert_eq!(report.checked_tasks, 1);
+    }
+}
@@ -0,0 +1,589 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use bytes::Bytes;
+use dragonfly_client_config::dfdaemon::IoEngineType;
+use dragonfly_client_core::{
+    error::{ErrorType, OrErr},
+    Result,
+};
+use std::alloc::{self, Layout};
+use std::io;
+use std::path::Path;
+use std::ptr::NonNull;
+use tracing::warn;
+
+/// ALIGNMENT is the alignment of the buffer, offset and length of the direct I/O, it is the
+/// logical block size of the most disks.
+pub const ALIGNMENT: usize = 4096;
+
+/// AlignedBuffer is the buffer aligned to the ALIGNMENT, it is required by the direct I/O.
+pub struct AlignedBuffer {
+    /// ptr is the pointer of the allocated memory.
+    ptr: NonNull<u8>,
+
+    /// len is the length of the filled bytes.
+    len: usize,
+
+    /// capacity is the size of the allocated memory, it is the multiple of the ALIGNMENT.
+    capacity: usize,
+}
+
+/// SAFETY: AlignedBuffer owns the allocated memory exclusively like `Vec<u8>`.
+unsafe impl Send for AlignedBuffer {}
+
+/// SAFETY: AlignedBuffer only exposes the shared slice by the shared reference.
+unsafe impl Sync for AlignedBuffer {}
+
+/// AlignedBuffer implements the aligned buffer.
+impl AlignedBuffer {
+    /// new allocates the zeroed buffer whose capacity is rounded up to the ALIGNMENT.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = align_up(capacity.max(1) as u64) as usize;
+        let layout = Layout::from_size_align(capacity, ALIGNMENT).expect("invalid buffer layout");
+
+        // SAFETY: the size of the layout is not zero.
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        Self {
+            ptr,
+            len: 0,
+            capacity,
+        }
+    }
+
+    /// len returns the length of the filled bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// is_empty returns whether the buffer has no filled bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// spare_mut returns the unfilled part of the buffer.
+    pub fn spare_mut(&mut self) -> &mut [u8] {
+        let len = self.len;
+        &mut self.as_full_mut()[len..]
+    }
+
+    /// advance marks the next n bytes of the unfilled part as filled.
+    pub fn advance(&mut self, n: usize) {
+        assert!(self.len + n <= self.capacity, "advance out of capacity");
+        self.len += n;
+    }
+
+    /// as_full_mut returns the entire allocated memory of the buffer.
+    fn as_full_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the memory is allocated with the capacity and initialized by zero.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.capacity) }
+    }
+}
+
+/// AlignedBuffer implements AsRef<[u8]> for the filled bytes.
+impl AsRef<[u8]> for AlignedBuffer {
+    fn as_ref(&self) -> &[u8] {
+        // SAFETY: the filled bytes are in the allocated memory.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+/// AlignedBuffer implements Drop to free the allocated memory.
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        // SAFETY: the memory is allocated by the same layout in new.
+        unsafe {
+            alloc::dealloc(
+                self.ptr.as_ptr(),
+                Layout::from_size_align_unchecked(self.capacity, ALIGNMENT),
+            );
+        }
+    }
+}
+
+/// IoEngine writes and reads the piece content by the direct I/O or io_uring, the piece is
+/// transferred in the aligned chunks in the blocking thread.
+#[derive(Debug, Clone, Copy)]
+pub struct IoEngine {
+    /// engine_type is the type of the io engine.
+    engine_type: IoEngineType,
+
+    /// chunk_size is the size of the single read or write, it is the multiple of the ALIGNMENT.
+    chunk_size: usize,
+}
+
+/// IoEngine implements the io engine.
+impl IoEngine {
+    /// MAX_CHUNK_SIZE is the max size of the single read or write, the length of the io_uring
+    /// operation is u32.
+    const MAX_CHUNK_SIZE: usize = 1 << 30;
+
+    /// new returns a new io engine. The direct and io_uring engines are only supported on
+    /// linux, the standard engine is used on the other platforms. If io_uring is disabled by
+    /// the kernel, the direct engine is used.
+    pub fn new(engine_type: IoEngineType, chunk_size: usize) -> Self {
+        let engine_type = match engine_type {
+            IoEngineType::Standard => IoEngineType::Standard,
+            _ if !cfg!(target_os = "linux") => {
+                warn!(
+                    "io engine {} is not supported, fallback to standard",
+                    engine_type
+                );
+                IoEngineType::Standard
+            }
+            IoEngineType::IoUring if !sys::is_io_uring_supported() => {
+                warn!("io_uring is not supported by the kernel, fallback to direct");
+                IoEngineType::Direct
+            }
+            engine_type => engine_type,
+        };
+
+        Self {
+            engine_type,
+            chunk_size: chunk_size.clamp(ALIGNMENT, Self::MAX_CHUNK_SIZE) / ALIGNMENT * ALIGNMENT,
+        }
+    }
+
+    /// is_standard returns whether the piece content is transferred by the buffered I/O of
+    /// the tokio.
+    pub fn is_standard(&self) -> bool {
+        self.engine_type == IoEngineType::Standard
+    }
+
+    /// write_at writes the buffer to the file at the offset. The aligned part is written by
+    /// the direct I/O, and the unaligned tail, e.g. the last piece, is written by the
+    /// buffered I/O.
+    pub async fn write_at(&self, path: &Path, offset: u64, buffer: AlignedBuffer) -> Result<()> {
+        let engine = *self;
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            sys::write_at(
+                engine.engine_type,
+                &path,
+                offset,
+                buffer.as_ref(),
+                engine.chunk_size,
+            )
+        })
+        .await
+        .or_err(ErrorType::AsyncRuntimeError)??;
+
+        Ok(())
+    }
+
+    /// read_at reads the length of the file at the offset. The aligned range covering the
+    /// offset and length is read by the direct I/O, and the requested range is returned
+    /// without copying.
+    pub async fn read_at(&self, path: &Path, offset: u64, length: u64) -> Result<Bytes> {
+        let engine = *self;
+        let path = path.to_path_buf();
+        let content = tokio::task::spawn_blocking(move || -> io::Result<Bytes> {
+            let aligned_offset = align_down(offset);
+            let aligned_length = (align_up(offset + length) - aligned_offset) as usize;
+            let mut buffer = AlignedBuffer::new(aligned_length);
+            let n = sys::read_at(
+                engine.engine_type,
+                &path,
+                aligned_offset,
+                &mut buffer.as_full_mut()[..aligned_length],
+                engine.chunk_size,
+            )?;
+
+            let start = (offset - aligned_offset) as usize;
+            let end = start + length as usize;
+            if n < end {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "expected length {} but got {}",
+                        length,
+                        n.saturating_sub(start)
+                    ),
+                ));
+            }
+
+            buffer.advance(n);
+            Ok(Bytes::from_owner(buffer).slice(start..end))
+        })
+        .await
+        .or_err(ErrorType::AsyncRuntimeError)??;
+
+        Ok(content)
+    }
+}
+
+/// align_down rounds the value down to the multiple of the ALIGNMENT.
+fn align_down(value: u64) -> u64 {
+    value / ALIGNMENT as u64 * ALIGNMENT as u64
+}
+
+/// align_up rounds the value up to the multiple of the ALIGNMENT.
+fn align_up(value: u64) -> u64 {
+    value.div_ceil(ALIGNMENT as u64) * ALIGNMENT as u64
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::ALIGNMENT;
+    use dragonfly_client_config::dfdaemon::IoEngineType;
+    use io_uring::{opcode, types, IoUring};
+    use std::cell::RefCell;
+    use std::fs::{File, OpenOptions};
+    use std::io;
+    use std::os::unix::fs::{FileExt, OpenOptionsExt};
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+    use tracing::{debug, error};
+
+    /// RING_ENTRIES is the number of the entries of the io_uring submission queue, the chunks
+    /// of the piece are submitted in the batches of the entries.
+    const RING_ENTRIES: u32 = 32;
+
+    /// IORING_ENTER_GETEVENTS is the flag of io_uring_enter to wait for the completions.
+    const IORING_ENTER_GETEVENTS: u32 = 1;
+
+    thread_local! {
+        /// RING is the io_uring ring of the blocking thread, it is reused by the pieces
+        /// transferred in the same thread.
+        static RING: RefCell<Option<IoUring>> = const { RefCell::new(None) };
+    }
+
+    /// is_io_uring_supported returns whether the io_uring ring can be created.
+    pub fn is_io_uring_supported() -> bool {
+        IoUring::new(1).is_ok()
+    }
+
+    /// write_at writes the data to the file at the offset.
+    pub fn write_at(
+        engine_type: IoEngineType,
+        path: &Path,
+        offset: u64,
+        data: &[u8],
+        chunk_size: usize,
+    ) -> io::Result<()> {
+        // The direct I/O requires the aligned offset and length, so only the aligned part is
+        // written by the direct I/O.
+        let direct_length = if offset % ALIGNMENT as u64 == 0 {
+            data.len() / ALIGNMENT * ALIGNMENT
+        } else {
+            0
+        };
+
+        if direct_length > 0 {
+            let f = open_direct(path, true)?;
+            let chunks = split(direct_length, chunk_size);
+            match engine_type {
+                IoEngineType::IoUring => {
+                    let results = submit(&f, data.as_ptr().cast_mut(), offset, &chunks, true)?;
+                    for ((_, length), n) in chunks.iter().zip(results) {
+                        if n != *length {
+                            return Err(io::Error::new(
+                                io::ErrorKind::WriteZero,
+                                format!("short write {} of {}", n, length),
+                            ));
+                        }
+                    }
+                }
+                _ => {
+                    for (start, length) in chunks {
+                        f.write_all_at(&data[start..start + length], offset + start as u64)?;
+                    }
+                }
+            }
+        }
+
+        if direct_length < data.len() {
+            let f = OpenOptions::new().write(true).open(path)?;
+            f.write_all_at(&data[direct_length..], offset + direct_length as u64)?;
+        }
+
+        Ok(())
+    }
+
+    /// read_at reads the file at the aligned offset into the aligned buffer, returns the
+    /// length of the bytes read before the end of the file.
+    pub fn read_at(
+        engine_type: IoEngineType,
+        path: &Path,
+        offset: u64,
+        buffer: &mut [u8],
+        chunk_size: usize,
+    ) -> io::Result<usize> {
+        let f = open_direct(path, false)?;
+        let chunks = split(buffer.len(), chunk_size);
+        let mut n = 0;
+        match engine_type {
+            IoEngineType::IoUring => {
+                let results = submit(&f, buffer.as_mut_ptr(), offset, &chunks, false)?;
+                for ((_, length), result) in chunks.iter().zip(results) {
+                    n += result;
+                    if result < *length {
+                        break;
+                    }
+                }
+            }
+            _ => {
+                while n < buffer.len() {
+                    let result = f.read_at(&mut buffer[n..], offset + n as u64)?;
+                    if result == 0 {
+                        break;
+                    }
+
+                    n += result;
+                }
+            }
+        }
+
+        Ok(n)
+    }
+
+    /// open_direct opens the file with O_DIRECT. If the file system does not support O_DIRECT,
+    /// e.g. tmpfs, the file is opened without O_DIRECT.
+    fn open_direct(path: &Path, write: bool) -> io::Result<File> {
+        let mut options = OpenOptions::new();
+        options.read(!write).write(write);
+        match options.clone().custom_flags(libc::O_DIRECT).open(path) {
+            Err(err) if err.raw_os_error() == Some(libc::EINVAL) => {
+                debug!("open {:?} with O_DIRECT failed: {}", path, err);
+                options.open(path)
+            }
+            result => result,
+        }
+    }
+
+    /// split splits the length into the chunks of (start, length).
+    fn split(length: usize, chunk_size: usize) -> Vec<(usize, usize)> {
+        (0..length)
+            .step_by(chunk_size)
+            .map(|start| (start, chunk_size.min(length - start)))
+            .collect()
+    }
+
+    /// submit submits the chunks of the buffer to the io_uring ring of the current thread, and
+    /// waits for the completions. It returns the transferred length of every chunk.
+    ///
+    /// The entries in flight point into the buffer, so submit never returns before all the
+    /// submitted entries complete. The ring is reused only if the batches succeed, otherwise
+    /// it is dropped with the entries left in the submission queue.
+    fn submit(
+        f: &File,
+        ptr: *mut u8,
+        offset: u64,
+        chunks: &[(usize, usize)],
+        write: bool,
+    ) -> io::Result<Vec<usize>> {
+        RING.with(|slot| {
+            let mut ring = match slot.borrow_mut().take() {
+                Some(ring) => ring,
+                None => IoUring::new(RING_ENTRIES)?,
+            };
+
+            let fd = types::Fd(f.as_raw_fd());
+            let mut results = vec![0; chunks.len()];
+            for (batch, batch_chunks) in chunks.chunks(RING_ENTRIES as usize).enumerate() {
+                let base = batch * RING_ENTRIES as usize;
+                let mut pushed = 0;
+                let mut error = None;
+                for (index, (start, length)) in batch_chunks.iter().enumerate() {
+                    // SAFETY: the chunk is in the buffer.
+                    let buf = unsafe { ptr.add(*start) };
+                    let entry = if write {
+                        opcode::Write::new(fd, buf, *length as u32)
+                            .offset(offset + *start as u64)
+                            .build()
+                    } else {
+                        opcode::Read::new(fd, buf, *length as u32)
+                            .offset(offset + *start as u64)
+                            .build()
+                    }
+                    .user_data((base + index) as u64);
+
+                    // SAFETY: the buffer and the file outlive the operation, because all the
+                    // submitted entries of the batch are waited below.
+                    if unsafe { ring.submission().push(&entry) }.is_err() {
+                        error = Some(io::Error::other("io_uring submission queue is full"));
+                        break;
+                    }
+
+                    pushed += 1;
+                }
+
+                // Submit the pushed entries and reap their completions. If the submission
+                // fails, only the entries consumed by the kernel are in flight, and they are
+                // waited without submitting the others.
+                let mut in_flight = pushed;
+                let mut completed = 0;
+                let mut submit_failed = false;
+                while completed < in_flight {
+                    let result = if !submit_failed {
+                        ring.submit_and_wait(in_flight - completed)
+                    } else {
+                        wait(&ring, in_flight - completed)
+                    };
+
+                    match result {
+                        Ok(_) => {}
+                        Err(err) if is_transient(&err) => {}
+                        Err(err) if !submit_failed => {
+                            submit_failed = true;
+                            in_flight = pushed - ring.submission().len();
+                            error.get_or_insert(err);
+                        }
+                        Err(err) => {
+                            // The kernel may still access the buffer, so the process is
+                            // aborted instead of freeing the buffer in use.
+                            error!("wait io_uring completions failed: {}", err);
+                            std::process::abort();
+                        }
+                    }
+
+                    for cqe in ring.completion() {
+                        completed += 1;
+                        let index = cqe.user_data() as usize;
+                        if !(base..base + batch_chunks.len()).contains(&index) {
+                            continue;
+                        }
+
+                        if cqe.result() < 0 {
+                            error.get_or_insert(io::Error::from_raw_os_error(-cqe.result()));
+                        } else {
+                            results[index] = cqe.result() as usize;
+                        }
+                    }
+                }
+
+                if let Some(err) = error {
+                    return Err(err);
+                }
+            }
+
+            *slot.borrow_mut() = Some(ring);
+            Ok(results)
+        })
+    }
+
+    /// wait waits for the completions of the entries in flight without submitting the
+    /// entries in the submission queue.
+    fn wait(ring: &IoUring, want: usize) -> io::Result<usize> {
+        // SAFETY: no entry is submitted, and the arguments are not passed.
+        unsafe {
+            ring.submitter()
+                .enter::<libc::sigset_t>(0, want as u32, IORING_ENTER_GETEVENTS, None)
+        }
+    }
+
+    /// is_transient returns whether io_uring_enter can be retried, it is interrupted by the
+    /// signal, or the completion queue is overflowed before the completions are reaped.
+    fn is_transient(err: &io::Error) -> bool {
+        matches!(
+            err.raw_os_error(),
+            Some(libc::EINTR | libc::EAGAIN | libc::EBUSY)
+        )
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use dragonfly_client_config::dfdaemon::IoEngineType;
+    use std::io;
+    use std::path::Path;
+
+    /// is_io_uring_supported returns false on the platforms except linux.
+    pub fn is_io_uring_supported() -> bool {
+        false
+    }
+
+    /// write_at is not supported on the platforms except linux.
+    pub fn write_at(
+        _engine_type: IoEngineType,
+        _path: &Path,
+        _offset: u64,
+        _data: &[u8],
+        _chunk_size: usize,
+    ) -> io::Result<()> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+
+    /// read_at is not supported on the platforms except linux.
+    pub fn read_at(
+        _engine_type: IoEngineType,
+        _path: &Path,
+        _offset: u64,
+        _buffer: &mut [u8],
+        _chunk_size: usize,
+    ) -> io::Result<usize> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_aligned_buffer() {
+        let mut buffer = AlignedBuffer::new(10);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.spare_mut().len(), ALIGNMENT);
+        assert_eq!(buffer.spare_mut().as_ptr() as usize % ALIGNMENT, 0);
+
+        buffer.spare_mut()[..5].copy_from_slice(b"hello");
+        buffer.advance(5);
+        assert_eq!(buffer.as_ref(), b"hello");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_write_and_read_at() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("content");
+        let data = (0..3 * ALIGNMENT + 100)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+
+        for engine_type in [IoEngineType::Direct, IoEngineType::IoUring] {
+            std::fs::write(&path, vec![0; ALIGNMENT + data.len()]).unwrap();
+            let engine = IoEngine::new(engine_type, ALIGNMENT);
+
+            let mut buffer = AlignedBuffer::new(data.len());
+            buffer.spare_mut()[..data.len()].copy_from_slice(&data);
+            buffer.advance(data.len());
+            engine
+                .write_at(&path, ALIGNMENT as u64, buffer)
+                .await
+                .unwrap();
+
+            let content = engine
+                .read_at(&path, ALIGNMENT as u64, data.len() as u64)
+                .await
+                .unwrap();
+            assert_eq!(content.as_ref(), data.as_slice());
+
+            let content = engine
+                .read_at(&path, ALIGNMENT as u64 + 10, 20)
+                .await
+                .unwrap();
+            assert_eq!(content.as_ref(), &data[10..30]);
+
+            assert!(engine
+                .read_at(&path, ALIGNMENT as u64, data.len() as u64 + 1)
+                .await
+                .is_err());
+        }
+    }
+}
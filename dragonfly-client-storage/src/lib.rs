@@ -14,23 +14,31 @@
  * limitations under the License.
  */
 
-use chrono::NaiveDateTime;
+use bytes::Bytes;
+use chrono::{NaiveDateTime, Utc};
 use dragonfly_api::common::v2::Range;
 use dragonfly_client_config::dfdaemon::Config;
 use dragonfly_client_core::{Error, Result};
-use dragonfly_client_util::digest::{Algorithm, Digest};
+use dragonfly_client_util::digest::{Algorithm, Digest, Hasher, SEPARATOR};
 use reqwest::header::HeaderMap;
+use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll};
 use std::time::Duration;
-use tokio::io::AsyncRead;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, ReadBuf};
 use tokio::time::sleep;
 use tokio_util::either::Either;
 use tracing::{debug, error, info, instrument, warn};
 
+pub mod bundle;
 pub mod cache;
 pub mod content;
+pub mod encryption;
+pub mod fsck;
+pub mod io_engine;
 pub mod metadata;
 pub mod storage_engine;
 
@@ -43,7 +51,7 @@ pub struct Storage {
     config: Arc<Config>,
 
     /// metadata implements the metadata storage.
-    metadata: metadata::Metadata,
+    metadata: Arc<metadata::Metadata>,
 
     /// content implements the content storage.
     content: content::Content,
@@ -56,7 +64,7 @@ pub struct Storage {
 impl Storage {
     /// new returns a new storage.
     pub async fn new(config: Arc<Config>, dir: &Path, log_dir: PathBuf) -> Result<Self> {
-        let metadata = metadata::Metadata::new(config.clone(), dir, &log_dir)?;
+        let metadata = Arc::new(metadata::Metadata::new(config.clone(), dir, &log_dir)?);
         let content = content::Content::new(config.clone(), dir).await?;
         let cache = cache::Cache::new(config.clone());
 
@@ -68,6 +76,13 @@ impl Storage {
         })
     }
 
+    /// dropped_metadata_objects returns the number of the metadata objects of each namespace
+    /// which can not be migrated to the current schema and are dropped when the storage is
+    /// opened.
+    pub fn dropped_metadata_objects(&self) -> &HashMap<&'static str, usize> {
+        self.metadata.dropped_objects()
+    }
+
     /// total_space returns the total space of the disk.
     pub fn total_space(&self) -> Result<u64> {
         self.content.total_space()
@@ -83,18 +98,87 @@ impl Storage {
         self.content.has_enough_space(content_length)
     }
 
-    /// hard_link_task hard links the task content to the destination.
+    /// hard_link_task hard links the task content to the destination. The content of the
+    /// compressed or encrypted task can not be hard linked, because the destination must be
+    /// plaintext. The hard link is refused by the configuration rather than the stored pieces,
+    /// because the destination is linked before the pieces are downloaded.
     #[instrument(skip_all)]
     pub async fn hard_link_task(&self, task_id: &str, to: &Path) -> Result<()> {
+        if self.config.storage.compression.enable
+            || self.config.storage.encryption.enable
+            || self.get_encoded_pieces(task_id)?.is_some()
+        {
+            return Err(Error::Unsupported(format!(
+                "hard link encoded task {}",
+                task_id
+            )));
+        }
+
         self.content.hard_link_task(task_id, to).await
     }
 
-    /// copy_task copies the task content to the destination.
+    /// copy_task copies the task content to the destination, the compressed or encrypted task
+    /// is decoded piece by piece. The encrypted task is copied only if the plaintext output
+    /// is allowed.
     #[instrument(skip_all)]
     pub async fn copy_task(&self, id: &str, to: &Path) -> Result<()> {
+        if let Some(pieces) = self.get_encoded_pieces(id)? {
+            self.check_plaintext_output(id, &pieces)?;
+            return self.content.copy_task_by_pieces(id, &pieces, to).await;
+        }
+
         self.content.copy_task(id, to).await
     }
 
+    /// get_encoded_pieces returns the finished pieces of the task sorted by number if any
+    /// piece of the task is stored compressed or encrypted, otherwise returns None.
+    fn get_encoded_pieces(&self, task_id: &str) -> Result<Option<Vec<metadata::Piece>>> {
+        let mut pieces = self
+            .metadata
+            .get_pieces(task_id)?
+            .into_iter()
+            .filter(|piece| piece.is_finished())
+            .collect::<Vec<_>>();
+
+        if !pieces.iter().any(|piece| piece.is_encoded()) {
+            return Ok(None);
+        }
+
+        pieces.sort_by_key(|piece| piece.number);
+        Ok(Some(pieces))
+    }
+
+    /// check_output checks whether the task can be downloaded to the output path before the
+    /// download started. The output path is plaintext, so it is rejected if the encryption is
+    /// enabled and the plaintext output is not allowed.
+    pub fn check_output(&self, task_id: &str) -> Result<()> {
+        if self.config.storage.encryption.enable
+            && !self.config.storage.encryption.allow_plaintext_output
+        {
+            return Err(Error::Unsupported(format!(
+                "download encrypted task {} to plaintext output",
+                task_id
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// check_plaintext_output checks whether the pieces can be decrypted to the output path,
+    /// the decrypted output must be explicitly allowed by the configuration.
+    fn check_plaintext_output(&self, task_id: &str, pieces: &[metadata::Piece]) -> Result<()> {
+        if !self.config.storage.encryption.allow_plaintext_output
+            && pieces.iter().any(|piece| piece.encryption.is_some())
+        {
+            return Err(Error::Unsupported(format!(
+                "copy encrypted task {} to plaintext output",
+                task_id
+            )));
+        }
+
+        Ok(())
+    }
+
     /// is_same_dev_inode_as_task checks if the task content is on the same device inode as the
     /// destination.
     pub async fn is_same_dev_inode_as_task(&self, id: &str, to: &Path) -> Result<bool> {
@@ -103,8 +187,14 @@ impl Storage {
 
     /// prepare_download_task_started prepares the metadata of the task when the task downloads
     /// started.
-    pub async fn prepare_download_task_started(&self, id: &str) -> Result<metadata::Task> {
-        self.metadata.download_task_started(id, None, None, None)
+    pub async fn prepare_download_task_started(
+        &self,
+        id: &str,
+        application: Option<String>,
+        tag: Option<String>,
+    ) -> Result<metadata::Task> {
+        self.metadata
+            .download_task_started(id, None, None, None, application, tag, None)
     }
 
     /// download_task_started updates the metadata of the task and create task content
@@ -116,6 +206,7 @@ impl Storage {
         piece_length: u64,
         content_length: u64,
         response_header: Option<HeaderMap>,
+        digest: Option<String>,
     ) -> Result<metadata::Task> {
         self.content.create_task(id, content_length).await?;
 
@@ -124,9 +215,115 @@ impl Storage {
             Some(piece_length),
             Some(content_length),
             response_header,
+            None,
+            None,
+            digest,
         )
     }
 
+    /// reuse_task reuses the content and pieces of the finished task which has the same digest
+    /// as the task, the content is hard linked to the task and the finished pieces are cloned.
+    /// The content of the finished task is verified by the digest before reused, so the task
+    /// is not polluted by the wrong digest. It returns the number of the reused pieces, and
+    /// zero if no finished task can be reused.
+    #[instrument(skip_all)]
+    pub async fn reuse_task(&self, task: &metadata::Task) -> Result<usize> {
+        let Some(digest) = task.digest.as_ref() else {
+            return Ok(0);
+        };
+
+        let Some(digest_index) = self.metadata.get_digest_index(digest)? else {
+            return Ok(0);
+        };
+
+        if digest_index.task_id == task.id {
+            return Ok(0);
+        }
+
+        // The finished task must have the same piece length and content length, otherwise
+        // the pieces can not be reused.
+        let Some(reused_task) = self.metadata.get_task(&digest_index.task_id)? else {
+            self.metadata.delete_digest_index(digest)?;
+            return Ok(0);
+        };
+
+        if !reused_task.is_finished()
+            || reused_task.piece_length != task.piece_length
+            || reused_task.content_length != task.content_length
+        {
+            return Ok(0);
+        }
+
+        // The encrypted pieces are bound to the task id, so they can not be reused by the
+        // other task.
+        if self
+            .metadata
+            .get_pieces(&reused_task.id)?
+            .iter()
+            .any(|piece| piece.encryption.is_some())
+        {
+            return Ok(0);
+        }
+
+        let expected_digest = digest.parse::<Digest>().map_err(Error::ValidationError)?;
+        if let Err(err) = self
+            .verify_task_digest(&reused_task.id, expected_digest)
+            .await
+        {
+            warn!(
+                "verify task {} by digest {} failed: {}",
+                reused_task.id, digest, err
+            );
+            self.metadata.delete_digest_index(digest)?;
+            return Ok(0);
+        }
+
+        self.content.link_task(&reused_task.id, &task.id).await?;
+        let reused_pieces = self.metadata.clone_pieces(&reused_task.id, &task.id)?;
+        info!(
+            "task {} reuses {} pieces of task {} by digest {}",
+            task.id, reused_pieces, reused_task.id, digest
+        );
+
+        Ok(reused_pieces)
+    }
+
+    /// verify_task_digest verifies the content of the task by the digest. The compressed or
+    /// encrypted task is verified by the decoded content of the pieces.
+    async fn verify_task_digest(&self, task_id: &str, expected_digest: Digest) -> Result<()> {
+        let Some(pieces) = self.get_encoded_pieces(task_id)? else {
+            return self
+                .content
+                .verify_task_digest(task_id, expected_digest)
+                .await;
+        };
+
+        let mut hasher = Hasher::new(expected_digest.algorithm());
+        let mut buffer = vec![0; self.config.storage.read_buffer_size];
+        for piece in pieces {
+            let mut reader = self.content.read_piece(task_id, &piece, None).await?;
+
+            loop {
+                let n = reader.read(&mut buffer).await?;
+                if n == 0 {
+                    break;
+                }
+
+                hasher.update(&buffer[..n]);
+            }
+        }
+
+        let digest = hasher.finalize();
+        if digest.to_string() != expected_digest.to_string() {
+            return Err(Error::DigestMismatch(
+                expected_digest.to_string(),
+                digest.to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
     /// download_task_finished updates the metadata of the task when the task downloads finished.
     #[instrument(skip_all)]
     pub fn download_task_finished(&self, id: &str) -> Result<metadata::Task> {
@@ -141,8 +338,12 @@ impl Storage {
 
     /// prefetch_task_started updates the metadata of the task when the task prefetches started.
     #[instrument(skip_all)]
-    pub async fn prefetch_task_started(&self, id: &str) -> Result<metadata::Task> {
-        self.metadata.prefetch_task_started(id)
+    pub async fn prefetch_task_started(
+        &self,
+        id: &str,
+        request: Vec<u8>,
+    ) -> Result<metadata::Task> {
+        self.metadata.prefetch_task_started(id, request)
     }
 
     /// prefetch_task_failed updates the metadata of the task when the task prefetches failed.
@@ -175,6 +376,89 @@ impl Storage {
         self.metadata.get_tasks()
     }
 
+    /// resume_task prepares the unfinished task to be resumed after dfdaemon restarts. The
+    /// pieces which are not finished or whose content does not match the digest are removed,
+    /// so only the missing pieces are downloaded again. It returns the number of the kept pieces.
+    #[instrument(skip_all)]
+    pub async fn resume_task(&self, task: &metadata::Task) -> Result<usize> {
+        let Some(content_length) = task.content_length() else {
+            return Err(Error::InvalidContentLength);
+        };
+
+        // Create the task content if it is lost, the pieces will be verified failed.
+        self.content.create_task(&task.id, content_length).await?;
+
+        let mut kept_pieces = 0;
+        for piece in self.metadata.get_pieces(&task.id)? {
+            let piece_id = self.piece_id(&task.id, piece.number);
+
+            // The piece is interrupted by the restart, remove it to download again.
+            if !piece.is_finished() {
+                debug!("piece {} is not finished, remove it", piece_id);
+                self.metadata.delete_piece(&piece_id)?;
+                continue;
+            }
+
+            match self.verify_piece(&task.id, &piece).await {
+                Ok(()) => kept_pieces += 1,
+                Err(err) => {
+                    warn!("verify piece {} failed, remove it: {}", piece_id, err);
+                    self.metadata.delete_piece(&piece_id)?;
+                }
+            }
+        }
+
+        Ok(kept_pieces)
+    }
+
+    /// verify_piece verifies the content of the piece by the digest.
+    async fn verify_piece(&self, task_id: &str, piece: &metadata::Piece) -> Result<()> {
+        let reader = self.content.read_piece(task_id, piece, None).await?;
+
+        self.verify_piece_content(reader, piece).await
+    }
+
+    /// verify_piece_content reads the content of the piece from the reader and verifies it
+    /// by the length and digest of the piece metadata, the piece is hashed by the algorithm of
+    /// its digest.
+    async fn verify_piece_content<R: AsyncRead + Unpin>(
+        &self,
+        mut reader: R,
+        piece: &metadata::Piece,
+    ) -> Result<()> {
+        let algorithm = piece
+            .digest
+            .split_once(SEPARATOR)
+            .and_then(|(algorithm, _)| algorithm.parse::<Algorithm>().ok())
+            .unwrap_or(Algorithm::Crc32);
+        let mut hasher = Hasher::new(algorithm);
+        let mut buffer = vec![0; self.config.storage.read_buffer_size];
+        let mut length = 0;
+        loop {
+            let n = reader.read(&mut buffer).await?;
+            if n == 0 {
+                break;
+            }
+
+            hasher.update(&buffer[..n]);
+            length += n as u64;
+        }
+
+        if length != piece.length {
+            return Err(Error::ContentLengthMismatch(piece.length, length));
+        }
+
+        let digest = hasher.finalize();
+        if piece.digest != digest.to_string() {
+            return Err(Error::DigestMismatch(
+                piece.digest.clone(),
+                digest.to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
     /// delete_task deletes the task metadatas, task content and piece metadatas.
     #[instrument(skip_all)]
     pub async fn delete_task(&self, id: &str) {
@@ -190,23 +474,66 @@ impl Storage {
             error!("delete task content failed: {}", err);
         });
 
-        let mut cache = self.cache.clone();
-        cache.delete_task(id).await.unwrap_or_else(|err| {
+        self.cache.delete_task(id).await.unwrap_or_else(|err| {
             info!("delete task from cache failed: {}", err);
         });
     }
 
+    /// reload_encryption_keys reloads the encryption keys to rotate the active key, the new
+    /// pieces are encrypted by the active key and the old pieces are still decrypted by their
+    /// keys.
+    #[instrument(skip_all)]
+    pub async fn reload_encryption_keys(&self) -> Result<()> {
+        self.content.reload_encryption_keys().await
+    }
+
+    /// promote_task moves the content of the finished task to the fastest tier of the storage
+    /// directories, and returns whether the task is promoted.
+    #[instrument(skip_all)]
+    pub async fn promote_task(&self, id: &str) -> Result<bool> {
+        let task = self
+            .metadata
+            .get_task(id)?
+            .ok_or_else(|| Error::TaskNotFound(id.to_string()))?;
+        if !task.is_finished() {
+            return Ok(false);
+        }
+
+        let Some(content_length) = task.content_length() else {
+            return Ok(false);
+        };
+
+        self.content.promote_task(id, content_length).await
+    }
+
     /// hard_link_persistent_cache_task hard links the persistent cache task content to the destination.
+    /// The content of the encrypted persistent cache task can not be hard linked.
     #[instrument(skip_all)]
     pub async fn hard_link_persistent_cache_task(&self, task_id: &str, to: &Path) -> Result<()> {
+        if self.config.storage.encryption.enable || self.get_encoded_pieces(task_id)?.is_some() {
+            return Err(Error::Unsupported(format!(
+                "hard link encoded persistent cache task {}",
+                task_id
+            )));
+        }
+
         self.content
             .hard_link_persistent_cache_task(task_id, to)
             .await
     }
 
     /// copy_taskcopy_persistent_cache_taskcopies the persistent cache task content to the destination.
+    /// The encrypted persistent cache task is decrypted piece by piece if the plaintext output is allowed.
     #[instrument(skip_all)]
     pub async fn copy_persistent_cache_task(&self, id: &str, to: &Path) -> Result<()> {
+        if let Some(pieces) = self.get_encoded_pieces(id)? {
+            self.check_plaintext_output(id, &pieces)?;
+            return self
+                .content
+                .copy_persistent_cache_task_by_pieces(id, &pieces, to)
+                .await;
+        }
+
         self.content.copy_persistent_cache_task(id, to).await
     }
 
@@ -376,9 +703,9 @@ impl Storage {
     ) -> Result<metadata::Piece> {
         let response = self
             .content
-            .write_persistent_cache_piece(task_id, offset, length, reader)
+            .write_persistent_cache_piece(task_id, number, offset, length, reader)
             .await?;
-        let digest = Digest::new(Algorithm::Crc32, response.hash);
+        let digest = Digest::new(self.content.piece_digest_algorithm(), response.hash);
 
         self.metadata.create_persistent_cache_piece(
             piece_id,
@@ -386,6 +713,7 @@ impl Storage {
             offset,
             length,
             digest.to_string().as_str(),
+            response.encryption,
         )
     }
 
@@ -412,13 +740,14 @@ impl Storage {
         &self,
         piece_id: &str,
         task_id: &str,
+        number: u32,
         offset: u64,
         length: u64,
         reader: &mut R,
         timeout: Duration,
     ) -> Result<metadata::Piece> {
         tokio::select! {
-            piece = self.handle_downloaded_from_source_finished(piece_id, task_id, offset, length, reader) => {
+            piece = self.handle_downloaded_from_source_finished(piece_id, task_id, number, offset, length, reader) => {
                 piece
             }
             _ = sleep(timeout) => {
@@ -433,22 +762,25 @@ impl Storage {
         &self,
         piece_id: &str,
         task_id: &str,
+        number: u32,
         offset: u64,
         length: u64,
         reader: &mut R,
     ) -> Result<metadata::Piece> {
         let response = self
             .content
-            .write_piece(task_id, offset, length, reader)
+            .write_piece(task_id, number, offset, length, reader)
             .await?;
 
-        let digest = Digest::new(Algorithm::Crc32, response.hash);
+        let digest = Digest::new(self.content.piece_digest_algorithm(), response.hash);
         self.metadata.download_piece_finished(
             piece_id,
             offset,
             length,
             digest.to_string().as_str(),
             None,
+            response.compressed_length,
+            response.encryption,
         )
     }
 
@@ -459,6 +791,7 @@ impl Storage {
         &self,
         piece_id: &str,
         task_id: &str,
+        number: u32,
         offset: u64,
         length: u64,
         expected_digest: &str,
@@ -467,7 +800,7 @@ impl Storage {
         timeout: Duration,
     ) -> Result<metadata::Piece> {
         tokio::select! {
-            piece = self.handle_downloaded_piece_from_parent_finished(piece_id, task_id, offset, length, expected_digest, parent_id, reader) => {
+            piece = self.handle_downloaded_piece_from_parent_finished(piece_id, task_id, number, offset, length, expected_digest, parent_id, reader) => {
                 piece
             }
             _ = sleep(timeout) => {
@@ -483,6 +816,7 @@ impl Storage {
         &self,
         piece_id: &str,
         task_id: &str,
+        number: u32,
         offset: u64,
         length: u64,
         expected_digest: &str,
@@ -491,19 +825,18 @@ impl Storage {
     ) -> Result<metadata::Piece> {
         let response = self
             .content
-            .write_piece(task_id, offset, length, reader)
+            .write_piece(task_id, number, offset, length, reader)
             .await?;
 
         let length = response.length;
-        let digest = Digest::new(Algorithm::Crc32, response.hash);
+        let digest = Digest::new(self.content.piece_digest_algorithm(), response.hash);
 
         // Check the digest of the piece.
-        if expected_digest != digest.to_string() {
-            return Err(Error::DigestMismatch(
-                expected_digest.to_string(),
-                digest.to_string(),
-            ));
-        }
+        check_piece_digest(
+            expected_digest,
+            &digest,
+            self.config.download.integrity.enable,
+        )?;
 
         self.metadata.download_piece_finished(
             piece_id,
@@ -511,6 +844,8 @@ impl Storage {
             length,
             digest.to_string().as_str(),
             Some(parent_id.to_string()),
+            response.compressed_length,
+            response.encryption,
         )
     }
 
@@ -520,8 +855,9 @@ impl Storage {
         self.metadata.download_piece_failed(piece_id)
     }
 
-    /// upload_piece updates the metadata of the piece and
-    /// returns the data of the piece.
+    /// upload_piece updates the metadata of the piece and returns the data of the piece. The
+    /// task is uploading until the returned reader is read to the end or dropped, so that the
+    /// gc does not evict the task while the content is streamed to the other peers.
     #[instrument(skip_all)]
     pub async fn upload_piece(
         &self,
@@ -538,6 +874,7 @@ impl Storage {
         // Get the piece metadata and return the content of the piece.
         match self.metadata.get_piece(piece_id) {
             Ok(Some(piece)) => {
+                let length = range.as_ref().map_or(piece.length, |range| range.length);
                 if self.cache.contains_piece(task_id, piece_id).await {
                     match self
                         .cache
@@ -545,27 +882,29 @@ impl Storage {
                         .await
                     {
                         Ok(reader) => {
-                            // Finish uploading the task.
-                            self.metadata.upload_task_finished(task_id)?;
                             debug!("get piece from cache: {}", piece_id);
-                            return Ok(Either::Left(reader));
+                            return Ok(UploadReader::new(
+                                Either::Left(reader),
+                                self.metadata.clone(),
+                                task_id,
+                                length,
+                            ));
                         }
                         Err(err) => {
+                            // Failed uploading the task.
+                            self.metadata.upload_task_failed(task_id)?;
                             return Err(err);
                         }
                     }
                 }
 
-                match self
-                    .content
-                    .read_piece(task_id, piece.offset, piece.length, range)
-                    .await
-                {
-                    Ok(reader) => {
-                        // Finish uploading the task.
-                        self.metadata.upload_task_finished(task_id)?;
-                        Ok(Either::Right(reader))
-                    }
+                match self.content.read_piece(task_id, &piece, range).await {
+                    Ok(reader) => Ok(UploadReader::new(
+                        Either::Right(reader),
+                        self.metadata.clone(),
+                        task_id,
+                        length,
+                    )),
                     Err(err) => {
                         // Failed uploading the task.
                         self.metadata.upload_task_failed(task_id)?;
@@ -637,6 +976,7 @@ impl Storage {
         &self,
         piece_id: &str,
         task_id: &str,
+        number: u32,
         offset: u64,
         length: u64,
         expected_digest: &str,
@@ -645,19 +985,18 @@ impl Storage {
     ) -> Result<metadata::Piece> {
         let response = self
             .content
-            .write_persistent_cache_piece(task_id, offset, length, reader)
+            .write_persistent_cache_piece(task_id, number, offset, length, reader)
             .await?;
 
         let length = response.length;
-        let digest = Digest::new(Algorithm::Crc32, response.hash);
+        let digest = Digest::new(self.content.piece_digest_algorithm(), response.hash);
 
         // Check the digest of the piece.
-        if expected_digest != digest.to_string() {
-            return Err(Error::DigestMismatch(
-                expected_digest.to_string(),
-                digest.to_string(),
-            ));
-        }
+        check_piece_digest(
+            expected_digest,
+            &digest,
+            self.config.download.integrity.enable,
+        )?;
 
         self.metadata.download_piece_finished(
             piece_id,
@@ -665,6 +1004,8 @@ impl Storage {
             length,
             digest.to_string().as_str(),
             Some(parent_id.to_string()),
+            None,
+            response.encryption,
         )
     }
 
@@ -696,7 +1037,7 @@ impl Storage {
             Ok(Some(piece)) => {
                 match self
                     .content
-                    .read_persistent_cache_piece(task_id, piece.offset, piece.length, range)
+                    .read_persistent_cache_piece(task_id, &piece, range)
                     .await
                 {
                     Ok(reader) => {
@@ -748,6 +1089,243 @@ impl Storage {
         self.metadata.piece_id(task_id, number)
     }
 
+    /// create_cache_task creates a new cache task in the memory cache, if the cache task
+    /// already exists, return it directly. If the cache task is not admitted by the cache
+    /// policy, it bypasses the cache and is released once the downloads are finished.
+    #[instrument(skip_all)]
+    pub async fn create_cache_task(
+        &self,
+        id: &str,
+        piece_length: u64,
+        content_length: u64,
+    ) -> Result<cache::Task> {
+        if let Some(task) = self.cache.get_task(id).await {
+            return Ok(task);
+        }
+
+        if let Some(task) = self.cache.put_task(id, piece_length, content_length).await {
+            return Ok(task);
+        }
+
+        info!(
+            "cache task {} is not admitted and bypasses the cache: content_length={}",
+            id, content_length
+        );
+        Ok(self
+            .cache
+            .bypass_task(id, piece_length, content_length)
+            .await)
+    }
+
+    /// get_cache_task returns the cache task from the memory cache.
+    pub async fn get_cache_task(&self, id: &str) -> Option<cache::Task> {
+        self.cache.get_task(id).await
+    }
+
+    /// access_cache_task returns the cache task from the memory cache and records the access
+    /// of the cache task, it is called once per download of the cache task.
+    pub async fn access_cache_task(&self, id: &str) -> Option<cache::Task> {
+        self.cache.access_task(id).await
+    }
+
+    /// delete_cache_task deletes the cache task and its pieces from the memory cache.
+    #[instrument(skip_all)]
+    pub async fn delete_cache_task(&self, id: &str) -> Result<()> {
+        self.cache.delete_task(id).await
+    }
+
+    /// copy_cache_task copies the cache task content from the memory cache to the destination,
+    /// all pieces of the cache task must be finished.
+    #[instrument(skip_all)]
+    pub async fn copy_cache_task(&self, id: &str, to: &Path) -> Result<()> {
+        let Some(task) = self.cache.get_task(id).await else {
+            return Err(Error::TaskNotFound(id.to_string()));
+        };
+
+        // Ensure the parent directory of the destination exists.
+        if let Some(parent) = to.parent() {
+            if !parent.exists() {
+                tokio::fs::create_dir_all(parent).await.inspect_err(|err| {
+                    error!("failed to create directory {:?}: {}", parent, err);
+                })?;
+            }
+        }
+
+        let mut to_f = tokio::fs::OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(to)
+            .await?;
+
+        let piece_count = task.content_length().div_ceil(task.piece_length()) as u32;
+        for number in 0..piece_count {
+            let Some((_, content)) = self.get_cache_piece(id, number).await? else {
+                return Err(Error::PieceNotFound(self.cache_piece_id(id, number)));
+            };
+
+            to_f.write_all(&content).await?;
+        }
+
+        to_f.flush().await?;
+        info!("copy cache task {} to {:?} success", id, to);
+        Ok(())
+    }
+
+    /// download_cache_piece_from_source_finished is used for downloading cache piece from source,
+    /// the content of the piece is stored in the memory cache.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip_all)]
+    pub async fn download_cache_piece_from_source_finished<R: AsyncRead + Unpin + ?Sized>(
+        &self,
+        piece_id: &str,
+        task_id: &str,
+        number: u32,
+        offset: u64,
+        length: u64,
+        reader: &mut R,
+        timeout: Duration,
+    ) -> Result<metadata::Piece> {
+        let mut content = Vec::with_capacity(length as usize);
+        let mut reader = reader.take(length);
+        tokio::select! {
+            result = reader.read_to_end(&mut content) => {
+                result?;
+            }
+            _ = sleep(timeout) => {
+                return Err(Error::DownloadPieceFinished(piece_id.to_string()));
+            }
+        }
+
+        if content.len() as u64 != length {
+            return Err(Error::ContentLengthMismatch(length, content.len() as u64));
+        }
+
+        let content = Bytes::from(content);
+        let digest = Digest::new(Algorithm::Crc32, crc32fast::hash(&content).to_string());
+        self.cache.write_piece(task_id, piece_id, content).await?;
+        Ok(Self::cache_piece(
+            number,
+            offset,
+            length,
+            digest.to_string(),
+            None,
+        ))
+    }
+
+    /// download_cache_piece_from_parent_finished is used for downloading cache piece from parent,
+    /// the content of the piece is verified by the expected digest and stored in the memory cache.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip_all)]
+    pub async fn download_cache_piece_from_parent_finished(
+        &self,
+        piece_id: &str,
+        task_id: &str,
+        number: u32,
+        offset: u64,
+        expected_digest: &str,
+        parent_id: &str,
+        content: Bytes,
+    ) -> Result<metadata::Piece> {
+        let length = content.len() as u64;
+        let digest = Digest::new(Algorithm::Crc32, crc32fast::hash(&content).to_string());
+
+        // Check the digest of the piece.
+        if expected_digest != digest.to_string() {
+            return Err(Error::DigestMismatch(
+                expected_digest.to_string(),
+                digest.to_string(),
+            ));
+        }
+
+        self.cache.write_piece(task_id, piece_id, content).await?;
+        Ok(Self::cache_piece(
+            number,
+            offset,
+            length,
+            digest.to_string(),
+            Some(parent_id.to_string()),
+        ))
+    }
+
+    /// is_cache_piece_exists returns whether the cache piece exists in the memory cache.
+    pub async fn is_cache_piece_exists(&self, task_id: &str, piece_id: &str) -> bool {
+        self.cache.contains_piece(task_id, piece_id).await
+    }
+
+    /// get_cache_piece returns the cache piece metadata and content from the memory cache, the
+    /// offset and length of the piece are calculated by the piece length of the cache task.
+    #[instrument(skip_all)]
+    pub async fn get_cache_piece(
+        &self,
+        task_id: &str,
+        number: u32,
+    ) -> Result<Option<(metadata::Piece, Bytes)>> {
+        let Some(task) = self.cache.get_task(task_id).await else {
+            return Err(Error::TaskNotFound(task_id.to_string()));
+        };
+
+        let piece_id = self.cache_piece_id(task_id, number);
+        let offset = task.piece_length() * number as u64;
+        if offset >= task.content_length() {
+            return Err(Error::InvalidParameter);
+        }
+
+        let length = task.piece_length().min(task.content_length() - offset);
+        let piece = Self::cache_piece(number, offset, length, String::new(), None);
+        let content = match self
+            .cache
+            .read_piece(task_id, piece_id.as_str(), piece.clone(), None)
+            .await
+        {
+            Ok(mut reader) => {
+                let mut content = Vec::with_capacity(length as usize);
+                reader.read_to_end(&mut content).await?;
+                Bytes::from(content)
+            }
+            Err(Error::PieceNotFound(_)) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let digest = Digest::new(Algorithm::Crc32, crc32fast::hash(&content).to_string());
+        Ok(Some((
+            metadata::Piece {
+                digest: digest.to_string(),
+                ..piece
+            },
+            content,
+        )))
+    }
+
+    /// cache_piece_id returns the cache piece id.
+    #[inline]
+    pub fn cache_piece_id(&self, task_id: &str, number: u32) -> String {
+        self.metadata.piece_id(task_id, number)
+    }
+
+    /// cache_piece constructs the finished piece metadata of the cache piece, the cache piece is
+    /// only stored in the memory cache, so the metadata is not persisted.
+    fn cache_piece(
+        number: u32,
+        offset: u64,
+        length: u64,
+        digest: String,
+        parent_id: Option<String>,
+    ) -> metadata::Piece {
+        let now = Utc::now().naive_utc();
+        metadata::Piece {
+            number,
+            offset,
+            length,
+            digest,
+            parent_id,
+            created_at: now,
+            updated_at: now,
+            finished_at: Some(now),
+            ..Default::default()
+        }
+    }
+
     /// wait_for_piece_finished waits for the piece to be finished.
     #[instrument(skip_all)]
     async fn wait_for_piece_finished(&self, piece_id: &str) -> Result<metadata::Piece> {
@@ -813,3 +1391,248 @@ impl Storage {
         }
     }
 }
+
+/// UploadReader is the reader of the piece uploaded to the other peers, it holds the uploading
+/// count of the task until the content is read to the end or the reader is dropped. The upload
+/// is finished if the expected length is read, otherwise it is failed.
+pub struct UploadReader<R> {
+    /// reader is the reader of the piece content.
+    reader: Pin<Box<R>>,
+
+    /// metadata is the metadata storage.
+    metadata: Arc<metadata::Metadata>,
+
+    /// task_id is the id of the uploading task.
+    task_id: String,
+
+    /// remaining is the length of the content not read yet.
+    remaining: u64,
+
+    /// failed indicates whether reading the content failed.
+    failed: bool,
+}
+
+/// UploadReader implements the upload reader.
+impl<R: AsyncRead> UploadReader<R> {
+    /// new returns a new UploadReader, the uploading count of the task must be increased
+    /// before.
+    fn new(reader: R, metadata: Arc<metadata::Metadata>, task_id: &str, length: u64) -> Self {
+        Self {
+            reader: Box::pin(reader),
+            metadata,
+            task_id: task_id.to_string(),
+            remaining: length,
+            failed: false,
+        }
+    }
+}
+
+/// UploadReader implements AsyncRead.
+impl<R: AsyncRead> AsyncRead for UploadReader<R> {
+    /// poll_read reads the piece content and records the remaining length.
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let filled = buf.filled().len();
+        let result = self.reader.as_mut().poll_read(cx, buf);
+        match &result {
+            Poll::Ready(Ok(())) => {
+                let read = (buf.filled().len() - filled) as u64;
+                self.remaining = self.remaining.saturating_sub(read);
+            }
+            Poll::Ready(Err(_)) => self.failed = true,
+            Poll::Pending => {}
+        }
+
+        result
+    }
+}
+
+/// UploadReader implements Drop.
+impl<R> Drop for UploadReader<R> {
+    /// drop finishes uploading the task.
+    fn drop(&mut self) {
+        let result = if !self.failed && self.remaining == 0 {
+            self.metadata.upload_task_finished(&self.task_id)
+        } else {
+            self.metadata.upload_task_failed(&self.task_id)
+        };
+
+        if let Err(err) = result {
+            error!("finish uploading task {} failed: {}", self.task_id, err);
+        }
+    }
+}
+
+/// check_piece_digest checks the digest of the piece downloaded from the parent by the expected
+/// digest. The digests calculated by the different algorithms are not comparable, e.g. the
+/// integrity is only enabled by one of the peers, so the digest is not checked in that case.
+/// If the integrity is enabled, the expected digest must be sha256, otherwise the piece is
+/// stored without being verified.
+fn check_piece_digest(expected_digest: &str, digest: &Digest, integrity: bool) -> Result<()> {
+    if let Some((algorithm, _)) = expected_digest.split_once(SEPARATOR) {
+        if algorithm != digest.algorithm().to_string() {
+            if integrity {
+                return Err(Error::ValidationError(format!(
+                    "expected digest {} is not {} with integrity enabled",
+                    expected_digest,
+                    digest.algorithm()
+                )));
+            }
+
+            warn!(
+                "skip to check the digest {} by the expected digest {}",
+                digest, expected_digest
+            );
+            return Ok(());
+        }
+    }
+
+    if expected_digest != digest.to_string() {
+        return Err(Error::DigestMismatch(
+            expected_digest.to_string(),
+            digest.to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tempfile::tempdir;
+
+    #[test]
+    fn should_check_piece_digest() {
+        let digest = Digest::new(Algorithm::Sha256, "abc".to_string());
+        assert!(check_piece_digest("sha256:abc", &digest, true).is_ok());
+        assert!(matches!(
+            check_piece_digest("sha256:abd", &digest, false),
+            Err(Error::DigestMismatch(_, _))
+        ));
+
+        // The digest of the other algorithm is skipped only if the integrity is disabled.
+        assert!(check_piece_digest("crc32:123", &digest, false).is_ok());
+        assert!(matches!(
+            check_piece_digest("crc32:123", &digest, true),
+            Err(Error::ValidationError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_copy_plaintext_output_with_compression() {
+        let dir = tempdir().unwrap();
+        let mut config = Config::default();
+        config.storage.compression.enable = true;
+        let storage = Storage::new(Arc::new(config), dir.path(), dir.path().join("log"))
+            .await
+            .unwrap();
+
+        let task_id = "d3c4e940ad06c47fc36ac67801e6f8e36cb400e2391708620bc7e865b102062c";
+        let content = vec![b'a'; 4096];
+        storage
+            .download_task_started(task_id, 4096, 4096, None, None)
+            .await
+            .unwrap();
+
+        // The output can not be hard linked before the pieces are downloaded.
+        let output_path = dir.path().join("output");
+        assert!(matches!(
+            storage.hard_link_task(task_id, &output_path).await,
+            Err(Error::Unsupported(_))
+        ));
+        assert!(!output_path.exists());
+
+        let piece_id = storage.piece_id(task_id, 0);
+        storage.download_piece_started(&piece_id, 0).await.unwrap();
+        let piece = storage
+            .download_piece_from_source_finished(
+                &piece_id,
+                task_id,
+                0,
+                0,
+                4096,
+                &mut Cursor::new(content.clone()),
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+        assert!(piece.compressed_length.is_some());
+
+        storage.copy_task(task_id, &output_path).await.unwrap();
+        assert_eq!(std::fs::read(&output_path).unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn should_reject_plaintext_output_with_encryption() {
+        let dir = tempdir().unwrap();
+        let key_file = dir.path().join("keys");
+        std::fs::write(&key_file, format!("k1:{}\n", "11".repeat(32))).unwrap();
+
+        let mut config = Config::default();
+        config.storage.encryption.enable = true;
+        config.storage.encryption.key_file = Some(key_file);
+        let storage = Storage::new(Arc::new(config), dir.path(), dir.path().join("log"))
+            .await
+            .unwrap();
+
+        let task_id = "d3c4e940ad06c47fc36ac67801e6f8e36cb400e2391708620bc7e865b102062c";
+        assert!(matches!(
+            storage.check_output(task_id),
+            Err(Error::Unsupported(_))
+        ));
+
+        // The output can not be hard linked before the pieces are downloaded.
+        storage
+            .download_task_started(task_id, 4, 4, None, None)
+            .await
+            .unwrap();
+        let output_path = dir.path().join("output");
+        assert!(matches!(
+            storage.hard_link_task(task_id, &output_path).await,
+            Err(Error::Unsupported(_))
+        ));
+        assert!(!output_path.exists());
+    }
+
+    #[tokio::test]
+    async fn should_hold_uploading_count_until_upload_reader_finished() {
+        let dir = tempdir().unwrap();
+        let log_dir = dir.path().join("log");
+        let metadata = Arc::new(
+            metadata::Metadata::new(Arc::new(Config::default()), dir.path(), &log_dir).unwrap(),
+        );
+
+        let task_id = "d3c4e940ad06c47fc36ac67801e6f8e36cb400e2391708620bc7e865b102062c";
+        metadata
+            .download_task_started(task_id, Some(4), Some(4), None, None, None, None)
+            .unwrap();
+
+        // The upload is finished after the content is read to the end.
+        metadata.upload_task_started(task_id).unwrap();
+        let mut reader =
+            UploadReader::new(Cursor::new(b"data".to_vec()), metadata.clone(), task_id, 4);
+        assert!(metadata.get_task(task_id).unwrap().unwrap().is_uploading());
+
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).await.unwrap();
+        assert!(metadata.get_task(task_id).unwrap().unwrap().is_uploading());
+
+        drop(reader);
+        let task = metadata.get_task(task_id).unwrap().unwrap();
+        assert!(!task.is_uploading());
+        assert_eq!(task.uploaded_count, 1);
+
+        // The upload is failed if the reader is dropped before the content is read.
+        metadata.upload_task_started(task_id).unwrap();
+        let reader = UploadReader::new(Cursor::new(b"data".to_vec()), metadata.clone(), task_id, 4);
+        drop(reader);
+        let task = metadata.get_task(task_id).unwrap().unwrap();
+        assert!(!task.is_uploading());
+        assert_eq!(task.uploaded_count, 1);
+    }
+}
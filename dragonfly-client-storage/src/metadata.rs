@@ -15,8 +15,11 @@
  */
 
 use chrono::{NaiveDateTime, Utc};
-use dragonfly_client_config::dfdaemon::Config;
-use dragonfly_client_core::{Error, Result};
+use dragonfly_client_config::dfdaemon::{Config, EncryptionAlgorithm, MetadataEngineType};
+use dragonfly_client_core::{
+    error::{ErrorType, OrErr},
+    Error, Result,
+};
 use dragonfly_client_util::{digest, http::headermap_to_hashmap};
 use reqwest::header::HeaderMap;
 use serde::{Deserialize, Serialize};
@@ -25,9 +28,13 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
-use tracing::{error, info, instrument};
+use tracing::{error, info, instrument, warn};
 
-use crate::storage_engine::{rocksdb::RocksdbStorageEngine, DatabaseObject, StorageEngineOwned};
+use crate::storage_engine::{DatabaseObject, Engine, StorageEngineOwned};
+
+/// MIGRATE_SCHEMA_BATCH_SIZE is the number of the objects read from the storage engine and
+/// migrated in a batch.
+const MIGRATE_SCHEMA_BATCH_SIZE: usize = 1024;
 
 /// Task is the metadata of the task.
 #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
@@ -65,12 +72,75 @@ pub struct Task {
 
     /// finished_at is the time when the task downloads finished.
     pub finished_at: Option<NaiveDateTime>,
+
+    /// application is the application of the task, it is set by the first download of the task.
+    pub application: Option<String>,
+
+    /// tag is the tag of the task, it is set by the first download of the task.
+    pub tag: Option<String>,
+
+    /// prefetch_request is the serialized download request of the prefetch, it is used to
+    /// resume the prefetch after dfdaemon restarts.
+    pub prefetch_request: Option<Vec<u8>>,
+
+    /// digest is the sha256 digest of the content, the finished task is indexed by it to
+    /// deduplicate the tasks which have the same content.
+    pub digest: Option<String>,
 }
 
 /// Task implements the task database object.
 impl DatabaseObject for Task {
     /// NAMESPACE is the namespace of [Task] objects.
     const NAMESPACE: &'static str = "task";
+
+    /// VERSION is the version of the encoding of [Task] objects.
+    const VERSION: u32 = 1;
+
+    /// migrate converts the serialized task of the given version to the next version.
+    fn migrate(version: u32, bytes: &[u8]) -> Result<Vec<u8>> {
+        match version {
+            // Version 1 adds the application, tag, prefetch_request and digest fields.
+            0 => {
+                let task: TaskV0 = bincode::deserialize(bytes).or_err(ErrorType::SerializeError)?;
+                Task {
+                    id: task.id,
+                    piece_length: task.piece_length,
+                    content_length: task.content_length,
+                    response_header: task.response_header,
+                    uploading_count: task.uploading_count,
+                    uploaded_count: task.uploaded_count,
+                    updated_at: task.updated_at,
+                    created_at: task.created_at,
+                    prefetched_at: task.prefetched_at,
+                    failed_at: task.failed_at,
+                    finished_at: task.finished_at,
+                    ..Default::default()
+                }
+                .serialized()
+            }
+            _ => Err(Error::Unsupported(format!(
+                "migrate {} from version {}",
+                Self::NAMESPACE,
+                version
+            ))),
+        }
+    }
+}
+
+/// TaskV0 is the encoding of [Task] objects of version 0.
+#[derive(Serialize, Deserialize)]
+struct TaskV0 {
+    id: String,
+    piece_length: Option<u64>,
+    content_length: Option<u64>,
+    response_header: HashMap<String, String>,
+    uploading_count: i64,
+    uploaded_count: u64,
+    updated_at: NaiveDateTime,
+    created_at: NaiveDateTime,
+    prefetched_at: Option<NaiveDateTime>,
+    failed_at: Option<NaiveDateTime>,
+    finished_at: Option<NaiveDateTime>,
 }
 
 /// Task implements the task metadata.
@@ -95,6 +165,12 @@ impl Task {
         self.prefetched_at.is_some()
     }
 
+    /// is_resumable returns whether the task can be resumed after dfdaemon restarts, the task
+    /// is resumable if the download started with the content length and not finished.
+    pub fn is_resumable(&self) -> bool {
+        !self.is_finished() && self.content_length.is_some() && self.piece_length.is_some()
+    }
+
     /// is_failed returns whether the task downloads failed.
     pub fn is_failed(&self) -> bool {
         self.failed_at.is_some()
@@ -250,12 +326,124 @@ pub struct Piece {
 
     /// finished_at is the time when the piece downloads finished.
     pub finished_at: Option<NaiveDateTime>,
+
+    /// compressed_length is the length of the zstd compressed content stored at the offset of
+    /// the piece, None means the piece is stored uncompressed.
+    pub compressed_length: Option<u64>,
+
+    /// encryption is the encryption of the content stored at the offset of the piece, None
+    /// means the piece is stored unencrypted.
+    pub encryption: Option<PieceEncryption>,
+}
+
+/// PieceEncryption is the encryption metadata of the piece content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PieceEncryption {
+    /// algorithm is the AEAD algorithm to encrypt the piece content.
+    pub algorithm: EncryptionAlgorithm,
+
+    /// key_id is the id of the key to encrypt the piece content.
+    pub key_id: String,
+
+    /// nonce is the random nonce to encrypt the piece content.
+    pub nonce: Vec<u8>,
+
+    /// tag is the detached authentication tag of the encrypted piece content.
+    pub tag: Vec<u8>,
 }
 
 /// Piece implements the piece database object.
 impl DatabaseObject for Piece {
     /// NAMESPACE is the namespace of [Piece] objects.
     const NAMESPACE: &'static str = "piece";
+
+    /// VERSION is the version of the encoding of [Piece] objects.
+    const VERSION: u32 = 2;
+
+    /// EVENTUAL_DURABILITY is enabled for [Piece] objects, because they are updated on every
+    /// piece download and the lost piece is downloaded again.
+    const EVENTUAL_DURABILITY: bool = true;
+
+    /// migrate converts the serialized piece of the given version to the next version.
+    fn migrate(version: u32, bytes: &[u8]) -> Result<Vec<u8>> {
+        match version {
+            // Version 1 adds the compressed_length field.
+            0 => {
+                let piece: PieceV0 =
+                    bincode::deserialize(bytes).or_err(ErrorType::SerializeError)?;
+                Ok(bincode::serialize(&PieceV1 {
+                    number: piece.number,
+                    offset: piece.offset,
+                    length: piece.length,
+                    digest: piece.digest,
+                    parent_id: piece.parent_id,
+                    uploading_count: piece.uploading_count,
+                    uploaded_count: piece.uploaded_count,
+                    updated_at: piece.updated_at,
+                    created_at: piece.created_at,
+                    finished_at: piece.finished_at,
+                    compressed_length: None,
+                })
+                .or_err(ErrorType::SerializeError)?)
+            }
+            // Version 2 adds the encryption field.
+            1 => {
+                let piece: PieceV1 =
+                    bincode::deserialize(bytes).or_err(ErrorType::SerializeError)?;
+                Piece {
+                    number: piece.number,
+                    offset: piece.offset,
+                    length: piece.length,
+                    digest: piece.digest,
+                    parent_id: piece.parent_id,
+                    uploading_count: piece.uploading_count,
+                    uploaded_count: piece.uploaded_count,
+                    updated_at: piece.updated_at,
+                    created_at: piece.created_at,
+                    finished_at: piece.finished_at,
+                    compressed_length: piece.compressed_length,
+                    encryption: None,
+                }
+                .serialized()
+            }
+            _ => Err(Error::Unsupported(format!(
+                "migrate {} from version {}",
+                Self::NAMESPACE,
+                version
+            ))),
+        }
+    }
+}
+
+/// PieceV0 is the encoding of [Piece] objects of version 0.
+#[derive(Serialize, Deserialize)]
+struct PieceV0 {
+    number: u32,
+    offset: u64,
+    length: u64,
+    digest: String,
+    parent_id: Option<String>,
+    uploading_count: i64,
+    uploaded_count: u64,
+    updated_at: NaiveDateTime,
+    created_at: NaiveDateTime,
+    finished_at: Option<NaiveDateTime>,
+}
+
+/// PieceV1 is the encoding of [Piece] objects of version 1.
+#[derive(Serialize, Deserialize)]
+struct PieceV1 {
+    number: u32,
+    offset: u64,
+    length: u64,
+    digest: String,
+    parent_id: Option<String>,
+    uploading_count: i64,
+    uploaded_count: u64,
+    updated_at: NaiveDateTime,
+    created_at: NaiveDateTime,
+    finished_at: Option<NaiveDateTime>,
+    compressed_length: Option<u64>,
 }
 
 /// Piece implements the piece metadata.
@@ -270,6 +458,12 @@ impl Piece {
         self.finished_at.is_some()
     }
 
+    /// is_encoded returns whether the piece content is stored compressed or encrypted, the
+    /// encoded piece must be read entirely and decoded.
+    pub fn is_encoded(&self) -> bool {
+        self.compressed_length.is_some() || self.encryption.is_some()
+    }
+
     /// cost returns the cost of the piece downloaded.
     pub fn cost(&self) -> Option<Duration> {
         match self
@@ -316,17 +510,54 @@ impl Piece {
     }
 }
 
+/// DigestIndex is the index from the digest of the content to the finished task.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DigestIndex {
+    /// digest is the sha256 digest of the content.
+    pub digest: String,
+
+    /// task_id is the id of the finished task which has the content.
+    pub task_id: String,
+}
+
+/// DigestIndex implements the digest index database object.
+impl DatabaseObject for DigestIndex {
+    /// NAMESPACE is the namespace of [DigestIndex] objects.
+    const NAMESPACE: &'static str = "digest_index";
+}
+
+/// Schema is the version of the encoding of the objects in the namespace.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schema {
+    /// namespace is the namespace of the objects.
+    pub namespace: String,
+
+    /// version is the version of the encoding of the objects.
+    pub version: u32,
+}
+
+/// Schema implements the schema database object.
+impl DatabaseObject for Schema {
+    /// NAMESPACE is the namespace of [Schema] objects.
+    const NAMESPACE: &'static str = "schema";
+}
+
 /// Metadata manages the metadata of [Task], [Piece] and [PersistentCacheTask].
-pub struct Metadata<E = RocksdbStorageEngine>
+pub struct Metadata<E = Engine>
 where
     E: StorageEngineOwned,
 {
     /// db is the underlying storage engine instance.
     db: E,
+
+    /// dropped_objects is the number of the objects dropped by the schema migration of each
+    /// namespace when the metadata is opened.
+    dropped_objects: HashMap<&'static str, usize>,
 }
 
 impl<E: StorageEngineOwned> Metadata<E> {
     /// download_task_started updates the metadata of the task when the task downloads started.
+    #[allow(clippy::too_many_arguments)]
     #[instrument(skip_all)]
     pub fn download_task_started(
         &self,
@@ -334,6 +565,9 @@ impl<E: StorageEngineOwned> Metadata<E> {
         piece_length: Option<u64>,
         content_length: Option<u64>,
         response_header: Option<HeaderMap>,
+        application: Option<String>,
+        tag: Option<String>,
+        digest: Option<String>,
     ) -> Result<Task> {
         // Convert the response header to hashmap.
         let response_header = response_header
@@ -363,6 +597,20 @@ impl<E: StorageEngineOwned> Metadata<E> {
                     task.response_header = response_header;
                 }
 
+                // Protect application and tag to be overwritten by None.
+                if application.is_some() {
+                    task.application = application;
+                }
+
+                if tag.is_some() {
+                    task.tag = tag;
+                }
+
+                // Protect digest to be overwritten by None.
+                if digest.is_some() {
+                    task.digest = digest;
+                }
+
                 task
             }
             None => Task {
@@ -370,6 +618,9 @@ impl<E: StorageEngineOwned> Metadata<E> {
                 piece_length,
                 content_length,
                 response_header,
+                application,
+                tag,
+                digest,
                 updated_at: Utc::now().naive_utc(),
                 created_at: Utc::now().naive_utc(),
                 ..Default::default()
@@ -394,6 +645,18 @@ impl<E: StorageEngineOwned> Metadata<E> {
         };
 
         self.db.put(id.as_bytes(), &task)?;
+
+        // Index the finished task by the digest of the content.
+        if let Some(digest) = task.digest.as_ref() {
+            self.db.put(
+                digest.as_bytes(),
+                &DigestIndex {
+                    digest: digest.clone(),
+                    task_id: id.to_string(),
+                },
+            )?;
+        }
+
         Ok(task)
     }
 
@@ -415,7 +678,7 @@ impl<E: StorageEngineOwned> Metadata<E> {
 
     /// prefetch_task_started updates the metadata of the task when the task prefetch started.
     #[instrument(skip_all)]
-    pub fn prefetch_task_started(&self, id: &str) -> Result<Task> {
+    pub fn prefetch_task_started(&self, id: &str, request: Vec<u8>) -> Result<Task> {
         let task = match self.db.get::<Task>(id.as_bytes())? {
             Some(mut task) => {
                 // If the task is prefetched, return an error.
@@ -425,6 +688,7 @@ impl<E: StorageEngineOwned> Metadata<E> {
 
                 task.updated_at = Utc::now().naive_utc();
                 task.prefetched_at = Some(Utc::now().naive_utc());
+                task.prefetch_request = Some(request);
                 task.failed_at = None;
                 task
             }
@@ -442,6 +706,7 @@ impl<E: StorageEngineOwned> Metadata<E> {
             Some(mut task) => {
                 task.updated_at = Utc::now().naive_utc();
                 task.prefetched_at = None;
+                task.prefetch_request = None;
                 task.failed_at = Some(Utc::now().naive_utc());
                 task
             }
@@ -535,9 +800,36 @@ impl<E: StorageEngineOwned> Metadata<E> {
     #[instrument(skip_all)]
     pub fn delete_task(&self, id: &str) -> Result<()> {
         info!("delete task metadata {}", id);
+
+        // Delete the digest index if it points to the task.
+        if let Some(digest) = self
+            .db
+            .get::<Task>(id.as_bytes())?
+            .and_then(|task| task.digest)
+        {
+            if let Some(digest_index) = self.get_digest_index(&digest)? {
+                if digest_index.task_id == id {
+                    self.delete_digest_index(&digest)?;
+                }
+            }
+        }
+
         self.db.delete::<Task>(id.as_bytes())
     }
 
+    /// get_digest_index gets the digest index by the digest of the content.
+    #[instrument(skip_all)]
+    pub fn get_digest_index(&self, digest: &str) -> Result<Option<DigestIndex>> {
+        self.db.get(digest.as_bytes())
+    }
+
+    /// delete_digest_index deletes the digest index by the digest of the content.
+    #[instrument(skip_all)]
+    pub fn delete_digest_index(&self, digest: &str) -> Result<()> {
+        info!("delete digest index {}", digest);
+        self.db.delete::<DigestIndex>(digest.as_bytes())
+    }
+
     /// create_persistent_cache_task creates a new persistent cache task.
     #[instrument(skip_all)]
     pub fn create_persistent_cache_task_started(
@@ -763,6 +1055,7 @@ impl<E: StorageEngineOwned> Metadata<E> {
         offset: u64,
         length: u64,
         digest: &str,
+        encryption: Option<PieceEncryption>,
     ) -> Result<Piece> {
         // Construct the piece metadata.
         let piece = Piece {
@@ -770,6 +1063,7 @@ impl<E: StorageEngineOwned> Metadata<E> {
             offset,
             length,
             digest: digest.to_string(),
+            encryption,
             // Persistent cache piece does not have parent id, because the piece content is
             // imported by local.
             parent_id: None,
@@ -801,6 +1095,7 @@ impl<E: StorageEngineOwned> Metadata<E> {
     }
 
     /// download_piece_finished updates the metadata of the piece when the piece downloads finished.
+    #[allow(clippy::too_many_arguments)]
     #[instrument(skip_all)]
     pub fn download_piece_finished(
         &self,
@@ -809,6 +1104,8 @@ impl<E: StorageEngineOwned> Metadata<E> {
         length: u64,
         digest: &str,
         parent_id: Option<String>,
+        compressed_length: Option<u64>,
+        encryption: Option<PieceEncryption>,
     ) -> Result<Piece> {
         let piece = match self.db.get::<Piece>(piece_id.as_bytes())? {
             Some(mut piece) => {
@@ -816,6 +1113,8 @@ impl<E: StorageEngineOwned> Metadata<E> {
                 piece.length = length;
                 piece.digest = digest.to_string();
                 piece.parent_id = parent_id;
+                piece.compressed_length = compressed_length;
+                piece.encryption = encryption;
                 piece.updated_at = Utc::now().naive_utc();
                 piece.finished_at = Some(Utc::now().naive_utc());
                 piece
@@ -868,6 +1167,18 @@ impl<E: StorageEngineOwned> Metadata<E> {
             .collect()
     }
 
+    /// get_piece_ids returns the ids of all the piece metadatas.
+    #[instrument(skip_all)]
+    pub fn get_piece_ids(&self) -> Result<Vec<String>> {
+        self.db
+            .iter_raw::<Piece>()?
+            .map(|ele| {
+                let (key, _) = ele?;
+                Ok(String::from_utf8_lossy(&key).to_string())
+            })
+            .collect()
+    }
+
     /// delete_piece deletes the piece metadata.
     #[instrument(skip_all)]
     pub fn delete_piece(&self, piece_id: &str) -> Result<()> {
@@ -904,40 +1215,243 @@ impl<E: StorageEngineOwned> Metadata<E> {
         Ok(())
     }
 
+    /// clone_pieces clones the finished piece metadatas of the task to the other task, and
+    /// returns the number of the cloned pieces.
+    #[instrument(skip_all)]
+    pub fn clone_pieces(&self, from_task_id: &str, to_task_id: &str) -> Result<usize> {
+        let mut cloned_pieces = 0;
+        for mut piece in self.get_pieces(from_task_id)? {
+            if !piece.is_finished() {
+                continue;
+            }
+
+            piece.updated_at = Utc::now().naive_utc();
+            self.db
+                .put(self.piece_id(to_task_id, piece.number).as_bytes(), &piece)?;
+            cloned_pieces += 1;
+        }
+
+        Ok(cloned_pieces)
+    }
+
     /// piece_id returns the piece id.
     #[inline]
     pub fn piece_id(&self, task_id: &str, number: u32) -> String {
         format!("{}-{}", task_id, number)
     }
+
+    /// get_schema_version returns the version of the encoding of the objects in the namespace
+    /// stored in the database.
+    pub fn get_schema_version<O: DatabaseObject>(&self) -> Result<Option<u32>> {
+        Ok(self
+            .db
+            .get::<Schema>(O::NAMESPACE.as_bytes())?
+            .map(|schema| schema.version))
+    }
+
+    /// dropped_objects returns the number of the objects dropped by the schema migration of
+    /// each namespace when the metadata is opened.
+    pub fn dropped_objects(&self) -> &HashMap<&'static str, usize> {
+        &self.dropped_objects
+    }
+
+    /// migrate_schema migrates the objects of all the namespaces to the current version of
+    /// the encoding, so the metadata kept by the previous dfdaemon can be decoded. It returns
+    /// the number of the objects which can not be migrated and are dropped of each namespace.
+    #[instrument(skip_all)]
+    pub fn migrate_schema(&self) -> Result<HashMap<&'static str, usize>> {
+        let dropped_objects = HashMap::from([
+            (Task::NAMESPACE, self.migrate_namespace_schema::<Task>()?),
+            (
+                PersistentCacheTask::NAMESPACE,
+                self.migrate_namespace_schema::<PersistentCacheTask>()?,
+            ),
+            (Piece::NAMESPACE, self.migrate_namespace_schema::<Piece>()?),
+            (
+                DigestIndex::NAMESPACE,
+                self.migrate_namespace_schema::<DigestIndex>()?,
+            ),
+        ]);
+
+        for (namespace, dropped) in dropped_objects.iter() {
+            if *dropped > 0 {
+                warn!(
+                    "dropped {} {} objects which can not be migrated, they will be downloaded again",
+                    dropped, namespace
+                );
+            }
+        }
+
+        Ok(dropped_objects)
+    }
+
+    /// migrate_namespace_schema migrates the objects of the namespace from the stored version
+    /// to the current version step by step, and returns the number of the dropped objects.
+    /// The objects are read from the iterator in batches of [MIGRATE_SCHEMA_BATCH_SIZE], so
+    /// the whole namespace is not loaded into memory. The object which can not be migrated is
+    /// deleted, so it can be downloaded again.
+    fn migrate_namespace_schema<O: DatabaseObject>(&self) -> Result<usize> {
+        let version = match self.get_schema_version::<O>()? {
+            Some(version) => version,
+            // The objects written before the schema is versioned are version 0, and the empty
+            // namespace has nothing to migrate.
+            None => {
+                if self.db.iter_raw::<O>()?.next().is_some() {
+                    0
+                } else {
+                    O::VERSION
+                }
+            }
+        };
+
+        if version > O::VERSION {
+            return Err(Error::Unsupported(format!(
+                "{} version {} is newer than {}",
+                O::NAMESPACE,
+                version,
+                O::VERSION
+            )));
+        }
+
+        let mut dropped = 0;
+        if version < O::VERSION {
+            info!(
+                "migrate {} from version {} to {}",
+                O::NAMESPACE,
+                version,
+                O::VERSION
+            );
+
+            // The iterator of rocksdb reads from the implicit snapshot, so the objects written
+            // by the migration are not visited again.
+            let mut objects = self.db.iter_raw::<O>()?;
+            let mut batch = Vec::with_capacity(MIGRATE_SCHEMA_BATCH_SIZE);
+            loop {
+                for object in objects.by_ref().take(MIGRATE_SCHEMA_BATCH_SIZE) {
+                    batch.push(object?);
+                }
+
+                if batch.is_empty() {
+                    break;
+                }
+
+                for (key, value) in batch.drain(..) {
+                    let mut result = Ok(value.into_vec());
+                    for version in version..O::VERSION {
+                        result = result.and_then(|bytes| O::migrate(version, &bytes));
+                    }
+
+                    match result.and_then(|bytes| O::deserialize_from(&bytes)) {
+                        Ok(object) => self.db.put::<O>(&key, &object)?,
+                        Err(err) => {
+                            error!(
+                                "migrate {} {} failed: {}",
+                                O::NAMESPACE,
+                                String::from_utf8_lossy(&key),
+                                err
+                            );
+                            self.db.delete::<O>(&key)?;
+                            dropped += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.db.put(
+            O::NAMESPACE.as_bytes(),
+            &Schema {
+                namespace: O::NAMESPACE.to_string(),
+                version: O::VERSION,
+            },
+        )?;
+
+        Ok(dropped)
+    }
+
+    /// migrate_to copies all the [Task], [PersistentCacheTask], [Piece] and [DigestIndex]
+    /// objects to the metadata of the target storage engine, and returns the number of the
+    /// copied objects. The existing objects with the same keys in the target are overwritten.
+    #[instrument(skip_all)]
+    pub fn migrate_to<T: StorageEngineOwned>(&self, target: &Metadata<T>) -> Result<usize> {
+        let mut migrated = self.migrate_namespace_to::<Task, T>(target)?;
+        migrated += self.migrate_namespace_to::<PersistentCacheTask, T>(target)?;
+        migrated += self.migrate_namespace_to::<Piece, T>(target)?;
+        migrated += self.migrate_namespace_to::<DigestIndex, T>(target)?;
+
+        info!("migrated {} metadata objects", migrated);
+        Ok(migrated)
+    }
+
+    /// migrate_namespace_to copies the objects of the namespace to the metadata of the target
+    /// storage engine.
+    fn migrate_namespace_to<O: DatabaseObject, T: StorageEngineOwned>(
+        &self,
+        target: &Metadata<T>,
+    ) -> Result<usize> {
+        let mut migrated = 0;
+        for ele in self.db.iter::<O>()? {
+            let (key, value) = ele?;
+            target.db.put::<O>(&key, &value)?;
+            migrated += 1;
+        }
+
+        info!(
+            "migrated {} objects of namespace {}",
+            migrated,
+            O::NAMESPACE
+        );
+        Ok(migrated)
+    }
 }
 
 /// Metadata implements the metadata of the storage engine.
-impl Metadata<RocksdbStorageEngine> {
-    /// new creates a new metadata instance.
+impl Metadata<Engine> {
+    /// NAMESPACES are the namespaces of the metadata objects.
+    const NAMESPACES: [&'static str; 5] = [
+        Task::NAMESPACE,
+        Piece::NAMESPACE,
+        PersistentCacheTask::NAMESPACE,
+        DigestIndex::NAMESPACE,
+        Schema::NAMESPACE,
+    ];
+
+    /// new creates a new metadata instance with the storage engine of the configuration.
     #[instrument(skip_all)]
-    pub fn new(
-        config: Arc<Config>,
-        dir: &Path,
-        log_dir: &PathBuf,
-    ) -> Result<Metadata<RocksdbStorageEngine>> {
-        let db = RocksdbStorageEngine::open(
+    pub fn new(config: Arc<Config>, dir: &Path, log_dir: &PathBuf) -> Result<Metadata<Engine>> {
+        Self::open(
+            config.storage.metadata_engine,
             dir,
             log_dir,
-            &[
-                Task::NAMESPACE,
-                Piece::NAMESPACE,
-                PersistentCacheTask::NAMESPACE,
-            ],
             config.storage.keep,
-        )?;
+        )
+    }
+
+    /// open opens a metadata instance with the given storage engine, it is used to open
+    /// the metadata of the other storage engine for migration.
+    #[instrument(skip_all)]
+    pub fn open(
+        engine_type: MetadataEngineType,
+        dir: &Path,
+        log_dir: &PathBuf,
+        keep: bool,
+    ) -> Result<Metadata<Engine>> {
+        let db = Engine::open(engine_type, dir, log_dir, &Self::NAMESPACES, keep)?;
+        let mut metadata = Metadata {
+            db,
+            dropped_objects: HashMap::new(),
+        };
 
-        Ok(Metadata { db })
+        // Migrate the kept objects to the current version of the encoding.
+        metadata.dropped_objects = metadata.migrate_schema()?;
+        Ok(metadata)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage_engine::Operations;
     use tempfile::tempdir;
 
     #[test]
@@ -975,7 +1489,15 @@ mod tests {
 
         // Test download_task_started.
         metadata
-            .download_task_started(task_id, Some(1024), Some(1024), None)
+            .download_task_started(
+                task_id,
+                Some(1024),
+                Some(1024),
+                None,
+                Some("app".to_string()),
+                Some("tag".to_string()),
+                None,
+            )
             .unwrap();
         let task = metadata
             .get_task(task_id)
@@ -985,14 +1507,34 @@ mod tests {
         assert_eq!(task.piece_length, Some(1024));
         assert_eq!(task.content_length, Some(1024));
         assert!(task.response_header.is_empty());
+        assert_eq!(task.application, Some("app".to_string()));
+        assert_eq!(task.tag, Some("tag".to_string()));
         assert_eq!(task.uploading_count, 0);
         assert_eq!(task.uploaded_count, 0);
         assert!(!task.is_finished());
 
+        assert!(task.is_resumable());
+
+        // Test prefetch_task_started.
+        let task = metadata
+            .prefetch_task_started(task_id, b"request".to_vec())
+            .unwrap();
+        assert!(task.is_prefetched());
+        assert_eq!(task.prefetch_request, Some(b"request".to_vec()));
+        assert!(metadata
+            .prefetch_task_started(task_id, b"request".to_vec())
+            .is_err());
+
+        // Test prefetch_task_failed.
+        let task = metadata.prefetch_task_failed(task_id).unwrap();
+        assert!(!task.is_prefetched());
+        assert!(task.prefetch_request.is_none());
+
         // Test download_task_finished.
         metadata.download_task_finished(task_id).unwrap();
         let task = metadata.get_task(task_id).unwrap().unwrap();
         assert!(task.is_finished());
+        assert!(!task.is_resumable());
 
         // Test upload_task_started.
         metadata.upload_task_started(task_id).unwrap();
@@ -1015,7 +1557,7 @@ mod tests {
         // Test get_tasks.
         let task_id = "a535b115f18d96870f0422ac891f91dd162f2f391e4778fb84279701fcd02dd1";
         metadata
-            .download_task_started(task_id, Some(1024), None, None)
+            .download_task_started(task_id, Some(1024), None, None, None, None, None)
             .unwrap();
         let tasks = metadata.get_tasks().unwrap();
         assert_eq!(tasks.len(), 2);
@@ -1026,6 +1568,64 @@ mod tests {
         assert!(task.is_none());
     }
 
+    #[test]
+    fn test_digest_index() {
+        let dir = tempdir().unwrap();
+        let log_dir = dir.path().join("log");
+        let metadata = Metadata::new(Arc::new(Config::default()), dir.path(), &log_dir).unwrap();
+        let task_id = "d3c4e940ad06c47fc36ac67801e6f8e36cb400e2391708620bc7e865b102062c";
+        let digest = "sha256:9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+        // The unfinished task is not indexed.
+        metadata
+            .download_task_started(
+                task_id,
+                Some(4),
+                Some(4),
+                None,
+                None,
+                None,
+                Some(digest.to_string()),
+            )
+            .unwrap();
+        assert!(metadata.get_digest_index(digest).unwrap().is_none());
+
+        // Test download_task_finished indexes the task.
+        metadata.download_task_finished(task_id).unwrap();
+        let digest_index = metadata.get_digest_index(digest).unwrap().unwrap();
+        assert_eq!(digest_index.task_id, task_id);
+
+        // Test clone_pieces only clones the finished pieces.
+        let other_task_id = "a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d2e3f4a5b6c7d8e9f0a1b2c3d4e5f6a7b8";
+        metadata
+            .download_piece_started(metadata.piece_id(task_id, 0).as_str(), 0)
+            .unwrap();
+        metadata
+            .download_piece_finished(
+                metadata.piece_id(task_id, 0).as_str(),
+                0,
+                4,
+                "crc32:1",
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        metadata
+            .download_piece_started(metadata.piece_id(task_id, 1).as_str(), 1)
+            .unwrap();
+        assert_eq!(metadata.clone_pieces(task_id, other_task_id).unwrap(), 1);
+        let piece = metadata
+            .get_piece(metadata.piece_id(other_task_id, 0).as_str())
+            .unwrap()
+            .unwrap();
+        assert_eq!(piece.digest, "crc32:1");
+
+        // Test delete_task deletes the digest index.
+        metadata.delete_task(task_id).unwrap();
+        assert!(metadata.get_digest_index(digest).unwrap().is_none());
+    }
+
     #[test]
     fn test_piece_lifecycle() {
         let dir = tempdir().unwrap();
@@ -1043,7 +1643,7 @@ mod tests {
 
         // Test download_piece_finished.
         metadata
-            .download_piece_finished(piece_id.as_str(), 0, 1024, "digest1", None)
+            .download_piece_finished(piece_id.as_str(), 0, 1024, "digest1", None, None, None)
             .unwrap();
         let piece = metadata.get_piece(piece_id.as_str()).unwrap().unwrap();
         assert_eq!(piece.length, 1024);
@@ -1059,6 +1659,11 @@ mod tests {
         let pieces = metadata.get_pieces(task_id).unwrap();
         assert_eq!(pieces.len(), 3);
 
+        // Test get_piece_ids.
+        let piece_ids = metadata.get_piece_ids().unwrap();
+        assert_eq!(piece_ids.len(), 3);
+        assert!(piece_ids.contains(&metadata.piece_id(task_id, 1)));
+
         // Test download_piece_failed.
         let piece_id = metadata.piece_id(task_id, 2);
         metadata
@@ -1076,4 +1681,143 @@ mod tests {
         let pieces = metadata.get_pieces(task_id).unwrap();
         assert!(pieces.is_empty());
     }
+
+    #[test]
+    fn should_migrate_metadata_between_engines() {
+        let dir = tempdir().unwrap();
+        let log_dir = dir.path().join("log");
+        let source =
+            Metadata::open(MetadataEngineType::Memory, dir.path(), &log_dir, false).unwrap();
+        let task_id = "d3c4e940ad06c47fc36ac67801e6f8e36cb400e2391708620bc7e865b102062c";
+        let persistent_cache_task_id =
+            "a7b8c9d0e1f2a3b4c5d6e7f8a9b0c1d2e3f4a5b6c7d8e9f0a1b2c3d4e5f6a7b8";
+
+        source
+            .download_task_started(task_id, Some(4), Some(8), None, None, None, None)
+            .unwrap();
+        source
+            .download_persistent_cache_task_started(
+                persistent_cache_task_id,
+                Duration::from_secs(60),
+                true,
+                4,
+                4,
+                Utc::now().naive_utc(),
+            )
+            .unwrap();
+        for number in 0..2 {
+            source
+                .download_piece_started(source.piece_id(task_id, number).as_str(), number)
+                .unwrap();
+        }
+
+        let target = Metadata::open(MetadataEngineType::Redb, dir.path(), &log_dir, false).unwrap();
+        assert_eq!(source.migrate_to(&target).unwrap(), 4);
+
+        let task = target.get_task(task_id).unwrap().unwrap();
+        assert_eq!(task.content_length, Some(8));
+        assert!(target
+            .get_persistent_cache_task(persistent_cache_task_id)
+            .unwrap()
+            .is_some());
+        assert_eq!(target.get_pieces(task_id).unwrap().len(), 2);
+    }
+
+    impl DatabaseObject for TaskV0 {
+        const NAMESPACE: &'static str = "task";
+    }
+
+    impl DatabaseObject for PieceV0 {
+        const NAMESPACE: &'static str = "piece";
+    }
+
+    #[test]
+    fn should_migrate_schema_of_kept_objects() {
+        #[derive(Serialize, Deserialize)]
+        struct CorruptedPiece(u8);
+
+        impl DatabaseObject for CorruptedPiece {
+            const NAMESPACE: &'static str = "piece";
+        }
+
+        let dir = tempdir().unwrap();
+        let log_dir = dir.path().join("log");
+        let metadata =
+            Metadata::open(MetadataEngineType::Memory, dir.path(), &log_dir, false).unwrap();
+        assert_eq!(metadata.get_schema_version::<Task>().unwrap(), Some(1));
+        assert_eq!(metadata.get_schema_version::<Piece>().unwrap(), Some(2));
+
+        // Write the task of version 0 written before the schema is versioned.
+        let task_id = "d3c4e940ad06c47fc36ac67801e6f8e36cb400e2391708620bc7e865b102062c";
+        metadata
+            .db
+            .put(
+                task_id.as_bytes(),
+                &TaskV0 {
+                    id: task_id.to_string(),
+                    piece_length: Some(4),
+                    content_length: Some(8),
+                    response_header: HashMap::new(),
+                    uploading_count: 0,
+                    uploaded_count: 2,
+                    updated_at: Utc::now().naive_utc(),
+                    created_at: Utc::now().naive_utc(),
+                    prefetched_at: None,
+                    failed_at: None,
+                    finished_at: Some(Utc::now().naive_utc()),
+                },
+            )
+            .unwrap();
+        let piece_id = metadata.piece_id(task_id, 0);
+        metadata
+            .db
+            .put(
+                piece_id.as_bytes(),
+                &PieceV0 {
+                    number: 0,
+                    offset: 0,
+                    length: 4,
+                    digest: "crc32:1".to_string(),
+                    parent_id: None,
+                    uploading_count: 0,
+                    uploaded_count: 0,
+                    updated_at: Utc::now().naive_utc(),
+                    created_at: Utc::now().naive_utc(),
+                    finished_at: Some(Utc::now().naive_utc()),
+                },
+            )
+            .unwrap();
+        metadata
+            .db
+            .delete::<Schema>(Task::NAMESPACE.as_bytes())
+            .unwrap();
+        metadata
+            .db
+            .delete::<Schema>(Piece::NAMESPACE.as_bytes())
+            .unwrap();
+        let corrupted_piece_id = metadata.piece_id(task_id, 1);
+        metadata
+            .db
+            .put(corrupted_piece_id.as_bytes(), &CorruptedPiece(1))
+            .unwrap();
+        assert!(metadata.get_task(task_id).is_err());
+
+        let dropped_objects = metadata.migrate_schema().unwrap();
+        assert_eq!(dropped_objects.get(Task::NAMESPACE), Some(&0));
+        assert_eq!(dropped_objects.get(Piece::NAMESPACE), Some(&1));
+        assert!(metadata.get_piece(&corrupted_piece_id).unwrap().is_none());
+        assert_eq!(metadata.get_schema_version::<Task>().unwrap(), Some(1));
+        let task = metadata.get_task(task_id).unwrap().unwrap();
+        assert_eq!(task.content_length, Some(8));
+        assert_eq!(task.uploaded_count, 2);
+        assert!(task.is_finished());
+        assert!(task.digest.is_none());
+
+        assert_eq!(metadata.get_schema_version::<Piece>().unwrap(), Some(2));
+        let piece = metadata.get_piece(&piece_id).unwrap().unwrap();
+        assert_eq!(piece.length, 4);
+        assert!(piece.is_finished());
+        assert!(piece.compressed_length.is_none());
+        assert!(piece.encryption.is_none());
+    }
 }
@@ -0,0 +1,102 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::storage_engine::{DatabaseObject, Operations};
+use dragonfly_client_config::dfdaemon::MetadataEngineType;
+use dragonfly_client_core::{Error, Result};
+
+/// DisabledStorageEngine is the storage engine whose cargo feature is not enabled, it can not
+/// be opened, so the operations are never called.
+pub enum DisabledStorageEngine {}
+
+/// DisabledStorageEngine implements the disabled storage engine.
+impl DisabledStorageEngine {
+    /// open returns the unsupported error of the storage engine type.
+    pub fn open(engine_type: MetadataEngineType) -> Result<Self> {
+        Err(Error::Unsupported(format!(
+            "metadata engine {} is not enabled by the cargo feature",
+            engine_type
+        )))
+    }
+}
+
+/// DisabledStorageEngine implements the storage engine operations.
+impl Operations for DisabledStorageEngine {
+    /// get gets the object by key.
+    fn get<O: DatabaseObject>(&self, _key: &[u8]) -> Result<Option<O>> {
+        match *self {}
+    }
+
+    /// is_exist checks if the object exists by key.
+    fn is_exist<O: DatabaseObject>(&self, _key: &[u8]) -> Result<bool> {
+        match *self {}
+    }
+
+    /// put puts the object by key.
+    fn put<O: DatabaseObject>(&self, _key: &[u8], _value: &O) -> Result<()> {
+        match *self {}
+    }
+
+    /// delete deletes the object by key.
+    fn delete<O: DatabaseObject>(&self, _key: &[u8]) -> Result<()> {
+        match *self {}
+    }
+
+    /// iter iterates all objects.
+    fn iter<O: DatabaseObject>(&self) -> Result<impl Iterator<Item = Result<(Box<[u8]>, O)>>> {
+        match *self {}
+
+        #[allow(unreachable_code)]
+        Ok(std::iter::empty())
+    }
+
+    /// iter_raw iterates all objects without serialization.
+    fn iter_raw<O: DatabaseObject>(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>)>>> {
+        match *self {}
+
+        #[allow(unreachable_code)]
+        Ok(std::iter::empty())
+    }
+
+    /// prefix_iter iterates all objects with prefix.
+    fn prefix_iter<O: DatabaseObject>(
+        &self,
+        _prefix: &[u8],
+    ) -> Result<impl Iterator<Item = Result<(Box<[u8]>, O)>>> {
+        match *self {}
+
+        #[allow(unreachable_code)]
+        Ok(std::iter::empty())
+    }
+
+    /// prefix_iter_raw iterates all objects with prefix without serialization.
+    fn prefix_iter_raw<O: DatabaseObject>(
+        &self,
+        _prefix: &[u8],
+    ) -> Result<impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>)>>> {
+        match *self {}
+
+        #[allow(unreachable_code)]
+        Ok(std::iter::empty())
+    }
+
+    /// batch_delete deletes objects by keys.
+    fn batch_delete<O: DatabaseObject>(&self, _keys: Vec<&[u8]>) -> Result<()> {
+        match *self {}
+    }
+}
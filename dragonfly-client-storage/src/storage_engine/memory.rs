@@ -0,0 +1,222 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::storage_engine::{DatabaseObject, Operations, StorageEngine};
+use dragonfly_client_core::{Error, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::sync::RwLock;
+use tracing::info;
+
+/// Namespace is the sorted objects of the namespace, the key and value are serialized.
+type Namespace = BTreeMap<Box<[u8]>, Box<[u8]>>;
+
+/// MemoryStorageEngine is a storage engine based on memory, the objects are lost when it is
+/// dropped. It is used for the tests and the ephemeral nodes.
+pub struct MemoryStorageEngine {
+    /// namespaces are the objects grouped by the namespace.
+    namespaces: HashMap<&'static str, RwLock<Namespace>>,
+}
+
+/// MemoryStorageEngine implements the storage engine of the memory.
+impl MemoryStorageEngine {
+    /// open opens a memory storage engine with the given namespaces.
+    pub fn open(namespaces: &[&'static str]) -> Self {
+        info!("initializing metadata in memory: {:?}", namespaces);
+        Self {
+            namespaces: namespaces
+                .iter()
+                .map(|namespace| (*namespace, RwLock::new(BTreeMap::new())))
+                .collect(),
+        }
+    }
+
+    /// namespace returns the objects of the namespace of the given object.
+    fn namespace<O: DatabaseObject>(&self) -> Result<&RwLock<Namespace>> {
+        self.namespaces
+            .get(O::NAMESPACE)
+            .ok_or_else(|| Error::ColumnFamilyNotFound(O::NAMESPACE.to_string()))
+    }
+
+    /// collect collects the serialized objects with the prefix, the objects are copied out, so
+    /// the lock is not held by the returned iterator.
+    #[allow(clippy::type_complexity)]
+    fn collect<O: DatabaseObject>(&self, prefix: &[u8]) -> Result<Vec<(Box<[u8]>, Box<[u8]>)>> {
+        let namespace = self.namespace::<O>()?.read().unwrap();
+        Ok(namespace
+            .range::<[u8], _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+}
+
+/// MemoryStorageEngine implements the storage engine operations.
+impl Operations for MemoryStorageEngine {
+    /// get gets the object by key.
+    fn get<O: DatabaseObject>(&self, key: &[u8]) -> Result<Option<O>> {
+        let namespace = self.namespace::<O>()?.read().unwrap();
+        match namespace.get(key) {
+            Some(value) => Ok(Some(O::deserialize_from(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// is_exist checks if the object exists by key.
+    fn is_exist<O: DatabaseObject>(&self, key: &[u8]) -> Result<bool> {
+        Ok(self.namespace::<O>()?.read().unwrap().contains_key(key))
+    }
+
+    /// put puts the object by key.
+    fn put<O: DatabaseObject>(&self, key: &[u8], value: &O) -> Result<()> {
+        let value = value.serialized()?;
+        self.namespace::<O>()?
+            .write()
+            .unwrap()
+            .insert(key.into(), value.into_boxed_slice());
+        Ok(())
+    }
+
+    /// delete deletes the object by key.
+    fn delete<O: DatabaseObject>(&self, key: &[u8]) -> Result<()> {
+        self.namespace::<O>()?.write().unwrap().remove(key);
+        Ok(())
+    }
+
+    /// iter iterates all objects.
+    fn iter<O: DatabaseObject>(&self) -> Result<impl Iterator<Item = Result<(Box<[u8]>, O)>>> {
+        Ok(self
+            .collect::<O>(&[])?
+            .into_iter()
+            .map(|(key, value)| Ok((key, O::deserialize_from(&value)?))))
+    }
+
+    /// iter_raw iterates all objects without serialization.
+    fn iter_raw<O: DatabaseObject>(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>)>>> {
+        Ok(self.collect::<O>(&[])?.into_iter().map(Ok))
+    }
+
+    /// prefix_iter iterates all objects with prefix.
+    fn prefix_iter<O: DatabaseObject>(
+        &self,
+        prefix: &[u8],
+    ) -> Result<impl Iterator<Item = Result<(Box<[u8]>, O)>>> {
+        Ok(self
+            .collect::<O>(prefix)?
+            .into_iter()
+            .map(|(key, value)| Ok((key, O::deserialize_from(&value)?))))
+    }
+
+    /// prefix_iter_raw iterates all objects with prefix without serialization.
+    fn prefix_iter_raw<O: DatabaseObject>(
+        &self,
+        prefix: &[u8],
+    ) -> Result<impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>)>>> {
+        Ok(self.collect::<O>(prefix)?.into_iter().map(Ok))
+    }
+
+    /// batch_delete deletes objects by keys.
+    fn batch_delete<O: DatabaseObject>(&self, keys: Vec<&[u8]>) -> Result<()> {
+        let mut namespace = self.namespace::<O>()?.write().unwrap();
+        for key in keys {
+            namespace.remove(key);
+        }
+
+        Ok(())
+    }
+}
+
+/// MemoryStorageEngine implements the memory of the storage engine.
+impl StorageEngine<'_> for MemoryStorageEngine {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+    struct Object {
+        id: String,
+        value: i32,
+    }
+
+    impl DatabaseObject for Object {
+        const NAMESPACE: &'static str = "object";
+    }
+
+    #[test]
+    fn test_put_get_and_delete() {
+        let engine = MemoryStorageEngine::open(&[Object::NAMESPACE]);
+        let object = Object {
+            id: "1".to_string(),
+            value: 42,
+        };
+
+        engine.put::<Object>(object.id.as_bytes(), &object).unwrap();
+        assert!(engine.is_exist::<Object>(object.id.as_bytes()).unwrap());
+        assert_eq!(
+            engine.get::<Object>(object.id.as_bytes()).unwrap(),
+            Some(object.clone())
+        );
+
+        engine.delete::<Object>(object.id.as_bytes()).unwrap();
+        assert!(engine
+            .get::<Object>(object.id.as_bytes())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_prefix_iter_and_batch_delete() {
+        let engine = MemoryStorageEngine::open(&[Object::NAMESPACE]);
+        for (key, value) in [("a-1", 1), ("a-2", 2), ("b-1", 3)] {
+            engine
+                .put::<Object>(
+                    key.as_bytes(),
+                    &Object {
+                        id: key.to_string(),
+                        value,
+                    },
+                )
+                .unwrap();
+        }
+
+        let objects = engine
+            .prefix_iter::<Object>(b"a-")
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            objects.iter().map(|(_, o)| o.value).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(engine.iter_raw::<Object>().unwrap().count(), 3);
+
+        engine
+            .batch_delete::<Object>(vec![b"a-1".as_slice(), b"b-1".as_slice()])
+            .unwrap();
+        assert_eq!(engine.iter::<Object>().unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_namespace_not_found() {
+        let engine = MemoryStorageEngine::open(&[]);
+        let result = engine.get::<Object>(b"1");
+        assert!(matches!(result, Err(Error::ColumnFamilyNotFound(_))));
+    }
+}
@@ -14,12 +14,20 @@
  * limitations under the License.
  */
 
+use dragonfly_client_config::dfdaemon::MetadataEngineType;
 use dragonfly_client_core::{
     error::{ErrorType, OrErr},
-    Result,
+    Error, Result,
 };
 use serde::{de::DeserializeOwned, Serialize};
+use std::path::{Path, PathBuf};
 
+#[cfg(not(all(feature = "rocksdb", feature = "redb")))]
+pub mod disabled;
+pub mod memory;
+#[cfg(feature = "redb")]
+pub mod redb;
+#[cfg(feature = "rocksdb")]
 pub mod rocksdb;
 
 /// DatabaseObject marks a type can be stored in database, which has a namespace.
@@ -29,6 +37,27 @@ pub trait DatabaseObject: Serialize + DeserializeOwned {
     /// NAMESPACE is the namespace of the object.
     const NAMESPACE: &'static str;
 
+    /// VERSION is the version of the encoding of the object. The encoding is not
+    /// self-describing, so it must be increased when the fields of the object are added,
+    /// removed or reordered, and the step from the previous version must be added to
+    /// `migrate`.
+    const VERSION: u32 = 0;
+
+    /// EVENTUAL_DURABILITY marks whether the object is persisted eventually, the storage
+    /// engine may not sync the write of the object to disk. It is enabled for the objects
+    /// which are written frequently and can be recovered, e.g. the pieces.
+    const EVENTUAL_DURABILITY: bool = false;
+
+    /// migrate converts the serialized object of the given version to the serialized object
+    /// of the next version.
+    fn migrate(version: u32, _bytes: &[u8]) -> Result<Vec<u8>> {
+        Err(Error::Unsupported(format!(
+            "migrate {} from version {}",
+            Self::NAMESPACE,
+            version
+        )))
+    }
+
     /// serialized serializes the object to bytes.
     fn serialized(&self) -> Result<Vec<u8>> {
         Ok(bincode::serialize(self).or_err(ErrorType::SerializeError)?)
@@ -86,3 +115,189 @@ pub trait Operations {
     // batch_delete deletes objects by keys.
     fn batch_delete<O: DatabaseObject>(&self, keys: Vec<&[u8]>) -> Result<()>;
 }
+
+/// Engine is the storage engine selected by the `storage.metadataEngine` configuration.
+pub enum Engine {
+    /// Rocksdb is the storage engine based on rocksdb.
+    #[cfg(feature = "rocksdb")]
+    Rocksdb(rocksdb::RocksdbStorageEngine),
+
+    /// Rocksdb is the disabled storage engine if the rocksdb feature is not enabled.
+    #[cfg(not(feature = "rocksdb"))]
+    Rocksdb(disabled::DisabledStorageEngine),
+
+    /// Redb is the storage engine based on redb.
+    #[cfg(feature = "redb")]
+    Redb(redb::RedbStorageEngine),
+
+    /// Redb is the disabled storage engine if the redb feature is not enabled.
+    #[cfg(not(feature = "redb"))]
+    Redb(disabled::DisabledStorageEngine),
+
+    /// Memory is the storage engine based on memory.
+    Memory(memory::MemoryStorageEngine),
+}
+
+/// Engine implements the selection of the storage engine.
+impl Engine {
+    /// open opens the storage engine of the given type with the given directory and namespaces.
+    pub fn open(
+        engine_type: MetadataEngineType,
+        dir: &Path,
+        log_dir: &PathBuf,
+        namespaces: &[&'static str],
+        keep: bool,
+    ) -> Result<Self> {
+        // The log directory is only used by the rocksdb engine.
+        #[cfg(not(feature = "rocksdb"))]
+        let _ = log_dir;
+
+        match engine_type {
+            #[cfg(feature = "rocksdb")]
+            MetadataEngineType::Rocksdb => Ok(Engine::Rocksdb(
+                rocksdb::RocksdbStorageEngine::open(dir, log_dir, namespaces, keep)?,
+            )),
+            #[cfg(not(feature = "rocksdb"))]
+            MetadataEngineType::Rocksdb => Ok(Engine::Rocksdb(
+                disabled::DisabledStorageEngine::open(engine_type)?,
+            )),
+            #[cfg(feature = "redb")]
+            MetadataEngineType::Redb => Ok(Engine::Redb(redb::RedbStorageEngine::open(
+                dir, namespaces, keep,
+            )?)),
+            #[cfg(not(feature = "redb"))]
+            MetadataEngineType::Redb => Ok(Engine::Redb(disabled::DisabledStorageEngine::open(
+                engine_type,
+            )?)),
+            MetadataEngineType::Memory => Ok(Engine::Memory(memory::MemoryStorageEngine::open(
+                namespaces,
+            ))),
+        }
+    }
+}
+
+/// Engine implements the storage engine operations by dispatching to the selected engine.
+impl Operations for Engine {
+    /// get gets the object by key.
+    fn get<O: DatabaseObject>(&self, key: &[u8]) -> Result<Option<O>> {
+        match self {
+            Engine::Rocksdb(engine) => engine.get::<O>(key),
+            Engine::Redb(engine) => engine.get::<O>(key),
+            Engine::Memory(engine) => engine.get::<O>(key),
+        }
+    }
+
+    /// is_exist checks if the object exists by key.
+    fn is_exist<O: DatabaseObject>(&self, key: &[u8]) -> Result<bool> {
+        match self {
+            Engine::Rocksdb(engine) => engine.is_exist::<O>(key),
+            Engine::Redb(engine) => engine.is_exist::<O>(key),
+            Engine::Memory(engine) => engine.is_exist::<O>(key),
+        }
+    }
+
+    /// put puts the object by key.
+    fn put<O: DatabaseObject>(&self, key: &[u8], value: &O) -> Result<()> {
+        match self {
+            Engine::Rocksdb(engine) => engine.put::<O>(key, value),
+            Engine::Redb(engine) => engine.put::<O>(key, value),
+            Engine::Memory(engine) => engine.put::<O>(key, value),
+        }
+    }
+
+    /// delete deletes the object by key.
+    fn delete<O: DatabaseObject>(&self, key: &[u8]) -> Result<()> {
+        match self {
+            Engine::Rocksdb(engine) => engine.delete::<O>(key),
+            Engine::Redb(engine) => engine.delete::<O>(key),
+            Engine::Memory(engine) => engine.delete::<O>(key),
+        }
+    }
+
+    /// iter iterates all objects.
+    fn iter<O: DatabaseObject>(&self) -> Result<impl Iterator<Item = Result<(Box<[u8]>, O)>>> {
+        Ok(match self {
+            Engine::Rocksdb(engine) => EngineIter::Rocksdb(engine.iter::<O>()?),
+            Engine::Redb(engine) => EngineIter::Redb(engine.iter::<O>()?),
+            Engine::Memory(engine) => EngineIter::Memory(engine.iter::<O>()?),
+        })
+    }
+
+    /// iter_raw iterates all objects without serialization.
+    fn iter_raw<O: DatabaseObject>(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>)>>> {
+        Ok(match self {
+            Engine::Rocksdb(engine) => EngineIter::Rocksdb(engine.iter_raw::<O>()?),
+            Engine::Redb(engine) => EngineIter::Redb(engine.iter_raw::<O>()?),
+            Engine::Memory(engine) => EngineIter::Memory(engine.iter_raw::<O>()?),
+        })
+    }
+
+    /// prefix_iter iterates all objects with prefix.
+    fn prefix_iter<O: DatabaseObject>(
+        &self,
+        prefix: &[u8],
+    ) -> Result<impl Iterator<Item = Result<(Box<[u8]>, O)>>> {
+        Ok(match self {
+            Engine::Rocksdb(engine) => EngineIter::Rocksdb(engine.prefix_iter::<O>(prefix)?),
+            Engine::Redb(engine) => EngineIter::Redb(engine.prefix_iter::<O>(prefix)?),
+            Engine::Memory(engine) => EngineIter::Memory(engine.prefix_iter::<O>(prefix)?),
+        })
+    }
+
+    /// prefix_iter_raw iterates all objects with prefix without serialization.
+    fn prefix_iter_raw<O: DatabaseObject>(
+        &self,
+        prefix: &[u8],
+    ) -> Result<impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>)>>> {
+        Ok(match self {
+            Engine::Rocksdb(engine) => EngineIter::Rocksdb(engine.prefix_iter_raw::<O>(prefix)?),
+            Engine::Redb(engine) => EngineIter::Redb(engine.prefix_iter_raw::<O>(prefix)?),
+            Engine::Memory(engine) => EngineIter::Memory(engine.prefix_iter_raw::<O>(prefix)?),
+        })
+    }
+
+    /// batch_delete deletes objects by keys.
+    fn batch_delete<O: DatabaseObject>(&self, keys: Vec<&[u8]>) -> Result<()> {
+        match self {
+            Engine::Rocksdb(engine) => engine.batch_delete::<O>(keys),
+            Engine::Redb(engine) => engine.batch_delete::<O>(keys),
+            Engine::Memory(engine) => engine.batch_delete::<O>(keys),
+        }
+    }
+}
+
+/// Engine implements the storage engine.
+impl StorageEngine<'_> for Engine {}
+
+/// EngineIter is the iterator of the selected storage engine.
+enum EngineIter<R, D, M> {
+    /// Rocksdb is the iterator of the rocksdb storage engine.
+    Rocksdb(R),
+
+    /// Redb is the iterator of the redb storage engine.
+    Redb(D),
+
+    /// Memory is the iterator of the memory storage engine.
+    Memory(M),
+}
+
+/// EngineIter implements the Iterator.
+impl<T, R, D, M> Iterator for EngineIter<R, D, M>
+where
+    R: Iterator<Item = T>,
+    D: Iterator<Item = T>,
+    M: Iterator<Item = T>,
+{
+    type Item = T;
+
+    /// next returns the next item of the selected storage engine.
+    fn next(&mut self) -> Option<T> {
+        match self {
+            EngineIter::Rocksdb(iter) => iter.next(),
+            EngineIter::Redb(iter) => iter.next(),
+            EngineIter::Memory(iter) => iter.next(),
+        }
+    }
+}
@@ -0,0 +1,350 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::storage_engine::{DatabaseObject, Operations, StorageEngine};
+use dragonfly_client_core::{
+    error::{ErrorType, ExternalError, OrErr},
+    Error, Result,
+};
+use redb::{Durability, TableDefinition, TableError};
+use std::path::Path;
+use tracing::{info, warn};
+
+/// RedbStorageEngine is a storage engine based on redb, a pure-Rust embedded B-tree database.
+pub struct RedbStorageEngine {
+    /// inner is the inner redb database.
+    inner: redb::Database,
+}
+
+/// RedbStorageEngine implements the storage engine of the redb.
+impl RedbStorageEngine {
+    /// DEFAULT_FILE_NAME is the default file name to store metadata.
+    const DEFAULT_FILE_NAME: &'static str = "metadata.redb";
+
+    /// open opens a redb storage engine with the given directory and tables.
+    pub fn open(dir: &Path, table_names: &[&'static str], keep: bool) -> Result<Self> {
+        info!("initializing metadata file: {:?} {:?}", dir, table_names);
+        let path = dir.join(Self::DEFAULT_FILE_NAME);
+
+        // If the storage is not kept, remove the database file.
+        if !keep {
+            if let Err(err) = std::fs::remove_file(&path) {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    warn!("remove {:?} failed: {}", path, err);
+                }
+            }
+        }
+
+        std::fs::create_dir_all(dir)?;
+        let db = redb::Database::create(&path).or_err(ErrorType::StorageError)?;
+
+        // Create the tables, so the read transaction can open them.
+        let txn = db.begin_write().or_err(ErrorType::StorageError)?;
+        for name in table_names {
+            txn.open_table(table_definition(name))
+                .or_err(ErrorType::StorageError)?;
+        }
+        txn.commit().or_err(ErrorType::StorageError)?;
+
+        info!("metadata initialized file: {:?}", path);
+        Ok(Self { inner: db })
+    }
+
+    /// collect collects the serialized objects with the prefix. The redb range borrows the read
+    /// transaction, so the objects are copied out before the transaction is dropped.
+    #[allow(clippy::type_complexity)]
+    fn collect<O: DatabaseObject>(&self, prefix: &[u8]) -> Result<Vec<(Box<[u8]>, Box<[u8]>)>> {
+        let txn = self.inner.begin_read().or_err(ErrorType::StorageError)?;
+        let table = txn
+            .open_table(table_definition(O::NAMESPACE))
+            .map_err(table_error::<O>)?;
+
+        let mut objects = Vec::new();
+        for entry in table.range(prefix..).or_err(ErrorType::StorageError)? {
+            let (key, value) = entry.or_err(ErrorType::StorageError)?;
+            if !key.value().starts_with(prefix) {
+                break;
+            }
+
+            objects.push((Box::from(key.value()), Box::from(value.value())));
+        }
+
+        Ok(objects)
+    }
+}
+
+/// RedbStorageEngine implements the storage engine operations.
+impl Operations for RedbStorageEngine {
+    /// get gets the object by key.
+    fn get<O: DatabaseObject>(&self, key: &[u8]) -> Result<Option<O>> {
+        let txn = self.inner.begin_read().or_err(ErrorType::StorageError)?;
+        let table = txn
+            .open_table(table_definition(O::NAMESPACE))
+            .map_err(table_error::<O>)?;
+
+        match table.get(key).or_err(ErrorType::StorageError)? {
+            Some(value) => Ok(Some(O::deserialize_from(value.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// is_exist checks if the object exists by key.
+    fn is_exist<O: DatabaseObject>(&self, key: &[u8]) -> Result<bool> {
+        let txn = self.inner.begin_read().or_err(ErrorType::StorageError)?;
+        let table = txn
+            .open_table(table_definition(O::NAMESPACE))
+            .map_err(table_error::<O>)?;
+
+        Ok(table.get(key).or_err(ErrorType::StorageError)?.is_some())
+    }
+
+    /// put puts the object by key. The write transaction of the object with the eventual
+    /// durability is not synced to disk, it is persisted by the next immediate commit.
+    fn put<O: DatabaseObject>(&self, key: &[u8], value: &O) -> Result<()> {
+        let value = value.serialized()?;
+        let mut txn = self.inner.begin_write().or_err(ErrorType::StorageError)?;
+        if O::EVENTUAL_DURABILITY {
+            txn.set_durability(Durability::Eventual);
+        }
+
+        {
+            let mut table = txn
+                .open_table(table_definition(O::NAMESPACE))
+                .or_err(ErrorType::StorageError)?;
+            table
+                .insert(key, value.as_slice())
+                .or_err(ErrorType::StorageError)?;
+        }
+
+        txn.commit().or_err(ErrorType::StorageError)?;
+        Ok(())
+    }
+
+    /// delete deletes the object by key.
+    fn delete<O: DatabaseObject>(&self, key: &[u8]) -> Result<()> {
+        self.batch_delete::<O>(vec![key])
+    }
+
+    /// iter iterates all objects.
+    fn iter<O: DatabaseObject>(&self) -> Result<impl Iterator<Item = Result<(Box<[u8]>, O)>>> {
+        Ok(self
+            .collect::<O>(&[])?
+            .into_iter()
+            .map(|(key, value)| Ok((key, O::deserialize_from(&value)?))))
+    }
+
+    /// iter_raw iterates all objects without serialization.
+    fn iter_raw<O: DatabaseObject>(
+        &self,
+    ) -> Result<impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>)>>> {
+        Ok(self.collect::<O>(&[])?.into_iter().map(Ok))
+    }
+
+    /// prefix_iter iterates all objects with prefix.
+    fn prefix_iter<O: DatabaseObject>(
+        &self,
+        prefix: &[u8],
+    ) -> Result<impl Iterator<Item = Result<(Box<[u8]>, O)>>> {
+        Ok(self
+            .collect::<O>(prefix)?
+            .into_iter()
+            .map(|(key, value)| Ok((key, O::deserialize_from(&value)?))))
+    }
+
+    /// prefix_iter_raw iterates all objects with prefix without serialization.
+    fn prefix_iter_raw<O: DatabaseObject>(
+        &self,
+        prefix: &[u8],
+    ) -> Result<impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>)>>> {
+        Ok(self.collect::<O>(prefix)?.into_iter().map(Ok))
+    }
+
+    /// batch_delete deletes objects by keys.
+    fn batch_delete<O: DatabaseObject>(&self, keys: Vec<&[u8]>) -> Result<()> {
+        let txn = self.inner.begin_write().or_err(ErrorType::StorageError)?;
+        {
+            let mut table = txn
+                .open_table(table_definition(O::NAMESPACE))
+                .or_err(ErrorType::StorageError)?;
+            for key in keys {
+                table.remove(key).or_err(ErrorType::StorageError)?;
+            }
+        }
+
+        txn.commit().or_err(ErrorType::StorageError)?;
+        Ok(())
+    }
+}
+
+/// RedbStorageEngine implements the redb of the storage engine.
+impl StorageEngine<'_> for RedbStorageEngine {}
+
+/// RedbStorageEngine implements the Drop trait.
+impl Drop for RedbStorageEngine {
+    /// drop persists the commits with the eventual durability by an immediate commit.
+    fn drop(&mut self) {
+        let result = self
+            .inner
+            .begin_write()
+            .or_err(ErrorType::StorageError)
+            .and_then(|txn| txn.commit().or_err(ErrorType::StorageError));
+        if let Err(err) = result {
+            warn!("persist metadata failed: {}", err);
+        }
+    }
+}
+
+/// table_definition returns the table definition of the namespace.
+fn table_definition(name: &str) -> TableDefinition<'_, &'static [u8], &'static [u8]> {
+    TableDefinition::new(name)
+}
+
+/// table_error converts the error of opening the table for the given object.
+fn table_error<O: DatabaseObject>(err: TableError) -> Error {
+    match err {
+        TableError::TableDoesNotExist(_) => Error::ColumnFamilyNotFound(O::NAMESPACE.to_string()),
+        err => ExternalError::new(ErrorType::StorageError)
+            .with_cause(err.into())
+            .into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use tempfile::tempdir;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+    struct Object {
+        id: String,
+        value: i32,
+    }
+
+    impl DatabaseObject for Object {
+        const NAMESPACE: &'static str = "object";
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+    struct EventualObject {
+        id: String,
+    }
+
+    impl DatabaseObject for EventualObject {
+        const NAMESPACE: &'static str = "eventual_object";
+        const EVENTUAL_DURABILITY: bool = true;
+    }
+
+    #[test]
+    fn test_put_get_and_delete() {
+        let dir = tempdir().unwrap();
+        let engine = RedbStorageEngine::open(dir.path(), &[Object::NAMESPACE], false).unwrap();
+        let object = Object {
+            id: "1".to_string(),
+            value: 42,
+        };
+
+        engine.put::<Object>(object.id.as_bytes(), &object).unwrap();
+        assert!(engine.is_exist::<Object>(object.id.as_bytes()).unwrap());
+        assert_eq!(
+            engine.get::<Object>(object.id.as_bytes()).unwrap(),
+            Some(object.clone())
+        );
+
+        engine.delete::<Object>(object.id.as_bytes()).unwrap();
+        assert!(!engine.is_exist::<Object>(object.id.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_prefix_iter_and_batch_delete() {
+        let dir = tempdir().unwrap();
+        let engine = RedbStorageEngine::open(dir.path(), &[Object::NAMESPACE], false).unwrap();
+        for (key, value) in [("a-1", 1), ("a-2", 2), ("b-1", 3)] {
+            engine
+                .put::<Object>(
+                    key.as_bytes(),
+                    &Object {
+                        id: key.to_string(),
+                        value,
+                    },
+                )
+                .unwrap();
+        }
+
+        let objects = engine
+            .prefix_iter::<Object>(b"a-")
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            objects.iter().map(|(_, o)| o.value).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(engine.iter_raw::<Object>().unwrap().count(), 3);
+
+        engine
+            .batch_delete::<Object>(vec![b"a-1".as_slice(), b"b-1".as_slice()])
+            .unwrap();
+        assert_eq!(engine.iter::<Object>().unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_keep_objects_after_reopen() {
+        let dir = tempdir().unwrap();
+        let object = Object {
+            id: "1".to_string(),
+            value: 42,
+        };
+
+        {
+            let engine = RedbStorageEngine::open(dir.path(), &[Object::NAMESPACE], false).unwrap();
+            engine.put::<Object>(object.id.as_bytes(), &object).unwrap();
+        }
+
+        let engine = RedbStorageEngine::open(dir.path(), &[Object::NAMESPACE], true).unwrap();
+        assert_eq!(
+            engine.get::<Object>(object.id.as_bytes()).unwrap(),
+            Some(object)
+        );
+    }
+
+    #[test]
+    fn test_persist_eventual_objects_after_reopen() {
+        let dir = tempdir().unwrap();
+        let object = EventualObject {
+            id: "1".to_string(),
+        };
+
+        {
+            let engine =
+                RedbStorageEngine::open(dir.path(), &[EventualObject::NAMESPACE], false).unwrap();
+            engine
+                .put::<EventualObject>(object.id.as_bytes(), &object)
+                .unwrap();
+            assert_eq!(
+                engine.get::<EventualObject>(object.id.as_bytes()).unwrap(),
+                Some(object.clone())
+            );
+        }
+
+        let engine =
+            RedbStorageEngine::open(dir.path(), &[EventualObject::NAMESPACE], true).unwrap();
+        assert_eq!(
+            engine.get::<EventualObject>(object.id.as_bytes()).unwrap(),
+            Some(object)
+        );
+    }
+}
@@ -149,6 +149,53 @@ impl FromStr for Digest {
     }
 }
 
+/// Hasher calculates the digest of the content incrementally, it is used when the content is
+/// not a plain file, e.g. the content is read piece by piece.
+pub enum Hasher {
+    /// Crc32 is the hasher of crc32 algorithm.
+    Crc32(crc32fast::Hasher),
+
+    /// Sha256 is the hasher of sha256 algorithm.
+    Sha256(sha2::Sha256),
+
+    /// Sha512 is the hasher of sha512 algorithm.
+    Sha512(sha2::Sha512),
+}
+
+/// Hasher implements the Hasher.
+impl Hasher {
+    /// new returns a new Hasher of the algorithm.
+    pub fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Crc32 => Hasher::Crc32(crc32fast::Hasher::new()),
+            Algorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            Algorithm::Sha512 => Hasher::Sha512(sha2::Sha512::new()),
+        }
+    }
+
+    /// update updates the hasher with the data.
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Crc32(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    /// finalize returns the digest of the updated data.
+    pub fn finalize(self) -> Digest {
+        match self {
+            Hasher::Crc32(hasher) => Digest::new(Algorithm::Crc32, hasher.finalize().to_string()),
+            Hasher::Sha256(hasher) => {
+                Digest::new(Algorithm::Sha256, hex::encode(hasher.finalize()))
+            }
+            Hasher::Sha512(hasher) => {
+                Digest::new(Algorithm::Sha512, hex::encode(hasher.finalize()))
+            }
+        }
+    }
+}
+
 /// calculate_file_digest calculates the digest of a file.
 #[instrument(skip_all)]
 pub fn calculate_file_digest(algorithm: Algorithm, path: &Path) -> ClientResult<Digest> {
@@ -222,6 +269,30 @@ mod tests {
         assert!("invalid".parse::<Algorithm>().is_err());
     }
 
+    #[test]
+    fn test_hasher() {
+        let content = b"test content";
+        for algorithm in [Algorithm::Crc32, Algorithm::Sha256, Algorithm::Sha512] {
+            let mut hasher = Hasher::new(algorithm);
+            hasher.update(&content[..4]);
+            hasher.update(&content[4..]);
+
+            let mut expected = Hasher::new(algorithm);
+            expected.update(content);
+            assert_eq!(
+                hasher.finalize().to_string(),
+                expected.finalize().to_string()
+            );
+        }
+
+        let mut hasher = Hasher::new(Algorithm::Sha256);
+        hasher.update(content);
+        assert_eq!(
+            hasher.finalize().encoded(),
+            "6ae8a75555209fd6c44157c0aed8016e763ff435a19cf186f76863140143ff72"
+        );
+    }
+
     #[test]
     fn test_digest_display() {
         let digest = Digest::new(Algorithm::Sha256, "encoded_hash".to_string());
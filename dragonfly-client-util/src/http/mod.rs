@@ -19,6 +19,7 @@ use dragonfly_client_core::{
     error::{ErrorType, OrErr},
     Error, Result,
 };
+use http_range_header::{EndPosition, StartPosition};
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use std::collections::HashMap;
 
@@ -96,6 +97,117 @@ pub fn parse_range_header(range_header_value: &str, content_length: u64) -> Resu
     Ok(Range { start, length })
 }
 
+/// parse_multi_range_header parses a Range header string with multiple ranges as per RFC 9110,
+/// the unsatisfiable ranges are ignored, and the overlapping or adjacent ranges are merged.
+/// The ranges are sorted by the start offset, e.g. "Range": "bytes=0-99,200-299".
+pub fn parse_multi_range_header(
+    range_header_value: &str,
+    content_length: u64,
+) -> Result<Vec<Range>> {
+    let parsed_ranges =
+        http_range_header::parse_range_header(range_header_value).or_err(ErrorType::ParseError)?;
+
+    // Resolve the ranges by the content length, the validation of the parsed ranges rejects the
+    // overlapping ranges, so the ranges are resolved here.
+    let mut ranges: Vec<(u64, u64)> = parsed_ranges
+        .ranges
+        .iter()
+        .filter_map(|range| {
+            let last_byte = content_length.checked_sub(1)?;
+            match (range.start, range.end) {
+                (StartPosition::Index(start), _) if start > last_byte => None,
+                (StartPosition::Index(start), EndPosition::Index(end)) if end >= start => {
+                    Some((start, end.min(last_byte)))
+                }
+                (StartPosition::Index(start), EndPosition::LastByte) => Some((start, last_byte)),
+                (StartPosition::FromLast(length), _) if length > 0 => {
+                    Some((content_length.saturating_sub(length), last_byte))
+                }
+                _ => None,
+            }
+        })
+        .collect();
+    if ranges.is_empty() {
+        return Err(Error::EmptyHTTPRangeError);
+    }
+
+    // Merge the overlapping and adjacent ranges.
+    ranges.sort_unstable();
+    let mut merged_ranges: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged_ranges.last_mut() {
+            Some((_, last_end)) if start <= last_end.saturating_add(1) => {
+                *last_end = (*last_end).max(end);
+            }
+            _ => merged_ranges.push((start, end)),
+        }
+    }
+
+    Ok(merged_ranges
+        .into_iter()
+        .map(|(start, end)| Range {
+            start,
+            length: end - start + 1,
+        })
+        .collect())
+}
+
+/// is_multi_range returns whether the Range header string contains multiple ranges.
+pub fn is_multi_range(range_header_value: &str) -> bool {
+    http_range_header::parse_range_header(range_header_value)
+        .map(|parsed_ranges| parsed_ranges.ranges.len() > 1)
+        .unwrap_or_default()
+}
+
+/// is_if_range_matched returns whether the If-Range header string matches the validators of the
+/// response header as per RFC 7233. The entity tag is compared by the strong comparison, and
+/// the date is compared with the Last-Modified header exactly.
+pub fn is_if_range_matched(if_range_header_value: &str, response_header: &HeaderMap) -> bool {
+    let if_range = if_range_header_value.trim();
+
+    // The weak entity tag never matches.
+    if if_range.starts_with("W/") {
+        return false;
+    }
+
+    if if_range.starts_with('"') {
+        return response_header
+            .get(reqwest::header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .is_some_and(|etag| etag.trim() == if_range);
+    }
+
+    response_header
+        .get(reqwest::header::LAST_MODIFIED)
+        .and_then(|last_modified| last_modified.to_str().ok())
+        .is_some_and(|last_modified| last_modified.trim() == if_range)
+}
+
+/// is_if_none_match_matched returns whether the If-None-Match header string matches the entity
+/// tag of the response header as per RFC 7232, the entity tags are compared by the weak
+/// comparison.
+pub fn is_if_none_match_matched(
+    if_none_match_header_value: &str,
+    response_header: &HeaderMap,
+) -> bool {
+    // The wildcard matches any existing representation.
+    if if_none_match_header_value.trim() == "*" {
+        return true;
+    }
+
+    let Some(etag) = response_header
+        .get(reqwest::header::ETAG)
+        .and_then(|etag| etag.to_str().ok())
+    else {
+        return false;
+    };
+
+    let etag = etag.trim().trim_start_matches("W/");
+    if_none_match_header_value
+        .split(',')
+        .any(|if_none_match| if_none_match.trim().trim_start_matches("W/") == etag)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -167,4 +279,75 @@ mod tests {
         assert_eq!(range.start, 0);
         assert_eq!(range.length, 101);
     }
+
+    #[test]
+    fn test_parse_multi_range_header() {
+        let ranges = parse_multi_range_header("bytes=200-299, 0-99", 1000).unwrap();
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].start, 0);
+        assert_eq!(ranges[0].length, 100);
+        assert_eq!(ranges[1].start, 200);
+        assert_eq!(ranges[1].length, 100);
+
+        let ranges = parse_multi_range_header("bytes=0-9,-10", 100).unwrap();
+        assert_eq!(ranges[1].start, 90);
+        assert_eq!(ranges[1].length, 10);
+
+        let ranges = parse_multi_range_header("bytes=0-99,50-149,150-199,500-", 1000).unwrap();
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].start, 0);
+        assert_eq!(ranges[0].length, 200);
+        assert_eq!(ranges[1].start, 500);
+        assert_eq!(ranges[1].length, 500);
+
+        let ranges = parse_multi_range_header("bytes=0-99,2000-2099", 1000).unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].length, 100);
+
+        assert!(parse_multi_range_header("bytes=2000-2099", 1000).is_err());
+        assert!(parse_multi_range_header("bytes=0-99", 0).is_err());
+    }
+
+    #[test]
+    fn test_is_multi_range() {
+        assert!(is_multi_range("bytes=0-99,200-299"));
+        assert!(!is_multi_range("bytes=0-99"));
+        assert!(!is_multi_range("invalid"));
+    }
+
+    #[test]
+    fn test_is_if_range_matched() {
+        let mut header = HeaderMap::new();
+        header.insert(reqwest::header::ETAG, HeaderValue::from_static("\"v1\""));
+        header.insert(
+            reqwest::header::LAST_MODIFIED,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+
+        assert!(is_if_range_matched("\"v1\"", &header));
+        assert!(!is_if_range_matched("\"v2\"", &header));
+        assert!(!is_if_range_matched("W/\"v1\"", &header));
+        assert!(is_if_range_matched(
+            "Wed, 21 Oct 2015 07:28:00 GMT",
+            &header
+        ));
+        assert!(!is_if_range_matched(
+            "Thu, 22 Oct 2015 07:28:00 GMT",
+            &header
+        ));
+        assert!(!is_if_range_matched("\"v1\"", &HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_is_if_none_match_matched() {
+        let mut header = HeaderMap::new();
+        header.insert(reqwest::header::ETAG, HeaderValue::from_static("W/\"v1\""));
+
+        assert!(is_if_none_match_matched("\"v1\"", &header));
+        assert!(is_if_none_match_matched("\"v0\", W/\"v1\"", &header));
+        assert!(is_if_none_match_matched("*", &header));
+        assert!(!is_if_none_match_matched("\"v2\"", &header));
+        assert!(is_if_none_match_matched("*", &HeaderMap::new()));
+        assert!(!is_if_none_match_matched("\"v1\"", &HeaderMap::new()));
+    }
 }
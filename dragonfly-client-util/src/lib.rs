@@ -18,5 +18,6 @@ pub mod digest;
 pub mod fs;
 pub mod http;
 pub mod id_generator;
+pub mod merkle;
 pub mod net;
 pub mod tls;
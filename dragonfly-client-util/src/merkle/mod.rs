@@ -0,0 +1,151 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::digest::{Algorithm, Digest};
+use sha2::{Digest as Sha2Digest, Sha256};
+
+/// LEAF_PREFIX is the prefix of the leaf hash, it distinguishes the leaves from the inner
+/// nodes, so an inner node can not be forged as a leaf.
+const LEAF_PREFIX: u8 = 0x00;
+
+/// NODE_PREFIX is the prefix of the inner node hash.
+const NODE_PREFIX: u8 = 0x01;
+
+/// MerkleTree is the merkle tree of the task, the leaves are the digests of the pieces
+/// ordered by the piece number, and the root commits to the content of all the pieces.
+pub struct MerkleTree {
+    /// levels are the hashes of the tree from the leaves to the root.
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+/// MerkleTree implements the merkle tree.
+impl MerkleTree {
+    /// new builds the merkle tree by the digests of the pieces. The node without the sibling
+    /// is promoted to the upper level directly.
+    pub fn new(leaves: &[String]) -> Self {
+        let mut levels = vec![leaves
+            .iter()
+            .map(|leaf| {
+                let mut hasher = Sha256::new();
+                hasher.update([LEAF_PREFIX]);
+                hasher.update(leaf.as_bytes());
+                hasher.finalize().into()
+            })
+            .collect::<Vec<[u8; 32]>>()];
+
+        while levels.last().map_or(0, Vec::len) > 1 {
+            let level = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|nodes| match nodes {
+                    [left, right] => {
+                        let mut hasher = Sha256::new();
+                        hasher.update([NODE_PREFIX]);
+                        hasher.update(left);
+                        hasher.update(right);
+                        hasher.finalize().into()
+                    }
+                    [node] => *node,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(level);
+        }
+
+        Self { levels }
+    }
+
+    /// root returns the root of the merkle tree, the root of the empty tree is the sha256 of
+    /// the empty content.
+    pub fn root(&self) -> Digest {
+        let root = match self.levels.last().and_then(|level| level.first()) {
+            Some(root) => hex::encode(root),
+            None => hex::encode(Sha256::digest(b"")),
+        };
+
+        Digest::new(Algorithm::Sha256, root)
+    }
+
+    /// len returns the number of the leaves.
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// is_empty returns whether the tree has no leaves.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|number| {
+                format!(
+                    "sha256:{}",
+                    hex::encode(Sha256::digest(number.to_be_bytes()))
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_merkle_root() {
+        let tree = MerkleTree::new(&[]);
+        assert!(tree.is_empty());
+        assert_eq!(
+            tree.root().to_string(),
+            "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+
+        // The root is deterministic for the same leaves.
+        for count in [1, 2, 3, 7, 8] {
+            let tree = MerkleTree::new(&leaves(count));
+            assert_eq!(tree.len(), count);
+            assert_eq!(
+                tree.root().to_string(),
+                MerkleTree::new(&leaves(count)).root().to_string()
+            );
+        }
+
+        // The root of the single leaf is not the leaf itself.
+        let leaf = leaves(1);
+        assert_ne!(MerkleTree::new(&leaf).root().to_string(), leaf[0]);
+    }
+
+    #[test]
+    fn test_merkle_root_changes() {
+        let original = leaves(5);
+        let root = MerkleTree::new(&original).root().to_string();
+
+        // Forge one of the leaves.
+        let mut forged = original.clone();
+        forged[3] = leaves(6)[5].clone();
+        assert_ne!(MerkleTree::new(&forged).root().to_string(), root);
+
+        // Reorder the leaves.
+        let mut reordered = original.clone();
+        reordered.swap(0, 1);
+        assert_ne!(MerkleTree::new(&reordered).root().to_string(), root);
+
+        // Drop the last leaf.
+        assert_ne!(MerkleTree::new(&original[..4]).root().to_string(), root);
+    }
+}
@@ -216,6 +216,12 @@ impl SchedulerAnnouncer {
             platform: None,
         };
 
+        // Get the port for downloading pieces by the storage server protocol.
+        let download_port = match self.config.storage.server.protocol.as_str() {
+            "tcp" => self.config.storage.server.tcp_port,
+            _ => self.config.upload.server.port,
+        };
+
         // Struct the host information.
         let host = Host {
             id: self.host_id.to_string(),
@@ -223,7 +229,7 @@ impl SchedulerAnnouncer {
             hostname: self.config.host.hostname.clone(),
             ip: self.config.host.ip.unwrap().to_string(),
             port: self.config.upload.server.port as i32,
-            download_port: self.config.upload.server.port as i32,
+            download_port: download_port as i32,
             os: env::consts::OS.to_string(),
             platform: env::consts::OS.to_string(),
             platform_family: env::consts::FAMILY.to_string(),
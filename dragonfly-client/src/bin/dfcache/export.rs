@@ -14,11 +14,14 @@
  * limitations under the License.
  */
 
+use bytes::Bytes;
 use clap::Parser;
+use dragonfly_api::common::v2::{Hdfs, ObjectStorage};
 use dragonfly_api::dfdaemon::v2::{
     download_persistent_cache_task_response, DownloadPersistentCacheTaskRequest,
 };
 use dragonfly_api::errordetails::v2::Backend;
+use dragonfly_client_backend::{hdfs, object_storage, BackendFactory, PutRequest};
 use dragonfly_client_core::{
     error::{ErrorType, OrErr},
     Error, Result,
@@ -27,16 +30,31 @@ use dragonfly_client_util::fs::fallocate;
 use indicatif::{ProgressBar, ProgressState, ProgressStyle};
 use local_ip_address::local_ip;
 use path_absolutize::*;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::Duration;
 use std::{cmp::min, fmt::Write};
 use termion::{color, style};
 use tokio::fs::{self, OpenOptions};
 use tokio::io::{AsyncSeekExt, AsyncWriteExt, SeekFrom};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::io::StreamReader;
 use tracing::{debug, error, info};
+use url::Url;
 
 use super::*;
 
+/// EXPORT_BUFFER_PIECES is the number of the pieces buffered in the pipe between the
+/// downloaded pieces and the backend when the file is exported to the url.
+const EXPORT_BUFFER_PIECES: usize = 4;
+
+/// MAX_EXPORT_PENDING_SIZE is the max size of the out of order pieces buffered in memory
+/// when the file is exported to the url. The pieces are downloaded concurrently by dfdaemon,
+/// so only a window of the pieces is out of order, the export fails if the window is exceeded.
+const MAX_EXPORT_PENDING_SIZE: u64 = 1024 * 1024 * 1024;
+
 /// ExportCommand is the subcommand of export.
 #[derive(Debug, Clone, Parser)]
 pub struct ExportCommand {
@@ -74,9 +92,59 @@ pub struct ExportCommand {
     #[arg(
         short = 'O',
         long = "output",
+        required_unless_present = "url",
+        conflicts_with = "url",
         help = "Specify the output path of exporting file"
     )]
-    output: PathBuf,
+    output: Option<PathBuf>,
+
+    #[arg(
+        long = "url",
+        help = "Specify the object storage or HDFS url to export the file to, such as s3://<bucket>/<path>. If it is set, the content of the file is written to the url instead of the output path"
+    )]
+    url: Option<Url>,
+
+    #[arg(long, help = "Specify the region for the Object Storage Service")]
+    storage_region: Option<String>,
+
+    #[arg(long, help = "Specify the endpoint for the Object Storage Service")]
+    storage_endpoint: Option<String>,
+
+    #[arg(
+        long,
+        help = "Specify the access key ID for the Object Storage Service"
+    )]
+    storage_access_key_id: Option<String>,
+
+    #[arg(
+        long,
+        help = "Specify the access key secret for the Object Storage Service"
+    )]
+    storage_access_key_secret: Option<String>,
+
+    #[arg(
+        long,
+        help = "Specify the session token for Amazon Simple Storage Service(S3)"
+    )]
+    storage_session_token: Option<String>,
+
+    #[arg(
+        long,
+        help = "Specify the local path to the credential file which is used for OAuth2 authentication for Google Cloud Storage Service(GCS)"
+    )]
+    storage_credential_path: Option<String>,
+
+    #[arg(
+        long,
+        help = "Specify the predefined ACL for Google Cloud Storage Service(GCS), the default ACL of the bucket is used if not set"
+    )]
+    storage_predefined_acl: Option<String>,
+
+    #[arg(
+        long,
+        help = "Specify the delegation token for Hadoop Distributed File System(HDFS)"
+    )]
+    hdfs_delegation_token: Option<String>,
 
     #[arg(
         long = "timeout",
@@ -450,13 +518,19 @@ impl ExportCommand {
     /// file assembly. The operation provides real-time progress feedback and handles file
     /// creation, directory setup, and efficient piece-by-piece writing with sparse file allocation.
     async fn run(&self, dfdaemon_download_client: DfdaemonDownloadClient) -> Result<()> {
+        // Export the file to the object storage or HDFS url.
+        let Some(output) = self.output.as_ref() else {
+            let url = self.url.as_ref().ok_or(Error::InvalidParameter)?;
+            return self.export_to_url(dfdaemon_download_client, url).await;
+        };
+
         // Dfcache needs to notify dfdaemon to transfer the piece content of downloading file via unix domain socket
         // when the `transfer_from_dfdaemon` is true. Otherwise, dfdaemon will download the file and hardlink or
         // copy the file to the output path.
         let (output_path, need_piece_content) = if self.transfer_from_dfdaemon {
             (None, true)
         } else {
-            let absolute_path = Path::new(&self.output).absolutize()?;
+            let absolute_path = Path::new(output).absolutize()?;
             info!("export file to: {}", absolute_path.to_string_lossy());
             (Some(absolute_path.to_string_lossy().to_string()), false)
         };
@@ -488,7 +562,7 @@ impl ExportCommand {
         // If transfer_from_dfdaemon is true, then dfcache needs to create the output file and write the
         // piece content to the output file.
         let mut f = if self.transfer_from_dfdaemon {
-            if let Some(parent) = self.output.parent() {
+            if let Some(parent) = output.parent() {
                 if !parent.exists() {
                     fs::create_dir_all(parent).await.inspect_err(|err| {
                         error!("failed to create directory {:?}: {}", parent, err);
@@ -500,10 +574,10 @@ impl ExportCommand {
                 .create_new(true)
                 .write(true)
                 .mode(dfcache::DEFAULT_OUTPUT_FILE_MODE)
-                .open(&self.output)
+                .open(output)
                 .await
                 .inspect_err(|err| {
-                    error!("open file {:?} failed: {}", self.output, err);
+                    error!("open file {:?} failed: {}", output, err);
                 })?;
 
             Some(f)
@@ -538,7 +612,7 @@ impl ExportCommand {
                         fallocate(f, response.content_length)
                             .await
                             .inspect_err(|err| {
-                                error!("fallocate {:?} failed: {}", self.output, err);
+                                error!("fallocate {:?} failed: {}", output, err);
                             })?;
                     }
 
@@ -556,7 +630,7 @@ impl ExportCommand {
                         f.seek(SeekFrom::Start(piece.offset))
                             .await
                             .inspect_err(|err| {
-                                error!("seek {:?} failed: {}", self.output, err);
+                                error!("seek {:?} failed: {}", output, err);
                             })?;
 
                         let content = piece.content.ok_or(Error::InvalidParameter).inspect_err(|_err| {
@@ -564,10 +638,10 @@ impl ExportCommand {
                         })?;
 
                         f.write_all(&content).await.inspect_err(|err| {
-                            error!("write {:?} failed: {}", self.output, err);
+                            error!("write {:?} failed: {}", output, err);
                         })?;
 
-                        debug!("copy piece {} to {:?} success", piece.number, self.output);
+                        debug!("copy piece {} to {:?} success", piece.number, output);
                     };
 
                     downloaded += piece.length;
@@ -582,14 +656,226 @@ impl ExportCommand {
         Ok(())
     }
 
+    /// export_to_url exports the persistent cache task to the object storage or HDFS url. The
+    /// piece content is transferred from dfdaemon and written to the backend in order by the
+    /// streaming put, so the file is not stored in the local file system.
+    async fn export_to_url(
+        &self,
+        dfdaemon_download_client: DfdaemonDownloadClient,
+        url: &Url,
+    ) -> Result<()> {
+        info!("export file to: {}", url);
+        let backend_factory = BackendFactory::new(None)?;
+        let backend = backend_factory.build(url.as_str())?;
+
+        // Create dfdaemon client.
+        let response = dfdaemon_download_client
+            .download_persistent_cache_task(DownloadPersistentCacheTaskRequest {
+                task_id: self.id.clone(),
+                // When scheduler triggers the export task, it will set true. If the export task is
+                // triggered by the user, it will set false.
+                persistent: false,
+                tag: Some(self.tag.clone()),
+                application: Some(self.application.clone()),
+                output_path: None,
+                timeout: Some(
+                    prost_wkt_types::Duration::try_from(self.timeout)
+                        .or_err(ErrorType::ParseError)?,
+                ),
+                need_piece_content: true,
+                force_hard_link: false,
+                digest: self.digest.clone(),
+                remote_ip: Some(local_ip().unwrap().to_string()),
+            })
+            .await
+            .inspect_err(|err| {
+                error!("download persistent cache task failed: {}", err);
+            })?;
+
+        // Get the content length of the task from the started response.
+        let mut out_stream = response.into_inner();
+        let content_length = loop {
+            let message = out_stream
+                .message()
+                .await
+                .inspect_err(|err| {
+                    error!("get message failed: {}", err);
+                })?
+                .ok_or_else(|| Error::Unknown("download stream is closed".to_string()))?;
+
+            if let Some(download_persistent_cache_task_response::Response::DownloadPersistentCacheTaskStartedResponse(
+                response,
+            )) = message.response
+            {
+                break response.content_length;
+            }
+        };
+
+        // Initialize progress bar.
+        let progress_bar = ProgressBar::new(content_length);
+        progress_bar.set_style(
+            ProgressStyle::with_template(
+                "[{elapsed_precise}] [{wide_bar}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+            )
+            .or_err(ErrorType::ParseError)?
+            .with_key("eta", |state: &ProgressState, w: &mut dyn Write| {
+                write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap()
+            })
+            .progress_chars("#>-"),
+        );
+
+        // Only initialize object storage when the scheme is an object storage protocol.
+        let object_storage = match object_storage::Scheme::from_str(url.scheme()) {
+            Ok(_) => Some(ObjectStorage {
+                access_key_id: self.storage_access_key_id.clone(),
+                access_key_secret: self.storage_access_key_secret.clone(),
+                session_token: self.storage_session_token.clone(),
+                region: self.storage_region.clone(),
+                endpoint: self.storage_endpoint.clone(),
+                credential_path: self.storage_credential_path.clone(),
+                predefined_acl: self.storage_predefined_acl.clone(),
+            }),
+            Err(_) => None,
+        };
+
+        // Only initialize HDFS when the scheme is HDFS protocol.
+        let hdfs = match url.scheme() {
+            hdfs::HDFS_SCHEME => Some(Hdfs {
+                delegation_token: self.hdfs_delegation_token.clone(),
+            }),
+            _ => None,
+        };
+
+        // The pieces are downloaded out of order, so the pieces are buffered until the
+        // previous pieces are written to the backend.
+        //
+        // The transfer error is sent to the put after the pieces, so the put is always driven
+        // to completion, and the uploaded content is aborted by the backend if the transfer fails.
+        let (sender, receiver) = mpsc::channel::<std::io::Result<Bytes>>(EXPORT_BUFFER_PIECES);
+        let transfer = async {
+            let sender = sender;
+            let result = async {
+                let mut offset = 0;
+                let mut pending = BTreeMap::new();
+                let mut pending_size = 0;
+                while let Some(message) = out_stream.message().await.inspect_err(|err| {
+                    error!("get message failed: {}", err);
+                })? {
+                    if let Some(download_persistent_cache_task_response::Response::DownloadPieceFinishedResponse(
+                        response,
+                    )) = message.response
+                    {
+                        let piece = response.piece.ok_or(Error::InvalidParameter).inspect_err(|_err| {
+                            error!("response piece is missing");
+                        })?;
+
+                        let content = piece.content.ok_or(Error::InvalidParameter).inspect_err(|_err| {
+                            error!("piece content is missing");
+                        })?;
+
+                        pending_size += content.len() as u64;
+                        pending.insert(piece.offset, content);
+                        while let Some(content) = pending.remove(&offset) {
+                            pending_size -= content.len() as u64;
+                            offset += content.len() as u64;
+                            sender.send(Ok(Bytes::from(content))).await.map_err(|_| {
+                                error!("write {} failed: put is closed", url);
+                                Error::Unknown(format!("put {} is closed", url))
+                            })?;
+
+                            progress_bar.set_position(offset);
+                        }
+
+                        if pending_size > MAX_EXPORT_PENDING_SIZE {
+                            error!(
+                                "out of order pieces of {} exceed {} bytes",
+                                url, MAX_EXPORT_PENDING_SIZE
+                            );
+                            return Err(Error::Unknown(format!(
+                                "out of order pieces exceed {} bytes",
+                                MAX_EXPORT_PENDING_SIZE
+                            )));
+                        }
+
+                        debug!("copy piece {} to {} success", piece.number, url);
+                    }
+                }
+
+                if !pending.is_empty() || offset != content_length {
+                    error!("pieces of {} are missing", url);
+                    return Err(Error::ContentLengthMismatch(content_length, offset));
+                }
+
+                Ok::<_, Error>(())
+            }
+            .await;
+
+            if let Err(err) = result.as_ref() {
+                let _ = sender
+                    .send(Err(std::io::Error::other(err.to_string())))
+                    .await;
+            }
+
+            result
+        };
+
+        let put = backend.put(
+            PutRequest {
+                task_id: self.id.clone(),
+                url: url.to_string(),
+                content_length: Some(content_length),
+                http_header: None,
+                timeout: self.timeout,
+                client_cert: None,
+                object_storage,
+                hdfs,
+            },
+            Box::new(StreamReader::new(ReceiverStream::new(receiver))),
+        );
+
+        let (transfer_result, put_result) = tokio::join!(transfer, put);
+        let response = put_result.inspect_err(|err| {
+            error!("put {} failed: {}", url, err);
+        })?;
+        transfer_result.inspect_err(|err| {
+            error!("transfer {} failed: {}", url, err);
+        })?;
+
+        info!("put {} success: {}", url, response.content_length);
+        progress_bar.finish_with_message("exported");
+        Ok(())
+    }
+
     /// Validates command line arguments for the export operation to ensure safe file output.
     ///
     /// This function performs essential validation of the output path to prevent file conflicts
     /// and ensure the target location is suitable for export operations. It checks parent
     /// directory existence, prevents accidental file overwrites, and validates path accessibility
-    /// before allowing the export operation to proceed.
+    /// before allowing the export operation to proceed. The url must be a file of the object
+    /// storage or HDFS if the file is exported to the url.
     fn validate_args(&self) -> Result<()> {
-        let absolute_path = Path::new(&self.output).absolutize()?;
+        if let Some(url) = self.url.as_ref() {
+            if object_storage::Scheme::from_str(url.scheme()).is_err()
+                && url.scheme() != hdfs::HDFS_SCHEME
+            {
+                return Err(Error::ValidationError(format!(
+                    "url {} is not an object storage or HDFS url",
+                    url
+                )));
+            }
+
+            if url.path().ends_with('/') {
+                return Err(Error::ValidationError(format!(
+                    "url {} is a directory",
+                    url
+                )));
+            }
+
+            return Ok(());
+        }
+
+        let output = self.output.as_ref().ok_or(Error::InvalidParameter)?;
+        let absolute_path = Path::new(output).absolutize()?;
         match absolute_path.parent() {
             Some(parent_path) => {
                 if !parent_path.is_dir() {
@@ -602,7 +888,7 @@ impl ExportCommand {
             None => {
                 return Err(Error::ValidationError(format!(
                     "output path {} is not exist",
-                    self.output.to_string_lossy()
+                    output.to_string_lossy()
                 )));
             }
         }
@@ -610,7 +896,7 @@ impl ExportCommand {
         if absolute_path.exists() {
             return Err(Error::ValidationError(format!(
                 "output path {} is already exist",
-                self.output.to_string_lossy()
+                output.to_string_lossy()
             )));
         }
 
@@ -55,6 +55,7 @@ struct Args {
 
 #[derive(Debug, Clone, Subcommand)]
 #[command(args_conflicts_with_subcommands = true)]
+#[allow(clippy::large_enum_variant)]
 pub enum Command {
     #[command(
         name = "import",
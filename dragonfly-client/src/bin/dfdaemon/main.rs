@@ -23,20 +23,30 @@ use dragonfly_client::grpc::{
     manager::ManagerClient, scheduler::SchedulerClient,
 };
 use dragonfly_client::health::Health;
-use dragonfly_client::metrics::Metrics;
+use dragonfly_client::metrics::{collect_metadata_migration_dropped_object_metrics, Metrics};
 use dragonfly_client::proxy::Proxy;
-use dragonfly_client::resource::{persistent_cache_task::PersistentCacheTask, task::Task};
+use dragonfly_client::resource::{
+    cache_task::CacheTask, parent_selector::ParentSelector,
+    persistent_cache_task::PersistentCacheTask, task::Task,
+};
 use dragonfly_client::shutdown;
 use dragonfly_client::stats::Stats;
+use dragonfly_client::tcp::server::TCPServer;
 use dragonfly_client::tracing::init_tracing;
+use dragonfly_client_backend::credential::{
+    CredentialProvider, CredentialProviderChain, CredentialScope, EnvironmentProvider,
+    MetadataProvider, SecretDirProvider, SharedCredentialsFileProvider,
+};
 use dragonfly_client_backend::BackendFactory;
 use dragonfly_client_config::{dfdaemon, VersionValueParser};
-use dragonfly_client_storage::Storage;
+use dragonfly_client_storage::{metadata::Metadata, Storage};
 use dragonfly_client_util::{id_generator::IDGenerator, net::Interface};
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use termion::{color, style};
+use tokio::fs::File;
+use tokio::io::{BufReader, BufWriter};
 use tokio::sync::mpsc;
 use tokio::sync::Barrier;
 use tracing::{error, info, Level};
@@ -94,6 +104,50 @@ struct Args {
     #[arg(long, default_value_t = true, help = "Specify whether to print log")]
     console: bool,
 
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Check the integrity of the storage and exit, dfdaemon must be stopped before checking"
+    )]
+    fsck: bool,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "fsck",
+        help = "Delete the corrupted tasks and orphaned contents found by the storage check"
+    )]
+    repair: bool,
+
+    #[arg(
+        long,
+        value_parser = ["rocksdb", "redb"],
+        conflicts_with = "fsck",
+        help = "Copy the metadata from the specified engine to the engine of storage.metadataEngine and exit, dfdaemon must be stopped before migrating"
+    )]
+    migrate_metadata_from: Option<String>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["fsck", "migrate_metadata_from"],
+        help = "Export the finished tasks of the storage to the bundle file and exit, dfdaemon must be stopped before exporting"
+    )]
+    export_bundle: Option<PathBuf>,
+
+    #[arg(
+        long = "bundle-task-id",
+        requires = "export_bundle",
+        help = "Specify the id of the task to export to the bundle, all the finished tasks are exported if it is not specified"
+    )]
+    bundle_task_ids: Vec<String>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["fsck", "migrate_metadata_from", "export_bundle"],
+        help = "Import the tasks from the bundle file into the storage and exit, dfdaemon must be stopped before importing"
+    )]
+    import_bundle: Option<PathBuf>,
+
     #[arg(
         short = 'V',
         long = "version",
@@ -154,12 +208,170 @@ async fn main() -> Result<(), anyhow::Error> {
         args.console,
     );
 
+    // Check the integrity of the storage and exit.
+    if args.fsck {
+        // Keep the metadata and content of the storage, otherwise they will be cleaned
+        // when the storage is opened.
+        let mut fsck_config = (*config).clone();
+        fsck_config.storage.keep = true;
+
+        let storage = Storage::new(
+            Arc::new(fsck_config),
+            config.storage.dir.as_path(),
+            args.log_dir,
+        )
+        .await
+        .inspect_err(|err| {
+            error!("initialize storage failed: {}", err);
+        })?;
+
+        let report = storage.check(args.repair).await.inspect_err(|err| {
+            error!("check storage failed: {}", err);
+        })?;
+
+        println!(
+            "checked tasks: {}, checked pieces: {}",
+            report.checked_tasks, report.checked_pieces
+        );
+        for id in report.corrupted_tasks.iter() {
+            println!("corrupted task: {}", id);
+        }
+
+        for id in report.orphaned_contents.iter() {
+            println!("orphaned content: {}", id);
+        }
+
+        for id in report.orphaned_pieces.iter() {
+            println!("orphaned piece: {}", id);
+        }
+
+        if report.is_consistent() {
+            println!("storage is consistent");
+            return Ok(());
+        }
+
+        if args.repair {
+            println!("storage is repaired");
+            return Ok(());
+        }
+
+        std::process::exit(1);
+    }
+
+    // Migrate the metadata from the other storage engine and exit.
+    if let Some(from) = args.migrate_metadata_from.as_deref() {
+        let from = match from {
+            "redb" => dfdaemon::MetadataEngineType::Redb,
+            _ => dfdaemon::MetadataEngineType::Rocksdb,
+        };
+
+        let to = config.storage.metadata_engine;
+        if from == to || to == dfdaemon::MetadataEngineType::Memory {
+            error!("can not migrate metadata from {} to {}", from, to);
+            std::process::exit(1);
+        }
+
+        // Keep the metadata of both engines, otherwise they will be cleaned when
+        // the metadata is opened.
+        let source = Metadata::open(from, config.storage.dir.as_path(), &args.log_dir, true)
+            .inspect_err(|err| {
+                error!("open {} metadata failed: {}", from, err);
+            })?;
+        let target = Metadata::open(to, config.storage.dir.as_path(), &args.log_dir, true)
+            .inspect_err(|err| {
+                error!("open {} metadata failed: {}", to, err);
+            })?;
+
+        let migrated = source.migrate_to(&target).inspect_err(|err| {
+            error!("migrate metadata failed: {}", err);
+        })?;
+
+        println!(
+            "migrated {} metadata objects from {} to {}",
+            migrated, from, to
+        );
+        return Ok(());
+    }
+
+    // Export the tasks to the bundle or import the tasks from the bundle and exit.
+    if args.export_bundle.is_some() || args.import_bundle.is_some() {
+        // Keep the metadata and content of the storage, otherwise they will be cleaned
+        // when the storage is opened.
+        let mut bundle_config = (*config).clone();
+        bundle_config.storage.keep = true;
+
+        let storage = Storage::new(
+            Arc::new(bundle_config),
+            config.storage.dir.as_path(),
+            args.log_dir,
+        )
+        .await
+        .inspect_err(|err| {
+            error!("initialize storage failed: {}", err);
+        })?;
+
+        if let Some(path) = args.export_bundle.as_ref() {
+            let mut writer = BufWriter::new(File::create(path).await.inspect_err(|err| {
+                error!("create bundle {} failed: {}", path.display(), err);
+            })?);
+
+            let report = storage
+                .export_bundle(&args.bundle_task_ids, &mut writer)
+                .await
+                .inspect_err(|err| {
+                    error!("export bundle failed: {}", err);
+                })?;
+
+            for id in report.skipped_tasks.iter() {
+                println!("skipped unfinished task: {}", id);
+            }
+
+            println!(
+                "exported tasks: {}, pieces: {}, bytes: {} to {}",
+                report.tasks.len(),
+                report.pieces,
+                report.bytes,
+                path.display()
+            );
+        }
+
+        if let Some(path) = args.import_bundle.as_ref() {
+            let mut reader = BufReader::new(File::open(path).await.inspect_err(|err| {
+                error!("open bundle {} failed: {}", path.display(), err);
+            })?);
+
+            let report = storage
+                .import_bundle(&mut reader)
+                .await
+                .inspect_err(|err| {
+                    error!("import bundle failed: {}", err);
+                })?;
+
+            for id in report.skipped_tasks.iter() {
+                println!("skipped existing task: {}", id);
+            }
+
+            println!(
+                "imported tasks: {}, pieces: {}, bytes: {} from {}",
+                report.tasks.len(),
+                report.pieces,
+                report.bytes,
+                path.display()
+            );
+        }
+
+        return Ok(());
+    }
+
     // Initialize storage.
     let storage = Storage::new(config.clone(), config.storage.dir.as_path(), args.log_dir)
         .await
         .inspect_err(|err| {
             error!("initialize storage failed: {}", err);
         })?;
+    for (namespace, count) in storage.dropped_metadata_objects() {
+        collect_metadata_migration_dropped_object_metrics(namespace, *count);
+    }
     let storage = Arc::new(storage);
 
     // Initialize id generator.
@@ -203,11 +415,72 @@ async fn main() -> Result<(), anyhow::Error> {
         })?;
     let scheduler_client = Arc::new(scheduler_client);
 
-    let backend_factory = BackendFactory::new(Some(config.server.plugin_dir.as_path()))
+    // Initialize the credential providers of the object storage.
+    let mut credential_providers: Vec<Box<dyn CredentialProvider>> = Vec::new();
+    if config.backend.credential.env {
+        credential_providers.push(Box::new(EnvironmentProvider::new()));
+    }
+
+    if let Some(path) = config.backend.credential.shared_credentials_file.clone() {
+        credential_providers.push(Box::new(SharedCredentialsFileProvider::new(
+            path,
+            &config.backend.credential.profile,
+        )));
+    }
+
+    if let Some(dir) = config.backend.credential.secret_dir.clone() {
+        credential_providers.push(Box::new(SecretDirProvider::new(dir)));
+    }
+
+    if let Some(endpoint) = config.backend.credential.metadata_endpoint.as_deref() {
+        credential_providers.push(Box::new(MetadataProvider::new(endpoint).inspect_err(
+            |err| {
+                error!("initialize metadata credential provider failed: {}", err);
+            },
+        )?));
+    }
+
+    let credential_provider = CredentialProviderChain::new(credential_providers);
+    let credential_provider: Option<Arc<dyn CredentialProvider>> = if credential_provider.is_empty()
+    {
+        None
+    } else {
+        Some(Arc::new(credential_provider))
+    };
+
+    // Initialize the backend factories, the credential of the providers is only applied to
+    // the requests of the download server. The requests of the upload server are sent by the
+    // remote peers and the scheduler, so its backend factory does not load the credential.
+    let new_backend_factory = |credential_provider| {
+        BackendFactory::new_with_credential_provider(
+            Some(config.server.plugin_dir.as_path()),
+            credential_provider,
+            CredentialScope::new(
+                config.backend.credential.allowed_buckets.clone(),
+                config.backend.credential.allowed_endpoints.clone(),
+            ),
+        )
+        .and_then(|backend_factory| {
+            backend_factory
+                .with_oci_allowed_token_hosts(config.backend.oci.allowed_token_hosts.clone())
+        })
+        .map(|backend_factory| {
+            backend_factory.with_file_allowed_dirs(config.backend.file.allowed_dirs.clone())
+        })
         .inspect_err(|err| {
             error!("initialize backend factory failed: {}", err);
-        })?;
-    let backend_factory = Arc::new(backend_factory);
+        })
+    };
+    let backend_factory = Arc::new(new_backend_factory(credential_provider)?);
+    let upload_backend_factory = Arc::new(new_backend_factory(None)?);
+
+    // Initialize parent selector.
+    let parent_selector = ParentSelector::new(
+        config.clone(),
+        id_generator.host_id().as_str(),
+        shutdown.clone(),
+    );
+    let parent_selector = Arc::new(parent_selector);
 
     // Initialize task manager.
     let task = Task::new(
@@ -216,9 +489,17 @@ async fn main() -> Result<(), anyhow::Error> {
         storage.clone(),
         scheduler_client.clone(),
         backend_factory.clone(),
+        parent_selector.clone(),
     )?;
     let task = Arc::new(task);
 
+    // Resume the unfinished tasks of the last run, the pieces are verified before the servers
+    // start, so the corrupted pieces are never uploaded to the other peers.
+    let resumed_tasks = task.resume_tasks().await.unwrap_or_else(|err| {
+        error!("resume tasks failed: {}", err);
+        Vec::new()
+    });
+
     // Initialize persistent cache task manager.
     let persistent_cache_task = PersistentCacheTask::new(
         config.clone(),
@@ -226,9 +507,26 @@ async fn main() -> Result<(), anyhow::Error> {
         storage.clone(),
         scheduler_client.clone(),
         backend_factory.clone(),
+        parent_selector.clone(),
     )?;
     let persistent_cache_task = Arc::new(persistent_cache_task);
 
+    // Initialize cache task manager.
+    let cache_task = CacheTask::new(
+        config.clone(),
+        id_generator.clone(),
+        storage.clone(),
+        scheduler_client.clone(),
+        backend_factory.clone(),
+        parent_selector.clone(),
+    )?;
+    let cache_task = Arc::new(cache_task);
+
+    // Initialize the task managers of the upload server, which share the pieces and the
+    // rate limiters with the task managers of the download server.
+    let upload_task = Arc::new(task.with_backend_factory(upload_backend_factory.clone()));
+    let upload_cache_task = Arc::new(cache_task.with_backend_factory(upload_backend_factory));
+
     let interface = Interface::new(config.host.ip.unwrap(), config.upload.rate_limit);
     let interface = Arc::new(interface);
 
@@ -279,8 +577,9 @@ async fn main() -> Result<(), anyhow::Error> {
     let mut dfdaemon_upload_grpc = DfdaemonUploadServer::new(
         config.clone(),
         SocketAddr::new(config.upload.server.ip.unwrap(), config.upload.server.port),
-        task.clone(),
+        upload_task,
         persistent_cache_task.clone(),
+        upload_cache_task,
         interface.clone(),
         shutdown.clone(),
         shutdown_complete_tx.clone(),
@@ -292,10 +591,25 @@ async fn main() -> Result<(), anyhow::Error> {
         config.download.server.socket_path.clone(),
         task.clone(),
         persistent_cache_task.clone(),
+        cache_task.clone(),
         shutdown.clone(),
         shutdown_complete_tx.clone(),
     );
 
+    // Initialize tcp storage server, it is only started when the storage server protocol is tcp.
+    let tcp_server = (config.storage.server.protocol == "tcp").then(|| {
+        TCPServer::new(
+            SocketAddr::new(
+                config.storage.server.ip.unwrap(),
+                config.storage.server.tcp_port,
+            ),
+            config.upload.server.auth.clone(),
+            task.clone(),
+            shutdown.clone(),
+            shutdown_complete_tx.clone(),
+        )
+    });
+
     // Initialize garbage collector.
     let gc = GC::new(
         config.clone(),
@@ -310,7 +624,17 @@ async fn main() -> Result<(), anyhow::Error> {
     info!("dfdaemon started at pid {}", std::process::id());
 
     // grpc server started barrier.
-    let grpc_server_started_barrier = Arc::new(Barrier::new(3));
+    let grpc_server_started_barrier = Arc::new(Barrier::new(4));
+
+    // Resume the prefetches of the resumed tasks when the grpc servers are started.
+    {
+        let barrier = grpc_server_started_barrier.clone();
+        let task = task.clone();
+        tokio::spawn(async move {
+            barrier.wait().await;
+            task.resume_prefetches(resumed_tasks).await;
+        });
+    }
 
     // Wait for servers to exit or shutdown signal.
     tokio::select! {
@@ -365,6 +689,15 @@ async fn main() -> Result<(), anyhow::Error> {
             info!("proxy server exited");
         },
 
+        _ = tokio::spawn(async move {
+            match tcp_server {
+                Some(tcp_server) => tcp_server.run().await.unwrap_or_else(|err| error!("tcp storage server failed: {}", err)),
+                None => std::future::pending().await,
+            }
+        }) => {
+            info!("tcp storage server exited");
+        },
+
         _ = shutdown::shutdown_signal() => {},
     }
 
@@ -379,6 +712,10 @@ async fn main() -> Result<(), anyhow::Error> {
     // of scheduler_client, so scheduler_client can be released normally.
     drop(persistent_cache_task);
 
+    // Drop cache task to release scheduler_client. when drop the cache task, it will release the Arc reference
+    // of scheduler_client, so scheduler_client can be released normally.
+    drop(cache_task);
+
     // Drop scheduler_client to release dynconfig. when drop the scheduler_client, it will release the
     // Arc reference of dynconfig, so dynconfig can be released normally.
     drop(scheduler_client);
@@ -18,19 +18,22 @@ use bytesize::ByteSize;
 use clap::Parser;
 use dragonfly_api::common::v2::{Download, Hdfs, ObjectStorage, TaskType};
 use dragonfly_api::dfdaemon::v2::{
-    download_task_response, DownloadTaskRequest, ListTaskEntriesRequest,
+    download_task_response, DownloadTaskRequest, Entry, ListTaskEntriesRequest,
 };
 use dragonfly_api::errordetails::v2::Backend;
 use dragonfly_client::grpc::dfdaemon_download::DfdaemonDownloadClient;
 use dragonfly_client::grpc::health::HealthClient;
 use dragonfly_client::resource::piece::MIN_PIECE_LENGTH;
 use dragonfly_client::tracing::init_tracing;
-use dragonfly_client_backend::{hdfs, object_storage, BackendFactory, DirEntry};
+use dragonfly_client_backend::{
+    hdfs, object_storage, oci, BackendFactory, DirEntry, DEFAULT_LIST_PAGE_SIZE,
+};
 use dragonfly_client_config::VersionValueParser;
 use dragonfly_client_config::{self, dfdaemon, dfget};
 use dragonfly_client_core::error::{ErrorType, OrErr};
 use dragonfly_client_core::{Error, Result};
 use dragonfly_client_util::{fs::fallocate, http::header_vec_to_hashmap};
+use futures::{pin_mut, TryStreamExt};
 use glob::Pattern;
 use indicatif::{MultiProgress, ProgressBar, ProgressState, ProgressStyle};
 use local_ip_address::local_ip;
@@ -80,6 +83,12 @@ Examples:
 
   # Download a file from Tencent Cloud Object Storage Service(COS).
   $ dfget cos://<bucket>/<path> -O /tmp/file.txt --storage-access-key-id=<access_key_id> --storage-access-key-secret=<access_key_secret> --storage-endpoint=<endpoint>
+
+  # Download a directory from the shared file system mounted by the seed peers, such as NFS.
+  $ dfget file:///<path>/ -O /tmp/dir --recursive
+
+  # Download the config and the layers of an image from the OCI registry.
+  $ dfget oci://<registry>/<repository>:<tag>/ -O /tmp/image --recursive --header "Authorization: Basic <credential>"
 "#;
 
 #[derive(Debug, Parser, Clone)]
@@ -257,7 +266,7 @@ struct Args {
     #[arg(
         long,
         default_value_t = 10,
-        help = "Specify the max count of file to download when downloading a directory. If the actual file count is greater than this value, the downloading will be rejected"
+        help = "Specify the max count of file to download when downloading a directory. The files are counted by listing the directory before downloading, if the actual file count is greater than this value, the downloading will be rejected and no file is downloaded"
     )]
     max_files: usize,
 
@@ -268,6 +277,12 @@ struct Args {
     )]
     max_concurrent_requests: usize,
 
+    #[arg(
+        long,
+        help = "Specify the max depth of the entries to download when downloading a directory, 1 means only the files directly in the directory are downloaded. If it is not set, the directory is downloaded recursively"
+    )]
+    max_depth: Option<usize>,
+
     #[arg(
         short = 'l',
         long,
@@ -640,44 +655,29 @@ async fn run(mut args: Args, dfdaemon_download_client: DfdaemonDownloadClient) -
 
 /// Downloads all files in a directory from various storage backends (object storage, HDFS, etc.).
 ///
-/// This function handles directory-based downloads by recursively fetching all entries
-/// in the specified directory. It supports filtering files based on include patterns,
-/// enforces download limits, and performs concurrent downloads with configurable
-/// concurrency control. The function creates the necessary directory structure
-/// locally and downloads files while preserving the remote directory hierarchy.
+/// This function handles directory-based downloads by listing the entries in the specified
+/// directory by pages. The directory is listed twice, the first pass only counts the files,
+/// so the directory with more files than `max_files` is rejected before any file is
+/// downloaded. In the second pass, the entries of each page are downloaded while the rest
+/// pages are listed, and the listing waits for the permits of the concurrent downloads, so
+/// the entries are never collected in memory. It supports filtering files based on include
+/// patterns, and creates the necessary directory structure locally while preserving the
+/// remote directory hierarchy.
 async fn download_dir(args: Args, download_client: DfdaemonDownloadClient) -> Result<()> {
-    // Initialize the object storage config and the hdfs config.
-    let object_storage = Some(ObjectStorage {
-        access_key_id: args.storage_access_key_id.clone(),
-        access_key_secret: args.storage_access_key_secret.clone(),
-        session_token: args.storage_session_token.clone(),
-        region: args.storage_region.clone(),
-        endpoint: args.storage_endpoint.clone(),
-        credential_path: args.storage_credential_path.clone(),
-        predefined_acl: args.storage_predefined_acl.clone(),
-    });
-
-    let hdfs = Some(Hdfs {
-        delegation_token: args.hdfs_delegation_token.clone(),
-    });
-
-    // Get all entries in the directory.
-    let mut entries = get_entries(&args, object_storage, hdfs, download_client.clone()).await?;
-    if let Some(ref include_files) = args.include_files {
-        entries = filter_entries(&args.url, entries, include_files)?;
-    }
-
-    // If the entries is empty, then return directly.
-    if entries.is_empty() {
-        warn!("no entries found in directory {}", args.url);
-        return Ok(());
-    }
+    // Count the files before downloading, so the directory with too many files is rejected
+    // without the partial download.
+    info!("count task entries: {:?}", args.url);
+    let file_count = count_files(&args, &download_client).await?;
+    info!("found {} files in {}", file_count, args.url);
 
-    // If the actual file count is greater than the max_files, then reject the downloading.
-    let count = entries.iter().filter(|entry| !entry.is_dir).count();
-    if count > args.max_files {
-        return Err(Error::MaxDownloadFilesExceeded(count));
-    }
+    // List task entries by pages.
+    info!("list task entries: {:?}", args.url);
+    let pages = download_client.list_task_entries_by_page(
+        list_task_entries_request(&args)?,
+        DEFAULT_LIST_PAGE_SIZE,
+        args.max_depth,
+    );
+    pin_mut!(pages);
 
     // Initialize the multi progress bar.
     let multi_progress_bar = MultiProgress::new();
@@ -686,43 +686,66 @@ async fn download_dir(args: Args, download_client: DfdaemonDownloadClient) -> Re
     let mut join_set = JoinSet::new();
     let semaphore = Arc::new(Semaphore::new(args.max_concurrent_requests));
 
-    // Iterate all entries in the directory.
-    for entry in entries {
-        let entry_url: Url = entry.url.parse().or_err(ErrorType::ParseError)?;
-
-        // If entry is a directory, then create the output directory. If entry is a file,
-        // then download the file to the output directory.
-        if entry.is_dir {
-            let output_dir = make_output_by_entry(args.url.clone(), &args.output, entry)?;
-            fs::create_dir_all(&output_dir).await.inspect_err(|err| {
-                error!("create {} failed: {}", output_dir.to_string_lossy(), err);
-            })?;
-        } else {
-            let mut entry_args = args.clone();
-            entry_args.output = make_output_by_entry(args.url.clone(), &args.output, entry)?;
-            entry_args.url = entry_url;
-
-            let progress_bar = multi_progress_bar.add(ProgressBar::new(0));
-            async fn download_entry(
-                args: Args,
-                progress_bar: ProgressBar,
-                download_client: DfdaemonDownloadClient,
-                semaphore: Arc<Semaphore>,
-            ) -> Result<()> {
-                // Limit the concurrent download tasks.
-                let _permit = semaphore.acquire().await.unwrap();
-                download(args, progress_bar, download_client).await
+    // Iterate all entries in the directory page by page.
+    let mut count = 0;
+    let mut is_empty = true;
+    while let Some(page) = pages.try_next().await.inspect_err(|err| {
+        error!("list task entries failed: {}", err);
+    })? {
+        let entries = page_entries(&args, page)?;
+
+        // The files may be added to the directory after they are counted, so the file count
+        // is checked again to stop the downloading.
+        count += entries.iter().filter(|entry| !entry.is_dir).count();
+        if count > args.max_files {
+            join_set.abort_all();
+            return Err(Error::MaxDownloadFilesExceeded(count));
+        }
+
+        for entry in entries {
+            is_empty = false;
+            let entry_url: Url = entry.url.parse().or_err(ErrorType::ParseError)?;
+
+            // If entry is a directory, then create the output directory. If entry is a file,
+            // then download the file to the output directory.
+            if entry.is_dir {
+                let output_dir = make_output_by_entry(args.url.clone(), &args.output, entry)?;
+                fs::create_dir_all(&output_dir).await.inspect_err(|err| {
+                    error!("create {} failed: {}", output_dir.to_string_lossy(), err);
+                })?;
+            } else {
+                let mut entry_args = args.clone();
+                entry_args.output = make_output_by_entry(args.url.clone(), &args.output, entry)?;
+                entry_args.url = entry_url;
+
+                // Limit the concurrent download tasks, the listing is paused until a download
+                // task is finished.
+                let permit = semaphore.clone().acquire_owned().await.unwrap();
+                let progress_bar = multi_progress_bar.add(ProgressBar::new(0));
+                let download_client = download_client.clone();
+                join_set.spawn(async move {
+                    let _permit = permit;
+                    download(entry_args, progress_bar, download_client).await
+                });
             }
 
-            join_set.spawn(download_entry(
-                entry_args,
-                progress_bar,
-                download_client.clone(),
-                semaphore.clone(),
-            ));
+            // Stop the downloading as soon as any download task failed.
+            while let Some(message) = join_set.try_join_next() {
+                if let Err(err) = message.or_err(ErrorType::AsyncRuntimeError)? {
+                    error!("download entry failed: {}", err);
+                    join_set.abort_all();
+                    return Err(err);
+                }
+            }
         }
     }
 
+    // If the entries is empty, then return directly.
+    if is_empty {
+        warn!("no entries found in directory {}", args.url);
+        return Ok(());
+    }
+
     // Wait for all download tasks finished.
     while let Some(message) = join_set
         .join_next()
@@ -743,6 +766,63 @@ async fn download_dir(args: Args, download_client: DfdaemonDownloadClient) -> Re
     Ok(())
 }
 
+/// Counts the files in a directory by listing it page by page without downloading them.
+///
+/// The files are filtered by the include patterns as the downloading, and the counting stops
+/// as soon as the count is greater than `max_files`.
+async fn count_files(args: &Args, download_client: &DfdaemonDownloadClient) -> Result<usize> {
+    let pages = download_client.list_task_entries_by_page(
+        list_task_entries_request(args)?,
+        DEFAULT_LIST_PAGE_SIZE,
+        args.max_depth,
+    );
+    pin_mut!(pages);
+
+    let mut count = 0;
+    while let Some(page) = pages.try_next().await.inspect_err(|err| {
+        error!("list task entries failed: {}", err);
+    })? {
+        count += page_entries(args, page)?
+            .iter()
+            .filter(|entry| !entry.is_dir)
+            .count();
+        if count > args.max_files {
+            return Err(Error::MaxDownloadFilesExceeded(count));
+        }
+    }
+
+    Ok(count)
+}
+
+/// Builds the request to list the entries of the directory.
+fn list_task_entries_request(args: &Args) -> Result<ListTaskEntriesRequest> {
+    // Initialize the object storage config and the hdfs config.
+    let object_storage = Some(ObjectStorage {
+        access_key_id: args.storage_access_key_id.clone(),
+        access_key_secret: args.storage_access_key_secret.clone(),
+        session_token: args.storage_session_token.clone(),
+        region: args.storage_region.clone(),
+        endpoint: args.storage_endpoint.clone(),
+        credential_path: args.storage_credential_path.clone(),
+        predefined_acl: args.storage_predefined_acl.clone(),
+    });
+
+    let hdfs = Some(Hdfs {
+        delegation_token: args.hdfs_delegation_token.clone(),
+    });
+
+    Ok(ListTaskEntriesRequest {
+        task_id: Uuid::new_v4().to_string(),
+        url: args.url.to_string(),
+        request_header: header_vec_to_hashmap(args.header.clone().unwrap_or_default())?,
+        timeout: None,
+        certificate_chain: Vec::new(),
+        object_storage,
+        hdfs,
+        remote_ip: Some(local_ip().unwrap().to_string()),
+    })
+}
+
 /// Downloads a single file from various storage backends using the dfdaemon service.
 ///
 /// This function handles single file downloads by communicating with a dfdaemon client.
@@ -777,6 +857,16 @@ async fn download(
         _ => None,
     };
 
+    // The blob of the OCI registry is addressed by its digest, so the digest is used to
+    // calculate the task id and verify the content, the same blob referenced by the
+    // different images is downloaded once.
+    let mut digest = args.digest;
+    let mut content_for_calculating_task_id = args.content_for_calculating_task_id;
+    if let Some(blob_digest) = oci::blob_digest(&args.url) {
+        digest.get_or_insert_with(|| blob_digest.clone());
+        content_for_calculating_task_id.get_or_insert(blob_digest);
+    }
+
     // If the `filtered_query_params` is not provided, then use the default value.
     let filtered_query_params = args
         .filtered_query_params
@@ -796,7 +886,7 @@ async fn download(
         .download_task(DownloadTaskRequest {
             download: Some(Download {
                 url: args.url.to_string(),
-                digest: args.digest,
+                digest,
                 // NOTE: Dfget does not support range download.
                 range: None,
                 r#type: TaskType::Standard as i32,
@@ -820,7 +910,7 @@ async fn download(
                 object_storage,
                 hdfs,
                 force_hard_link: args.force_hard_link,
-                content_for_calculating_task_id: args.content_for_calculating_task_id,
+                content_for_calculating_task_id,
                 remote_ip: Some(local_ip().unwrap().to_string()),
             }),
         })
@@ -933,46 +1023,24 @@ async fn download(
     Ok(())
 }
 
-/// Retrieves all directory entries from a remote storage location.
+/// Converts a page of the listed task entries into the local `DirEntry` format.
 ///
-/// This function communicates with the dfdaemon service to list all entries
-/// (files and subdirectories) in the specified directory URL. It supports
-/// various storage backends including object storage and HDFS by passing
-/// the appropriate credentials and configuration. The function converts
-/// the gRPC response into a local `DirEntry` format for further processing.
-async fn get_entries(
-    args: &Args,
-    object_storage: Option<ObjectStorage>,
-    hdfs: Option<Hdfs>,
-    download_client: DfdaemonDownloadClient,
-) -> Result<Vec<DirEntry>> {
-    info!("list task entries: {:?}", args.url);
-    // List task entries.
-    let response = download_client
-        .list_task_entries(ListTaskEntriesRequest {
-            task_id: Uuid::new_v4().to_string(),
-            url: args.url.to_string(),
-            request_header: header_vec_to_hashmap(args.header.clone().unwrap_or_default())?,
-            timeout: None,
-            certificate_chain: Vec::new(),
-            object_storage,
-            hdfs,
-            remote_ip: Some(local_ip().unwrap().to_string()),
-        })
-        .await
-        .inspect_err(|err| {
-            error!("list task entries failed: {}", err);
-        })?;
-
-    Ok(response
-        .entries
+/// The entries are filtered by the include patterns if they are specified, and the
+/// URLs of the entries are validated against the root URL.
+fn page_entries(args: &Args, page: Vec<Entry>) -> Result<Vec<DirEntry>> {
+    let entries: Vec<DirEntry> = page
         .into_iter()
         .map(|entry| DirEntry {
             url: entry.url,
             content_length: entry.content_length as usize,
             is_dir: entry.is_dir,
         })
-        .collect())
+        .collect();
+
+    match args.include_files {
+        Some(ref include_files) => filter_entries(&args.url, entries, include_files),
+        None => Ok(entries),
+    }
 }
 
 /// Filters directory entries based on include patterns and validates their URLs.
@@ -1743,4 +1811,53 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn should_convert_page_entries() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let new_entry = |url: &str, is_dir: bool| Entry {
+            url: url.to_string(),
+            content_length: 10,
+            is_dir,
+        };
+        let page = vec![
+            new_entry("s3://bucket/root/dir/", true),
+            new_entry("s3://bucket/root/dir/file.txt", false),
+            new_entry("s3://bucket/root/dir/file.json", false),
+        ];
+
+        let args = Args::parse_from(vec![
+            "dfget",
+            "s3://bucket/root/",
+            "--output",
+            tempdir.path().as_os_str().to_str().unwrap(),
+        ]);
+        let entries = page_entries(&args, page.clone()).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].url, "s3://bucket/root/dir/file.txt");
+        assert_eq!(entries[1].content_length, 10);
+        assert!(entries[0].is_dir);
+
+        let args = Args::parse_from(vec![
+            "dfget",
+            "s3://bucket/root/",
+            "--include-files",
+            "dir/*.txt",
+            "--output",
+            tempdir.path().as_os_str().to_str().unwrap(),
+        ]);
+        let mut urls: Vec<String> = page_entries(&args, page)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.url)
+            .collect();
+        urls.sort();
+        assert_eq!(
+            urls,
+            vec![
+                "s3://bucket/root/dir/".to_string(),
+                "s3://bucket/root/dir/file.txt".to_string()
+            ]
+        );
+    }
 }
@@ -0,0 +1,200 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use chrono::NaiveDateTime;
+use dragonfly_client_config::dfdaemon::EvictionPolicyType;
+use dragonfly_client_storage::metadata;
+use std::cmp::Ordering;
+
+/// Evictable is the task which can be evicted by the garbage collector.
+pub trait Evictable {
+    /// size returns the space occupied by the task.
+    fn size(&self) -> u64;
+
+    /// uploaded_count returns the count of the task has been uploaded by other peers.
+    fn uploaded_count(&self) -> u64;
+
+    /// updated_at returns the time when the task is downloaded or uploaded lastly.
+    fn updated_at(&self) -> NaiveDateTime;
+}
+
+/// Task implements the Evictable trait.
+impl Evictable for metadata::Task {
+    /// size returns the content length of the task, it is 0 if the task has no content length.
+    fn size(&self) -> u64 {
+        self.content_length().unwrap_or_default()
+    }
+
+    /// uploaded_count returns the count of the task has been uploaded by other peers.
+    fn uploaded_count(&self) -> u64 {
+        self.uploaded_count
+    }
+
+    /// updated_at returns the time when the task is downloaded or uploaded lastly.
+    fn updated_at(&self) -> NaiveDateTime {
+        self.updated_at
+    }
+}
+
+/// PersistentCacheTask implements the Evictable trait.
+impl Evictable for metadata::PersistentCacheTask {
+    /// size returns the content length of the persistent cache task.
+    fn size(&self) -> u64 {
+        self.content_length()
+    }
+
+    /// uploaded_count returns the count of the persistent cache task has been uploaded by
+    /// other peers.
+    fn uploaded_count(&self) -> u64 {
+        self.uploaded_count
+    }
+
+    /// updated_at returns the time when the persistent cache task is downloaded or uploaded
+    /// lastly.
+    fn updated_at(&self) -> NaiveDateTime {
+        self.updated_at
+    }
+}
+
+/// EvictionPolicy decides the order of the tasks evicted by disk usage.
+pub trait EvictionPolicy: Send + Sync {
+    /// compare compares two tasks at the given time, the task ordered first is evicted first.
+    fn compare(&self, a: &dyn Evictable, b: &dyn Evictable, now: NaiveDateTime) -> Ordering;
+}
+
+/// new_eviction_policy creates the eviction policy by the type.
+pub fn new_eviction_policy(typ: EvictionPolicyType) -> Box<dyn EvictionPolicy> {
+    match typ {
+        EvictionPolicyType::Lru => Box::new(Lru),
+        EvictionPolicyType::Lfu => Box::new(Lfu),
+        EvictionPolicyType::Size => Box::new(SizeWeighted),
+    }
+}
+
+/// Lru evicts the least recently used task first.
+pub struct Lru;
+
+/// Lru implements the EvictionPolicy trait.
+impl EvictionPolicy for Lru {
+    /// compare orders the tasks by the updated time, the oldest task is evicted first.
+    fn compare(&self, a: &dyn Evictable, b: &dyn Evictable, _now: NaiveDateTime) -> Ordering {
+        a.updated_at().cmp(&b.updated_at())
+    }
+}
+
+/// Lfu evicts the task uploaded to the other peers least frequently first.
+pub struct Lfu;
+
+/// Lfu implements the EvictionPolicy trait.
+impl EvictionPolicy for Lfu {
+    /// compare orders the tasks by the uploaded count, if the uploaded counts are equal,
+    /// the least recently used task is evicted first.
+    fn compare(&self, a: &dyn Evictable, b: &dyn Evictable, now: NaiveDateTime) -> Ordering {
+        a.uploaded_count()
+            .cmp(&b.uploaded_count())
+            .then_with(|| Lru.compare(a, b, now))
+    }
+}
+
+/// SizeWeighted evicts the task with the largest size weighted by the idle time first, so the
+/// large and cold tasks are evicted before the small or hot tasks.
+pub struct SizeWeighted;
+
+/// SizeWeighted implements the size weighted eviction policy.
+impl SizeWeighted {
+    /// score returns the product of the size and the idle seconds of the task.
+    fn score(task: &dyn Evictable, now: NaiveDateTime) -> u128 {
+        let idle_seconds = (now - task.updated_at()).num_seconds().max(1) as u128;
+        task.size() as u128 * idle_seconds
+    }
+}
+
+/// SizeWeighted implements the EvictionPolicy trait.
+impl EvictionPolicy for SizeWeighted {
+    /// compare orders the tasks by the score in descending order, if the scores are equal,
+    /// the least recently used task is evicted first.
+    fn compare(&self, a: &dyn Evictable, b: &dyn Evictable, now: NaiveDateTime) -> Ordering {
+        Self::score(b, now)
+            .cmp(&Self::score(a, now))
+            .then_with(|| Lru.compare(a, b, now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn new_task(id: &str, content_length: u64, uploaded_count: u64, idle: i64) -> metadata::Task {
+        metadata::Task {
+            id: id.to_string(),
+            content_length: Some(content_length),
+            uploaded_count,
+            updated_at: Utc::now().naive_utc() - Duration::seconds(idle),
+            ..Default::default()
+        }
+    }
+
+    fn sort(typ: EvictionPolicyType, mut tasks: Vec<metadata::Task>) -> Vec<String> {
+        let policy = new_eviction_policy(typ);
+        let now = Utc::now().naive_utc();
+        tasks.sort_by(|a, b| policy.compare(a, b, now));
+        tasks.into_iter().map(|task| task.id).collect()
+    }
+
+    #[test]
+    fn should_evict_least_recently_used_task_first() {
+        let tasks = vec![
+            new_task("hot", 1024, 100, 10),
+            new_task("cold", 1024, 100, 1000),
+            new_task("warm", 1024, 0, 100),
+        ];
+
+        assert_eq!(
+            sort(EvictionPolicyType::Lru, tasks),
+            ["cold", "warm", "hot"]
+        );
+    }
+
+    #[test]
+    fn should_evict_least_frequently_uploaded_task_first() {
+        let tasks = vec![
+            new_task("popular", 1024, 100, 1000),
+            new_task("unused-recent", 1024, 0, 10),
+            new_task("unused-old", 1024, 0, 100),
+            new_task("rare", 1024, 1, 10),
+        ];
+
+        assert_eq!(
+            sort(EvictionPolicyType::Lfu, tasks),
+            ["unused-old", "unused-recent", "rare", "popular"]
+        );
+    }
+
+    #[test]
+    fn should_evict_large_and_cold_task_first() {
+        let tasks = vec![
+            new_task("small-cold", 1024, 0, 1000),
+            new_task("large-hot", 1024 * 1024, 0, 1),
+            new_task("large-cold", 1024 * 1024, 0, 1000),
+        ];
+
+        assert_eq!(
+            sort(EvictionPolicyType::Size, tasks),
+            ["large-cold", "large-hot", "small-cold"]
+        );
+    }
+}
@@ -16,20 +16,29 @@
 
 use crate::grpc::scheduler::SchedulerClient;
 use crate::shutdown;
-use chrono::Utc;
+use chrono::{NaiveDateTime, Utc};
 use dragonfly_api::scheduler::v2::DeleteTaskRequest;
 use dragonfly_client_config::dfdaemon::Config;
 use dragonfly_client_core::Result;
 use dragonfly_client_storage::{metadata, Storage};
+use eviction::EvictionPolicy;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc;
 use tracing::{error, info, instrument};
 
+pub mod eviction;
+pub mod quota;
+
 // DOWNLOAD_TASK_TIMEOUT is the timeout of downloading the task. If the task download timeout, the
 // task will be garbage collected by disk usage, default 2 hours.
 pub const DOWNLOAD_TASK_TIMEOUT: Duration = Duration::from_secs(2 * 60 * 60);
 
+// UPLOAD_TASK_TIMEOUT is the timeout of uploading the task. If the uploading count of the task is
+// not updated within the timeout, e.g. dfdaemon exits while uploading, the task is no longer
+// considered as uploading by the garbage collector, default 2 hours.
+pub const UPLOAD_TASK_TIMEOUT: Duration = Duration::from_secs(2 * 60 * 60);
+
 /// GC is the garbage collector of dfdaemon.
 pub struct GC {
     /// config is the configuration of the dfdaemon.
@@ -44,6 +53,12 @@ pub struct GC {
     /// scheduler_client is the grpc client of the scheduler.
     scheduler_client: Arc<SchedulerClient>,
 
+    /// eviction_policy decides the order of the tasks evicted by disk usage.
+    eviction_policy: Box<dyn EvictionPolicy>,
+
+    /// quota enforces the storage quotas of the applications.
+    quota: quota::Quota,
+
     /// shutdown is used to shutdown the garbage collector.
     shutdown: shutdown::Shutdown,
 
@@ -62,6 +77,13 @@ impl GC {
         shutdown_complete_tx: mpsc::UnboundedSender<()>,
     ) -> Self {
         GC {
+            eviction_policy: eviction::new_eviction_policy(config.gc.policy.eviction),
+            quota: quota::Quota::new(
+                config.clone(),
+                host_id.clone(),
+                storage.clone(),
+                scheduler_client.clone(),
+            ),
             config,
             host_id,
             storage,
@@ -96,10 +118,31 @@ impl GC {
                         info!("failed to evict task by ttl: {}", err);
                     }
 
+                    // Evict the task by quota, the tasks of the application exceeding the quota
+                    // are evicted before evicting by disk usage.
+                    if let Err(err) = self.quota.enforce().await {
+                        info!("failed to evict task by quota: {}", err);
+                    }
+
                     // Evict the cache by disk usage.
                     if let Err(err) = self.evict_task_by_disk_usage().await {
                         info!("failed to evict task by disk usage: {}", err);
                     }
+
+                    // Promote the hot tasks to the fastest tier after evicting, so the
+                    // space of the fastest tier is used by the remaining tasks.
+                    if self.config.storage.promotion.enable {
+                        if let Err(err) = self.promote_hot_tasks().await {
+                            info!("failed to promote hot tasks: {}", err);
+                        }
+                    }
+
+                    // Reload the encryption keys to pick up the rotated key.
+                    if self.config.storage.encryption.enable {
+                        if let Err(err) = self.storage.reload_encryption_keys().await {
+                            error!("failed to reload encryption keys: {}", err);
+                        }
+                    }
                 }
                 _ = shutdown.recv() => {
                     // Shutdown the garbage collector.
@@ -115,6 +158,17 @@ impl GC {
     async fn evict_task_by_ttl(&self) -> Result<()> {
         info!("start to evict by task ttl");
         for task in self.storage.get_tasks()? {
+            // If the task is pinned, skip it.
+            if is_task_pinned(&self.config, &task) {
+                continue;
+            }
+
+            // If the task is uploading, skip it.
+            if is_task_uploading(task.is_uploading(), task.updated_at) {
+                info!("task {} is uploading, skip it", task.id);
+                continue;
+            }
+
             // If the task is expired and not uploading, evict the task.
             if task.is_expired(self.config.gc.policy.task_ttl) {
                 self.storage.delete_task(&task.id).await;
@@ -129,6 +183,29 @@ impl GC {
         Ok(())
     }
 
+    /// promote_hot_tasks promotes the finished tasks which have been uploaded more than the
+    /// promotion count to the fastest tier of the storage directories.
+    #[instrument(skip_all)]
+    async fn promote_hot_tasks(&self) -> Result<()> {
+        info!("start to promote hot tasks");
+        for task in self.storage.get_tasks()? {
+            if !task.is_finished()
+                || task.uploaded_count < self.config.storage.promotion.uploaded_count
+            {
+                continue;
+            }
+
+            match self.storage.promote_task(&task.id).await {
+                Ok(true) => info!("promote task {}", task.id),
+                Ok(false) => {}
+                Err(err) => error!("failed to promote task {}: {}", task.id, err),
+            }
+        }
+
+        info!("promote hot tasks done");
+        Ok(())
+    }
+
     /// evict_task_by_disk_usage evicts the task by disk usage.
     #[instrument(skip_all)]
     async fn evict_task_by_disk_usage(&self) -> Result<()> {
@@ -163,7 +240,8 @@ impl GC {
     #[instrument(skip_all)]
     async fn evict_task_space(&self, need_evict_space: u64) -> Result<()> {
         let mut tasks = self.storage.get_tasks()?;
-        tasks.sort_by(|a, b| a.updated_at.cmp(&b.updated_at));
+        let now = Utc::now().naive_utc();
+        tasks.sort_by(|a, b| self.eviction_policy.compare(a, b, now));
 
         let mut evicted_space = 0;
         for task in tasks {
@@ -172,6 +250,17 @@ impl GC {
                 break;
             }
 
+            // If the task is pinned, skip it.
+            if is_task_pinned(&self.config, &task) {
+                continue;
+            }
+
+            // If the task is uploading, skip it.
+            if is_task_uploading(task.is_uploading(), task.updated_at) {
+                info!("task {} is uploading, skip it", task.id);
+                continue;
+            }
+
             // If the task has downloaded finished, task has the content length, evicted space is the
             // content length. If the task has started and did not download the data, and content
             // length is 0, evicted space is 0.
@@ -192,11 +281,7 @@ impl GC {
 
             //  If the task is started and not finished, and the task download is not timeout,
             //  skip it.
-            if task.is_started()
-                && !task.is_finished()
-                && !task.is_failed()
-                && (task.created_at + DOWNLOAD_TASK_TIMEOUT > Utc::now().naive_utc())
-            {
+            if is_task_downloading(&task) {
                 info!("task {} is started and not finished, skip it", task.id);
                 continue;
             }
@@ -235,6 +320,12 @@ impl GC {
     async fn evict_persistent_cache_task_by_ttl(&self) -> Result<()> {
         info!("start to evict by persistent cache task ttl");
         for task in self.storage.get_persistent_cache_tasks()? {
+            // If the persistent cache task is uploading, skip it.
+            if is_task_uploading(task.is_uploading(), task.updated_at) {
+                info!("persistent cache task {} is uploading, skip it", task.id);
+                continue;
+            }
+
             // If the persistent cache task is expired and not uploading, evict the persistent cache task.
             if task.is_expired() {
                 self.storage.delete_persistent_cache_task(&task.id).await;
@@ -283,7 +374,8 @@ impl GC {
     #[instrument(skip_all)]
     async fn evict_persistent_cache_task_space(&self, need_evict_space: u64) -> Result<()> {
         let mut tasks = self.storage.get_persistent_cache_tasks()?;
-        tasks.sort_by(|a, b| a.updated_at.cmp(&b.updated_at));
+        let now = Utc::now().naive_utc();
+        tasks.sort_by(|a, b| self.eviction_policy.compare(a, b, now));
 
         let mut evicted_space = 0;
         for task in tasks {
@@ -297,6 +389,12 @@ impl GC {
                 continue;
             }
 
+            // If the persistent cache task is uploading, skip it.
+            if is_task_uploading(task.is_uploading(), task.updated_at) {
+                info!("persistent cache task {} is uploading, skip it", task.id);
+                continue;
+            }
+
             //  If the task is started and not finished, and the task download is not timeout,
             //  skip it.
             if task.is_started()
@@ -327,3 +425,27 @@ impl GC {
         Ok(())
     }
 }
+
+/// is_task_pinned returns whether the task is pinned by the application or tag.
+fn is_task_pinned(config: &Config, task: &metadata::Task) -> bool {
+    config
+        .gc
+        .policy
+        .pinned
+        .is_pinned(task.application.as_deref(), task.tag.as_deref())
+}
+
+/// is_task_uploading returns whether the task is uploading to the other peers and the upload
+/// is not timeout.
+fn is_task_uploading(is_uploading: bool, updated_at: NaiveDateTime) -> bool {
+    is_uploading && updated_at + UPLOAD_TASK_TIMEOUT > Utc::now().naive_utc()
+}
+
+/// is_task_downloading returns whether the task is started and not finished, and the download is
+/// not timeout.
+fn is_task_downloading(task: &metadata::Task) -> bool {
+    task.is_started()
+        && !task.is_finished()
+        && !task.is_failed()
+        && (task.created_at + DOWNLOAD_TASK_TIMEOUT > Utc::now().naive_utc())
+}
@@ -0,0 +1,343 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::eviction::{self, EvictionPolicy};
+use super::{is_task_downloading, is_task_pinned, is_task_uploading};
+use crate::grpc::scheduler::SchedulerClient;
+use chrono::Utc;
+use dragonfly_api::scheduler::v2::DeleteTaskRequest;
+use dragonfly_client_config::dfdaemon::Config;
+use dragonfly_client_core::{Error, Result};
+use dragonfly_client_storage::{metadata, Storage};
+use std::sync::Arc;
+use tracing::{error, info, instrument};
+
+/// Quota enforces the storage quotas of the applications, the tasks of the application which
+/// exceeds the quota are evicted before the tasks of the other applications. The quota is a soft
+/// limit, the concurrent downloads of the same application may exceed it until the next gc.
+pub struct Quota {
+    /// config is the configuration of the dfdaemon.
+    config: Arc<Config>,
+
+    /// host_id is the id of the host.
+    host_id: String,
+
+    /// storage is the local storage.
+    storage: Arc<Storage>,
+
+    /// scheduler_client is the grpc client of the scheduler.
+    scheduler_client: Arc<SchedulerClient>,
+
+    /// eviction_policy decides the order of the tasks evicted within the application.
+    eviction_policy: Box<dyn EvictionPolicy>,
+}
+
+/// Quota implements the storage quotas of the applications.
+impl Quota {
+    /// new creates a new Quota.
+    pub fn new(
+        config: Arc<Config>,
+        host_id: String,
+        storage: Arc<Storage>,
+        scheduler_client: Arc<SchedulerClient>,
+    ) -> Self {
+        Self {
+            eviction_policy: eviction::new_eviction_policy(config.gc.policy.eviction),
+            config,
+            host_id,
+            storage,
+            scheduler_client,
+        }
+    }
+
+    /// reserve reserves the space of the task in the quota of the application. If the quota is
+    /// exceeded, the tasks of the application are evicted, and the error is returned if
+    /// the space can not be released.
+    #[instrument(skip_all)]
+    pub async fn reserve(
+        &self,
+        task_id: &str,
+        application: Option<&str>,
+        content_length: u64,
+    ) -> Result<()> {
+        let Some(application) = application else {
+            return Ok(());
+        };
+
+        let Some(quota) = self.config.gc.policy.quota(application) else {
+            return Ok(());
+        };
+
+        // The task itself is not counted, because its content may be left by the failed download.
+        let limit = quota.limit.as_u64();
+        let tasks: Vec<metadata::Task> = self
+            .tasks(application)?
+            .into_iter()
+            .filter(|task| task.id != task_id)
+            .collect();
+        let usage = Self::usage(&tasks);
+        let need_evict_space = Self::need_evict_space(application, usage, content_length, limit)?;
+        if need_evict_space == 0 {
+            return Ok(());
+        }
+
+        info!(
+            "application {} usage {} exceeds the quota {}, need to evict {}",
+            application, usage, limit, need_evict_space
+        );
+
+        let evicted_space = self.evict(tasks, need_evict_space).await;
+        if evicted_space < need_evict_space {
+            return Err(Error::NoSpace(format!(
+                "not enough quota of application {}: usage={}, limit={}, content_length={}",
+                application,
+                usage - evicted_space,
+                limit,
+                content_length
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// enforce evicts the tasks of the applications which exceed the quotas.
+    #[instrument(skip_all)]
+    pub async fn enforce(&self) -> Result<()> {
+        for quota in self.config.gc.policy.quotas.iter() {
+            let tasks = self.tasks(&quota.application)?;
+            let usage = Self::usage(&tasks);
+            let limit = quota.limit.as_u64();
+            if usage <= limit {
+                continue;
+            }
+
+            info!(
+                "start to evict task by quota, application {} usage {} exceeds the quota {}",
+                quota.application, usage, limit
+            );
+
+            let evicted_space = self.evict(tasks, usage - limit).await;
+            info!(
+                "evict task by quota done, application {} evicted {}",
+                quota.application, evicted_space
+            );
+        }
+
+        Ok(())
+    }
+
+    /// tasks returns the tasks downloaded by the application.
+    fn tasks(&self, application: &str) -> Result<Vec<metadata::Task>> {
+        Ok(self
+            .storage
+            .get_tasks()?
+            .into_iter()
+            .filter(|task| task.application.as_deref() == Some(application))
+            .collect())
+    }
+
+    /// usage returns the total content length of the tasks.
+    fn usage(tasks: &[metadata::Task]) -> u64 {
+        tasks
+            .iter()
+            .map(|task| task.content_length().unwrap_or_default())
+            .sum()
+    }
+
+    /// need_evict_space returns the space needs to be evicted from the tasks of the application
+    /// to reserve the content length, and the error is returned if the content length exceeds
+    /// the quota.
+    fn need_evict_space(
+        application: &str,
+        usage: u64,
+        content_length: u64,
+        limit: u64,
+    ) -> Result<u64> {
+        if content_length > limit {
+            return Err(Error::NoSpace(format!(
+                "content length {} exceeds the quota {} of application {}",
+                content_length, limit, application
+            )));
+        }
+
+        Ok((usage + content_length).saturating_sub(limit))
+    }
+
+    /// evictable_tasks returns the tasks evicted in the order of the eviction policy until the
+    /// need evict space is released, the pinned, uploading and downloading tasks are skipped.
+    fn evictable_tasks(
+        config: &Config,
+        eviction_policy: &dyn EvictionPolicy,
+        mut tasks: Vec<metadata::Task>,
+        need_evict_space: u64,
+    ) -> Vec<metadata::Task> {
+        let now = Utc::now().naive_utc();
+        tasks.sort_by(|a, b| eviction_policy.compare(a, b, now));
+
+        let mut evicted_space = 0;
+        let mut evictable_tasks = Vec::new();
+        for task in tasks {
+            // Evict enough space.
+            if evicted_space >= need_evict_space {
+                break;
+            }
+
+            // If the task is pinned, uploading or downloading, skip it.
+            if is_task_pinned(config, &task)
+                || is_task_uploading(task.is_uploading(), task.updated_at)
+                || is_task_downloading(&task)
+            {
+                continue;
+            }
+
+            evicted_space += task.content_length().unwrap_or_default();
+            evictable_tasks.push(task);
+        }
+
+        evictable_tasks
+    }
+
+    /// evict evicts the tasks by the eviction policy until the need evict space is released,
+    /// and returns the evicted space.
+    async fn evict(&self, tasks: Vec<metadata::Task>, need_evict_space: u64) -> u64 {
+        let mut evicted_space = 0;
+        for task in Self::evictable_tasks(
+            &self.config,
+            self.eviction_policy.as_ref(),
+            tasks,
+            need_evict_space,
+        ) {
+            // Evict the task.
+            self.storage.delete_task(&task.id).await;
+
+            // Update the evicted space.
+            let task_space = task.content_length().unwrap_or_default();
+            evicted_space += task_space;
+            info!("evict task {} size {} by quota", task.id, task_space);
+
+            self.scheduler_client
+                .delete_task(DeleteTaskRequest {
+                    host_id: self.host_id.clone(),
+                    task_id: task.id.clone(),
+                })
+                .await
+                .unwrap_or_else(|err| {
+                    error!("failed to delete peer {}: {}", task.id, err);
+                });
+        }
+
+        evicted_space
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use dragonfly_client_config::dfdaemon::EvictionPolicyType;
+
+    fn new_task(id: &str, content_length: u64, idle: i64) -> metadata::Task {
+        let updated_at = Utc::now().naive_utc() - Duration::seconds(idle);
+        metadata::Task {
+            id: id.to_string(),
+            content_length: Some(content_length),
+            application: Some("app".to_string()),
+            updated_at,
+            created_at: updated_at,
+            finished_at: Some(updated_at),
+            ..Default::default()
+        }
+    }
+
+    fn evictable_task_ids(
+        config: &Config,
+        tasks: Vec<metadata::Task>,
+        need_evict_space: u64,
+    ) -> Vec<String> {
+        let eviction_policy = eviction::new_eviction_policy(EvictionPolicyType::Lru);
+        Quota::evictable_tasks(config, eviction_policy.as_ref(), tasks, need_evict_space)
+            .into_iter()
+            .map(|task| task.id)
+            .collect()
+    }
+
+    #[test]
+    fn should_calculate_usage() {
+        let mut unknown = new_task("unknown", 0, 10);
+        unknown.content_length = None;
+        let tasks = vec![
+            new_task("foo", 1024, 10),
+            new_task("bar", 2048, 10),
+            unknown,
+        ];
+        assert_eq!(Quota::usage(&tasks), 3072);
+        assert_eq!(Quota::usage(&[]), 0);
+    }
+
+    #[test]
+    fn should_calculate_need_evict_space() {
+        assert_eq!(Quota::need_evict_space("app", 0, 1024, 4096).unwrap(), 0);
+        assert_eq!(Quota::need_evict_space("app", 3072, 1024, 4096).unwrap(), 0);
+        assert_eq!(
+            Quota::need_evict_space("app", 3072, 2048, 4096).unwrap(),
+            1024
+        );
+        assert!(matches!(
+            Quota::need_evict_space("app", 0, 8192, 4096),
+            Err(Error::NoSpace(_))
+        ));
+    }
+
+    #[test]
+    fn should_evict_tasks_by_eviction_order_until_enough_space() {
+        let config = Config::default();
+        let tasks = vec![
+            new_task("hot", 1024, 10),
+            new_task("cold", 1024, 1000),
+            new_task("warm", 1024, 100),
+        ];
+
+        assert_eq!(evictable_task_ids(&config, tasks.clone(), 1024), ["cold"]);
+        assert_eq!(
+            evictable_task_ids(&config, tasks.clone(), 1025),
+            ["cold", "warm"]
+        );
+        assert_eq!(
+            evictable_task_ids(&config, tasks, 8192),
+            ["cold", "warm", "hot"]
+        );
+    }
+
+    #[test]
+    fn should_skip_pinned_uploading_and_downloading_tasks() {
+        let mut config = Config::default();
+        config.gc.policy.pinned.tags = vec!["pinned".to_string()];
+
+        let mut pinned = new_task("pinned", 1024, 4000);
+        pinned.tag = Some("pinned".to_string());
+
+        let mut uploading = new_task("uploading", 1024, 3000);
+        uploading.uploading_count = 1;
+        uploading.updated_at = Utc::now().naive_utc();
+
+        let mut downloading = new_task("downloading", 1024, 2000);
+        downloading.finished_at = None;
+        downloading.created_at = Utc::now().naive_utc();
+
+        let tasks = vec![pinned, uploading, downloading, new_task("cold", 1024, 1000)];
+        assert_eq!(evictable_task_ids(&config, tasks, 4096), ["cold"]);
+    }
+}
@@ -0,0 +1,231 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use dragonfly_client_config::dfdaemon::UploadAuth;
+use std::net::IpAddr;
+use tonic::{metadata::MetadataValue, service::Interceptor, Request, Status};
+use tracing::warn;
+
+use super::interceptor::InjectTracingInterceptor;
+
+/// AUTHORIZATION_METADATA_KEY is the metadata key of the token.
+pub const AUTHORIZATION_METADATA_KEY: &str = "authorization";
+
+/// BEARER_PREFIX is the prefix of the token in the authorization metadata.
+pub const BEARER_PREFIX: &str = "Bearer ";
+
+/// Access is the access level required by the request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Read is the access for downloading and syncing the tasks and pieces.
+    Read,
+
+    /// Write is the access for deleting and updating the tasks.
+    Write,
+}
+
+/// authorize checks whether the request is allowed to access the upload server.
+pub fn authorize<T>(auth: &UploadAuth, request: &Request<T>, access: Access) -> Result<(), Status> {
+    let remote_ip = request.remote_addr().map(|addr| addr.ip());
+
+    // The mutating request is only allowed from the admin cidrs if they are configured,
+    // otherwise it is authorized as the read request.
+    if access == Access::Write && !auth.admin_cidrs.is_empty() {
+        if remote_ip.is_some_and(|ip| auth.is_admin(ip)) {
+            return Ok(());
+        }
+
+        warn!("permission denied for {:?}", remote_ip);
+        return Err(Status::permission_denied("permission denied"));
+    }
+
+    let token = request
+        .metadata()
+        .get(AUTHORIZATION_METADATA_KEY)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix(BEARER_PREFIX));
+    if is_read_authorized(auth, remote_ip, token) {
+        return Ok(());
+    }
+
+    warn!("unauthenticated request from {:?}", remote_ip);
+    Err(Status::unauthenticated("invalid token"))
+}
+
+/// is_read_authorized returns whether the request from the remote ip with the token is allowed
+/// to read the tasks and pieces.
+pub fn is_read_authorized(
+    auth: &UploadAuth,
+    remote_ip: Option<IpAddr>,
+    token: Option<&str>,
+) -> bool {
+    if !auth.is_enabled() {
+        return true;
+    }
+
+    if remote_ip.is_some_and(|ip| auth.is_peer(ip) || auth.is_admin(ip)) {
+        return true;
+    }
+
+    match (auth.token.as_ref(), token) {
+        (Some(expected), Some(token)) => constant_time_eq(token.as_bytes(), expected.as_bytes()),
+        _ => false,
+    }
+}
+
+/// constant_time_eq compares the bytes in constant time to avoid leaking the token by timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// InjectTokenInterceptor is a gRPC interceptor that injects the tracing context and
+/// the token of the upload server.
+#[derive(Clone)]
+pub struct InjectTokenInterceptor {
+    /// token is the bearer value of the authorization metadata.
+    token: Option<MetadataValue<tonic::metadata::Ascii>>,
+}
+
+/// InjectTokenInterceptor implements the token injection.
+impl InjectTokenInterceptor {
+    /// new creates a new InjectTokenInterceptor, the invalid token is ignored.
+    pub fn new(token: Option<&str>) -> Self {
+        let token = token.and_then(|token| {
+            MetadataValue::try_from(format!("{}{}", BEARER_PREFIX, token))
+                .inspect_err(|err| warn!("invalid upload client token: {}", err))
+                .ok()
+        });
+
+        Self { token }
+    }
+}
+
+/// InjectTokenInterceptor implements the tonic Interceptor interface.
+impl Interceptor for InjectTokenInterceptor {
+    /// call injects the tracing context and the token into the request.
+    fn call(&mut self, request: Request<()>) -> std::result::Result<Request<()>, Status> {
+        let mut request = InjectTracingInterceptor.call(request)?;
+        if let Some(token) = self.token.as_ref() {
+            request
+                .metadata_mut()
+                .insert(AUTHORIZATION_METADATA_KEY, token.clone());
+        }
+
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use tonic::transport::server::TcpConnectInfo;
+
+    fn make_request(remote_addr: &str, token: Option<&str>) -> Request<()> {
+        let mut request = Request::new(());
+        request.extensions_mut().insert(TcpConnectInfo {
+            local_addr: None,
+            remote_addr: Some(remote_addr.parse::<SocketAddr>().unwrap()),
+        });
+
+        if let Some(token) = token {
+            request = InjectTokenInterceptor::new(Some(token))
+                .call(request)
+                .unwrap();
+        }
+
+        request
+    }
+
+    #[test]
+    fn should_allow_read_requests_when_disabled() {
+        let auth = UploadAuth::default();
+        let request = make_request("10.0.0.1:4000", None);
+        assert!(authorize(&auth, &request, Access::Read).is_ok());
+        assert!(authorize(&auth, &request, Access::Write).is_ok());
+    }
+
+    #[test]
+    fn should_authorize_read_requests() {
+        let auth = UploadAuth {
+            token: Some("foo".to_string()),
+            peer_cidrs: vec!["10.0.0.0/8".parse().unwrap()],
+            admin_cidrs: vec![],
+        };
+
+        let request = make_request("10.0.0.1:4000", None);
+        assert!(authorize(&auth, &request, Access::Read).is_ok());
+
+        let request = make_request("192.168.0.1:4000", Some("foo"));
+        assert!(authorize(&auth, &request, Access::Read).is_ok());
+
+        // The mutating request is authorized as the read request if the admin cidrs are not
+        // configured.
+        assert!(authorize(&auth, &request, Access::Write).is_ok());
+
+        let request = make_request("192.168.0.1:4000", Some("bar"));
+        let status = authorize(&auth, &request, Access::Read).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        let request = make_request("192.168.0.1:4000", None);
+        assert!(authorize(&auth, &request, Access::Read).is_err());
+        assert!(authorize(&auth, &request, Access::Write).is_err());
+    }
+
+    #[test]
+    fn should_authorize_write_requests() {
+        let auth = UploadAuth {
+            token: Some("foo".to_string()),
+            peer_cidrs: vec!["10.0.0.0/8".parse().unwrap()],
+            admin_cidrs: vec!["192.168.0.10/32".parse().unwrap()],
+        };
+
+        let request = make_request("192.168.0.10:4000", None);
+        assert!(authorize(&auth, &request, Access::Read).is_ok());
+        assert!(authorize(&auth, &request, Access::Write).is_ok());
+
+        let request = make_request("10.0.0.1:4000", Some("foo"));
+        assert!(authorize(&auth, &request, Access::Read).is_ok());
+        let status = authorize(&auth, &request, Access::Write).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+    }
+
+    #[test]
+    fn should_authorize_read_requests_by_remote_ip_and_token() {
+        let auth = UploadAuth {
+            token: Some("foo".to_string()),
+            peer_cidrs: vec!["10.0.0.0/8".parse().unwrap()],
+            admin_cidrs: vec![],
+        };
+
+        assert!(is_read_authorized(
+            &auth,
+            Some("10.0.0.1".parse().unwrap()),
+            None
+        ));
+        assert!(is_read_authorized(&auth, None, Some("foo")));
+        assert!(!is_read_authorized(&auth, None, Some("fo")));
+        assert!(!is_read_authorized(
+            &auth,
+            Some("192.168.0.1".parse().unwrap()),
+            None
+        ));
+    }
+}
@@ -23,7 +23,7 @@ use crate::metrics::{
     collect_stat_task_started_metrics, collect_upload_task_failure_metrics,
     collect_upload_task_finished_metrics, collect_upload_task_started_metrics,
 };
-use crate::resource::{persistent_cache_task, task};
+use crate::resource::{cache_task, persistent_cache_task, task};
 use crate::shutdown;
 use dragonfly_api::common::v2::{CacheTask, PersistentCacheTask, Priority, Task, TaskType};
 use dragonfly_api::dfdaemon::v2::{
@@ -39,7 +39,10 @@ use dragonfly_api::dfdaemon::v2::{
 };
 use dragonfly_api::errordetails::v2::Backend;
 use dragonfly_api::scheduler::v2::DeleteHostRequest as SchedulerDeleteHostRequest;
-use dragonfly_client_backend::HeadRequest;
+use dragonfly_client_backend::{
+    is_list_header, take_list_header, HeadRequest, ListRequest, LIST_CONTINUATION_TOKEN_HEADER,
+    LIST_MAX_DEPTH_HEADER, LIST_PAGE_SIZE_HEADER,
+};
 use dragonfly_client_config::dfdaemon::Config;
 use dragonfly_client_core::{
     error::{ErrorType, OrErr},
@@ -50,8 +53,10 @@ use dragonfly_client_util::{
     http::{get_range, hashmap_to_headermap, headermap_to_hashmap},
     id_generator::{PersistentCacheTaskIDParameter, TaskIDParameter},
 };
+use futures::stream::{self, Stream};
 use hyper_util::rt::TokioIo;
 use opentelemetry::Context;
+use std::collections::HashMap;
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -87,6 +92,9 @@ pub struct DfdaemonDownloadServer {
     /// persistent_cache_task is the persistent cache task manager.
     persistent_cache_task: Arc<persistent_cache_task::PersistentCacheTask>,
 
+    /// cache_task is the cache task manager.
+    cache_task: Arc<cache_task::CacheTask>,
+
     /// shutdown is used to shutdown the grpc server.
     shutdown: shutdown::Shutdown,
 
@@ -102,6 +110,7 @@ impl DfdaemonDownloadServer {
         socket_path: PathBuf,
         task: Arc<task::Task>,
         persistent_cache_task: Arc<persistent_cache_task::PersistentCacheTask>,
+        cache_task: Arc<cache_task::CacheTask>,
         shutdown: shutdown::Shutdown,
         shutdown_complete_tx: mpsc::UnboundedSender<()>,
     ) -> Self {
@@ -110,6 +119,7 @@ impl DfdaemonDownloadServer {
             socket_path,
             task,
             persistent_cache_task,
+            cache_task,
             shutdown,
             _shutdown_complete: shutdown_complete_tx,
         }
@@ -124,6 +134,7 @@ impl DfdaemonDownloadServer {
                 socket_path: self.socket_path.clone(),
                 task: self.task.clone(),
                 persistent_cache_task: self.persistent_cache_task.clone(),
+                cache_task: self.cache_task.clone(),
             },
             ExtractTracingInterceptor,
         );
@@ -222,6 +233,9 @@ pub struct DfdaemonDownloadServerHandler {
 
     /// persistent_cache_task is the persistent cache task manager.
     persistent_cache_task: Arc<persistent_cache_task::PersistentCacheTask>,
+
+    /// cache_task is the cache task manager.
+    cache_task: Arc<cache_task::CacheTask>,
 }
 
 /// DfdaemonDownloadServerHandler implements the dfdaemon download grpc service.
@@ -624,7 +638,10 @@ impl DfdaemonDownload for DfdaemonDownloadServerHandler {
         // If prefetch flag is true, prefetch the full task.
         if download.prefetch {
             info!("try to prefetch task");
-            match task_manager.prefetch_task_started(task_id.as_str()).await {
+            match task_manager
+                .prefetch_task_started(task_id.as_str(), &download)
+                .await
+            {
                 Ok(_) => {
                     info!("prefetch task started");
                     let socket_path = self.socket_path.clone();
@@ -768,17 +785,97 @@ impl DfdaemonDownload for DfdaemonDownloadServerHandler {
                 Status::internal(err.to_string())
             })?;
 
+        // List the task entries by pages if the client requests the page size, the
+        // continuation token of the next page is returned in the response header. The
+        // reserved headers of the listing are removed, so they never reach the backend.
+        let mut request_header = request.request_header.clone();
+        let page_size = take_list_header(&mut request_header, LIST_PAGE_SIZE_HEADER);
+        let max_depth = take_list_header(&mut request_header, LIST_MAX_DEPTH_HEADER);
+        let continuation_token =
+            take_list_header(&mut request_header, LIST_CONTINUATION_TOKEN_HEADER);
+        request_header.retain(|key, _| !is_list_header(key));
+        if let Some(page_size) = page_size {
+            let page_size = page_size
+                .parse::<usize>()
+                .ok()
+                .filter(|page_size| *page_size > 0)
+                .ok_or_else(|| {
+                    // Collect the list tasks failure metrics.
+                    collect_list_task_entries_failure_metrics(TaskType::Standard as i32);
+
+                    error!("invalid page size: {}", page_size);
+                    Status::invalid_argument(format!("invalid page size: {}", page_size))
+                })?;
+
+            let max_depth = match max_depth {
+                Some(max_depth) => Some(max_depth.parse::<usize>().map_err(|err| {
+                    // Collect the list tasks failure metrics.
+                    collect_list_task_entries_failure_metrics(TaskType::Standard as i32);
+
+                    error!("invalid max depth {}: {}", max_depth, err);
+                    Status::invalid_argument(format!("invalid max depth: {}", max_depth))
+                })?),
+                None => None,
+            };
+
+            let response = backend
+                .list(ListRequest {
+                    task_id: request.task_id.clone(),
+                    url: request.url.clone(),
+                    http_header: Some(hashmap_to_headermap(&request_header).map_err(|err| {
+                        error!("parse request header: {}", err);
+                        Status::internal(err.to_string())
+                    })?),
+                    timeout: self.config.download.piece_timeout,
+                    client_cert: None,
+                    object_storage: request.object_storage.clone(),
+                    hdfs: request.hdfs.clone(),
+                    continuation_token,
+                    page_size,
+                    max_depth,
+                })
+                .await
+                .map_err(|err| {
+                    // Collect the list tasks failure metrics.
+                    collect_list_task_entries_failure_metrics(TaskType::Standard as i32);
+
+                    error!("list task entries: {}", err);
+                    Status::internal(err.to_string())
+                })?;
+
+            let mut response_header = HashMap::new();
+            if let Some(continuation_token) = response.continuation_token {
+                response_header.insert(
+                    LIST_CONTINUATION_TOKEN_HEADER.to_string(),
+                    continuation_token,
+                );
+            }
+
+            return Ok(Response::new(ListTaskEntriesResponse {
+                content_length: 0,
+                response_header,
+                status_code: None,
+                entries: response
+                    .entries
+                    .into_iter()
+                    .map(|dir_entry| Entry {
+                        url: dir_entry.url,
+                        content_length: dir_entry.content_length as u64,
+                        is_dir: dir_entry.is_dir,
+                    })
+                    .collect(),
+            }));
+        }
+
         // Head the task entries.
         let response = backend
             .head(HeadRequest {
                 task_id: request.task_id.clone(),
                 url: request.url.clone(),
-                http_header: Some(hashmap_to_headermap(&request.request_header).map_err(
-                    |err| {
-                        error!("parse request header: {}", err);
-                        Status::internal(err.to_string())
-                    },
-                )?),
+                http_header: Some(hashmap_to_headermap(&request_header).map_err(|err| {
+                    error!("parse request header: {}", err);
+                    Status::internal(err.to_string())
+                })?),
                 timeout: self.config.download.piece_timeout,
                 client_cert: None,
                 object_storage: request.object_storage.clone(),
@@ -1309,27 +1406,398 @@ impl DfdaemonDownload for DfdaemonDownloadServerHandler {
     )]
     async fn download_cache_task(
         &self,
-        _request: Request<DownloadCacheTaskRequest>,
+        request: Request<DownloadCacheTaskRequest>,
     ) -> Result<Response<Self::DownloadCacheTaskStream>, Status> {
-        todo!();
+        // If the parent context is set, use it as the parent context for the span.
+        if let Some(parent_ctx) = request.extensions().get::<Context>() {
+            Span::current().set_parent(parent_ctx.clone());
+        };
+
+        // Record the start time.
+        let start_time = Instant::now();
+
+        // Clone the request.
+        let mut request = request.into_inner();
+
+        // Generate the task id.
+        let task_id = self
+            .cache_task
+            .id_generator
+            .task_id(match request.content_for_calculating_task_id.clone() {
+                Some(content) => TaskIDParameter::Content(content),
+                None => TaskIDParameter::URLBased {
+                    url: request.url.clone(),
+                    piece_length: request.piece_length,
+                    tag: request.tag.clone(),
+                    application: request.application.clone(),
+                    filtered_query_params: request.filtered_query_params.clone(),
+                },
+            })
+            .map_err(|e| {
+                error!("generate task id: {}", e);
+                Status::invalid_argument(e.to_string())
+            })?;
+
+        // Generate the host id.
+        let host_id = self.cache_task.id_generator.host_id();
+
+        // Generate the peer id.
+        let peer_id = self.cache_task.id_generator.peer_id();
+
+        // Span record the host id, task id and peer id.
+        Span::current().record("host_id", host_id.as_str());
+        Span::current().record("task_id", task_id.as_str());
+        Span::current().record("peer_id", peer_id.as_str());
+        Span::current().record("url", request.url.clone());
+        Span::current().record(
+            "remote_ip",
+            request.remote_ip.clone().unwrap_or_default().as_str(),
+        );
+        info!("download cache task in download server");
+
+        // Download cache task started.
+        info!("download cache task started: {:?}", request);
+        let task = match self
+            .cache_task
+            .download_started(task_id.as_str(), request.clone())
+            .await
+        {
+            Err(ClientError::BackendError(err)) => {
+                error!("download started failed by error: {}", err);
+                match serde_json::to_vec::<Backend>(&Backend {
+                    message: err.message.clone(),
+                    header: headermap_to_hashmap(&err.header.clone().unwrap_or_default()),
+                    status_code: err.status_code.map(|code| code.as_u16() as i32),
+                }) {
+                    Ok(json) => {
+                        return Err(Status::with_details(
+                            Code::Internal,
+                            err.to_string(),
+                            json.into(),
+                        ));
+                    }
+                    Err(err) => {
+                        error!("serialize error: {}", err);
+                        return Err(Status::internal(err.to_string()));
+                    }
+                }
+            }
+            Err(err) => {
+                error!("download started failed: {}", err);
+                return Err(Status::internal(err.to_string()));
+            }
+            Ok(task) => {
+                // Collect download task started metrics.
+                collect_download_task_started_metrics(
+                    TaskType::Cache as i32,
+                    request.tag.clone().unwrap_or_default().as_str(),
+                    request.application.clone().unwrap_or_default().as_str(),
+                    request.priority.to_string().as_str(),
+                );
+
+                task
+            }
+        };
+
+        info!(
+            "content length {}, piece length {}",
+            task.content_length(),
+            task.piece_length()
+        );
+
+        Span::current().record("content_length", task.content_length());
+
+        // Request's range priority is higher than the request header's range.
+        if request.range.is_none() {
+            // Convert the header.
+            let request_header = match hashmap_to_headermap(&request.request_header) {
+                Ok(header) => header,
+                Err(e) => {
+                    // Collect download task failure metrics.
+                    collect_download_task_failure_metrics(
+                        TaskType::Cache as i32,
+                        request.tag.clone().unwrap_or_default().as_str(),
+                        request.application.clone().unwrap_or_default().as_str(),
+                        request.priority.to_string().as_str(),
+                    );
+
+                    error!("convert header: {}", e);
+                    return Err(Status::invalid_argument(e.to_string()));
+                }
+            };
+
+            request.range = match get_range(&request_header, task.content_length()) {
+                Ok(range) => range,
+                Err(e) => {
+                    // Collect download task failure metrics.
+                    collect_download_task_failure_metrics(
+                        TaskType::Cache as i32,
+                        request.tag.clone().unwrap_or_default().as_str(),
+                        request.application.clone().unwrap_or_default().as_str(),
+                        request.priority.to_string().as_str(),
+                    );
+
+                    error!("get range failed: {}", e);
+                    return Err(Status::failed_precondition(e.to_string()));
+                }
+            };
+        }
+
+        // Initialize stream channel.
+        let task_manager = self.cache_task.clone();
+        let (out_stream_tx, out_stream_rx) = mpsc::channel(10 * 1024);
+
+        // Define the error handler to send the error to the stream.
+        async fn handle_error(
+            out_stream_tx: &Sender<Result<DownloadCacheTaskResponse, Status>>,
+            err: impl std::error::Error,
+        ) {
+            out_stream_tx
+                .send_timeout(
+                    Err(Status::internal(err.to_string())),
+                    super::REQUEST_TIMEOUT,
+                )
+                .await
+                .unwrap_or_else(|err| error!("send download progress error: {:?}", err));
+        }
+
+        tokio::spawn(
+            async move {
+                match task_manager
+                    .download(
+                        task_id.as_str(),
+                        &task,
+                        host_id.as_str(),
+                        peer_id.as_str(),
+                        request.clone(),
+                        out_stream_tx.clone(),
+                    )
+                    .await
+                {
+                    Ok(_) => {
+                        // Collect download task finished metrics.
+                        collect_download_task_finished_metrics(
+                            TaskType::Cache as i32,
+                            request.tag.clone().unwrap_or_default().as_str(),
+                            request.application.clone().unwrap_or_default().as_str(),
+                            request.priority.to_string().as_str(),
+                            task.content_length(),
+                            request.range,
+                            start_time.elapsed(),
+                        );
+
+                        // Download cache task succeeded.
+                        info!("download cache task succeeded");
+                        if request.range.is_none() {
+                            if let Some(output_path) = &request.output_path {
+                                let output_path = Path::new(output_path.as_str());
+                                if output_path.exists() {
+                                    error!(
+                                        "output path {} is already exists",
+                                        output_path.display()
+                                    );
+
+                                    handle_error(
+                                        &out_stream_tx,
+                                        Status::internal(format!(
+                                            "output path {} is already exists",
+                                            output_path.display()
+                                        )),
+                                    )
+                                    .await;
+                                    return;
+                                }
+
+                                if let Err(err) =
+                                    task_manager.copy_task(task_id.as_str(), output_path).await
+                                {
+                                    error!("copy cache task: {}", err);
+                                    handle_error(&out_stream_tx, err).await;
+                                    return;
+                                }
+
+                                // Verify the file digest if it is provided.
+                                if let Some(raw_digest) = &request.digest {
+                                    let digest = match raw_digest.parse::<Digest>() {
+                                        Ok(digest) => digest,
+                                        Err(err) => {
+                                            error!("parse digest: {}", err);
+                                            handle_error(
+                                                &out_stream_tx,
+                                                Status::invalid_argument(format!(
+                                                    "invalid digest({}): {}",
+                                                    raw_digest, err
+                                                )),
+                                            )
+                                            .await;
+                                            return;
+                                        }
+                                    };
+
+                                    if let Err(err) = verify_file_digest(digest, output_path) {
+                                        error!("verify file digest: {}", err);
+                                        handle_error(&out_stream_tx, err).await;
+                                        return;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    Err(ClientError::BackendError(err)) => {
+                        // Collect download task failure metrics.
+                        collect_download_task_failure_metrics(
+                            TaskType::Cache as i32,
+                            request.tag.clone().unwrap_or_default().as_str(),
+                            request.application.clone().unwrap_or_default().as_str(),
+                            request.priority.to_string().as_str(),
+                        );
+
+                        match serde_json::to_vec::<Backend>(&Backend {
+                            message: err.message.clone(),
+                            header: headermap_to_hashmap(&err.header.clone().unwrap_or_default()),
+                            status_code: err.status_code.map(|code| code.as_u16() as i32),
+                        }) {
+                            Ok(json) => {
+                                handle_error(
+                                    &out_stream_tx,
+                                    Status::with_details(
+                                        Code::Internal,
+                                        err.to_string(),
+                                        json.into(),
+                                    ),
+                                )
+                                .await;
+                            }
+                            Err(err) => {
+                                error!("serialize error: {}", err);
+                                handle_error(&out_stream_tx, err).await;
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        error!("download cache task failed: {}", err);
+
+                        // Collect download task failure metrics.
+                        collect_download_task_failure_metrics(
+                            TaskType::Cache as i32,
+                            request.tag.clone().unwrap_or_default().as_str(),
+                            request.application.clone().unwrap_or_default().as_str(),
+                            request.priority.to_string().as_str(),
+                        );
+
+                        handle_error(&out_stream_tx, err).await;
+                    }
+                }
+
+                drop(out_stream_tx);
+            }
+            .in_current_span(),
+        );
+
+        Ok(Response::new(ReceiverStream::new(out_stream_rx)))
     }
 
     /// stat_cache_task gets the status of the cache task.
-    #[instrument(skip_all, fields(host_id, task_id, remote_pi, local_only))]
+    #[instrument(skip_all, fields(host_id, task_id, remote_ip, local_only))]
     async fn stat_cache_task(
         &self,
-        _request: Request<DfdaemonStatCacheTaskRequest>,
+        request: Request<DfdaemonStatCacheTaskRequest>,
     ) -> Result<Response<CacheTask>, Status> {
-        todo!();
+        // If the parent context is set, use it as the parent context for the span.
+        if let Some(parent_ctx) = request.extensions().get::<Context>() {
+            Span::current().set_parent(parent_ctx.clone());
+        };
+
+        // Clone the request.
+        let request = request.into_inner();
+
+        // Generate the host id.
+        let host_id = self.cache_task.id_generator.host_id();
+
+        // Get the task id from the request.
+        let task_id = request.task_id;
+
+        // Get the local_only flag from the request, default to false.
+        let local_only = request.local_only;
+
+        // Span record the host id and task id.
+        Span::current().record("host_id", host_id.as_str());
+        Span::current().record("task_id", task_id.as_str());
+        Span::current().record(
+            "remote_ip",
+            request.remote_ip.clone().unwrap_or_default().as_str(),
+        );
+        Span::current().record("local_only", local_only.to_string().as_str());
+        info!("stat cache task in download server");
+
+        // Collect the stat cache task metrics.
+        collect_stat_task_started_metrics(TaskType::Cache as i32);
+
+        match self
+            .cache_task
+            .stat(task_id.as_str(), host_id.as_str(), local_only)
+            .await
+        {
+            Ok(task) => Ok(Response::new(task)),
+            Err(err) => {
+                // Collect the stat cache task failure metrics.
+                collect_stat_task_failure_metrics(TaskType::Cache as i32);
+
+                error!("stat cache task failed: {}", err);
+                Err(match err {
+                    ClientError::TaskNotFound(id) => {
+                        Status::not_found(format!("cache task not found: {}", id))
+                    }
+                    _ => Status::internal(err.to_string()),
+                })
+            }
+        }
     }
 
     /// delete_cache_task calls the dfdaemon to delete the cache task.
     #[instrument(skip_all, fields(host_id, task_id, remote_ip))]
     async fn delete_cache_task(
         &self,
-        _request: Request<DeleteCacheTaskRequest>,
+        request: Request<DeleteCacheTaskRequest>,
     ) -> Result<Response<()>, Status> {
-        todo!();
+        // If the parent context is set, use it as the parent context for the span.
+        if let Some(parent_ctx) = request.extensions().get::<Context>() {
+            Span::current().set_parent(parent_ctx.clone());
+        };
+
+        // Clone the request.
+        let request = request.into_inner();
+
+        // Generate the host id.
+        let host_id = self.cache_task.id_generator.host_id();
+
+        // Get the task id from the request.
+        let task_id = request.task_id;
+
+        // Span record the host id and task id.
+        Span::current().record("host_id", host_id.as_str());
+        Span::current().record("task_id", task_id.as_str());
+        Span::current().record(
+            "remote_ip",
+            request.remote_ip.clone().unwrap_or_default().as_str(),
+        );
+        info!("delete cache task in download server");
+
+        // Collect the delete cache task started metrics.
+        collect_delete_task_started_metrics(TaskType::Cache as i32);
+
+        self.cache_task
+            .delete(task_id.as_str(), host_id.as_str())
+            .await
+            .map_err(|err| {
+                // Collect the delete cache task failure metrics.
+                collect_delete_task_failure_metrics(TaskType::Cache as i32);
+
+                error!("delete cache task: {}", err);
+                Status::internal(err.to_string())
+            })?;
+
+        Ok(Response::new(()))
     }
 }
 
@@ -1420,6 +1888,49 @@ impl DfdaemonDownloadClient {
         Ok(response.into_inner())
     }
 
+    /// list_task_entries_by_page lists the task entries by pages, the stream yields the
+    /// entries of each page, and the next page is requested only when the stream is polled,
+    /// so the large directory is not loaded into the memory at once. The pages are requested
+    /// by the unary list task entries with the reserved `X-Dragonfly-List-*` request headers
+    /// instead of a server-streaming RPC, and the prefix and the delimiter are not supported.
+    /// If the dfdaemon does not support the paginated listing, all the entries are returned
+    /// in the first page.
+    pub fn list_task_entries_by_page(
+        &self,
+        mut request: ListTaskEntriesRequest,
+        page_size: usize,
+        max_depth: Option<usize>,
+    ) -> impl Stream<Item = ClientResult<Vec<Entry>>> + '_ {
+        request
+            .request_header
+            .insert(LIST_PAGE_SIZE_HEADER.to_string(), page_size.to_string());
+        if let Some(max_depth) = max_depth {
+            request
+                .request_header
+                .insert(LIST_MAX_DEPTH_HEADER.to_string(), max_depth.to_string());
+        }
+
+        stream::try_unfold(Some(request), move |request| async move {
+            let Some(mut request) = request else {
+                return Ok(None);
+            };
+
+            let mut response = self.list_task_entries(request.clone()).await?;
+            let next_request = response
+                .response_header
+                .remove(LIST_CONTINUATION_TOKEN_HEADER)
+                .map(|continuation_token| {
+                    request.request_header.insert(
+                        LIST_CONTINUATION_TOKEN_HEADER.to_string(),
+                        continuation_token,
+                    );
+                    request
+                });
+
+            Ok::<_, ClientError>(Some((response.entries, next_request)))
+        })
+    }
+
     /// delete_task tells the dfdaemon to delete the task.
     #[instrument(skip_all)]
     pub async fn delete_task(&self, request: DeleteTaskRequest) -> ClientResult<()> {
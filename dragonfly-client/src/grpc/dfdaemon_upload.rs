@@ -22,10 +22,10 @@ use crate::metrics::{
     collect_update_task_started_metrics, collect_upload_piece_failure_metrics,
     collect_upload_piece_finished_metrics, collect_upload_piece_started_metrics,
 };
-use crate::resource::{persistent_cache_task, task};
+use crate::resource::{cache_task, persistent_cache_task, task};
 use crate::shutdown;
 use dragonfly_api::common::v2::{
-    CacheTask, Host, Network, PersistentCacheTask, Piece, Priority, Task, TaskType,
+    CacheTask, Cpu, Host, Network, PersistentCacheTask, Piece, Priority, Task, TaskType,
 };
 use dragonfly_api::dfdaemon::v2::{
     dfdaemon_upload_client::DfdaemonUploadClient as DfdaemonUploadGRPCClient,
@@ -42,7 +42,7 @@ use dragonfly_api::dfdaemon::v2::{
     SyncPiecesResponse, UpdatePersistentCacheTaskRequest,
 };
 use dragonfly_api::errordetails::v2::Backend;
-use dragonfly_client_config::dfdaemon::Config;
+use dragonfly_client_config::dfdaemon::{Config, UploadAuth};
 use dragonfly_client_core::{
     error::{ErrorType, OrErr},
     Error as ClientError, Result as ClientResult,
@@ -57,6 +57,7 @@ use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use sysinfo::System;
 use tokio::io::AsyncReadExt;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::Sender;
@@ -72,7 +73,8 @@ use tracing::{debug, error, info, instrument, Instrument, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 use url::Url;
 
-use super::interceptor::{ExtractTracingInterceptor, InjectTracingInterceptor};
+use super::auth::{authorize, Access, InjectTokenInterceptor};
+use super::interceptor::ExtractTracingInterceptor;
 
 /// DfdaemonUploadServer is the grpc server of the upload.
 pub struct DfdaemonUploadServer {
@@ -88,6 +90,9 @@ pub struct DfdaemonUploadServer {
     /// persistent_cache_task is the persistent cache task manager.
     persistent_cache_task: Arc<persistent_cache_task::PersistentCacheTask>,
 
+    /// cache_task is the cache task manager.
+    cache_task: Arc<cache_task::CacheTask>,
+
     /// interface is the network interface.
     interface: Arc<Interface>,
 
@@ -101,11 +106,13 @@ pub struct DfdaemonUploadServer {
 /// DfdaemonUploadServer implements the grpc server of the upload.
 impl DfdaemonUploadServer {
     /// new creates a new DfdaemonUploadServer.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         config: Arc<Config>,
         addr: SocketAddr,
         task: Arc<task::Task>,
         persistent_cache_task: Arc<persistent_cache_task::PersistentCacheTask>,
+        cache_task: Arc<cache_task::CacheTask>,
         interface: Arc<Interface>,
         shutdown: shutdown::Shutdown,
         shutdown_complete_tx: mpsc::UnboundedSender<()>,
@@ -116,6 +123,7 @@ impl DfdaemonUploadServer {
             task,
             interface,
             persistent_cache_task,
+            cache_task,
             shutdown,
             _shutdown_complete: shutdown_complete_tx,
         }
@@ -128,7 +136,9 @@ impl DfdaemonUploadServer {
                 socket_path: self.config.download.server.socket_path.clone(),
                 task: self.task.clone(),
                 persistent_cache_task: self.persistent_cache_task.clone(),
+                cache_task: self.cache_task.clone(),
                 interface: self.interface.clone(),
+                auth: self.config.upload.server.auth.clone(),
             },
             ExtractTracingInterceptor,
         );
@@ -213,8 +223,14 @@ pub struct DfdaemonUploadServerHandler {
     /// persistent_cache_task is the persistent cache task manager.
     persistent_cache_task: Arc<persistent_cache_task::PersistentCacheTask>,
 
+    /// cache_task is the cache task manager.
+    cache_task: Arc<cache_task::CacheTask>,
+
     /// interface is the network interface.
     interface: Arc<Interface>,
+
+    /// auth is the authorization configuration of the upload server.
+    auth: UploadAuth,
 }
 
 /// DfdaemonUploadServerHandler implements the dfdaemon upload grpc service.
@@ -232,6 +248,9 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
         &self,
         request: Request<DownloadTaskRequest>,
     ) -> Result<Response<Self::DownloadTaskStream>, Status> {
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Read)?;
+
         // If the parent context is set, use it as the parent context for the span.
         if let Some(parent_ctx) = request.extensions().get::<Context>() {
             Span::current().set_parent(parent_ctx.clone());
@@ -593,7 +612,10 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
         // If prefetch flag is true, prefetch the full task.
         if download.prefetch {
             info!("try to prefetch task");
-            match task_manager.prefetch_task_started(task_id.as_str()).await {
+            match task_manager
+                .prefetch_task_started(task_id.as_str(), &download)
+                .await
+            {
                 Ok(_) => {
                     info!("prefetch task started");
                     let socket_path = self.socket_path.clone();
@@ -641,6 +663,9 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
     /// stat_task stats the task.
     #[instrument(skip_all, fields(host_id, task_id, remote_ip, local_only))]
     async fn stat_task(&self, request: Request<StatTaskRequest>) -> Result<Response<Task>, Status> {
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Read)?;
+
         // If the parent context is set, use it as the parent context for the span.
         if let Some(parent_ctx) = request.extensions().get::<Context>() {
             Span::current().set_parent(parent_ctx.clone());
@@ -701,6 +726,9 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
         &self,
         request: Request<DeleteTaskRequest>,
     ) -> Result<Response<()>, Status> {
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Write)?;
+
         // If the parent context is set, use it as the parent context for the span.
         if let Some(parent_ctx) = request.extensions().get::<Context>() {
             Span::current().set_parent(parent_ctx.clone());
@@ -752,6 +780,9 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
         &self,
         request: Request<SyncPiecesRequest>,
     ) -> Result<Response<Self::SyncPiecesStream>, Status> {
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Read)?;
+
         // If the parent context is set, use it as the parent context for the span.
         if let Some(parent_ctx) = request.extensions().get::<Context>() {
             Span::current().set_parent(parent_ctx.clone());
@@ -885,6 +916,9 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
         &self,
         request: Request<DownloadPieceRequest>,
     ) -> Result<Response<DownloadPieceResponse>, Status> {
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Read)?;
+
         // If the parent context is set, use it as the parent context for the span.
         if let Some(parent_ctx) = request.extensions().get::<Context>() {
             Span::current().set_parent(parent_ctx.clone());
@@ -997,6 +1031,9 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
         &self,
         request: Request<SyncHostRequest>,
     ) -> Result<Response<Self::SyncHostStream>, Status> {
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Read)?;
+
         // If the parent context is set, use it as the parent context for the span.
         if let Some(parent_ctx) = request.extensions().get::<Context>() {
             Span::current().set_parent(parent_ctx.clone());
@@ -1030,11 +1067,21 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
         let (out_stream_tx, out_stream_rx) = mpsc::channel(10 * 1024);
         tokio::spawn(
             async move {
+                // Initialize the system information for calculating the cpu usage, the cpu usage
+                // is calculated by the difference between the two refreshes, so refresh it before
+                // the loop to make the first host info valid.
+                let mut sys = System::new();
+                sys.refresh_cpu_usage();
+
                 // Start the host info update loop.
                 loop {
                     // Wait for the host info refresh interval.
                     tokio::time::sleep(DEFAULT_HOST_INFO_REFRESH_INTERVAL).await;
 
+                    // Refresh the cpu usage, the usage is calculated by the difference between
+                    // the two refreshes.
+                    sys.refresh_cpu_usage();
+
                     // Wait for getting the network data.
                     let network_data = interface.get_network_data().await;
                     debug!(
@@ -1048,6 +1095,11 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
                     // Send host info.
                     match out_stream_tx
                         .send(Ok(Host {
+                            cpu: Some(Cpu {
+                                logical_count: sys.cpus().len() as u32,
+                                percent: sys.global_cpu_usage() as f64,
+                                ..Default::default()
+                            }),
                             network: Some(Network {
                                 max_rx_bandwidth: network_data.max_rx_bandwidth,
                                 rx_bandwidth: network_data.rx_bandwidth,
@@ -1087,6 +1139,9 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
         &self,
         request: Request<DownloadPersistentCacheTaskRequest>,
     ) -> Result<Response<Self::DownloadPersistentCacheTaskStream>, Status> {
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Read)?;
+
         // If the parent context is set, use it as the parent context for the span.
         if let Some(parent_ctx) = request.extensions().get::<Context>() {
             Span::current().set_parent(parent_ctx.clone());
@@ -1319,6 +1374,9 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
         &self,
         request: Request<UpdatePersistentCacheTaskRequest>,
     ) -> Result<Response<()>, Status> {
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Write)?;
+
         // If the parent context is set, use it as the parent context for the span.
         if let Some(parent_ctx) = request.extensions().get::<Context>() {
             Span::current().set_parent(parent_ctx.clone());
@@ -1366,6 +1424,9 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
         &self,
         request: Request<StatPersistentCacheTaskRequest>,
     ) -> Result<Response<PersistentCacheTask>, Status> {
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Read)?;
+
         // If the parent context is set, use it as the parent context for the span.
         if let Some(parent_ctx) = request.extensions().get::<Context>() {
             Span::current().set_parent(parent_ctx.clone());
@@ -1413,6 +1474,9 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
         &self,
         request: Request<DeletePersistentCacheTaskRequest>,
     ) -> Result<Response<()>, Status> {
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Write)?;
+
         // If the parent context is set, use it as the parent context for the span.
         if let Some(parent_ctx) = request.extensions().get::<Context>() {
             Span::current().set_parent(parent_ctx.clone());
@@ -1452,6 +1516,9 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
         &self,
         request: Request<SyncPersistentCachePiecesRequest>,
     ) -> Result<Response<Self::SyncPersistentCachePiecesStream>, Status> {
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Read)?;
+
         // If the parent context is set, use it as the parent context for the span.
         if let Some(parent_ctx) = request.extensions().get::<Context>() {
             Span::current().set_parent(parent_ctx.clone());
@@ -1576,6 +1643,9 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
         &self,
         request: Request<DownloadPersistentCachePieceRequest>,
     ) -> Result<Response<DownloadPersistentCachePieceResponse>, Status> {
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Read)?;
+
         // If the parent context is set, use it as the parent context for the span.
         if let Some(parent_ctx) = request.extensions().get::<Context>() {
             Span::current().set_parent(parent_ctx.clone());
@@ -1702,27 +1772,382 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
     )]
     async fn download_cache_task(
         &self,
-        _request: Request<DownloadCacheTaskRequest>,
+        request: Request<DownloadCacheTaskRequest>,
     ) -> Result<Response<Self::DownloadCacheTaskStream>, Status> {
-        todo!();
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Read)?;
+
+        // If the parent context is set, use it as the parent context for the span.
+        if let Some(parent_ctx) = request.extensions().get::<Context>() {
+            Span::current().set_parent(parent_ctx.clone());
+        };
+
+        // Record the start time.
+        let start_time = Instant::now();
+
+        // Clone the request.
+        let mut request = request.into_inner();
+
+        // Generate the task id.
+        let task_id = self
+            .cache_task
+            .id_generator
+            .task_id(match request.content_for_calculating_task_id.clone() {
+                Some(content) => TaskIDParameter::Content(content),
+                None => TaskIDParameter::URLBased {
+                    url: request.url.clone(),
+                    piece_length: request.piece_length,
+                    tag: request.tag.clone(),
+                    application: request.application.clone(),
+                    filtered_query_params: request.filtered_query_params.clone(),
+                },
+            })
+            .map_err(|e| {
+                error!("generate task id: {}", e);
+                Status::invalid_argument(e.to_string())
+            })?;
+
+        // Generate the host id.
+        let host_id = self.cache_task.id_generator.host_id();
+
+        // Generate the peer id.
+        let peer_id = self.cache_task.id_generator.peer_id();
+
+        // Span record the host id, task id and peer id.
+        Span::current().record("host_id", host_id.as_str());
+        Span::current().record("task_id", task_id.as_str());
+        Span::current().record("peer_id", peer_id.as_str());
+        Span::current().record("url", request.url.clone());
+        Span::current().record(
+            "remote_ip",
+            request.remote_ip.clone().unwrap_or_default().as_str(),
+        );
+        info!("download cache task in upload server");
+
+        // Download cache task started.
+        info!("download cache task started: {:?}", request);
+        let task = match self
+            .cache_task
+            .download_started(task_id.as_str(), request.clone())
+            .await
+        {
+            Err(ClientError::BackendError(err)) => {
+                error!("download started failed by error: {}", err);
+                match serde_json::to_vec::<Backend>(&Backend {
+                    message: err.message.clone(),
+                    header: headermap_to_hashmap(&err.header.clone().unwrap_or_default()),
+                    status_code: err.status_code.map(|code| code.as_u16() as i32),
+                }) {
+                    Ok(json) => {
+                        return Err(Status::with_details(
+                            Code::Internal,
+                            err.to_string(),
+                            json.into(),
+                        ));
+                    }
+                    Err(err) => {
+                        error!("serialize error: {}", err);
+                        return Err(Status::internal(err.to_string()));
+                    }
+                }
+            }
+            Err(err) => {
+                error!("download started failed: {}", err);
+                return Err(Status::internal(err.to_string()));
+            }
+            Ok(task) => {
+                // Collect download task started metrics.
+                collect_download_task_started_metrics(
+                    TaskType::Cache as i32,
+                    request.tag.clone().unwrap_or_default().as_str(),
+                    request.application.clone().unwrap_or_default().as_str(),
+                    request.priority.to_string().as_str(),
+                );
+
+                task
+            }
+        };
+
+        info!(
+            "content length {}, piece length {}",
+            task.content_length(),
+            task.piece_length()
+        );
+
+        Span::current().record("content_length", task.content_length());
+
+        // Request's range priority is higher than the request header's range.
+        if request.range.is_none() {
+            // Convert the header.
+            let request_header = match hashmap_to_headermap(&request.request_header) {
+                Ok(header) => header,
+                Err(e) => {
+                    // Collect download task failure metrics.
+                    collect_download_task_failure_metrics(
+                        TaskType::Cache as i32,
+                        request.tag.clone().unwrap_or_default().as_str(),
+                        request.application.clone().unwrap_or_default().as_str(),
+                        request.priority.to_string().as_str(),
+                    );
+
+                    error!("convert header: {}", e);
+                    return Err(Status::invalid_argument(e.to_string()));
+                }
+            };
+
+            request.range = match get_range(&request_header, task.content_length()) {
+                Ok(range) => range,
+                Err(e) => {
+                    // Collect download task failure metrics.
+                    collect_download_task_failure_metrics(
+                        TaskType::Cache as i32,
+                        request.tag.clone().unwrap_or_default().as_str(),
+                        request.application.clone().unwrap_or_default().as_str(),
+                        request.priority.to_string().as_str(),
+                    );
+
+                    error!("get range failed: {}", e);
+                    return Err(Status::failed_precondition(e.to_string()));
+                }
+            };
+        }
+
+        // Initialize stream channel.
+        let task_manager = self.cache_task.clone();
+        let (out_stream_tx, out_stream_rx) = mpsc::channel(10 * 1024);
+
+        // Define the error handler to send the error to the stream.
+        async fn handle_error(
+            out_stream_tx: &Sender<Result<DownloadCacheTaskResponse, Status>>,
+            err: impl std::error::Error,
+        ) {
+            out_stream_tx
+                .send_timeout(
+                    Err(Status::internal(err.to_string())),
+                    super::REQUEST_TIMEOUT,
+                )
+                .await
+                .unwrap_or_else(|err| error!("send download progress error: {:?}", err));
+        }
+
+        tokio::spawn(
+            async move {
+                match task_manager
+                    .download(
+                        task_id.as_str(),
+                        &task,
+                        host_id.as_str(),
+                        peer_id.as_str(),
+                        request.clone(),
+                        out_stream_tx.clone(),
+                    )
+                    .await
+                {
+                    Ok(_) => {
+                        // Collect download task finished metrics.
+                        collect_download_task_finished_metrics(
+                            TaskType::Cache as i32,
+                            request.tag.clone().unwrap_or_default().as_str(),
+                            request.application.clone().unwrap_or_default().as_str(),
+                            request.priority.to_string().as_str(),
+                            task.content_length(),
+                            request.range,
+                            start_time.elapsed(),
+                        );
+
+                        // Download cache task succeeded.
+                        info!("download cache task succeeded");
+                        if request.range.is_none() {
+                            if let Some(output_path) = &request.output_path {
+                                let output_path = Path::new(output_path.as_str());
+                                if output_path.exists() {
+                                    error!(
+                                        "output path {} is already exists",
+                                        output_path.display()
+                                    );
+
+                                    handle_error(
+                                        &out_stream_tx,
+                                        Status::internal(format!(
+                                            "output path {} is already exists",
+                                            output_path.display()
+                                        )),
+                                    )
+                                    .await;
+                                    return;
+                                }
+
+                                if let Err(err) =
+                                    task_manager.copy_task(task_id.as_str(), output_path).await
+                                {
+                                    error!("copy cache task: {}", err);
+                                    handle_error(&out_stream_tx, err).await;
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                    Err(ClientError::BackendError(err)) => {
+                        // Collect download task failure metrics.
+                        collect_download_task_failure_metrics(
+                            TaskType::Cache as i32,
+                            request.tag.clone().unwrap_or_default().as_str(),
+                            request.application.clone().unwrap_or_default().as_str(),
+                            request.priority.to_string().as_str(),
+                        );
+
+                        match serde_json::to_vec::<Backend>(&Backend {
+                            message: err.message.clone(),
+                            header: headermap_to_hashmap(&err.header.clone().unwrap_or_default()),
+                            status_code: err.status_code.map(|code| code.as_u16() as i32),
+                        }) {
+                            Ok(json) => {
+                                handle_error(
+                                    &out_stream_tx,
+                                    Status::with_details(
+                                        Code::Internal,
+                                        err.to_string(),
+                                        json.into(),
+                                    ),
+                                )
+                                .await;
+                            }
+                            Err(err) => {
+                                error!("serialize error: {}", err);
+                                handle_error(&out_stream_tx, err).await;
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        error!("download cache task failed: {}", err);
+
+                        // Collect download task failure metrics.
+                        collect_download_task_failure_metrics(
+                            TaskType::Cache as i32,
+                            request.tag.clone().unwrap_or_default().as_str(),
+                            request.application.clone().unwrap_or_default().as_str(),
+                            request.priority.to_string().as_str(),
+                        );
+
+                        handle_error(&out_stream_tx, err).await;
+                    }
+                }
+
+                drop(out_stream_tx);
+            }
+            .in_current_span(),
+        );
+
+        Ok(Response::new(ReceiverStream::new(out_stream_rx)))
     }
 
-    /// stat_cache_task stats the cache task.
+    /// stat_cache_task gets the status of the cache task.
     #[instrument(skip_all, fields(host_id, task_id, remote_ip, local_only))]
     async fn stat_cache_task(
         &self,
-        _request: Request<StatCacheTaskRequest>,
+        request: Request<StatCacheTaskRequest>,
     ) -> Result<Response<CacheTask>, Status> {
-        todo!();
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Read)?;
+
+        // If the parent context is set, use it as the parent context for the span.
+        if let Some(parent_ctx) = request.extensions().get::<Context>() {
+            Span::current().set_parent(parent_ctx.clone());
+        };
+
+        // Clone the request.
+        let request = request.into_inner();
+
+        // Generate the host id.
+        let host_id = self.cache_task.id_generator.host_id();
+
+        // Get the task id from the request.
+        let task_id = request.task_id;
+
+        // Get the local_only flag from the request, default to false.
+        let local_only = request.local_only;
+
+        // Span record the host id and task id.
+        Span::current().record("host_id", host_id.as_str());
+        Span::current().record("task_id", task_id.as_str());
+        Span::current().record(
+            "remote_ip",
+            request.remote_ip.clone().unwrap_or_default().as_str(),
+        );
+        Span::current().record("local_only", local_only.to_string().as_str());
+        info!("stat cache task in upload server");
+
+        // Collect the stat cache task metrics.
+        collect_stat_task_started_metrics(TaskType::Cache as i32);
+
+        match self
+            .cache_task
+            .stat(task_id.as_str(), host_id.as_str(), local_only)
+            .await
+        {
+            Ok(task) => Ok(Response::new(task)),
+            Err(err) => {
+                // Collect the stat cache task failure metrics.
+                collect_stat_task_failure_metrics(TaskType::Cache as i32);
+
+                error!("stat cache task failed: {}", err);
+                Err(match err {
+                    ClientError::TaskNotFound(id) => {
+                        Status::not_found(format!("cache task not found: {}", id))
+                    }
+                    _ => Status::internal(err.to_string()),
+                })
+            }
+        }
     }
 
-    /// delete_cache_task deletes the cache task.
+    /// delete_cache_task calls the dfdaemon to delete the cache task.
     #[instrument(skip_all, fields(host_id, task_id, remote_ip))]
     async fn delete_cache_task(
         &self,
-        _request: Request<DeleteCacheTaskRequest>,
+        request: Request<DeleteCacheTaskRequest>,
     ) -> Result<Response<()>, Status> {
-        todo!();
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Write)?;
+
+        // If the parent context is set, use it as the parent context for the span.
+        if let Some(parent_ctx) = request.extensions().get::<Context>() {
+            Span::current().set_parent(parent_ctx.clone());
+        };
+
+        // Clone the request.
+        let request = request.into_inner();
+
+        // Generate the host id.
+        let host_id = self.cache_task.id_generator.host_id();
+
+        // Get the task id from the request.
+        let task_id = request.task_id;
+
+        // Span record the host id and task id.
+        Span::current().record("host_id", host_id.as_str());
+        Span::current().record("task_id", task_id.as_str());
+        Span::current().record(
+            "remote_ip",
+            request.remote_ip.clone().unwrap_or_default().as_str(),
+        );
+        info!("delete cache task in upload server");
+
+        // Collect the delete cache task started metrics.
+        collect_delete_task_started_metrics(TaskType::Cache as i32);
+
+        self.cache_task
+            .delete(task_id.as_str(), host_id.as_str())
+            .await
+            .map_err(|err| {
+                // Collect the delete cache task failure metrics.
+                collect_delete_task_failure_metrics(TaskType::Cache as i32);
+
+                error!("delete cache task: {}", err);
+                Status::internal(err.to_string())
+            })?;
+
+        Ok(Response::new(()))
     }
 
     /// SyncCachePiecesStream is the stream of the sync cache pieces response.
@@ -1732,9 +2157,129 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
     #[instrument(skip_all, fields(host_id, remote_host_id, task_id))]
     async fn sync_cache_pieces(
         &self,
-        _request: Request<SyncCachePiecesRequest>,
+        request: Request<SyncCachePiecesRequest>,
     ) -> Result<Response<Self::SyncCachePiecesStream>, Status> {
-        todo!();
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Read)?;
+
+        // If the parent context is set, use it as the parent context for the span.
+        if let Some(parent_ctx) = request.extensions().get::<Context>() {
+            Span::current().set_parent(parent_ctx.clone());
+        };
+
+        // Clone the request.
+        let request = request.into_inner();
+
+        // Generate the host id.
+        let host_id = self.cache_task.id_generator.host_id();
+
+        // Get the remote host id from the request.
+        let remote_host_id = request.host_id;
+
+        // Get the task id from tae request.
+        let task_id = request.task_id;
+
+        // Span record the host id and task id.
+        Span::current().record("host_id", host_id.clone());
+        Span::current().record("remote_host_id", remote_host_id.as_str());
+        Span::current().record("task_id", task_id.clone());
+        info!("sync cache pieces in upload server");
+
+        // Get the interested piece numbers from the request.
+        let mut interested_piece_numbers = request.interested_cache_piece_numbers.clone();
+
+        // Clone the cache task.
+        let task_manager = self.cache_task.clone();
+
+        // Initialize stream channel.
+        let (out_stream_tx, out_stream_rx) = mpsc::channel(10 * 1024);
+        tokio::spawn(
+            async move {
+                loop {
+                    let mut finished_piece_numbers = Vec::new();
+                    for interested_piece_number in interested_piece_numbers.iter() {
+                        let piece = match task_manager
+                            .piece
+                            .get_cache(task_id.as_str(), *interested_piece_number)
+                            .await
+                        {
+                            Ok(Some((piece, _))) => piece,
+                            Ok(None) => continue,
+                            Err(err) => {
+                                error!(
+                                    "send cache piece metadata {}-{}: {}",
+                                    task_id, interested_piece_number, err
+                                );
+                                out_stream_tx
+                                    .send_timeout(
+                                        Err(Status::internal(err.to_string())),
+                                        super::REQUEST_TIMEOUT,
+                                    )
+                                    .await
+                                    .unwrap_or_else(|err| {
+                                        error!(
+                                            "send cache piece metadata {}-{} to stream: {}",
+                                            task_id, interested_piece_number, err
+                                        );
+                                    });
+
+                                drop(out_stream_tx);
+                                return;
+                            }
+                        };
+
+                        // Send the piece metadata to the stream.
+                        match out_stream_tx
+                            .send_timeout(
+                                Ok(SyncCachePiecesResponse {
+                                    number: piece.number,
+                                    offset: piece.offset,
+                                    length: piece.length,
+                                }),
+                                super::REQUEST_TIMEOUT,
+                            )
+                            .await
+                        {
+                            Ok(_) => {
+                                info!("send cache piece metadata {}-{}", task_id, piece.number);
+                            }
+                            Err(err) => {
+                                error!(
+                                    "send cache piece metadata {}-{} to stream: {}",
+                                    task_id, interested_piece_number, err
+                                );
+
+                                drop(out_stream_tx);
+                                return;
+                            }
+                        }
+
+                        // Add the finished piece number to the finished piece numbers.
+                        finished_piece_numbers.push(piece.number);
+                    }
+
+                    // Remove the finished piece numbers from the interested piece numbers.
+                    interested_piece_numbers
+                        .retain(|number| !finished_piece_numbers.contains(number));
+
+                    // If all the interested pieces are finished, return.
+                    if interested_piece_numbers.is_empty() {
+                        info!("all the interested cache pieces are finished");
+                        drop(out_stream_tx);
+                        return;
+                    }
+
+                    // Wait for the piece to be finished.
+                    tokio::time::sleep(
+                        dragonfly_client_storage::DEFAULT_WAIT_FOR_PIECE_FINISHED_INTERVAL,
+                    )
+                    .await;
+                }
+            }
+            .in_current_span(),
+        );
+
+        Ok(Response::new(ReceiverStream::new(out_stream_rx)))
     }
 
     /// download_cache_piece provides the cache piece content for parent.
@@ -1744,9 +2289,90 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
     )]
     async fn download_cache_piece(
         &self,
-        _request: Request<DownloadCachePieceRequest>,
+        request: Request<DownloadCachePieceRequest>,
     ) -> Result<Response<DownloadCachePieceResponse>, Status> {
-        todo!();
+        // Authorize the request by the token and the remote address.
+        authorize(&self.auth, &request, Access::Read)?;
+
+        // If the parent context is set, use it as the parent context for the span.
+        if let Some(parent_ctx) = request.extensions().get::<Context>() {
+            Span::current().set_parent(parent_ctx.clone());
+        };
+
+        // Clone the request.
+        let request = request.into_inner();
+
+        // Generate the host id.
+        let host_id = self.cache_task.id_generator.host_id();
+
+        // Get the remote host id from the request.
+        let remote_host_id = request.host_id;
+
+        // Get the task id from the request.
+        let task_id = request.task_id;
+
+        // Get the interested piece number from the request.
+        let piece_number = request.piece_number;
+
+        // Generate the piece id.
+        let piece_id = self
+            .cache_task
+            .piece
+            .cache_id(task_id.as_str(), piece_number);
+
+        // Span record the host id, task id and piece number.
+        Span::current().record("host_id", host_id.as_str());
+        Span::current().record("remote_host_id", remote_host_id.as_str());
+        Span::current().record("task_id", task_id.as_str());
+        Span::current().record("piece_id", piece_id.as_str());
+        info!("download cache piece in upload server");
+
+        // Collect upload piece started metrics.
+        collect_upload_piece_started_metrics();
+        info!("start upload cache piece content");
+
+        // Get the piece metadata and content from the memory cache.
+        let (piece, content) = self
+            .cache_task
+            .piece
+            .upload_cache_from_local(piece_id.as_str(), task_id.as_str(), piece_number)
+            .await
+            .map_err(|err| {
+                // Collect upload piece failure metrics.
+                collect_upload_piece_failure_metrics();
+
+                error!("upload cache piece content from memory cache: {}", err);
+                match err {
+                    ClientError::TaskNotFound(_) | ClientError::PieceNotFound(_) => {
+                        Status::not_found(err.to_string())
+                    }
+                    _ => Status::internal(err.to_string()),
+                }
+            })?;
+
+        Span::current().record("piece_length", piece.length);
+
+        // Collect upload piece finished metrics.
+        collect_upload_piece_finished_metrics();
+        info!("finished upload cache piece content");
+
+        // Return the piece.
+        Ok(Response::new(DownloadCachePieceResponse {
+            piece: Some(Piece {
+                number: piece.number,
+                parent_id: piece.parent_id.clone(),
+                offset: piece.offset,
+                length: piece.length,
+                digest: piece.digest.clone(),
+                content: Some(content.to_vec()),
+                traffic_type: None,
+                cost: None,
+                created_at: None,
+            }),
+            // Calculate the digest of the piece metadata, including the number, offset, length and
+            // content digest. The digest is used to verify the integrity of the piece metadata.
+            digest: Some(piece.calculate_digest()),
+        }))
     }
 }
 
@@ -1754,7 +2380,7 @@ impl DfdaemonUpload for DfdaemonUploadServerHandler {
 #[derive(Clone)]
 pub struct DfdaemonUploadClient {
     /// client is the grpc client of the dfdaemon upload.
-    pub client: DfdaemonUploadGRPCClient<InterceptedService<Channel, InjectTracingInterceptor>>,
+    pub client: DfdaemonUploadGRPCClient<InterceptedService<Channel, InjectTokenInterceptor>>,
 }
 
 /// DfdaemonUploadClient implements the dfdaemon upload grpc client.
@@ -1818,9 +2444,12 @@ impl DfdaemonUploadClient {
                 .or_err(ErrorType::ConnectError)?,
         };
 
-        let client = DfdaemonUploadGRPCClient::with_interceptor(channel, InjectTracingInterceptor)
-            .max_decoding_message_size(usize::MAX)
-            .max_encoding_message_size(usize::MAX);
+        let client = DfdaemonUploadGRPCClient::with_interceptor(
+            channel,
+            InjectTokenInterceptor::new(config.upload.client.token.as_deref()),
+        )
+        .max_decoding_message_size(usize::MAX)
+        .max_encoding_message_size(usize::MAX);
         Ok(Self { client })
     }
 
@@ -1850,6 +2479,14 @@ impl DfdaemonUploadClient {
         Ok(response)
     }
 
+    /// stat_task gets the status of the task from the parent.
+    #[instrument(skip_all)]
+    pub async fn stat_task(&self, request: StatTaskRequest) -> ClientResult<Task> {
+        let request = Self::make_request(request);
+        let response = self.client.clone().stat_task(request).await?;
+        Ok(response.into_inner())
+    }
+
     /// sync_pieces provides the piece metadata for parent.
     #[instrument(skip_all)]
     pub async fn sync_pieces(
@@ -1980,6 +2617,31 @@ impl DfdaemonUploadClient {
         Ok(response.into_inner())
     }
 
+    /// sync_cache_pieces provides the cache piece metadata for parent.
+    #[instrument(skip_all)]
+    pub async fn sync_cache_pieces(
+        &self,
+        request: SyncCachePiecesRequest,
+    ) -> ClientResult<tonic::Response<tonic::codec::Streaming<SyncCachePiecesResponse>>> {
+        let request = Self::make_request(request);
+        let response = self.client.clone().sync_cache_pieces(request).await?;
+        Ok(response)
+    }
+
+    /// download_cache_piece provides the cache piece content for parent.
+    #[instrument(skip_all)]
+    pub async fn download_cache_piece(
+        &self,
+        request: DownloadCachePieceRequest,
+        timeout: Duration,
+    ) -> ClientResult<DownloadCachePieceResponse> {
+        let mut request = tonic::Request::new(request);
+        request.set_timeout(timeout);
+
+        let response = self.client.clone().download_cache_piece(request).await?;
+        Ok(response.into_inner())
+    }
+
     /// exchange_ib_verbs_queue_pair_endpoint exchanges ib verbs queue pair endpoint.
     #[instrument(skip_all)]
     pub async fn exchange_ib_verbs_queue_pair_endpoint(
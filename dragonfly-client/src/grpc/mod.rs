@@ -24,6 +24,7 @@ use std::time::Duration;
 use tonic::Request;
 use tracing::{error, info, instrument, Instrument};
 
+pub mod auth;
 pub mod dfdaemon_download;
 pub mod dfdaemon_upload;
 pub mod health;
@@ -15,16 +15,17 @@
  */
 
 use crate::dynconfig::Dynconfig;
-use dragonfly_api::common::v2::{Peer, PersistentCachePeer, PersistentCacheTask, Task};
+use dragonfly_api::common::v2::{CacheTask, Peer, PersistentCachePeer, PersistentCacheTask, Task};
 use dragonfly_api::manager::v2::Scheduler;
 use dragonfly_api::scheduler::v2::{
-    scheduler_client::SchedulerClient as SchedulerGRPCClient, AnnounceHostRequest,
-    AnnouncePeerRequest, AnnouncePeerResponse, AnnouncePersistentCachePeerRequest,
-    AnnouncePersistentCachePeerResponse, DeleteHostRequest, DeletePeerRequest,
-    DeletePersistentCachePeerRequest, DeletePersistentCacheTaskRequest, DeleteTaskRequest,
-    StatPeerRequest, StatPersistentCachePeerRequest, StatPersistentCacheTaskRequest,
-    StatTaskRequest, UploadPersistentCacheTaskFailedRequest,
-    UploadPersistentCacheTaskFinishedRequest, UploadPersistentCacheTaskStartedRequest,
+    scheduler_client::SchedulerClient as SchedulerGRPCClient, AnnounceCachePeerRequest,
+    AnnounceCachePeerResponse, AnnounceHostRequest, AnnouncePeerRequest, AnnouncePeerResponse,
+    AnnouncePersistentCachePeerRequest, AnnouncePersistentCachePeerResponse,
+    DeleteCacheTaskRequest, DeleteHostRequest, DeletePeerRequest, DeletePersistentCachePeerRequest,
+    DeletePersistentCacheTaskRequest, DeleteTaskRequest, StatCacheTaskRequest, StatPeerRequest,
+    StatPersistentCachePeerRequest, StatPersistentCacheTaskRequest, StatTaskRequest,
+    UploadPersistentCacheTaskFailedRequest, UploadPersistentCacheTaskFinishedRequest,
+    UploadPersistentCacheTaskStartedRequest,
 };
 use dragonfly_client_config::dfdaemon::Config;
 use dragonfly_client_core::error::{ErrorType, OrErr};
@@ -329,6 +330,47 @@ impl SchedulerClient {
         Ok(())
     }
 
+    /// announce_cache_peer announces the cache peer to the scheduler.
+    #[instrument(skip_all)]
+    pub async fn announce_cache_peer(
+        &self,
+        task_id: &str,
+        peer_id: &str,
+        request: impl tonic::IntoStreamingRequest<Message = AnnounceCachePeerRequest>,
+    ) -> Result<tonic::Response<tonic::codec::Streaming<AnnounceCachePeerResponse>>> {
+        let response = self
+            .client(task_id, Some(peer_id))
+            .await?
+            .announce_cache_peer(request)
+            .await?;
+        Ok(response)
+    }
+
+    /// stat_cache_task gets the status of the cache task.
+    #[instrument(skip(self))]
+    pub async fn stat_cache_task(&self, request: StatCacheTaskRequest) -> Result<CacheTask> {
+        let task_id = request.task_id.clone();
+        let request = Self::make_request(request);
+        let response = self
+            .client(task_id.as_str(), None)
+            .await?
+            .stat_cache_task(request)
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    /// delete_cache_task tells the scheduler that the cache task is deleting.
+    #[instrument(skip(self))]
+    pub async fn delete_cache_task(&self, request: DeleteCacheTaskRequest) -> Result<()> {
+        let task_id = request.task_id.clone();
+        let request = Self::make_request(request);
+        self.client(task_id.as_str(), None)
+            .await?
+            .delete_cache_task(request)
+            .await?;
+        Ok(())
+    }
+
     /// announce_persistent_cache_peer announces the persistent cache peer to the scheduler.
     #[instrument(skip_all)]
     pub async fn announce_persistent_cache_peer(
@@ -24,4 +24,5 @@ pub mod proxy;
 pub mod resource;
 pub mod shutdown;
 pub mod stats;
+pub mod tcp;
 pub mod tracing;
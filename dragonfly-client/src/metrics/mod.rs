@@ -163,6 +163,28 @@ lazy_static! {
             &["scheme", "method"]
         ).expect("metric can be created");
 
+    /// CONCURRENT_BACKEND_ORIGIN_REQUEST_GAUGE is used to gauge the number of concurrent back-to-source requests of the origin.
+    pub static ref CONCURRENT_BACKEND_ORIGIN_REQUEST_GAUGE: IntGaugeVec =
+        IntGaugeVec::new(
+            Opts::new("concurrent_backend_origin_request_total", "Gauge of the number of concurrent back-to-source requests of the origin.").namespace(dragonfly_client_config::SERVICE_NAME).subsystem(dragonfly_client_config::NAME),
+            &["origin"]
+        ).expect("metric can be created");
+
+    /// BACKEND_ORIGIN_REJECTED_REQUEST_COUNT is used to count the number of back-to-source requests rejected by the origin protection.
+    pub static ref BACKEND_ORIGIN_REJECTED_REQUEST_COUNT: IntCounterVec =
+        IntCounterVec::new(
+            Opts::new("backend_origin_rejected_request_total", "Counter of the number of the back-to-source requests rejected by the origin protection.").namespace(dragonfly_client_config::SERVICE_NAME).subsystem(dragonfly_client_config::NAME),
+            &["origin", "reason"]
+        ).expect("metric can be created");
+
+    /// BACKEND_ORIGIN_CIRCUIT_BREAKER_STATE is used to gauge the circuit breaker state of the origin,
+    /// 0 is closed, 1 is open and 2 is half open.
+    pub static ref BACKEND_ORIGIN_CIRCUIT_BREAKER_STATE: IntGaugeVec =
+        IntGaugeVec::new(
+            Opts::new("backend_origin_circuit_breaker_state", "Gauge of the circuit breaker state of the origin, 0 is closed, 1 is open and 2 is half open.").namespace(dragonfly_client_config::SERVICE_NAME).subsystem(dragonfly_client_config::NAME),
+            &["origin"]
+        ).expect("metric can be created");
+
     /// PROXY_REQUEST_COUNT is used to count the number of proxy requset.
     pub static ref PROXY_REQUEST_COUNT: IntCounterVec =
         IntCounterVec::new(
@@ -267,6 +289,14 @@ lazy_static! {
             Opts::new("disk_usage_space_total", "Gauge of the disk usage space in bytes").namespace(dragonfly_client_config::SERVICE_NAME).subsystem(dragonfly_client_config::NAME),
             &[]
         ).expect("metric can be created");
+
+    /// METADATA_MIGRATION_DROPPED_OBJECT_COUNT is used to count the metadata objects which can not be
+    /// migrated to the current schema and are dropped.
+    pub static ref METADATA_MIGRATION_DROPPED_OBJECT_COUNT: IntCounterVec =
+        IntCounterVec::new(
+            Opts::new("metadata_migration_dropped_object_total", "Counter of the number of the dropped objects of the metadata migration.").namespace(dragonfly_client_config::SERVICE_NAME).subsystem(dragonfly_client_config::NAME),
+            &["namespace"]
+        ).expect("metric can be created");
 }
 
 /// register_custom_metrics registers all custom metrics.
@@ -323,6 +353,18 @@ fn register_custom_metrics() {
         .register(Box::new(BACKEND_REQUEST_DURATION.clone()))
         .expect("metric can be registered");
 
+    REGISTRY
+        .register(Box::new(CONCURRENT_BACKEND_ORIGIN_REQUEST_GAUGE.clone()))
+        .expect("metric can be registered");
+
+    REGISTRY
+        .register(Box::new(BACKEND_ORIGIN_REJECTED_REQUEST_COUNT.clone()))
+        .expect("metric can be registered");
+
+    REGISTRY
+        .register(Box::new(BACKEND_ORIGIN_CIRCUIT_BREAKER_STATE.clone()))
+        .expect("metric can be registered");
+
     REGISTRY
         .register(Box::new(PROXY_REQUEST_COUNT.clone()))
         .expect("metric can be registered");
@@ -382,6 +424,10 @@ fn register_custom_metrics() {
     REGISTRY
         .register(Box::new(DISK_USAGE_SPACE.clone()))
         .expect("metric can be registered");
+
+    REGISTRY
+        .register(Box::new(METADATA_MIGRATION_DROPPED_OBJECT_COUNT.clone()))
+        .expect("metric can be registered");
 }
 
 /// reset_custom_metrics resets all custom metrics.
@@ -399,6 +445,9 @@ fn reset_custom_metrics() {
     BACKEND_REQUEST_COUNT.reset();
     BACKEND_REQUEST_FAILURE_COUNT.reset();
     BACKEND_REQUEST_DURATION.reset();
+    CONCURRENT_BACKEND_ORIGIN_REQUEST_GAUGE.reset();
+    BACKEND_ORIGIN_REJECTED_REQUEST_COUNT.reset();
+    BACKEND_ORIGIN_CIRCUIT_BREAKER_STATE.reset();
     PROXY_REQUEST_COUNT.reset();
     PROXY_REQUEST_FAILURE_COUNT.reset();
     PROXY_REQUEST_VIA_DFDAEMON_COUNT.reset();
@@ -414,6 +463,7 @@ fn reset_custom_metrics() {
     DELETE_HOST_FAILURE_COUNT.reset();
     DISK_SPACE.reset();
     DISK_USAGE_SPACE.reset();
+    METADATA_MIGRATION_DROPPED_OBJECT_COUNT.reset();
 }
 
 /// TaskSize represents the size of the task.
@@ -727,6 +777,56 @@ pub fn collect_backend_request_finished_metrics(scheme: &str, method: &str, cost
         .observe(cost.as_millis() as f64);
 }
 
+/// collect_backend_origin_request_started_metrics collects the back-to-source request started
+/// metrics of the origin.
+pub fn collect_backend_origin_request_started_metrics(origin: &str) {
+    CONCURRENT_BACKEND_ORIGIN_REQUEST_GAUGE
+        .with_label_values(&[origin])
+        .inc();
+}
+
+/// collect_backend_origin_request_finished_metrics collects the back-to-source request finished
+/// metrics of the origin.
+pub fn collect_backend_origin_request_finished_metrics(origin: &str) {
+    CONCURRENT_BACKEND_ORIGIN_REQUEST_GAUGE
+        .with_label_values(&[origin])
+        .dec();
+}
+
+/// collect_backend_origin_request_rejected_metrics collects the back-to-source request rejected
+/// metrics of the origin.
+pub fn collect_backend_origin_request_rejected_metrics(origin: &str, reason: &str) {
+    BACKEND_ORIGIN_REJECTED_REQUEST_COUNT
+        .with_label_values(&[origin, reason])
+        .inc();
+}
+
+/// collect_backend_origin_circuit_breaker_state_metrics collects the circuit breaker state
+/// metrics of the origin.
+pub fn collect_backend_origin_circuit_breaker_state_metrics(origin: &str, state: i64) {
+    BACKEND_ORIGIN_CIRCUIT_BREAKER_STATE
+        .with_label_values(&[origin])
+        .set(state);
+}
+
+/// remove_backend_origin_metrics removes the metrics of the origin, it is called when the
+/// idle origin is evicted.
+pub fn remove_backend_origin_metrics(origin: &str) {
+    let _ = CONCURRENT_BACKEND_ORIGIN_REQUEST_GAUGE.remove_label_values(&[origin]);
+    let _ = BACKEND_ORIGIN_CIRCUIT_BREAKER_STATE.remove_label_values(&[origin]);
+    for reason in ["retry_after", "circuit_breaker_open", "limit_exceeded"] {
+        let _ = BACKEND_ORIGIN_REJECTED_REQUEST_COUNT.remove_label_values(&[origin, reason]);
+    }
+}
+
+/// collect_metadata_migration_dropped_object_metrics collects the dropped object metrics of
+/// the metadata migration.
+pub fn collect_metadata_migration_dropped_object_metrics(namespace: &str, count: usize) {
+    METADATA_MIGRATION_DROPPED_OBJECT_COUNT
+        .with_label_values(&[namespace])
+        .inc_by(count as u64);
+}
+
 /// collect_proxy_request_started_metrics collects the proxy request started metrics.
 pub fn collect_proxy_request_started_metrics() {
     PROXY_REQUEST_COUNT.with_label_values(&[]).inc();
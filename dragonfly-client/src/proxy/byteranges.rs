@@ -0,0 +1,199 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use dragonfly_api::common::v2::Range;
+
+/// MAX_BYTE_RANGES is the max number of the ranges of the request with multiple ranges, the
+/// request with more ranges is responded with the entire content, as the common http servers.
+pub const MAX_BYTE_RANGES: usize = 64;
+
+/// ByteRanges is the multipart/byteranges body of the response for the request with
+/// multiple ranges, refer to https://www.rfc-editor.org/rfc/rfc7233#appendix-A.
+pub struct ByteRanges {
+    /// boundary is the boundary between the parts.
+    boundary: String,
+
+    /// content_type is the content type of the task, it is the content type of every part.
+    content_type: Option<String>,
+
+    /// content_length is the content length of the task.
+    content_length: u64,
+
+    /// ranges are the ranges of the parts sorted by the start offset.
+    ranges: Vec<Range>,
+}
+
+/// ByteRanges implements the multipart/byteranges body.
+impl ByteRanges {
+    /// new creates a new ByteRanges.
+    pub fn new(content_type: Option<String>, content_length: u64, ranges: Vec<Range>) -> Self {
+        Self {
+            boundary: uuid::Uuid::new_v4().simple().to_string(),
+            content_type,
+            content_length,
+            ranges,
+        }
+    }
+
+    /// content_type returns the content type of the multipart/byteranges body.
+    pub fn content_type(&self) -> String {
+        format!("multipart/byteranges; boundary={}", self.boundary)
+    }
+
+    /// length returns the length of the multipart/byteranges body.
+    pub fn length(&self) -> u64 {
+        let mut length = self.closing().len() as u64;
+        for (index, range) in self.ranges.iter().enumerate() {
+            length += self.part_header(index, range).len() as u64 + range.length;
+        }
+
+        length
+    }
+
+    /// ranges returns the ranges of the parts.
+    pub fn ranges(&self) -> &[Range] {
+        &self.ranges
+    }
+
+    /// groups groups the parts whose ranges fall in the same pieces, the parts of a group are
+    /// downloaded by one download task request with the range covering them, so the pieces
+    /// shared by the parts are not requested repeatedly. The ranges are sorted and do not
+    /// overlap, the part is grouped with the previous part if it starts in the piece where
+    /// the previous part ends.
+    pub fn groups(&self, piece_length: u64) -> Vec<std::ops::Range<usize>> {
+        let piece_length = piece_length.max(1);
+        let is_same_piece = |last: &Range, range: &Range| {
+            (last.start + last.length - 1) / piece_length == range.start / piece_length
+        };
+
+        let mut groups: Vec<std::ops::Range<usize>> = Vec::new();
+        for (index, range) in self.ranges.iter().enumerate() {
+            match groups.last_mut() {
+                Some(group) if is_same_piece(&self.ranges[group.end - 1], range) => {
+                    group.end = index + 1;
+                }
+                _ => groups.push(index..index + 1),
+            }
+        }
+
+        groups
+    }
+
+    /// part_header returns the boundary and the headers of the part.
+    pub fn part_header(&self, index: usize, range: &Range) -> String {
+        let mut header = String::new();
+        if index > 0 {
+            header.push_str("\r\n");
+        }
+
+        header.push_str(&format!("--{}\r\n", self.boundary));
+        if let Some(content_type) = self.content_type.as_ref() {
+            header.push_str(&format!("Content-Type: {}\r\n", content_type));
+        }
+
+        header.push_str(&format!(
+            "Content-Range: bytes {}-{}/{}\r\n\r\n",
+            range.start,
+            range.start + range.length - 1,
+            self.content_length
+        ));
+        header
+    }
+
+    /// closing returns the closing boundary of the body.
+    pub fn closing(&self) -> String {
+        format!("\r\n--{}--\r\n", self.boundary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_make_byte_ranges() {
+        let mut byte_ranges = ByteRanges::new(
+            Some("text/plain".to_string()),
+            26,
+            vec![
+                Range {
+                    start: 2,
+                    length: 3,
+                },
+                Range {
+                    start: 10,
+                    length: 2,
+                },
+            ],
+        );
+        byte_ranges.boundary = "boundary".to_string();
+
+        let mut body = String::new();
+        for (index, (range, content)) in byte_ranges.ranges().iter().zip(["cde", "kl"]).enumerate()
+        {
+            body.push_str(&byte_ranges.part_header(index, range));
+            body.push_str(content);
+        }
+        body.push_str(&byte_ranges.closing());
+
+        let expected = "--boundary\r\nContent-Type: text/plain\r\nContent-Range: bytes 2-4/26\r\n\r\ncde\r\n--boundary\r\nContent-Type: text/plain\r\nContent-Range: bytes 10-11/26\r\n\r\nkl\r\n--boundary--\r\n";
+        assert_eq!(body, expected);
+        assert_eq!(byte_ranges.length(), expected.len() as u64);
+        assert_eq!(
+            byte_ranges.content_type(),
+            "multipart/byteranges; boundary=boundary"
+        );
+    }
+
+    #[test]
+    fn should_group_ranges_in_same_pieces() {
+        let byte_ranges = ByteRanges::new(
+            None,
+            100,
+            [(0, 1), (2, 1), (9, 2), (30, 5), (39, 1), (80, 10)]
+                .into_iter()
+                .map(|(start, length)| Range { start, length })
+                .collect(),
+        );
+
+        assert_eq!(byte_ranges.groups(10), vec![0..3, 3..5, 5..6]);
+        assert_eq!(byte_ranges.groups(100), vec![0..6]);
+        assert_eq!(
+            byte_ranges.groups(1),
+            vec![0..1, 1..2, 2..3, 3..4, 4..5, 5..6]
+        );
+    }
+
+    #[test]
+    fn should_make_part_header_without_content_type() {
+        let byte_ranges = ByteRanges::new(
+            None,
+            26,
+            vec![Range {
+                start: 0,
+                length: 10,
+            }],
+        );
+
+        assert_eq!(
+            byte_ranges.part_header(0, &byte_ranges.ranges()[0]),
+            format!(
+                "--{}\r\nContent-Range: bytes 0-9/26\r\n\r\n",
+                byte_ranges.boundary
+            )
+        );
+    }
+}
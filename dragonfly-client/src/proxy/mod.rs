@@ -21,17 +21,22 @@ use crate::metrics::{
 };
 use crate::resource::{piece::MIN_PIECE_LENGTH, task::Task};
 use crate::shutdown;
+use byteranges::{ByteRanges, MAX_BYTE_RANGES};
 use bytes::Bytes;
-use dragonfly_api::common::v2::{Download, TaskType};
+use dragonfly_api::common::v2::{Download, Range, TaskType};
 use dragonfly_api::dfdaemon::v2::{
-    download_task_response, DownloadTaskRequest, DownloadTaskStartedResponse,
+    download_task_response, DownloadTaskRequest, DownloadTaskResponse, DownloadTaskStartedResponse,
 };
 use dragonfly_api::errordetails::v2::Backend;
 use dragonfly_client_config::dfdaemon::{Config, Rule};
 use dragonfly_client_core::error::{ErrorType, OrErr};
 use dragonfly_client_core::{Error as ClientError, Result as ClientResult};
 use dragonfly_client_util::{
-    http::{hashmap_to_headermap, headermap_to_hashmap},
+    http::{
+        hashmap_to_headermap, headermap_to_hashmap, is_if_none_match_matched, is_if_range_matched,
+        is_multi_range, parse_multi_range_header,
+    },
+    id_generator::TaskIDParameter,
     tls::{generate_self_signed_certs_by_ca_cert, generate_simple_self_signed_certs, NoVerifier},
 };
 use futures::TryStreamExt;
@@ -53,14 +58,16 @@ use rustls_pki_types::CertificateDer;
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::io::{AsyncWriteExt, BufReader, BufWriter};
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
 use tokio::net::TcpListener;
 use tokio::net::TcpStream;
 use tokio::sync::{mpsc, Barrier};
 use tokio_rustls::TlsAcceptor;
 use tokio_util::io::ReaderStream;
-use tracing::{debug, error, info, instrument, Instrument, Span};
+use tonic::codec::Streaming;
+use tracing::{debug, error, info, instrument, warn, Instrument, Span};
 
+pub mod byteranges;
 pub mod header;
 
 lazy_static! {
@@ -693,8 +700,16 @@ async fn proxy_via_dfdaemon(
     // Collect the metrics for the proxy request via dfdaemon.
     collect_proxy_request_via_dfdaemon_metrics();
 
+    // Get the conditional headers and the multiple ranges of the request, they are handled
+    // by the proxy with the response header of the task.
+    let has_range = request.headers().contains_key(reqwest::header::RANGE);
+    let if_none_match = get_header_value(request.headers(), reqwest::header::IF_NONE_MATCH);
+    let if_range = get_header_value(request.headers(), reqwest::header::IF_RANGE);
+    let mut multi_range = get_header_value(request.headers(), reqwest::header::RANGE)
+        .filter(|range| is_multi_range(range));
+
     // Make the download task request.
-    let download_task_request =
+    let mut download_task_request =
         match make_download_task_request(config.clone(), rule, request, remote_ip) {
             Ok(download_task_request) => download_task_request,
             Err(err) => {
@@ -707,75 +722,105 @@ async fn proxy_via_dfdaemon(
             }
         };
 
-    // Download the task by the dfdaemon download client.
-    let response = match dfdaemon_download_client
-        .download_task(download_task_request)
-        .await
-    {
-        Ok(response) => response,
-        Err(err) => match err {
-            ClientError::TonicStatus(err) => {
-                match serde_json::from_slice::<Backend>(err.details()) {
-                    Ok(backend) => {
-                        error!("download task failed: {:?}", backend);
-                        return Ok(make_error_response(
-                            header::ErrorType::Backend,
-                            http::StatusCode::from_u16(
-                                backend.status_code.unwrap_or_default() as u16
-                            )
-                            .unwrap_or(http::StatusCode::INTERNAL_SERVER_ERROR),
-                            Some(hashmap_to_headermap(&backend.header)?),
-                        ));
-                    }
-                    Err(_) => {
-                        error!("download task failed: {}", err);
-                        return Ok(make_error_response(
-                            header::ErrorType::Dfdaemon,
-                            http::StatusCode::INTERNAL_SERVER_ERROR,
-                            None,
-                        ));
-                    }
-                };
+    // Evaluate the conditional headers and resolve the multiple ranges by the response header
+    // of the task before the download is started, so the not modified response does not
+    // trigger downloading the task.
+    if if_none_match.is_some() || (has_range && if_range.is_some()) || multi_range.is_some() {
+        let (content_length, response_header) = match head_task(&task, &download_task_request).await
+        {
+            Ok(head) => head,
+            Err(err) => {
+                error!("head task failed: {}", err);
+                return Ok(make_head_task_error_response(err));
             }
-            _ => {
-                error!("download task failed: {}", err);
-                return Ok(make_error_response(
-                    header::ErrorType::Dfdaemon,
-                    http::StatusCode::INTERNAL_SERVER_ERROR,
-                    None,
-                ));
+        };
+
+        // Return the not modified response if the entity tag of the task matches.
+        if let Some(if_none_match) = if_none_match.as_ref() {
+            if is_if_none_match_matched(if_none_match, &response_header) {
+                debug!(
+                    "if-none-match {} matched, response not modified",
+                    if_none_match
+                );
+                return Ok(make_not_modified_response(&response_header));
             }
-        },
-    };
+        }
 
-    // Handle the response from the download grpc server.
-    let mut out_stream = response.into_inner();
-    let Ok(Some(message)) = out_stream.message().await else {
-        error!("response message failed");
-        return Ok(make_error_response(
-            header::ErrorType::Dfdaemon,
-            http::StatusCode::INTERNAL_SERVER_ERROR,
-            None,
-        ));
-    };
+        // If the validator of the If-Range header does not match the task, the range is
+        // ignored and the entire content of the task is downloaded.
+        if let Some(if_range) = if_range.as_ref() {
+            if has_range && !is_if_range_matched(if_range, &response_header) {
+                debug!("if-range {} not matched, download entire content", if_range);
+                if let Some(download) = download_task_request.download.as_mut() {
+                    download
+                        .request_header
+                        .remove(reqwest::header::RANGE.as_str());
+                    download.prefetch = false;
+                }
 
-    // Span record the host_id, task_id, and peer_id.
-    Span::current().record("host_id", message.host_id.as_str());
-    Span::current().record("task_id", message.task_id.as_str());
-    Span::current().record("peer_id", message.peer_id.as_str());
+                multi_range = None;
+            }
+        }
 
-    // Handle the download task started response.
-    let Some(download_task_response::Response::DownloadTaskStartedResponse(
-        download_task_started_response,
-    )) = message.response
-    else {
-        error!("response is not started");
-        return Ok(make_error_response(
-            header::ErrorType::Dfdaemon,
-            http::StatusCode::INTERNAL_SERVER_ERROR,
-            None,
-        ));
-    };
+        // Respond the parts of the multiple ranges in the multipart/byteranges body.
+        if let Some(multi_range) = multi_range {
+            let ranges = match parse_multi_range_header(&multi_range, content_length) {
+                Ok(ranges) => ranges,
+                Err(err) => {
+                    error!("parse multiple ranges failed: {}", err);
+                    let mut header = http::HeaderMap::new();
+                    header.insert(
+                        reqwest::header::CONTENT_RANGE,
+                        format!("bytes */{}", content_length)
+                            .parse()
+                            .or_err(ErrorType::ParseError)?,
+                    );
+
+                    return Ok(make_error_response(
+                        header::ErrorType::Proxy,
+                        http::StatusCode::RANGE_NOT_SATISFIABLE,
+                        Some(header),
+                    ));
+                }
+            };
+
+            // The request with too many ranges is responded with the entire content, so a
+            // single request can not fan out into lots of download task requests.
+            if ranges.len() > MAX_BYTE_RANGES {
+                warn!(
+                    "{} ranges exceed the max {}, download entire content",
+                    ranges.len(),
+                    MAX_BYTE_RANGES
+                );
+                if let Some(download) = download_task_request.download.as_mut() {
+                    download
+                        .request_header
+                        .remove(reqwest::header::RANGE.as_str());
+                    download.prefetch = false;
+                }
+            } else {
+                return proxy_byte_ranges_via_dfdaemon(
+                    config,
+                    task,
+                    download_task_request,
+                    ByteRanges::new(
+                        get_header_value(&response_header, reqwest::header::CONTENT_TYPE),
+                        content_length,
+                        ranges,
+                    ),
+                    dfdaemon_download_client,
+                )
+                .await;
+            }
+        }
+    }
+
+    // Download the task by the dfdaemon download client.
+    let (mut out_stream, task_id, download_task_started_response) =
+        match start_download_task(&dfdaemon_download_client, download_task_request).await {
+            Ok(started) => started,
+            Err(err) => return Ok(make_download_task_error_response(err)),
+        };
 
     // Write the status code to the writer.
     let (sender, mut receiver) = mpsc::channel(10 * 1024);
@@ -795,7 +840,7 @@ async fn proxy_via_dfdaemon(
     // Construct the response.
     let mut response = Response::new(boxed_body);
     *response.headers_mut() = make_response_headers(
-        message.task_id.as_str(),
+        task_id.as_str(),
         config.host.ip.unwrap(),
         download_task_started_response.clone(),
     )?;
@@ -984,6 +1029,373 @@ async fn proxy_via_dfdaemon(
     }
 }
 
+/// proxy_byte_ranges_via_dfdaemon proxies the request with multiple ranges via the dfdaemon,
+/// the ranges falling in the same pieces are grouped, and every group is downloaded by its own
+/// download task request with the range covering the group, so only the pieces of the ranges
+/// are downloaded.
+async fn proxy_byte_ranges_via_dfdaemon(
+    config: Arc<Config>,
+    task: Arc<Task>,
+    download_task_request: DownloadTaskRequest,
+    byte_ranges: ByteRanges,
+    dfdaemon_download_client: DfdaemonDownloadClient,
+) -> ClientResult<Response> {
+    // The piece length is unknown before the download is started, the pieces are at least
+    // the min piece length and the piece length is a multiple of it, so the ranges in the
+    // same block of the min piece length are in the same piece.
+    let piece_length = download_task_request
+        .download
+        .as_ref()
+        .and_then(|download| download.piece_length)
+        .unwrap_or(MIN_PIECE_LENGTH);
+    let groups = byte_ranges.groups(piece_length);
+    let Some(first_group) = groups.first() else {
+        return Ok(make_error_response(
+            header::ErrorType::Proxy,
+            http::StatusCode::RANGE_NOT_SATISFIABLE,
+            None,
+        ));
+    };
+
+    // Download the first group before responding, so the error of the download is
+    // responded to the client.
+    let (out_stream, task_id, download_task_started_response) = match start_download_task(
+        &dfdaemon_download_client,
+        make_range_download_task_request(
+            &download_task_request,
+            &covering_range(&byte_ranges.ranges()[first_group.clone()]),
+        ),
+    )
+    .await
+    {
+        Ok(started) => started,
+        Err(err) => return Ok(make_download_task_error_response(err)),
+    };
+
+    // Construct the response headers, the multipart/byteranges body replaces the content
+    // range of the first group.
+    let mut response_headers = make_response_headers(
+        task_id.as_str(),
+        config.host.ip.unwrap(),
+        download_task_started_response.clone(),
+    )?;
+    response_headers.remove(reqwest::header::CONTENT_RANGE);
+    response_headers.insert(
+        reqwest::header::CONTENT_TYPE,
+        byte_ranges
+            .content_type()
+            .parse()
+            .or_err(ErrorType::ParseError)?,
+    );
+    response_headers.insert(reqwest::header::CONTENT_LENGTH, byte_ranges.length().into());
+
+    // Write the parts of the ranges to the reader.
+    let read_buffer_size = config.proxy.read_buffer_size;
+    let (reader, writer) = tokio::io::duplex(read_buffer_size);
+    let mut writer = BufWriter::with_capacity(read_buffer_size, writer);
+    tokio::spawn(
+        async move {
+            let mut started = Some((out_stream, download_task_started_response));
+            if let Err(err) = async {
+                for group in groups {
+                    let ranges = &byte_ranges.ranges()[group.clone()];
+                    let covering_range = covering_range(ranges);
+                    let (mut out_stream, download_task_started_response) = match started.take() {
+                        Some(started) => started,
+                        None => {
+                            let (out_stream, _, download_task_started_response) =
+                                start_download_task(
+                                    &dfdaemon_download_client,
+                                    make_range_download_task_request(
+                                        &download_task_request,
+                                        &covering_range,
+                                    ),
+                                )
+                                .await?;
+                            (out_stream, download_task_started_response)
+                        }
+                    };
+
+                    // Download the covering range into the pipe, and write the parts of the
+                    // group from the pipe with the content between the parts skipped.
+                    let (mut group_reader, mut group_writer) = tokio::io::duplex(read_buffer_size);
+                    let task = &task;
+                    let download = async move {
+                        let length = write_download_task(
+                            task,
+                            &mut out_stream,
+                            &download_task_started_response,
+                            &mut group_writer,
+                            read_buffer_size,
+                        )
+                        .await?;
+                        group_writer.shutdown().await?;
+                        if length != covering_range.length {
+                            return Err(ClientError::ContentLengthMismatch(
+                                covering_range.length,
+                                length,
+                            ));
+                        }
+
+                        Ok::<(), ClientError>(())
+                    };
+
+                    let byte_ranges = &byte_ranges;
+                    let writer = &mut writer;
+                    let parts = async move {
+                        let mut offset = covering_range.start;
+                        for (index, range) in group.zip(ranges) {
+                            tokio::io::copy(
+                                &mut (&mut group_reader).take(range.start - offset),
+                                &mut tokio::io::sink(),
+                            )
+                            .await?;
+                            writer
+                                .write_all(byte_ranges.part_header(index, range).as_bytes())
+                                .await?;
+                            let length = tokio::io::copy(
+                                &mut (&mut group_reader).take(range.length),
+                                writer,
+                            )
+                            .await?;
+                            if length != range.length {
+                                return Err(ClientError::ContentLengthMismatch(
+                                    range.length,
+                                    length,
+                                ));
+                            }
+
+                            offset = range.start + range.length;
+                        }
+
+                        Ok::<(), ClientError>(())
+                    };
+
+                    let (download_result, parts_result) = tokio::join!(download, parts);
+                    download_result?;
+                    parts_result?;
+                }
+
+                writer.write_all(byte_ranges.closing().as_bytes()).await?;
+                Ok::<(), ClientError>(())
+            }
+            .await
+            {
+                error!("write byte ranges error: {}", err);
+            }
+
+            writer.shutdown().await.unwrap_or_else(|err| {
+                error!("writer shutdown error: {}", err);
+            });
+        }
+        .in_current_span(),
+    );
+
+    // Construct the response.
+    let reader_stream = ReaderStream::with_capacity(reader, read_buffer_size);
+    let stream_body = StreamBody::new(reader_stream.map_ok(Frame::data).map_err(ClientError::from));
+    let mut response = Response::new(stream_body.boxed());
+    *response.headers_mut() = response_headers;
+    *response.status_mut() = http::StatusCode::PARTIAL_CONTENT;
+    Ok(response)
+}
+
+/// covering_range returns the range from the start of the first range to the end of the last
+/// range, the ranges are sorted by the start offset.
+fn covering_range(ranges: &[Range]) -> Range {
+    let start = ranges.first().map(|range| range.start).unwrap_or_default();
+    let end = ranges
+        .last()
+        .map(|range| range.start + range.length)
+        .unwrap_or_default();
+    Range {
+        start,
+        length: end - start,
+    }
+}
+
+/// make_range_download_task_request makes the download task request of the range.
+fn make_range_download_task_request(
+    download_task_request: &DownloadTaskRequest,
+    range: &Range,
+) -> DownloadTaskRequest {
+    let mut download_task_request = download_task_request.clone();
+    if let Some(download) = download_task_request.download.as_mut() {
+        download.request_header.insert(
+            reqwest::header::RANGE.to_string(),
+            format!("bytes={}-{}", range.start, range.start + range.length - 1),
+        );
+    }
+
+    download_task_request
+}
+
+/// write_download_task writes the content of the downloaded pieces to the writer in order of
+/// the piece number, and returns the length of the written content.
+async fn write_download_task<W: AsyncWrite + Unpin>(
+    task: &Task,
+    out_stream: &mut Streaming<DownloadTaskResponse>,
+    download_task_started_response: &DownloadTaskStartedResponse,
+    writer: &mut W,
+    read_buffer_size: usize,
+) -> ClientResult<u64> {
+    let Some(first_piece) = download_task_started_response.pieces.first() else {
+        error!("response pieces is empty");
+        return Err(ClientError::UnexpectedResponse);
+    };
+    let mut need_piece_number = first_piece.number;
+
+    // Write the pieces in order, the piece which is not in order is stored in the hashmap.
+    let mut finished_piece_readers = HashMap::new();
+    let mut length = 0;
+    while let Some(message) = out_stream.message().await? {
+        let Some(download_task_response::Response::DownloadPieceFinishedResponse(
+            download_piece_finished_response,
+        )) = message.response
+        else {
+            error!("response unknown message");
+            return Err(ClientError::UnexpectedResponse);
+        };
+
+        let Some(piece) = download_piece_finished_response.piece else {
+            error!("response piece is empty");
+            return Err(ClientError::UnexpectedResponse);
+        };
+
+        let piece_range_reader = task
+            .piece
+            .download_from_local_into_async_read(
+                task.piece
+                    .id(message.task_id.as_str(), piece.number)
+                    .as_str(),
+                message.task_id.as_str(),
+                piece.length,
+                download_task_started_response.range,
+                true,
+                false,
+            )
+            .await?;
+        finished_piece_readers.insert(
+            piece.number,
+            BufReader::with_capacity(read_buffer_size, piece_range_reader),
+        );
+
+        while let Some(mut piece_range_reader) = finished_piece_readers.remove(&need_piece_number) {
+            length += tokio::io::copy(&mut piece_range_reader, writer).await?;
+            need_piece_number += 1;
+        }
+    }
+
+    Ok(length)
+}
+
+/// head_task gets the content length and the response header of the task to download.
+async fn head_task(
+    task: &Task,
+    download_task_request: &DownloadTaskRequest,
+) -> ClientResult<(u64, http::HeaderMap)> {
+    let download = download_task_request
+        .download
+        .as_ref()
+        .ok_or(ClientError::InvalidParameter)?;
+
+    let task_id =
+        task.id_generator
+            .task_id(match download.content_for_calculating_task_id.clone() {
+                Some(content) => TaskIDParameter::Content(content),
+                None => TaskIDParameter::URLBased {
+                    url: download.url.clone(),
+                    piece_length: download.piece_length,
+                    tag: download.tag.clone(),
+                    application: download.application.clone(),
+                    filtered_query_params: download.filtered_query_params.clone(),
+                },
+            })?;
+
+    task.head(task_id.as_str(), download).await
+}
+
+/// make_head_task_error_response makes an error response by the error of getting the response
+/// header of the task.
+fn make_head_task_error_response(err: ClientError) -> Response {
+    if let ClientError::BackendError(err) = err {
+        return make_error_response(
+            header::ErrorType::Backend,
+            err.status_code
+                .unwrap_or(http::StatusCode::INTERNAL_SERVER_ERROR),
+            err.header,
+        );
+    }
+
+    make_error_response(
+        header::ErrorType::Dfdaemon,
+        http::StatusCode::INTERNAL_SERVER_ERROR,
+        None,
+    )
+}
+
+/// start_download_task starts to download the task by the dfdaemon download client, and returns
+/// the response stream, the task id and the started response.
+async fn start_download_task(
+    dfdaemon_download_client: &DfdaemonDownloadClient,
+    download_task_request: DownloadTaskRequest,
+) -> ClientResult<(
+    Streaming<DownloadTaskResponse>,
+    String,
+    DownloadTaskStartedResponse,
+)> {
+    let response = dfdaemon_download_client
+        .download_task(download_task_request)
+        .await
+        .inspect_err(|err| {
+            error!("download task failed: {}", err);
+        })?;
+
+    // Handle the response from the download grpc server.
+    let mut out_stream = response.into_inner();
+    let Ok(Some(message)) = out_stream.message().await else {
+        error!("response message failed");
+        return Err(ClientError::UnexpectedResponse);
+    };
+
+    // Span record the host_id, task_id, and peer_id.
+    Span::current().record("host_id", message.host_id.as_str());
+    Span::current().record("task_id", message.task_id.as_str());
+    Span::current().record("peer_id", message.peer_id.as_str());
+
+    // Handle the download task started response.
+    let Some(download_task_response::Response::DownloadTaskStartedResponse(
+        download_task_started_response,
+    )) = message.response
+    else {
+        error!("response is not started");
+        return Err(ClientError::UnexpectedResponse);
+    };
+
+    Ok((out_stream, message.task_id, download_task_started_response))
+}
+
+/// make_download_task_error_response makes an error response by the error of starting to
+/// download the task.
+fn make_download_task_error_response(err: ClientError) -> Response {
+    if let ClientError::TonicStatus(err) = err {
+        if let Ok(backend) = serde_json::from_slice::<Backend>(err.details()) {
+            return make_error_response(
+                header::ErrorType::Backend,
+                http::StatusCode::from_u16(backend.status_code.unwrap_or_default() as u16)
+                    .unwrap_or(http::StatusCode::INTERNAL_SERVER_ERROR),
+                hashmap_to_headermap(&backend.header).ok(),
+            );
+        }
+    }
+
+    make_error_response(
+        header::ErrorType::Dfdaemon,
+        http::StatusCode::INTERNAL_SERVER_ERROR,
+        None,
+    )
+}
+
 /// proxy_via_http proxies the HTTP request directly to the remote server.
 #[instrument(skip_all)]
 async fn proxy_via_http(request: Request<hyper::body::Incoming>) -> ClientResult<Response> {
@@ -1098,6 +1510,11 @@ fn make_download_task_request(
     // Registry will return the 403 status code if the Host header is set.
     header.remove(reqwest::header::HOST);
 
+    // The conditional headers are handled by the proxy with the response header of the task,
+    // so the backend always responds the content.
+    header.remove(reqwest::header::IF_NONE_MATCH);
+    header.remove(reqwest::header::IF_RANGE);
+
     // Validate the request arguments.
     let piece_length = header::get_piece_length(&header).map(|piece_length| piece_length.as_u64());
     if let Some(piece_length) = piece_length {
@@ -1235,6 +1652,36 @@ fn find_matching_rule(rules: Option<&[Rule]>, url: &str) -> Option<Rule> {
     rules?.iter().find(|rule| rule.regex.is_match(url)).cloned()
 }
 
+/// make_not_modified_response makes a not modified response with the validators and the cache
+/// headers of the task.
+fn make_not_modified_response(response_header: &http::HeaderMap) -> Response {
+    let mut response = Response::new(empty());
+    *response.status_mut() = http::StatusCode::NOT_MODIFIED;
+    for name in [
+        reqwest::header::CACHE_CONTROL,
+        reqwest::header::CONTENT_LOCATION,
+        reqwest::header::DATE,
+        reqwest::header::ETAG,
+        reqwest::header::EXPIRES,
+        reqwest::header::LAST_MODIFIED,
+        reqwest::header::VARY,
+    ] {
+        if let Some(value) = response_header.get(&name) {
+            response.headers_mut().insert(name, value.clone());
+        }
+    }
+
+    response
+}
+
+/// get_header_value returns the string value of the header by the name.
+fn get_header_value(header: &http::HeaderMap, name: http::header::HeaderName) -> Option<String> {
+    header
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
 /// make_error_response makes an error response with the given status and message.
 fn make_error_response(
     error_type: header::ErrorType,
@@ -0,0 +1,867 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::grpc::{scheduler::SchedulerClient, REQUEST_TIMEOUT};
+use crate::metrics::{
+    collect_backend_request_failure_metrics, collect_backend_request_finished_metrics,
+    collect_backend_request_started_metrics,
+};
+use dragonfly_api::common::v2::{
+    CachePeer, CacheTask as CommonCacheTask, Hdfs, ObjectStorage, Piece, SizeScope, TaskType,
+    TrafficType,
+};
+use dragonfly_api::dfdaemon::{
+    self,
+    v2::{download_cache_task_response, DownloadCacheTaskRequest, DownloadCacheTaskResponse},
+};
+use dragonfly_api::scheduler::v2::{
+    announce_cache_peer_request, announce_cache_peer_response, AnnounceCachePeerRequest,
+    AnnounceCachePeerResponse, DeleteCacheTaskRequest, DownloadCachePeerBackToSourceFailedRequest,
+    DownloadCachePeerBackToSourceFinishedRequest, DownloadCachePeerBackToSourceStartedRequest,
+    DownloadCachePeerFailedRequest, DownloadCachePeerFinishedRequest,
+    DownloadCachePeerStartedRequest, DownloadPieceBackToSourceFailedRequest,
+    DownloadPieceBackToSourceFinishedRequest, DownloadPieceFailedRequest,
+    DownloadPieceFinishedRequest, RegisterCachePeerRequest, RescheduleCachePeerRequest,
+    StatCacheTaskRequest,
+};
+use dragonfly_client_backend::{BackendFactory, HeadRequest};
+use dragonfly_client_config::dfdaemon::Config;
+use dragonfly_client_core::{error::BackendError, Error, Result as ClientResult};
+use dragonfly_client_storage::{cache, metadata, Storage};
+use dragonfly_client_util::{http::hashmap_to_headermap, id_generator::IDGenerator};
+use reqwest::header::HeaderMap;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc::{self, Sender};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{codec::Streaming, Request, Status};
+use tracing::{debug, error, info, instrument};
+
+use super::task::{Announcement, PartialDownloader, PieceRequest, ScheduleResponse, StorageKind};
+use super::*;
+
+/// CacheTask represents a cache task manager, the content of the cache task is stored
+/// in the memory cache instead of the disk.
+pub struct CacheTask {
+    /// config is the configuration of the dfdaemon.
+    config: Arc<Config>,
+
+    /// id_generator is the id generator.
+    pub id_generator: Arc<IDGenerator>,
+
+    /// storage is the local storage.
+    storage: Arc<Storage>,
+
+    /// scheduler_client is the grpc client of the scheduler.
+    pub scheduler_client: Arc<SchedulerClient>,
+
+    /// backend_factory is the backend factory.
+    pub backend_factory: Arc<BackendFactory>,
+
+    /// piece is the piece manager.
+    pub piece: Arc<piece::Piece>,
+
+    /// parent_selector is the parent selector.
+    pub parent_selector: Arc<parent_selector::ParentSelector>,
+}
+
+/// CacheTask implements the cache task manager.
+impl CacheTask {
+    /// new returns a new CacheTask.
+    pub fn new(
+        config: Arc<Config>,
+        id_generator: Arc<IDGenerator>,
+        storage: Arc<Storage>,
+        scheduler_client: Arc<SchedulerClient>,
+        backend_factory: Arc<BackendFactory>,
+        parent_selector: Arc<parent_selector::ParentSelector>,
+    ) -> ClientResult<Self> {
+        let piece = piece::Piece::new(
+            config.clone(),
+            id_generator.clone(),
+            storage.clone(),
+            backend_factory.clone(),
+        )?;
+        let piece = Arc::new(piece);
+
+        Ok(Self {
+            config,
+            id_generator,
+            storage: storage.clone(),
+            scheduler_client: scheduler_client.clone(),
+            backend_factory: backend_factory.clone(),
+            piece: piece.clone(),
+            parent_selector,
+        })
+    }
+
+    /// with_backend_factory returns a new CacheTask which downloads from the source by the
+    /// backend factory, the piece manager shares the rate limiters with the cache task.
+    pub fn with_backend_factory(&self, backend_factory: Arc<BackendFactory>) -> Self {
+        Self {
+            config: self.config.clone(),
+            id_generator: self.id_generator.clone(),
+            storage: self.storage.clone(),
+            scheduler_client: self.scheduler_client.clone(),
+            backend_factory: backend_factory.clone(),
+            piece: Arc::new(self.piece.with_backend_factory(backend_factory)),
+            parent_selector: self.parent_selector.clone(),
+        }
+    }
+
+    /// get gets the cache task from the memory cache.
+    #[instrument(skip_all)]
+    pub async fn get(&self, id: &str) -> Option<cache::Task> {
+        self.storage.get_cache_task(id).await
+    }
+
+    /// download_started creates the cache task in the memory cache when the cache task
+    /// downloads started.
+    #[instrument(skip_all)]
+    pub async fn download_started(
+        &self,
+        id: &str,
+        request: DownloadCacheTaskRequest,
+    ) -> ClientResult<cache::Task> {
+        // Record the access of the cache task once per download, the access frequency decides
+        // whether the cache task is admitted by the cache policy.
+        if let Some(task) = self.storage.access_cache_task(id).await {
+            return Ok(task);
+        }
+
+        // Handle the request header.
+        let mut request_header =
+            hashmap_to_headermap(&request.request_header).inspect_err(|err| {
+                error!("convert header: {}", err);
+            })?;
+
+        // Remove the range header to prevent the server from
+        // returning a 206 partial content and returning
+        // a 200 full content.
+        request_header.remove(reqwest::header::RANGE);
+
+        // Head the url to get the content length.
+        let backend = self.backend_factory.build(request.url.as_str())?;
+
+        // Record the start time.
+        let start_time = Instant::now();
+
+        // Collect the backend request started metrics.
+        collect_backend_request_started_metrics(
+            backend.scheme().as_str(),
+            http::Method::HEAD.as_str(),
+        );
+        let response = backend
+            .head(HeadRequest {
+                task_id: id.to_string(),
+                url: request.url,
+                http_header: Some(request_header),
+                timeout: self.config.download.piece_timeout,
+                client_cert: None,
+                object_storage: request.object_storage,
+                hdfs: request.hdfs,
+            })
+            .await
+            .inspect_err(|_err| {
+                // Collect the backend request failure metrics.
+                collect_backend_request_failure_metrics(
+                    backend.scheme().as_str(),
+                    http::Method::HEAD.as_str(),
+                );
+            })?;
+
+        // Check if the status code is success.
+        if !response.success {
+            // Collect the backend request failure metrics.
+            collect_backend_request_failure_metrics(
+                backend.scheme().as_str(),
+                http::Method::HEAD.as_str(),
+            );
+
+            return Err(Error::BackendError(Box::new(BackendError {
+                message: response.error_message.unwrap_or_default(),
+                status_code: response.http_status_code,
+                header: response.http_header,
+            })));
+        }
+
+        // Collect the backend request finished metrics.
+        collect_backend_request_finished_metrics(
+            backend.scheme().as_str(),
+            http::Method::HEAD.as_str(),
+            start_time.elapsed(),
+        );
+
+        // The empty cache task is not stored in the memory cache, so the content length
+        // must be greater than 0.
+        let content_length = match response.content_length {
+            Some(content_length) if content_length > 0 => content_length,
+            _ => return Err(Error::InvalidContentLength),
+        };
+
+        let piece_length = match request.piece_length {
+            Some(piece_length) => self
+                .piece
+                .calculate_piece_length(piece::PieceLengthStrategy::FixedPieceLength(piece_length)),
+            None => {
+                self.piece
+                    .calculate_piece_length(piece::PieceLengthStrategy::OptimizeByFileLength(
+                        content_length,
+                    ))
+            }
+        };
+
+        self.storage
+            .create_cache_task(id, piece_length, content_length)
+            .await
+    }
+
+    /// copy_task copies the cache task content from the memory cache to the destination.
+    #[instrument(skip_all)]
+    pub async fn copy_task(&self, id: &str, to: &Path) -> ClientResult<()> {
+        self.storage.copy_cache_task(id, to).await
+    }
+
+    /// download downloads a cache task. The task is held during the download, so the pieces
+    /// are still written into the task if it is evicted from the memory cache in the meantime.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip_all)]
+    pub async fn download(
+        &self,
+        task_id: &str,
+        task: &cache::Task,
+        host_id: &str,
+        peer_id: &str,
+        request: DownloadCacheTaskRequest,
+        download_progress_tx: Sender<Result<DownloadCacheTaskResponse, Status>>,
+    ) -> ClientResult<()> {
+        // Get the content length and the piece length from the task.
+        let content_length = task.content_length();
+        let piece_length = task.piece_length();
+
+        // Calculate the interested pieces to download.
+        let interested_pieces =
+            match self
+                .piece
+                .calculate_interested(piece_length, content_length, request.range)
+            {
+                Ok(interested_pieces) => interested_pieces,
+                Err(err) => {
+                    error!("calculate interested pieces error: {:?}", err);
+                    return Err(err);
+                }
+            };
+        debug!(
+            "interested pieces: {:?}",
+            interested_pieces
+                .iter()
+                .map(|p| p.number)
+                .collect::<Vec<u32>>()
+        );
+
+        // Construct the pieces for the download cache task started response.
+        let mut pieces = Vec::new();
+        for interested_piece in interested_pieces.clone() {
+            pieces.push(Piece {
+                number: interested_piece.number,
+                parent_id: interested_piece.parent_id.clone(),
+                offset: interested_piece.offset,
+                length: interested_piece.length,
+                digest: interested_piece.digest.clone(),
+                content: None,
+                traffic_type: None,
+                cost: interested_piece.prost_cost(),
+                created_at: Some(prost_wkt_types::Timestamp::from(
+                    interested_piece.created_at,
+                )),
+            });
+        }
+
+        // Send the download cache task started request.
+        download_progress_tx
+            .send_timeout(
+                Ok(DownloadCacheTaskResponse {
+                    host_id: host_id.to_string(),
+                    task_id: task_id.to_string(),
+                    peer_id: peer_id.to_string(),
+                    response: Some(
+                        download_cache_task_response::Response::DownloadCacheTaskStartedResponse(
+                            dfdaemon::v2::DownloadCacheTaskStartedResponse {
+                                content_length,
+                                range: request.range,
+                                response_header: HashMap::new(),
+                                pieces,
+                                is_finished: task.piece_count().await as u32
+                                    == self
+                                        .piece
+                                        .calculate_piece_count(piece_length, content_length),
+                            },
+                        ),
+                    ),
+                }),
+                REQUEST_TIMEOUT,
+            )
+            .await
+            .inspect_err(|err| {
+                error!("send DownloadCacheTaskStartedResponse failed: {:?}", err);
+            })?;
+
+        // Initialize the partial downloader of the pieces in the memory cache.
+        let downloader = PartialDownloader::<CacheStorage>::new(
+            self.config.clone(),
+            self.scheduler_client.clone(),
+            self.piece.clone(),
+            self.parent_selector.clone(),
+        );
+
+        // Download the pieces from the local.
+        debug!("download the cache pieces from local");
+        let finished_pieces = match downloader
+            .download_partial_from_local(
+                task_id,
+                host_id,
+                peer_id,
+                request.need_piece_content,
+                interested_pieces.clone(),
+                download_progress_tx.clone(),
+            )
+            .await
+        {
+            Ok(finished_pieces) => finished_pieces,
+            Err(err) => {
+                error!("download from local error: {:?}", err);
+                return Err(err);
+            }
+        };
+
+        // Remove the finished pieces from the pieces.
+        let interested_pieces = self
+            .piece
+            .remove_finished_from_interested(finished_pieces, interested_pieces);
+        info!(
+            "interested pieces after removing the finished piece: {:?}",
+            interested_pieces
+                .iter()
+                .map(|p| p.number)
+                .collect::<Vec<u32>>()
+        );
+
+        // Check if all pieces are downloaded.
+        if interested_pieces.is_empty() {
+            info!("all cache pieces are downloaded from local");
+            return Ok(());
+        };
+        debug!("download the cache pieces with scheduler");
+
+        // Download the pieces with scheduler.
+        let finished_pieces = match downloader
+            .download_partial_with_scheduler(
+                task_id,
+                host_id,
+                peer_id,
+                interested_pieces.clone(),
+                content_length,
+                AnnounceCachePeerRequest {
+                    host_id: host_id.to_string(),
+                    task_id: task_id.to_string(),
+                    peer_id: peer_id.to_string(),
+                    request: Some(
+                        announce_cache_peer_request::Request::RegisterCachePeerRequest(
+                            RegisterCachePeerRequest {
+                                url: request.url.clone(),
+                                digest: request.digest.clone(),
+                                range: request.range,
+                                r#type: request.r#type,
+                                tag: request.tag.clone(),
+                                application: request.application.clone(),
+                                priority: request.priority,
+                                filtered_query_params: request.filtered_query_params.clone(),
+                                request_header: request.request_header.clone(),
+                                piece_length: request.piece_length,
+                                output_path: request.output_path.clone(),
+                                timeout: request.timeout,
+                                disable_back_to_source: request.disable_back_to_source,
+                                need_back_to_source: request.need_back_to_source,
+                                certificate_chain: request.certificate_chain.clone(),
+                                prefetch: request.prefetch,
+                                object_storage: request.object_storage.clone(),
+                                hdfs: request.hdfs.clone(),
+                                is_prefetch: request.is_prefetch,
+                                need_piece_content: request.need_piece_content,
+                                content_for_calculating_task_id: request
+                                    .content_for_calculating_task_id
+                                    .clone(),
+                                remote_ip: request.remote_ip.clone(),
+                            },
+                        ),
+                    ),
+                },
+                PieceRequest::from(&request),
+                download_progress_tx.clone(),
+            )
+            .await
+        {
+            Ok(finished_pieces) => finished_pieces,
+            Err(err) => {
+                error!("download with scheduler error: {:?}", err);
+                Vec::new()
+            }
+        };
+
+        // Remove the finished pieces from the pieces.
+        let interested_pieces = self
+            .piece
+            .remove_finished_from_interested(finished_pieces, interested_pieces);
+        info!(
+            "interested pieces after removing the finished piece: {:?}",
+            interested_pieces
+                .iter()
+                .map(|p| p.number)
+                .collect::<Vec<u32>>()
+        );
+
+        // Check if all pieces are downloaded.
+        if interested_pieces.is_empty() {
+            info!("all cache pieces are downloaded with scheduler");
+            return Ok(());
+        };
+
+        // If disable back-to-source is true, return an error directly.
+        if request.disable_back_to_source {
+            error!("download back-to-source is disabled");
+            return Err(Error::Unknown("download failed".to_string()));
+        };
+
+        // Download the pieces from the source.
+        if let Err(err) = downloader
+            .download_partial_from_source(
+                task_id,
+                host_id,
+                peer_id,
+                interested_pieces.clone(),
+                PieceRequest::from(&request),
+                download_progress_tx.clone(),
+            )
+            .await
+        {
+            error!("download from source error: {:?}", err);
+            return Err(err);
+        }
+
+        info!("all cache pieces are downloaded from source");
+        Ok(())
+    }
+
+    /// stat returns the cache task from the memory cache or the scheduler.
+    #[instrument(skip_all)]
+    pub async fn stat(
+        &self,
+        task_id: &str,
+        host_id: &str,
+        local_only: bool,
+    ) -> ClientResult<CommonCacheTask> {
+        if local_only {
+            let Some(task) = self.storage.get_cache_task(task_id).await else {
+                return Err(Error::TaskNotFound(task_id.to_owned()));
+            };
+
+            let mut pieces = Vec::new();
+            let piece_count = self
+                .piece
+                .calculate_piece_count(task.piece_length(), task.content_length());
+            for number in 0..piece_count {
+                let Some((piece, _)) = self.piece.get_cache(task_id, number).await? else {
+                    continue;
+                };
+
+                // The traffic_type indicates whether the first download was from the source or hit the remote peer cache.
+                // If the parent_id exists, the piece was downloaded from a remote peer. Otherwise, it was
+                // downloaded from the source.
+                let traffic_type = match piece.parent_id {
+                    None => TrafficType::BackToSource,
+                    Some(_) => TrafficType::RemotePeer,
+                };
+
+                pieces.push(Piece {
+                    number: piece.number,
+                    parent_id: piece.parent_id.clone(),
+                    offset: piece.offset,
+                    length: piece.length,
+                    digest: piece.digest.clone(),
+                    content: None,
+                    traffic_type: Some(traffic_type as i32),
+                    cost: None,
+                    created_at: None,
+                });
+            }
+
+            return Ok(CommonCacheTask {
+                id: task_id.to_string(),
+                r#type: TaskType::Cache as i32,
+                url: String::new(),
+                digest: None,
+                tag: None,
+                application: None,
+                filtered_query_params: Vec::new(),
+                request_header: HashMap::new(),
+                content_length: task.content_length(),
+                piece_count: pieces.len() as u32,
+                size_scope: SizeScope::Normal as i32,
+                pieces,
+                state: String::new(),
+                peer_count: 0,
+                has_available_peer: false,
+                created_at: None,
+                updated_at: None,
+            });
+        }
+
+        let task = self
+            .scheduler_client
+            .stat_cache_task(StatCacheTaskRequest {
+                host_id: host_id.to_string(),
+                task_id: task_id.to_string(),
+            })
+            .await
+            .inspect_err(|err| {
+                error!("stat cache task failed: {}", err);
+            })?;
+
+        Ok(task)
+    }
+
+    /// delete deletes a cache task from the memory cache and the scheduler.
+    #[instrument(skip_all)]
+    pub async fn delete(&self, task_id: &str, host_id: &str) -> ClientResult<()> {
+        self.storage
+            .delete_cache_task(task_id)
+            .await
+            .inspect_err(|err| {
+                error!(
+                    "delete cache task {} from memory cache error: {:?}",
+                    task_id, err
+                );
+            })?;
+
+        self.scheduler_client
+            .delete_cache_task(DeleteCacheTaskRequest {
+                host_id: host_id.to_string(),
+                task_id: task_id.to_string(),
+            })
+            .await
+            .inspect_err(|err| {
+                error!(
+                    "delete cache task {} failed from scheduler: {:?}",
+                    task_id, err
+                );
+            })?;
+
+        info!("delete cache task {} from memory cache", task_id);
+        Ok(())
+    }
+}
+
+/// PieceRequest implements the conversion from the download request of the cache task.
+impl From<&DownloadCacheTaskRequest> for PieceRequest {
+    fn from(request: &DownloadCacheTaskRequest) -> Self {
+        Self {
+            url: request.url.clone(),
+            request_header: request.request_header.clone(),
+            is_prefetch: request.is_prefetch,
+            need_piece_content: request.need_piece_content,
+            object_storage: request.object_storage.clone(),
+            hdfs: request.hdfs.clone(),
+        }
+    }
+}
+
+/// CacheStorage is the storage kind of the cache task, the pieces are stored in the memory
+/// cache.
+pub struct CacheStorage;
+
+/// CacheStorage implements the storage kind with the announce cache peer protocol.
+#[tonic::async_trait]
+impl StorageKind for CacheStorage {
+    type Parent = CachePeer;
+    type AnnounceRequest = AnnounceCachePeerRequest;
+    type AnnounceResponse = AnnounceCachePeerResponse;
+    type DownloadResponse = DownloadCacheTaskResponse;
+
+    // The cache peers do not verify the pieces by the digests of the seed peers.
+    const VERIFY_PIECES: bool = false;
+
+    fn collected_parent(parent: CachePeer) -> piece_collector::CollectedParent {
+        piece_collector::CollectedParent {
+            id: parent.id,
+            host: parent.host,
+        }
+    }
+
+    async fn collect_pieces(
+        config: Arc<Config>,
+        host_id: &str,
+        task_id: &str,
+        interested_pieces: Vec<metadata::Piece>,
+        parents: Vec<piece_collector::CollectedParent>,
+        parent_selector: Arc<parent_selector::ParentSelector>,
+    ) -> (
+        mpsc::Receiver<piece_collector::CollectedPiece>,
+        Option<piece_collector::PieceCollector>,
+    ) {
+        let piece_collector = piece_collector::CachePieceCollector::new(
+            config,
+            host_id,
+            task_id,
+            interested_pieces,
+            parents,
+            parent_selector,
+        )
+        .await;
+        (piece_collector.run().await, None)
+    }
+
+    async fn announce(
+        scheduler_client: &SchedulerClient,
+        task_id: &str,
+        peer_id: &str,
+        request: Request<ReceiverStream<AnnounceCachePeerRequest>>,
+    ) -> ClientResult<Streaming<AnnounceCachePeerResponse>> {
+        let response = scheduler_client
+            .announce_cache_peer(task_id, peer_id, request)
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    fn schedule_response(
+        response: AnnounceCachePeerResponse,
+    ) -> ClientResult<ScheduleResponse<CachePeer>> {
+        match response.response.ok_or(Error::UnexpectedResponse)? {
+            announce_cache_peer_response::Response::EmptyCacheTaskResponse(_) => {
+                Ok(ScheduleResponse::EmptyTask)
+            }
+            announce_cache_peer_response::Response::NormalCacheTaskResponse(response) => {
+                Ok(ScheduleResponse::NormalTask(response.candidate_parents))
+            }
+            announce_cache_peer_response::Response::NeedBackToSourceResponse(response) => {
+                Ok(ScheduleResponse::NeedBackToSource(response))
+            }
+        }
+    }
+
+    fn announce_request(
+        host_id: &str,
+        task_id: &str,
+        peer_id: &str,
+        announcement: Announcement<CachePeer>,
+    ) -> AnnounceCachePeerRequest {
+        let request = match announcement {
+            Announcement::DownloadPeerStarted => {
+                announce_cache_peer_request::Request::DownloadCachePeerStartedRequest(
+                    DownloadCachePeerStartedRequest {},
+                )
+            }
+            Announcement::DownloadPeerFinished {
+                content_length,
+                piece_count,
+            } => announce_cache_peer_request::Request::DownloadCachePeerFinishedRequest(
+                DownloadCachePeerFinishedRequest {
+                    content_length,
+                    piece_count,
+                },
+            ),
+            Announcement::DownloadPeerFailed { description } => {
+                announce_cache_peer_request::Request::DownloadCachePeerFailedRequest(
+                    DownloadCachePeerFailedRequest {
+                        description: Some(description),
+                    },
+                )
+            }
+            Announcement::ReschedulePeer {
+                candidate_parents,
+                description,
+            } => announce_cache_peer_request::Request::RescheduleCachePeerRequest(
+                RescheduleCachePeerRequest {
+                    candidate_parents,
+                    description: Some(description),
+                },
+            ),
+            Announcement::DownloadPeerBackToSourceStarted => {
+                announce_cache_peer_request::Request::DownloadCachePeerBackToSourceStartedRequest(
+                    DownloadCachePeerBackToSourceStartedRequest { description: None },
+                )
+            }
+            Announcement::DownloadPeerBackToSourceFinished {
+                content_length,
+                piece_count,
+            } => {
+                announce_cache_peer_request::Request::DownloadCachePeerBackToSourceFinishedRequest(
+                    DownloadCachePeerBackToSourceFinishedRequest {
+                        content_length,
+                        piece_count,
+                    },
+                )
+            }
+            Announcement::DownloadPeerBackToSourceFailed { description } => {
+                announce_cache_peer_request::Request::DownloadCachePeerBackToSourceFailedRequest(
+                    DownloadCachePeerBackToSourceFailedRequest {
+                        description: Some(description),
+                    },
+                )
+            }
+            Announcement::DownloadPieceFinished(piece) => {
+                announce_cache_peer_request::Request::DownloadPieceFinishedRequest(
+                    DownloadPieceFinishedRequest { piece: Some(piece) },
+                )
+            }
+            Announcement::DownloadPieceFailed {
+                piece_number,
+                parent_id,
+                temporary,
+            } => announce_cache_peer_request::Request::DownloadPieceFailedRequest(
+                DownloadPieceFailedRequest {
+                    piece_number: Some(piece_number),
+                    parent_id,
+                    temporary,
+                },
+            ),
+            Announcement::DownloadPieceBackToSourceFinished(piece) => {
+                announce_cache_peer_request::Request::DownloadPieceBackToSourceFinishedRequest(
+                    DownloadPieceBackToSourceFinishedRequest { piece: Some(piece) },
+                )
+            }
+            Announcement::DownloadPieceBackToSourceFailed(response) => {
+                announce_cache_peer_request::Request::DownloadPieceBackToSourceFailedRequest(
+                    DownloadPieceBackToSourceFailedRequest {
+                        piece_number: None,
+                        response: Some(response),
+                    },
+                )
+            }
+        };
+
+        AnnounceCachePeerRequest {
+            host_id: host_id.to_string(),
+            task_id: task_id.to_string(),
+            peer_id: peer_id.to_string(),
+            request: Some(request),
+        }
+    }
+
+    fn download_piece_finished_response(
+        host_id: &str,
+        task_id: &str,
+        peer_id: &str,
+        piece: Piece,
+    ) -> DownloadCacheTaskResponse {
+        DownloadCacheTaskResponse {
+            host_id: host_id.to_string(),
+            task_id: task_id.to_string(),
+            peer_id: peer_id.to_string(),
+            response: Some(
+                download_cache_task_response::Response::DownloadPieceFinishedResponse(
+                    dfdaemon::v2::DownloadPieceFinishedResponse { piece: Some(piece) },
+                ),
+            ),
+        }
+    }
+
+    fn piece_id(piece_manager: &piece::Piece, task_id: &str, number: u32) -> String {
+        piece_manager.cache_id(task_id, number)
+    }
+
+    async fn get_finished_piece(
+        piece_manager: &piece::Piece,
+        task_id: &str,
+        number: u32,
+    ) -> ClientResult<Option<metadata::Piece>> {
+        let piece = piece_manager.get_cache(task_id, number).await?;
+        Ok(piece.map(|(piece, _)| piece))
+    }
+
+    fn download_from_local(piece_manager: &piece::Piece, task_id: &str, length: u64) {
+        piece_manager.download_cache_from_local(task_id, length);
+    }
+
+    async fn download_from_parent(
+        piece_manager: &piece::Piece,
+        piece_id: &str,
+        host_id: &str,
+        task_id: &str,
+        number: u32,
+        length: u64,
+        parent: piece_collector::CollectedParent,
+        is_prefetch: bool,
+        _verifier: Option<Arc<piece_verifier::PieceVerifier>>,
+    ) -> ClientResult<metadata::Piece> {
+        piece_manager
+            .download_cache_from_parent(
+                piece_id,
+                host_id,
+                task_id,
+                number,
+                length,
+                parent,
+                is_prefetch,
+            )
+            .await
+    }
+
+    async fn download_from_source(
+        piece_manager: &piece::Piece,
+        piece_id: &str,
+        task_id: &str,
+        number: u32,
+        url: &str,
+        offset: u64,
+        length: u64,
+        request_header: HeaderMap,
+        is_prefetch: bool,
+        object_storage: Option<ObjectStorage>,
+        hdfs: Option<Hdfs>,
+    ) -> ClientResult<metadata::Piece> {
+        piece_manager
+            .download_cache_from_source(
+                piece_id,
+                task_id,
+                number,
+                url,
+                offset,
+                length,
+                request_header,
+                is_prefetch,
+                object_storage,
+                hdfs,
+            )
+            .await
+    }
+
+    async fn read_piece(
+        piece_manager: &piece::Piece,
+        piece_id: &str,
+        task_id: &str,
+        number: u32,
+        _length: u64,
+    ) -> ClientResult<Vec<u8>> {
+        let (_, content) = piece_manager
+            .get_cache(task_id, number)
+            .await?
+            .ok_or_else(|| Error::PieceNotFound(piece_id.to_string()))?;
+
+        Ok(content.to_vec())
+    }
+}
@@ -14,8 +14,12 @@
  * limitations under the License.
  */
 
+pub mod cache_task;
+pub mod origin_guard;
+pub mod parent_selector;
 pub mod persistent_cache_task;
 pub mod piece;
 pub mod piece_collector;
 pub mod piece_downloader;
+pub mod piece_verifier;
 pub mod task;
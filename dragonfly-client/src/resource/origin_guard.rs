@@ -0,0 +1,712 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::metrics::{
+    collect_backend_origin_circuit_breaker_state_metrics,
+    collect_backend_origin_request_finished_metrics,
+    collect_backend_origin_request_rejected_metrics,
+    collect_backend_origin_request_started_metrics, remove_backend_origin_metrics,
+};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use dragonfly_client_config::dfdaemon::Origin;
+use dragonfly_client_core::{error::BackendError, Error, Result};
+use leaky_bucket::RateLimiter;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest::StatusCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, info, warn};
+use url::Url;
+
+/// ORIGIN_IDLE_TIMEOUT is the duration after which the origin without the requests is evicted.
+const ORIGIN_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// ORIGIN_EVICTION_INTERVAL is the interval to evict the idle origins.
+const ORIGIN_EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// MAX_ORIGIN_METRIC_LABELS is the max number of the origins labelled in the metrics, the
+/// metrics of the other origins are aggregated to the OTHER_ORIGIN_METRIC_LABEL.
+const MAX_ORIGIN_METRIC_LABELS: usize = 100;
+
+/// OTHER_ORIGIN_METRIC_LABEL is the label of the origins which exceed the max number of the
+/// origins labelled in the metrics.
+const OTHER_ORIGIN_METRIC_LABEL: &str = "other";
+
+/// CircuitState is the state of the circuit breaker of the origin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Closed is the state that the requests are sent to the origin.
+    Closed = 0,
+
+    /// Open is the state that the requests fail fast without sending to the origin.
+    Open = 1,
+
+    /// HalfOpen is the state that one request probes the origin.
+    HalfOpen = 2,
+}
+
+/// Circuit is the circuit breaker and the Retry-After of the origin.
+struct Circuit {
+    /// state is the state of the circuit breaker.
+    state: CircuitState,
+
+    /// opened_at is the time of the circuit breaker opened.
+    opened_at: Option<Instant>,
+
+    /// probing indicates whether a request is probing the origin in the half open state.
+    probing: bool,
+
+    /// window_started_at is the start time of the current window.
+    window_started_at: Instant,
+
+    /// requests is the number of the finished requests in the current window.
+    requests: u32,
+
+    /// failures is the number of the failed requests in the current window.
+    failures: u32,
+
+    /// retry_after is the time until which the origin asks the requests to wait.
+    retry_after: Option<Instant>,
+
+    /// used_at is the last time of the request to the origin.
+    used_at: Instant,
+}
+
+/// OriginState is the limits and the circuit breaker of the origin.
+struct OriginState {
+    /// label is the label of the origin in the metrics.
+    label: String,
+
+    /// semaphore limits the concurrent requests to the origin.
+    semaphore: Option<Arc<Semaphore>>,
+
+    /// rate_limiter limits the requests per second to the origin.
+    rate_limiter: Option<RateLimiter>,
+
+    /// circuit is the circuit breaker and the Retry-After of the origin.
+    circuit: Mutex<Circuit>,
+}
+
+/// OriginState implements the state of the origin.
+impl OriginState {
+    /// is_idle returns whether the origin has no request in the idle timeout and does not ask
+    /// the requests to wait, the state is only referenced by the guard if it has no request.
+    fn is_idle(self: &Arc<Self>, idle_timeout: Duration) -> bool {
+        if Arc::strong_count(self) > 1 {
+            return false;
+        }
+
+        let circuit = self.circuit.lock().unwrap();
+        circuit.used_at.elapsed() >= idle_timeout
+            && circuit
+                .retry_after
+                .is_none_or(|retry_after| retry_after <= Instant::now())
+    }
+
+    /// collect_circuit_breaker_state_metrics collects the circuit breaker state metrics, the
+    /// state of the aggregated origins is not collected.
+    fn collect_circuit_breaker_state_metrics(&self, state: CircuitState) {
+        if self.label != OTHER_ORIGIN_METRIC_LABEL {
+            collect_backend_origin_circuit_breaker_state_metrics(&self.label, state as i64);
+        }
+    }
+}
+
+/// OriginPermit is the permit of a back-to-source request to the origin, the request must
+/// report its result by finished, and the concurrent limit is released when it is dropped.
+pub struct OriginPermit {
+    /// config is the origin protection configuration.
+    config: Origin,
+
+    /// origin is the origin of the request.
+    origin: String,
+
+    /// state is the state of the origin.
+    state: Arc<OriginState>,
+
+    /// probe indicates whether the request probes the origin in the half open state.
+    probe: bool,
+
+    /// _permit is the permit of the concurrent limit.
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+/// OriginPermit implements the origin permit.
+impl OriginPermit {
+    /// finished reports the result of the request. The unsuccessful requests without the
+    /// response, and with the 5xx and 429 responses are the failures of the origin, and the
+    /// Retry-After of the 429 and 503 responses is honoured by the following requests.
+    pub fn finished(
+        &mut self,
+        success: bool,
+        status_code: Option<StatusCode>,
+        header: Option<&HeaderMap>,
+    ) {
+        let failed = !success
+            && status_code.is_none_or(|status_code| {
+                status_code.is_server_error() || status_code == StatusCode::TOO_MANY_REQUESTS
+            });
+
+        let retry_after = match (status_code, header) {
+            (
+                Some(StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE),
+                Some(header),
+            ) => parse_retry_after(header, Utc::now())
+                .map(|retry_after| retry_after.min(self.config.max_retry_after)),
+            _ => None,
+        };
+
+        let mut circuit = self.state.circuit.lock().unwrap();
+        let now = Instant::now();
+        if let Some(retry_after) = retry_after {
+            info!(
+                "origin {} asks to retry after {:?}",
+                self.origin, retry_after
+            );
+            circuit.retry_after = Some(now + retry_after);
+        }
+
+        if !self.config.circuit_breaker.enable {
+            return;
+        }
+
+        // The probe request closes the circuit breaker if it succeeds, otherwise opens the
+        // circuit breaker again.
+        if self.probe {
+            self.probe = false;
+            circuit.probing = false;
+            if failed {
+                self.transit(&mut circuit, CircuitState::Open, now);
+            } else {
+                self.transit(&mut circuit, CircuitState::Closed, now);
+            }
+
+            return;
+        }
+
+        // The requests sent before the circuit breaker opened are ignored.
+        if circuit.state != CircuitState::Closed {
+            return;
+        }
+
+        if now.duration_since(circuit.window_started_at) >= self.config.circuit_breaker.window {
+            circuit.window_started_at = now;
+            circuit.requests = 0;
+            circuit.failures = 0;
+        }
+
+        circuit.requests += 1;
+        if failed {
+            circuit.failures += 1;
+        }
+
+        if circuit.requests >= self.config.circuit_breaker.min_requests
+            && circuit.failures as u64 * 100
+                >= circuit.requests as u64
+                    * self.config.circuit_breaker.failure_threshold_percent as u64
+        {
+            warn!(
+                "origin {} failed {} of {} requests, open the circuit breaker",
+                self.origin, circuit.failures, circuit.requests
+            );
+            self.transit(&mut circuit, CircuitState::Open, now);
+        }
+    }
+
+    /// transit transits the circuit breaker to the state and resets the window.
+    fn transit(&self, circuit: &mut Circuit, state: CircuitState, now: Instant) {
+        circuit.state = state;
+        circuit.opened_at = (state == CircuitState::Open).then_some(now);
+        circuit.window_started_at = now;
+        circuit.requests = 0;
+        circuit.failures = 0;
+        self.state.collect_circuit_breaker_state_metrics(state);
+    }
+}
+
+/// OriginPermit implements Drop.
+impl Drop for OriginPermit {
+    /// drop releases the probe if the probe request does not report its result, so that the
+    /// next request can probe the origin.
+    fn drop(&mut self) {
+        let mut circuit = self.state.circuit.lock().unwrap();
+        if self.probe {
+            circuit.probing = false;
+        }
+
+        circuit.used_at = Instant::now();
+        drop(circuit);
+        collect_backend_origin_request_finished_metrics(&self.state.label);
+    }
+}
+
+/// OriginGuard protects the origins from the back-to-source traffic. It limits the concurrent
+/// requests and the requests per second of each origin, holds the requests until the
+/// Retry-After of the origin, and fails fast when the circuit breaker of the origin is open,
+/// so that the scheduler prefers the parents. The idle origins are evicted, and the metrics
+/// of the origins are aggregated if there are too many origins.
+pub struct OriginGuard {
+    /// config is the origin protection configuration.
+    config: Origin,
+
+    /// origins is the state of the origins by the origin.
+    origins: DashMap<String, Arc<OriginState>>,
+
+    /// labels is the number of the origins labelled in the metrics.
+    labels: AtomicUsize,
+
+    /// idle_timeout is the duration after which the origin without the requests is evicted.
+    idle_timeout: Duration,
+
+    /// eviction_interval is the interval to evict the idle origins.
+    eviction_interval: Duration,
+
+    /// evicted_at is the last time of evicting the idle origins.
+    evicted_at: Mutex<Instant>,
+}
+
+/// OriginGuard implements the origin guard.
+impl OriginGuard {
+    /// new returns a new OriginGuard.
+    pub fn new(config: Origin) -> Self {
+        Self {
+            config,
+            origins: DashMap::new(),
+            labels: AtomicUsize::new(0),
+            idle_timeout: ORIGIN_IDLE_TIMEOUT,
+            eviction_interval: ORIGIN_EVICTION_INTERVAL,
+            evicted_at: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// acquire acquires the permit of a back-to-source request to the origin of the url. The
+    /// request waits for the Retry-After and the limits no longer than the timeout, otherwise
+    /// it fails fast with the 503 backend error.
+    pub async fn acquire(&self, url: &str, timeout: Duration) -> Result<OriginPermit> {
+        let origin = origin(url)?;
+        self.evict_idle_origins();
+        let state = self.state(&origin);
+        let deadline = Instant::now() + timeout;
+
+        // Wait for the Retry-After of the origin if it is within the timeout.
+        let retry_after = state
+            .circuit
+            .lock()
+            .unwrap()
+            .retry_after
+            .and_then(|retry_after| retry_after.checked_duration_since(Instant::now()));
+        if let Some(retry_after) = retry_after {
+            if Instant::now() + retry_after > deadline {
+                return Err(self.reject(&origin, &state, "retry_after", retry_after));
+            }
+
+            tokio::time::sleep(retry_after).await;
+        }
+
+        // Check the circuit breaker of the origin.
+        let mut probe = false;
+        if self.config.circuit_breaker.enable {
+            let mut circuit = state.circuit.lock().unwrap();
+            match circuit.state {
+                CircuitState::Closed => {}
+                CircuitState::Open | CircuitState::HalfOpen => {
+                    let elapsed = circuit
+                        .opened_at
+                        .map(|opened_at| opened_at.elapsed())
+                        .unwrap_or(self.config.circuit_breaker.open_duration);
+                    if elapsed < self.config.circuit_breaker.open_duration || circuit.probing {
+                        let remaining = self
+                            .config
+                            .circuit_breaker
+                            .open_duration
+                            .saturating_sub(elapsed);
+                        drop(circuit);
+                        return Err(self.reject(
+                            &origin,
+                            &state,
+                            "circuit_breaker_open",
+                            remaining,
+                        ));
+                    }
+
+                    info!("probe the origin {}", origin);
+                    circuit.state = CircuitState::HalfOpen;
+                    circuit.probing = true;
+                    state.collect_circuit_breaker_state_metrics(CircuitState::HalfOpen);
+                    probe = true;
+                }
+            }
+        }
+
+        // Build the permit before waiting for the limits, so that the probe is released if
+        // the request is cancelled.
+        collect_backend_origin_request_started_metrics(&state.label);
+        let mut permit = OriginPermit {
+            config: self.config.clone(),
+            origin: origin.clone(),
+            state: state.clone(),
+            probe,
+            _permit: None,
+        };
+
+        let limit = async {
+            if let Some(rate_limiter) = state.rate_limiter.as_ref() {
+                rate_limiter.acquire_one().await;
+            }
+
+            match state.semaphore.as_ref() {
+                Some(semaphore) => semaphore.clone().acquire_owned().await.ok(),
+                None => None,
+            }
+        };
+
+        match tokio::time::timeout_at(deadline.into(), limit).await {
+            Ok(semaphore_permit) => {
+                permit._permit = semaphore_permit;
+                Ok(permit)
+            }
+            Err(_) => Err(self.reject(&origin, &state, "limit_exceeded", Duration::ZERO)),
+        }
+    }
+
+    /// evict_idle_origins evicts the origins which have no request in the idle timeout, the
+    /// origins are checked at most once in the eviction interval.
+    fn evict_idle_origins(&self) {
+        {
+            let mut evicted_at = self.evicted_at.lock().unwrap();
+            if evicted_at.elapsed() < self.eviction_interval {
+                return;
+            }
+
+            *evicted_at = Instant::now();
+        }
+
+        self.origins.retain(|origin, state| {
+            if !state.is_idle(self.idle_timeout) {
+                return true;
+            }
+
+            debug!("evict the idle origin {}", origin);
+            if state.label != OTHER_ORIGIN_METRIC_LABEL {
+                self.labels.fetch_sub(1, Ordering::SeqCst);
+                remove_backend_origin_metrics(&state.label);
+            }
+
+            false
+        });
+    }
+
+    /// state returns the state of the origin, it is created if not exists. The origin is
+    /// labelled in the metrics if the number of the labelled origins does not exceed the
+    /// max, otherwise it is aggregated.
+    fn state(&self, origin: &str) -> Arc<OriginState> {
+        let state =
+            self.origins
+                .entry(origin.to_string())
+                .or_insert_with(|| {
+                    let label = match self.labels.fetch_update(
+                        Ordering::SeqCst,
+                        Ordering::SeqCst,
+                        |labels| (labels < MAX_ORIGIN_METRIC_LABELS).then_some(labels + 1),
+                    ) {
+                        Ok(_) => origin.to_string(),
+                        Err(_) => OTHER_ORIGIN_METRIC_LABEL.to_string(),
+                    };
+
+                    let semaphore = (self.config.concurrent_request_limit > 0)
+                        .then(|| Arc::new(Semaphore::new(self.config.concurrent_request_limit)));
+
+                    let rate_limit = self.config.request_rate_limit;
+                    let rate_limiter = (rate_limit > 0).then(|| {
+                        RateLimiter::builder()
+                            .initial(rate_limit)
+                            .refill(rate_limit)
+                            .max(rate_limit)
+                            .interval(Duration::from_secs(1))
+                            .fair(false)
+                            .build()
+                    });
+
+                    Arc::new(OriginState {
+                        label,
+                        semaphore,
+                        rate_limiter,
+                        circuit: Mutex::new(Circuit {
+                            state: CircuitState::Closed,
+                            opened_at: None,
+                            probing: false,
+                            window_started_at: Instant::now(),
+                            requests: 0,
+                            failures: 0,
+                            retry_after: None,
+                            used_at: Instant::now(),
+                        }),
+                    })
+                })
+                .clone();
+
+        state.circuit.lock().unwrap().used_at = Instant::now();
+        state
+    }
+
+    /// reject returns the 503 backend error of the rejected request, the Retry-After header
+    /// tells the scheduler when the origin is available again.
+    fn reject(
+        &self,
+        origin: &str,
+        state: &OriginState,
+        reason: &str,
+        retry_after: Duration,
+    ) -> Error {
+        warn!(
+            "reject back-to-source request to origin {}: {}",
+            origin, reason
+        );
+        collect_backend_origin_request_rejected_metrics(&state.label, reason);
+
+        let mut header = HeaderMap::new();
+        let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+        header.insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+
+        Error::BackendError(Box::new(BackendError {
+            message: format!("origin {} is unavailable: {}", origin, reason),
+            status_code: Some(StatusCode::SERVICE_UNAVAILABLE),
+            header: Some(header),
+        }))
+    }
+}
+
+/// origin returns the origin of the url, which is the scheme, host and port.
+fn origin(url: &str) -> Result<String> {
+    let url = Url::parse(url).map_err(|_| Error::InvalidURI(url.to_string()))?;
+    let host = url.host_str().unwrap_or_default();
+    match url.port_or_known_default() {
+        Some(port) => Ok(format!("{}://{}:{}", url.scheme(), host, port)),
+        None => Ok(format!("{}://{}", url.scheme(), host)),
+    }
+}
+
+/// parse_retry_after parses the Retry-After header, which is either the seconds or the
+/// HTTP-date.
+fn parse_retry_after(header: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = header.get(header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        date.with_timezone(&Utc)
+            .signed_duration_since(now)
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dragonfly_client_config::dfdaemon::CircuitBreaker;
+
+    fn config() -> Origin {
+        Origin {
+            concurrent_request_limit: 1,
+            request_rate_limit: 0,
+            max_retry_after: Duration::from_secs(60),
+            circuit_breaker: CircuitBreaker {
+                enable: true,
+                window: Duration::from_secs(10),
+                min_requests: 2,
+                failure_threshold_percent: 50,
+                open_duration: Duration::from_millis(100),
+            },
+        }
+    }
+
+    #[test]
+    fn should_parse_origin() {
+        assert_eq!(
+            origin("https://example.com/v2/blobs").unwrap(),
+            "https://example.com:443"
+        );
+        assert_eq!(
+            origin("http://example.com:8080/file").unwrap(),
+            "http://example.com:8080"
+        );
+        assert_eq!(origin("s3://bucket/key").unwrap(), "s3://bucket");
+        assert!(origin("example").is_err());
+    }
+
+    #[test]
+    fn should_parse_retry_after() {
+        let now = DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let mut header = HeaderMap::new();
+        header.insert(header::RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(
+            parse_retry_after(&header, now),
+            Some(Duration::from_secs(120))
+        );
+
+        header.insert(
+            header::RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:29:00 GMT"),
+        );
+        assert_eq!(
+            parse_retry_after(&header, now),
+            Some(Duration::from_secs(60))
+        );
+
+        header.insert(header::RETRY_AFTER, HeaderValue::from_static("invalid"));
+        assert_eq!(parse_retry_after(&header, now), None);
+    }
+
+    #[tokio::test]
+    async fn should_limit_concurrent_requests() {
+        let guard = OriginGuard::new(config());
+        let url = "http://example.com/file";
+
+        let permit = guard.acquire(url, Duration::from_secs(1)).await.unwrap();
+        assert!(guard.acquire(url, Duration::from_millis(50)).await.is_err());
+        assert!(guard
+            .acquire("http://other.com/file", Duration::from_millis(50))
+            .await
+            .is_ok());
+
+        drop(permit);
+        assert!(guard.acquire(url, Duration::from_millis(50)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_honour_retry_after() {
+        let guard = OriginGuard::new(config());
+        let url = "http://example.com/file";
+
+        let mut header = HeaderMap::new();
+        header.insert(header::RETRY_AFTER, HeaderValue::from_static("1"));
+        guard
+            .acquire(url, Duration::from_secs(1))
+            .await
+            .unwrap()
+            .finished(false, Some(StatusCode::TOO_MANY_REQUESTS), Some(&header));
+
+        match guard.acquire(url, Duration::from_millis(100)).await {
+            Err(Error::BackendError(err)) => {
+                assert_eq!(err.status_code, Some(StatusCode::SERVICE_UNAVAILABLE));
+                assert!(err.header.unwrap().contains_key(header::RETRY_AFTER));
+            }
+            _ => panic!("should reject the request"),
+        }
+
+        assert!(guard.acquire(url, Duration::from_secs(5)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_open_and_close_circuit_breaker() {
+        let guard = OriginGuard::new(config());
+        let url = "http://example.com/file";
+
+        guard
+            .acquire(url, Duration::from_secs(1))
+            .await
+            .unwrap()
+            .finished(true, Some(StatusCode::OK), None);
+        guard
+            .acquire(url, Duration::from_secs(1))
+            .await
+            .unwrap()
+            .finished(false, Some(StatusCode::BAD_GATEWAY), None);
+
+        // The circuit breaker is open and the requests fail fast.
+        assert!(guard.acquire(url, Duration::from_secs(1)).await.is_err());
+
+        // The first request after the open duration probes the origin.
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let mut probe = guard.acquire(url, Duration::from_secs(1)).await.unwrap();
+        assert!(probe.probe);
+        probe.finished(true, Some(StatusCode::OK), None);
+        drop(probe);
+
+        let permit = guard.acquire(url, Duration::from_secs(1)).await.unwrap();
+        assert!(!permit.probe);
+    }
+
+    #[tokio::test]
+    async fn should_not_count_client_errors_as_failures() {
+        let guard = OriginGuard::new(config());
+        let url = "http://example.com/file";
+
+        for _ in 0..4 {
+            guard
+                .acquire(url, Duration::from_secs(1))
+                .await
+                .unwrap()
+                .finished(false, Some(StatusCode::NOT_FOUND), None);
+        }
+
+        assert!(guard.acquire(url, Duration::from_secs(1)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_evict_idle_origins() {
+        let mut guard = OriginGuard::new(config());
+        guard.idle_timeout = Duration::ZERO;
+        guard.eviction_interval = Duration::ZERO;
+
+        let permit = guard
+            .acquire("http://example.com/file", Duration::from_secs(1))
+            .await
+            .unwrap();
+        guard
+            .acquire("http://idle.com/file", Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(guard.origins.len(), 2);
+        assert_eq!(guard.labels.load(Ordering::SeqCst), 2);
+
+        // The origin with the request in flight is not evicted.
+        guard.evict_idle_origins();
+        assert_eq!(guard.origins.len(), 1);
+        assert!(guard.origins.contains_key("http://example.com:80"));
+        assert_eq!(guard.labels.load(Ordering::SeqCst), 1);
+
+        drop(permit);
+        guard.evict_idle_origins();
+        assert!(guard.origins.is_empty());
+        assert_eq!(guard.labels.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn should_aggregate_origin_metric_labels() {
+        let guard = OriginGuard::new(config());
+        for i in 0..MAX_ORIGIN_METRIC_LABELS {
+            let state = guard.state(&format!("http://example-{}.com:80", i));
+            assert_eq!(state.label, format!("http://example-{}.com:80", i));
+        }
+
+        let state = guard.state("http://other.com:80");
+        assert_eq!(state.label, OTHER_ORIGIN_METRIC_LABEL);
+        assert_eq!(
+            guard.labels.load(Ordering::SeqCst),
+            MAX_ORIGIN_METRIC_LABELS
+        );
+    }
+}
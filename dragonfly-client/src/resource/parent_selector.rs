@@ -0,0 +1,684 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::grpc::dfdaemon_upload::DfdaemonUploadClient;
+use crate::resource::piece_collector::CollectedParent;
+use crate::shutdown;
+use dashmap::DashMap;
+use dragonfly_api::common::v2::Host;
+use dragonfly_api::dfdaemon::v2::SyncHostRequest;
+use dragonfly_client_config::dfdaemon::Config;
+use dragonfly_client_core::{Error, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_stream::StreamExt;
+use tracing::{debug, error, info, instrument, Instrument};
+
+/// DEFAULT_PARENT_SCORE is the score of the parent whose host information has not been
+/// synchronized yet, e.g. the parent exceeds the capacity of the parent selector.
+const DEFAULT_PARENT_SCORE: f64 = 0.5;
+
+/// UNAVAILABLE_PARENT_SCORE is the score of the parent whose host information can not be
+/// synchronized, the parent will not be selected unless all of the parents are unavailable.
+const UNAVAILABLE_PARENT_SCORE: f64 = 0.0;
+
+/// CPU_WEIGHT is the weight of the idle cpu in the parent score.
+const CPU_WEIGHT: f64 = 0.3;
+
+/// NETWORK_WEIGHT is the weight of the idle transmit bandwidth in the parent score.
+const NETWORK_WEIGHT: f64 = 0.4;
+
+/// UPLOAD_WEIGHT is the weight of the pieces downloading from the parent in the parent score.
+const UPLOAD_WEIGHT: f64 = 0.3;
+
+/// UPLOAD_CONCURRENCY_FACTOR is the number of pieces downloading from the parent concurrently
+/// that halves the upload part of the parent score.
+const UPLOAD_CONCURRENCY_FACTOR: f64 = 16.0;
+
+/// Connection is the sync host connection of the parent.
+struct Connection {
+    /// generation identifies the sync host task of the connection, it prevents the task of the
+    /// unregistered connection from updating the score of the new connection.
+    generation: u64,
+
+    /// active_requests is the number of piece collectors that are using the parent.
+    active_requests: usize,
+
+    /// host_score is the score calculated by the latest host information of the parent, None
+    /// means the host information has not been synchronized yet.
+    host_score: Option<f64>,
+
+    /// shutdown is used to stop synchronizing host information from the parent.
+    shutdown: shutdown::Shutdown,
+}
+
+/// InflightPiece is the guard of the piece downloading from the parent, the number of the
+/// inflight pieces of the parent is decreased when the guard is dropped.
+pub struct InflightPiece {
+    /// host_id is the host id of the parent.
+    host_id: Option<String>,
+
+    /// inflight_pieces is the number of the pieces downloading from the parents.
+    inflight_pieces: Arc<DashMap<String, usize>>,
+}
+
+/// InflightPiece implements the Drop trait.
+impl Drop for InflightPiece {
+    /// drop decreases the number of the inflight pieces of the parent.
+    fn drop(&mut self) {
+        if let Some(host_id) = self.host_id.as_ref() {
+            self.inflight_pieces.remove_if_mut(host_id, |_, count| {
+                *count = count.saturating_sub(1);
+                *count == 0
+            });
+        }
+    }
+}
+
+/// ParentSelector synchronizes the host information of the parents by the `DfdaemonUpload.SyncHost`
+/// streaming and selects the optimal parent for downloading pieces.
+pub struct ParentSelector {
+    /// config is the configuration of the dfdaemon.
+    config: Arc<Config>,
+
+    /// host_id is the id of the host.
+    host_id: String,
+
+    /// connections is the sync host connections of the parents, the key is the host id
+    /// of the parent. The connections are guarded by a single lock, so the capacity and
+    /// the scores are consistent with the registered parents.
+    connections: Arc<Mutex<HashMap<String, Connection>>>,
+
+    /// inflight_pieces is the number of the pieces downloading from the parents by the
+    /// host, the key is the host id of the parent.
+    inflight_pieces: Arc<DashMap<String, usize>>,
+
+    /// next_generation is the generation of the next connection.
+    next_generation: AtomicU64,
+
+    /// shutdown is used to shutdown the parent selector.
+    shutdown: shutdown::Shutdown,
+}
+
+/// ParentSelector implements the parent selector.
+impl ParentSelector {
+    /// new creates a new ParentSelector.
+    pub fn new(config: Arc<Config>, host_id: &str, shutdown: shutdown::Shutdown) -> Self {
+        Self {
+            config,
+            host_id: host_id.to_string(),
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            inflight_pieces: Arc::new(DashMap::new()),
+            next_generation: AtomicU64::new(0),
+            shutdown,
+        }
+    }
+
+    /// is_enabled returns whether the parent selector is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.config.download.parent_selector.enable
+    }
+
+    /// register starts synchronizing the host information from the parents. If the parent is
+    /// already synchronized, it only increases the reference of the connection. The number of
+    /// connections is limited by the capacity of the parent selector.
+    #[instrument(skip_all)]
+    pub fn register(&self, parents: &[CollectedParent]) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut connections = self.connections.lock().unwrap();
+        for parent in parents {
+            let Some(host) = parent.host.clone() else {
+                continue;
+            };
+
+            if let Some(connection) = connections.get_mut(&host.id) {
+                connection.active_requests += 1;
+                continue;
+            }
+
+            if connections.len() >= self.config.download.parent_selector.capacity {
+                debug!(
+                    "parent selector is full, skip to sync host {} of parent {}",
+                    host.id, parent.id
+                );
+                continue;
+            }
+
+            let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+            let shutdown = shutdown::Shutdown::new();
+            connections.insert(
+                host.id.clone(),
+                Connection {
+                    generation,
+                    active_requests: 1,
+                    host_score: None,
+                    shutdown: shutdown.clone(),
+                },
+            );
+
+            info!("start to sync host {} of parent {}", host.id, parent.id);
+            tokio::spawn(
+                Self::sync_host(
+                    self.config.clone(),
+                    self.host_id.clone(),
+                    host,
+                    generation,
+                    self.connections.clone(),
+                    shutdown,
+                    self.shutdown.clone(),
+                )
+                .in_current_span(),
+            );
+        }
+    }
+
+    /// unregister decreases the reference of the parents' connections, and stops synchronizing
+    /// the host information from the parent when it is not used by any piece collector.
+    #[instrument(skip_all)]
+    pub fn unregister(&self, parents: &[CollectedParent]) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut connections = self.connections.lock().unwrap();
+        for parent in parents {
+            let Some(host) = parent.host.as_ref() else {
+                continue;
+            };
+
+            let Some(connection) = connections.get_mut(&host.id) else {
+                continue;
+            };
+
+            connection.active_requests = connection.active_requests.saturating_sub(1);
+            if connection.active_requests > 0 {
+                continue;
+            }
+
+            if let Some(connection) = connections.remove(&host.id) {
+                info!("stop to sync host {} of parent {}", host.id, parent.id);
+                connection.shutdown.trigger();
+            }
+        }
+    }
+
+    /// start_download marks a piece is downloading from the parent until the returned guard
+    /// is dropped, the inflight pieces decrease the score of the parent.
+    pub fn start_download(&self, parent: &CollectedParent) -> InflightPiece {
+        let host_id = match parent.host.as_ref() {
+            Some(host) if self.is_enabled() => {
+                *self.inflight_pieces.entry(host.id.clone()).or_insert(0) += 1;
+                Some(host.id.clone())
+            }
+            _ => None,
+        };
+
+        InflightPiece {
+            host_id,
+            inflight_pieces: self.inflight_pieces.clone(),
+        }
+    }
+
+    /// select selects a parent from the candidate parents. If the parent selector is enabled,
+    /// the parent is selected randomly weighted by its score, so the parents with high load
+    /// are rarely selected and the unavailable parents are skipped. Otherwise, the parent is
+    /// selected randomly.
+    pub fn select<'a>(&self, parents: &'a [CollectedParent]) -> Option<&'a CollectedParent> {
+        if parents.is_empty() {
+            return None;
+        }
+
+        if !self.is_enabled() {
+            return parents.get(fastrand::usize(..parents.len()));
+        }
+
+        let scores = {
+            let connections = self.connections.lock().unwrap();
+            parents
+                .iter()
+                .map(|parent| match parent.host.as_ref() {
+                    Some(host) => {
+                        let host_score = connections
+                            .get(&host.id)
+                            .and_then(|connection| connection.host_score);
+
+                        let inflight_pieces = self
+                            .inflight_pieces
+                            .get(&host.id)
+                            .map(|count| *count)
+                            .unwrap_or(0);
+
+                        Self::parent_score(host_score, inflight_pieces)
+                    }
+                    None => Self::parent_score(None, 0),
+                })
+                .collect::<Vec<f64>>()
+        };
+
+        parents.get(Self::weighted_random_index(&scores, fastrand::f64()))
+    }
+
+    /// weighted_random_index returns the index of the scores selected by the random value in
+    /// [0, 1). If all of the scores are zero, the index is selected uniformly.
+    fn weighted_random_index(scores: &[f64], random: f64) -> usize {
+        let total: f64 = scores.iter().sum();
+        if total <= 0.0 {
+            return ((random * scores.len() as f64) as usize).min(scores.len() - 1);
+        }
+
+        let mut target = random * total;
+        for (index, score) in scores.iter().enumerate() {
+            if *score <= 0.0 {
+                continue;
+            }
+
+            if target < *score {
+                return index;
+            }
+
+            target -= score;
+        }
+
+        // Fallback to the last available parent because of the floating point precision.
+        scores.iter().rposition(|score| *score > 0.0).unwrap_or(0)
+    }
+
+    /// parent_score calculates the score of the parent by the host score and the number of the
+    /// pieces downloading from the parent, the range of the score is [0, 1] and the higher score
+    /// means the parent is more idle. If the host score is unavailable, the parent score is
+    /// unavailable too.
+    fn parent_score(host_score: Option<f64>, inflight_pieces: usize) -> f64 {
+        let host_score = host_score.unwrap_or((CPU_WEIGHT + NETWORK_WEIGHT) * DEFAULT_PARENT_SCORE);
+        if host_score <= UNAVAILABLE_PARENT_SCORE {
+            return UNAVAILABLE_PARENT_SCORE;
+        }
+
+        let upload_score = 1.0 / (1.0 + inflight_pieces as f64 / UPLOAD_CONCURRENCY_FACTOR);
+        host_score + UPLOAD_WEIGHT * upload_score
+    }
+
+    /// host_score calculates the score of the parent by the host information, the range of the
+    /// score is (0, CPU_WEIGHT + NETWORK_WEIGHT]. The score of the busiest host is still above
+    /// zero, because zero means the parent is unavailable.
+    fn host_score(host: &Host) -> f64 {
+        // Calculate the idle ratio of the cpu.
+        let cpu_score = match host.cpu.as_ref() {
+            Some(cpu) => 1.0 - (cpu.percent / 100.0).clamp(0.0, 1.0),
+            None => DEFAULT_PARENT_SCORE,
+        };
+
+        // Calculate the idle ratio of the transmit bandwidth.
+        let network_score = match host.network.as_ref() {
+            Some(network) => match (network.max_tx_bandwidth, network.tx_bandwidth) {
+                (0, _) | (_, None) => DEFAULT_PARENT_SCORE,
+                (max_tx_bandwidth, Some(tx_bandwidth)) => {
+                    1.0 - (tx_bandwidth as f64 / max_tx_bandwidth as f64).clamp(0.0, 1.0)
+                }
+            },
+            None => DEFAULT_PARENT_SCORE,
+        };
+
+        (CPU_WEIGHT * cpu_score + NETWORK_WEIGHT * network_score).max(f64::EPSILON)
+    }
+
+    /// update_host_score updates the host score of the connection if the connection is still
+    /// registered by the sync host task of the generation.
+    fn update_host_score(
+        connections: &Mutex<HashMap<String, Connection>>,
+        host_id: &str,
+        generation: u64,
+        host_score: f64,
+    ) {
+        let mut connections = connections.lock().unwrap();
+        if let Some(connection) = connections.get_mut(host_id) {
+            if connection.generation == generation {
+                connection.host_score = Some(host_score);
+            }
+        }
+    }
+
+    /// sync_host synchronizes the host information from the parent until the connection is
+    /// unregistered or the parent selector is shutdown. If the synchronization fails, the parent
+    /// is marked as unavailable and it will retry after the sync interval.
+    #[instrument(skip_all, fields(parent_host_id = parent_host.id))]
+    async fn sync_host(
+        config: Arc<Config>,
+        host_id: String,
+        parent_host: Host,
+        generation: u64,
+        connections: Arc<Mutex<HashMap<String, Connection>>>,
+        mut connection_shutdown: shutdown::Shutdown,
+        mut shutdown: shutdown::Shutdown,
+    ) {
+        let sync_interval = config.download.parent_selector.sync_interval;
+        loop {
+            tokio::select! {
+                result = Self::sync_host_once(config.clone(), host_id.as_str(), &parent_host, generation, connections.clone(), sync_interval) => {
+                    if let Err(err) = result {
+                        error!("sync host {} failed: {}", parent_host.id, err);
+                    }
+
+                    // Mark the parent as unavailable until the synchronization is recovered.
+                    Self::update_host_score(&connections, parent_host.id.as_str(), generation, UNAVAILABLE_PARENT_SCORE);
+                }
+                _ = connection_shutdown.recv() => {
+                    debug!("sync host {} is unregistered", parent_host.id);
+                    return;
+                }
+                _ = shutdown.recv() => {
+                    info!("parent selector shutting down");
+                    return;
+                }
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(sync_interval) => {}
+                _ = connection_shutdown.recv() => {
+                    debug!("sync host {} is unregistered", parent_host.id);
+                    return;
+                }
+                _ = shutdown.recv() => {
+                    info!("parent selector shutting down");
+                    return;
+                }
+            }
+        }
+    }
+
+    /// sync_host_once opens the sync host streaming to the parent and updates the score of
+    /// the parent at most once per sync interval.
+    async fn sync_host_once(
+        config: Arc<Config>,
+        host_id: &str,
+        parent_host: &Host,
+        generation: u64,
+        connections: Arc<Mutex<HashMap<String, Connection>>>,
+        sync_interval: Duration,
+    ) -> Result<()> {
+        let dfdaemon_upload_client = DfdaemonUploadClient::new(
+            config,
+            format!("http://{}:{}", parent_host.ip, parent_host.port),
+            false,
+        )
+        .await?;
+
+        let response = dfdaemon_upload_client
+            .sync_host(SyncHostRequest {
+                host_id: host_id.to_string(),
+                peer_id: String::new(),
+            })
+            .await?;
+
+        let out_stream = response.into_inner();
+        tokio::pin!(out_stream);
+
+        let mut last_updated_at: Option<Instant> = None;
+        while let Some(host) = out_stream.try_next().await? {
+            if last_updated_at.is_some_and(|updated_at| updated_at.elapsed() < sync_interval) {
+                continue;
+            }
+
+            let host_score = Self::host_score(&host);
+            debug!("parent host {} score is {}", parent_host.id, host_score);
+            Self::update_host_score(
+                &connections,
+                parent_host.id.as_str(),
+                generation,
+                host_score,
+            );
+            last_updated_at = Some(Instant::now());
+        }
+
+        Err(Error::Unknown(format!(
+            "sync host stream of {} is closed",
+            parent_host.id
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dragonfly_api::common::v2::{Cpu, Network};
+
+    fn new_parent_selector(enable: bool, capacity: usize) -> ParentSelector {
+        let mut config = Config::default();
+        config.download.parent_selector.enable = enable;
+        config.download.parent_selector.capacity = capacity;
+        config.download.parent_selector.sync_interval = Duration::from_millis(100);
+        ParentSelector::new(Arc::new(config), "host", shutdown::Shutdown::new())
+    }
+
+    fn new_parent(index: usize) -> CollectedParent {
+        CollectedParent {
+            id: format!("peer-{}", index),
+            host: Some(Host {
+                id: format!("host-{}", index),
+                ip: "127.0.0.1".to_string(),
+                // The port is not listened, so the sync host always fails.
+                port: 1,
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn test_host_score() {
+        let idle_host = Host {
+            cpu: Some(Cpu {
+                percent: 0.0,
+                ..Default::default()
+            }),
+            network: Some(Network {
+                max_tx_bandwidth: 1000,
+                tx_bandwidth: Some(0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(
+            (ParentSelector::host_score(&idle_host) - (CPU_WEIGHT + NETWORK_WEIGHT)).abs() < 1e-9
+        );
+
+        let busy_host = Host {
+            cpu: Some(Cpu {
+                percent: 100.0,
+                ..Default::default()
+            }),
+            network: Some(Network {
+                max_tx_bandwidth: 1000,
+                tx_bandwidth: Some(1000),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(ParentSelector::host_score(&busy_host) > UNAVAILABLE_PARENT_SCORE);
+        assert!(ParentSelector::host_score(&busy_host) < 1e-9);
+
+        let unknown_host = Host::default();
+        assert!(
+            (ParentSelector::host_score(&unknown_host)
+                - (CPU_WEIGHT + NETWORK_WEIGHT) * DEFAULT_PARENT_SCORE)
+                .abs()
+                < 1e-9
+        );
+    }
+
+    #[test]
+    fn test_parent_score() {
+        let idle_score = ParentSelector::parent_score(Some(CPU_WEIGHT + NETWORK_WEIGHT), 0);
+        assert!((idle_score - 1.0).abs() < 1e-9);
+
+        let busy_score = ParentSelector::parent_score(Some(CPU_WEIGHT + NETWORK_WEIGHT), 64);
+        assert!(busy_score < idle_score);
+
+        assert_eq!(
+            ParentSelector::parent_score(Some(UNAVAILABLE_PARENT_SCORE), 0),
+            UNAVAILABLE_PARENT_SCORE
+        );
+
+        let unknown_score = ParentSelector::parent_score(None, 0);
+        assert!(unknown_score > UNAVAILABLE_PARENT_SCORE);
+        assert!(unknown_score < idle_score);
+    }
+
+    #[test]
+    fn test_weighted_random_index() {
+        let scores = vec![0.0, 0.5, 0.0, 0.5];
+        assert_eq!(ParentSelector::weighted_random_index(&scores, 0.0), 1);
+        assert_eq!(ParentSelector::weighted_random_index(&scores, 0.49), 1);
+        assert_eq!(ParentSelector::weighted_random_index(&scores, 0.5), 3);
+        assert_eq!(ParentSelector::weighted_random_index(&scores, 0.999), 3);
+
+        let scores = vec![0.0, 0.0, 0.0];
+        assert_eq!(ParentSelector::weighted_random_index(&scores, 0.0), 0);
+        assert_eq!(ParentSelector::weighted_random_index(&scores, 0.5), 1);
+        assert_eq!(ParentSelector::weighted_random_index(&scores, 0.999), 2);
+    }
+
+    #[tokio::test]
+    async fn test_select() {
+        let parent_selector = new_parent_selector(true, 20);
+        let parents = vec![new_parent(1), new_parent(2)];
+
+        parent_selector.register(&parents);
+        {
+            let mut connections = parent_selector.connections.lock().unwrap();
+            connections.get_mut("host-1").unwrap().generation = u64::MAX;
+            connections.get_mut("host-1").unwrap().host_score = Some(0.0);
+            connections.get_mut("host-2").unwrap().generation = u64::MAX;
+            connections.get_mut("host-2").unwrap().host_score = Some(0.5);
+        }
+
+        for _ in 0..100 {
+            let parent = parent_selector.select(&parents).unwrap();
+            assert_eq!(parent.id, "peer-2");
+        }
+
+        assert!(parent_selector.select(&[]).is_none());
+        parent_selector.unregister(&parents);
+    }
+
+    #[tokio::test]
+    async fn test_register_and_unregister() {
+        let parent_selector = new_parent_selector(true, 20);
+        let parents = vec![new_parent(1)];
+
+        parent_selector.register(&parents);
+        parent_selector.register(&parents);
+        assert_eq!(
+            parent_selector
+                .connections
+                .lock()
+                .unwrap()
+                .get("host-1")
+                .unwrap()
+                .active_requests,
+            2
+        );
+
+        parent_selector.unregister(&parents);
+        assert_eq!(
+            parent_selector
+                .connections
+                .lock()
+                .unwrap()
+                .get("host-1")
+                .unwrap()
+                .active_requests,
+            1
+        );
+
+        parent_selector.unregister(&parents);
+        assert!(parent_selector.connections.lock().unwrap().is_empty());
+
+        // Unregister the parent that is not registered.
+        parent_selector.unregister(&parents);
+        assert!(parent_selector.connections.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_register_with_capacity() {
+        let parent_selector = new_parent_selector(true, 1);
+        parent_selector.register(&[new_parent(1), new_parent(2)]);
+
+        let connections = parent_selector.connections.lock().unwrap();
+        assert_eq!(connections.len(), 1);
+        assert!(connections.contains_key("host-1"));
+    }
+
+    #[tokio::test]
+    async fn test_register_with_disabled() {
+        let parent_selector = new_parent_selector(false, 20);
+        let parents = vec![new_parent(1)];
+
+        parent_selector.register(&parents);
+        assert!(parent_selector.connections.lock().unwrap().is_empty());
+
+        let _inflight_piece = parent_selector.start_download(&parents[0]);
+        assert!(parent_selector.inflight_pieces.is_empty());
+        assert_eq!(parent_selector.select(&parents).unwrap().id, "peer-1");
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_parent() {
+        let parent_selector = new_parent_selector(true, 20);
+        let parents = vec![new_parent(1)];
+        parent_selector.register(&parents);
+
+        let mut host_score = None;
+        for _ in 0..50 {
+            host_score = parent_selector
+                .connections
+                .lock()
+                .unwrap()
+                .get("host-1")
+                .and_then(|connection| connection.host_score);
+            if host_score.is_some() {
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(host_score, Some(UNAVAILABLE_PARENT_SCORE));
+
+        // The stale sync host task must not update the score after unregistering.
+        parent_selector.unregister(&parents);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(parent_selector.connections.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_start_download() {
+        let parent_selector = new_parent_selector(true, 20);
+        let parent = new_parent(1);
+
+        let first = parent_selector.start_download(&parent);
+        let second = parent_selector.start_download(&parent);
+        assert_eq!(*parent_selector.inflight_pieces.get("host-1").unwrap(), 2);
+
+        drop(first);
+        assert_eq!(*parent_selector.inflight_pieces.get("host-1").unwrap(), 1);
+
+        drop(second);
+        assert!(parent_selector.inflight_pieces.is_empty());
+    }
+}
@@ -79,6 +79,9 @@ pub struct PersistentCacheTask {
 
     /// piece is the piece manager.
     pub piece: Arc<piece::Piece>,
+
+    /// parent_selector is the parent selector.
+    pub parent_selector: Arc<parent_selector::ParentSelector>,
 }
 
 /// PersistentCacheTask is the implementation of PersistentCacheTask.
@@ -90,6 +93,7 @@ impl PersistentCacheTask {
         storage: Arc<Storage>,
         scheduler_client: Arc<SchedulerClient>,
         backend_factory: Arc<BackendFactory>,
+        parent_selector: Arc<parent_selector::ParentSelector>,
     ) -> ClientResult<Self> {
         let piece = piece::Piece::new(
             config.clone(),
@@ -105,6 +109,7 @@ impl PersistentCacheTask {
             storage,
             scheduler_client,
             piece,
+            parent_selector,
         })
     }
 
@@ -420,6 +425,12 @@ impl PersistentCacheTask {
         host_id: &str,
         request: DownloadPersistentCacheTaskRequest,
     ) -> ClientResult<metadata::PersistentCacheTask> {
+        // The output path is plaintext, so it is checked before the persistent cache task
+        // downloads started.
+        if request.output_path.is_some() {
+            self.storage.check_output(task_id)?;
+        }
+
         let response = self
             .scheduler_client
             .stat_persistent_cache_task(StatPersistentCacheTaskRequest {
@@ -1026,6 +1037,7 @@ impl PersistentCacheTask {
                     host: peer.host,
                 })
                 .collect(),
+            self.parent_selector.clone(),
         )
         .await;
         let mut piece_collector_rx = piece_collector.run().await;
@@ -1061,6 +1073,7 @@ impl PersistentCacheTask {
                 need_piece_content: bool,
                 parent: piece_collector::CollectedParent,
                 piece_manager: Arc<super::piece::Piece>,
+                parent_selector: Arc<parent_selector::ParentSelector>,
                 semaphore: Arc<Semaphore>,
                 download_progress_tx: Sender<Result<DownloadPersistentCacheTaskResponse, Status>>,
                 in_stream_tx: Sender<AnnouncePersistentCachePeerRequest>,
@@ -1070,6 +1083,10 @@ impl PersistentCacheTask {
                 // Limit the concurrent download count.
                 let _permit = semaphore.acquire().await.unwrap();
 
+                // Record the piece downloading from the parent, it decreases the score of the
+                // parent in the parent selector until the piece is downloaded.
+                let _inflight_piece = parent_selector.start_download(&parent);
+
                 let piece_id = piece_manager.persistent_cache_id(task_id.as_str(), number);
                 info!(
                     "start to download persistent cache piece {} from parent {:?}",
@@ -1098,6 +1115,7 @@ impl PersistentCacheTask {
                         Error::DownloadFromParentFailed(DownloadFromParentFailed {
                             piece_number: number,
                             parent_id: parent.id.clone(),
+                            temporary: true,
                         })
                     })?;
 
@@ -1213,6 +1231,7 @@ impl PersistentCacheTask {
                     need_piece_content,
                     collect_piece.parent.clone(),
                     self.piece.clone(),
+                    self.parent_selector.clone(),
                     semaphore.clone(),
                     download_progress_tx.clone(),
                     in_stream_tx.clone(),
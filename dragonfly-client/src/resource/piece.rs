@@ -20,6 +20,9 @@ use crate::metrics::{
     collect_backend_request_started_metrics, collect_download_piece_traffic_metrics,
     collect_upload_piece_traffic_metrics,
 };
+use crate::resource::origin_guard::OriginGuard;
+use crate::resource::piece_verifier::PieceVerifier;
+use bytes::Bytes;
 use chrono::Utc;
 use dragonfly_api::common::v2::{Hdfs, ObjectStorage, Range, TrafficType};
 use dragonfly_client_backend::{BackendFactory, GetRequest};
@@ -82,6 +85,9 @@ pub struct Piece {
 
     /// prefetch_rate_limiter is the rate limiter of the prefetch speed in bps(bytes per second).
     prefetch_rate_limiter: Arc<RateLimiter>,
+
+    /// origin_guard protects the origins from the back-to-source traffic.
+    origin_guard: Arc<OriginGuard>,
 }
 
 /// Piece implements the piece manager.
@@ -130,9 +136,26 @@ impl Piece {
                     .fair(false)
                     .build(),
             ),
+            origin_guard: Arc::new(OriginGuard::new(config.download.origin.clone())),
         })
     }
 
+    /// with_backend_factory returns a new Piece which downloads from the source by the
+    /// backend factory, the rate limiters and the origin guard are shared with the piece.
+    pub fn with_backend_factory(&self, backend_factory: Arc<BackendFactory>) -> Self {
+        Self {
+            config: self.config.clone(),
+            id_generator: self.id_generator.clone(),
+            storage: self.storage.clone(),
+            downloader: self.downloader.clone(),
+            backend_factory,
+            download_rate_limiter: self.download_rate_limiter.clone(),
+            upload_rate_limiter: self.upload_rate_limiter.clone(),
+            prefetch_rate_limiter: self.prefetch_rate_limiter.clone(),
+            origin_guard: self.origin_guard.clone(),
+        }
+    }
+
     /// id generates a new piece id.
     #[inline]
     pub fn id(&self, task_id: &str, number: u32) -> String {
@@ -201,6 +224,8 @@ impl Piece {
                         updated_at: Utc::now().naive_utc(),
                         created_at: Utc::now().naive_utc(),
                         finished_at: None,
+                        compressed_length: None,
+                        encryption: None,
                     });
                 }
 
@@ -251,6 +276,8 @@ impl Piece {
                 updated_at: Utc::now().naive_utc(),
                 created_at: Utc::now().naive_utc(),
                 finished_at: None,
+                compressed_length: None,
+                encryption: None,
             });
 
             offset = (number + 1) * piece_length;
@@ -404,7 +431,9 @@ impl Piece {
         );
     }
 
-    /// download_from_parent downloads a single piece from a parent.
+    /// download_from_parent downloads a single piece from a parent. If the verifier is set, the
+    /// piece is verified by the trusted digest before it is stored, because the parent may
+    /// forge both the piece and its digest.
     #[allow(clippy::too_many_arguments)]
     #[instrument(skip_all, fields(piece_id))]
     pub async fn download_from_parent(
@@ -416,6 +445,7 @@ impl Piece {
         length: u64,
         parent: piece_collector::CollectedParent,
         is_prefetch: bool,
+        verifier: Option<Arc<PieceVerifier>>,
     ) -> Result<metadata::Piece> {
         // Span record the piece_id.
         Span::current().record("piece_id", piece_id);
@@ -455,8 +485,9 @@ impl Piece {
         let (content, offset, digest) = self
             .downloader
             .download_piece(
-                format!("{}:{}", host.ip, host.port).as_str(),
+                format!("{}:{}", host.ip, host.download_port).as_str(),
                 number,
+                length,
                 host_id,
                 task_id,
             )
@@ -467,6 +498,18 @@ impl Piece {
                     error!("set piece metadata failed: {}", err)
                 };
             })?;
+
+        // Verify the piece by the trusted digest, and the stored digest is checked by the
+        // trusted digest instead of the digest sent by the parent.
+        let digest = match verifier {
+            Some(verifier) => verifier.verify(number, &content).await.inspect_err(|err| {
+                error!("verify piece from parent {} failed: {}", parent.id, err);
+                if let Some(err) = self.storage.download_piece_failed(piece_id).err() {
+                    error!("set piece metadata failed: {}", err)
+                };
+            })?,
+            None => digest,
+        };
         let mut reader = Cursor::new(content);
 
         // Record the finish of downloading piece.
@@ -475,6 +518,7 @@ impl Piece {
             .download_piece_from_parent_finished(
                 piece_id,
                 task_id,
+                number,
                 offset,
                 length,
                 digest.as_str(),
@@ -562,6 +606,19 @@ impl Piece {
             };
         })?;
 
+        // Acquire the permit of the origin, it is held until the piece is stored, and fails
+        // fast if the origin is unavailable.
+        let mut origin_permit = self
+            .origin_guard
+            .acquire(url, self.config.download.piece_timeout)
+            .await
+            .inspect_err(|err| {
+                error!("acquire origin permit failed: {}", err);
+                if let Some(err) = self.storage.download_piece_failed(piece_id).err() {
+                    error!("set piece metadata failed: {}", err)
+                };
+            })?;
+
         // Record the start time.
         let start_time = Instant::now();
 
@@ -570,7 +627,7 @@ impl Piece {
             backend.scheme().as_str(),
             http::Method::GET.as_str(),
         );
-        let mut response = backend
+        let response = backend
             .get(GetRequest {
                 task_id: task_id.to_string(),
                 piece_id: piece_id.to_string(),
@@ -585,8 +642,11 @@ impl Piece {
                 object_storage,
                 hdfs,
             })
-            .await
-            .inspect_err(|err| {
+            .await;
+
+        let mut response = match response {
+            Ok(response) => response,
+            Err(err) => {
                 // Collect the backend request failure metrics.
                 collect_backend_request_failure_metrics(
                     backend.scheme().as_str(),
@@ -595,12 +655,23 @@ impl Piece {
 
                 // if the request is failed.
                 error!("backend get failed: {}", err);
+                origin_permit.finished(false, None, None);
                 if let Some(err) = self.storage.download_piece_failed(piece_id).err() {
                     error!("set piece metadata failed: {}", err)
                 };
-            })?;
+
+                return Err(err);
+            }
+        };
 
         if !response.success {
+            // Report the failed response to the origin guard.
+            origin_permit.finished(
+                false,
+                response.http_status_code,
+                response.http_header.as_ref(),
+            );
+
             // Collect the backend request failure metrics.
             collect_backend_request_failure_metrics(
                 backend.scheme().as_str(),
@@ -633,12 +704,16 @@ impl Piece {
             start_time.elapsed(),
         );
 
-        // Record the finish of downloading piece.
+        // Record the finish of downloading piece, the origin succeeds only if the body is
+        // read and stored.
+        let status_code = response.http_status_code;
+        let header = response.http_header.take();
         match self
             .storage
             .download_piece_from_source_finished(
                 piece_id,
                 task_id,
+                number,
                 offset,
                 length,
                 &mut response.reader,
@@ -647,6 +722,7 @@ impl Piece {
             .await
         {
             Ok(piece) => {
+                origin_permit.finished(true, status_code, header.as_ref());
                 collect_download_piece_traffic_metrics(
                     &TrafficType::BackToSource,
                     self.id_generator.task_type(task_id) as i32,
@@ -656,6 +732,11 @@ impl Piece {
                 Ok(piece)
             }
             Err(err) => {
+                // The body read error is the failure of the origin, but no space is not.
+                if !matches!(err, Error::NoSpace(_)) {
+                    origin_permit.finished(false, None, None);
+                }
+
                 error!("download piece finished: {}", err);
                 if let Some(err) = self.storage.download_piece_failed(piece_id).err() {
                     error!("set piece metadata failed: {}", err)
@@ -820,8 +901,9 @@ impl Piece {
         let (content, offset, digest) = self
             .downloader
             .download_persistent_cache_piece(
-                format!("{}:{}", host.ip, host.port).as_str(),
+                format!("{}:{}", host.ip, host.download_port).as_str(),
                 number,
+                length,
                 host_id,
                 task_id,
             )
@@ -844,6 +926,7 @@ impl Piece {
             .download_persistent_cache_piece_from_parent_finished(
                 piece_id,
                 task_id,
+                number,
                 offset,
                 length,
                 digest.as_str(),
@@ -875,6 +958,278 @@ impl Piece {
             }
         }
     }
+
+    /// cache_id generates a new cache piece id.
+    #[inline]
+    pub fn cache_id(&self, task_id: &str, number: u32) -> String {
+        self.storage.cache_piece_id(task_id, number)
+    }
+
+    /// get_cache gets a cache piece and its content from the memory cache.
+    #[instrument(skip_all)]
+    pub async fn get_cache(
+        &self,
+        task_id: &str,
+        number: u32,
+    ) -> Result<Option<(metadata::Piece, Bytes)>> {
+        self.storage.get_cache_piece(task_id, number).await
+    }
+
+    /// upload_cache_from_local uploads a cache piece from the memory cache.
+    #[instrument(skip_all, fields(piece_id))]
+    pub async fn upload_cache_from_local(
+        &self,
+        piece_id: &str,
+        task_id: &str,
+        number: u32,
+    ) -> Result<(metadata::Piece, Bytes)> {
+        // Span record the piece_id.
+        Span::current().record("piece_id", piece_id);
+
+        let (piece, content) = self
+            .storage
+            .get_cache_piece(task_id, number)
+            .await?
+            .ok_or_else(|| Error::PieceNotFound(piece_id.to_string()))?;
+
+        // Acquire the upload rate limiter.
+        self.upload_rate_limiter
+            .acquire(piece.length as usize)
+            .await;
+
+        collect_upload_piece_traffic_metrics(
+            self.id_generator.task_type(task_id) as i32,
+            piece.length,
+        );
+        Ok((piece, content))
+    }
+
+    /// download_cache_from_local downloads a cache piece from the memory cache. Fake the download
+    /// cache piece from the memory cache, just collect the metrics.
+    #[instrument(skip_all)]
+    pub fn download_cache_from_local(&self, task_id: &str, length: u64) {
+        collect_download_piece_traffic_metrics(
+            &TrafficType::LocalPeer,
+            self.id_generator.task_type(task_id) as i32,
+            length,
+        );
+    }
+
+    /// download_cache_from_parent downloads a cache piece from a parent into the memory cache.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip_all, fields(piece_id))]
+    pub async fn download_cache_from_parent(
+        &self,
+        piece_id: &str,
+        host_id: &str,
+        task_id: &str,
+        number: u32,
+        length: u64,
+        parent: piece_collector::CollectedParent,
+        is_prefetch: bool,
+    ) -> Result<metadata::Piece> {
+        // Span record the piece_id.
+        Span::current().record("piece_id", piece_id);
+        Span::current().record("piece_length", length);
+
+        if is_prefetch {
+            // Acquire the prefetch rate limiter.
+            self.prefetch_rate_limiter.acquire(length as usize).await;
+        } else {
+            // Acquire the download rate limiter.
+            self.download_rate_limiter.acquire(length as usize).await;
+        }
+
+        // Create a dfdaemon client.
+        let host = parent.host.clone().ok_or_else(|| {
+            error!("peer host is empty");
+            Error::InvalidPeer(parent.id.clone())
+        })?;
+
+        let (content, offset, digest) = self
+            .downloader
+            .download_cache_piece(
+                format!("{}:{}", host.ip, host.download_port).as_str(),
+                number,
+                length,
+                host_id,
+                task_id,
+            )
+            .await
+            .inspect_err(|err| {
+                error!("download cache piece failed: {}", err);
+            })?;
+
+        if content.len() as u64 != length {
+            error!(
+                "cache piece {} length mismatch: expected {}, actual {}",
+                piece_id,
+                length,
+                content.len()
+            );
+            return Err(Error::ContentLengthMismatch(length, content.len() as u64));
+        }
+
+        // Record the finish of downloading piece.
+        let piece = self
+            .storage
+            .download_cache_piece_from_parent_finished(
+                piece_id,
+                task_id,
+                number,
+                offset,
+                digest.as_str(),
+                parent.id.as_str(),
+                Bytes::from(content),
+            )
+            .await
+            .inspect_err(|err| {
+                error!("download cache piece finished: {}", err);
+            })?;
+
+        collect_download_piece_traffic_metrics(
+            &TrafficType::RemotePeer,
+            self.id_generator.task_type(task_id) as i32,
+            length,
+        );
+
+        Ok(piece)
+    }
+
+    /// download_cache_from_source downloads a cache piece from the source into the memory cache.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip_all, fields(piece_id))]
+    pub async fn download_cache_from_source(
+        &self,
+        piece_id: &str,
+        task_id: &str,
+        number: u32,
+        url: &str,
+        offset: u64,
+        length: u64,
+        request_header: HeaderMap,
+        is_prefetch: bool,
+        object_storage: Option<ObjectStorage>,
+        hdfs: Option<Hdfs>,
+    ) -> Result<metadata::Piece> {
+        // Span record the piece_id.
+        Span::current().record("piece_id", piece_id);
+        Span::current().record("piece_length", length);
+
+        if is_prefetch {
+            // Acquire the prefetch rate limiter.
+            self.prefetch_rate_limiter.acquire(length as usize).await;
+        } else {
+            // Acquire the download rate limiter.
+            self.download_rate_limiter.acquire(length as usize).await;
+        }
+
+        // Add range header to the request by offset and length.
+        let mut request_header = request_header.clone();
+        request_header.insert(
+            header::RANGE,
+            format!("bytes={}-{}", offset, offset + length - 1)
+                .parse()
+                .unwrap(),
+        );
+
+        // Download the piece from the source.
+        let backend = self.backend_factory.build(url).inspect_err(|err| {
+            error!("build backend failed: {}", err);
+        })?;
+
+        // Record the start time.
+        let start_time = Instant::now();
+
+        // Collect the backend request started metrics.
+        collect_backend_request_started_metrics(
+            backend.scheme().as_str(),
+            http::Method::GET.as_str(),
+        );
+        let mut response = backend
+            .get(GetRequest {
+                task_id: task_id.to_string(),
+                piece_id: piece_id.to_string(),
+                url: url.to_string(),
+                range: Some(Range {
+                    start: offset,
+                    length,
+                }),
+                http_header: Some(request_header),
+                timeout: self.config.download.piece_timeout,
+                client_cert: None,
+                object_storage,
+                hdfs,
+            })
+            .await
+            .inspect_err(|err| {
+                // Collect the backend request failure metrics.
+                collect_backend_request_failure_metrics(
+                    backend.scheme().as_str(),
+                    http::Method::GET.as_str(),
+                );
+
+                // if the request is failed.
+                error!("backend get failed: {}", err);
+            })?;
+
+        if !response.success {
+            // Collect the backend request failure metrics.
+            collect_backend_request_failure_metrics(
+                backend.scheme().as_str(),
+                http::Method::GET.as_str(),
+            );
+
+            // if the status code is not OK.
+            let mut buffer = String::new();
+            response
+                .reader
+                .read_to_string(&mut buffer)
+                .await
+                .unwrap_or_default();
+
+            let error_message = response.error_message.unwrap_or_default();
+            error!("backend get failed: {} {}", error_message, buffer.as_str());
+
+            return Err(Error::BackendError(Box::new(BackendError {
+                message: error_message,
+                status_code: Some(response.http_status_code.unwrap_or_default()),
+                header: Some(response.http_header.unwrap_or_default()),
+            })));
+        }
+
+        // Collect the backend request finished metrics.
+        collect_backend_request_finished_metrics(
+            backend.scheme().as_str(),
+            http::Method::GET.as_str(),
+            start_time.elapsed(),
+        );
+
+        // Record the finish of downloading piece.
+        let piece = self
+            .storage
+            .download_cache_piece_from_source_finished(
+                piece_id,
+                task_id,
+                number,
+                offset,
+                length,
+                &mut response.reader,
+                self.config.storage.write_piece_timeout,
+            )
+            .await
+            .inspect_err(|err| {
+                error!("download cache piece finished: {}", err);
+            })?;
+
+        collect_download_piece_traffic_metrics(
+            &TrafficType::BackToSource,
+            self.id_generator.task_type(task_id) as i32,
+            length,
+        );
+
+        Ok(piece)
+    }
 }
 
 #[cfg(test)]
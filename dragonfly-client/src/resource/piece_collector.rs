@@ -15,9 +15,12 @@
  */
 
 use crate::grpc::dfdaemon_upload::DfdaemonUploadClient;
-use dashmap::DashMap;
+use crate::resource::parent_selector::ParentSelector;
+use dashmap::{DashMap, DashSet};
 use dragonfly_api::common::v2::Host;
-use dragonfly_api::dfdaemon::v2::{SyncPersistentCachePiecesRequest, SyncPiecesRequest};
+use dragonfly_api::dfdaemon::v2::{
+    SyncCachePiecesRequest, SyncPersistentCachePiecesRequest, SyncPiecesRequest,
+};
 use dragonfly_client_config::dfdaemon::Config;
 use dragonfly_client_core::{Error, Result};
 use dragonfly_client_storage::metadata;
@@ -71,6 +74,13 @@ pub struct PieceCollector {
 
     /// collected_pieces is a map to store the collected pieces from different parents.
     collected_pieces: Arc<DashMap<u32, Vec<CollectedParent>>>,
+
+    /// blocked_parents are the ids of the parents which are skipped, e.g. the parents which
+    /// send the forged pieces.
+    blocked_parents: Arc<DashSet<String>>,
+
+    /// parent_selector is used to select the optimal parent for downloading pieces.
+    parent_selector: Arc<ParentSelector>,
 }
 
 /// PieceCollector is used to collect pieces from peers.
@@ -82,6 +92,7 @@ impl PieceCollector {
         task_id: &str,
         interested_pieces: Vec<metadata::Piece>,
         parents: Vec<CollectedParent>,
+        parent_selector: Arc<ParentSelector>,
     ) -> Self {
         let collected_pieces = Arc::new(DashMap::with_capacity(interested_pieces.len()));
         for interested_piece in &interested_pieces {
@@ -95,9 +106,18 @@ impl PieceCollector {
             parents,
             interested_pieces,
             collected_pieces,
+            blocked_parents: Arc::new(DashSet::new()),
+            parent_selector,
         }
     }
 
+    /// block_parent skips the parent for the rest of the collection, the pieces are collected
+    /// from the other parents.
+    pub fn block_parent(&self, parent_id: &str) {
+        info!("block parent {} of task {}", parent_id, self.task_id);
+        self.blocked_parents.insert(parent_id.to_string());
+    }
+
     /// run runs the piece collector.
     #[instrument(skip_all)]
     pub async fn run(&self) -> Receiver<CollectedPiece> {
@@ -107,24 +127,34 @@ impl PieceCollector {
         let parents = self.parents.clone();
         let interested_pieces = self.interested_pieces.clone();
         let collected_pieces = self.collected_pieces.clone();
+        let blocked_parents = self.blocked_parents.clone();
+        let parent_selector = self.parent_selector.clone();
         let collected_piece_timeout = self.config.download.collected_piece_timeout;
         let (collected_piece_tx, collected_piece_rx) = mpsc::channel(128 * 1024);
         tokio::spawn(
             async move {
+                // Synchronize the host information of the parents for selecting the optimal
+                // parent during the collection.
+                parent_selector.register(&parents);
+
                 Self::collect_from_parents(
                     config,
                     &host_id,
                     &task_id,
-                    parents,
+                    parents.clone(),
                     interested_pieces,
                     collected_pieces,
+                    blocked_parents,
                     collected_piece_tx,
                     collected_piece_timeout,
+                    parent_selector.clone(),
                 )
                 .await
                 .unwrap_or_else(|err| {
                     error!("collect pieces failed: {}", err);
                 });
+
+                parent_selector.unregister(&parents);
             }
             .in_current_span(),
         );
@@ -139,11 +169,14 @@ impl PieceCollector {
     ///    to collect the same piece information from different parents. This allows the collector
     ///    to gather multiple sources for each piece.
     ///
-    /// 2. **Selection Phase**: After the wait period, randomly selects one parent from the available
-    ///    candidates for each piece and forwards it to the piece downloader.
+    /// 2. **Selection Phase**: After the wait period, selects one parent from the available
+    ///    candidates for each piece by the parent selector and forwards it to the piece downloader.
     ///
     /// **Load Balancing Strategy**:
-    /// The random parent selection is designed to distribute download load across multiple parents
+    /// If the parent selector is enabled, the parent is selected randomly weighted by its score
+    /// calculated from the synchronized host information, so the parents with high cpu, network
+    /// and upload load are rarely selected. Otherwise, the parent is selected randomly.
+    /// The parent selection is designed to distribute download load across multiple parents
     /// during concurrent piece downloads. This approach ensures:
     /// - Optimal utilization of bandwidth from multiple parent nodes
     /// - Prevention of overwhelming any single parent with too many requests
@@ -160,8 +193,10 @@ impl PieceCollector {
         parents: Vec<CollectedParent>,
         interested_pieces: Vec<metadata::Piece>,
         collected_pieces: Arc<DashMap<u32, Vec<CollectedParent>>>,
+        blocked_parents: Arc<DashSet<String>>,
         collected_piece_tx: Sender<CollectedPiece>,
         collected_piece_timeout: Duration,
+        parent_selector: Arc<ParentSelector>,
     ) -> Result<()> {
         // Create a task to collect pieces from peers.
         let mut join_set = JoinSet::new();
@@ -174,8 +209,10 @@ impl PieceCollector {
                 parent: CollectedParent,
                 interested_pieces: Vec<metadata::Piece>,
                 collected_pieces: Arc<DashMap<u32, Vec<CollectedParent>>>,
+                blocked_parents: Arc<DashSet<String>>,
                 collected_piece_tx: Sender<CollectedPiece>,
                 collected_piece_timeout: Duration,
+                parent_selector: Arc<ParentSelector>,
             ) -> Result<CollectedParent> {
                 info!("sync pieces from parent {}", parent.id);
 
@@ -221,6 +258,13 @@ impl PieceCollector {
                     error!("sync pieces from parent {} failed: {}", parent.id, err);
                 })? {
                     let message = message?;
+
+                    // Stop collecting the pieces from the blocked parent.
+                    if blocked_parents.contains(&parent.id) {
+                        info!("stop to sync pieces from blocked parent {}", parent.id);
+                        break;
+                    }
+
                     if let Some(mut parents) = collected_pieces.get_mut(&message.number) {
                         parents.push(parent.clone());
                     } else {
@@ -230,12 +274,20 @@ impl PieceCollector {
                     // Wait for collecting the piece from different parents when the first
                     // piece is collected.
                     tokio::time::sleep(DEFAULT_WAIT_FOR_PIECE_FROM_DIFFERENT_PARENTS).await;
-                    let parents = match collected_pieces.remove(&message.number) {
+                    let mut parents = match collected_pieces.remove(&message.number) {
                         Some((_, parents)) => parents,
                         None => continue,
                     };
 
-                    let parent = match parents.get(fastrand::usize(..parents.len())) {
+                    // If all the parents of the piece are blocked, wait for collecting the
+                    // piece from the other parents.
+                    parents.retain(|parent| !blocked_parents.contains(&parent.id));
+                    if parents.is_empty() {
+                        collected_pieces.insert(message.number, parents);
+                        continue;
+                    }
+
+                    let parent = match parent_selector.select(&parents) {
                         Some(parent) => parent,
                         None => {
                             error!(
@@ -274,8 +326,10 @@ impl PieceCollector {
                     parent.clone(),
                     interested_pieces.clone(),
                     collected_pieces.clone(),
+                    blocked_parents.clone(),
                     collected_piece_tx.clone(),
                     collected_piece_timeout,
+                    parent_selector.clone(),
                 )
                 .in_current_span(),
             );
@@ -325,6 +379,9 @@ pub struct PersistentCachePieceCollector {
 
     /// collected_pieces is a map to store the collected pieces from different parents.
     collected_pieces: Arc<DashMap<u32, Vec<CollectedParent>>>,
+
+    /// parent_selector is used to select the optimal parent for downloading pieces.
+    parent_selector: Arc<ParentSelector>,
 }
 
 /// PersistentCachePieceCollector is used to collect persistent cache pieces from peers.
@@ -336,6 +393,7 @@ impl PersistentCachePieceCollector {
         task_id: &str,
         interested_pieces: Vec<metadata::Piece>,
         parents: Vec<CollectedParent>,
+        parent_selector: Arc<ParentSelector>,
     ) -> Self {
         let collected_pieces = Arc::new(DashMap::with_capacity(interested_pieces.len()));
         for interested_piece in &interested_pieces {
@@ -349,6 +407,7 @@ impl PersistentCachePieceCollector {
             parents,
             interested_pieces,
             collected_pieces,
+            parent_selector,
         }
     }
 
@@ -361,24 +420,32 @@ impl PersistentCachePieceCollector {
         let parents = self.parents.clone();
         let interested_pieces = self.interested_pieces.clone();
         let collected_pieces = self.collected_pieces.clone();
+        let parent_selector = self.parent_selector.clone();
         let collected_piece_timeout = self.config.download.piece_timeout;
         let (collected_piece_tx, collected_piece_rx) = mpsc::channel(10 * 1024);
         tokio::spawn(
             async move {
+                // Synchronize the host information of the parents for selecting the optimal
+                // parent during the collection.
+                parent_selector.register(&parents);
+
                 Self::collect_from_parents(
                     config,
                     &host_id,
                     &task_id,
-                    parents,
+                    parents.clone(),
                     interested_pieces,
                     collected_pieces,
                     collected_piece_tx,
                     collected_piece_timeout,
+                    parent_selector.clone(),
                 )
                 .await
                 .unwrap_or_else(|err| {
                     error!("collect persistent cache pieces failed: {}", err);
                 });
+
+                parent_selector.unregister(&parents);
             }
             .in_current_span(),
         );
@@ -393,11 +460,14 @@ impl PersistentCachePieceCollector {
     ///    to collect the same piece information from different parents. This allows the collector
     ///    to gather multiple sources for each piece.
     ///
-    /// 2. **Selection Phase**: After the wait period, randomly selects one parent from the available
-    ///    candidates for each piece and forwards it to the piece downloader.
+    /// 2. **Selection Phase**: After the wait period, selects one parent from the available
+    ///    candidates for each piece by the parent selector and forwards it to the piece downloader.
     ///
     /// **Load Balancing Strategy**:
-    /// The random parent selection is designed to distribute download load across multiple parents
+    /// If the parent selector is enabled, the parent is selected randomly weighted by its score
+    /// calculated from the synchronized host information, so the parents with high cpu, network
+    /// and upload load are rarely selected. Otherwise, the parent is selected randomly.
+    /// The parent selection is designed to distribute download load across multiple parents
     /// during concurrent piece downloads. This approach ensures:
     /// - Optimal utilization of bandwidth from multiple parent nodes
     /// - Prevention of overwhelming any single parent with too many requests
@@ -416,6 +486,7 @@ impl PersistentCachePieceCollector {
         collected_pieces: Arc<DashMap<u32, Vec<CollectedParent>>>,
         collected_piece_tx: Sender<CollectedPiece>,
         collected_piece_timeout: Duration,
+        parent_selector: Arc<ParentSelector>,
     ) -> Result<()> {
         // Create a task to collect pieces from peers.
         let mut join_set = JoinSet::new();
@@ -430,6 +501,7 @@ impl PersistentCachePieceCollector {
                 collected_pieces: Arc<DashMap<u32, Vec<CollectedParent>>>,
                 collected_piece_tx: Sender<CollectedPiece>,
                 collected_piece_timeout: Duration,
+                parent_selector: Arc<ParentSelector>,
             ) -> Result<CollectedParent> {
                 info!("sync persistent cache pieces from parent {}", parent.id);
 
@@ -495,7 +567,7 @@ impl PersistentCachePieceCollector {
                         None => continue,
                     };
 
-                    let parent = match parents.get(fastrand::usize(..parents.len())) {
+                    let parent = match parent_selector.select(&parents) {
                         Some(parent) => parent,
                         None => {
                             error!(
@@ -536,6 +608,7 @@ impl PersistentCachePieceCollector {
                     collected_pieces.clone(),
                     collected_piece_tx.clone(),
                     collected_piece_timeout,
+                    parent_selector.clone(),
                 )
                 .in_current_span(),
             );
@@ -565,3 +638,282 @@ impl PersistentCachePieceCollector {
         Ok(())
     }
 }
+
+/// CachePieceCollector is used to collect cache pieces from peers.
+pub struct CachePieceCollector {
+    /// config is the configuration of the dfdaemon.
+    config: Arc<Config>,
+
+    /// host_id is the id of the host.
+    host_id: String,
+
+    /// task_id is the id of the cache task.
+    task_id: String,
+
+    /// parents is the parent peers.
+    parents: Vec<CollectedParent>,
+
+    /// interested_pieces is the pieces interested by the collector.
+    interested_pieces: Vec<metadata::Piece>,
+
+    /// collected_pieces is a map to store the collected pieces from different parents.
+    collected_pieces: Arc<DashMap<u32, Vec<CollectedParent>>>,
+
+    /// parent_selector is used to select the optimal parent for downloading pieces.
+    parent_selector: Arc<ParentSelector>,
+}
+
+/// CachePieceCollector is used to collect cache pieces from peers.
+impl CachePieceCollector {
+    /// new creates a new CachePieceCollector.
+    pub async fn new(
+        config: Arc<Config>,
+        host_id: &str,
+        task_id: &str,
+        interested_pieces: Vec<metadata::Piece>,
+        parents: Vec<CollectedParent>,
+        parent_selector: Arc<ParentSelector>,
+    ) -> Self {
+        let collected_pieces = Arc::new(DashMap::with_capacity(interested_pieces.len()));
+        for interested_piece in &interested_pieces {
+            collected_pieces.insert(interested_piece.number, Vec::new());
+        }
+
+        Self {
+            config,
+            task_id: task_id.to_string(),
+            host_id: host_id.to_string(),
+            parents,
+            interested_pieces,
+            collected_pieces,
+            parent_selector,
+        }
+    }
+
+    /// run runs the piece collector.
+    #[instrument(skip_all)]
+    pub async fn run(&self) -> Receiver<CollectedPiece> {
+        let config = self.config.clone();
+        let host_id = self.host_id.clone();
+        let task_id = self.task_id.clone();
+        let parents = self.parents.clone();
+        let interested_pieces = self.interested_pieces.clone();
+        let collected_pieces = self.collected_pieces.clone();
+        let parent_selector = self.parent_selector.clone();
+        let collected_piece_timeout = self.config.download.piece_timeout;
+        let (collected_piece_tx, collected_piece_rx) = mpsc::channel(10 * 1024);
+        tokio::spawn(
+            async move {
+                // Synchronize the host information of the parents for selecting the optimal
+                // parent during the collection.
+                parent_selector.register(&parents);
+
+                Self::collect_from_parents(
+                    config,
+                    &host_id,
+                    &task_id,
+                    parents.clone(),
+                    interested_pieces,
+                    collected_pieces,
+                    collected_piece_tx,
+                    collected_piece_timeout,
+                    parent_selector.clone(),
+                )
+                .await
+                .unwrap_or_else(|err| {
+                    error!("collect cache pieces failed: {}", err);
+                });
+
+                parent_selector.unregister(&parents);
+            }
+            .in_current_span(),
+        );
+
+        collected_piece_rx
+    }
+
+    /// collect_from_parents collects pieces from multiple parents with load balancing strategy.
+    ///
+    /// The collection process works in two phases:
+    /// 1. **Synchronization Phase**: Waits for a configured duration (DEFAULT_WAIT_FOR_PIECE_FROM_DIFFERENT_PARENTS)
+    ///    to collect the same piece information from different parents. This allows the collector
+    ///    to gather multiple sources for each piece.
+    ///
+    /// 2. **Selection Phase**: After the wait period, selects one parent from the available
+    ///    candidates for each piece by the parent selector and forwards it to the piece downloader.
+    ///
+    /// **Load Balancing Strategy**:
+    /// If the parent selector is enabled, the parent is selected randomly weighted by its score
+    /// calculated from the synchronized host information, so the parents with high cpu, network
+    /// and upload load are rarely selected. Otherwise, the parent is selected randomly.
+    /// The parent selection is designed to distribute download load across multiple parents
+    /// during concurrent piece downloads. This approach ensures:
+    /// - Optimal utilization of bandwidth from multiple parent nodes
+    /// - Prevention of overwhelming any single parent with too many requests
+    /// - Better overall download performance through parallel connections
+    ///
+    /// This strategy is particularly effective when downloading multiple pieces simultaneously,
+    /// as it naturally spreads the workload across the available parent pool.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip_all)]
+    async fn collect_from_parents(
+        config: Arc<Config>,
+        host_id: &str,
+        task_id: &str,
+        parents: Vec<CollectedParent>,
+        interested_pieces: Vec<metadata::Piece>,
+        collected_pieces: Arc<DashMap<u32, Vec<CollectedParent>>>,
+        collected_piece_tx: Sender<CollectedPiece>,
+        collected_piece_timeout: Duration,
+        parent_selector: Arc<ParentSelector>,
+    ) -> Result<()> {
+        // Create a task to collect pieces from peers.
+        let mut join_set = JoinSet::new();
+        for parent in parents.iter() {
+            #[allow(clippy::too_many_arguments)]
+            async fn sync_pieces(
+                config: Arc<Config>,
+                host_id: String,
+                task_id: String,
+                parent: CollectedParent,
+                interested_pieces: Vec<metadata::Piece>,
+                collected_pieces: Arc<DashMap<u32, Vec<CollectedParent>>>,
+                collected_piece_tx: Sender<CollectedPiece>,
+                collected_piece_timeout: Duration,
+                parent_selector: Arc<ParentSelector>,
+            ) -> Result<CollectedParent> {
+                info!("sync cache pieces from parent {}", parent.id);
+
+                // If candidate_parent.host is None, skip it.
+                let host = parent.host.clone().ok_or_else(|| {
+                    error!("cache peer {:?} host is empty", parent);
+                    Error::InvalidPeer(parent.id.clone())
+                })?;
+
+                // Create a dfdaemon client.
+                let dfdaemon_upload_client = DfdaemonUploadClient::new(
+                    config,
+                    format!("http://{}:{}", host.ip, host.port),
+                    false,
+                )
+                .await
+                .inspect_err(|err| {
+                    error!(
+                        "create dfdaemon upload client from parent {} failed: {}",
+                        parent.id, err
+                    );
+                })?;
+
+                let response = dfdaemon_upload_client
+                    .sync_cache_pieces(SyncCachePiecesRequest {
+                        host_id: host_id.to_string(),
+                        task_id: task_id.to_string(),
+                        interested_cache_piece_numbers: interested_pieces
+                            .iter()
+                            .map(|piece| piece.number)
+                            .collect(),
+                    })
+                    .await
+                    .inspect_err(|err| {
+                        error!(
+                            "sync cache pieces from parent {} failed: {}",
+                            parent.id, err
+                        );
+                    })?;
+
+                // If the response repeating timeout exceeds the piece download timeout, the stream will return error.
+                let out_stream = response.into_inner().timeout(collected_piece_timeout);
+                tokio::pin!(out_stream);
+
+                while let Some(message) = out_stream.try_next().await.inspect_err(|err| {
+                    error!(
+                        "sync cache pieces from parent {} failed: {}",
+                        parent.id, err
+                    );
+                })? {
+                    let message = message?;
+                    if let Some(mut parents) = collected_pieces.get_mut(&message.number) {
+                        parents.push(parent.clone());
+                    } else {
+                        continue;
+                    }
+
+                    // Wait for collecting the piece from different parents when the first
+                    // piece is collected.
+                    tokio::time::sleep(DEFAULT_WAIT_FOR_PIECE_FROM_DIFFERENT_PARENTS).await;
+                    let parents = match collected_pieces.remove(&message.number) {
+                        Some((_, parents)) => parents,
+                        None => continue,
+                    };
+
+                    let parent = match parent_selector.select(&parents) {
+                        Some(parent) => parent,
+                        None => {
+                            error!(
+                                "collected_pieces does not contain parent for piece {}",
+                                message.number
+                            );
+                            continue;
+                        }
+                    };
+
+                    info!(
+                        "picked up piece {}-{} metadata from parent {}",
+                        task_id, message.number, parent.id
+                    );
+
+                    collected_piece_tx
+                        .send(CollectedPiece {
+                            number: message.number,
+                            length: message.length,
+                            parent: parent.clone(),
+                        })
+                        .await
+                        .inspect_err(|err| {
+                            error!("send CollectedPiece failed: {}", err);
+                        })?;
+                }
+
+                Ok(parent)
+            }
+
+            join_set.spawn(
+                sync_pieces(
+                    config.clone(),
+                    host_id.to_string(),
+                    task_id.to_string(),
+                    parent.clone(),
+                    interested_pieces.clone(),
+                    collected_pieces.clone(),
+                    collected_piece_tx.clone(),
+                    collected_piece_timeout,
+                    parent_selector.clone(),
+                )
+                .in_current_span(),
+            );
+        }
+
+        // Wait for all tasks to finish.
+        while let Some(message) = join_set.join_next().await {
+            match message {
+                Ok(Ok(peer)) => {
+                    info!("peer {} sync cache pieces finished", peer.id);
+
+                    // If all pieces are collected, abort all tasks.
+                    if collected_pieces.is_empty() {
+                        info!("all cache pieces are collected, abort all tasks");
+                        join_set.abort_all();
+                    }
+                }
+                Ok(Err(err)) => {
+                    error!("sync cache pieces failed: {}", err);
+                }
+                Err(err) => {
+                    error!("sync cache pieces failed: {}", err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
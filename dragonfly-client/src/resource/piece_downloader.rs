@@ -15,7 +15,10 @@
  */
 
 use crate::grpc::dfdaemon_upload::DfdaemonUploadClient;
-use dragonfly_api::dfdaemon::v2::{DownloadPersistentCachePieceRequest, DownloadPieceRequest};
+use crate::tcp;
+use dragonfly_api::dfdaemon::v2::{
+    DownloadCachePieceRequest, DownloadPersistentCachePieceRequest, DownloadPieceRequest,
+};
 use dragonfly_client_config::dfdaemon::Config;
 use dragonfly_client_core::{Error, Result};
 use dragonfly_client_storage::metadata;
@@ -23,6 +26,8 @@ use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, BufStream};
+use tokio::net::TcpStream;
 use tokio::sync::Mutex;
 use tracing::{debug, error, info, instrument};
 
@@ -32,15 +37,21 @@ const DEFAULT_DOWNLOADER_CAPACITY: usize = 2000;
 /// DEFAULT_DOWNLOADER_IDLE_TIMEOUT is the default idle timeout for the downloader.
 const DEFAULT_DOWNLOADER_IDLE_TIMEOUT: Duration = Duration::from_secs(420);
 
+/// DEFAULT_TCP_DOWNLOADER_MAX_IDLE_CONNECTIONS_PER_ADDR is the default maximum number of the idle
+/// connections for a peer's address in the tcp downloader.
+const DEFAULT_TCP_DOWNLOADER_MAX_IDLE_CONNECTIONS_PER_ADDR: usize = 16;
+
 /// Downloader is the interface for downloading pieces, which is implemented by different
 /// protocols. The downloader is used to download pieces from the other peers.
 #[tonic::async_trait]
 pub trait Downloader: Send + Sync {
-    /// download_piece downloads a piece from the other peer by different protocols.
+    /// download_piece downloads a piece from the other peer by different protocols, the length
+    /// is the expected length of the piece and the content of other length is rejected.
     async fn download_piece(
         &self,
         addr: &str,
         number: u32,
+        length: u64,
         host_id: &str,
         task_id: &str,
     ) -> Result<(Vec<u8>, u64, String)>;
@@ -51,6 +62,17 @@ pub trait Downloader: Send + Sync {
         &self,
         addr: &str,
         number: u32,
+        length: u64,
+        host_id: &str,
+        task_id: &str,
+    ) -> Result<(Vec<u8>, u64, String)>;
+
+    /// download_cache_piece downloads a cache piece from the other peer by different protocols.
+    async fn download_cache_piece(
+        &self,
+        addr: &str,
+        number: u32,
+        length: u64,
         host_id: &str,
         task_id: &str,
     ) -> Result<(Vec<u8>, u64, String)>;
@@ -67,12 +89,16 @@ pub struct DownloaderFactory {
 impl DownloaderFactory {
     /// new returns a new DownloadFactory.
     pub fn new(protocol: &str, config: Arc<Config>) -> Result<Self> {
-        let downloader = match protocol {
+        let downloader: Arc<dyn Downloader + Send + Sync> = match protocol {
             "grpc" => Arc::new(GRPCDownloader::new(
                 config.clone(),
                 DEFAULT_DOWNLOADER_CAPACITY,
                 DEFAULT_DOWNLOADER_IDLE_TIMEOUT,
             )),
+            "tcp" => Arc::new(TCPDownloader::new(
+                config.clone(),
+                DEFAULT_TCP_DOWNLOADER_MAX_IDLE_CONNECTIONS_PER_ADDR,
+            )),
             _ => {
                 error!("downloader unsupported protocol: {}", protocol);
                 return Err(Error::InvalidParameter);
@@ -264,6 +290,7 @@ impl Downloader for GRPCDownloader {
         &self,
         addr: &str,
         number: u32,
+        length: u64,
         host_id: &str,
         task_id: &str,
     ) -> Result<(Vec<u8>, u64, String)> {
@@ -301,6 +328,16 @@ impl Downloader for GRPCDownloader {
             return Err(Error::InvalidParameter);
         };
 
+        if content.len() as u64 != length {
+            error!(
+                "piece {} length mismatch: expected {}, actual {}",
+                number,
+                length,
+                content.len()
+            );
+            return Err(Error::ContentLengthMismatch(length, content.len() as u64));
+        }
+
         // Calculate the digest of the piece metadata and compare it with the expected digest,
         // it verifies the integrity of the piece metadata.
         let piece_metadata = metadata::Piece {
@@ -331,6 +368,7 @@ impl Downloader for GRPCDownloader {
         &self,
         addr: &str,
         number: u32,
+        length: u64,
         host_id: &str,
         task_id: &str,
     ) -> Result<(Vec<u8>, u64, String)> {
@@ -368,6 +406,16 @@ impl Downloader for GRPCDownloader {
             return Err(Error::InvalidParameter);
         };
 
+        if content.len() as u64 != length {
+            error!(
+                "piece {} length mismatch: expected {}, actual {}",
+                number,
+                length,
+                content.len()
+            );
+            return Err(Error::ContentLengthMismatch(length, content.len() as u64));
+        }
+
         // Calculate the digest of the piece metadata and compare it with the expected digest,
         // it verifies the integrity of the piece metadata.
         let piece_metadata = metadata::Piece {
@@ -390,4 +438,337 @@ impl Downloader for GRPCDownloader {
 
         Ok((content, piece.offset, piece.digest))
     }
+
+    /// download_cache_piece downloads a cache piece from the other peer by the gRPC protocol.
+    #[instrument(skip_all)]
+    async fn download_cache_piece(
+        &self,
+        addr: &str,
+        number: u32,
+        length: u64,
+        host_id: &str,
+        task_id: &str,
+    ) -> Result<(Vec<u8>, u64, String)> {
+        let entry = self.client_entry(addr).await?;
+        let request_guard = RequestGuard::new(entry.active_requests.clone());
+        let response = match entry
+            .client
+            .download_cache_piece(
+                DownloadCachePieceRequest {
+                    host_id: host_id.to_string(),
+                    task_id: task_id.to_string(),
+                    piece_number: number,
+                },
+                self.config.download.piece_timeout,
+            )
+            .await
+        {
+            Ok(response) => response,
+            Err(err) => {
+                // If the request fails, it will drop the request guard and remove the client
+                // entry to avoid using the invalid client.
+                drop(request_guard);
+                self.remove_client_entry(addr).await;
+                return Err(err);
+            }
+        };
+
+        let Some(piece) = response.piece else {
+            error!("cache piece is missing");
+            return Err(Error::InvalidParameter);
+        };
+
+        let Some(content) = piece.content else {
+            error!("cache piece content is missing");
+            return Err(Error::InvalidParameter);
+        };
+
+        if content.len() as u64 != length {
+            error!(
+                "piece {} length mismatch: expected {}, actual {}",
+                number,
+                length,
+                content.len()
+            );
+            return Err(Error::ContentLengthMismatch(length, content.len() as u64));
+        }
+
+        // Calculate the digest of the piece metadata and compare it with the expected digest,
+        // it verifies the integrity of the piece metadata.
+        let piece_metadata = metadata::Piece {
+            number,
+            length: piece.length,
+            offset: piece.offset,
+            digest: piece.digest.clone(),
+            ..Default::default()
+        };
+
+        if let Some(expected_digest) = response.digest {
+            let digest = piece_metadata.calculate_digest();
+            if expected_digest != digest {
+                return Err(Error::DigestMismatch(
+                    expected_digest.to_string(),
+                    digest.to_string(),
+                ));
+            }
+        }
+
+        Ok((content, piece.offset, piece.digest))
+    }
+}
+
+/// TCPDownloader is the downloader for downloading pieces by the tcp protocol. It will reuse the
+/// idle connections to download pieces from the other peers by peer's address.
+pub struct TCPDownloader {
+    /// config is the configuration of the dfdaemon.
+    config: Arc<Config>,
+
+    /// connections is the map of the idle connections by the peer's address.
+    connections: Arc<Mutex<HashMap<String, Vec<BufStream<TcpStream>>>>>,
+
+    /// max_idle_connections_per_addr is the maximum number of the idle connections for
+    /// a peer's address.
+    max_idle_connections_per_addr: usize,
+}
+
+/// TCPDownloader implements the downloader with the tcp protocol.
+impl TCPDownloader {
+    /// new returns a new TCPDownloader.
+    pub fn new(config: Arc<Config>, max_idle_connections_per_addr: usize) -> Self {
+        Self {
+            config,
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            max_idle_connections_per_addr,
+        }
+    }
+
+    /// download downloads a piece by the tcp protocol. If the reused connection is closed by
+    /// the peer, it will retry with a new connection.
+    async fn download(
+        &self,
+        addr: &str,
+        request: tcp::DownloadPieceRequest,
+        length: u64,
+    ) -> Result<(Vec<u8>, u64, String)> {
+        if let Some(stream) = self.take_connection(addr).await {
+            match self
+                .download_with_connection(addr, stream, &request, length)
+                .await
+            {
+                Err(Error::IO(err)) => {
+                    debug!(
+                        "reused connection {} failed, retry with new connection: {}",
+                        addr, err
+                    );
+                }
+                result => return result,
+            }
+        }
+
+        debug!("creating connection: {}", addr);
+        let stream =
+            tokio::time::timeout(self.config.download.piece_timeout, TcpStream::connect(addr))
+                .await
+                .map_err(|_| Error::Unknown(format!("connect to {} timeout", addr)))??;
+        stream.set_nodelay(true)?;
+        self.download_with_connection(addr, BufStream::new(stream), &request, length)
+            .await
+    }
+
+    /// download_with_connection downloads a piece with the connection, the connection is put
+    /// back to the idle connections if the response is read completely. The length in the
+    /// response is checked by the expected length before the content is read, so the peer can
+    /// not make the downloader allocate the memory with an arbitrary length.
+    async fn download_with_connection(
+        &self,
+        addr: &str,
+        mut stream: BufStream<TcpStream>,
+        request: &tcp::DownloadPieceRequest,
+        expected_length: u64,
+    ) -> Result<(Vec<u8>, u64, String)> {
+        let result = tokio::time::timeout(self.config.download.piece_timeout, async {
+            request.write_to(&mut stream).await?;
+            match tcp::DownloadPieceResponse::read_from(&mut stream).await? {
+                tcp::DownloadPieceResponse::Ok {
+                    offset,
+                    length,
+                    digest,
+                } => {
+                    // The content is not read, so the connection is closed instead of reused.
+                    if length != expected_length {
+                        error!(
+                            "download piece from {} length mismatch: expected {}, actual {}",
+                            addr, expected_length, length
+                        );
+                        return Err(Error::ContentLengthMismatch(expected_length, length));
+                    }
+
+                    let mut content = vec![0; length as usize];
+                    stream.read_exact(&mut content).await?;
+                    Ok::<Result<(Vec<u8>, u64, String)>, Error>(Ok((content, offset, digest)))
+                }
+                tcp::DownloadPieceResponse::Error { status, message } => {
+                    error!(
+                        "download piece from {} failed: {:?} {}",
+                        addr, status, message
+                    );
+                    match status {
+                        tcp::Status::NotFound => Ok(Err(Error::PieceNotFound(message))),
+                        // The connection is closed by the peer after the unauthenticated
+                        // response, so it is not reused.
+                        tcp::Status::Unauthenticated => Err(Error::Unauthorized),
+                        _ => Ok(Err(Error::Unknown(message))),
+                    }
+                }
+            }
+        })
+        .await
+        .map_err(|_| Error::Unknown(format!("download piece from {} timeout", addr)))??;
+
+        // The response is read completely, so the connection can be reused whether the
+        // piece is downloaded or not.
+        self.put_connection(addr, stream).await;
+        result
+    }
+
+    /// take_connection takes an idle connection by the peer's address.
+    async fn take_connection(&self, addr: &str) -> Option<BufStream<TcpStream>> {
+        let mut connections = self.connections.lock().await;
+        let idle_connections = connections.get_mut(addr)?;
+        let stream = idle_connections.pop();
+        if idle_connections.is_empty() {
+            connections.remove(addr);
+        }
+
+        stream
+    }
+
+    /// put_connection puts the connection back to the idle connections, the connection is
+    /// closed if the idle connections exceed the limit.
+    async fn put_connection(&self, addr: &str, stream: BufStream<TcpStream>) {
+        let mut connections = self.connections.lock().await;
+        let idle_connections = connections.entry(addr.to_string()).or_default();
+        if idle_connections.len() < self.max_idle_connections_per_addr {
+            idle_connections.push(stream);
+        }
+    }
+}
+
+/// TCPDownloader implements the Downloader trait.
+#[tonic::async_trait]
+impl Downloader for TCPDownloader {
+    /// download_piece downloads a piece from the other peer by the tcp protocol.
+    #[instrument(skip_all)]
+    async fn download_piece(
+        &self,
+        addr: &str,
+        number: u32,
+        length: u64,
+        host_id: &str,
+        task_id: &str,
+    ) -> Result<(Vec<u8>, u64, String)> {
+        self.download(
+            addr,
+            tcp::DownloadPieceRequest {
+                piece_type: tcp::PieceType::Standard,
+                piece_number: number,
+                host_id: host_id.to_string(),
+                task_id: task_id.to_string(),
+                token: self.config.upload.client.token.clone().unwrap_or_default(),
+            },
+            length,
+        )
+        .await
+    }
+
+    /// download_persistent_cache_piece downloads a persistent cache piece from the other peer by
+    /// the tcp protocol.
+    #[instrument(skip_all)]
+    async fn download_persistent_cache_piece(
+        &self,
+        addr: &str,
+        number: u32,
+        length: u64,
+        host_id: &str,
+        task_id: &str,
+    ) -> Result<(Vec<u8>, u64, String)> {
+        self.download(
+            addr,
+            tcp::DownloadPieceRequest {
+                piece_type: tcp::PieceType::PersistentCache,
+                piece_number: number,
+                host_id: host_id.to_string(),
+                task_id: task_id.to_string(),
+                token: self.config.upload.client.token.clone().unwrap_or_default(),
+            },
+            length,
+        )
+        .await
+    }
+
+    /// download_cache_piece downloads a cache piece from the other peer by the tcp protocol.
+    #[instrument(skip_all)]
+    async fn download_cache_piece(
+        &self,
+        addr: &str,
+        number: u32,
+        length: u64,
+        host_id: &str,
+        task_id: &str,
+    ) -> Result<(Vec<u8>, u64, String)> {
+        self.download(
+            addr,
+            tcp::DownloadPieceRequest {
+                piece_type: tcp::PieceType::Cache,
+                piece_number: number,
+                host_id: host_id.to_string(),
+                task_id: task_id.to_string(),
+                token: self.config.upload.client.token.clone().unwrap_or_default(),
+            },
+            length,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn should_reject_tcp_piece_with_unexpected_length() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            tcp::DownloadPieceRequest::read_from(&mut stream)
+                .await
+                .unwrap()
+                .unwrap();
+
+            // The peer announces a huge length without sending the content.
+            let mut buf = Vec::new();
+            tcp::DownloadPieceResponse::Ok {
+                offset: 0,
+                length: u64::MAX,
+                digest: "crc32:0".to_string(),
+            }
+            .write_to(&mut buf)
+            .await
+            .unwrap();
+            stream.write_all(&buf).await.unwrap();
+            stream.flush().await.unwrap();
+        });
+
+        let downloader = TCPDownloader::new(Arc::new(Config::default()), 1);
+        let result = downloader
+            .download_piece(&addr, 0, 1024, "host", "task")
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::ContentLengthMismatch(1024, u64::MAX))
+        ));
+    }
 }
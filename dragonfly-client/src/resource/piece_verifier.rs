@@ -0,0 +1,497 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::grpc::dfdaemon_upload::DfdaemonUploadClient;
+use crate::resource::piece_collector::CollectedParent;
+use dragonfly_api::common::v2::{Host, Task};
+use dragonfly_api::dfdaemon::v2::StatTaskRequest;
+use dragonfly_client_config::dfdaemon::{Config, HostType};
+use dragonfly_client_core::{Error, Result};
+use dragonfly_client_util::digest::{Algorithm, Hasher, SEPARATOR};
+use dragonfly_client_util::merkle::MerkleTree;
+use futures::future::join_all;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{error, info, instrument};
+
+/// MIN_REFRESH_INTERVAL is the minimum interval to refresh the trusted digests from the seed
+/// peers, it prevents the pieces not finished by the seed peers from flooding them.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Manifest is the trusted digests of the pieces established by the seed peers.
+#[derive(Default)]
+struct Manifest {
+    /// digests are the sha256 digests of the pieces by the piece number.
+    digests: HashMap<u32, String>,
+
+    /// root is the merkle root of the task, it is established when the seed peer has finished
+    /// all the pieces of the task.
+    root: Option<String>,
+
+    /// refreshed_at is the time of the latest refresh.
+    refreshed_at: Option<Instant>,
+}
+
+/// PieceVerifier verifies the pieces downloaded from the untrusted parents before they are
+/// stored. The seed peers download the pieces from the source and hash them by sha256, so the
+/// digests of the seed peers are trusted, and the parent whose piece does not match the
+/// trusted digest is forging the piece. The digests are trusted only if the quorum of the seed
+/// peers report them, and all of the seed peers must agree on the digests. The digests of all
+/// the pieces form the merkle tree of the task, and the seed peers must agree on the merkle root.
+pub struct PieceVerifier {
+    /// config is the configuration of the dfdaemon.
+    config: Arc<Config>,
+
+    /// task_id is the id of the task.
+    task_id: String,
+
+    /// seed_parents are the parents which are seed peers.
+    seed_parents: Vec<CollectedParent>,
+
+    /// manifest is the trusted digests of the pieces.
+    manifest: Mutex<Manifest>,
+}
+
+/// is_seed_parent returns whether the host of the parent is a seed peer, only the seed peers
+/// establish the trusted digests. The pieces can not be verified without the seed parents, so
+/// the task is downloaded from the source instead.
+pub fn is_seed_parent(host: Option<&Host>) -> bool {
+    host.is_some_and(|host| host.r#type != HostType::Normal as u32)
+}
+
+/// PieceVerifier implements the piece verifier.
+impl PieceVerifier {
+    /// new creates a new PieceVerifier, only the seed peers of the parents establish the
+    /// trusted digests.
+    pub fn new(config: Arc<Config>, task_id: &str, parents: &[CollectedParent]) -> Self {
+        let seed_parents = parents
+            .iter()
+            .filter(|parent| is_seed_parent(parent.host.as_ref()))
+            .cloned()
+            .collect();
+
+        Self {
+            config,
+            task_id: task_id.to_string(),
+            seed_parents,
+            manifest: Mutex::new(Manifest::default()),
+        }
+    }
+
+    /// verify verifies the content of the piece by the trusted digest, and returns the trusted
+    /// digest if the content matches.
+    #[instrument(skip_all)]
+    pub async fn verify(&self, number: u32, content: &[u8]) -> Result<String> {
+        let expected_digest = self.trusted_digest(number).await?;
+
+        let mut hasher = Hasher::new(Algorithm::Sha256);
+        hasher.update(content);
+        let digest = hasher.finalize().to_string();
+        if digest != expected_digest {
+            return Err(Error::DigestMismatch(expected_digest, digest));
+        }
+
+        Ok(expected_digest)
+    }
+
+    /// trusted_digest returns the trusted digest of the piece, the digests are refreshed from
+    /// the seed peers if the piece is unknown.
+    async fn trusted_digest(&self, number: u32) -> Result<String> {
+        let mut manifest = self.manifest.lock().await;
+        if let Some(digest) = manifest.digests.get(&number) {
+            return Ok(digest.clone());
+        }
+
+        if manifest
+            .refreshed_at
+            .is_none_or(|refreshed_at| refreshed_at.elapsed() >= MIN_REFRESH_INTERVAL)
+        {
+            self.refresh(&mut manifest).await?;
+        }
+
+        manifest.digests.get(&number).cloned().ok_or_else(|| {
+            Error::Unknown(format!(
+                "piece {} of task {} is not finished by the seed peers",
+                number, self.task_id
+            ))
+        })
+    }
+
+    /// quorum returns the number of the seed peers which must report the same digest before
+    /// it is trusted, it is the majority of the seed peers.
+    fn quorum(&self) -> usize {
+        self.seed_parents.len() / 2 + 1
+    }
+
+    /// refresh refreshes the trusted digests from all of the seed peers, the quorum of the
+    /// seed peers must respond.
+    async fn refresh(&self, manifest: &mut Manifest) -> Result<()> {
+        manifest.refreshed_at = Some(Instant::now());
+        if self.seed_parents.is_empty() {
+            return Err(Error::Unsupported(format!(
+                "verify pieces of task {} without seed peer parents",
+                self.task_id
+            )));
+        }
+
+        let results = join_all(
+            self.seed_parents
+                .iter()
+                .map(|parent| async move { (parent, self.stat_task(parent).await) }),
+        )
+        .await;
+
+        let mut tasks = Vec::with_capacity(results.len());
+        for (parent, result) in results {
+            match result {
+                Ok(task) => tasks.push((parent.id.clone(), task)),
+                Err(err) => {
+                    error!("stat task from seed peer {} failed: {}", parent.id, err);
+                }
+            }
+        }
+
+        let quorum = self.quorum();
+        if tasks.len() < quorum {
+            return Err(Error::Unknown(format!(
+                "{} of {} seed peers respond with the digests of task {}, but {} are required",
+                tasks.len(),
+                self.seed_parents.len(),
+                self.task_id,
+                quorum
+            )));
+        }
+
+        Self::merge(manifest, quorum, tasks)
+    }
+
+    /// stat_task stats the local task of the seed peer, the finished pieces are returned
+    /// with their digests.
+    async fn stat_task(&self, parent: &CollectedParent) -> Result<Task> {
+        let host = parent
+            .host
+            .as_ref()
+            .ok_or_else(|| Error::InvalidPeer(parent.id.clone()))?;
+
+        let dfdaemon_upload_client = DfdaemonUploadClient::new(
+            self.config.clone(),
+            format!("http://{}:{}", host.ip, host.port),
+            false,
+        )
+        .await?;
+
+        dfdaemon_upload_client
+            .stat_task(StatTaskRequest {
+                task_id: self.task_id.clone(),
+                remote_ip: None,
+                local_only: true,
+            })
+            .await
+    }
+
+    /// merge merges the digests of the task stated from the seed peers into the manifest. The
+    /// seed peers must agree on the digests and the merkle root, and they must be the same as
+    /// the established ones, otherwise the seed peers disagree on the content of the task. The
+    /// digest and the merkle root are established only if the quorum of the seed peers report
+    /// them.
+    fn merge(manifest: &mut Manifest, quorum: usize, tasks: Vec<(String, Task)>) -> Result<()> {
+        let sha256 = Algorithm::Sha256.to_string();
+
+        // The seed peers hash the pieces by crc32 if the integrity is not enabled, then no
+        // digest can be trusted.
+        let is_sha256 = |digest: &str| {
+            digest
+                .split_once(SEPARATOR)
+                .is_some_and(|(algorithm, _)| algorithm == sha256)
+        };
+        if !tasks.is_empty()
+            && tasks.iter().all(|(_, task)| {
+                !task.pieces.is_empty() && !task.pieces.iter().any(|piece| is_sha256(&piece.digest))
+            })
+        {
+            return Err(Error::Unsupported(
+                "verify pieces by the seed peers without the integrity enabled".to_string(),
+            ));
+        }
+
+        let mut reported_digests: HashMap<u32, (String, usize)> = HashMap::new();
+        let mut reported_root: Option<(String, usize)> = None;
+        for (seed_peer_id, task) in tasks {
+            let mut pieces = task
+                .pieces
+                .into_iter()
+                .filter(|piece| is_sha256(&piece.digest))
+                .collect::<Vec<_>>();
+            pieces.sort_by_key(|piece| piece.number);
+
+            for piece in pieces.iter() {
+                let expected_digest = manifest.digests.get(&piece.number).or(reported_digests
+                    .get(&piece.number)
+                    .map(|(digest, _)| digest));
+                if let Some(digest) = expected_digest {
+                    if *digest != piece.digest {
+                        error!(
+                            "seed peer {} reports digest {} of piece {}, but {} is reported",
+                            seed_peer_id, piece.digest, piece.number, digest
+                        );
+
+                        return Err(Error::DigestMismatch(digest.clone(), piece.digest.clone()));
+                    }
+                }
+
+                reported_digests
+                    .entry(piece.number)
+                    .or_insert_with(|| (piece.digest.clone(), 0))
+                    .1 += 1;
+            }
+
+            // The merkle root is reported if the seed peer has finished all the pieces.
+            let length: u64 = pieces.iter().map(|piece| piece.length).sum();
+            let complete = task.content_length > 0
+                && length == task.content_length
+                && pieces
+                    .iter()
+                    .enumerate()
+                    .all(|(index, piece)| piece.number == index as u32);
+            if complete {
+                let leaves = pieces
+                    .iter()
+                    .map(|piece| piece.digest.clone())
+                    .collect::<Vec<String>>();
+                let root = MerkleTree::new(&leaves).root().to_string();
+                let expected_root = manifest
+                    .root
+                    .as_ref()
+                    .or(reported_root.as_ref().map(|(root, _)| root));
+                if let Some(expected_root) = expected_root {
+                    if *expected_root != root {
+                        error!(
+                            "seed peer {} reports merkle root {}, but {} is reported",
+                            seed_peer_id, root, expected_root
+                        );
+
+                        return Err(Error::DigestMismatch(expected_root.clone(), root));
+                    }
+                }
+
+                reported_root.get_or_insert((root, 0)).1 += 1;
+            }
+        }
+
+        if let Some((root, count)) = reported_root {
+            if manifest.root.is_none() && count >= quorum {
+                info!(
+                    "merkle root {} is established by {} seed peers",
+                    root, count
+                );
+                manifest.root = Some(root);
+            }
+        }
+
+        manifest.digests.extend(
+            reported_digests
+                .into_iter()
+                .filter(|(_, (_, count))| *count >= quorum)
+                .map(|(number, (digest, _))| (number, digest)),
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dragonfly_api::common::v2::Piece;
+
+    fn sha256(content: &[u8]) -> String {
+        let mut hasher = Hasher::new(Algorithm::Sha256);
+        hasher.update(content);
+        hasher.finalize().to_string()
+    }
+
+    fn new_task(pieces: &[&[u8]], content_length: u64) -> Task {
+        let mut offset = 0;
+        Task {
+            id: "task".to_string(),
+            content_length,
+            pieces: pieces
+                .iter()
+                .enumerate()
+                .map(|(number, content)| {
+                    let piece = Piece {
+                        number: number as u32,
+                        offset,
+                        length: content.len() as u64,
+                        digest: sha256(content),
+                        ..Default::default()
+                    };
+                    offset += content.len() as u64;
+                    piece
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_new_piece_verifier() {
+        let parents = vec![
+            CollectedParent {
+                id: "normal".to_string(),
+                host: Some(Host {
+                    r#type: HostType::Normal as u32,
+                    ..Default::default()
+                }),
+            },
+            CollectedParent {
+                id: "seed".to_string(),
+                host: Some(Host {
+                    r#type: HostType::Super as u32,
+                    ..Default::default()
+                }),
+            },
+            CollectedParent {
+                id: "unknown".to_string(),
+                host: None,
+            },
+        ];
+
+        let verifier = PieceVerifier::new(Arc::new(Config::default()), "task", &parents);
+        assert_eq!(verifier.seed_parents.len(), 1);
+        assert_eq!(verifier.seed_parents[0].id, "seed");
+    }
+
+    #[tokio::test]
+    async fn test_verify() {
+        let verifier = PieceVerifier::new(Arc::new(Config::default()), "task", &[]);
+        PieceVerifier::merge(
+            &mut *verifier.manifest.lock().await,
+            1,
+            vec![("seed".to_string(), new_task(&[b"foo", b"bar"], 6))],
+        )
+        .unwrap();
+
+        assert_eq!(verifier.verify(0, b"foo").await.unwrap(), sha256(b"foo"));
+        assert!(matches!(
+            verifier.verify(1, b"baz").await,
+            Err(Error::DigestMismatch(_, _))
+        ));
+
+        // The piece is unknown and there is no seed peer to refresh the digests.
+        assert!(matches!(
+            verifier.verify(2, b"foo").await,
+            Err(Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_merge() {
+        // The merkle root is not established until the seed peer finishes all the pieces.
+        let mut manifest = Manifest::default();
+        PieceVerifier::merge(
+            &mut manifest,
+            1,
+            vec![("seed".to_string(), new_task(&[b"foo"], 6))],
+        )
+        .unwrap();
+        assert_eq!(manifest.digests.len(), 1);
+        assert!(manifest.root.is_none());
+
+        PieceVerifier::merge(
+            &mut manifest,
+            1,
+            vec![("seed".to_string(), new_task(&[b"foo", b"bar"], 6))],
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.root,
+            Some(
+                MerkleTree::new(&[sha256(b"foo"), sha256(b"bar")])
+                    .root()
+                    .to_string()
+            )
+        );
+
+        // The seed peers without the integrity enabled report the crc32 digests only.
+        let mut task = new_task(&[b"foo"], 3);
+        task.pieces[0].digest = "crc32:123".to_string();
+        assert!(matches!(
+            PieceVerifier::merge(&mut manifest, 1, vec![("seed".to_string(), task)]),
+            Err(Error::Unsupported(_))
+        ));
+
+        // The crc32 digests are not trusted.
+        let mut task = new_task(&[b"foo", b"bar", b"baz"], 9);
+        task.pieces[2].digest = "crc32:123".to_string();
+        PieceVerifier::merge(&mut manifest, 1, vec![("seed".to_string(), task)]).unwrap();
+        assert!(!manifest.digests.contains_key(&2));
+
+        // The seed peer disagrees on the digest of the piece.
+        assert!(PieceVerifier::merge(
+            &mut manifest,
+            1,
+            vec![("forged".to_string(), new_task(&[b"fox"], 3))]
+        )
+        .is_err());
+        assert_eq!(manifest.digests[&0], sha256(b"foo"));
+    }
+
+    #[test]
+    fn test_merge_with_quorum() {
+        // The digest reported by one of the seed peers is not trusted.
+        let mut manifest = Manifest::default();
+        PieceVerifier::merge(
+            &mut manifest,
+            2,
+            vec![
+                ("seed1".to_string(), new_task(&[b"foo", b"bar"], 6)),
+                ("seed2".to_string(), new_task(&[b"foo"], 6)),
+            ],
+        )
+        .unwrap();
+        assert_eq!(manifest.digests.len(), 1);
+        assert_eq!(manifest.digests[&0], sha256(b"foo"));
+        assert!(manifest.root.is_none());
+
+        // The merkle root is established by the quorum of the seed peers.
+        PieceVerifier::merge(
+            &mut manifest,
+            2,
+            vec![
+                ("seed1".to_string(), new_task(&[b"foo", b"bar"], 6)),
+                ("seed2".to_string(), new_task(&[b"foo", b"bar"], 6)),
+            ],
+        )
+        .unwrap();
+        assert_eq!(manifest.digests[&1], sha256(b"bar"));
+        assert!(manifest.root.is_some());
+
+        // The seed peers disagree on the digest of the piece.
+        let mut manifest = Manifest::default();
+        assert!(PieceVerifier::merge(
+            &mut manifest,
+            2,
+            vec![
+                ("seed1".to_string(), new_task(&[b"foo"], 3)),
+                ("seed2".to_string(), new_task(&[b"fox"], 3)),
+            ],
+        )
+        .is_err());
+        assert!(manifest.digests.is_empty());
+    }
+}
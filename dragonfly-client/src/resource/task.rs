@@ -14,7 +14,8 @@
  * limitations under the License.
  */
 
-use crate::grpc::{scheduler::SchedulerClient, REQUEST_TIMEOUT};
+use crate::gc::quota::Quota;
+use crate::grpc::{prefetch_task, scheduler::SchedulerClient, REQUEST_TIMEOUT};
 use crate::metrics::{
     collect_backend_request_failure_metrics, collect_backend_request_finished_metrics,
     collect_backend_request_started_metrics,
@@ -25,19 +26,20 @@ use dragonfly_api::common::v2::{
 };
 use dragonfly_api::dfdaemon::{
     self,
-    v2::{download_task_response, DownloadTaskResponse},
+    v2::{download_task_response, DownloadTaskRequest, DownloadTaskResponse},
 };
 use dragonfly_api::errordetails::v2::{Backend, Unknown};
 use dragonfly_api::scheduler::v2::{
     announce_peer_request, announce_peer_response, download_piece_back_to_source_failed_request,
-    AnnouncePeerRequest, DeleteTaskRequest, DownloadPeerBackToSourceFailedRequest,
-    DownloadPeerBackToSourceFinishedRequest, DownloadPeerBackToSourceStartedRequest,
-    DownloadPeerFailedRequest, DownloadPeerFinishedRequest, DownloadPeerStartedRequest,
-    DownloadPieceBackToSourceFailedRequest, DownloadPieceBackToSourceFinishedRequest,
-    DownloadPieceFailedRequest, DownloadPieceFinishedRequest, RegisterPeerRequest,
+    AnnouncePeerRequest, AnnouncePeerResponse, DeleteTaskRequest,
+    DownloadPeerBackToSourceFailedRequest, DownloadPeerBackToSourceFinishedRequest,
+    DownloadPeerBackToSourceStartedRequest, DownloadPeerFailedRequest, DownloadPeerFinishedRequest,
+    DownloadPeerStartedRequest, DownloadPieceBackToSourceFailedRequest,
+    DownloadPieceBackToSourceFinishedRequest, DownloadPieceFailedRequest,
+    DownloadPieceFinishedRequest, NeedBackToSourceResponse, RegisterPeerRequest,
     ReschedulePeerRequest, StatTaskRequest,
 };
-use dragonfly_client_backend::{BackendFactory, HeadRequest};
+use dragonfly_client_backend::{BackendFactory, HeadRequest, HeadResponse};
 use dragonfly_client_config::dfdaemon::Config;
 use dragonfly_client_core::{
     error::{BackendError, DownloadFromParentFailed, ErrorType, OrErr},
@@ -45,11 +47,13 @@ use dragonfly_client_core::{
 };
 use dragonfly_client_storage::{metadata, Storage};
 use dragonfly_client_util::{
+    digest::{Algorithm, Digest},
     http::{hashmap_to_headermap, headermap_to_hashmap},
     id_generator::IDGenerator,
 };
 use reqwest::header::HeaderMap;
 use std::collections::HashMap;
+use std::marker::PhantomData;
 use std::path::Path;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -63,11 +67,15 @@ use tokio::sync::{
 };
 use tokio::task::JoinSet;
 use tokio_stream::{wrappers::ReceiverStream, StreamExt};
-use tonic::{Request, Status};
+use tonic::{codec::Streaming, Request, Status};
 use tracing::{debug, error, info, instrument, warn, Instrument};
 
 use super::*;
 
+/// DOCKER_CONTENT_DIGEST_HEADER is the response header of the registry which contains the
+/// digest of the blob.
+const DOCKER_CONTENT_DIGEST_HEADER: &str = "Docker-Content-Digest";
+
 /// Task represents a task manager.
 pub struct Task {
     /// config is the configuration of the dfdaemon.
@@ -87,6 +95,12 @@ pub struct Task {
 
     /// piece is the piece manager.
     pub piece: Arc<piece::Piece>,
+
+    /// parent_selector is the parent selector.
+    pub parent_selector: Arc<parent_selector::ParentSelector>,
+
+    /// quota enforces the storage quotas of the applications.
+    quota: Quota,
 }
 
 /// Task implements the task manager.
@@ -98,6 +112,7 @@ impl Task {
         storage: Arc<Storage>,
         scheduler_client: Arc<SchedulerClient>,
         backend_factory: Arc<BackendFactory>,
+        parent_selector: Arc<parent_selector::ParentSelector>,
     ) -> ClientResult<Self> {
         let piece = piece::Piece::new(
             config.clone(),
@@ -106,6 +121,12 @@ impl Task {
             backend_factory.clone(),
         )?;
         let piece = Arc::new(piece);
+        let quota = Quota::new(
+            config.clone(),
+            id_generator.host_id(),
+            storage.clone(),
+            scheduler_client.clone(),
+        );
 
         Ok(Self {
             config,
@@ -114,9 +135,31 @@ impl Task {
             scheduler_client: scheduler_client.clone(),
             backend_factory: backend_factory.clone(),
             piece: piece.clone(),
+            parent_selector,
+            quota,
         })
     }
 
+    /// with_backend_factory returns a new Task which downloads from the source by the backend
+    /// factory, the piece manager shares the rate limiters with the task.
+    pub fn with_backend_factory(&self, backend_factory: Arc<BackendFactory>) -> Self {
+        Self {
+            config: self.config.clone(),
+            id_generator: self.id_generator.clone(),
+            storage: self.storage.clone(),
+            scheduler_client: self.scheduler_client.clone(),
+            backend_factory: backend_factory.clone(),
+            piece: Arc::new(self.piece.with_backend_factory(backend_factory)),
+            parent_selector: self.parent_selector.clone(),
+            quota: Quota::new(
+                self.config.clone(),
+                self.id_generator.host_id(),
+                self.storage.clone(),
+                self.scheduler_client.clone(),
+            ),
+        }
+    }
+
     /// get gets the metadata of the task.
     #[instrument(skip_all)]
     pub fn get(&self, id: &str) -> ClientResult<Option<metadata::Task>> {
@@ -130,7 +173,15 @@ impl Task {
         id: &str,
         request: Download,
     ) -> ClientResult<metadata::Task> {
-        let task = self.storage.prepare_download_task_started(id).await?;
+        // The output path is plaintext, so it is checked before the task downloads started.
+        if request.output_path.is_some() {
+            self.storage.check_output(id)?;
+        }
+
+        let task = self
+            .storage
+            .prepare_download_task_started(id, request.application.clone(), request.tag.clone())
+            .await?;
 
         if task.content_length.is_some() && task.piece_length.is_some() {
             // Attempt to create a hard link from the task file to the output path.
@@ -157,6 +208,106 @@ impl Task {
             return Ok(task);
         }
 
+        // Head the url to get the content length.
+        let response = self.head_backend(id, &request).await?;
+
+        let content_length = match response.content_length {
+            Some(content_length) => content_length,
+            None => return Err(Error::InvalidContentLength),
+        };
+
+        let piece_length = match request.piece_length {
+            Some(piece_length) => self
+                .piece
+                .calculate_piece_length(piece::PieceLengthStrategy::FixedPieceLength(piece_length)),
+            None => {
+                self.piece
+                    .calculate_piece_length(piece::PieceLengthStrategy::OptimizeByFileLength(
+                        content_length,
+                    ))
+            }
+        };
+
+        // If the task is not finished, check if the storage has enough space to
+        // store the task.
+        if !task.is_finished() && !self.storage.has_enough_space(content_length)? {
+            return Err(Error::NoSpace(format!(
+                "not enough space to store the task: content_length={}",
+                content_length
+            )));
+        }
+
+        // If the task is not finished, reserve the space in the quota of the application, the
+        // tasks of the application may be evicted.
+        if !task.is_finished() {
+            self.quota
+                .reserve(id, request.application.as_deref(), content_length)
+                .await?;
+        }
+
+        // Get the digest to deduplicate the task before the response header is stored.
+        let digest = self.dedup_digest(request.digest.as_deref(), response.http_header.as_ref());
+
+        let task = self
+            .storage
+            .download_task_started(
+                id,
+                piece_length,
+                content_length,
+                response.http_header,
+                digest,
+            )
+            .await?;
+
+        // Reuse the content and pieces of the finished task which has the same digest, then
+        // the reused pieces are downloaded from local.
+        if !task.is_finished() {
+            if let Err(err) = self.storage.reuse_task(&task).await {
+                warn!("reuse task by digest failed: {}", err);
+            }
+        }
+
+        // Attempt to create a hard link from the task file to the output path.
+        //
+        // Behavior based on force_hard_link setting:
+        // 1. force_hard_link is true:
+        //    - Success: Continue processing
+        //    - Failure: Return error immediately
+        // 2. force_hard_link is false:
+        //    - Success: Continue processing
+        //    - Failure: Fall back to copying the file instead
+        if let Some(output_path) = &request.output_path {
+            if let Err(err) = self
+                .storage
+                .hard_link_task(id, Path::new(output_path.as_str()))
+                .await
+            {
+                if request.force_hard_link {
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(task)
+    }
+
+    /// head gets the content length and the response header of the task, they are read from
+    /// the metadata if the task has been started, otherwise the backend is requested.
+    #[instrument(skip_all)]
+    pub async fn head(&self, id: &str, request: &Download) -> ClientResult<(u64, HeaderMap)> {
+        if let Some(task) = self.storage.get_task(id)? {
+            if let Some(content_length) = task.content_length() {
+                return Ok((content_length, hashmap_to_headermap(&task.response_header)?));
+            }
+        }
+
+        let response = self.head_backend(id, request).await?;
+        let content_length = response.content_length.ok_or(Error::InvalidContentLength)?;
+        Ok((content_length, response.http_header.unwrap_or_default()))
+    }
+
+    /// head_backend heads the url of the download request by the backend.
+    async fn head_backend(&self, id: &str, request: &Download) -> ClientResult<HeadResponse> {
         // Handle the request header.
         let mut request_header =
             hashmap_to_headermap(&request.request_header).inspect_err(|err| {
@@ -168,7 +319,6 @@ impl Task {
         // a 200 full content.
         request_header.remove(reqwest::header::RANGE);
 
-        // Head the url to get the content length.
         let backend = self.backend_factory.build(request.url.as_str())?;
 
         // Record the start time.
@@ -182,12 +332,12 @@ impl Task {
         let response = backend
             .head(HeadRequest {
                 task_id: id.to_string(),
-                url: request.url,
+                url: request.url.clone(),
                 http_header: Some(request_header),
                 timeout: self.config.download.piece_timeout,
                 client_cert: None,
-                object_storage: request.object_storage,
-                hdfs: request.hdfs,
+                object_storage: request.object_storage.clone(),
+                hdfs: request.hdfs.clone(),
             })
             .await
             .inspect_err(|_err| {
@@ -220,59 +370,31 @@ impl Task {
             start_time.elapsed(),
         );
 
-        let content_length = match response.content_length {
-            Some(content_length) => content_length,
-            None => return Err(Error::InvalidContentLength),
-        };
-
-        let piece_length = match request.piece_length {
-            Some(piece_length) => self
-                .piece
-                .calculate_piece_length(piece::PieceLengthStrategy::FixedPieceLength(piece_length)),
-            None => {
-                self.piece
-                    .calculate_piece_length(piece::PieceLengthStrategy::OptimizeByFileLength(
-                        content_length,
-                    ))
-            }
-        };
-
-        // If the task is not finished, check if the storage has enough space to
-        // store the task.
-        if !task.is_finished() && !self.storage.has_enough_space(content_length)? {
-            return Err(Error::NoSpace(format!(
-                "not enough space to store the task: content_length={}",
-                content_length
-            )));
-        }
-
-        let task = self
-            .storage
-            .download_task_started(id, piece_length, content_length, response.http_header)
-            .await;
+        Ok(response)
+    }
 
-        // Attempt to create a hard link from the task file to the output path.
-        //
-        // Behavior based on force_hard_link setting:
-        // 1. force_hard_link is true:
-        //    - Success: Continue processing
-        //    - Failure: Return error immediately
-        // 2. force_hard_link is false:
-        //    - Success: Continue processing
-        //    - Failure: Fall back to copying the file instead
-        if let Some(output_path) = &request.output_path {
-            if let Err(err) = self
-                .storage
-                .hard_link_task(id, Path::new(output_path.as_str()))
-                .await
-            {
-                if request.force_hard_link {
-                    return Err(err);
-                }
-            }
+    /// dedup_digest returns the sha256 digest of the content to deduplicate the tasks which
+    /// have the same content, the digest is from the download request or the
+    /// Docker-Content-Digest response header of the registry.
+    fn dedup_digest(
+        &self,
+        request_digest: Option<&str>,
+        response_header: Option<&HeaderMap>,
+    ) -> Option<String> {
+        if !self.config.storage.dedup {
+            return None;
         }
 
-        task
+        request_digest
+            .or_else(|| {
+                response_header?
+                    .get(DOCKER_CONTENT_DIGEST_HEADER)?
+                    .to_str()
+                    .ok()
+            })
+            .and_then(|digest| digest.parse::<Digest>().ok())
+            .filter(|digest| digest.algorithm() == Algorithm::Sha256)
+            .map(|digest| digest.to_string())
     }
 
     /// download_finished updates the metadata of the task when the task downloads finished.
@@ -287,10 +409,16 @@ impl Task {
         self.storage.download_task_failed(id).await.map(|_| ())
     }
 
-    /// prefetch_task_started updates the metadata of the task when the task prefetch started.
+    /// prefetch_task_started updates the metadata of the task when the task prefetch started,
+    /// the download request is stored to resume the prefetch after dfdaemon restarts.
     #[instrument(skip_all)]
-    pub async fn prefetch_task_started(&self, id: &str) -> ClientResult<metadata::Task> {
-        self.storage.prefetch_task_started(id).await
+    pub async fn prefetch_task_started(
+        &self,
+        id: &str,
+        request: &Download,
+    ) -> ClientResult<metadata::Task> {
+        let request = encode_prefetch_request(request)?;
+        self.storage.prefetch_task_started(id, request).await
     }
 
     /// prefetch_task_failed updates the metadata of the task when the task prefetch failed.
@@ -299,6 +427,69 @@ impl Task {
         self.storage.prefetch_task_failed(id).await
     }
 
+    /// resume_tasks prepares the unfinished tasks of the last run to be resumed, the finished
+    /// pieces are verified and kept, so the subsequent download only downloads the missing
+    /// pieces. It returns the resumable tasks.
+    #[instrument(skip_all)]
+    pub async fn resume_tasks(&self) -> ClientResult<Vec<metadata::Task>> {
+        let mut tasks = Vec::new();
+        for task in self.storage.get_tasks()? {
+            if !task.is_resumable() {
+                continue;
+            }
+
+            match self.storage.resume_task(&task).await {
+                Ok(kept_pieces) => {
+                    info!(
+                        "resume task {} with {} finished pieces",
+                        task.id, kept_pieces
+                    );
+                    tasks.push(task);
+                }
+                Err(err) => {
+                    error!("resume task {} failed, delete it: {}", task.id, err);
+                    self.storage.delete_task(&task.id).await;
+                }
+            }
+        }
+
+        Ok(tasks)
+    }
+
+    /// resume_prefetches resumes the prefetches of the resumable tasks by the dfdaemon download
+    /// server, it must be called after the dfdaemon download server is started.
+    #[instrument(skip_all)]
+    pub async fn resume_prefetches(&self, tasks: Vec<metadata::Task>) {
+        for task in tasks {
+            let Some(request) = task.prefetch_request.as_ref() else {
+                continue;
+            };
+
+            let download = match decode_prefetch_request(request) {
+                Ok(download) => download,
+                Err(err) => {
+                    error!("deserialize prefetch request of task {}: {}", task.id, err);
+                    continue;
+                }
+            };
+
+            info!("resume prefetch task {}", task.id);
+            if let Err(err) = prefetch_task(
+                self.config.download.server.socket_path.clone(),
+                Request::new(DownloadTaskRequest {
+                    download: Some(download),
+                }),
+            )
+            .await
+            {
+                error!("resume prefetch task {} failed: {}", task.id, err);
+                if let Err(err) = self.prefetch_task_failed(task.id.as_str()).await {
+                    error!("prefetch task failed: {}", err);
+                }
+            }
+        }
+    }
+
     /// is_same_dev_inode checks if the task is on the same device inode as the given path.
     pub async fn is_same_dev_inode(&self, id: &str, to: &Path) -> ClientResult<bool> {
         self.storage.is_same_dev_inode_as_task(id, to).await
@@ -400,11 +591,19 @@ impl Task {
                 error!("send DownloadTaskStartedResponse failed: {:?}", err);
             })?;
 
+        // Initialize the partial downloader of the pieces on the disk.
+        let downloader = PartialDownloader::<DiskStorage>::new(
+            self.config.clone(),
+            self.scheduler_client.clone(),
+            self.piece.clone(),
+            self.parent_selector.clone(),
+        );
+
         // Download the pieces from the local.
         debug!("download the pieces from local");
-        let finished_pieces = match self
+        let finished_pieces = match downloader
             .download_partial_from_local(
-                task,
+                task_id,
                 host_id,
                 peer_id,
                 request.need_piece_content,
@@ -440,14 +639,24 @@ impl Task {
         debug!("download the pieces with scheduler");
 
         // Download the pieces with scheduler.
-        let finished_pieces = match self
+        let finished_pieces = match downloader
             .download_partial_with_scheduler(
-                task,
+                task_id,
                 host_id,
                 peer_id,
                 interested_pieces.clone(),
                 content_length,
-                request.clone(),
+                AnnouncePeerRequest {
+                    host_id: host_id.to_string(),
+                    task_id: task_id.to_string(),
+                    peer_id: peer_id.to_string(),
+                    request: Some(announce_peer_request::Request::RegisterPeerRequest(
+                        RegisterPeerRequest {
+                            download: Some(request.clone()),
+                        },
+                    )),
+                },
+                PieceRequest::from(&request),
                 download_progress_tx.clone(),
             )
             .await
@@ -466,13 +675,13 @@ impl Task {
                 };
 
                 // Download the pieces from the source.
-                if let Err(err) = self
+                if let Err(err) = downloader
                     .download_partial_from_source(
-                        task,
+                        task_id,
                         host_id,
                         peer_id,
                         interested_pieces.clone(),
-                        request.clone(),
+                        PieceRequest::from(&request),
                         download_progress_tx.clone(),
                     )
                     .await
@@ -511,13 +720,13 @@ impl Task {
         };
 
         // Download the pieces from the source.
-        if let Err(err) = self
+        if let Err(err) = downloader
             .download_partial_from_source(
-                task,
+                task_id,
                 host_id,
                 peer_id,
                 interested_pieces.clone(),
-                request.clone(),
+                PieceRequest::from(&request),
                 download_progress_tx.clone(),
             )
             .await
@@ -530,47 +739,678 @@ impl Task {
         Ok(())
     }
 
-    /// download_partial_with_scheduler downloads a partial task with scheduler.
-    #[allow(clippy::too_many_arguments)]
+    /// stat_task returns the task metadata.
     #[instrument(skip_all)]
-    async fn download_partial_with_scheduler(
+    pub async fn stat(
         &self,
-        task: &metadata::Task,
+        task_id: &str,
         host_id: &str,
-        peer_id: &str,
-        interested_pieces: Vec<metadata::Piece>,
-        content_length: u64,
-        request: Download,
-        download_progress_tx: Sender<Result<DownloadTaskResponse, Status>>,
-    ) -> ClientResult<Vec<metadata::Piece>> {
-        // Get the id of the task.
-        let task_id = task.id.as_str();
+        local_only: bool,
+    ) -> ClientResult<CommonTask> {
+        if local_only {
+            let Some(task_metadata) = self.storage.get_task(task_id).inspect_err(|err| {
+                error!("get task {} from local storage error: {:?}", task_id, err);
+            })?
+            else {
+                return Err(Error::TaskNotFound(task_id.to_owned()));
+            };
 
-        // Initialize the schedule count.
-        let mut schedule_count = 0;
+            let piece_metadatas = self.piece.get_all(task_id).inspect_err(|err| {
+                error!(
+                    "get pieces for task {} from local storage error: {:?}",
+                    task_id, err
+                );
+            })?;
 
-        // Initialize the finished pieces.
-        let mut finished_pieces: Vec<metadata::Piece> = Vec::new();
+            let pieces = piece_metadatas
+                .into_iter()
+                .filter(|piece| piece.is_finished())
+                .map(|piece| {
+                    // The traffic_type indicates whether the first download was from the source or hit the remote peer cache.
+                    // If the parent_id exists, the piece was downloaded from a remote peer. Otherwise, it was
+                    // downloaded from the source.
+                    let traffic_type = match piece.parent_id {
+                        None => TrafficType::BackToSource,
+                        Some(_) => TrafficType::RemotePeer,
+                    };
 
-        // Initialize stream channel.
-        let (in_stream_tx, in_stream_rx) = mpsc::channel(10 * 1024);
+                    Piece {
+                        number: piece.number,
+                        parent_id: piece.parent_id.clone(),
+                        offset: piece.offset,
+                        length: piece.length,
+                        digest: piece.digest.clone(),
+                        content: None,
+                        traffic_type: Some(traffic_type as i32),
+                        cost: piece.prost_cost(),
+                        created_at: Some(prost_wkt_types::Timestamp::from(piece.created_at)),
+                    }
+                })
+                .collect::<Vec<Piece>>();
 
-        // Send the register peer request.
-        in_stream_tx
-            .send_timeout(
-                AnnouncePeerRequest {
-                    host_id: host_id.to_string(),
-                    task_id: task_id.to_string(),
-                    peer_id: peer_id.to_string(),
-                    request: Some(announce_peer_request::Request::RegisterPeerRequest(
-                        RegisterPeerRequest {
-                            download: Some(request.clone()),
-                        },
-                    )),
-                },
-                REQUEST_TIMEOUT,
+            return Ok(CommonTask {
+                id: task_metadata.id,
+                r#type: TaskType::Standard as i32,
+                url: String::new(),
+                digest: None,
+                tag: None,
+                application: None,
+                filtered_query_params: Vec::new(),
+                request_header: HashMap::new(),
+                content_length: task_metadata.content_length.unwrap_or(0),
+                piece_count: pieces.len() as u32,
+                size_scope: SizeScope::Normal as i32,
+                pieces,
+                state: String::new(),
+                peer_count: 0,
+                has_available_peer: false,
+                created_at: Some(prost_wkt_types::Timestamp::from(task_metadata.created_at)),
+                updated_at: Some(prost_wkt_types::Timestamp::from(task_metadata.updated_at)),
+            });
+        }
+
+        let task = self
+            .scheduler_client
+            .stat_task(StatTaskRequest {
+                host_id: host_id.to_string(),
+                task_id: task_id.to_string(),
+            })
+            .await
+            .inspect_err(|err| {
+                error!("stat task failed: {}", err);
+            })?;
+
+        Ok(task)
+    }
+
+    /// Delete a task and reclaim local storage.
+    #[instrument(skip_all)]
+    pub async fn delete(&self, task_id: &str, host_id: &str) -> ClientResult<()> {
+        let task = self.storage.get_task(task_id).inspect_err(|err| {
+            error!("get task {} from local storage error: {:?}", task_id, err);
+        })?;
+
+        match task {
+            Some(task) => {
+                self.storage.delete_task(task.id.as_str()).await;
+
+                self.scheduler_client
+                    .delete_task(DeleteTaskRequest {
+                        host_id: host_id.to_string(),
+                        task_id: task_id.to_string(),
+                    })
+                    .await
+                    .inspect_err(|err| {
+                        error!("delete task {} failed from scheduler: {:?}", task_id, err);
+                    })?;
+
+                info!("delete task {} from local storage", task.id);
+                Ok(())
+            }
+            None => {
+                error!("delete_task task {} not found", task_id);
+                Err(Error::TaskNotFound(task_id.to_owned()))
+            }
+        }
+    }
+}
+
+/// Announcement is the request of the peer sent to the scheduler by the announce stream, it is
+/// converted to the announce request of the storage kind.
+pub enum Announcement<P> {
+    /// DownloadPeerStarted is sent when the peer starts to download from the parents.
+    DownloadPeerStarted,
+
+    /// DownloadPeerFinished is sent when the peer finishes downloading from the parents.
+    DownloadPeerFinished {
+        content_length: u64,
+        piece_count: u32,
+    },
+
+    /// DownloadPeerFailed is sent when the peer fails to download.
+    DownloadPeerFailed { description: String },
+
+    /// ReschedulePeer is sent when the peer needs the other parents to download the remaining
+    /// pieces.
+    ReschedulePeer {
+        candidate_parents: Vec<P>,
+        description: String,
+    },
+
+    /// DownloadPeerBackToSourceStarted is sent when the peer starts to download from the source.
+    DownloadPeerBackToSourceStarted,
+
+    /// DownloadPeerBackToSourceFinished is sent when the peer finishes downloading from the
+    /// source.
+    DownloadPeerBackToSourceFinished {
+        content_length: u64,
+        piece_count: u32,
+    },
+
+    /// DownloadPeerBackToSourceFailed is sent when the peer fails to download from the source.
+    DownloadPeerBackToSourceFailed { description: String },
+
+    /// DownloadPieceFinished is sent when the piece is downloaded from the parent.
+    DownloadPieceFinished(Piece),
+
+    /// DownloadPieceFailed is sent when the piece fails to download from the parent.
+    DownloadPieceFailed {
+        piece_number: u32,
+        parent_id: String,
+        temporary: bool,
+    },
+
+    /// DownloadPieceBackToSourceFinished is sent when the piece is downloaded from the source.
+    DownloadPieceBackToSourceFinished(Piece),
+
+    /// DownloadPieceBackToSourceFailed is sent when the piece fails to download from the source.
+    DownloadPieceBackToSourceFailed(download_piece_back_to_source_failed_request::Response),
+}
+
+/// ScheduleResponse is the response of the scheduler received by the announce stream.
+pub enum ScheduleResponse<P> {
+    /// EmptyTask is received when the content of the task is empty.
+    EmptyTask,
+
+    /// NormalTask is received with the candidate parents to download the pieces from.
+    NormalTask(Vec<P>),
+
+    /// NeedBackToSource is received when the pieces need to be downloaded from the source.
+    NeedBackToSource(NeedBackToSourceResponse),
+}
+
+/// PieceRequest is the part of the download request used to download the pieces.
+#[derive(Clone, Debug)]
+pub struct PieceRequest {
+    /// url is the url of the source.
+    pub url: String,
+
+    /// request_header is the request header of the source.
+    pub request_header: HashMap<String, String>,
+
+    /// is_prefetch indicates whether the download is the prefetch.
+    pub is_prefetch: bool,
+
+    /// need_piece_content indicates whether the piece content is sent by the download progress.
+    pub need_piece_content: bool,
+
+    /// object_storage is the object storage of the source.
+    pub object_storage: Option<ObjectStorage>,
+
+    /// hdfs is the hdfs of the source.
+    pub hdfs: Option<Hdfs>,
+}
+
+/// PieceRequest implements the conversion from the download request of the task.
+impl From<&Download> for PieceRequest {
+    fn from(request: &Download) -> Self {
+        Self {
+            url: request.url.clone(),
+            request_header: request.request_header.clone(),
+            is_prefetch: request.is_prefetch,
+            need_piece_content: request.need_piece_content,
+            object_storage: request.object_storage.clone(),
+            hdfs: request.hdfs.clone(),
+        }
+    }
+}
+
+/// StorageKind is the kind of the storage which the pieces are downloaded into. The pieces of
+/// the task are stored on the disk and the pieces of the cache task are stored in the memory
+/// cache, both are downloaded by the PartialDownloader with the announce protocol of the kind.
+#[tonic::async_trait]
+pub trait StorageKind: Send + Sync + 'static {
+    /// Parent is the candidate parent scheduled by the scheduler.
+    type Parent: Clone + Send + Sync + 'static;
+
+    /// AnnounceRequest is the request sent to the scheduler by the announce stream.
+    type AnnounceRequest: Send + 'static;
+
+    /// AnnounceResponse is the response received from the scheduler by the announce stream.
+    type AnnounceResponse: Send + 'static;
+
+    /// DownloadResponse is the response sent by the download progress.
+    type DownloadResponse: Send + 'static;
+
+    /// VERIFY_PIECES indicates whether the pieces from the parents are verified by the digests
+    /// of the seed peers if the integrity is enabled.
+    const VERIFY_PIECES: bool;
+
+    /// collected_parent converts the candidate parent to the parent to collect the pieces from.
+    fn collected_parent(parent: Self::Parent) -> piece_collector::CollectedParent;
+
+    /// collect_pieces runs the piece collector of the parents, it returns the collected pieces
+    /// and the collector which can block the parents if the kind supports it.
+    async fn collect_pieces(
+        config: Arc<Config>,
+        host_id: &str,
+        task_id: &str,
+        interested_pieces: Vec<metadata::Piece>,
+        parents: Vec<piece_collector::CollectedParent>,
+        parent_selector: Arc<parent_selector::ParentSelector>,
+    ) -> (
+        mpsc::Receiver<piece_collector::CollectedPiece>,
+        Option<piece_collector::PieceCollector>,
+    );
+
+    /// announce opens the announce stream to the scheduler.
+    async fn announce(
+        scheduler_client: &SchedulerClient,
+        task_id: &str,
+        peer_id: &str,
+        request: Request<ReceiverStream<Self::AnnounceRequest>>,
+    ) -> ClientResult<Streaming<Self::AnnounceResponse>>;
+
+    /// schedule_response converts the response received by the announce stream.
+    fn schedule_response(
+        response: Self::AnnounceResponse,
+    ) -> ClientResult<ScheduleResponse<Self::Parent>>;
+
+    /// announce_request converts the announcement to the request of the announce stream.
+    fn announce_request(
+        host_id: &str,
+        task_id: &str,
+        peer_id: &str,
+        announcement: Announcement<Self::Parent>,
+    ) -> Self::AnnounceRequest;
+
+    /// download_piece_finished_response returns the download progress of the finished piece.
+    fn download_piece_finished_response(
+        host_id: &str,
+        task_id: &str,
+        peer_id: &str,
+        piece: Piece,
+    ) -> Self::DownloadResponse;
+
+    /// piece_id returns the id of the piece in the storage.
+    fn piece_id(piece_manager: &piece::Piece, task_id: &str, number: u32) -> String;
+
+    /// get_finished_piece returns the piece if it is finished in the storage.
+    async fn get_finished_piece(
+        piece_manager: &piece::Piece,
+        task_id: &str,
+        number: u32,
+    ) -> ClientResult<Option<metadata::Piece>>;
+
+    /// download_from_local fakes the download of the piece from the storage.
+    fn download_from_local(piece_manager: &piece::Piece, task_id: &str, length: u64);
+
+    /// download_from_parent downloads the piece from the parent into the storage.
+    #[allow(clippy::too_many_arguments)]
+    async fn download_from_parent(
+        piece_manager: &piece::Piece,
+        piece_id: &str,
+        host_id: &str,
+        task_id: &str,
+        number: u32,
+        length: u64,
+        parent: piece_collector::CollectedParent,
+        is_prefetch: bool,
+        verifier: Option<Arc<piece_verifier::PieceVerifier>>,
+    ) -> ClientResult<metadata::Piece>;
+
+    /// download_from_source downloads the piece from the source into the storage.
+    #[allow(clippy::too_many_arguments)]
+    async fn download_from_source(
+        piece_manager: &piece::Piece,
+        piece_id: &str,
+        task_id: &str,
+        number: u32,
+        url: &str,
+        offset: u64,
+        length: u64,
+        request_header: HeaderMap,
+        is_prefetch: bool,
+        object_storage: Option<ObjectStorage>,
+        hdfs: Option<Hdfs>,
+    ) -> ClientResult<metadata::Piece>;
+
+    /// read_piece reads the content of the finished piece from the storage.
+    async fn read_piece(
+        piece_manager: &piece::Piece,
+        piece_id: &str,
+        task_id: &str,
+        number: u32,
+        length: u64,
+    ) -> ClientResult<Vec<u8>>;
+}
+
+/// DiskStorage is the storage kind of the task, the pieces are stored on the disk.
+pub struct DiskStorage;
+
+/// DiskStorage implements the storage kind with the announce peer protocol.
+#[tonic::async_trait]
+impl StorageKind for DiskStorage {
+    type Parent = Peer;
+    type AnnounceRequest = AnnouncePeerRequest;
+    type AnnounceResponse = AnnouncePeerResponse;
+    type DownloadResponse = DownloadTaskResponse;
+
+    const VERIFY_PIECES: bool = true;
+
+    fn collected_parent(parent: Peer) -> piece_collector::CollectedParent {
+        piece_collector::CollectedParent {
+            id: parent.id,
+            host: parent.host,
+        }
+    }
+
+    async fn collect_pieces(
+        config: Arc<Config>,
+        host_id: &str,
+        task_id: &str,
+        interested_pieces: Vec<metadata::Piece>,
+        parents: Vec<piece_collector::CollectedParent>,
+        parent_selector: Arc<parent_selector::ParentSelector>,
+    ) -> (
+        mpsc::Receiver<piece_collector::CollectedPiece>,
+        Option<piece_collector::PieceCollector>,
+    ) {
+        let piece_collector = piece_collector::PieceCollector::new(
+            config,
+            host_id,
+            task_id,
+            interested_pieces,
+            parents,
+            parent_selector,
+        )
+        .await;
+        let piece_collector_rx = piece_collector.run().await;
+        (piece_collector_rx, Some(piece_collector))
+    }
+
+    async fn announce(
+        scheduler_client: &SchedulerClient,
+        task_id: &str,
+        peer_id: &str,
+        request: Request<ReceiverStream<AnnouncePeerRequest>>,
+    ) -> ClientResult<Streaming<AnnouncePeerResponse>> {
+        let response = scheduler_client
+            .announce_peer(task_id, peer_id, request)
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    fn schedule_response(response: AnnouncePeerResponse) -> ClientResult<ScheduleResponse<Peer>> {
+        match response.response.ok_or(Error::UnexpectedResponse)? {
+            announce_peer_response::Response::EmptyTaskResponse(_) => {
+                Ok(ScheduleResponse::EmptyTask)
+            }
+            announce_peer_response::Response::NormalTaskResponse(response) => {
+                Ok(ScheduleResponse::NormalTask(response.candidate_parents))
+            }
+            announce_peer_response::Response::NeedBackToSourceResponse(response) => {
+                Ok(ScheduleResponse::NeedBackToSource(response))
+            }
+        }
+    }
+
+    fn announce_request(
+        host_id: &str,
+        task_id: &str,
+        peer_id: &str,
+        announcement: Announcement<Peer>,
+    ) -> AnnouncePeerRequest {
+        let request = match announcement {
+            Announcement::DownloadPeerStarted => {
+                announce_peer_request::Request::DownloadPeerStartedRequest(
+                    DownloadPeerStartedRequest {},
+                )
+            }
+            Announcement::DownloadPeerFinished {
+                content_length,
+                piece_count,
+            } => announce_peer_request::Request::DownloadPeerFinishedRequest(
+                DownloadPeerFinishedRequest {
+                    content_length,
+                    piece_count,
+                },
+            ),
+            Announcement::DownloadPeerFailed { description } => {
+                announce_peer_request::Request::DownloadPeerFailedRequest(
+                    DownloadPeerFailedRequest {
+                        description: Some(description),
+                    },
+                )
+            }
+            Announcement::ReschedulePeer {
+                candidate_parents,
+                description,
+            } => announce_peer_request::Request::ReschedulePeerRequest(ReschedulePeerRequest {
+                candidate_parents,
+                description: Some(description),
+            }),
+            Announcement::DownloadPeerBackToSourceStarted => {
+                announce_peer_request::Request::DownloadPeerBackToSourceStartedRequest(
+                    DownloadPeerBackToSourceStartedRequest { description: None },
+                )
+            }
+            Announcement::DownloadPeerBackToSourceFinished {
+                content_length,
+                piece_count,
+            } => announce_peer_request::Request::DownloadPeerBackToSourceFinishedRequest(
+                DownloadPeerBackToSourceFinishedRequest {
+                    content_length,
+                    piece_count,
+                },
+            ),
+            Announcement::DownloadPeerBackToSourceFailed { description } => {
+                announce_peer_request::Request::DownloadPeerBackToSourceFailedRequest(
+                    DownloadPeerBackToSourceFailedRequest {
+                        description: Some(description),
+                    },
+                )
+            }
+            Announcement::DownloadPieceFinished(piece) => {
+                announce_peer_request::Request::DownloadPieceFinishedRequest(
+                    DownloadPieceFinishedRequest { piece: Some(piece) },
+                )
+            }
+            Announcement::DownloadPieceFailed {
+                piece_number,
+                parent_id,
+                temporary,
+            } => announce_peer_request::Request::DownloadPieceFailedRequest(
+                DownloadPieceFailedRequest {
+                    piece_number: Some(piece_number),
+                    parent_id,
+                    temporary,
+                },
+            ),
+            Announcement::DownloadPieceBackToSourceFinished(piece) => {
+                announce_peer_request::Request::DownloadPieceBackToSourceFinishedRequest(
+                    DownloadPieceBackToSourceFinishedRequest { piece: Some(piece) },
+                )
+            }
+            Announcement::DownloadPieceBackToSourceFailed(response) => {
+                announce_peer_request::Request::DownloadPieceBackToSourceFailedRequest(
+                    DownloadPieceBackToSourceFailedRequest {
+                        piece_number: None,
+                        response: Some(response),
+                    },
+                )
+            }
+        };
+
+        AnnouncePeerRequest {
+            host_id: host_id.to_string(),
+            task_id: task_id.to_string(),
+            peer_id: peer_id.to_string(),
+            request: Some(request),
+        }
+    }
+
+    fn download_piece_finished_response(
+        host_id: &str,
+        task_id: &str,
+        peer_id: &str,
+        piece: Piece,
+    ) -> DownloadTaskResponse {
+        DownloadTaskResponse {
+            host_id: host_id.to_string(),
+            task_id: task_id.to_string(),
+            peer_id: peer_id.to_string(),
+            response: Some(
+                download_task_response::Response::DownloadPieceFinishedResponse(
+                    dfdaemon::v2::DownloadPieceFinishedResponse { piece: Some(piece) },
+                ),
+            ),
+        }
+    }
+
+    fn piece_id(piece_manager: &piece::Piece, task_id: &str, number: u32) -> String {
+        piece_manager.id(task_id, number)
+    }
+
+    async fn get_finished_piece(
+        piece_manager: &piece::Piece,
+        task_id: &str,
+        number: u32,
+    ) -> ClientResult<Option<metadata::Piece>> {
+        let piece = piece_manager.get(piece_manager.id(task_id, number).as_str())?;
+        Ok(piece.filter(|piece| piece.is_finished()))
+    }
+
+    fn download_from_local(piece_manager: &piece::Piece, task_id: &str, length: u64) {
+        piece_manager.download_from_local(task_id, length);
+    }
+
+    async fn download_from_parent(
+        piece_manager: &piece::Piece,
+        piece_id: &str,
+        host_id: &str,
+        task_id: &str,
+        number: u32,
+        length: u64,
+        parent: piece_collector::CollectedParent,
+        is_prefetch: bool,
+        verifier: Option<Arc<piece_verifier::PieceVerifier>>,
+    ) -> ClientResult<metadata::Piece> {
+        piece_manager
+            .download_from_parent(
+                piece_id,
+                host_id,
+                task_id,
+                number,
+                length,
+                parent,
+                is_prefetch,
+                verifier,
+            )
+            .await
+    }
+
+    async fn download_from_source(
+        piece_manager: &piece::Piece,
+        piece_id: &str,
+        task_id: &str,
+        number: u32,
+        url: &str,
+        offset: u64,
+        length: u64,
+        request_header: HeaderMap,
+        is_prefetch: bool,
+        object_storage: Option<ObjectStorage>,
+        hdfs: Option<Hdfs>,
+    ) -> ClientResult<metadata::Piece> {
+        piece_manager
+            .download_from_source(
+                piece_id,
+                task_id,
+                number,
+                url,
+                offset,
+                length,
+                request_header,
+                is_prefetch,
+                object_storage,
+                hdfs,
             )
             .await
+    }
+
+    async fn read_piece(
+        piece_manager: &piece::Piece,
+        piece_id: &str,
+        task_id: &str,
+        _number: u32,
+        length: u64,
+    ) -> ClientResult<Vec<u8>> {
+        let mut reader = piece_manager
+            .download_from_local_into_async_read(piece_id, task_id, length, None, true, false)
+            .await?;
+
+        let mut content = vec![0; length as usize];
+        reader.read_exact(&mut content).await?;
+        Ok(content)
+    }
+}
+
+/// PartialDownloader downloads the partial pieces of the storage kind from the local storage,
+/// the parents scheduled by the scheduler and the source.
+pub struct PartialDownloader<K: StorageKind> {
+    /// config is the configuration of the dfdaemon.
+    config: Arc<Config>,
+
+    /// scheduler_client is the grpc client of the scheduler.
+    scheduler_client: Arc<SchedulerClient>,
+
+    /// piece is the piece manager.
+    piece: Arc<piece::Piece>,
+
+    /// parent_selector is the parent selector.
+    parent_selector: Arc<parent_selector::ParentSelector>,
+
+    /// kind is the storage kind of the pieces.
+    kind: PhantomData<K>,
+}
+
+/// PartialDownloader implements the partial downloads of the storage kind.
+impl<K: StorageKind> PartialDownloader<K> {
+    /// new returns a new PartialDownloader.
+    pub fn new(
+        config: Arc<Config>,
+        scheduler_client: Arc<SchedulerClient>,
+        piece: Arc<piece::Piece>,
+        parent_selector: Arc<parent_selector::ParentSelector>,
+    ) -> Self {
+        Self {
+            config,
+            scheduler_client,
+            piece,
+            parent_selector,
+            kind: PhantomData,
+        }
+    }
+
+    /// download_partial_with_scheduler downloads the partial pieces with scheduler, the
+    /// register_request registers the peer by the announce stream.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip_all)]
+    pub async fn download_partial_with_scheduler(
+        &self,
+        task_id: &str,
+        host_id: &str,
+        peer_id: &str,
+        interested_pieces: Vec<metadata::Piece>,
+        content_length: u64,
+        register_request: K::AnnounceRequest,
+        request: PieceRequest,
+        download_progress_tx: Sender<Result<K::DownloadResponse, Status>>,
+    ) -> ClientResult<Vec<metadata::Piece>> {
+        // Initialize the schedule count.
+        let mut schedule_count = 0;
+
+        // Initialize the finished pieces.
+        let mut finished_pieces: Vec<metadata::Piece> = Vec::new();
+
+        // Initialize stream channel.
+        let (in_stream_tx, in_stream_rx) = mpsc::channel(10 * 1024);
+
+        // Send the register peer request.
+        in_stream_tx
+            .send_timeout(register_request, REQUEST_TIMEOUT)
+            .await
             .inspect_err(|err| {
                 error!("send RegisterPeerRequest failed: {:?}", err);
             })?;
@@ -579,18 +1419,14 @@ impl Task {
         // Initialize the stream.
         let in_stream = ReceiverStream::new(in_stream_rx);
         let request_stream = Request::new(in_stream);
-        let response = self
-            .scheduler_client
-            .announce_peer(task_id, peer_id, request_stream)
+        let response = K::announce(&self.scheduler_client, task_id, peer_id, request_stream)
             .await
             .inspect_err(|err| {
                 error!("announce peer failed: {:?}", err);
             })?;
         info!("announced peer has been connected");
 
-        let out_stream = response
-            .into_inner()
-            .timeout(self.config.scheduler.schedule_timeout);
+        let out_stream = response.timeout(self.config.scheduler.schedule_timeout);
         tokio::pin!(out_stream);
 
         while let Some(message) = out_stream.try_next().await.inspect_err(|err| {
@@ -599,28 +1435,17 @@ impl Task {
             // Check if the schedule count is exceeded.
             schedule_count += 1;
             if schedule_count > self.config.scheduler.max_schedule_count {
-                in_stream_tx
-                    .send_timeout(
-                        AnnouncePeerRequest {
-                            host_id: host_id.to_string(),
-                            task_id: task_id.to_string(),
-                            peer_id: peer_id.to_string(),
-                            request: Some(
-                                announce_peer_request::Request::DownloadPeerFailedRequest(
-                                    DownloadPeerFailedRequest {
-                                        description: Some(
-                                            "max schedule count exceeded".to_string(),
-                                        ),
-                                    },
-                                ),
-                            ),
-                        },
-                        REQUEST_TIMEOUT,
-                    )
-                    .await
-                    .unwrap_or_else(|err| {
-                        error!("send DownloadPeerFailedRequest failed: {:?}", err)
-                    });
+                announce::<K>(
+                    &in_stream_tx,
+                    host_id,
+                    task_id,
+                    peer_id,
+                    Announcement::DownloadPeerFailed {
+                        description: "max schedule count exceeded".to_string(),
+                    },
+                )
+                .await
+                .unwrap_or_else(|err| error!("send DownloadPeerFailedRequest failed: {:?}", err));
                 info!("sent DownloadPeerFailedRequest");
 
                 // Wait for the latest message to be sent.
@@ -628,88 +1453,87 @@ impl Task {
                 return Ok(finished_pieces);
             }
 
-            let response = message?.response.ok_or(Error::UnexpectedResponse)?;
-            match response {
-                announce_peer_response::Response::EmptyTaskResponse(response) => {
+            match K::schedule_response(message?)? {
+                ScheduleResponse::EmptyTask => {
                     // If the task is empty, return an empty vector.
-                    info!("empty task response: {:?}", response);
+                    info!("empty task response");
 
                     // Send the download peer started request.
-                    in_stream_tx
-                        .send_timeout(
-                            AnnouncePeerRequest {
-                                host_id: host_id.to_string(),
-                                task_id: task_id.to_string(),
-                                peer_id: peer_id.to_string(),
-                                request: Some(
-                                    announce_peer_request::Request::DownloadPeerStartedRequest(
-                                        DownloadPeerStartedRequest {},
-                                    ),
-                                ),
-                            },
-                            REQUEST_TIMEOUT,
-                        )
-                        .await
-                        .inspect_err(|err| {
-                            error!("send DownloadPeerStartedRequest failed: {:?}", err);
-                        })?;
+                    announce::<K>(
+                        &in_stream_tx,
+                        host_id,
+                        task_id,
+                        peer_id,
+                        Announcement::DownloadPeerStarted,
+                    )
+                    .await
+                    .inspect_err(|err| {
+                        error!("send DownloadPeerStartedRequest failed: {:?}", err);
+                    })?;
                     info!("sent DownloadPeerStartedRequest");
 
                     // Send the download peer finished request.
-                    in_stream_tx
-                        .send_timeout(
-                            AnnouncePeerRequest {
-                                host_id: host_id.to_string(),
-                                task_id: task_id.to_string(),
-                                peer_id: peer_id.to_string(),
-                                request: Some(
-                                    announce_peer_request::Request::DownloadPeerFinishedRequest(
-                                        DownloadPeerFinishedRequest {
-                                            content_length: 0,
-                                            piece_count: 0,
-                                        },
-                                    ),
-                                ),
-                            },
-                            REQUEST_TIMEOUT,
-                        )
-                        .await
-                        .inspect_err(|err| {
-                            error!("send DownloadPeerFinishedRequest failed: {:?}", err);
-                        })?;
+                    announce::<K>(
+                        &in_stream_tx,
+                        host_id,
+                        task_id,
+                        peer_id,
+                        Announcement::DownloadPeerFinished {
+                            content_length: 0,
+                            piece_count: 0,
+                        },
+                    )
+                    .await
+                    .inspect_err(|err| {
+                        error!("send DownloadPeerFinishedRequest failed: {:?}", err);
+                    })?;
                     info!("sent DownloadPeerFinishedRequest");
 
                     // Wait for the latest message to be sent.
                     in_stream_tx.closed().await;
                     return Ok(Vec::new());
                 }
-                announce_peer_response::Response::NormalTaskResponse(response) => {
+                ScheduleResponse::NormalTask(candidate_parents) => {
                     // If the task is normal, download the pieces from the parent.
+                    let parents = candidate_parents
+                        .iter()
+                        .cloned()
+                        .map(K::collected_parent)
+                        .collect::<Vec<_>>();
                     info!(
                         "normal task response: {:?}",
-                        response
-                            .candidate_parents
+                        parents
                             .iter()
                             .map(|p| p.id.clone())
                             .collect::<Vec<String>>()
                     );
 
+                    // The pieces from the parents can not be verified without the seed peers if
+                    // the integrity is enabled, so download the pieces from the source instead
+                    // of failing every piece from the parents.
+                    if K::VERIFY_PIECES
+                        && self.config.download.integrity.enable
+                        && !parents
+                            .iter()
+                            .any(|parent| piece_verifier::is_seed_parent(parent.host.as_ref()))
+                    {
+                        error!(
+                            "integrity is enabled, but no seed peer is in the candidate parents"
+                        );
+                        fail_peer_for_integrity::<K>(host_id, task_id, peer_id, &in_stream_tx)
+                            .await;
+                        return Ok(finished_pieces);
+                    }
+
                     // Send the download peer started request.
-                    match in_stream_tx
-                        .send_timeout(
-                            AnnouncePeerRequest {
-                                host_id: host_id.to_string(),
-                                task_id: task_id.to_string(),
-                                peer_id: peer_id.to_string(),
-                                request: Some(
-                                    announce_peer_request::Request::DownloadPeerStartedRequest(
-                                        DownloadPeerStartedRequest {},
-                                    ),
-                                ),
-                            },
-                            REQUEST_TIMEOUT,
-                        )
-                        .await
+                    match announce::<K>(
+                        &in_stream_tx,
+                        host_id,
+                        task_id,
+                        peer_id,
+                        Announcement::DownloadPeerStarted,
+                    )
+                    .await
                     {
                         Ok(_) => info!("sent DownloadPeerStartedRequest"),
                         Err(err) => {
@@ -727,10 +1551,10 @@ impl Task {
                     // Download the pieces from the parent.
                     let partial_finished_pieces = match self
                         .download_partial_with_scheduler_from_parent(
-                            task,
+                            task_id,
                             host_id,
                             peer_id,
-                            response.candidate_parents.clone(),
+                            parents,
                             remaining_interested_pieces.clone(),
                             request.is_prefetch,
                             request.need_piece_content,
@@ -748,6 +1572,14 @@ impl Task {
 
                             partial_finished_pieces
                         }
+                        Err(Error::Unsupported(err)) => {
+                            // The seed peers can not verify the pieces, so download the pieces
+                            // from the source instead of rescheduling.
+                            error!("verify pieces from parent error: {}", err);
+                            fail_peer_for_integrity::<K>(host_id, task_id, peer_id, &in_stream_tx)
+                                .await;
+                            return Ok(finished_pieces);
+                        }
                         Err(err) => {
                             error!("download from parent error: {:?}", err);
                             Vec::new()
@@ -760,27 +1592,20 @@ impl Task {
                         partial_finished_pieces.clone(),
                     );
 
-                    // Check if all pieces are downloaded.
-                    if finished_pieces.len() == interested_pieces.len() {
-                        // Send the download peer finished request.
-                        match in_stream_tx
-                            .send_timeout(
-                                AnnouncePeerRequest {
-                                    host_id: host_id.to_string(),
-                                    task_id: task_id.to_string(),
-                                    peer_id: peer_id.to_string(),
-                                    request: Some(
-                                        announce_peer_request::Request::DownloadPeerFinishedRequest(
-                                            DownloadPeerFinishedRequest {
-                                                content_length,
-                                                piece_count: interested_pieces.len() as u32,
-                                            },
-                                        ),
-                                    ),
-                                },
-                                REQUEST_TIMEOUT,
-                            )
-                            .await
+                    // Check if all pieces are downloaded.
+                    if finished_pieces.len() == interested_pieces.len() {
+                        // Send the download peer finished request.
+                        match announce::<K>(
+                            &in_stream_tx,
+                            host_id,
+                            task_id,
+                            peer_id,
+                            Announcement::DownloadPeerFinished {
+                                content_length,
+                                piece_count: interested_pieces.len() as u32,
+                            },
+                        )
+                        .await
                         {
                             Ok(_) => info!("sent DownloadPeerFinishedRequest"),
                             Err(err) => {
@@ -794,27 +1619,17 @@ impl Task {
                     }
 
                     // If not all pieces are downloaded, send the reschedule request.
-                    match in_stream_tx
-                        .send_timeout(
-                            AnnouncePeerRequest {
-                                host_id: host_id.to_string(),
-                                task_id: task_id.to_string(),
-                                peer_id: peer_id.to_string(),
-                                request: Some(
-                                    announce_peer_request::Request::ReschedulePeerRequest(
-                                        ReschedulePeerRequest {
-                                            candidate_parents: response.candidate_parents,
-                                            description: Some(
-                                                "not all pieces are downloaded from parent"
-                                                    .to_string(),
-                                            ),
-                                        },
-                                    ),
-                                ),
-                            },
-                            REQUEST_TIMEOUT,
-                        )
-                        .await
+                    match announce::<K>(
+                        &in_stream_tx,
+                        host_id,
+                        task_id,
+                        peer_id,
+                        Announcement::ReschedulePeer {
+                            candidate_parents,
+                            description: "not all pieces are downloaded from parent".to_string(),
+                        },
+                    )
+                    .await
                     {
                         Ok(_) => info!("sent ReschedulePeerRequest"),
                         Err(err) => {
@@ -823,28 +1638,26 @@ impl Task {
                         }
                     };
                 }
-                announce_peer_response::Response::NeedBackToSourceResponse(response) => {
+                ScheduleResponse::NeedBackToSource(response) => {
                     // If the task need back to source, download the pieces from the source.
                     info!("need back to source response: {:?}", response);
 
                     // Send the download peer back-to-source request.
-                    match in_stream_tx
-                        .send_timeout(AnnouncePeerRequest {
-                            host_id: host_id.to_string(),
-                            task_id: task_id.to_string(),
-                            peer_id: peer_id.to_string(),
-                            request: Some(
-                                announce_peer_request::Request::DownloadPeerBackToSourceStartedRequest(
-                                    DownloadPeerBackToSourceStartedRequest {
-                                        description: None,
-                                    },
-                                ),
-                            ),
-                        }, REQUEST_TIMEOUT)
-                    .await {
+                    match announce::<K>(
+                        &in_stream_tx,
+                        host_id,
+                        task_id,
+                        peer_id,
+                        Announcement::DownloadPeerBackToSourceStarted,
+                    )
+                    .await
+                    {
                         Ok(_) => info!("sent DownloadPeerBackToSourceStartedRequest"),
                         Err(err) => {
-                            error!("send DownloadPeerBackToSourceStartedRequest failed: {:?}", err);
+                            error!(
+                                "send DownloadPeerBackToSourceStartedRequest failed: {:?}",
+                                err
+                            );
                             return Ok(finished_pieces);
                         }
                     };
@@ -858,7 +1671,7 @@ impl Task {
                     // Download the pieces from the source.
                     let partial_finished_pieces = match self
                         .download_partial_with_scheduler_from_source(
-                            task,
+                            task_id,
                             host_id,
                             peer_id,
                             remaining_interested_pieces.clone(),
@@ -870,23 +1683,22 @@ impl Task {
                     {
                         Ok(finished_pieces) => finished_pieces,
                         Err(err) => {
-                            in_stream_tx
-                                .send_timeout(AnnouncePeerRequest {
-                                    host_id: host_id.to_string(),
-                                    task_id: task_id.to_string(),
-                                    peer_id: peer_id.to_string(),
-                                    request: Some(
-                                        announce_peer_request::Request::DownloadPeerBackToSourceFailedRequest(
-                                            DownloadPeerBackToSourceFailedRequest {
-                                                description: Some(err.to_string()),
-                                            },
-                                        ),
-                                    ),
-                                }, REQUEST_TIMEOUT)
-                                .await
-                                .unwrap_or_else(|err| {
-                                    error!("send DownloadPeerBackToSourceFailedRequest failed: {:?}", err)
-                                });
+                            announce::<K>(
+                                &in_stream_tx,
+                                host_id,
+                                task_id,
+                                peer_id,
+                                Announcement::DownloadPeerBackToSourceFailed {
+                                    description: err.to_string(),
+                                },
+                            )
+                            .await
+                            .unwrap_or_else(|err| {
+                                error!(
+                                    "send DownloadPeerBackToSourceFailedRequest failed: {:?}",
+                                    err
+                                )
+                            });
                             info!("sent DownloadPeerBackToSourceFailedRequest");
 
                             // Wait for the latest message to be sent.
@@ -903,28 +1715,24 @@ impl Task {
 
                     if partial_finished_pieces.len() == remaining_interested_pieces.len() {
                         // Send the download peer finished request.
-                        match in_stream_tx
-                            .send_timeout(
-                                AnnouncePeerRequest {
-                                    host_id: host_id.to_string(),
-                                    task_id: task_id.to_string(),
-                                    peer_id: peer_id.to_string(),
-                                    request: Some(
-                                        announce_peer_request::Request::DownloadPeerBackToSourceFinishedRequest(
-                                            DownloadPeerBackToSourceFinishedRequest {
-                                                content_length,
-                                                piece_count: interested_pieces.len() as u32,
-                                            },
-                                        ),
-                                    ),
-                                },
-                                REQUEST_TIMEOUT,
-                            )
-                            .await
+                        match announce::<K>(
+                            &in_stream_tx,
+                            host_id,
+                            task_id,
+                            peer_id,
+                            Announcement::DownloadPeerBackToSourceFinished {
+                                content_length,
+                                piece_count: interested_pieces.len() as u32,
+                            },
+                        )
+                        .await
                         {
                             Ok(_) => info!("sent DownloadPeerBackToSourceFinishedRequest"),
                             Err(err) => {
-                                error!("send DownloadPeerBackToSourceFinishedRequest failed: {:?}", err);
+                                error!(
+                                    "send DownloadPeerBackToSourceFinishedRequest failed: {:?}",
+                                    err
+                                );
                             }
                         }
 
@@ -933,23 +1741,23 @@ impl Task {
                         return Ok(finished_pieces);
                     }
 
-                    match in_stream_tx
-                        .send_timeout(AnnouncePeerRequest {
-                            host_id: host_id.to_string(),
-                            task_id: task_id.to_string(),
-                            peer_id: peer_id.to_string(),
-                            request: Some(
-                                announce_peer_request::Request::DownloadPeerBackToSourceFailedRequest(
-                                    DownloadPeerBackToSourceFailedRequest {
-                                        description: Some("not all pieces are downloaded from source".to_string()),
-                                    },
-                                ),
-                            ),
-                        }, REQUEST_TIMEOUT)
-                    .await {
+                    match announce::<K>(
+                        &in_stream_tx,
+                        host_id,
+                        task_id,
+                        peer_id,
+                        Announcement::DownloadPeerBackToSourceFailed {
+                            description: "not all pieces are downloaded from source".to_string(),
+                        },
+                    )
+                    .await
+                    {
                         Ok(_) => info!("sent DownloadPeerBackToSourceFailedRequest"),
                         Err(err) => {
-                            error!("send DownloadPeerBackToSourceFailedRequest failed: {:?}", err);
+                            error!(
+                                "send DownloadPeerBackToSourceFailedRequest failed: {:?}",
+                                err
+                            );
                         }
                     }
 
@@ -965,40 +1773,42 @@ impl Task {
         Ok(finished_pieces)
     }
 
-    /// download_partial_with_scheduler_from_parent downloads a partial task with scheduler from a parent.
+    /// download_partial_with_scheduler_from_parent downloads the partial pieces with scheduler
+    /// from the parents.
     #[allow(clippy::too_many_arguments)]
     #[instrument(skip_all)]
     async fn download_partial_with_scheduler_from_parent(
         &self,
-        task: &metadata::Task,
+        task_id: &str,
         host_id: &str,
         peer_id: &str,
-        parents: Vec<Peer>,
+        parents: Vec<piece_collector::CollectedParent>,
         interested_pieces: Vec<metadata::Piece>,
         is_prefetch: bool,
         need_piece_content: bool,
-        download_progress_tx: Sender<Result<DownloadTaskResponse, Status>>,
-        in_stream_tx: Sender<AnnouncePeerRequest>,
+        download_progress_tx: Sender<Result<K::DownloadResponse, Status>>,
+        in_stream_tx: Sender<K::AnnounceRequest>,
     ) -> ClientResult<Vec<metadata::Piece>> {
-        // Get the id of the task.
-        let task_id = task.id.as_str();
+        // Initialize the piece verifier to verify the pieces by the digests of the seed peers,
+        // if the integrity is enabled.
+        let verifier = (K::VERIFY_PIECES && self.config.download.integrity.enable).then(|| {
+            Arc::new(piece_verifier::PieceVerifier::new(
+                self.config.clone(),
+                task_id,
+                &parents,
+            ))
+        });
 
         // Initialize the piece collector.
-        let piece_collector = piece_collector::PieceCollector::new(
+        let (mut piece_collector_rx, piece_collector) = K::collect_pieces(
             self.config.clone(),
             host_id,
             task_id,
             interested_pieces.clone(),
-            parents
-                .into_iter()
-                .map(|peer| piece_collector::CollectedParent {
-                    id: peer.id,
-                    host: peer.host,
-                })
-                .collect(),
+            parents,
+            self.parent_selector.clone(),
         )
         .await;
-        let mut piece_collector_rx = piece_collector.run().await;
 
         // Initialize the interrupt. If download from parent failed with scheduler or download
         // progress, interrupt the collector and return the finished pieces.
@@ -1022,160 +1832,8 @@ impl Task {
                 break;
             }
 
-            async fn download_from_parent(
-                task_id: String,
-                host_id: String,
-                peer_id: String,
-                number: u32,
-                length: u64,
-                parent: piece_collector::CollectedParent,
-                piece_manager: Arc<piece::Piece>,
-                semaphore: Arc<Semaphore>,
-                download_progress_tx: Sender<Result<DownloadTaskResponse, Status>>,
-                in_stream_tx: Sender<AnnouncePeerRequest>,
-                interrupt: Arc<AtomicBool>,
-                finished_pieces: Arc<Mutex<Vec<metadata::Piece>>>,
-                is_prefetch: bool,
-                need_piece_content: bool,
-            ) -> ClientResult<metadata::Piece> {
-                // Limit the concurrent piece count.
-                let _permit = semaphore.acquire().await.unwrap();
-
-                let piece_id = piece_manager.id(task_id.as_str(), number);
-                info!(
-                    "start to download piece {} from parent {:?}",
-                    piece_id,
-                    parent.id.clone()
-                );
-
-                let metadata = piece_manager
-                    .download_from_parent(
-                        piece_id.as_str(),
-                        host_id.as_str(),
-                        task_id.as_str(),
-                        number,
-                        length,
-                        parent.clone(),
-                        is_prefetch,
-                    )
-                    .await
-                    .map_err(|err| {
-                        error!(
-                            "download piece {} from parent {:?} error: {:?}",
-                            piece_id,
-                            parent.id.clone(),
-                            err
-                        );
-                        Error::DownloadFromParentFailed(DownloadFromParentFailed {
-                            piece_number: number,
-                            parent_id: parent.id.clone(),
-                        })
-                    })?;
-
-                // Construct the piece.
-                let mut piece = Piece {
-                    number: metadata.number,
-                    parent_id: metadata.parent_id.clone(),
-                    offset: metadata.offset,
-                    length: metadata.length,
-                    digest: metadata.digest.clone(),
-                    content: None,
-                    traffic_type: Some(TrafficType::RemotePeer as i32),
-                    cost: metadata.prost_cost(),
-                    created_at: Some(prost_wkt_types::Timestamp::from(metadata.created_at)),
-                };
-
-                // If need_piece_content is true, read the piece content from the local.
-                if need_piece_content {
-                    let mut reader = piece_manager
-                        .download_from_local_into_async_read(
-                            piece_id.as_str(),
-                            task_id.as_str(),
-                            metadata.length,
-                            None,
-                            true,
-                            false,
-                        )
-                        .await
-                        .inspect_err(|err| {
-                            error!("read piece {} failed: {:?}", piece_id, err);
-                            interrupt.store(true, Ordering::SeqCst);
-                        })?;
-
-                    let mut content = vec![0; metadata.length as usize];
-                    reader.read_exact(&mut content).await.inspect_err(|err| {
-                        error!("read piece {} failed: {:?}", piece_id, err);
-                        interrupt.store(true, Ordering::SeqCst);
-                    })?;
-
-                    piece.content = Some(content);
-                }
-
-                // Send the download piece finished request.
-                in_stream_tx
-                    .send_timeout(
-                        AnnouncePeerRequest {
-                            host_id: host_id.to_string(),
-                            task_id: task_id.to_string(),
-                            peer_id: peer_id.to_string(),
-                            request: Some(
-                                announce_peer_request::Request::DownloadPieceFinishedRequest(
-                                    DownloadPieceFinishedRequest {
-                                        piece: Some(piece.clone()),
-                                    },
-                                ),
-                            ),
-                        },
-                        REQUEST_TIMEOUT,
-                    )
-                    .await
-                    .unwrap_or_else(|err| {
-                        error!(
-                            "send DownloadPieceFinishedRequest for piece {} failed: {:?}",
-                            piece_id, err
-                        );
-                        interrupt.store(true, Ordering::SeqCst);
-                    });
-
-                // Send the download progress.
-                download_progress_tx
-                    .send_timeout(
-                        Ok(DownloadTaskResponse {
-                            host_id: host_id.to_string(),
-                            task_id: task_id.to_string(),
-                            peer_id: peer_id.to_string(),
-                            response: Some(
-                                download_task_response::Response::DownloadPieceFinishedResponse(
-                                    dfdaemon::v2::DownloadPieceFinishedResponse {
-                                        piece: Some(piece.clone()),
-                                    },
-                                ),
-                            ),
-                        }),
-                        REQUEST_TIMEOUT,
-                    )
-                    .await
-                    .unwrap_or_else(|err| {
-                        error!(
-                            "send DownloadPieceFinishedResponse for piece {} failed: {:?}",
-                            piece_id, err
-                        );
-                        interrupt.store(true, Ordering::SeqCst);
-                    });
-
-                info!(
-                    "finished piece {} from parent {:?}",
-                    piece_id, metadata.parent_id
-                );
-
-                let mut finished_pieces = finished_pieces.lock().unwrap();
-                finished_pieces.push(metadata.clone());
-
-                Ok(metadata)
-            }
-
             join_set.spawn(
-                download_from_parent(
+                download_piece_from_parent::<K>(
                     task_id.to_string(),
                     host_id.to_string(),
                     peer_id.to_string(),
@@ -1183,6 +1841,7 @@ impl Task {
                     collect_piece.length,
                     collect_piece.parent.clone(),
                     self.piece.clone(),
+                    self.parent_selector.clone(),
                     semaphore.clone(),
                     download_progress_tx.clone(),
                     in_stream_tx.clone(),
@@ -1190,6 +1849,7 @@ impl Task {
                     finished_pieces.clone(),
                     is_prefetch,
                     need_piece_content,
+                    verifier.clone(),
                 )
                 .in_current_span(),
             );
@@ -1205,40 +1865,51 @@ impl Task {
             match message {
                 Ok(_) => {}
                 Err(Error::DownloadFromParentFailed(err)) => {
-                    let (piece_number, parent_id) = (err.piece_number, err.parent_id);
+                    let (piece_number, parent_id, temporary) =
+                        (err.piece_number, err.parent_id, err.temporary);
+
+                    // Skip the parent which is forging the pieces, and report it to the
+                    // scheduler by the failure which is not temporary.
+                    if !temporary {
+                        error!(
+                            "parent {} sends forged piece {}",
+                            parent_id,
+                            K::piece_id(&self.piece, task_id, piece_number)
+                        );
+                        if let Some(piece_collector) = piece_collector.as_ref() {
+                            piece_collector.block_parent(&parent_id);
+                        }
+                    }
 
                     // Send the download piece failed request.
-                    in_stream_tx
-                        .send_timeout(
-                            AnnouncePeerRequest {
-                                host_id: host_id.to_string(),
-                                task_id: task_id.to_string(),
-                                peer_id: peer_id.to_string(),
-                                request: Some(
-                                    announce_peer_request::Request::DownloadPieceFailedRequest(
-                                        DownloadPieceFailedRequest {
-                                            piece_number: Some(err.piece_number),
-                                            parent_id,
-                                            temporary: true,
-                                        },
-                                    ),
-                                ),
-                            },
-                            REQUEST_TIMEOUT,
+                    announce::<K>(
+                        &in_stream_tx,
+                        host_id,
+                        task_id,
+                        peer_id,
+                        Announcement::DownloadPieceFailed {
+                            piece_number,
+                            parent_id,
+                            temporary,
+                        },
+                    )
+                    .await
+                    .unwrap_or_else(|err| {
+                        error!(
+                            "send DownloadPieceFailedRequest for piece {} failed: {:?}",
+                            K::piece_id(&self.piece, task_id, piece_number),
+                            err
                         )
-                        .await
-                        .unwrap_or_else(|err| {
-                            error!(
-                                "send DownloadPieceFailedRequest for piece {} failed: {:?}",
-                                self.piece.id(task_id, piece_number),
-                                err
-                            )
-                        });
+                    });
 
                     // If the download failed from the parent, continue to download the next
                     // piece and ignore the error.
                     continue;
                 }
+                Err(Error::Unsupported(err)) => {
+                    join_set.detach_all();
+                    return Err(Error::Unsupported(err));
+                }
                 Err(Error::SendTimeout) => {
                     join_set.detach_all();
 
@@ -1262,184 +1933,33 @@ impl Task {
         Ok(finished_pieces)
     }
 
-    /// download_partial_with_scheduler_from_source downloads a partial task with scheduler from the source.
+    /// download_partial_with_scheduler_from_source downloads the partial pieces with scheduler
+    /// from the source.
     #[allow(clippy::too_many_arguments)]
     #[instrument(skip_all)]
     async fn download_partial_with_scheduler_from_source(
         &self,
-        task: &metadata::Task,
+        task_id: &str,
         host_id: &str,
         peer_id: &str,
         interested_pieces: Vec<metadata::Piece>,
-        request: Download,
-        download_progress_tx: Sender<Result<DownloadTaskResponse, Status>>,
-        in_stream_tx: Sender<AnnouncePeerRequest>,
+        request: PieceRequest,
+        download_progress_tx: Sender<Result<K::DownloadResponse, Status>>,
+        in_stream_tx: Sender<K::AnnounceRequest>,
     ) -> ClientResult<Vec<metadata::Piece>> {
-        // Get the id of the task.
-        let task_id = task.id.as_str();
-
-        // Convert the header.
-        let request_header: HeaderMap = (&request.request_header)
-            .try_into()
-            .or_err(ErrorType::ParseError)?;
-
         // Initialize the finished pieces.
         let mut finished_pieces: Vec<metadata::Piece> = Vec::new();
 
-        // Download the piece from the local.
-        let mut join_set = JoinSet::new();
-        let semaphore = Arc::new(Semaphore::new(
-            self.config.download.concurrent_piece_count as usize,
-        ));
-        for interested_piece in interested_pieces {
-            async fn download_from_source(
-                task_id: String,
-                host_id: String,
-                peer_id: String,
-                number: u32,
-                url: String,
-                offset: u64,
-                length: u64,
-                request_header: HeaderMap,
-                is_prefetch: bool,
-                need_piece_content: bool,
-                piece_manager: Arc<piece::Piece>,
-                semaphore: Arc<Semaphore>,
-                download_progress_tx: Sender<Result<DownloadTaskResponse, Status>>,
-                in_stream_tx: Sender<AnnouncePeerRequest>,
-                object_storage: Option<ObjectStorage>,
-                hdfs: Option<Hdfs>,
-            ) -> ClientResult<metadata::Piece> {
-                // Limit the concurrent download count.
-                let _permit = semaphore.acquire().await.unwrap();
-
-                let piece_id = piece_manager.id(task_id.as_str(), number);
-                info!("start to download piece {} from source", piece_id);
-
-                let metadata = piece_manager
-                    .download_from_source(
-                        piece_id.as_str(),
-                        task_id.as_str(),
-                        number,
-                        url.as_str(),
-                        offset,
-                        length,
-                        request_header,
-                        is_prefetch,
-                        object_storage,
-                        hdfs,
-                    )
-                    .await?;
-
-                // Construct the piece.
-                let mut piece = Piece {
-                    number: metadata.number,
-                    parent_id: metadata.parent_id.clone(),
-                    offset: metadata.offset,
-                    length: metadata.length,
-                    digest: metadata.digest.clone(),
-                    content: None,
-                    traffic_type: Some(TrafficType::BackToSource as i32),
-                    cost: metadata.prost_cost(),
-                    created_at: Some(prost_wkt_types::Timestamp::from(metadata.created_at)),
-                };
-
-                // If need_piece_content is true, read the piece content from the local.
-                if need_piece_content {
-                    let mut reader = piece_manager
-                        .download_from_local_into_async_read(
-                            piece_id.as_str(),
-                            task_id.as_str(),
-                            metadata.length,
-                            None,
-                            true,
-                            false,
-                        )
-                        .await
-                        .inspect_err(|err| {
-                            error!("read piece {} failed: {:?}", piece_id, err);
-                        })?;
-
-                    let mut content = vec![0; metadata.length as usize];
-                    reader.read_exact(&mut content).await.inspect_err(|err| {
-                        error!("read piece {} failed: {:?}", piece_id, err);
-                    })?;
-
-                    piece.content = Some(content);
-                }
-
-                // Send the download piece finished request.
-                in_stream_tx
-                        .send_timeout(
-                            AnnouncePeerRequest {
-                                host_id: host_id.to_string(),
-                                task_id: task_id.to_string(),
-                                peer_id: peer_id.to_string(),
-                                request: Some(
-                                    announce_peer_request::Request::DownloadPieceBackToSourceFinishedRequest(
-                                        DownloadPieceBackToSourceFinishedRequest {
-                                            piece: Some(piece.clone()),
-                                        },
-                                    ),
-                                ),
-                            },
-                            REQUEST_TIMEOUT,
-                        )
-                        .await.unwrap_or_else(|err| {
-                            error!("send DownloadPieceBackToSourceFinishedRequest for piece {} failed: {:?}", piece_id, err);
-                        });
-
-                // Send the download progress.
-                download_progress_tx
-                    .send_timeout(
-                        Ok(DownloadTaskResponse {
-                            host_id: host_id.to_string(),
-                            task_id: task_id.to_string(),
-                            peer_id: peer_id.to_string(),
-                            response: Some(
-                                download_task_response::Response::DownloadPieceFinishedResponse(
-                                    dfdaemon::v2::DownloadPieceFinishedResponse {
-                                        piece: Some(piece.clone()),
-                                    },
-                                ),
-                            ),
-                        }),
-                        REQUEST_TIMEOUT,
-                    )
-                    .await
-                    .unwrap_or_else(|err| {
-                        error!(
-                            "send DownloadPieceFinishedResponse for piece {} failed: {:?}",
-                            piece_id, err
-                        );
-                    });
-
-                info!("finished piece {} from source", piece_id);
-                Ok(metadata)
-            }
-
-            join_set.spawn(
-                download_from_source(
-                    task_id.to_string(),
-                    host_id.to_string(),
-                    peer_id.to_string(),
-                    interested_piece.number,
-                    request.url.clone(),
-                    interested_piece.offset,
-                    interested_piece.length,
-                    request_header.clone(),
-                    request.is_prefetch,
-                    request.need_piece_content,
-                    self.piece.clone(),
-                    semaphore.clone(),
-                    download_progress_tx.clone(),
-                    in_stream_tx.clone(),
-                    request.object_storage.clone(),
-                    request.hdfs.clone(),
-                )
-                .in_current_span(),
-            );
-        }
+        // Download the pieces from the source.
+        let mut join_set = self.spawn_download_from_source(
+            task_id,
+            host_id,
+            peer_id,
+            &interested_pieces,
+            &request,
+            download_progress_tx,
+            Some(in_stream_tx.clone()),
+        )?;
 
         // Wait for the pieces to be downloaded.
         while let Some(message) = join_set
@@ -1457,25 +1977,30 @@ impl Task {
                     join_set.detach_all();
 
                     // Send the download piece http failed request.
-                    in_stream_tx.send_timeout(AnnouncePeerRequest {
-                                    host_id: host_id.to_string(),
-                                    task_id: task_id.to_string(),
-                                    peer_id: peer_id.to_string(),
-                                    request: Some(announce_peer_request::Request::DownloadPieceBackToSourceFailedRequest(
-                                            DownloadPieceBackToSourceFailedRequest{
-                                                piece_number: None,
-                                                response: Some(download_piece_back_to_source_failed_request::Response::Backend(
-                                                        Backend{
-                                                            message: err.message.clone(),
-                                                            header: headermap_to_hashmap(&err.header.clone().unwrap_or_default()),
-                                                            status_code: err.status_code.map(|code| code.as_u16() as i32),
-                                                        }
-                                                )),
-                                            }
-                                    )),
-                                }, REQUEST_TIMEOUT)
-                                .await
-                                .unwrap_or_else(|err| error!("send DownloadPieceBackToSourceFailedRequest error: {:?}", err));
+                    announce::<K>(
+                        &in_stream_tx,
+                        host_id,
+                        task_id,
+                        peer_id,
+                        Announcement::DownloadPieceBackToSourceFailed(
+                            download_piece_back_to_source_failed_request::Response::Backend(
+                                Backend {
+                                    message: err.message.clone(),
+                                    header: headermap_to_hashmap(
+                                        &err.header.clone().unwrap_or_default(),
+                                    ),
+                                    status_code: err.status_code.map(|code| code.as_u16() as i32),
+                                },
+                            ),
+                        ),
+                    )
+                    .await
+                    .unwrap_or_else(|err| {
+                        error!(
+                            "send DownloadPieceBackToSourceFailedRequest error: {:?}",
+                            err
+                        )
+                    });
 
                     // If the backend error with source, return the error.
                     // It will stop the download from the source with scheduler
@@ -1486,23 +2011,26 @@ impl Task {
                     join_set.detach_all();
 
                     // Send the download piece failed request.
-                    in_stream_tx.send_timeout(AnnouncePeerRequest {
-                                    host_id: host_id.to_string(),
-                                    task_id: task_id.to_string(),
-                                    peer_id: peer_id.to_string(),
-                                    request: Some(announce_peer_request::Request::DownloadPieceBackToSourceFailedRequest(
-                                            DownloadPieceBackToSourceFailedRequest{
-                                                piece_number: None,
-                                                response: Some(download_piece_back_to_source_failed_request::Response::Unknown(
-                                                        Unknown{
-                                                            message: Some("send timeout".to_string()),
-                                                        }
-                                                )),
-                                            }
-                                    )),
-                                }, REQUEST_TIMEOUT)
-                                .await
-                                .unwrap_or_else(|err| error!("send DownloadPieceBackToSourceFailedRequest error: {:?}", err));
+                    announce::<K>(
+                        &in_stream_tx,
+                        host_id,
+                        task_id,
+                        peer_id,
+                        Announcement::DownloadPieceBackToSourceFailed(
+                            download_piece_back_to_source_failed_request::Response::Unknown(
+                                Unknown {
+                                    message: Some("send timeout".to_string()),
+                                },
+                            ),
+                        ),
+                    )
+                    .await
+                    .unwrap_or_else(|err| {
+                        error!(
+                            "send DownloadPieceBackToSourceFailedRequest error: {:?}",
+                            err
+                        )
+                    });
 
                     // If the send timeout with scheduler or download progress, return
                     // the finished pieces. It will stop the download from the source with
@@ -1513,23 +2041,26 @@ impl Task {
                     join_set.detach_all();
 
                     // Send the download piece failed request.
-                    in_stream_tx.send_timeout(AnnouncePeerRequest {
-                                    host_id: host_id.to_string(),
-                                    task_id: task_id.to_string(),
-                                    peer_id: peer_id.to_string(),
-                                    request: Some(announce_peer_request::Request::DownloadPieceBackToSourceFailedRequest(
-                                            DownloadPieceBackToSourceFailedRequest{
-                                                piece_number: None,
-                                                response: Some(download_piece_back_to_source_failed_request::Response::Unknown(
-                                                        Unknown{
-                                                            message: Some(err.to_string()),
-                                                        }
-                                                )),
-                                            }
-                                    )),
-                                }, REQUEST_TIMEOUT)
-                                .await
-                                .unwrap_or_else(|err| error!("send DownloadPieceBackToSourceFailedRequest error: {:?}", err));
+                    announce::<K>(
+                        &in_stream_tx,
+                        host_id,
+                        task_id,
+                        peer_id,
+                        Announcement::DownloadPieceBackToSourceFailed(
+                            download_piece_back_to_source_failed_request::Response::Unknown(
+                                Unknown {
+                                    message: Some(err.to_string()),
+                                },
+                            ),
+                        ),
+                    )
+                    .await
+                    .unwrap_or_else(|err| {
+                        error!(
+                            "send DownloadPieceBackToSourceFailedRequest error: {:?}",
+                            err
+                        )
+                    });
 
                     // If the unknown error, return the error.
                     // It will stop the download from the source with scheduler
@@ -1542,49 +2073,41 @@ impl Task {
         Ok(finished_pieces)
     }
 
-    /// download_partial_from_local downloads a partial task from a local.
-    #[allow(clippy::too_many_arguments)]
+    /// download_partial_from_local downloads the partial pieces from the local storage.
     #[instrument(skip_all)]
-    async fn download_partial_from_local(
+    pub async fn download_partial_from_local(
         &self,
-        task: &metadata::Task,
+        task_id: &str,
         host_id: &str,
         peer_id: &str,
         need_piece_content: bool,
         interested_pieces: Vec<metadata::Piece>,
-        download_progress_tx: Sender<Result<DownloadTaskResponse, Status>>,
+        download_progress_tx: Sender<Result<K::DownloadResponse, Status>>,
     ) -> ClientResult<Vec<metadata::Piece>> {
-        // Get the id of the task.
-        let task_id = task.id.as_str();
-
         // Initialize the finished pieces.
         let mut finished_pieces: Vec<metadata::Piece> = Vec::new();
 
         // Download the piece from the local.
         for interested_piece in interested_pieces {
-            let piece_id = self.piece.id(task_id, interested_piece.number);
-
-            // Get the piece metadata from the local storage.
-            let piece = match self.piece.get(piece_id.as_str()) {
-                Ok(Some(piece)) => piece,
-                Ok(None) => {
-                    debug!("piece {} not found in local storage", piece_id);
-                    continue;
-                }
-                Err(err) => {
-                    error!("get piece {} from local storage error: {:?}", piece_id, err);
-                    continue;
-                }
-            };
-
-            if !piece.is_finished() {
-                debug!("piece {} is not finished, skip it", piece_id);
-                continue;
-            }
+            let piece_id = K::piece_id(&self.piece, task_id, interested_piece.number);
+
+            // Get the finished piece from the local storage.
+            let piece =
+                match K::get_finished_piece(&self.piece, task_id, interested_piece.number).await {
+                    Ok(Some(piece)) => piece,
+                    Ok(None) => {
+                        debug!("piece {} not finished in local storage", piece_id);
+                        continue;
+                    }
+                    Err(err) => {
+                        error!("get piece {} from local storage error: {:?}", piece_id, err);
+                        continue;
+                    }
+                };
 
             // Fake the download from the local.
-            self.piece.download_from_local(task_id, piece.length);
-            info!("finished piece {} from local", piece_id,);
+            K::download_from_local(&self.piece, task_id, piece.length);
+            info!("finished piece {} from local", piece_id);
 
             // Construct the piece.
             let mut piece = Piece {
@@ -1601,23 +2124,15 @@ impl Task {
 
             // If need_piece_content is true, read the piece content from the local.
             if need_piece_content {
-                let mut reader = self
-                    .piece
-                    .download_from_local_into_async_read(
-                        piece_id.as_str(),
-                        task_id,
-                        piece.length,
-                        None,
-                        true,
-                        false,
-                    )
-                    .await
-                    .inspect_err(|err| {
-                        error!("read piece {} failed: {:?}", piece_id, err);
-                    })?;
-
-                let mut content = vec![0; piece.length as usize];
-                reader.read_exact(&mut content).await.inspect_err(|err| {
+                let content = K::read_piece(
+                    &self.piece,
+                    piece_id.as_str(),
+                    task_id,
+                    piece.number,
+                    piece.length,
+                )
+                .await
+                .inspect_err(|err| {
                     error!("read piece {} failed: {:?}", piece_id, err);
                 })?;
 
@@ -1627,18 +2142,9 @@ impl Task {
             // Send the download progress.
             download_progress_tx
                 .send_timeout(
-                    Ok(DownloadTaskResponse {
-                        host_id: host_id.to_string(),
-                        task_id: task_id.to_string(),
-                        peer_id: peer_id.to_string(),
-                        response: Some(
-                            download_task_response::Response::DownloadPieceFinishedResponse(
-                                dfdaemon::v2::DownloadPieceFinishedResponse {
-                                    piece: Some(piece.clone()),
-                                },
-                            ),
-                        ),
-                    }),
+                    Ok(K::download_piece_finished_response(
+                        host_id, task_id, peer_id, piece,
+                    )),
                     REQUEST_TIMEOUT,
                 )
                 .await
@@ -1656,161 +2162,31 @@ impl Task {
         Ok(finished_pieces)
     }
 
-    /// download_partial_from_source downloads a partial task from the source.
-    #[allow(clippy::too_many_arguments)]
+    /// download_partial_from_source downloads the partial pieces from the source without
+    /// scheduler.
     #[instrument(skip_all)]
-    async fn download_partial_from_source(
+    pub async fn download_partial_from_source(
         &self,
-        task: &metadata::Task,
+        task_id: &str,
         host_id: &str,
         peer_id: &str,
         interested_pieces: Vec<metadata::Piece>,
-        request: Download,
-        download_progress_tx: Sender<Result<DownloadTaskResponse, Status>>,
+        request: PieceRequest,
+        download_progress_tx: Sender<Result<K::DownloadResponse, Status>>,
     ) -> ClientResult<Vec<metadata::Piece>> {
-        // Get the id of the task.
-        let task_id = task.id.as_str();
-
-        // Convert the header.
-        let request_header: HeaderMap = (&request.request_header)
-            .try_into()
-            .or_err(ErrorType::ParseError)?;
-
         // Initialize the finished pieces.
         let mut finished_pieces: Vec<metadata::Piece> = Vec::new();
 
         // Download the pieces.
-        let mut join_set = JoinSet::new();
-        let semaphore = Arc::new(Semaphore::new(
-            self.config.download.concurrent_piece_count as usize,
-        ));
-
-        for interested_piece in &interested_pieces {
-            async fn download_from_source(
-                task_id: String,
-                host_id: String,
-                peer_id: String,
-                number: u32,
-                url: String,
-                offset: u64,
-                length: u64,
-                request_header: HeaderMap,
-                is_prefetch: bool,
-                need_piece_content: bool,
-                piece_manager: Arc<piece::Piece>,
-                semaphore: Arc<Semaphore>,
-                download_progress_tx: Sender<Result<DownloadTaskResponse, Status>>,
-                object_storage: Option<ObjectStorage>,
-                hdfs: Option<Hdfs>,
-            ) -> ClientResult<metadata::Piece> {
-                // Limit the concurrent download count.
-                let _permit = semaphore.acquire().await.unwrap();
-
-                let piece_id = piece_manager.id(task_id.as_str(), number);
-                info!("start to download piece {} from source", piece_id);
-
-                let metadata = piece_manager
-                    .download_from_source(
-                        piece_id.as_str(),
-                        task_id.as_str(),
-                        number,
-                        url.as_str(),
-                        offset,
-                        length,
-                        request_header,
-                        is_prefetch,
-                        object_storage,
-                        hdfs,
-                    )
-                    .await?;
-
-                // Construct the piece.
-                let mut piece = Piece {
-                    number: metadata.number,
-                    parent_id: None,
-                    offset: metadata.offset,
-                    length: metadata.length,
-                    digest: metadata.digest.clone(),
-                    content: None,
-                    traffic_type: Some(TrafficType::BackToSource as i32),
-                    cost: metadata.prost_cost(),
-                    created_at: Some(prost_wkt_types::Timestamp::from(metadata.created_at)),
-                };
-
-                // If need_piece_content is true, read the piece content from the local.
-                if need_piece_content {
-                    let mut reader = piece_manager
-                        .download_from_local_into_async_read(
-                            piece_id.as_str(),
-                            task_id.as_str(),
-                            piece.length,
-                            None,
-                            true,
-                            false,
-                        )
-                        .await
-                        .inspect_err(|err| {
-                            error!("read piece {} failed: {:?}", piece_id, err);
-                        })?;
-
-                    let mut content = vec![0; piece.length as usize];
-                    reader.read_exact(&mut content).await.inspect_err(|err| {
-                        error!("read piece {} failed: {:?}", piece_id, err);
-                    })?;
-
-                    piece.content = Some(content);
-                }
-
-                // Send the download progress.
-                download_progress_tx
-                    .send_timeout(
-                        Ok(DownloadTaskResponse {
-                            host_id: host_id.to_string(),
-                            task_id: task_id.to_string(),
-                            peer_id: peer_id.to_string(),
-                            response: Some(
-                                download_task_response::Response::DownloadPieceFinishedResponse(
-                                    dfdaemon::v2::DownloadPieceFinishedResponse {
-                                        piece: Some(piece.clone()),
-                                    },
-                                ),
-                            ),
-                        }),
-                        REQUEST_TIMEOUT,
-                    )
-                    .await
-                    .unwrap_or_else(|err| {
-                        error!(
-                            "send DownloadPieceFinishedResponse for piece {} failed: {:?}",
-                            piece_id, err
-                        );
-                    });
-
-                info!("finished piece {} from source", piece_id);
-                Ok(metadata)
-            }
-
-            join_set.spawn(
-                download_from_source(
-                    task_id.to_string(),
-                    host_id.to_string(),
-                    peer_id.to_string(),
-                    interested_piece.number,
-                    request.url.clone(),
-                    interested_piece.offset,
-                    interested_piece.length,
-                    request_header.clone(),
-                    request.is_prefetch,
-                    request.need_piece_content,
-                    self.piece.clone(),
-                    semaphore.clone(),
-                    download_progress_tx.clone(),
-                    request.object_storage.clone(),
-                    request.hdfs.clone(),
-                )
-                .in_current_span(),
-            );
-        }
+        let mut join_set = self.spawn_download_from_source(
+            task_id,
+            host_id,
+            peer_id,
+            &interested_pieces,
+            &request,
+            download_progress_tx,
+            None,
+        )?;
 
         // Wait for the pieces to be downloaded.
         while let Some(message) = join_set
@@ -1842,123 +2218,405 @@ impl Task {
             ));
         }
 
-        return Ok(finished_pieces);
+        Ok(finished_pieces)
     }
 
-    /// stat_task returns the task metadata.
-    #[instrument(skip_all)]
-    pub async fn stat(
+    /// spawn_download_from_source spawns the downloads of the pieces from the source, the
+    /// finished pieces are announced to the scheduler if in_stream_tx is provided.
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_download_from_source(
         &self,
         task_id: &str,
         host_id: &str,
-        local_only: bool,
-    ) -> ClientResult<CommonTask> {
-        if local_only {
-            let Some(task_metadata) = self.storage.get_task(task_id).inspect_err(|err| {
-                error!("get task {} from local storage error: {:?}", task_id, err);
-            })?
-            else {
-                return Err(Error::TaskNotFound(task_id.to_owned()));
-            };
+        peer_id: &str,
+        interested_pieces: &[metadata::Piece],
+        request: &PieceRequest,
+        download_progress_tx: Sender<Result<K::DownloadResponse, Status>>,
+        in_stream_tx: Option<Sender<K::AnnounceRequest>>,
+    ) -> ClientResult<JoinSet<ClientResult<metadata::Piece>>> {
+        // Convert the header.
+        let request_header: HeaderMap = (&request.request_header)
+            .try_into()
+            .or_err(ErrorType::ParseError)?;
 
-            let piece_metadatas = self.piece.get_all(task_id).inspect_err(|err| {
-                error!(
-                    "get pieces for task {} from local storage error: {:?}",
-                    task_id, err
-                );
-            })?;
+        let mut join_set = JoinSet::new();
+        let semaphore = Arc::new(Semaphore::new(
+            self.config.download.concurrent_piece_count as usize,
+        ));
+        for interested_piece in interested_pieces {
+            join_set.spawn(
+                download_piece_from_source::<K>(
+                    task_id.to_string(),
+                    host_id.to_string(),
+                    peer_id.to_string(),
+                    interested_piece.number,
+                    request.url.clone(),
+                    interested_piece.offset,
+                    interested_piece.length,
+                    request_header.clone(),
+                    request.is_prefetch,
+                    request.need_piece_content,
+                    self.piece.clone(),
+                    semaphore.clone(),
+                    download_progress_tx.clone(),
+                    in_stream_tx.clone(),
+                    request.object_storage.clone(),
+                    request.hdfs.clone(),
+                )
+                .in_current_span(),
+            );
+        }
 
-            let pieces = piece_metadatas
-                .into_iter()
-                .filter(|piece| piece.is_finished())
-                .map(|piece| {
-                    // The traffic_type indicates whether the first download was from the source or hit the remote peer cache.
-                    // If the parent_id exists, the piece was downloaded from a remote peer. Otherwise, it was
-                    // downloaded from the source.
-                    let traffic_type = match piece.parent_id {
-                        None => TrafficType::BackToSource,
-                        Some(_) => TrafficType::RemotePeer,
-                    };
+        Ok(join_set)
+    }
+}
 
-                    Piece {
-                        number: piece.number,
-                        parent_id: piece.parent_id.clone(),
-                        offset: piece.offset,
-                        length: piece.length,
-                        digest: piece.digest.clone(),
-                        content: None,
-                        traffic_type: Some(traffic_type as i32),
-                        cost: piece.prost_cost(),
-                        created_at: Some(prost_wkt_types::Timestamp::from(piece.created_at)),
-                    }
-                })
-                .collect::<Vec<Piece>>();
+/// announce sends the announcement of the storage kind to the scheduler by the announce stream.
+async fn announce<K: StorageKind>(
+    in_stream_tx: &Sender<K::AnnounceRequest>,
+    host_id: &str,
+    task_id: &str,
+    peer_id: &str,
+    announcement: Announcement<K::Parent>,
+) -> ClientResult<()> {
+    in_stream_tx
+        .send_timeout(
+            K::announce_request(host_id, task_id, peer_id, announcement),
+            REQUEST_TIMEOUT,
+        )
+        .await?;
+    Ok(())
+}
 
-            return Ok(CommonTask {
-                id: task_metadata.id,
-                r#type: TaskType::Standard as i32,
-                url: String::new(),
-                digest: None,
-                tag: None,
-                application: None,
-                filtered_query_params: Vec::new(),
-                request_header: HashMap::new(),
-                content_length: task_metadata.content_length.unwrap_or(0),
-                piece_count: pieces.len() as u32,
-                size_scope: SizeScope::Normal as i32,
-                pieces,
-                state: String::new(),
-                peer_count: 0,
-                has_available_peer: false,
-                created_at: Some(prost_wkt_types::Timestamp::from(task_metadata.created_at)),
-                updated_at: Some(prost_wkt_types::Timestamp::from(task_metadata.updated_at)),
-            });
+/// fail_peer_for_integrity sends the download peer failed request to the scheduler, because
+/// the pieces from the parents can not be verified by the seed peers if the integrity is
+/// enabled, then the pieces are downloaded from the source.
+async fn fail_peer_for_integrity<K: StorageKind>(
+    host_id: &str,
+    task_id: &str,
+    peer_id: &str,
+    in_stream_tx: &Sender<K::AnnounceRequest>,
+) {
+    announce::<K>(
+        in_stream_tx,
+        host_id,
+        task_id,
+        peer_id,
+        Announcement::DownloadPeerFailed {
+            description: "integrity requires seed peers with integrity enabled".to_string(),
+        },
+    )
+    .await
+    .unwrap_or_else(|err| error!("send DownloadPeerFailedRequest failed: {:?}", err));
+    info!("sent DownloadPeerFailedRequest");
+
+    // Wait for the latest message to be sent.
+    in_stream_tx.closed().await;
+}
+
+/// download_piece_from_parent downloads the piece of the storage kind from the parent, and
+/// announces the finished piece to the scheduler and the download progress.
+#[allow(clippy::too_many_arguments)]
+async fn download_piece_from_parent<K: StorageKind>(
+    task_id: String,
+    host_id: String,
+    peer_id: String,
+    number: u32,
+    length: u64,
+    parent: piece_collector::CollectedParent,
+    piece_manager: Arc<piece::Piece>,
+    parent_selector: Arc<parent_selector::ParentSelector>,
+    semaphore: Arc<Semaphore>,
+    download_progress_tx: Sender<Result<K::DownloadResponse, Status>>,
+    in_stream_tx: Sender<K::AnnounceRequest>,
+    interrupt: Arc<AtomicBool>,
+    finished_pieces: Arc<Mutex<Vec<metadata::Piece>>>,
+    is_prefetch: bool,
+    need_piece_content: bool,
+    verifier: Option<Arc<piece_verifier::PieceVerifier>>,
+) -> ClientResult<metadata::Piece> {
+    // Limit the concurrent piece count.
+    let _permit = semaphore.acquire().await.unwrap();
+
+    // Record the piece downloading from the parent, it decreases the score of the
+    // parent in the parent selector until the piece is downloaded.
+    let _inflight_piece = parent_selector.start_download(&parent);
+
+    let piece_id = K::piece_id(&piece_manager, task_id.as_str(), number);
+    info!(
+        "start to download piece {} from parent {:?}",
+        piece_id,
+        parent.id.clone()
+    );
+
+    let metadata = K::download_from_parent(
+        &piece_manager,
+        piece_id.as_str(),
+        host_id.as_str(),
+        task_id.as_str(),
+        number,
+        length,
+        parent.clone(),
+        is_prefetch,
+        verifier.clone(),
+    )
+    .await
+    .map_err(|err| {
+        error!(
+            "download piece {} from parent {:?} error: {:?}",
+            piece_id,
+            parent.id.clone(),
+            err
+        );
+
+        // The pieces can not be verified by the seed peers, so stop downloading
+        // from the parents.
+        if let Error::Unsupported(_) = err {
+            interrupt.store(true, Ordering::SeqCst);
+            return err;
         }
 
-        let task = self
-            .scheduler_client
-            .stat_task(StatTaskRequest {
-                host_id: host_id.to_string(),
-                task_id: task_id.to_string(),
-            })
-            .await
-            .inspect_err(|err| {
-                error!("stat task failed: {}", err);
-            })?;
+        // The parent which sends the piece mismatching the trusted digest is
+        // forging the piece, the failure is not temporary.
+        let forged = verifier.is_some() && matches!(err, Error::DigestMismatch(_, _));
+        Error::DownloadFromParentFailed(DownloadFromParentFailed {
+            piece_number: number,
+            parent_id: parent.id.clone(),
+            temporary: !forged,
+        })
+    })?;
+
+    // Construct the piece.
+    let mut piece = Piece {
+        number: metadata.number,
+        parent_id: metadata.parent_id.clone(),
+        offset: metadata.offset,
+        length: metadata.length,
+        digest: metadata.digest.clone(),
+        content: None,
+        traffic_type: Some(TrafficType::RemotePeer as i32),
+        cost: metadata.prost_cost(),
+        created_at: Some(prost_wkt_types::Timestamp::from(metadata.created_at)),
+    };
+
+    // If need_piece_content is true, read the piece content from the local.
+    if need_piece_content {
+        let content = K::read_piece(
+            &piece_manager,
+            piece_id.as_str(),
+            task_id.as_str(),
+            number,
+            metadata.length,
+        )
+        .await
+        .inspect_err(|err| {
+            error!("read piece {} failed: {:?}", piece_id, err);
+            interrupt.store(true, Ordering::SeqCst);
+        })?;
 
-        Ok(task)
+        piece.content = Some(content);
     }
 
-    /// Delete a task and reclaim local storage.
-    #[instrument(skip_all)]
-    pub async fn delete(&self, task_id: &str, host_id: &str) -> ClientResult<()> {
-        let task = self.storage.get_task(task_id).inspect_err(|err| {
-            error!("get task {} from local storage error: {:?}", task_id, err);
+    // Send the download piece finished request.
+    announce::<K>(
+        &in_stream_tx,
+        host_id.as_str(),
+        task_id.as_str(),
+        peer_id.as_str(),
+        Announcement::DownloadPieceFinished(piece.clone()),
+    )
+    .await
+    .unwrap_or_else(|err| {
+        error!(
+            "send DownloadPieceFinishedRequest for piece {} failed: {:?}",
+            piece_id, err
+        );
+        interrupt.store(true, Ordering::SeqCst);
+    });
+
+    // Send the download progress.
+    download_progress_tx
+        .send_timeout(
+            Ok(K::download_piece_finished_response(
+                host_id.as_str(),
+                task_id.as_str(),
+                peer_id.as_str(),
+                piece,
+            )),
+            REQUEST_TIMEOUT,
+        )
+        .await
+        .unwrap_or_else(|err| {
+            error!(
+                "send DownloadPieceFinishedResponse for piece {} failed: {:?}",
+                piece_id, err
+            );
+            interrupt.store(true, Ordering::SeqCst);
+        });
+
+    info!(
+        "finished piece {} from parent {:?}",
+        piece_id, metadata.parent_id
+    );
+
+    let mut finished_pieces = finished_pieces.lock().unwrap();
+    finished_pieces.push(metadata.clone());
+
+    Ok(metadata)
+}
+
+/// download_piece_from_source downloads the piece of the storage kind from the source, and
+/// announces the finished piece to the download progress, and to the scheduler if
+/// in_stream_tx is provided.
+#[allow(clippy::too_many_arguments)]
+async fn download_piece_from_source<K: StorageKind>(
+    task_id: String,
+    host_id: String,
+    peer_id: String,
+    number: u32,
+    url: String,
+    offset: u64,
+    length: u64,
+    request_header: HeaderMap,
+    is_prefetch: bool,
+    need_piece_content: bool,
+    piece_manager: Arc<piece::Piece>,
+    semaphore: Arc<Semaphore>,
+    download_progress_tx: Sender<Result<K::DownloadResponse, Status>>,
+    in_stream_tx: Option<Sender<K::AnnounceRequest>>,
+    object_storage: Option<ObjectStorage>,
+    hdfs: Option<Hdfs>,
+) -> ClientResult<metadata::Piece> {
+    // Limit the concurrent download count.
+    let _permit = semaphore.acquire().await.unwrap();
+
+    let piece_id = K::piece_id(&piece_manager, task_id.as_str(), number);
+    info!("start to download piece {} from source", piece_id);
+
+    let metadata = K::download_from_source(
+        &piece_manager,
+        piece_id.as_str(),
+        task_id.as_str(),
+        number,
+        url.as_str(),
+        offset,
+        length,
+        request_header,
+        is_prefetch,
+        object_storage,
+        hdfs,
+    )
+    .await?;
+
+    // Construct the piece.
+    let mut piece = Piece {
+        number: metadata.number,
+        parent_id: metadata.parent_id.clone(),
+        offset: metadata.offset,
+        length: metadata.length,
+        digest: metadata.digest.clone(),
+        content: None,
+        traffic_type: Some(TrafficType::BackToSource as i32),
+        cost: metadata.prost_cost(),
+        created_at: Some(prost_wkt_types::Timestamp::from(metadata.created_at)),
+    };
+
+    // If need_piece_content is true, read the piece content from the local.
+    if need_piece_content {
+        let content = K::read_piece(
+            &piece_manager,
+            piece_id.as_str(),
+            task_id.as_str(),
+            number,
+            metadata.length,
+        )
+        .await
+        .inspect_err(|err| {
+            error!("read piece {} failed: {:?}", piece_id, err);
         })?;
 
-        match task {
-            Some(task) => {
-                self.storage.delete_task(task.id.as_str()).await;
+        piece.content = Some(content);
+    }
 
-                self.scheduler_client
-                    .delete_task(DeleteTaskRequest {
-                        host_id: host_id.to_string(),
-                        task_id: task_id.to_string(),
-                    })
-                    .await
-                    .inspect_err(|err| {
-                        error!("delete task {} failed from scheduler: {:?}", task_id, err);
-                    })?;
+    // Send the download piece finished request.
+    if let Some(in_stream_tx) = in_stream_tx {
+        announce::<K>(
+            &in_stream_tx,
+            host_id.as_str(),
+            task_id.as_str(),
+            peer_id.as_str(),
+            Announcement::DownloadPieceBackToSourceFinished(piece.clone()),
+        )
+        .await
+        .unwrap_or_else(|err| {
+            error!(
+                "send DownloadPieceBackToSourceFinishedRequest for piece {} failed: {:?}",
+                piece_id, err
+            );
+        });
+    }
 
-                info!("delete task {} from local storage", task.id);
-                Ok(())
-            }
-            None => {
-                error!("delete_task task {} not found", task_id);
-                Err(Error::TaskNotFound(task_id.to_owned()))
-            }
-        }
+    // Send the download progress.
+    download_progress_tx
+        .send_timeout(
+            Ok(K::download_piece_finished_response(
+                host_id.as_str(),
+                task_id.as_str(),
+                peer_id.as_str(),
+                piece,
+            )),
+            REQUEST_TIMEOUT,
+        )
+        .await
+        .unwrap_or_else(|err| {
+            error!(
+                "send DownloadPieceFinishedResponse for piece {} failed: {:?}",
+                piece_id, err
+            );
+        });
+
+    info!("finished piece {} from source", piece_id);
+    Ok(metadata)
+}
+
+/// SENSITIVE_PREFETCH_REQUEST_HEADERS are the request headers carrying the credentials, they are
+/// not stored with the prefetch request.
+const SENSITIVE_PREFETCH_REQUEST_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-amz-security-token",
+];
+
+/// encode_prefetch_request serializes the download request of the prefetch to be stored in the
+/// metadata. The request headers and the object storage and hdfs credentials are stripped,
+/// because the metadata is stored in plaintext, so the resumed prefetch of the private source
+/// needs the credentials provided by the dfdaemon.
+fn encode_prefetch_request(request: &Download) -> ClientResult<Vec<u8>> {
+    let mut request = request.clone();
+    request.request_header.retain(|key, _| {
+        !SENSITIVE_PREFETCH_REQUEST_HEADERS
+            .iter()
+            .any(|header| key.eq_ignore_ascii_case(header))
+    });
+
+    if let Some(object_storage) = request.object_storage.as_mut() {
+        object_storage.access_key_id = None;
+        object_storage.access_key_secret = None;
+        object_storage.session_token = None;
     }
+
+    if let Some(hdfs) = request.hdfs.as_mut() {
+        hdfs.delegation_token = None;
+    }
+
+    Ok(serde_json::to_vec(&request).or_err(ErrorType::SerializeError)?)
+}
+
+/// decode_prefetch_request deserializes the download request of the prefetch stored in the
+/// metadata.
+fn decode_prefetch_request(request: &[u8]) -> serde_json::Result<Download> {
+    serde_json::from_slice::<Download>(request)
 }
 
 #[cfg(test)]
@@ -1967,6 +2625,76 @@ mod tests {
     use std::sync::Arc;
     use tempfile::tempdir;
 
+    #[tokio::test]
+    async fn should_resume_prefetch_without_credentials_after_restart() {
+        let temp_dir = tempdir().unwrap();
+        let log_dir = temp_dir.path().join("log");
+        std::fs::create_dir_all(&log_dir).unwrap();
+
+        // Keep the metadata of the tasks when dfdaemon restarts.
+        let mut config = Config::default();
+        config.storage.keep = true;
+        let config = Arc::new(config);
+        let task_id = "d3c4e940ad06c47fc36ac67801e6f8e36cb400e2391708620bc7e865b102062c";
+
+        let download = Download {
+            url: "https://example.com/file".to_string(),
+            request_header: HashMap::from([
+                ("Authorization".to_string(), "Bearer foo".to_string()),
+                ("Cookie".to_string(), "session=bar".to_string()),
+                ("X-Custom".to_string(), "baz".to_string()),
+            ]),
+            object_storage: Some(ObjectStorage {
+                access_key_id: Some("id".to_string()),
+                access_key_secret: Some("secret".to_string()),
+                region: Some("region".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // Start the prefetch and stop dfdaemon before the task is finished.
+        let storage = Storage::new(config.clone(), temp_dir.path(), log_dir.clone())
+            .await
+            .unwrap();
+        storage
+            .download_task_started(task_id, 1024, 4096, None, None)
+            .await
+            .unwrap();
+        storage
+            .prefetch_task_started(task_id, encode_prefetch_request(&download).unwrap())
+            .await
+            .unwrap();
+        drop(storage);
+
+        // Restart dfdaemon, the prefetch is resumed by the stored request.
+        let storage = Storage::new(config, temp_dir.path(), log_dir)
+            .await
+            .unwrap();
+        let tasks: Vec<metadata::Task> = storage
+            .get_tasks()
+            .unwrap()
+            .into_iter()
+            .filter(|task| task.is_resumable())
+            .collect();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(storage.resume_task(&tasks[0]).await.unwrap(), 0);
+
+        let request = tasks[0].prefetch_request.as_ref().unwrap();
+        let resumed = decode_prefetch_request(request).unwrap();
+        assert_eq!(resumed.url, download.url);
+        assert_eq!(
+            resumed.request_header,
+            HashMap::from([("X-Custom".to_string(), "baz".to_string())])
+        );
+
+        let object_storage = resumed.object_storage.unwrap();
+        assert!(object_storage.access_key_id.is_none());
+        assert!(object_storage.access_key_secret.is_none());
+        assert_eq!(object_storage.region, Some("region".to_string()));
+        assert!(!String::from_utf8_lossy(request).contains("\"secret\""));
+    }
+
     // test_delete_task_not_found tests the Task.delete method when the task does not exist.
     #[tokio::test]
     async fn test_delete_task_not_found() {
@@ -1995,7 +2723,7 @@ mod tests {
         // Create a task and save it to storage.
         let task_id = "test-task-id";
         storage
-            .download_task_started(task_id, 1024, 4096, None)
+            .download_task_started(task_id, 1024, 4096, None, None)
             .await
             .unwrap();
 
@@ -0,0 +1,327 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use dragonfly_client_core::{Error, Result};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+pub mod server;
+
+/// PROTOCOL_VERSION is the version of the tcp piece transfer protocol.
+pub const PROTOCOL_VERSION: u8 = 2;
+
+/// PieceType is the type of the piece requested by the tcp piece transfer protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PieceType {
+    /// Standard is the piece of the standard task stored in the disk.
+    Standard = 0,
+
+    /// PersistentCache is the piece of the persistent cache task stored in the disk.
+    PersistentCache = 1,
+
+    /// Cache is the piece of the cache task stored in the memory cache.
+    Cache = 2,
+}
+
+/// PieceType implements TryFrom<u8>.
+impl TryFrom<u8> for PieceType {
+    type Error = Error;
+
+    /// try_from converts the u8 to the piece type.
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(PieceType::Standard),
+            1 => Ok(PieceType::PersistentCache),
+            2 => Ok(PieceType::Cache),
+            _ => Err(Error::InvalidParameter),
+        }
+    }
+}
+
+/// Status is the status of the response in the tcp piece transfer protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Status {
+    /// Ok means the piece content follows the response header.
+    Ok = 0,
+
+    /// NotFound means the task or the piece is not found in the parent.
+    NotFound = 1,
+
+    /// Internal means the parent failed to upload the piece.
+    Internal = 2,
+
+    /// Unauthenticated means the request is not authorized by the upload auth of the parent.
+    Unauthenticated = 3,
+}
+
+/// Status implements TryFrom<u8>.
+impl TryFrom<u8> for Status {
+    type Error = Error;
+
+    /// try_from converts the u8 to the status.
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Status::Ok),
+            1 => Ok(Status::NotFound),
+            2 => Ok(Status::Internal),
+            3 => Ok(Status::Unauthenticated),
+            _ => Err(Error::InvalidParameter),
+        }
+    }
+}
+
+/// DownloadPieceRequest is the request to download a piece from the parent.
+///
+/// Wire format:
+/// +---------+------------+--------------+-------------+---------+-------------+---------+-----------+-------+
+/// | version | piece type | piece number | host id len | host id | task id len | task id | token len | token |
+/// | u8      | u8         | u32          | u16         | bytes   | u16         | bytes   | u16       | bytes |
+/// +---------+------------+--------------+-------------+---------+-------------+---------+-----------+-------+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadPieceRequest {
+    /// piece_type is the type of the piece.
+    pub piece_type: PieceType,
+
+    /// piece_number is the number of the piece.
+    pub piece_number: u32,
+
+    /// host_id is the id of the host which downloads the piece.
+    pub host_id: String,
+
+    /// task_id is the id of the task.
+    pub task_id: String,
+
+    /// token is the token of the upload auth of the parent, it is empty if the token is not
+    /// configured by `upload.client.token`.
+    pub token: String,
+}
+
+/// DownloadPieceRequest implements the encoding and decoding of the request.
+impl DownloadPieceRequest {
+    /// write_to writes the request to the writer.
+    pub async fn write_to<W: AsyncWrite + Unpin + ?Sized>(&self, writer: &mut W) -> Result<()> {
+        let mut buf =
+            Vec::with_capacity(12 + self.host_id.len() + self.task_id.len() + self.token.len());
+        buf.push(PROTOCOL_VERSION);
+        buf.push(self.piece_type as u8);
+        buf.extend_from_slice(&self.piece_number.to_be_bytes());
+        put_string(&mut buf, &self.host_id)?;
+        put_string(&mut buf, &self.task_id)?;
+        put_string(&mut buf, &self.token)?;
+        writer.write_all(&buf).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+
+    /// read_from reads the request from the reader, it returns None if the connection is
+    /// closed before the request is read.
+    pub async fn read_from<R: AsyncRead + Unpin + ?Sized>(reader: &mut R) -> Result<Option<Self>> {
+        let version = match reader.read_u8().await {
+            Ok(version) => version,
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        if version != PROTOCOL_VERSION {
+            return Err(Error::Unsupported(format!(
+                "tcp protocol version {}",
+                version
+            )));
+        }
+
+        let piece_type = PieceType::try_from(reader.read_u8().await?)?;
+        let piece_number = reader.read_u32().await?;
+        let host_id = get_string(reader).await?;
+        let task_id = get_string(reader).await?;
+        let token = get_string(reader).await?;
+        Ok(Some(Self {
+            piece_type,
+            piece_number,
+            host_id,
+            task_id,
+            token,
+        }))
+    }
+}
+
+/// DownloadPieceResponse is the response header of downloading a piece from the parent,
+/// the piece content with the length follows the header if the status is ok.
+///
+/// Wire format:
+/// +--------+--------+--------+------------+--------+
+/// | status | offset | length | digest len | digest |
+/// | u8     | u64    | u64    | u16        | bytes  |
+/// +--------+--------+--------+------------+--------+
+///
+/// If the status is not ok, the status is followed by the error message:
+/// +--------+-------------+---------+
+/// | status | message len | message |
+/// | u8     | u16         | bytes   |
+/// +--------+-------------+---------+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadPieceResponse {
+    /// Ok is the header of the piece content.
+    Ok {
+        /// offset is the offset of the piece in the task.
+        offset: u64,
+
+        /// length is the length of the piece content.
+        length: u64,
+
+        /// digest is the digest of the piece content.
+        digest: String,
+    },
+
+    /// Error is the error of downloading the piece.
+    Error {
+        /// status is the status of the error.
+        status: Status,
+
+        /// message is the message of the error.
+        message: String,
+    },
+}
+
+/// DownloadPieceResponse implements the encoding and decoding of the response header.
+impl DownloadPieceResponse {
+    /// write_to writes the response header to the writer.
+    pub async fn write_to<W: AsyncWrite + Unpin + ?Sized>(&self, writer: &mut W) -> Result<()> {
+        let mut buf = Vec::new();
+        match self {
+            DownloadPieceResponse::Ok {
+                offset,
+                length,
+                digest,
+            } => {
+                buf.push(Status::Ok as u8);
+                buf.extend_from_slice(&offset.to_be_bytes());
+                buf.extend_from_slice(&length.to_be_bytes());
+                put_string(&mut buf, digest)?;
+            }
+            DownloadPieceResponse::Error { status, message } => {
+                buf.push(*status as u8);
+                put_string(&mut buf, message)?;
+            }
+        }
+
+        writer.write_all(&buf).await?;
+        Ok(())
+    }
+
+    /// read_from reads the response header from the reader.
+    pub async fn read_from<R: AsyncRead + Unpin + ?Sized>(reader: &mut R) -> Result<Self> {
+        match Status::try_from(reader.read_u8().await?)? {
+            Status::Ok => {
+                let offset = reader.read_u64().await?;
+                let length = reader.read_u64().await?;
+                let digest = get_string(reader).await?;
+                Ok(DownloadPieceResponse::Ok {
+                    offset,
+                    length,
+                    digest,
+                })
+            }
+            status => {
+                let message = get_string(reader).await?;
+                Ok(DownloadPieceResponse::Error { status, message })
+            }
+        }
+    }
+}
+
+/// put_string puts the string with the u16 length prefix into the buffer.
+fn put_string(buf: &mut Vec<u8>, value: &str) -> Result<()> {
+    let length = u16::try_from(value.len()).map_err(|_| Error::InvalidParameter)?;
+    buf.extend_from_slice(&length.to_be_bytes());
+    buf.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+/// get_string gets the string with the u16 length prefix from the reader.
+async fn get_string<R: AsyncRead + Unpin + ?Sized>(reader: &mut R) -> Result<String> {
+    let length = reader.read_u16().await?;
+    let mut buf = vec![0; length as usize];
+    reader.read_exact(&mut buf).await?;
+    String::from_utf8(buf).map_err(|_| Error::InvalidParameter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[tokio::test]
+    async fn should_encode_and_decode_download_piece_request() {
+        let request = DownloadPieceRequest {
+            piece_type: PieceType::Cache,
+            piece_number: 42,
+            host_id: "host".to_string(),
+            task_id: "task".to_string(),
+            token: "token".to_string(),
+        };
+
+        let mut buf = Vec::new();
+        request.write_to(&mut buf).await.unwrap();
+
+        let mut reader = Cursor::new(buf);
+        let decoded = DownloadPieceRequest::read_from(&mut reader)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(decoded, request);
+
+        // The connection is closed after the request is read.
+        assert!(DownloadPieceRequest::read_from(&mut reader)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn should_reject_unsupported_version() {
+        let mut reader = Cursor::new(vec![PROTOCOL_VERSION + 1, 0, 0, 0, 0, 0]);
+        assert!(matches!(
+            DownloadPieceRequest::read_from(&mut reader).await,
+            Err(Error::Unsupported(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_encode_and_decode_download_piece_response() {
+        let responses = vec![
+            DownloadPieceResponse::Ok {
+                offset: 4194304,
+                length: 1024,
+                digest: "crc32:12345".to_string(),
+            },
+            DownloadPieceResponse::Error {
+                status: Status::NotFound,
+                message: "piece not found".to_string(),
+            },
+        ];
+
+        for response in responses {
+            let mut buf = Vec::new();
+            response.write_to(&mut buf).await.unwrap();
+
+            let decoded = DownloadPieceResponse::read_from(&mut Cursor::new(buf))
+                .await
+                .unwrap();
+            assert_eq!(decoded, response);
+        }
+    }
+}
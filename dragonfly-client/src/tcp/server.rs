@@ -0,0 +1,314 @@
+/*
+ *     Copyright 2025 The Dragonfly Authors
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use super::{DownloadPieceRequest, DownloadPieceResponse, PieceType, Status};
+use crate::grpc::auth::is_read_authorized;
+use crate::metrics::{
+    collect_upload_piece_failure_metrics, collect_upload_piece_finished_metrics,
+    collect_upload_piece_started_metrics,
+};
+use crate::resource::task;
+use crate::shutdown;
+use dragonfly_client_config::dfdaemon::UploadAuth;
+use dragonfly_client_core::{Error as ClientError, Result as ClientResult};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, instrument, warn, Instrument};
+
+/// DEFAULT_WRITE_BUFFER_SIZE is the default buffer size for writing the response header and the
+/// small piece content to the connection.
+const DEFAULT_WRITE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// TCPServer is the tcp storage server, it uploads the piece content to the other peers without
+/// protobuf framing.
+pub struct TCPServer {
+    /// addr is the address of the tcp server.
+    addr: SocketAddr,
+
+    /// auth is the authorization configuration of the upload server, it is applied to the
+    /// requests of the tcp server too.
+    auth: Arc<UploadAuth>,
+
+    /// task is the task manager.
+    task: Arc<task::Task>,
+
+    /// shutdown is used to shutdown the tcp server.
+    shutdown: shutdown::Shutdown,
+
+    /// _shutdown_complete is used to notify the tcp server is shutdown.
+    _shutdown_complete: mpsc::UnboundedSender<()>,
+}
+
+/// TCPServer implements the tcp storage server.
+impl TCPServer {
+    /// new creates a new TCPServer.
+    pub fn new(
+        addr: SocketAddr,
+        auth: UploadAuth,
+        task: Arc<task::Task>,
+        shutdown: shutdown::Shutdown,
+        shutdown_complete_tx: mpsc::UnboundedSender<()>,
+    ) -> Self {
+        Self {
+            addr,
+            auth: Arc::new(auth),
+            task,
+            shutdown,
+            _shutdown_complete: shutdown_complete_tx,
+        }
+    }
+
+    /// run starts the tcp server.
+    pub async fn run(&self) -> ClientResult<()> {
+        // Clone the shutdown channel.
+        let mut shutdown = self.shutdown.clone();
+
+        let listener = TcpListener::bind(self.addr).await?;
+        info!("tcp storage server listening on {}", self.addr);
+
+        loop {
+            tokio::select! {
+                result = listener.accept() => {
+                    let (stream, remote_addr) = match result {
+                        Ok(accepted) => accepted,
+                        Err(err) => {
+                            error!("accept tcp connection failed: {}", err);
+                            continue;
+                        }
+                    };
+
+                    debug!("accepted tcp connection from {}", remote_addr);
+                    let task = self.task.clone();
+                    let auth = self.auth.clone();
+                    let mut shutdown = self.shutdown.clone();
+                    tokio::spawn(
+                        async move {
+                            tokio::select! {
+                                result = Self::handle_connection(task, auth, remote_addr, stream) => {
+                                    if let Err(err) = result {
+                                        error!("handle tcp connection from {} failed: {}", remote_addr, err);
+                                    }
+                                }
+                                _ = shutdown.recv() => {
+                                    debug!("tcp connection from {} closed by shutdown", remote_addr);
+                                }
+                            }
+                        }
+                        .in_current_span(),
+                    );
+                }
+                _ = shutdown.recv() => {
+                    // TCP server shutting down with signals.
+                    info!("tcp storage server shutting down");
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// handle_connection handles the requests of the connection one by one, the connection is
+    /// reused by the downloader until it is closed. Every request is authorized by the remote
+    /// address and the token like the upload server, and the connection is closed if the
+    /// request is not authorized.
+    async fn handle_connection(
+        task: Arc<task::Task>,
+        auth: Arc<UploadAuth>,
+        remote_addr: SocketAddr,
+        stream: TcpStream,
+    ) -> ClientResult<()> {
+        stream.set_nodelay(true)?;
+        let (reader, writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        let mut writer = BufWriter::with_capacity(DEFAULT_WRITE_BUFFER_SIZE, writer);
+
+        while let Some(request) = DownloadPieceRequest::read_from(&mut reader).await? {
+            let token = Some(request.token.as_str()).filter(|token| !token.is_empty());
+            if !is_read_authorized(&auth, Some(remote_addr.ip()), token) {
+                warn!("unauthenticated tcp request from {}", remote_addr);
+                DownloadPieceResponse::Error {
+                    status: Status::Unauthenticated,
+                    message: "invalid token".to_string(),
+                }
+                .write_to(&mut writer)
+                .await?;
+                writer.flush().await?;
+                return Err(ClientError::Unauthorized);
+            }
+
+            Self::handle_download_piece(&task, request, &mut writer).await?;
+            writer.flush().await?;
+        }
+
+        Ok(())
+    }
+
+    /// handle_download_piece writes the piece content to the connection. If the piece can not be
+    /// uploaded, the error response is written and the connection can be reused, otherwise if
+    /// writing the piece content fails, the error is returned and the connection is closed.
+    #[instrument(skip_all, fields(remote_host_id, task_id, piece_id))]
+    async fn handle_download_piece<W: AsyncWriteExt + Unpin>(
+        task: &task::Task,
+        request: DownloadPieceRequest,
+        writer: &mut W,
+    ) -> ClientResult<()> {
+        let DownloadPieceRequest {
+            piece_type,
+            piece_number,
+            host_id,
+            task_id,
+            ..
+        } = request;
+
+        let piece_id = match piece_type {
+            PieceType::Standard => task.piece.id(task_id.as_str(), piece_number),
+            PieceType::PersistentCache => task
+                .piece
+                .persistent_cache_id(task_id.as_str(), piece_number),
+            PieceType::Cache => task.piece.cache_id(task_id.as_str(), piece_number),
+        };
+
+        tracing::Span::current().record("remote_host_id", host_id.as_str());
+        tracing::Span::current().record("task_id", task_id.as_str());
+        tracing::Span::current().record("piece_id", piece_id.as_str());
+        debug!("download piece content in tcp storage server");
+
+        // Collect upload piece started metrics.
+        collect_upload_piece_started_metrics();
+
+        let result = match piece_type {
+            PieceType::Standard | PieceType::PersistentCache => {
+                let piece = match piece_type {
+                    PieceType::Standard => task.piece.get(piece_id.as_str()),
+                    _ => task.piece.get_persistent_cache(piece_id.as_str()),
+                };
+
+                match piece {
+                    Ok(Some(piece)) => {
+                        let reader = match piece_type {
+                            PieceType::Standard => task
+                                .piece
+                                .upload_from_local_into_async_read(
+                                    piece_id.as_str(),
+                                    task_id.as_str(),
+                                    piece.length,
+                                    None,
+                                    false,
+                                )
+                                .await
+                                .map(tokio_util::either::Either::Left),
+                            _ => task
+                                .piece
+                                .upload_persistent_cache_from_local_into_async_read(
+                                    piece_id.as_str(),
+                                    task_id.as_str(),
+                                    piece.length,
+                                    None,
+                                )
+                                .await
+                                .map(tokio_util::either::Either::Right),
+                        };
+
+                        match reader {
+                            Ok(reader) => {
+                                DownloadPieceResponse::Ok {
+                                    offset: piece.offset,
+                                    length: piece.length,
+                                    digest: piece.digest.clone(),
+                                }
+                                .write_to(writer)
+                                .await?;
+
+                                // Copy the piece content to the connection through the write
+                                // buffer, the content is not buffered in the memory as a whole.
+                                let copied =
+                                    tokio::io::copy(&mut reader.take(piece.length), writer)
+                                        .await
+                                        .inspect_err(|_| collect_upload_piece_failure_metrics())?;
+                                if copied != piece.length {
+                                    collect_upload_piece_failure_metrics();
+                                    return Err(ClientError::ContentLengthMismatch(
+                                        piece.length,
+                                        copied,
+                                    ));
+                                }
+
+                                Ok(())
+                            }
+                            Err(err) => Err(err),
+                        }
+                    }
+                    Ok(None) => Err(ClientError::PieceNotFound(piece_id.clone())),
+                    Err(err) => Err(err),
+                }
+            }
+            PieceType::Cache => {
+                match task
+                    .piece
+                    .upload_cache_from_local(piece_id.as_str(), task_id.as_str(), piece_number)
+                    .await
+                {
+                    Ok((piece, content)) => {
+                        DownloadPieceResponse::Ok {
+                            offset: piece.offset,
+                            length: piece.length,
+                            digest: piece.digest.clone(),
+                        }
+                        .write_to(writer)
+                        .await?;
+
+                        writer
+                            .write_all(&content)
+                            .await
+                            .inspect_err(|_| collect_upload_piece_failure_metrics())?;
+                        Ok(())
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+        };
+
+        match result {
+            Ok(()) => {
+                // Collect upload piece finished metrics.
+                collect_upload_piece_finished_metrics();
+                debug!("finished upload piece content");
+                Ok(())
+            }
+            Err(err) => {
+                // Collect upload piece failure metrics.
+                collect_upload_piece_failure_metrics();
+                error!("upload piece content failed: {}", err);
+
+                let status = match err {
+                    ClientError::TaskNotFound(_) | ClientError::PieceNotFound(_) => {
+                        Status::NotFound
+                    }
+                    _ => Status::Internal,
+                };
+
+                DownloadPieceResponse::Error {
+                    status,
+                    message: err.to_string(),
+                }
+                .write_to(writer)
+                .await
+            }
+        }
+    }
+}